@@ -0,0 +1,114 @@
+//! Evaluator hot paths: parsing and evaluating expressions, calling
+//! functions, and operating on long lists
+//!
+//! ```text
+//! cargo bench --bench evaluator
+//! ```
+
+use criterion::{BatchSize, Criterion, black_box, criterion_group, criterion_main};
+use wabznasm::arithmetic::{self, OverflowMode};
+use wabznasm::parser::parse_expression;
+use wabznasm::{Session, Value, kernels};
+
+/// Items in the lists the list benchmarks operate on
+const LEN: usize = 100_000;
+
+/// A session with `setup` evaluated
+fn session(setup: &[&str]) -> Session {
+    let mut session = Session::new();
+    session.eval_all(setup.iter().copied()).unwrap();
+    session
+}
+
+fn expressions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("expressions");
+    let source = "a: 2; b: 3; (a+b)*4-a%b";
+    group.bench_function("parse", |b| {
+        b.iter(|| parse_expression(black_box(source)).unwrap())
+    });
+    let mut s = session(&[]);
+    group.bench_function("eval", |b| b.iter(|| s.eval(black_box(source)).unwrap()));
+    let script = ["x: 10", "y: x*x", "z: y-x", "$[z>50;z;x]"];
+    group.bench_function("eval_all", |b| {
+        b.iter(|| s.eval_all(black_box(script)).unwrap())
+    });
+    group.finish();
+}
+
+fn calls(c: &mut Criterion) {
+    let mut group = c.benchmark_group("calls");
+    let mut s = session(&[
+        "inc: {[x] x+1}",
+        "add: {[x;y] x+y}",
+        "fib: {[n] $[n<2;n;fib[n-1]+fib[n-2]]}",
+        "add2: add[2]",
+    ]);
+    for (name, source) in [
+        ("lambda", "inc[1]"),
+        ("two_args", "add[1;2]"),
+        ("projection", "add2[1]"),
+        ("builtin", "count 1 2 3"),
+        ("recursive", "fib[15]"),
+    ] {
+        group.bench_function(name, |b| b.iter(|| s.eval(black_box(source)).unwrap()));
+    }
+    group.finish();
+}
+
+fn lists(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lists");
+    let mut s = session(&[
+        "seed 7",
+        &format!("x: {}?1000", LEN),
+        &format!("y: {}?1000", LEN),
+        "f: x*0.5",
+    ]);
+    for (name, source) in [
+        ("add", "count x+y"),
+        ("scale_float", "count f*2.5"),
+        ("sum", "sum x"),
+        ("avg", "avg f"),
+        ("max", "max x"),
+        ("compare", "count x>500"),
+        ("sort", "count asc x"),
+    ] {
+        group.bench_function(name, |b| b.iter(|| s.eval(black_box(source)).unwrap()));
+    }
+    group.finish();
+}
+
+/// The vector kernels against the item-by-item path they stand in for
+fn kernels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kernels");
+    let tree = parse_expression("1").unwrap();
+    let node = tree.root_node();
+    let left = Value::List((0..LEN as i64).map(Value::Integer).collect());
+    let right = Value::List((0..LEN as i64).rev().map(Value::Integer).collect());
+    group.bench_function("kernel", |b| {
+        b.iter(|| kernels::binary("+", black_box(&left), black_box(&right)).unwrap())
+    });
+    group.bench_function("general", |b| {
+        b.iter(|| {
+            arithmetic::general(
+                "+",
+                black_box(&left),
+                &right,
+                OverflowMode::Error,
+                node,
+                node,
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("kernel_parallel", |b| {
+        b.iter(|| kernels::binary_parallel("+", black_box(&left), black_box(&right)).unwrap())
+    });
+    group.bench_function("plain", |b| {
+        let vector = kernels::binary("+", &left, &right).unwrap();
+        b.iter_batched(|| vector.clone(), Value::plain, BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, expressions, calls, lists, kernels);
+criterion_main!(benches);
@@ -0,0 +1,91 @@
+//! Storage hot paths: inserting rows into a splayed table and scanning
+//! its columns back
+//!
+//! ```text
+//! cargo bench --bench storage
+//! ```
+
+use criterion::{BatchSize, Criterion, black_box, criterion_group, criterion_main};
+use storage::schema::SchemaBuilder;
+use storage::table::Row;
+use storage::{QStoreConfig, ScalarValue, Table};
+use tempfile::TempDir;
+
+/// Rows inserted or scanned per iteration
+const ROWS: usize = 1_000;
+
+/// The `i`th row of a market data table
+fn row(i: usize) -> Row {
+    let symbols = ["AAPL", "GOOGL", "MSFT", "TSLA"];
+    let sides = ["BUY", "SELL"];
+    Row::from([
+        (
+            "time".to_string(),
+            ScalarValue::Timestamp(1_640_995_200_000_000_000 + i as i64 * 1_000_000_000),
+        ),
+        (
+            "symbol".to_string(),
+            ScalarValue::Utf8(symbols[i % symbols.len()].to_string()),
+        ),
+        (
+            "price".to_string(),
+            ScalarValue::Float64(100.0 + i as f64 * 0.1),
+        ),
+        (
+            "size".to_string(),
+            ScalarValue::Int64(100 + (i % 10) as i64),
+        ),
+        (
+            "side".to_string(),
+            ScalarValue::Utf8(sides[i % sides.len()].to_string()),
+        ),
+    ])
+}
+
+/// An empty market data table in a fresh directory, which is removed when
+/// dropped
+fn table() -> (TempDir, Table) {
+    let dir = TempDir::new().unwrap();
+    let config = QStoreConfig::new(dir.path(), "trades".to_string());
+    let table = Table::new(SchemaBuilder::market_data(), config).unwrap();
+    (dir, table)
+}
+
+fn insert(c: &mut Criterion) {
+    let rows: Vec<Row> = (0..ROWS).map(row).collect();
+    c.bench_function("storage/insert", |b| {
+        b.iter_batched(
+            table,
+            |(dir, mut table)| {
+                for row in &rows {
+                    table.insert(row.clone()).unwrap();
+                }
+                (dir, table)
+            },
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+fn scan(c: &mut Criterion) {
+    let (_dir, mut table) = table();
+    for i in 0..ROWS {
+        table.insert(row(i)).unwrap();
+    }
+    let mut group = c.benchmark_group("storage/scan");
+    group.bench_function("column", |b| {
+        b.iter(|| table.get_column(black_box("price")).unwrap())
+    });
+    group.bench_function("rows", |b| b.iter(|| table.iter().unwrap().count()));
+    group.bench_function("filter", |b| {
+        b.iter(|| {
+            table
+                .filter(|row| row.get("size") == Some(&ScalarValue::Int64(105)))
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, insert, scan);
+criterion_main!(benches);
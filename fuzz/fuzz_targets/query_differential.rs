@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use storage::differential;
+
+fuzz_target!(|data: &[u8]| {
+    // Use the input as a seed for generated rows and queries
+    let mut seed = [0u8; 8];
+    let len = data.len().min(8);
+    seed[..len].copy_from_slice(&data[..len]);
+    let dir = tempfile::tempdir().unwrap();
+    if let Err(divergence) = differential::run(dir.path(), u64::from_le_bytes(seed), 50, 20).unwrap() {
+        panic!("{}", divergence);
+    }
+});
@@ -1,22 +1,40 @@
 // See https://tree-sitter.github.io/tree-sitter/creating-parsers
 // Precedence levels (higher number binds tighter)
 const PREC = {
+  APPLY: -1, // f x (argument extends as far right as possible)
   ASSIGN: 0, // : assignment
-  ADD: 1, // + -
-  MUL: 2, // * / %
-  EXP: 3, // ^ (right-assoc)
-  UNARY: 4, // prefix -
-  FACT: 5, // postfix !
-  CALL: 6, // function calls
+  DYADIC: 1, // # _ $ list verbs and cast (right-assoc)
+  COMPARE: 2, // = <> < > <= >=
+  ADD: 3, // + -
+  MUL: 4, // * / %
+  EXP: 5, // ^ (right-assoc)
+  UNARY: 6, // prefix -
+  FACT: 7, // postfix !
+  CALL: 8, // function calls
 };
 
+// One or more of rule, separated by commas
+const commaSep1 = (rule) => seq(rule, repeat(seq(field("separator", ","), rule)));
+
+// Statements separated by semicolons, evaluated in turn: a: 1; a+1
+const statements = ($) => seq(
+  field("statement", $.statement),
+  repeat(seq(field("separator", ";"), field("statement", $.statement)))
+);
+
+// The where clause of a query: where c1, c2, ...
+const whereClause = ($) => seq(
+  field("where", "where"),
+  commaSep1(field("filter", $.dyadic))
+);
+
 module.exports = grammar({
   name: "calc",
   extras: ($) => [/[\s\t\n\r]+/, $.comment],
 
   rules: {
-    // The top-level entry point - simplified to avoid conflicts
-    source_file: ($) => $.statement,
+    // The top-level entry point: one or more statements
+    source_file: ($) => statements($),
 
     // Statement can be assignment or expression
     statement: ($) => choice(
@@ -24,21 +42,27 @@ module.exports = grammar({
       $.expression
     ),
 
-    // Function assignment: name: {body} or name: expression
+    // Function assignment: name: {body} or name: expression; name:: value
+    // sets a global from within a function
     assignment: ($) => prec.right(PREC.ASSIGN, seq(
       field("name", $.identifier),
-      field("operator", ":"),
-      field("value", choice($.function_body, $.expression))
+      field("operator", choice(":", "::")),
+      field("value", $.expression)
     )),
 
-    // Function body: {expression} or {[params] expression}
+    // Function body: {expression} or {[params] expression}; lambdas are
+    // ordinary values, so they may be nested and returned from functions
     function_body: ($) => seq(
       field("left_brace", "{"),
       optional(field("params", $.parameter_list)),
-      field("body", $.expression),
+      field("body", $.block),
       field("right_brace", "}")
     ),
 
+    // Statements of a function body; assignments bind locals of the call
+    // and the last statement gives the result: {[x] t: x*2; t+1}
+    block: ($) => statements($),
+
     // Parameter list: [x;y;z]
     parameter_list: ($) => seq(
       field("left_bracket", "["),
@@ -49,8 +73,81 @@ module.exports = grammar({
       field("right_bracket", "]")
     ),
 
-    // Expressions with all operators, layered by precedence
-    expression: ($) => $.additive,
+    // Expressions with all operators, layered by precedence, or a query
+    expression: ($) => choice($.select, $.update, $.delete, $.dyadic),
+
+    // q-sql select: select [columns] [by groups] from table [where conditions]
+    // Columns and conditions are comma separated; each condition filters the
+    // rows the ones before it kept, and groups key the result by their values
+    select: ($) => prec.right(seq(
+      field("keyword", "select"),
+      optional(commaSep1(field("column", $.select_column))),
+      optional(seq(
+        field("by", "by"),
+        commaSep1(field("group", $.select_column))
+      )),
+      field("from", "from"),
+      field("table", $.dyadic),
+      optional(whereClause($))
+    )),
+
+    // q-sql update: update columns from table [where conditions] sets or
+    // adds columns in the rows the conditions keep
+    update: ($) => prec.right(seq(
+      field("keyword", "update"),
+      commaSep1(field("column", $.select_column)),
+      field("from", "from"),
+      field("table", $.dyadic),
+      optional(whereClause($))
+    )),
+
+    // q-sql delete: delete from table where conditions removes rows, and
+    // delete columns from table removes columns
+    delete: ($) => prec.right(seq(
+      field("keyword", "delete"),
+      optional(commaSep1(field("column", $.identifier))),
+      field("from", "from"),
+      field("table", $.dyadic),
+      optional(whereClause($))
+    )),
+
+    // A selected column, optionally renamed: px or value:px*qty
+    select_column: ($) => seq(
+      optional(seq(field("name", $.identifier), field("operator", ":"))),
+      field("value", $.dyadic)
+    ),
+
+    // List verbs bind loosest and associate to the right: 2#3_x is 2#(3_x)
+    dyadic: ($) =>
+      choice(
+        // n#x (take), n_x (drop), `type$x (cast), n?m (roll and deal) or
+        // ys?x (find), x in ys, x within (a;b) and n xbar x (bucket)
+        prec.right(
+          PREC.DYADIC,
+          seq(
+            field("left", $.comparison),
+            field("operator", choice("#", "_", "$", "?", "in", "within", "xbar")),
+            field("right", $.dyadic)
+          )
+        ),
+        // fallback
+        $.comparison
+      ),
+
+    // Comparisons (left-assoc), applied item by item to lists: xs>5
+    comparison: ($) =>
+      choice(
+        prec.left(
+          PREC.COMPARE,
+          seq(
+            field("left", $.comparison),
+            field("operator", choice("=", "<>", "<", ">", "<=", ">=")),
+            field("right", $.additive)
+          )
+        ),
+        // fallback
+        $.additive
+      ),
 
     // Lowest precedence: addition and subtraction (left-assoc)
     additive: ($) =>
@@ -142,6 +239,11 @@ module.exports = grammar({
       choice(
         // x!
         prec.left(PREC.FACT, seq(field("operand", $.postfix), field("operator", "!"))),
+        // keys!values (dictionary); an operand after ! distinguishes it from factorial
+        prec.left(
+          PREC.FACT,
+          seq(field("keys", $.postfix), field("operator", "!"), field("values", $.primary))
+        ),
         // fallback
         $.primary
       ),
@@ -151,10 +253,30 @@ module.exports = grammar({
       choice(
         // function call with arguments: f[x;y]
         $.function_call,
+        // prefix application: f x
+        $.application,
         // identifier/variable reference
         $.identifier,
-        // literal
+        // lambda: {[x] x+1}
+        $.function_body,
+        // conditional: $[c;t;f]
+        $.conditional,
+        // loops: do[n;body] and while[c;body]
+        $.loop,
+        // protected evaluation: @[f;x;handler]
+        $.trap,
+        // raise an error: '`msg
+        $.signal,
+        // literals
+        $.boolean,
+        $.timestamp,
+        $.date,
+        $.time,
         $.number,
+        $.symbol,
+        $.string,
+        $.vector,
+        $.list,
         // (expression)
         seq(
           field("left_paren", "("),
@@ -163,25 +285,104 @@ module.exports = grammar({
         )
       ),
 
-    // Function call: name[args] or name[]
-    function_call: ($) => prec(PREC.CALL, seq(
-      field("function", $.identifier),
+    // Function call: name[args] or name[]; what a call gives, such as a
+    // projection, or a lambda may be called in turn: add[2][3], {[x] x}[1]
+    function_call: ($) => prec.left(PREC.CALL, seq(
+      field("function", choice($.identifier, $.function_call, $.function_body)),
       field("left_bracket", "["),
       optional(field("args", $.argument_list)),
       field("right_bracket", "]")
     )),
 
-    // Argument list: expr;expr;expr
-    argument_list: ($) => seq(
+    // Conditional: $[c;t;f], or $[c1;t1;c2;t2;f] to test conditions in turn;
+    // only the branch taken is evaluated
+    conditional: ($) => seq(
+      field("left_bracket", "$["),
+      field("arg", $.expression),
+      repeat1(seq(field("separator", ";"), field("arg", $.expression))),
+      field("right_bracket", "]")
+    ),
+
+    // Loops: do[n;e1;e2] evaluates its body n times, while[c;e1;e2] as long
+    // as c holds; both re-evaluate their operands on each pass, and
+    // assignments in the body bind in the enclosing scope
+    loop: ($) => seq(
+      field("keyword", choice("do[", "while[")),
+      field("condition", $.expression),
+      repeat1(seq(field("separator", ";"), field("body", $.statement))),
+      field("right_bracket", "]")
+    ),
+
+    // Protected evaluation: @[f;x;handler] applies f to x, and if that fails
+    // gives the handler applied to the error message, or the handler itself
+    // when it is not a function
+    trap: ($) => seq(
+      field("left_bracket", "@["),
+      field("function", $.expression),
+      field("separator", ";"),
+      field("argument", $.expression),
+      field("separator", ";"),
+      field("handler", $.expression),
+      field("right_bracket", "]")
+    ),
+
+    // Signal: 'x raises x as an error, which @[f;x;handler] can trap
+    signal: ($) => seq(field("operator", "'"), field("message", $.primary)),
+
+    // Prefix application: asc 3 1 2 (the argument takes the rest of the expression)
+    application: ($) => prec.right(PREC.APPLY, seq(
+      field("function", $.identifier),
+      field("argument", $.expression)
+    )),
+
+    // Integer vector literal: 1 2 3
+    vector: ($) => seq($.number, repeat1($.number)),
+
+    // General list: () or (a;b;c)
+    list: ($) => seq(
+      field("left_paren", "("),
+      optional(seq(
+        field("item", $.expression),
+        repeat1(seq(field("separator", ";"), field("item", $.expression)))
+      )),
+      field("right_paren", ")")
+    ),
+
+    // Argument list: expr;expr;expr, where any slot may be left empty to
+    // project the function: add[2;]
+    argument_list: ($) => choice(
       field("arg", $.expression),
-      repeat(seq(field("separator", ";"), field("arg", $.expression)))
+      seq(
+        optional(field("arg", $.expression)),
+        repeat1(seq(field("separator", ";"), optional(field("arg", $.expression))))
+      )
     ),
 
-    // Identifier (variable/function names)
-    identifier: () => /[a-zA-Z_][a-zA-Z0-9_]*/,
+    // Identifier (variable/function names); no leading underscore so 2_x is a drop.
+    // Dotted names such as .db.create address namespaces
+    identifier: () => /\.?[a-zA-Z][a-zA-Z0-9_]*(\.[a-zA-Z][a-zA-Z0-9_]*)*/,
+
+    // Number literals: integers, or floats with a decimal point or an f
+    // suffix (2f, as whole floats print)
+    // 0N and 0n are the integer and float nulls, 0W and 0w their infinities
+    // Integers may also be written in hex (0x1f) or binary (0b1010)
+    number: () => /0x[0-9a-fA-F]+|0b[01]+|\d+(\.\d+)?f?|0[NWnw]/,
+
+    // Temporal literals: a date 2024.01.15, a time 12:30:00.250, and a
+    // timestamp 2024.01.15D12:30:00.250000000 whose time may be shortened
+    date: () => /\d{4}\.\d{2}\.\d{2}/,
+    time: () => /\d{2}:\d{2}(:\d{2}(\.\d{1,3})?)?/,
+    timestamp: () => /\d{4}\.\d{2}\.\d{2}D(\d{2}:\d{2}(:\d{2}(\.\d{1,9})?)?)?/,
+
+    // Symbol literals: `trades, or a symbol vector `time`sym`px
+    symbol: () => /(`[a-zA-Z0-9_.:\/]*)+/,
+
+    // String literals: "abc", with \" \\ \n \t and \r escapes; a single
+    // character "a" is a char atom
+    string: () => /"([^"\\]|\\.)*"/,
 
-    // Integer literals
-    number: () => /\d+/,
+    // Boolean literals: 1b, or a boolean vector 0101b
+    boolean: () => /[01]+b/,
 
     // Q/KDB+ style comments - only end-of-line comments to avoid division ambiguity
     comment: () => /\\[^\r\n]*/,
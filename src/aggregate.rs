@@ -0,0 +1,450 @@
+//! Aggregates: builtins that reduce a list to an atom
+//!
+//! `sum`, `avg`, `min` and `max` skip nulls, as in q, so a missing value
+//! does not hide the rest. The statistics `var`, `dev`, `cov` and `cor`
+//! are of the population, and like the weighted `wsum` and `wavg` skip the
+//! positions where either list has a null. An atom aggregates as a list of itself, which
+//! lets aggregates apply to the atoms of a single-row group. With parallel
+//! execution enabled, `sum`, `avg`, `min` and `max` reduce chunks of a long
+//! list at once; see [`crate::parallel`]. They and `count` read typed
+//! vectors ([`crate::vectors`]) as they are, without unpacking the items.
+//!
+//! ```text
+//! select sum size, avg px by sym from trades
+//! select vwap: wavg[size;px] by sym from trades
+//! ```
+
+use crate::arithmetic;
+use crate::builtins::Context;
+use crate::environment::{INFINITY_INTEGER, NULL_INTEGER, Value};
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::parallel;
+use std::cmp::Ordering;
+use tree_sitter::Node;
+
+/// The items to aggregate: a list's items, or an atom alone
+fn items(value: &Value) -> &[Value] {
+    match value {
+        Value::List(items) => items,
+        atom => std::slice::from_ref(atom),
+    }
+}
+
+fn not_numeric(name: &str, value: &Value, node: Node) -> EvalError {
+    EvalError::new(
+        EvalErrorKind::Type(format!(
+            "{} expects numbers, got {}",
+            name,
+            value.type_name()
+        )),
+        node,
+    )
+}
+
+/// `f` of the items, or of chunks of them across threads when the list is
+/// long enough, with the partial results combined by `combine`
+fn reduce<'a, T: Sync, R: Send>(
+    context: &Context,
+    items: &'a [T],
+    f: impl Fn(&'a [T]) -> Result<R, EvalError> + Sync,
+    combine: impl Fn(R, R) -> Result<R, EvalError>,
+) -> Result<R, EvalError> {
+    if !parallel::splits(context.parallel, items.len()) {
+        return f(items);
+    }
+    let mut parts = parallel::chunks(items, &f).into_iter();
+    let first = parts.next().unwrap_or_else(|| f(&[]))?;
+    parts.try_fold(first, |total, part| combine(total, part?))
+}
+
+/// Integer and float parts of a sum; the float part is there once a float
+/// is added
+#[derive(Clone, Copy)]
+struct Total {
+    integer: i64,
+    float: Option<f64>,
+}
+
+/// The sum of the numbers in `items`
+fn total(items: &[Value], node: Node) -> Result<Total, EvalError> {
+    let mut total = 0i64;
+    let mut float: Option<f64> = None;
+    for item in items.iter().filter(|item| !item.is_null()) {
+        match item {
+            Value::Integer(n) => total = add_integers(total, *n, node)?,
+            Value::Float(f) => *float.get_or_insert(0.0) += f,
+            Value::Boolean(b) => total += i64::from(*b),
+            other => return Err(not_numeric("sum", other, node)),
+        }
+    }
+    Ok(Total {
+        integer: total,
+        float,
+    })
+}
+
+fn add_integers(a: i64, b: i64, node: Node) -> Result<i64, EvalError> {
+    a.checked_add(b)
+        .ok_or_else(|| EvalError::new(EvalErrorKind::IntegerOverflow("sum".into()), node))
+}
+
+/// Sum of the integers of a vector that are not null
+fn sum_ints(context: &Context, ints: &[i64], node: Node) -> Result<i64, EvalError> {
+    reduce(
+        context,
+        ints,
+        |ints| {
+            ints.iter()
+                .filter(|&&n| n != NULL_INTEGER)
+                .try_fold(0, |total, &n| add_integers(total, n, node))
+        },
+        |a, b| add_integers(a, b, node),
+    )
+}
+
+/// A total and the number of items in it
+type Count = (f64, usize);
+
+/// Sum and number of the floats of a vector that are not null
+fn sum_floats(context: &Context, floats: &[f64]) -> Result<Count, EvalError> {
+    reduce(
+        context,
+        floats,
+        |floats| {
+            let present = floats.iter().filter(|f| !f.is_nan());
+            Ok(present.fold((0.0, 0), |(total, n), f| (total + f, n + 1)))
+        },
+        |(a, m), (b, n)| Ok((a + b, m + n)),
+    )
+}
+
+/// `sum x`: the total of the numbers in `x`; an integer unless any is a float
+pub fn sum(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    match &args[0] {
+        Value::IntVector(ints) => return Ok(Value::Integer(sum_ints(context, ints, node)?)),
+        // With every item null, no float is added
+        Value::FloatVector(floats) => {
+            return Ok(match sum_floats(context, floats)? {
+                (_, 0) => Value::Integer(0),
+                (total, _) => Value::Float(total),
+            });
+        }
+        _ => {}
+    }
+    let total = reduce(
+        context,
+        items(&args[0]),
+        |items| total(items, node),
+        |a, b| {
+            Ok(Total {
+                integer: add_integers(a.integer, b.integer, node)?,
+                float: match (a.float, b.float) {
+                    (None, None) => None,
+                    (a, b) => Some(a.unwrap_or(0.0) + b.unwrap_or(0.0)),
+                },
+            })
+        },
+    )?;
+    Ok(match total.float {
+        Some(f) => Value::Float(f + total.integer as f64),
+        None => Value::Integer(total.integer),
+    })
+}
+
+/// `count x`: the number of items in a list or dict, or rows in a table;
+/// an atom counts as one
+pub fn count(_: &mut Context, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    let n = match &args[0] {
+        Value::List(items) => items.len(),
+        Value::IntVector(ints) => ints.len(),
+        Value::FloatVector(floats) => floats.len(),
+        Value::Dict { keys, .. } => keys.len(),
+        Value::Table(table) => table.len(),
+        _ => 1,
+    };
+    Ok(Value::Integer(n as i64))
+}
+
+/// `avg x`: the mean of the numbers in `x`, as a float; `0n` if there are
+/// none
+pub fn avg(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let (total, n) = match &args[0] {
+        Value::FloatVector(floats) => sum_floats(context, floats)?,
+        Value::IntVector(ints) => reduce(
+            context,
+            ints,
+            |ints| {
+                let present = ints.iter().filter(|&&n| n != NULL_INTEGER);
+                let floats = present.filter_map(|&n| Value::Integer(n).as_f64());
+                Ok(floats.fold((0.0, 0), |(total, n), f| (total + f, n + 1)))
+            },
+            |(a, m), (b, n)| Ok((a + b, m + n)),
+        )?,
+        value => reduce(
+            context,
+            items(value),
+            |items| {
+                let mut total = 0.0;
+                let mut n = 0usize;
+                for item in items.iter().filter(|item| !item.is_null()) {
+                    total += item
+                        .as_f64()
+                        .ok_or_else(|| not_numeric("avg", item, node))?;
+                    n += 1;
+                }
+                Ok((total, n))
+            },
+            |(a, m), (b, n)| Ok((a + b, m + n)),
+        )?,
+    };
+    Ok(Value::Float(if n == 0 {
+        f64::NAN
+    } else {
+        total / n as f64
+    }))
+}
+
+/// The item kept so far, if any
+type Best<'a> = Option<&'a Value>;
+
+/// The least or greatest of `items` that is not null, if any
+fn best<'a>(
+    name: &str,
+    items: impl IntoIterator<Item = &'a Value>,
+    keep: Ordering,
+    node: Node,
+) -> Result<Best<'a>, EvalError> {
+    let mut best: Option<&Value> = None;
+    for item in items.into_iter().filter(|item| !item.is_null()) {
+        best = match best {
+            None => Some(item),
+            Some(current) => match item.compare(current) {
+                Some(ordering) if ordering == keep => Some(item),
+                Some(_) => Some(current),
+                None => {
+                    return Err(EvalError::new(
+                        EvalErrorKind::Type(format!(
+                            "{} cannot compare {} with {}",
+                            name,
+                            item.type_name(),
+                            current.type_name()
+                        )),
+                        node,
+                    ));
+                }
+            },
+        };
+    }
+    Ok(best)
+}
+
+/// The least or greatest item, or `0W` or `-0W` when there are none
+fn extreme(
+    context: &Context,
+    name: &str,
+    value: &Value,
+    keep: Ordering,
+    node: Node,
+) -> Result<Value, EvalError> {
+    let none = || {
+        Value::Integer(match keep {
+            Ordering::Less => INFINITY_INTEGER,
+            _ => -INFINITY_INTEGER,
+        })
+    };
+    match value {
+        Value::IntVector(ints) => {
+            let present = ints.iter().copied().filter(|&n| n != NULL_INTEGER);
+            let best = match keep {
+                Ordering::Less => present.min(),
+                _ => present.max(),
+            };
+            return Ok(best.map_or_else(none, Value::Integer));
+        }
+        Value::FloatVector(floats) => {
+            // The first of equal items is kept, as for lists
+            let best = floats
+                .iter()
+                .copied()
+                .filter(|f| !f.is_nan())
+                .reduce(|best, f| {
+                    if f.partial_cmp(&best) == Some(keep) {
+                        f
+                    } else {
+                        best
+                    }
+                });
+            return Ok(best.map_or_else(none, Value::Float));
+        }
+        _ => {}
+    }
+    let best = reduce(
+        context,
+        items(value),
+        |items| best(name, items, keep, node),
+        |a, b| best(name, a.into_iter().chain(b), keep, node),
+    )?;
+    Ok(best.cloned().unwrap_or_else(none))
+}
+
+/// `min x`: the least item of `x`
+pub fn min(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    extreme(context, "min", &args[0], Ordering::Less, node)
+}
+
+/// `max x`: the greatest item of `x`
+pub fn max(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    extreme(context, "max", &args[0], Ordering::Greater, node)
+}
+
+/// Numbers of a list as floats
+type Numbers = Vec<f64>;
+
+/// Numbers of two lists, paired by position
+type Pairs = [Numbers; 2];
+
+/// Items of two lists, paired by position
+type Present<'a> = Vec<(&'a Value, &'a Value)>;
+
+/// A weighted total and the total of the weights
+type WeightedResult = Result<(Value, Value), EvalError>;
+
+/// The numbers in `value` that are not null, as floats
+fn numbers(name: &str, value: &Value, node: Node) -> Result<Numbers, EvalError> {
+    items(value)
+        .iter()
+        .filter(|item| !item.is_null())
+        .map(|item| match item {
+            Value::Integer(_) | Value::BigInt(_) | Value::Float(_) | Value::Boolean(_) => {
+                item.as_f64().ok_or_else(|| not_numeric(name, item, node))
+            }
+            other => Err(not_numeric(name, other, node)),
+        })
+        .collect()
+}
+
+/// The items of `x` and `y` at the positions where neither is null, as a
+/// list of the two, checked to be numbers
+fn present<'a>(
+    name: &str,
+    x: &'a Value,
+    y: &'a Value,
+    node: Node,
+) -> Result<Present<'a>, EvalError> {
+    let (x, y) = (items(x), items(y));
+    if x.len() != y.len() {
+        return Err(EvalError::new(
+            EvalErrorKind::LengthMismatch(format!("{} vs {} in {}", x.len(), y.len(), name)),
+            node,
+        ));
+    }
+    let kept: Present = x
+        .iter()
+        .zip(y)
+        .filter(|(a, b)| !a.is_null() && !b.is_null())
+        .collect();
+    for item in kept.iter().flat_map(|&(a, b)| [a, b]) {
+        if !matches!(
+            item,
+            Value::Integer(_) | Value::BigInt(_) | Value::Float(_) | Value::Boolean(_)
+        ) {
+            return Err(not_numeric(name, item, node));
+        }
+    }
+    Ok(kept)
+}
+
+/// The items of `x` and `y` at the positions where neither is null, as
+/// two lists of floats
+fn pairs(name: &str, x: &Value, y: &Value, node: Node) -> Result<Pairs, EvalError> {
+    let kept = present(name, x, y, node)?;
+    let float = |item: &Value| item.as_f64().unwrap_or(f64::NAN);
+    Ok(kept
+        .iter()
+        .map(|&(a, b)| (float(a), float(b)))
+        .unzip()
+        .into())
+}
+
+/// Mean of `numbers`, `NaN` if there are none
+fn mean(numbers: &[f64]) -> f64 {
+    numbers.iter().sum::<f64>() / numbers.len() as f64
+}
+
+/// Mean of the products of the deviations of `xs` and `ys` from their means
+fn covariance(xs: &[f64], ys: &[f64]) -> f64 {
+    let (mx, my) = (mean(xs), mean(ys));
+    let products = xs.iter().zip(ys).map(|(x, y)| (x - mx) * (y - my));
+    products.sum::<f64>() / xs.len() as f64
+}
+
+/// The sum of the items of `x` weighted by those of `w`, and the sum of
+/// the weights, for the builtin `name`
+///
+/// Unless either list has floats, both are integers, with overflow resolved
+/// by the context's overflow mode as in arithmetic, so that no precision is
+/// lost to floats.
+fn weighted(context: &Context, name: &str, w: &Value, x: &Value, node: Node) -> WeightedResult {
+    let integers = |value: &Value| {
+        items(value)
+            .iter()
+            .all(|item| matches!(item, Value::Integer(_) | Value::Boolean(_)))
+    };
+    if !(integers(w) && integers(x)) {
+        let [ws, xs] = pairs(name, w, x, node)?;
+        let products = ws.iter().zip(&xs).map(|(w, x)| w * x);
+        return Ok((Value::Float(products.sum()), Value::Float(ws.iter().sum())));
+    }
+    let integer = |item: &Value| match item {
+        Value::Boolean(b) => Value::Integer(i64::from(*b)),
+        other => other.clone(),
+    };
+    let add = |a: &Value, b: &Value| arithmetic::binary("+", a, b, context.overflow, node, node);
+    let (mut total, mut weights) = (Value::Integer(0), Value::Integer(0));
+    for (w, x) in present(name, w, x, node)? {
+        let (w, x) = (integer(w), integer(x));
+        let product = arithmetic::binary("*", &w, &x, context.overflow, node, node)?;
+        total = add(&total, &product)?;
+        weights = add(&weights, &w)?;
+    }
+    Ok((total, weights))
+}
+
+/// `wsum[w;x]`: the sum of the items of `x` weighted by those of `w`; an
+/// integer unless either has floats
+pub fn wsum(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let (total, _) = weighted(context, "wsum", &args[0], &args[1], node)?;
+    Ok(total)
+}
+
+/// `wavg[w;x]`: the average of the items of `x` weighted by those of `w`
+pub fn wavg(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let (total, weights) = weighted(context, "wavg", &args[0], &args[1], node)?;
+    let float = |value: &Value| value.as_f64().unwrap_or(f64::NAN);
+    Ok(Value::Float(float(&total) / float(&weights)))
+}
+
+/// `var x`: the variance of the numbers in `x`
+pub fn var(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let xs = numbers("var", &args[0], node)?;
+    Ok(Value::Float(covariance(&xs, &xs)))
+}
+
+/// `dev x`: the standard deviation of the numbers in `x`
+pub fn dev(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let xs = numbers("dev", &args[0], node)?;
+    Ok(Value::Float(covariance(&xs, &xs).sqrt()))
+}
+
+/// `cov[x;y]`: the covariance of `x` and `y`
+pub fn cov(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let [xs, ys] = pairs("cov", &args[0], &args[1], node)?;
+    Ok(Value::Float(covariance(&xs, &ys)))
+}
+
+/// `cor[x;y]`: the correlation of `x` and `y`
+pub fn cor(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let [xs, ys] = pairs("cor", &args[0], &args[1], node)?;
+    let spread = (covariance(&xs, &xs) * covariance(&ys, &ys)).sqrt();
+    Ok(Value::Float(covariance(&xs, &ys) / spread))
+}
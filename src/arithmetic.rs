@@ -0,0 +1,413 @@
+//! Arithmetic on numeric atoms under a configurable overflow policy
+//!
+//! Integer arithmetic is checked. What happens when a result does not fit in
+//! 64 bits is decided by the evaluator's [`OverflowMode`]: raise an error
+//! (the default), wrap around, clamp to the representable range, or promote
+//! the result to a float or to a [`BigInt`]. Any float operand makes the
+//! operation a float one; a big operand makes it exact, whatever the mode.
+//!
+//! Nulls propagate: an operation with a `0N` or `0n` operand yields the null
+//! of the result type. In float operations `0N` and `0W` become `0n` and `0w`.
+//!
+//! Dates, times and timestamps follow the rules in [`crate::temporal`].
+//!
+//! Lists apply item by item, as comparisons do: `1 2 3+10` is `11 12 13`,
+//! and two lists must have the same length. Lists of one numeric type go
+//! through the vector kernels in [`crate::kernels`]; the evaluator keeps
+//! the typed vectors they give, and [`binary`] gives general lists.
+
+use crate::bigint::BigInt;
+use crate::environment::{NULL_INTEGER, Value};
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::kernels;
+use crate::temporal;
+use tree_sitter::Node;
+
+/// How integer arithmetic that overflows 64 bits is resolved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowMode {
+    /// Fail with [`EvalErrorKind::IntegerOverflow`]
+    #[default]
+    Error,
+    /// Two's-complement wrap-around, as bit-twiddling code expects
+    Wrap,
+    /// Clamp to `i64::MIN` or `i64::MAX`
+    Saturate,
+    /// Compute the result as a float instead
+    Promote,
+    /// Compute the result exactly as a big integer instead
+    BigInt,
+}
+
+impl OverflowMode {
+    /// Parse a mode from its name: `error`, `wrap`, `saturate`, `promote` or
+    /// `bigint`
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "error" => OverflowMode::Error,
+            "wrap" => OverflowMode::Wrap,
+            "saturate" => OverflowMode::Saturate,
+            "promote" => OverflowMode::Promote,
+            "bigint" => OverflowMode::BigInt,
+            _ => return None,
+        })
+    }
+
+    /// Name the mode is parsed from
+    pub fn name(self) -> &'static str {
+        match self {
+            OverflowMode::Error => "error",
+            OverflowMode::Wrap => "wrap",
+            OverflowMode::Saturate => "saturate",
+            OverflowMode::Promote => "promote",
+            OverflowMode::BigInt => "bigint",
+        }
+    }
+
+    /// Resolve an integer operation whose checked result is `checked`
+    ///
+    /// `wrapped` and `saturated` are only used when the checked result
+    /// overflowed, and `promoted` computes the exact result on demand for
+    /// promotion to a float or a big integer.
+    fn resolve(
+        self,
+        checked: Option<i64>,
+        wrapped: i64,
+        saturated: i64,
+        promoted: impl FnOnce() -> BigInt,
+        operation: &str,
+        node: Node,
+    ) -> Result<Value, EvalError> {
+        if let Some(n) = checked {
+            return Ok(Value::Integer(n));
+        }
+        match self {
+            OverflowMode::Error => Err(EvalError::new(
+                EvalErrorKind::IntegerOverflow(operation.into()),
+                node,
+            )),
+            OverflowMode::Wrap => Ok(Value::Integer(wrapped)),
+            OverflowMode::Saturate => Ok(Value::Integer(saturated)),
+            OverflowMode::Promote => Ok(Value::Float(promoted().to_f64())),
+            OverflowMode::BigInt => Ok(Value::from(promoted())),
+        }
+    }
+}
+
+/// A numeric atom, or an error naming what was expected
+fn number(value: &Value, context: &str, node: Node) -> Result<Number, EvalError> {
+    match value {
+        Value::Integer(n) => Ok(Number::Int(*n)),
+        Value::Float(f) => Ok(Number::Float(*f)),
+        // Against a float, a big integer is as near a float as it has
+        Value::BigInt(n) => Ok(Number::Float(n.to_f64())),
+        _ => Err(EvalError::new(
+            EvalErrorKind::Type(format!("expected a number in {}", context)),
+            node,
+        )),
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(n) => Value::Integer(n).as_f64().unwrap_or(f64::NAN),
+            Number::Float(f) => f,
+        }
+    }
+
+    fn is_null(self) -> bool {
+        match self {
+            Number::Int(n) => n == NULL_INTEGER,
+            Number::Float(f) => f.is_nan(),
+        }
+    }
+}
+
+/// `left op right` for `+ - * / %`
+///
+/// Integer division truncates. Dividing by zero is an error for integers and
+/// floats alike, unless an operand is null.
+pub fn binary(
+    op: &str,
+    left: &Value,
+    right: &Value,
+    mode: OverflowMode,
+    node: Node,
+    op_node: Node,
+) -> Result<Value, EvalError> {
+    if let Some(result) = kernels::binary(op, left, right) {
+        return Ok(result.plain());
+    }
+    general(op, left, right, mode, node, op_node)
+}
+
+/// [`binary`] without the vector kernels: item by item for lists
+pub fn general(
+    op: &str,
+    left: &Value,
+    right: &Value,
+    mode: OverflowMode,
+    node: Node,
+    op_node: Node,
+) -> Result<Value, EvalError> {
+    let each = |a: &Value, b: &Value| binary(op, a, b, mode, node, op_node);
+    match (left, right) {
+        (Value::List(l), Value::List(r)) => {
+            if l.len() != r.len() {
+                return Err(EvalError::new(
+                    EvalErrorKind::LengthMismatch(format!(
+                        "{} vs {} in arithmetic",
+                        l.len(),
+                        r.len()
+                    )),
+                    node,
+                ));
+            }
+            return l
+                .iter()
+                .zip(r)
+                .map(|(a, b)| each(a, b))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::List);
+        }
+        (Value::List(l), atom) => {
+            return l
+                .iter()
+                .map(|a| each(a, atom))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::List);
+        }
+        (atom, Value::List(r)) => {
+            return r
+                .iter()
+                .map(|b| each(atom, b))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::List);
+        }
+        _ => {}
+    }
+    if let Some(result) = temporal::binary(op, left, right, node) {
+        return result;
+    }
+    if let Some(result) = big_binary(op, left, right, node, op_node) {
+        return result;
+    }
+    let l = number(left, "arithmetic", node)?;
+    let r = number(right, "arithmetic", node)?;
+    if l.is_null() || r.is_null() {
+        return match (l, r) {
+            (Number::Int(_), Number::Int(_)) => Ok(Value::Integer(NULL_INTEGER)),
+            _ => Ok(Value::Float(f64::NAN)),
+        };
+    }
+    if matches!(op, "/" | "%") && r.as_f64() == 0.0 {
+        return Err(EvalError::new(EvalErrorKind::DivisionByZero, node));
+    }
+
+    let (a, b) = match (l, r) {
+        (Number::Int(a), Number::Int(b)) => (a, b),
+        _ => {
+            let (a, b) = (l.as_f64(), r.as_f64());
+            return Ok(Value::Float(match op {
+                "+" => a + b,
+                "-" => a - b,
+                "*" => a * b,
+                "/" => a / b,
+                "%" => a % b,
+                _ => return Err(unknown_operator(op, op_node)),
+            }));
+        }
+    };
+    match op {
+        "+" => mode.resolve(
+            a.checked_add(b),
+            a.wrapping_add(b),
+            a.saturating_add(b),
+            || BigInt::from(a).add(&BigInt::from(b)),
+            "addition",
+            node,
+        ),
+        "-" => mode.resolve(
+            a.checked_sub(b),
+            a.wrapping_sub(b),
+            a.saturating_sub(b),
+            || BigInt::from(a).sub(&BigInt::from(b)),
+            "subtraction",
+            node,
+        ),
+        "*" => mode.resolve(
+            a.checked_mul(b),
+            a.wrapping_mul(b),
+            a.saturating_mul(b),
+            || BigInt::from(a).mul(&BigInt::from(b)),
+            "multiplication",
+            node,
+        ),
+        // Only i64::MIN / -1 overflows
+        "/" => mode.resolve(
+            a.checked_div(b),
+            a.wrapping_div(b),
+            a.saturating_div(b),
+            || {
+                BigInt::from(a)
+                    .div_rem(&BigInt::from(b))
+                    .expect("the divisor is not zero")
+                    .0
+            },
+            "division",
+            node,
+        ),
+        // The remainder always fits; only i64::MIN % -1 needs wrapping to 0
+        "%" => Ok(Value::Integer(a.wrapping_rem(b))),
+        _ => Err(unknown_operator(op, op_node)),
+    }
+}
+
+/// The outcome of an operation
+type Evaluated = Result<Value, EvalError>;
+
+/// `left op right` computed exactly when either is a big integer and the
+/// other an integer, or `None` for other operands
+fn big_binary(
+    op: &str,
+    left: &Value,
+    right: &Value,
+    node: Node,
+    op_node: Node,
+) -> Option<Evaluated> {
+    let exact = |value: &Value| match value {
+        Value::BigInt(n) => Some(n.clone()),
+        Value::Integer(n) => Some(BigInt::from(*n)),
+        _ => None,
+    };
+    if !matches!(left, Value::BigInt(_)) && !matches!(right, Value::BigInt(_)) {
+        return None;
+    }
+    let (a, b) = (exact(left)?, exact(right)?);
+    if left.is_null() || right.is_null() {
+        return Some(Ok(Value::Integer(NULL_INTEGER)));
+    }
+    let result = match op {
+        "+" => a.add(&b),
+        "-" => a.sub(&b),
+        "*" => a.mul(&b),
+        "/" | "%" => match a.div_rem(&b) {
+            Some((quotient, _)) if op == "/" => quotient,
+            Some((_, remainder)) => remainder,
+            None => return Some(Err(EvalError::new(EvalErrorKind::DivisionByZero, node))),
+        },
+        _ => return Some(Err(unknown_operator(op, op_node))),
+    };
+    Some(Ok(Value::from(result)))
+}
+
+fn unknown_operator(op: &str, op_node: Node) -> EvalError {
+    EvalError::new(EvalErrorKind::UnknownOperator(op.into()), op_node)
+}
+
+/// `-x`
+pub fn negate(value: &Value, mode: OverflowMode, node: Node) -> Result<Value, EvalError> {
+    if let Value::BigInt(n) = value {
+        return Ok(Value::from(n.neg()));
+    }
+    match number(value, "unary operation", node)? {
+        Number::Float(f) => Ok(Value::Float(-f)),
+        Number::Int(NULL_INTEGER) => Ok(Value::Integer(NULL_INTEGER)),
+        Number::Int(n) => mode.resolve(
+            n.checked_neg(),
+            n.wrapping_neg(),
+            n.saturating_neg(),
+            || BigInt::from(n).neg(),
+            "negation",
+            node,
+        ),
+    }
+}
+
+/// `base^exponent` for integers, with a non-negative exponent below 64
+pub fn power(base: i64, exponent: i64, mode: OverflowMode, node: Node) -> Result<Value, EvalError> {
+    if base == NULL_INTEGER || exponent == NULL_INTEGER {
+        return Ok(Value::Integer(NULL_INTEGER));
+    }
+    if exponent < 0 {
+        return Err(EvalError::new(EvalErrorKind::NegativeExponent, node));
+    }
+    if exponent > 63 {
+        return Err(EvalError::new(EvalErrorKind::ExponentTooLarge, node));
+    }
+    let e = exponent as u32;
+    mode.resolve(
+        base.checked_pow(e),
+        base.wrapping_pow(e),
+        base.saturating_pow(e),
+        || (0..e).fold(BigInt::from(1), |acc, _| acc.mul(&BigInt::from(base))),
+        "exponentiation",
+        node,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+
+    fn add(a: i64, b: i64, mode: OverflowMode) -> Result<Value, EvalError> {
+        let tree = parse_expression("1").unwrap();
+        let node = tree.root_node();
+        binary(
+            "+",
+            &Value::Integer(a),
+            &Value::Integer(b),
+            mode,
+            node,
+            node,
+        )
+    }
+
+    #[test]
+    fn test_overflow_modes() {
+        assert!(matches!(
+            add(i64::MAX, 1, OverflowMode::Error).unwrap_err().kind,
+            EvalErrorKind::IntegerOverflow(_)
+        ));
+        assert_eq!(
+            add(i64::MAX, 1, OverflowMode::Wrap).unwrap(),
+            Value::Integer(i64::MIN)
+        );
+        assert_eq!(
+            add(i64::MAX, 1, OverflowMode::Saturate).unwrap(),
+            Value::Integer(i64::MAX)
+        );
+        assert_eq!(
+            add(i64::MAX, 1, OverflowMode::Promote).unwrap(),
+            Value::Float(i64::MAX as f64 + 1.0)
+        );
+        assert_eq!(
+            add(i64::MAX, 1, OverflowMode::BigInt).unwrap(),
+            Value::BigInt(BigInt::from(i64::MAX).add(&BigInt::from(1)))
+        );
+        // Results that fit are integers whatever the mode
+        assert_eq!(add(1, 2, OverflowMode::Promote).unwrap(), Value::Integer(3));
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        for mode in [
+            OverflowMode::Error,
+            OverflowMode::Wrap,
+            OverflowMode::Saturate,
+            OverflowMode::Promote,
+            OverflowMode::BigInt,
+        ] {
+            assert_eq!(OverflowMode::parse(mode.name()), Some(mode));
+        }
+        assert_eq!(OverflowMode::parse("clamp"), None);
+    }
+}
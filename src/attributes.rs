@@ -0,0 +1,300 @@
+//! List attributes: `` `s# ``, `` `u# `` and `` `g# ``
+//!
+//! An attribute asserts something about a list that lookups can rely on
+//! instead of scanning it:
+//!
+//! ```text
+//! x: `s#1 3 5 7      / sorted: lookups binary search
+//! y: `u#`a`b`c       / unique: lookups hash
+//! z: `g#`a`b`a`c     / grouped: the positions of each item are kept
+//! attr z             / `g
+//! `#z                / the plain list again
+//! ```
+//!
+//! Applying `` `s# `` to a list not in ascending order, or `` `u# `` to one
+//! with repeats, is an error. The hash index of a unique or grouped list is
+//! built when the attribute is applied.
+//!
+//! Attributes stay with a list while it is bound to a name, passed to or
+//! returned from a function, and given to builtins that exploit them
+//! ([`Attributed::shortcut`]). Anything else sees a plain list, as
+//! [`Value::plain`] gives it: `x+1` is not known to be sorted.
+//!
+//! A table column keeps the attribute of the list it was set from, as with
+//! `` update `g#sym from t `` (see [`crate::table`]). A query condition
+//! testing the column, as `sym=x`, `sym in xs` or `time within (lo;hi)`,
+//! finds its rows through the attribute ([`Attributed::matching`]), and
+//! `aj` takes the groups of a grouped or unique column and the order of a
+//! sorted time column from theirs.
+
+use crate::builtins::Context;
+use crate::environment::{ListItems, Value};
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::table::{self, Column};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tree_sitter::Node;
+
+/// What an attribute asserts about a list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    /// Items are in ascending order
+    Sorted,
+    /// No item occurs twice
+    Unique,
+    /// The positions of each distinct item are indexed
+    Grouped,
+}
+
+impl Attribute {
+    /// The attribute a symbol names: `s`, `u` or `g`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "s" => Some(Attribute::Sorted),
+            "u" => Some(Attribute::Unique),
+            "g" => Some(Attribute::Grouped),
+            _ => None,
+        }
+    }
+
+    /// Name of the attribute, as written before `#`
+    pub fn name(self) -> &'static str {
+        match self {
+            Attribute::Sorted => "s",
+            Attribute::Unique => "u",
+            Attribute::Grouped => "g",
+        }
+    }
+}
+
+/// Groups of distinct items by the hash of their item
+type Index = HashMap<u64, Vec<usize>>;
+
+/// Positions of each distinct item of a list
+pub type Groups = [Vec<usize>];
+
+/// A list with an attribute
+#[derive(Debug, Clone)]
+pub struct Attributed {
+    attribute: Attribute,
+    items: Column,
+    /// Positions of each distinct item, in order of first occurrence; empty
+    /// for a sorted list
+    groups: Vec<Vec<usize>>,
+    /// Groups by the hash of their item
+    index: Index,
+}
+
+impl Attributed {
+    /// `items` with `attribute`, or why they cannot have it
+    pub fn new(attribute: Attribute, items: Column) -> Result<Self, String> {
+        if attribute == Attribute::Sorted {
+            let ascending = items
+                .windows(2)
+                .all(|pair| pair[0].compare(&pair[1]).is_some_and(Ordering::is_le));
+            if !ascending {
+                return Err("`s# needs a list in ascending order".into());
+            }
+            return Ok(Self {
+                attribute,
+                items,
+                groups: Vec::new(),
+                index: Index::new(),
+            });
+        }
+        let groups = table::group_rows(&[&items]);
+        if attribute == Attribute::Unique && groups.len() < items.len() {
+            return Err("`u# needs a list without repeats".into());
+        }
+        let mut index = Index::new();
+        for (g, group) in groups.iter().enumerate() {
+            let key = table::hash_key([&items[group[0]]]);
+            index.entry(key).or_default().push(g);
+        }
+        Ok(Self {
+            attribute,
+            items,
+            groups,
+            index,
+        })
+    }
+
+    /// What the attribute asserts
+    pub fn attribute(&self) -> Attribute {
+        self.attribute
+    }
+
+    /// Items of the list
+    pub fn items(&self) -> &ListItems {
+        &self.items
+    }
+
+    /// The list without its attribute
+    pub fn into_items(self) -> Column {
+        self.items
+    }
+
+    /// Positions of each distinct item, in order of first occurrence, each
+    /// ascending; empty for a sorted list
+    pub fn groups(&self) -> &Groups {
+        &self.groups
+    }
+
+    /// Which of [`Attributed::groups`] holds the positions of `value`, if
+    /// it occurs in a unique or grouped list
+    pub fn group_of(&self, value: &Value) -> Option<usize> {
+        self.index
+            .get(&table::hash_key([value]))?
+            .iter()
+            .copied()
+            .find(|&g| self.items[self.groups[g][0]] == *value)
+    }
+
+    /// Positions at which `value` occurs, in ascending order
+    pub fn positions(&self, value: &Value) -> Vec<usize> {
+        if self.attribute == Attribute::Sorted {
+            let below = |item: &Value| item.compare(value).is_some_and(Ordering::is_lt);
+            let start = self.items.partition_point(below);
+            let end = start + self.items[start..].partition_point(|item| item == value);
+            return (start..end).collect();
+        }
+        self.group_of(value)
+            .map(|g| self.groups[g].clone())
+            .unwrap_or_default()
+    }
+
+    /// Positions, in ascending order, of the items for which `item op
+    /// value` holds, where the attribute finds them without looking at
+    /// every item: `=` an atom, `in` a list of atoms, or for a sorted list,
+    /// `within` a pair of bounds
+    pub fn matching(&self, op: &str, value: &Value) -> Option<Vec<usize>> {
+        // Nulls never equal each other item by item, so are left to scans
+        let atom = |value: &Value| value.as_list().is_none() && !value.is_null();
+        match (op, value.as_list()) {
+            ("=", None) if atom(value) => Some(self.positions(value)),
+            ("in", None) if atom(value) => Some(self.positions(value)),
+            ("in", Some(values)) if values.iter().all(atom) => {
+                let mut positions: Vec<usize> = values
+                    .iter()
+                    .flat_map(|value| self.positions(value))
+                    .collect();
+                positions.sort_unstable();
+                positions.dedup();
+                Some(positions)
+            }
+            ("within", Some([lo, hi])) if self.attribute == Attribute::Sorted => {
+                let below = |item: &Value| item.compare(lo).is_some_and(Ordering::is_lt);
+                let at_most = |item: &Value| item.compare(hi).is_some_and(Ordering::is_le);
+                let start = self.items.partition_point(below);
+                let end = start + self.items[start..].partition_point(at_most);
+                Some((start..end).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// First position at which `value` occurs
+    pub fn find(&self, value: &Value) -> Option<usize> {
+        self.positions(value).first().copied()
+    }
+
+    /// The result of the builtin `name` applied to the list alone, where
+    /// the attribute gives it without looking at every item
+    pub fn shortcut(&self, name: &str) -> Option<Value> {
+        let positions = |group: &Vec<usize>| {
+            Value::List(group.iter().map(|&i| Value::Integer(i as i64)).collect())
+        };
+        let non_null = |item: &&Value| !item.is_null();
+        match (self.attribute, name) {
+            (_, "attr") => Some(Value::Symbol(self.attribute.name().into())),
+            // Nulls sort first, and aggregates skip them
+            (Attribute::Sorted, "min") => self.items.iter().find(non_null).cloned(),
+            (Attribute::Sorted, "max") => self.items.iter().rev().find(non_null).cloned(),
+            (Attribute::Unique, "distinct") => Some(Value::List(self.items.clone())),
+            (Attribute::Unique | Attribute::Grouped, "group") => Some(Value::Dict {
+                keys: self
+                    .groups
+                    .iter()
+                    .map(|group| self.items[group[0]].clone())
+                    .collect(),
+                values: self.groups.iter().map(positions).collect(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the builtin `name` looks lists up through their attributes, and
+/// so is given them rather than plain lists
+pub fn exploited_by(name: &str) -> bool {
+    matches!(name, "in" | "within" | "find")
+}
+
+/// `` `s#x ``: `x` with the attribute the symbol names, or `` `#x `` for
+/// `x` without one
+pub fn apply(name: &str, target: Value, node: Node) -> Result<Value, EvalError> {
+    let error = |message: String| EvalError::new(EvalErrorKind::Other(message), node);
+    let items = match target.plain() {
+        Value::List(items) => items,
+        other => {
+            return Err(EvalError::new(
+                EvalErrorKind::Type(format!(
+                    "`{}# expects a list, got {}",
+                    name,
+                    other.type_name()
+                )),
+                node,
+            ));
+        }
+    };
+    if name.is_empty() {
+        return Ok(Value::List(items));
+    }
+    let attribute =
+        Attribute::parse(name).ok_or_else(|| error(format!("Unknown attribute `{}", name)))?;
+    let list = Attributed::new(attribute, items).map_err(error)?;
+    Ok(Value::Attributed(Arc::new(list)))
+}
+
+/// `attr x`: the attribute of the list `x`, or the empty symbol if it has
+/// none; lists with one answer through [`Attributed::shortcut`]
+pub fn attr(_: &mut Context, _: &[Value], _: Node) -> Result<Value, EvalError> {
+    Ok(Value::Symbol("".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[i64]) -> Column {
+        items.iter().map(|&n| Value::Integer(n)).collect()
+    }
+
+    #[test]
+    fn test_positions_use_the_attribute() {
+        let sorted = Attributed::new(Attribute::Sorted, list(&[1, 3, 3, 5])).unwrap();
+        assert_eq!(sorted.positions(&Value::Integer(3)), vec![1, 2]);
+        assert_eq!(sorted.find(&Value::Integer(4)), None);
+        let grouped = Attributed::new(Attribute::Grouped, list(&[2, 1, 2])).unwrap();
+        assert_eq!(grouped.positions(&Value::Integer(2)), vec![0, 2]);
+        assert_eq!(grouped.find(&Value::Integer(1)), Some(1));
+        assert!(Attributed::new(Attribute::Unique, list(&[2, 1, 2])).is_err());
+        assert!(Attributed::new(Attribute::Sorted, list(&[2, 1])).is_err());
+    }
+
+    #[test]
+    fn test_matching_conditions() {
+        let sorted = Attributed::new(Attribute::Sorted, list(&[1, 3, 3, 5, 8])).unwrap();
+        let grouped = Attributed::new(Attribute::Grouped, list(&[2, 1, 2, 4])).unwrap();
+        let pair = |lo, hi| Value::List(list(&[lo, hi]));
+        assert_eq!(sorted.matching("=", &Value::Integer(3)), Some(vec![1, 2]));
+        assert_eq!(sorted.matching("within", &pair(2, 5)), Some(vec![1, 2, 3]));
+        assert_eq!(grouped.matching("in", &pair(4, 2)), Some(vec![0, 2, 3]));
+        assert_eq!(grouped.matching("=", &Value::Integer(7)), Some(vec![]));
+        // Left to a scan
+        assert_eq!(grouped.matching("within", &pair(1, 2)), None);
+        assert_eq!(sorted.matching("=", &pair(1, 2)), None);
+        assert_eq!(sorted.matching("<", &Value::Integer(3)), None);
+    }
+}
@@ -0,0 +1,342 @@
+//! Arbitrary-precision integers for arithmetic that outgrows 64 bits
+//!
+//! Under the `bigint` overflow mode an integer operation whose result does
+//! not fit in 64 bits gives a [`BigInt`] instead of failing, and arithmetic
+//! with a big operand is exact. A result that fits back in 64 bits becomes an
+//! ordinary integer again, so a [`Value::BigInt`](crate::environment::Value)
+//! only ever holds a number beyond the range of `i64`, or one of its
+//! extremes, which as integers would read as `0N` or `0W`.
+//!
+//! Only what the evaluator needs is here: the four operations with
+//! truncating division, comparison, and decimal text in and out.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A quotient and its remainder
+pub type QuotientRemainder = (BigInt, BigInt);
+
+/// Limbs of a magnitude, least significant first
+type Limbs = Vec<u32>;
+
+/// A signed integer of any size
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    /// Magnitude in base 2^32, least significant limb first, with no
+    /// trailing zero limbs; zero has no limbs and is never negative
+    limbs: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        let negative = negative && !limbs.is_empty();
+        Self { negative, limbs }
+    }
+
+    /// Whether the number is below zero
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// The number as an `i64`, if it fits
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+        let magnitude = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &limb| (acc << 32) | u64::from(limb));
+        if self.negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    /// The nearest float to the number
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0.0, |acc, &limb| acc * 4294967296.0 + f64::from(limb));
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    /// The whole number `f` is, if it is finite and has no fraction
+    pub fn from_f64(f: f64) -> Option<Self> {
+        if !f.is_finite() || f.fract() != 0.0 {
+            return None;
+        }
+        let mut magnitude = f.abs();
+        let mut limbs = Vec::new();
+        while magnitude >= 1.0 {
+            limbs.push((magnitude % 4294967296.0) as u32);
+            magnitude = (magnitude / 4294967296.0).floor();
+        }
+        Some(Self::new(f < 0.0, limbs))
+    }
+
+    /// The number written in decimal digits, with an optional leading `-`
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut limbs = Vec::new();
+        for chunk in digits.as_bytes().chunks(9) {
+            let value: u32 = std::str::from_utf8(chunk).ok()?.parse().ok()?;
+            mul_small_add(&mut limbs, 10u32.pow(chunk.len() as u32), value);
+        }
+        Some(Self::new(negative, limbs))
+    }
+
+    /// `-self`
+    pub fn neg(&self) -> Self {
+        Self::new(!self.negative, self.limbs.clone())
+    }
+
+    /// `self + other`
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self::new(self.negative, add_magnitudes(&self.limbs, &other.limbs));
+        }
+        match compare_magnitudes(&self.limbs, &other.limbs) {
+            Ordering::Less => Self::new(other.negative, sub_magnitudes(&other.limbs, &self.limbs)),
+            _ => Self::new(self.negative, sub_magnitudes(&self.limbs, &other.limbs)),
+        }
+    }
+
+    /// `self - other`
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    /// `self * other`
+    pub fn mul(&self, other: &Self) -> Self {
+        let mut product = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let t = u64::from(a) * u64::from(b) + u64::from(product[i + j]) + carry;
+                product[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            product[i + other.limbs.len()] = carry as u32;
+        }
+        Self::new(self.negative != other.negative, product)
+    }
+
+    /// The quotient and remainder of `self / other`, truncating toward zero
+    /// as integer division does; `None` when `other` is zero
+    pub fn div_rem(&self, other: &Self) -> Option<QuotientRemainder> {
+        if other.limbs.is_empty() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitudes(&self.limbs, &other.limbs);
+        Some((
+            Self::new(self.negative != other.negative, quotient),
+            Self::new(self.negative, remainder),
+        ))
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> Self {
+        let magnitude = n.unsigned_abs();
+        Self::new(n < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.limbs, &other.limbs),
+            (true, true) => compare_magnitudes(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.limbs.is_empty() {
+            return write!(f, "0");
+        }
+        // Peel off nine decimal digits at a time, least significant first
+        let mut limbs = self.limbs.clone();
+        let mut chunks = Vec::new();
+        while !limbs.is_empty() {
+            chunks.push(div_small(&mut limbs, 1_000_000_000));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        if let Some(first) = chunks.next() {
+            write!(f, "{}", first)?;
+        }
+        chunks.try_for_each(|chunk| write!(f, "{:09}", chunk))
+    }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &limb) in long.iter().enumerate() {
+        let t = u64::from(limb) + u64::from(short.get(i).copied().unwrap_or(0)) + carry;
+        sum.push(t as u32);
+        carry = t >> 32;
+    }
+    sum.push(carry as u32);
+    sum
+}
+
+/// `a - b` for magnitudes with `a >= b`
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut borrow = 0i64;
+    a.iter()
+        .enumerate()
+        .map(|(i, &limb)| {
+            let mut t = i64::from(limb) - i64::from(b.get(i).copied().unwrap_or(0)) - borrow;
+            borrow = i64::from(t < 0);
+            if t < 0 {
+                t += 1 << 32;
+            }
+            t as u32
+        })
+        .collect()
+}
+
+/// `limbs * factor + addend`, in place
+fn mul_small_add(limbs: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = u64::from(addend);
+    for limb in limbs.iter_mut() {
+        let t = u64::from(*limb) * u64::from(factor) + carry;
+        *limb = t as u32;
+        carry = t >> 32;
+    }
+    if carry > 0 {
+        limbs.push(carry as u32);
+    }
+}
+
+/// Divide `limbs` by `divisor` in place, returning the remainder
+fn div_small(limbs: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in limbs.iter_mut().rev() {
+        let t = (remainder << 32) | u64::from(*limb);
+        *limb = (t / u64::from(divisor)) as u32;
+        remainder = t % u64::from(divisor);
+    }
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+    remainder as u32
+}
+
+/// Quotient and remainder of magnitudes, by binary long division
+fn div_rem_magnitudes(a: &[u32], b: &[u32]) -> (Limbs, Limbs) {
+    if let [divisor] = b {
+        let mut quotient = a.to_vec();
+        let remainder = div_small(&mut quotient, *divisor);
+        return (quotient, vec![remainder]);
+    }
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for bit in (0..a.len() * 32).rev() {
+        // remainder = remainder * 2 + the next bit of a
+        mul_small_add(&mut remainder, 2, (a[bit / 32] >> (bit % 32)) & 1);
+        if compare_magnitudes(&remainder, b) != Ordering::Less {
+            remainder = sub_magnitudes(&remainder, b);
+            while remainder.last() == Some(&0) {
+                remainder.pop();
+            }
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (quotient, remainder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(text: &str) -> BigInt {
+        BigInt::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_text() {
+        for text in [
+            "0",
+            "1",
+            "-1",
+            "4294967296",
+            "-123456789012345678901234567890",
+        ] {
+            assert_eq!(big(text).to_string(), text);
+        }
+        assert_eq!(big("-0").to_string(), "0");
+        assert_eq!(big("000123").to_string(), "123");
+        assert!(BigInt::parse("12a").is_none());
+        assert!(BigInt::parse("").is_none());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = big("9999999999999999");
+        let b = BigInt::from(999999);
+        assert_eq!(a.mul(&b).to_string(), "9999989999999999000001");
+        assert_eq!(a.add(&b.neg()).to_string(), "9999999999000000");
+        assert_eq!(b.sub(&a).to_string(), "-9999999999000000");
+        let (q, r) = a.mul(&b).add(&BigInt::from(5)).div_rem(&b).unwrap();
+        assert_eq!((q, r), (a.clone(), BigInt::from(5)));
+        // Division truncates toward zero, as for integers
+        let (q, r) = big("-100000000000000000000")
+            .div_rem(&big("30000000000000000000"))
+            .unwrap();
+        assert_eq!(
+            (q.to_string(), r.to_string()),
+            ("-3".into(), "-10000000000000000000".into())
+        );
+        assert!(a.div_rem(&BigInt::from(0)).is_none());
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(BigInt::from(i64::MAX).to_i64(), Some(i64::MAX));
+        assert_eq!(BigInt::from(i64::MAX).add(&BigInt::from(1)).to_i64(), None);
+        assert_eq!(big("18446744073709551616").to_f64(), 18446744073709551616.0);
+        assert_eq!(
+            BigInt::from_f64(1e20).unwrap().to_string(),
+            "100000000000000000000"
+        );
+        assert!(BigInt::from_f64(1.5).is_none());
+        assert!(big("-5") < big("3") && big("-5") < big("-4"));
+    }
+}
@@ -0,0 +1,127 @@
+//! Bit manipulation builtins: `and`, `or`, `xor`, `shl` and `shr`
+//!
+//! Integers are taken as 64-bit two's-complement patterns, so nulls and
+//! infinities are just bit patterns here: `0N` is the sign bit alone.
+//! Booleans combine to a boolean, and count as 0 or 1 against an integer.
+//! Like arithmetic, each applies item by item across lists.
+//!
+//! Integer literals may be written in hex or binary for this kind of work:
+//!
+//! ```text
+//! and[flags;0x04]      / test a flag
+//! or[0b1010;0b0101]    / 15
+//! shl[1;10]            / 1024
+//! ```
+
+use crate::builtins::Context;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use tree_sitter::Node;
+
+/// What a builtin does with one pair of atoms
+type AtomFn<'a> = &'a dyn Fn(&Value, &Value) -> Result<Value, EvalError>;
+
+/// Atoms matched up from two arguments
+type Pairs<'a> = Vec<(&'a Value, &'a Value)>;
+
+/// A bitwise operation on the bits of two integers
+type BitOp = fn(i64, i64) -> i64;
+
+/// A shift of an integer by a number of bits
+type ShiftOp = fn(i64, u32) -> i64;
+
+/// `f` applied to `left` and `right`, item by item across lists
+fn each(
+    name: &str,
+    left: &Value,
+    right: &Value,
+    node: Node,
+    f: AtomFn,
+) -> Result<Value, EvalError> {
+    let pairs: Pairs = match (left, right) {
+        (Value::List(l), Value::List(r)) if l.len() != r.len() => {
+            return Err(EvalError::new(
+                EvalErrorKind::LengthMismatch(format!("{} vs {} in {}", l.len(), r.len(), name)),
+                node,
+            ));
+        }
+        (Value::List(l), Value::List(r)) => l.iter().zip(r).collect(),
+        (Value::List(l), atom) => l.iter().map(|a| (a, atom)).collect(),
+        (atom, Value::List(r)) => r.iter().map(|b| (atom, b)).collect(),
+        (a, b) => return f(a, b),
+    };
+    pairs
+        .into_iter()
+        .map(|(a, b)| each(name, a, b, node, f))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Value::List)
+}
+
+/// The bits of an integer or boolean atom
+fn bits(value: &Value, name: &str, node: Node) -> Result<i64, EvalError> {
+    match value {
+        Value::Integer(n) => Ok(*n),
+        Value::Boolean(b) => Ok(*b as i64),
+        other => Err(EvalError::new(
+            EvalErrorKind::Type(format!(
+                "{} expects integers or booleans, got {}",
+                name,
+                other.type_name()
+            )),
+            node,
+        )),
+    }
+}
+
+/// A bitwise operation: booleans give a boolean, anything else an integer
+fn bitwise(name: &str, args: &[Value], node: Node, op: BitOp) -> Result<Value, EvalError> {
+    each(name, &args[0], &args[1], node, &|a, b| {
+        let result = op(bits(a, name, node)?, bits(b, name, node)?);
+        Ok(match (a, b) {
+            (Value::Boolean(_), Value::Boolean(_)) => Value::Boolean(result != 0),
+            _ => Value::Integer(result),
+        })
+    })
+}
+
+/// `and[x;y]`: the bits set in both `x` and `y`
+pub fn and(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    bitwise("and", args, node, |a, b| a & b)
+}
+
+/// `or[x;y]`: the bits set in either `x` or `y`
+pub fn or(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    bitwise("or", args, node, |a, b| a | b)
+}
+
+/// `xor[x;y]`: the bits set in exactly one of `x` and `y`
+pub fn xor(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    bitwise("xor", args, node, |a, b| a ^ b)
+}
+
+/// A shift of the integer `x` by `n` bits, which must be in `0..64`
+fn shift(name: &str, args: &[Value], node: Node, op: ShiftOp) -> Result<Value, EvalError> {
+    each(name, &args[0], &args[1], node, &|x, n| {
+        let x = bits(x, name, node)?;
+        let n = bits(n, name, node)?;
+        let n = u32::try_from(n).ok().filter(|n| *n < 64).ok_or_else(|| {
+            EvalError::new(
+                EvalErrorKind::Other(format!("{}: cannot shift by {} bits", name, n)),
+                node,
+            )
+        })?;
+        Ok(Value::Integer(op(x, n)))
+    })
+}
+
+/// `shl[x;n]`: the bits of `x` moved `n` places left, dropping those that
+/// leave the top
+pub fn shl(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    shift("shl", args, node, |x, n| x << n)
+}
+
+/// `shr[x;n]`: the bits of `x` moved `n` places right, copying the sign bit
+/// into the top so negative numbers stay negative
+pub fn shr(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    shift("shr", args, node, |x, n| x >> n)
+}
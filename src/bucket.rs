@@ -0,0 +1,99 @@
+//! Bucketing: `xbar`
+//!
+//! `x xbar y` rounds `y`, or each item of it, down to a multiple of `x`,
+//! which with `select ... by` makes bars from ticks:
+//!
+//! ```text
+//! 5 xbar 3 7 12 15                      / 0 5 10 15
+//! 0.25 xbar 1.3 1.6                     / 1.25 1.5
+//! 00:05 xbar (10:03;10:07)              / (10:00;10:05)
+//! select px: last px by 00:05 xbar time from trades
+//! ```
+//!
+//! An integer size counts the units of `y`: days of a date, milliseconds of
+//! a time, nanoseconds of a timestamp. A time size is a duration, so
+//! `00:05` buckets both times and timestamps by five minutes. Nulls and
+//! infinities stay as they are.
+
+use crate::builtins::Context;
+use crate::environment::{INFINITY_INTEGER, NULL_INTEGER, Value};
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::temporal::NANOS_PER_MILLI;
+use tree_sitter::Node;
+
+/// `n` rounded down to a multiple of `size`, which is positive; special
+/// integers stay
+fn floor(n: i64, size: i64, node: Node) -> Result<i64, EvalError> {
+    if n == NULL_INTEGER || n.abs() == INFINITY_INTEGER {
+        return Ok(n);
+    }
+    n.div_euclid(size)
+        .checked_mul(size)
+        .filter(|&floored| floored != NULL_INTEGER)
+        .ok_or_else(|| EvalError::new(EvalErrorKind::IntegerOverflow("xbar".into()), node))
+}
+
+/// `value` rounded down to a multiple of `size`
+fn bucket(size: &Value, value: &Value, node: Node) -> Result<Value, EvalError> {
+    let mismatch = || {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "xbar cannot bucket {} by {}",
+                value.type_name(),
+                size.type_name()
+            )),
+            node,
+        )
+    };
+    let narrow = |n: i64| i32::try_from(n).map_err(|_| mismatch());
+    match (size, value) {
+        (_, Value::List(items)) => items
+            .iter()
+            .map(|item| bucket(size, item, node))
+            .collect::<Result<_, _>>()
+            .map(Value::List),
+        (Value::Integer(s), Value::Integer(n)) => Ok(Value::Integer(floor(*n, *s, node)?)),
+        (Value::Integer(s), Value::Date(d)) => {
+            Ok(Value::Date(narrow(floor(i64::from(*d), *s, node)?)?))
+        }
+        (Value::Integer(s), Value::Time(t)) => {
+            Ok(Value::Time(narrow(floor(i64::from(*t), *s, node)?)?))
+        }
+        (Value::Time(s), Value::Time(t)) => Ok(Value::Time(narrow(floor(
+            i64::from(*t),
+            i64::from(*s),
+            node,
+        )?)?)),
+        (Value::Integer(s), Value::Timestamp(t)) => Ok(Value::Timestamp(floor(*t, *s, node)?)),
+        (Value::Time(s), Value::Timestamp(t)) => Ok(Value::Timestamp(floor(
+            *t,
+            i64::from(*s) * NANOS_PER_MILLI,
+            node,
+        )?)),
+        (Value::Integer(_) | Value::Float(_), Value::Integer(_) | Value::Float(_)) => {
+            let (s, n) = (size.as_f64(), value.as_f64());
+            match (s, n) {
+                (Some(s), Some(n)) => Ok(Value::Float((n / s).floor() * s)),
+                _ => Err(mismatch()),
+            }
+        }
+        _ => Err(mismatch()),
+    }
+}
+
+/// `x xbar y`: `y` rounded down to a multiple of the positive size `x`
+pub fn xbar(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let positive = match &args[0] {
+        Value::Integer(s) => *s > 0 && *s != INFINITY_INTEGER,
+        Value::Time(s) => *s > 0,
+        Value::Float(s) => *s > 0.0 && s.is_finite(),
+        _ => false,
+    };
+    if !positive {
+        return Err(EvalError::new(
+            EvalErrorKind::Type("xbar expects a positive size on the left".into()),
+            node,
+        ));
+    }
+    bucket(&args[0], &args[1], node)
+}
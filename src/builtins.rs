@@ -0,0 +1,1270 @@
+//! Built-in functions
+//!
+//! Builtins are resolved after user bindings, so a user definition with the
+//! same name shadows the builtin. They are called like any other function,
+//! either as `asc[x]` or by prefix application `asc x`.
+//!
+//! Builtins receive the evaluator's [`Context`] so that system builtins such
+//! as the `.db` namespace can reach state outside their arguments. Higher-order
+//! builtins such as `.ckpt.fold` receive an [`Apply`] instead, through which
+//! they can also call the functions they are given, or render them as text.
+
+use crate::aggregate;
+use crate::arithmetic::OverflowMode;
+use crate::attributes;
+use crate::bits;
+use crate::bucket;
+use crate::ckpt;
+use crate::db;
+use crate::diff::Diff;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::fill;
+use crate::interning::Symbol;
+use crate::lookup;
+use crate::matrix;
+use crate::memo::{self, MemoTable};
+use crate::parallel;
+use crate::random::Rng;
+use crate::reflect;
+use crate::script::{self, Script};
+use crate::strings;
+use crate::structural;
+use crate::table::Table;
+use crate::uniform;
+use crate::vectors;
+use crate::window;
+use lasso::Rodeo;
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::PathBuf;
+use tree_sitter::Node;
+
+// Type aliases for cleaner code
+type EvalListResult<'a> = Result<&'a [Value], EvalError>;
+type EvalGradeResult = Result<Vec<usize>, EvalError>;
+
+/// State shared by builtins across calls
+#[derive(Debug, Default)]
+pub struct Context {
+    /// Root directory the `.db` builtins create and open tables under
+    pub database: Option<PathBuf>,
+    /// Whether the database is open read-only, so the `.db` builtins never
+    /// write under it
+    pub read_only: bool,
+    /// Rows read from stored tables since the evaluator last reported them
+    pub rows_scanned: usize,
+    /// Generator behind `?`, reseeded by `seed`
+    pub rng: Rng,
+    /// What integer arithmetic does when a result overflows
+    pub overflow: OverflowMode,
+    /// Caches of the functions made by `memo`, indexed by
+    /// [`Value::Memo`]'s `cache`
+    pub memos: Vec<MemoTable>,
+    /// Length from which lists are split across threads, if parallel
+    /// execution is enabled; see [`crate::parallel`]
+    pub parallel: Option<usize>,
+}
+
+/// Signature of builtins that only need their arguments and the [`Context`]
+pub type BuiltinFn = fn(&mut Context, &[Value], Node) -> Result<Value, EvalError>;
+
+/// Signature of builtins that apply functions they are given
+pub type HigherOrderFn = fn(&mut dyn Apply, &[Value], Node) -> Result<Value, EvalError>;
+
+/// Access to the evaluator for higher-order builtins
+pub trait Apply {
+    /// Apply `function` to `args` as if called as `function[args]`
+    fn apply(&mut self, function: &Value, args: Vec<Value>, node: Node)
+    -> Result<Value, EvalError>;
+
+    /// State shared by builtins
+    fn context(&mut self) -> &mut Context;
+
+    /// Interner that the names and bodies of function values resolve in
+    fn interner(&self) -> &Rodeo;
+
+    /// Evaluate the source text `source` in the environment of the call,
+    /// with `bindings` defined on top of it
+    fn evaluate(
+        &mut self,
+        source: &str,
+        bindings: Bindings,
+        node: Node,
+    ) -> Result<Value, EvalError>;
+
+    /// Evaluate the statements of `script` in turn in the environment of
+    /// the call, their assignments binding there as at the top level
+    fn run(&mut self, script: &Script, node: Node) -> Result<Value, EvalError>;
+}
+
+/// Values bound to names, for [`Apply::evaluate`]
+pub type Bindings = Vec<(String, Value)>;
+
+/// How a builtin is implemented
+#[derive(Debug, Clone, Copy)]
+pub enum Implementation {
+    Plain(BuiltinFn),
+    HigherOrder(HigherOrderFn),
+}
+
+use Implementation::{HigherOrder, Plain};
+
+/// A use of a builtin, as source text, and the value it gives, formatted
+pub type Example = (&'static str, &'static str);
+
+/// A named function implemented in Rust
+#[derive(Debug)]
+pub struct Builtin {
+    /// Name the builtin is bound to
+    pub name: &'static str,
+    /// Number of arguments the builtin takes
+    pub arity: usize,
+    /// Names of the arguments, shown in the signature
+    pub params: &'static [&'static str],
+    /// What the builtin does, in a sentence
+    pub doc: &'static str,
+    /// Uses of the builtin, for `\h`
+    pub examples: &'static [Example],
+    /// Implementation
+    pub func: Implementation,
+}
+
+impl Builtin {
+    /// How the builtin is called, as `wsum[w;x]`
+    pub fn signature(&self) -> String {
+        format!("{}[{}]", self.name, self.params.join(";"))
+    }
+
+    /// The signature and description, then the examples, each with the
+    /// value it gives
+    pub fn manual(&self) -> String {
+        let mut text = format!("{}\n{}", self.signature(), self.doc);
+        if !self.examples.is_empty() {
+            text.push_str("\n\nExamples:");
+            for (source, value) in self.examples {
+                text.push_str(&format!("\n  {}\n  = {}", source, value));
+            }
+        }
+        text
+    }
+
+    /// Call the builtin, checking its arity first
+    pub fn call(
+        &self,
+        apply: &mut dyn Apply,
+        args: &[Value],
+        node: Node,
+    ) -> Result<Value, EvalError> {
+        if args.len() != self.arity {
+            return Err(EvalError::new(
+                EvalErrorKind::ArityMismatch(format!(
+                    "{} expects {} arguments, got {}",
+                    self.name,
+                    self.arity,
+                    args.len()
+                )),
+                node,
+            ));
+        }
+        // A list's attribute may give the result outright; otherwise
+        // builtins see plain lists, unless they exploit the attribute or
+        // read the vector
+        if let [Value::Attributed(list)] = args
+            && let Some(result) = list.shortcut(self.name)
+        {
+            return Ok(result);
+        }
+        let args: Vec<Value> = args
+            .iter()
+            .cloned()
+            .map(|arg| match arg {
+                Value::Attributed(_) if attributes::exploited_by(self.name) => arg,
+                Value::IntVector(_) | Value::FloatVector(_) if vectors::consumed_by(self.name) => {
+                    arg
+                }
+                other => other.plain(),
+            })
+            .collect();
+        match self.func {
+            Plain(func) => func(apply.context(), &args, node),
+            HigherOrder(func) => func(apply, &args, node),
+        }
+    }
+}
+
+/// Closure implementing a native function; errors are reported as messages
+pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync;
+
+/// Type name accepted by a parameter declared with any type
+pub const ANY_TYPE: &str = "any";
+
+/// Declared parameter of a native function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    /// Parameter name, shown in signatures
+    pub name: String,
+    /// Accepted type, as reported by `Value::type_name`, or [`ANY_TYPE`]
+    pub type_name: String,
+}
+
+/// A function implemented by an embedding application
+///
+/// Unlike [`Builtin`], natives are closures bound into an environment at run
+/// time, so they can capture application state. Parameters and a doc string
+/// may be declared for help and completion; declared parameter types are
+/// checked before the closure runs.
+pub struct NativeFunction {
+    /// Name the function was defined under
+    pub name: String,
+    /// Number of arguments the function takes
+    pub arity: usize,
+    /// Declared parameters; empty if only the arity is known
+    pub params: Vec<Param>,
+    /// Description shown by help
+    pub doc: Option<String>,
+    func: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    /// Wrap `func` as a native function of `arity` arguments
+    pub fn new<F>(name: impl Into<String>, arity: usize, func: F) -> Self
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            arity,
+            params: Vec::new(),
+            doc: None,
+            func: Box::new(func),
+        }
+    }
+
+    /// Declare the next parameter's name and accepted type
+    pub fn param(mut self, name: impl Into<String>, type_name: impl Into<String>) -> Self {
+        self.params.push(Param {
+            name: name.into(),
+            type_name: type_name.into(),
+        });
+        self
+    }
+
+    /// Attach a description
+    pub fn doc(mut self, doc: impl Into<String>) -> Self {
+        self.doc = Some(doc.into());
+        self
+    }
+
+    /// Call signature such as `vwap[px:list;size:list]`
+    pub fn signature(&self) -> String {
+        let params: Vec<String> = if self.params.is_empty() {
+            (1..=self.arity).map(|i| format!("x{}", i)).collect()
+        } else {
+            self.params
+                .iter()
+                .map(|p| format!("{}:{}", p.name, p.type_name))
+                .collect()
+        };
+        format!("{}[{}]", self.name, params.join(";"))
+    }
+
+    /// Call the function, checking its arity and declared types first
+    pub fn call(&self, args: &[Value], node: Node) -> Result<Value, EvalError> {
+        if args.len() != self.arity {
+            return Err(EvalError::new(
+                EvalErrorKind::ArityMismatch(format!(
+                    "{} expects {} arguments, got {}",
+                    self.name,
+                    self.arity,
+                    args.len()
+                )),
+                node,
+            ));
+        }
+        for (param, arg) in self.params.iter().zip(args) {
+            if param.type_name != ANY_TYPE && param.type_name != arg.type_name() {
+                return Err(EvalError::new(
+                    EvalErrorKind::Type(format!(
+                        "{}: {} expects {}, got {}",
+                        self.name,
+                        param.name,
+                        param.type_name,
+                        arg.type_name()
+                    )),
+                    node,
+                ));
+            }
+        }
+        let args: Vec<Value> = args.iter().cloned().map(Value::plain).collect();
+        (self.func)(&args).map_err(|message| EvalError::new(EvalErrorKind::Other(message), node))
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .field("params", &self.params)
+            .field("doc", &self.doc)
+            .finish_non_exhaustive()
+    }
+}
+
+static BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "asc",
+        arity: 1,
+        params: &["x"],
+        doc: "The items of the list `x` sorted ascending",
+        examples: &[("asc 3 1 2", "1 2 3")],
+        func: Plain(asc),
+    },
+    Builtin {
+        name: "desc",
+        arity: 1,
+        params: &["x"],
+        doc: "The items of the list `x` sorted descending",
+        examples: &[("desc 3 1 2", "3 2 1")],
+        func: Plain(desc),
+    },
+    Builtin {
+        name: "iasc",
+        arity: 1,
+        params: &["x"],
+        doc: "The indices that would sort the list `x` ascending",
+        examples: &[("iasc 30 10 20", "1 2 0")],
+        func: Plain(iasc),
+    },
+    Builtin {
+        name: "idesc",
+        arity: 1,
+        params: &["x"],
+        doc: "The indices that would sort the list `x` descending",
+        examples: &[("idesc 30 10 20", "0 2 1")],
+        func: Plain(idesc),
+    },
+    Builtin {
+        name: "where",
+        arity: 1,
+        params: &["x"],
+        doc: "The indices of the true items of a boolean list, or each index repeated as many times as an integer list says",
+        examples: &[("where 3 1 4>2", "0 2"), ("where 2 0 1", "0 0 2")],
+        func: Plain(where_),
+    },
+    Builtin {
+        name: "sum",
+        arity: 1,
+        params: &["x"],
+        doc: "The total of the numbers in `x`; an integer unless any is a float",
+        examples: &[("sum 1 2 3", "6"), ("sum 1 2.5", "3.5")],
+        func: Plain(aggregate::sum),
+    },
+    Builtin {
+        name: "count",
+        arity: 1,
+        params: &["x"],
+        doc: "The number of items in a list or dict, or rows in a table; an atom counts as one",
+        examples: &[("count 1 2 3", "3"), ("count 5", "1")],
+        func: Plain(aggregate::count),
+    },
+    Builtin {
+        name: "avg",
+        arity: 1,
+        params: &["x"],
+        doc: "The mean of the numbers in `x`, as a float",
+        examples: &[("avg 1 2 3 4", "2.5")],
+        func: Plain(aggregate::avg),
+    },
+    Builtin {
+        name: "wsum",
+        arity: 2,
+        params: &["w", "x"],
+        doc: "The sum of the items of `x` weighted by those of `w`",
+        examples: &[("wsum[1 2;3 4]", "11")],
+        func: Plain(aggregate::wsum),
+    },
+    Builtin {
+        name: "wavg",
+        arity: 2,
+        params: &["w", "x"],
+        doc: "The average of the items of `x` weighted by those of `w`",
+        examples: &[("wavg[1 3;10 20]", "17.5")],
+        func: Plain(aggregate::wavg),
+    },
+    Builtin {
+        name: "var",
+        arity: 1,
+        params: &["x"],
+        doc: "The variance of the numbers in `x`",
+        examples: &[("var 2 4 4 4 5 5 7 9", "4f")],
+        func: Plain(aggregate::var),
+    },
+    Builtin {
+        name: "dev",
+        arity: 1,
+        params: &["x"],
+        doc: "The standard deviation of the numbers in `x`",
+        examples: &[("dev 2 4 4 4 5 5 7 9", "2f")],
+        func: Plain(aggregate::dev),
+    },
+    Builtin {
+        name: "cov",
+        arity: 2,
+        params: &["x", "y"],
+        doc: "The covariance of `x` and `y`",
+        examples: &[("cov[1 2 3;2 4 6]", "1.3333333333333333")],
+        func: Plain(aggregate::cov),
+    },
+    Builtin {
+        name: "cor",
+        arity: 2,
+        params: &["x", "y"],
+        doc: "The correlation of `x` and `y`",
+        examples: &[("cor[1 2 3;2 4 6]", "1f")],
+        func: Plain(aggregate::cor),
+    },
+    Builtin {
+        name: "min",
+        arity: 1,
+        params: &["x"],
+        doc: "The least item of `x`",
+        examples: &[("min 3 1 2", "1")],
+        func: Plain(aggregate::min),
+    },
+    Builtin {
+        name: "max",
+        arity: 1,
+        params: &["x"],
+        doc: "The greatest item of `x`",
+        examples: &[("max 3 1 2", "3")],
+        func: Plain(aggregate::max),
+    },
+    Builtin {
+        name: "enlist",
+        arity: 1,
+        params: &["x"],
+        doc: "The list of `x` alone; a dict becomes a one-row table",
+        examples: &[("enlist 5", ",5")],
+        func: Plain(structural::enlist),
+    },
+    Builtin {
+        name: "sums",
+        arity: 1,
+        params: &["x"],
+        doc: "The running totals of `x`",
+        examples: &[("sums 1 2 3 4", "1 3 6 10")],
+        func: Plain(uniform::sums),
+    },
+    Builtin {
+        name: "prds",
+        arity: 1,
+        params: &["x"],
+        doc: "The running products of `x`",
+        examples: &[("prds 1 2 3 4", "1 2 6 24")],
+        func: Plain(uniform::prds),
+    },
+    Builtin {
+        name: "maxs",
+        arity: 1,
+        params: &["x"],
+        doc: "The greatest item of `x` so far at each item",
+        examples: &[("maxs 1 3 2 5", "1 3 3 5")],
+        func: Plain(uniform::maxs),
+    },
+    Builtin {
+        name: "mins",
+        arity: 1,
+        params: &["x"],
+        doc: "The least item of `x` so far at each item",
+        examples: &[("mins 3 1 2 0", "3 1 1 0")],
+        func: Plain(uniform::mins),
+    },
+    Builtin {
+        name: "deltas",
+        arity: 1,
+        params: &["x"],
+        doc: "The first item of `x`, then the difference of each item from the one before it",
+        examples: &[("deltas 1 4 9 16", "1 3 5 7")],
+        func: Plain(uniform::deltas),
+    },
+    Builtin {
+        name: "msum",
+        arity: 2,
+        params: &["n", "x"],
+        doc: "The sum of each item of `x` and the `n-1` before it",
+        examples: &[("msum[2;1 2 3 4]", "1 3 5 7")],
+        func: Plain(window::msum),
+    },
+    Builtin {
+        name: "mavg",
+        arity: 2,
+        params: &["n", "x"],
+        doc: "The average of each item of `x` and the `n-1` before it",
+        examples: &[("mavg[2;1 2 3 4]", "1 1.5 2.5 3.5")],
+        func: Plain(window::mavg),
+    },
+    Builtin {
+        name: "mmax",
+        arity: 2,
+        params: &["n", "x"],
+        doc: "The greatest of each item of `x` and the `n-1` before it",
+        examples: &[("mmax[2;1 3 2 0]", "1 3 3 2")],
+        func: Plain(window::mmax),
+    },
+    Builtin {
+        name: "mmin",
+        arity: 2,
+        params: &["n", "x"],
+        doc: "The least of each item of `x` and the `n-1` before it",
+        examples: &[("mmin[2;1 3 2 0]", "1 1 2 0")],
+        func: Plain(window::mmin),
+    },
+    Builtin {
+        name: "mdev",
+        arity: 2,
+        params: &["n", "x"],
+        doc: "The standard deviation of each item of `x` and the `n-1` before it",
+        examples: &[("mdev[2;1 3 5]", "0 1 1f")],
+        func: Plain(window::mdev),
+    },
+    Builtin {
+        name: "fills",
+        arity: 1,
+        params: &["x"],
+        doc: "`x` with each null replaced by the last value before it that is not null",
+        examples: &[("fills 1 0N 0N 4", "1 1 1 4")],
+        func: Plain(fill::fills),
+    },
+    Builtin {
+        name: "fill",
+        arity: 2,
+        params: &["x", "y"],
+        doc: "`y` with its nulls replaced by `x`",
+        examples: &[("fill[0;1 0N 3]", "1 0 3")],
+        func: Plain(fill::fill),
+    },
+    Builtin {
+        name: "ratios",
+        arity: 1,
+        params: &["x"],
+        doc: "The first item of `x`, then the ratio of each item to the one before it, as floats",
+        examples: &[("ratios 1 2 6", "1 2 3f")],
+        func: Plain(uniform::ratios),
+    },
+    Builtin {
+        name: "flip",
+        arity: 1,
+        params: &["x"],
+        doc: "The table whose columns are the dict `x`, the dict of a table's columns, or the transpose of a list of lists",
+        examples: &[("flip (1 2;3 4)", "(1 3;2 4)")],
+        func: Plain(structural::flip),
+    },
+    Builtin {
+        name: "mmu",
+        arity: 2,
+        params: &["x", "y"],
+        doc: "The matrix product of `x` and `y`; two vectors give their dot product",
+        examples: &[("mmu[1 2 3;4 5 6]", "32f")],
+        func: Plain(matrix::mmu),
+    },
+    Builtin {
+        name: "inv",
+        arity: 1,
+        params: &["x"],
+        doc: "The inverse of the square matrix `x`",
+        examples: &[("inv (2 0;0 4)", "(0.5 0;0 0.25)")],
+        func: Plain(matrix::inv),
+    },
+    Builtin {
+        name: "transpose",
+        arity: 1,
+        params: &["x"],
+        doc: "The rows of the matrix `x` as columns",
+        examples: &[("transpose (1 2;3 4)", "(1 3;2 4)")],
+        func: Plain(matrix::transpose),
+    },
+    Builtin {
+        name: "raze",
+        arity: 1,
+        params: &["x"],
+        doc: "The items of `x` joined one level down, so a list of lists becomes one list",
+        examples: &[("raze (1 2;3;4 5)", "1 2 3 4 5")],
+        func: Plain(structural::raze),
+    },
+    Builtin {
+        name: "reverse",
+        arity: 1,
+        params: &["x"],
+        doc: "The items of a list, entries of a dict or rows of a table in reverse order",
+        examples: &[("reverse 1 2 3", "3 2 1")],
+        func: Plain(structural::reverse),
+    },
+    Builtin {
+        name: "xbar",
+        arity: 2,
+        params: &["x", "y"],
+        doc: "`y` rounded down to a multiple of the positive size `x`",
+        examples: &[("5 xbar 3 7 12", "0 5 10")],
+        func: Plain(bucket::xbar),
+    },
+    Builtin {
+        name: "in",
+        arity: 2,
+        params: &["x", "ys"],
+        doc: "Whether `x`, or each item of it, is an item of `ys`",
+        examples: &[("2 in 1 2 3", "1b"), ("1 4 in 1 2 3", "10b")],
+        func: Plain(lookup::member),
+    },
+    Builtin {
+        name: "within",
+        arity: 2,
+        params: &["x", "range"],
+        doc: "Whether `x`, or each item of it, is at least the first item of `range` and at most the second",
+        examples: &[("1 5 9 within 2 8", "010b")],
+        func: Plain(lookup::within),
+    },
+    Builtin {
+        name: "find",
+        arity: 2,
+        params: &["ys", "x"],
+        doc: "The first position of `x`, or of each item of it, in `ys`; the count of `ys` where it does not occur",
+        examples: &[("find[10 20 30;20]", "1"), ("10 20 30?40", "3")],
+        func: Plain(lookup::find),
+    },
+    Builtin {
+        name: "distinct",
+        arity: 1,
+        params: &["x"],
+        doc: "The items of a list, or rows of a table, without repeats, in order of first appearance",
+        examples: &[("distinct 1 2 1 3 2", "1 2 3")],
+        func: Plain(structural::distinct),
+    },
+    Builtin {
+        name: "group",
+        arity: 1,
+        params: &["x"],
+        doc: "A dict from each distinct item of a list to the indices where it occurs",
+        examples: &[("group `a`b`a", "`a`b!(0 2;,1)")],
+        func: Plain(structural::group),
+    },
+    Builtin {
+        name: "first",
+        arity: 1,
+        params: &["x"],
+        doc: "The first item of a list, value of a dict or row of a table",
+        examples: &[("first 5 6 7", "5")],
+        func: Plain(structural::first),
+    },
+    Builtin {
+        name: "last",
+        arity: 1,
+        params: &["x"],
+        doc: "The last item of a list, value of a dict or row of a table",
+        examples: &[("last 5 6 7", "7")],
+        func: Plain(structural::last),
+    },
+    Builtin {
+        name: "xkey",
+        arity: 2,
+        params: &["columns", "t"],
+        doc: "`t` keyed by the named columns, moved to the front; `()` unkeys it",
+        examples: &[("count xkey[`a;flip `a`b!(1 2;3 4)]", "2")],
+        func: Plain(xkey),
+    },
+    Builtin {
+        name: "upsert",
+        arity: 2,
+        params: &["t", "rows"],
+        doc: "`t` with `rows`, a table or a dict for one row, written in; in a keyed table, rows with a present key replace it",
+        examples: &[("count upsert[flip `a`b!(1 2;3 4);`a`b!(5;6)]", "3")],
+        func: Plain(upsert),
+    },
+    Builtin {
+        name: "lj",
+        arity: 2,
+        params: &["t", "kt"],
+        doc: "Left join: each row of `t` with the values of the row of the keyed table `kt` whose key it holds",
+        examples: &[],
+        func: Plain(lj),
+    },
+    Builtin {
+        name: "ij",
+        arity: 2,
+        params: &["t", "kt"],
+        doc: "Inner join: like `lj`, but only the rows of `t` with a match",
+        examples: &[],
+        func: Plain(ij),
+    },
+    Builtin {
+        name: "uj",
+        arity: 2,
+        params: &["t1", "t2"],
+        doc: "Union join: the rows of both tables over all their columns",
+        examples: &[],
+        func: Plain(uj),
+    },
+    Builtin {
+        name: "aj",
+        arity: 3,
+        params: &["columns", "t", "q"],
+        doc: "As-of join: each row of `t` with the values of the last row of `q` with the same first column at or before its second",
+        examples: &[],
+        func: Plain(aj),
+    },
+    Builtin {
+        name: "and",
+        arity: 2,
+        params: &["x", "y"],
+        doc: "The bits set in both `x` and `y`",
+        examples: &[("and[12;10]", "8")],
+        func: Plain(bits::and),
+    },
+    Builtin {
+        name: "or",
+        arity: 2,
+        params: &["x", "y"],
+        doc: "The bits set in either `x` or `y`",
+        examples: &[("or[12;10]", "14")],
+        func: Plain(bits::or),
+    },
+    Builtin {
+        name: "xor",
+        arity: 2,
+        params: &["x", "y"],
+        doc: "The bits set in exactly one of `x` and `y`",
+        examples: &[("xor[12;10]", "6")],
+        func: Plain(bits::xor),
+    },
+    Builtin {
+        name: "shl",
+        arity: 2,
+        params: &["x", "n"],
+        doc: "The bits of `x` moved `n` places left",
+        examples: &[("shl[1;4]", "16")],
+        func: Plain(bits::shl),
+    },
+    Builtin {
+        name: "shr",
+        arity: 2,
+        params: &["x", "n"],
+        doc: "The bits of `x` moved `n` places right, keeping the sign",
+        examples: &[("shr[-16;2]", "-4")],
+        func: Plain(bits::shr),
+    },
+    Builtin {
+        name: "seed",
+        arity: 1,
+        params: &["n"],
+        doc: "Restart the numbers `?` rolls from seed `n`, so the rolls that follow repeat on every run",
+        examples: &[],
+        func: Plain(seed),
+    },
+    Builtin {
+        name: "lower",
+        arity: 1,
+        params: &["x"],
+        doc: "`x` with its characters and symbols in lower case",
+        examples: &[(r#"lower "Hello""#, r#""hello""#)],
+        func: Plain(strings::lower),
+    },
+    Builtin {
+        name: "upper",
+        arity: 1,
+        params: &["x"],
+        doc: "`x` with its characters and symbols in upper case",
+        examples: &[("upper `abc", "`ABC")],
+        func: Plain(strings::upper),
+    },
+    Builtin {
+        name: "trim",
+        arity: 1,
+        params: &["x"],
+        doc: "The string `x` without leading or trailing whitespace",
+        examples: &[(r#"trim "  hi  ""#, r#""hi""#)],
+        func: Plain(strings::trim),
+    },
+    Builtin {
+        name: "ltrim",
+        arity: 1,
+        params: &["x"],
+        doc: "The string `x` without leading whitespace",
+        examples: &[(r#"ltrim "  hi""#, r#""hi""#)],
+        func: Plain(strings::ltrim),
+    },
+    Builtin {
+        name: "rtrim",
+        arity: 1,
+        params: &["x"],
+        doc: "The string `x` without trailing whitespace",
+        examples: &[(r#"rtrim "hi  ""#, r#""hi""#)],
+        func: Plain(strings::rtrim),
+    },
+    Builtin {
+        name: "ss",
+        arity: 2,
+        params: &["s", "p"],
+        doc: "The positions where `p` occurs in the string `s`",
+        examples: &[(r#"ss["banana";"an"]"#, "1 3")],
+        func: Plain(strings::ss),
+    },
+    Builtin {
+        name: "ssr",
+        arity: 3,
+        params: &["s", "p", "r"],
+        doc: "The string `s` with each occurrence of `p` replaced by `r`",
+        examples: &[(r#"ssr["banana";"an";"AN"]"#, r#""bANANa""#)],
+        func: Plain(strings::ssr),
+    },
+    Builtin {
+        name: "vs",
+        arity: 2,
+        params: &["sep", "s"],
+        doc: "The string `s` split into the strings between each `sep`",
+        examples: &[(r#"vs[",";"a,bc,d"]"#, r#"(,"a";"bc";,"d")"#)],
+        func: Plain(strings::vs),
+    },
+    Builtin {
+        name: "sv",
+        arity: 2,
+        params: &["sep", "x"],
+        doc: "The strings of the list `x` joined into one, with `sep` between each",
+        examples: &[(r#"sv["-";("ab";"cd")]"#, r#""ab-cd""#)],
+        func: Plain(strings::sv),
+    },
+    Builtin {
+        name: "string",
+        arity: 1,
+        params: &["x"],
+        doc: "The text of an atom as a string, or a string for each item of a list",
+        examples: &[("string 42", r#""42""#)],
+        func: HigherOrder(strings::string),
+    },
+    Builtin {
+        name: "format",
+        arity: 2,
+        params: &["template", "x"],
+        doc: "`template` with each `{}` replaced by the text of `x`, or of the items of the list `x` in turn",
+        examples: &[(r#"format["{} + {}";1 2]"#, r#""1 + 2""#)],
+        func: HigherOrder(strings::format),
+    },
+    Builtin {
+        name: "diff",
+        arity: 2,
+        params: &["a", "b"],
+        doc: "The changes that turn `a` into `b`, as a list of dicts",
+        examples: &[("count diff[1 2 3;1 5 3]", "1")],
+        func: Plain(diff),
+    },
+    Builtin {
+        name: "type",
+        arity: 1,
+        params: &["x"],
+        doc: "The name of the type of `x`, as a symbol",
+        examples: &[("type 1", "`integer"), ("type `a", "`symbol")],
+        func: Plain(type_of),
+    },
+    Builtin {
+        name: "attr",
+        arity: 1,
+        params: &["x"],
+        doc: "The attribute of the list `x`, or the empty symbol if it has none",
+        examples: &[("attr 1 2 3", "`")],
+        func: Plain(attributes::attr),
+    },
+    Builtin {
+        name: "memo",
+        arity: 1,
+        params: &["f"],
+        doc: "A function that calls `f` once for each distinct argument list and remembers the results",
+        examples: &[("sq: memo {[x] x*x}; sq[4]", "16")],
+        func: Plain(memo::memo),
+    },
+    Builtin {
+        name: "parse",
+        arity: 1,
+        params: &["s"],
+        doc: "The parse tree of the source text `s`",
+        examples: &[(r#"parse "1+x""#, "(`+;1;`x)")],
+        func: HigherOrder(reflect::parse),
+    },
+    Builtin {
+        name: "eval",
+        arity: 1,
+        params: &["t"],
+        doc: "The value of the parse tree `t`, evaluated where `eval` is called",
+        examples: &[(r#"eval parse "1+2""#, "3")],
+        func: HigherOrder(reflect::eval),
+    },
+    Builtin {
+        name: "load",
+        arity: 1,
+        params: &["path"],
+        doc: "Evaluate the script at `path` statement by statement, giving the value of its last statement",
+        examples: &[],
+        func: HigherOrder(script::load),
+    },
+    Builtin {
+        name: "help",
+        arity: 1,
+        params: &["f"],
+        doc: "The documentation of the function `f` as a string, or how to call it if it has none",
+        examples: &[],
+        func: HigherOrder(help),
+    },
+    Builtin {
+        name: "error",
+        arity: 1,
+        params: &["msg"],
+        doc: "Raise the symbol or string `msg` as an error, like `'msg`",
+        examples: &[],
+        func: Plain(error),
+    },
+    Builtin {
+        name: ".ckpt.fold",
+        arity: 5,
+        params: &["name", "every", "f", "init", "xs"],
+        doc: "Fold `f` over `xs` from `init`, checkpointing every `every` items under `name` so an interrupted fold resumes",
+        examples: &[],
+        func: HigherOrder(ckpt::fold),
+    },
+    Builtin {
+        name: ".db.create",
+        arity: 2,
+        params: &["name", "schema"],
+        doc: "Create an empty table in the database, returning its name",
+        examples: &[],
+        func: Plain(db::create),
+    },
+    Builtin {
+        name: ".db.tables",
+        arity: 0,
+        params: &[],
+        doc: "The names of the tables in the database, sorted",
+        examples: &[],
+        func: Plain(db::tables),
+    },
+    Builtin {
+        name: ".db.stats",
+        arity: 1,
+        params: &["name"],
+        doc: "A table of the reads of each column of the table `name`, most read first",
+        examples: &[],
+        func: Plain(db::stats),
+    },
+];
+
+/// Look up a builtin by name
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+/// Names of all builtins
+pub fn names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|b| b.name)
+}
+
+/// All builtins, in the order they are registered
+pub fn all() -> &'static [Builtin] {
+    BUILTINS
+}
+
+fn expect_list<'a>(value: &'a Value, name: &str, node: Node) -> EvalListResult<'a> {
+    value.as_list().ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "{} expects a list, got {}",
+                name,
+                value.type_name()
+            )),
+            node,
+        )
+    })
+}
+
+/// Stable grade of `items`: the indices that would sort them
+fn grade(items: &[Value], descending: bool, parallel: bool, node: Node) -> EvalGradeResult {
+    // Reject incomparable items up front so the sort itself cannot fail
+    if let Some(first) = items.first() {
+        for item in items {
+            if first.compare(item).is_none() {
+                return Err(EvalError::new(
+                    EvalErrorKind::Type(format!(
+                        "cannot sort {} with {}",
+                        first.type_name(),
+                        item.type_name()
+                    )),
+                    node,
+                ));
+            }
+        }
+    }
+
+    let mut indices: Vec<usize> = (0..items.len()).collect();
+    let order = |&a: &usize, &b: &usize| {
+        let ordering = items[a].compare(&items[b]).unwrap_or(Ordering::Equal);
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    };
+    if parallel {
+        parallel::sort_by(&mut indices, order);
+    } else {
+        indices.sort_by(order);
+    }
+    Ok(indices)
+}
+
+fn sorted(
+    context: &Context,
+    args: &[Value],
+    name: &str,
+    descending: bool,
+    node: Node,
+) -> Result<Value, EvalError> {
+    let items = expect_list(&args[0], name, node)?;
+    let parallel = parallel::splits(context.parallel, items.len());
+    let indices = grade(items, descending, parallel, node)?;
+    Ok(Value::List(
+        indices.into_iter().map(|i| items[i].clone()).collect(),
+    ))
+}
+
+fn graded(
+    context: &Context,
+    args: &[Value],
+    name: &str,
+    descending: bool,
+    node: Node,
+) -> Result<Value, EvalError> {
+    let items = expect_list(&args[0], name, node)?;
+    let parallel = parallel::splits(context.parallel, items.len());
+    let indices = grade(items, descending, parallel, node)?;
+    Ok(Value::List(
+        indices
+            .into_iter()
+            .map(|i| Value::Integer(i as i64))
+            .collect(),
+    ))
+}
+
+/// `seed n`: restart the numbers `?` rolls from seed `n`, so the rolls that
+/// follow repeat on every run
+fn seed(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let seed = args[0].as_integer().ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "seed expects an integer, got {}",
+                args[0].type_name()
+            )),
+            node,
+        )
+    })?;
+    context.rng = Rng::new(seed as u64);
+    Ok(args[0].clone())
+}
+
+/// `` xkey[`sym;t] ``: `t` keyed by the named columns, moved to the front;
+/// `xkey[();t]` unkeys it
+fn xkey(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let names = column_names(&args[0], "xkey", node)?;
+    let table = expect_table(&args[1], "xkey", node)?;
+    table
+        .xkey(&names)
+        .map(Value::Table)
+        .map_err(|message| EvalError::new(EvalErrorKind::Other(message), node))
+}
+
+/// `upsert[t;rows]`: `t` with `rows`, a table or a dict for one row, written
+/// in; in a keyed table, rows whose key is present replace that row's values
+fn upsert(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let table = expect_table(&args[0], "upsert", node)?;
+    let rows = match &args[1] {
+        Value::Table(rows) => rows.unkeyed(),
+        Value::Dict { keys, values } => {
+            let values: Vec<Value> = values
+                .iter()
+                .map(|v| Value::List(vec![v.clone()]))
+                .collect();
+            Table::from_dict(keys, &values)
+                .map_err(|message| EvalError::new(EvalErrorKind::Type(message), node))?
+        }
+        other => {
+            return Err(EvalError::new(
+                EvalErrorKind::Type(format!(
+                    "upsert expects a table or dict of rows, got {}",
+                    other.type_name()
+                )),
+                node,
+            ));
+        }
+    };
+    table
+        .upsert(&rows)
+        .map(Value::Table)
+        .map_err(|message| EvalError::new(EvalErrorKind::Other(message), node))
+}
+
+/// `lj[t;kt]`: left join, each row of `t` with the values of the row of the
+/// keyed table `kt` whose key it holds
+fn lj(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    join(args, "lj", Table::left_join, node)
+}
+
+/// `ij[t;kt]`: inner join, like `lj` but only rows of `t` with a match
+fn ij(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    join(args, "ij", Table::inner_join, node)
+}
+
+/// `uj[t1;t2]`: union join, the rows of both tables over all their columns
+fn uj(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    join(args, "uj", Table::union_join, node)
+}
+
+/// `` aj[`sym`time;t;q] ``: as-of join, each row of `t` with the values of
+/// the last row of `q` with the same `sym` at or before its `time`
+fn aj(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let columns = column_names(&args[0], "aj", node)?;
+    let left = expect_table(&args[1], "aj", node)?;
+    let right = expect_table(&args[2], "aj", node)?;
+    left.as_of_join(&columns, right)
+        .map(Value::Table)
+        .map_err(|message| EvalError::new(EvalErrorKind::Other(message), node))
+}
+
+/// A join of two tables, such as [`Table::left_join`]
+type JoinFn = fn(&Table, &Table) -> Result<Table, String>;
+
+fn join(args: &[Value], name: &str, join: JoinFn, node: Node) -> Result<Value, EvalError> {
+    let left = expect_table(&args[0], name, node)?;
+    let right = expect_table(&args[1], name, node)?;
+    join(left, right)
+        .map(Value::Table)
+        .map_err(|message| EvalError::new(EvalErrorKind::Other(message), node))
+}
+
+/// Column names given as a symbol or a list of symbols
+type ColumnNames = Result<Vec<Symbol>, EvalError>;
+
+fn column_names(value: &Value, name: &str, node: Node) -> ColumnNames {
+    let symbol = |value: &Value| match value {
+        Value::Symbol(name) => Some(*name),
+        _ => None,
+    };
+    match value {
+        Value::List(items) => items.iter().map(symbol).collect(),
+        atom => symbol(atom).map(|name| vec![name]),
+    }
+    .ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "{} expects column names, got {}",
+                name,
+                value.type_name()
+            )),
+            node,
+        )
+    })
+}
+
+fn expect_table<'a>(value: &'a Value, name: &str, node: Node) -> Result<&'a Table, EvalError> {
+    match value {
+        Value::Table(table) => Ok(table),
+        other => Err(EvalError::new(
+            EvalErrorKind::Type(format!(
+                "{} expects a table, got {}",
+                name,
+                other.type_name()
+            )),
+            node,
+        )),
+    }
+}
+
+/// `diff[a;b]`: the changes that turn `a` into `b`, as a list of dicts
+fn diff(_: &mut Context, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    Ok(Diff::between(&args[0], &args[1]).to_value())
+}
+
+/// `type x`: the name of `x`'s type as a symbol, e.g. `` `integer ``
+fn type_of(_: &mut Context, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    Ok(Value::Symbol(args[0].type_name().into()))
+}
+
+/// `help f`: the docstring of the function `f` as a string, or how to call
+/// it if it has none
+fn help(apply: &mut dyn Apply, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let text = args[0].help(apply.interner()).ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "help expects a function, got {}",
+                args[0].type_name()
+            )),
+            node,
+        )
+    })?;
+    Ok(Value::string(&text))
+}
+
+/// `error[`msg]` or `error["msg"]`: raise `msg` as an error, like `'`msg`
+fn error(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let message = match &args[0] {
+        Value::Symbol(name) => name.to_string(),
+        Value::Char(c) => c.to_string(),
+        other => other.as_string().ok_or_else(|| {
+            EvalError::new(
+                EvalErrorKind::Type(format!(
+                    "error expects a symbol or string message, got {}",
+                    other.type_name()
+                )),
+                node,
+            )
+        })?,
+    };
+    Err(EvalError::new(EvalErrorKind::Signal(message), node))
+}
+
+fn asc(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    sorted(context, args, "asc", false, node)
+}
+
+fn desc(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    sorted(context, args, "desc", true, node)
+}
+
+fn iasc(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    graded(context, args, "iasc", false, node)
+}
+
+fn idesc(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    graded(context, args, "idesc", true, node)
+}
+
+/// `where`: indices of the true items of a boolean list
+///
+/// Given integer counts instead, each index is repeated that many times, so
+/// `where 2 0 1` is `0 0 2`.
+fn where_(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let items = expect_list(&args[0], "where", node)?;
+    let mut indices = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let repeat = match item {
+            Value::Boolean(b) => usize::from(*b),
+            Value::Integer(n) if *n >= 0 => *n as usize,
+            other => {
+                return Err(EvalError::new(
+                    EvalErrorKind::Type(format!(
+                        "where expects booleans or non-negative counts, got {}",
+                        other.type_name()
+                    )),
+                    node,
+                ));
+            }
+        };
+        indices.extend(std::iter::repeat_n(Value::Integer(i as i64), repeat));
+    }
+    Ok(Value::List(indices))
+}
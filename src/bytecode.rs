@@ -0,0 +1,643 @@
+//! Bytecode for function bodies
+//!
+//! A lambda's body is parsed and compiled once, when the lambda is
+//! evaluated, rather than on every call. Calls run the compiled [`Code`] on
+//! a stack machine in the evaluator:
+//!
+//! ```text
+//! {[x] y: x*2; $[y>10; y; f[y]]}
+//!
+//!  0 statement            6 statement         12 jump 16
+//!  1 load x               7 const 10          13 load f
+//!  2 const 2              8 load y            14 load y
+//!  3 arithmetic *         9 compare >         15 call 1
+//!  4 store y             10 jump unless 13
+//!  5 pop                 11 load y
+//! ```
+//!
+//! Names, operators, calls, conditionals and literals compile to ops.
+//! Arithmetic, comparisons and negations of constants are folded into
+//! constants as they compile, so `2*3+x` runs as `6+x`; one that fails, as
+//! `1+`a` does, is left to fail when it runs.
+//! Everything else, from queries to loops, is left to the tree-walking
+//! evaluator: an [`Op::Eval`] evaluates its node of the parse tree the code
+//! keeps. Ops refer to nodes by their index in that tree, so errors point
+//! into the body as they do when it is walked.
+//!
+//! The evaluator keeps the code of recently compiled bodies in a
+//! [`CodeCache`], so a lambda evaluated again, or a function made without
+//! code, such as one loaded from a checkpoint, is not parsed again.
+
+use crate::arithmetic::{self, OverflowMode};
+use crate::environment::Value;
+use crate::errors::EvalError;
+use crate::interning::InternedString;
+use crate::operators;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tree_sitter::{Node, Tree};
+
+/// One step of compiled code; `node` fields index nodes of the parse tree,
+/// for errors and for the evaluator
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    /// Start a statement, for tracing
+    Statement(usize),
+    /// Push a constant
+    Const(usize),
+    /// Push the value of the identifier at `node`
+    Load { name: String, node: usize },
+    /// Bind the value on top of the stack to `name`, leaving it there; `::`
+    /// sets a global
+    Store { name: String, global: bool },
+    /// Replace the left and right operands on top of the stack with the
+    /// arithmetic operator applied to them
+    Arithmetic {
+        op: String,
+        node: usize,
+        operator: usize,
+    },
+    /// Replace the right and left operands on top of the stack with their
+    /// comparison, as comparisons evaluate right to left
+    Compare { op: String, node: usize },
+    /// Negate the value on top of the stack
+    Negate { node: usize },
+    /// Apply the function under `argc` arguments to them
+    Call {
+        argc: usize,
+        node: usize,
+        function: usize,
+    },
+    /// Pop the top of the stack and jump to `target` unless it is true
+    JumpUnless { target: usize, node: usize },
+    /// Jump to `target`
+    Jump(usize),
+    /// Drop the top of the stack
+    Pop,
+    /// Push the value of the node, walked by the evaluator
+    Eval(usize),
+}
+
+/// A compiled function body
+pub struct Code {
+    /// Text of the body, which the tree's nodes index
+    pub source: String,
+    /// Parse tree of the body
+    pub tree: Tree,
+    pub ops: Vec<Op>,
+    pub constants: Vec<Value>,
+}
+
+impl fmt::Debug for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Code")
+            .field("source", &self.source)
+            .field("ops", &self.ops)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Code {
+    /// The node of the tree at `index`
+    pub fn node(&self, index: usize) -> Node<'_> {
+        let mut cursor = self.tree.walk();
+        cursor.goto_descendant(index);
+        cursor.node()
+    }
+
+    /// Source text of the node at `index`
+    pub fn text(&self, index: usize) -> &str {
+        &self.source[self.node(index).byte_range()]
+    }
+}
+
+/// Code of a body, and when it was last used
+type Cached = (Arc<Code>, u64);
+
+/// Compiled bodies by their interned text, the least recently used dropped
+/// once there are `capacity` of them
+#[derive(Debug)]
+pub struct CodeCache {
+    capacity: usize,
+    entries: HashMap<InternedString, Cached>,
+    clock: u64,
+}
+
+impl CodeCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// The code of `body`, if it is cached
+    pub fn get(&mut self, body: InternedString) -> Option<Arc<Code>> {
+        self.clock += 1;
+        let (code, used) = self.entries.get_mut(&body)?;
+        *used = self.clock;
+        Some(code.clone())
+    }
+
+    /// Cache `code` for `body`, dropping the least recently used code if
+    /// the cache is full
+    pub fn insert(&mut self, body: InternedString, code: Arc<Code>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.entries.contains_key(&body) {
+            self.shrink(self.capacity - 1);
+        }
+        self.clock += 1;
+        self.entries.insert(body, (code, self.clock));
+    }
+
+    /// Keep at most `capacity` bodies, dropping the least recently used
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.shrink(capacity);
+    }
+
+    /// Drop the least recently used code until at most `size` are left
+    fn shrink(&mut self, size: usize) {
+        while self.entries.len() > size {
+            let oldest = self.entries.iter().min_by_key(|(_, (_, used))| *used);
+            if let Some((&oldest, _)) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    /// Drop all cached code
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of bodies cached
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Gives the value of a literal node, if it has one
+pub type Literal<'a> = dyn FnMut(Node) -> Option<Value> + 'a;
+
+/// Compiles a parse tree, asking for the value of each literal
+pub struct Compiler<'a> {
+    source: &'a str,
+    literal: &'a mut Literal<'a>,
+    /// Index in the tree of each node, by its id
+    indexes: HashMap<usize, usize>,
+    ops: Vec<Op>,
+    constants: Vec<Value>,
+    /// Whether to fold operations on constants
+    fold: bool,
+    /// Ops before the last jump target, which folding leaves alone
+    barrier: usize,
+}
+
+/// Kinds of literal node, whose values are constants
+const LITERALS: &[&str] = &[
+    "number",
+    "boolean",
+    "symbol",
+    "string",
+    "date",
+    "time",
+    "timestamp",
+];
+
+/// Precedence layers, which pass their operand through when they have no
+/// operator
+const LAYERS: &[&str] = &[
+    "dyadic",
+    "comparison",
+    "additive",
+    "multiplicative",
+    "unary",
+    "power",
+    "postfix",
+];
+
+impl<'a> Compiler<'a> {
+    /// A compiler for trees of `source`, giving literals the values
+    /// `literal` finds for them; a literal it has none for is evaluated
+    /// when it is reached, so that its error is reported then
+    pub fn new(source: &'a str, literal: &'a mut Literal<'a>) -> Self {
+        Self {
+            source,
+            literal,
+            indexes: HashMap::new(),
+            ops: Vec::new(),
+            constants: Vec::new(),
+            fold: true,
+            barrier: 0,
+        }
+    }
+
+    /// Whether to fold operations on constants, as it does by default
+    pub fn folding(self, fold: bool) -> Self {
+        Self { fold, ..self }
+    }
+
+    /// `tree`, the parse of the source, compiled
+    pub fn compile(mut self, tree: Tree) -> Code {
+        self.body(tree.root_node());
+        Code {
+            source: self.source.to_string(),
+            tree,
+            ops: self.ops,
+            constants: self.constants,
+        }
+    }
+
+    /// Ops for the statements under `root`
+    fn body(&mut self, root: Node) {
+        let mut cursor = root.walk();
+        // Number the nodes in the order a cursor visits them
+        loop {
+            let node = cursor.node();
+            self.indexes.insert(node.id(), cursor.descendant_index());
+            if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                continue;
+            }
+            while cursor.goto_parent() && !cursor.goto_next_sibling() {}
+            if cursor.node() == root {
+                break;
+            }
+        }
+        cursor.reset(root);
+        let statements: Vec<Node> = root
+            .named_children(&mut cursor)
+            .filter(|child| child.kind() != "comment")
+            .collect();
+        if statements.is_empty() {
+            // The evaluator reports that there is nothing to evaluate
+            self.eval(root);
+        }
+        for (i, statement) in statements.into_iter().enumerate() {
+            if i > 0 {
+                self.ops.push(Op::Pop);
+            }
+            self.expression(statement);
+        }
+    }
+
+    /// Index of `node` in its tree, by which [`Code::node`] finds it again
+    fn index(&self, node: Node) -> usize {
+        self.indexes[&node.id()]
+    }
+
+    /// An op leaving `node` to the evaluator
+    fn eval(&mut self, node: Node) {
+        self.ops.push(Op::Eval(self.index(node)));
+    }
+
+    fn text(&self, node: Node) -> &'a str {
+        &self.source[node.byte_range()]
+    }
+
+    /// Ops pushing the value of `node`
+    fn expression(&mut self, node: Node) {
+        let operator = node.child_by_field_name("operator");
+        match (node.kind(), operator) {
+            ("statement", _) => {
+                self.ops.push(Op::Statement(self.index(node)));
+                self.wrapped(node);
+            }
+            ("expression", _) => self.wrapped(node),
+            (kind, None) if LAYERS.contains(&kind) => self.wrapped(node),
+            ("primary", _) => match node.child_by_field_name("expression") {
+                Some(inner) => self.expression(inner),
+                None => self.wrapped(node),
+            },
+            (kind, _) if LITERALS.contains(&kind) => match (self.literal)(node) {
+                Some(value) => self.constant(value),
+                None => self.eval(node),
+            },
+            ("identifier", _) => self.ops.push(Op::Load {
+                name: self.text(node).to_string(),
+                node: self.index(node),
+            }),
+            ("assignment", Some(operator)) => self.assignment(node, operator),
+            ("additive" | "multiplicative", Some(operator)) => {
+                let (Some(left), Some(right)) = (field(node, "left"), field(node, "right")) else {
+                    return self.eval(node);
+                };
+                self.expression(left);
+                self.expression(right);
+                let op = self.text(operator);
+                // Overflow fails here whatever the mode, so that it is left
+                // to the mode in force when the code runs
+                let mode = OverflowMode::Error;
+                if self.folded(2, |v| {
+                    arithmetic::binary(op, &v[0], &v[1], mode, node, operator)
+                }) {
+                    return;
+                }
+                self.ops.push(Op::Arithmetic {
+                    op: op.to_string(),
+                    node: self.index(node),
+                    operator: self.index(operator),
+                });
+            }
+            ("comparison", Some(operator)) => {
+                let (Some(left), Some(right)) = (field(node, "left"), field(node, "right")) else {
+                    return self.eval(node);
+                };
+                self.expression(right);
+                self.expression(left);
+                let op = self.text(operator);
+                if self.folded(2, |v| operators::compare(op, &v[1], &v[0], node)) {
+                    return;
+                }
+                self.ops.push(Op::Compare {
+                    op: op.to_string(),
+                    node: self.index(node),
+                });
+            }
+            ("unary", Some(_)) => match field(node, "operand") {
+                Some(operand) => {
+                    self.expression(operand);
+                    let mode = OverflowMode::Error;
+                    if self.folded(1, |v| arithmetic::negate(&v[0], mode, operand)) {
+                        return;
+                    }
+                    self.ops.push(Op::Negate {
+                        node: self.index(operand),
+                    });
+                }
+                None => self.eval(node),
+            },
+            ("function_call", _) => self.call(node),
+            ("application", _) => match (field(node, "function"), field(node, "argument")) {
+                (Some(function), Some(argument)) => {
+                    self.expression(function);
+                    self.expression(argument);
+                    self.ops.push(Op::Call {
+                        argc: 1,
+                        node: self.index(node),
+                        function: self.index(function),
+                    });
+                }
+                _ => self.eval(node),
+            },
+            ("conditional", _) => self.conditional(node),
+            _ => self.eval(node),
+        }
+    }
+
+    /// An op pushing `value`
+    fn constant(&mut self, value: Value) {
+        self.constants.push(value);
+        self.ops.push(Op::Const(self.constants.len() - 1));
+    }
+
+    /// Replace the last `n` ops with the constant `f` makes of their values,
+    /// if they all push constants and `f` succeeds; whether it did
+    fn folded(&mut self, n: usize, f: impl FnOnce(&[Value]) -> Result<Value, EvalError>) -> bool {
+        let start = self.ops.len().checked_sub(n);
+        let Some(start) = start.filter(|&start| self.fold && start >= self.barrier) else {
+            return false;
+        };
+        let indexes: Option<Vec<usize>> = self.ops[start..]
+            .iter()
+            .map(|op| match op {
+                Op::Const(index) => Some(*index),
+                _ => None,
+            })
+            .collect();
+        let Some(indexes) = indexes else {
+            return false;
+        };
+        let values: Vec<Value> = indexes.iter().map(|&i| self.constants[i].clone()).collect();
+        let Ok(value) = f(&values) else {
+            return false;
+        };
+        // The constants of the last ops are the last constants
+        self.ops.truncate(start);
+        self.constants
+            .truncate(indexes.into_iter().min().unwrap_or(self.constants.len()));
+        self.constant(value);
+        true
+    }
+
+    /// Ops for the single named child of a wrapper
+    fn wrapped(&mut self, node: Node) {
+        match node.named_child(0) {
+            Some(child) => self.expression(child),
+            None => self.eval(node),
+        }
+    }
+
+    fn assignment(&mut self, node: Node, operator: Node) {
+        let (Some(name), Some(value)) = (field(node, "name"), field(node, "value")) else {
+            return self.eval(node);
+        };
+        self.expression(value);
+        self.ops.push(Op::Store {
+            name: self.text(name).to_string(),
+            global: self.text(operator) == "::",
+        });
+    }
+
+    /// `f[a;b]`; calls with an empty slot project, which the evaluator does
+    fn call(&mut self, node: Node) {
+        let Some(function) = field(node, "function") else {
+            return self.eval(node);
+        };
+        let mut args = Vec::new();
+        if let Some(list) = field(node, "args") {
+            let mut slot = None;
+            let mut cursor = list.walk();
+            for child in list.children(&mut cursor) {
+                match child.kind() {
+                    ";" => args.push(slot.take()),
+                    "expression" => slot = Some(child),
+                    _ => {}
+                }
+            }
+            args.push(slot);
+        }
+        let Some(args) = args.into_iter().collect::<Option<Vec<Node>>>() else {
+            return self.eval(node);
+        };
+        self.expression(function);
+        for &arg in &args {
+            self.expression(arg);
+        }
+        self.ops.push(Op::Call {
+            argc: args.len(),
+            node: self.index(node),
+            function: self.index(function),
+        });
+    }
+
+    /// `$[c;t;f]`: each condition jumps past its branch unless it holds, and
+    /// each branch jumps to the end
+    fn conditional(&mut self, node: Node) {
+        let mut cursor = node.walk();
+        let args: Vec<Node> = node.children_by_field_name("arg", &mut cursor).collect();
+        if args.len().is_multiple_of(2) {
+            // No final branch, which the evaluator reports
+            return self.eval(node);
+        }
+        let mut exits = Vec::new();
+        for pair in args.chunks_exact(2) {
+            self.expression(pair[0]);
+            let test = self.ops.len();
+            self.ops.push(Op::JumpUnless {
+                target: 0,
+                node: self.index(pair[0]),
+            });
+            self.expression(pair[1]);
+            exits.push(self.ops.len());
+            self.ops.push(Op::Jump(0));
+            let next = self.ops.len();
+            self.barrier = next;
+            if let Op::JumpUnless { target, .. } = &mut self.ops[test] {
+                *target = next;
+            }
+        }
+        self.expression(args[args.len() - 1]);
+        let end = self.ops.len();
+        self.barrier = end;
+        for exit in exits {
+            self.ops[exit] = Op::Jump(end);
+        }
+    }
+}
+
+fn field<'t>(node: Node<'t>, name: &str) -> Option<Node<'t>> {
+    node.child_by_field_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_expression;
+
+    fn compile(source: &str) -> Code {
+        compile_folding(source, true)
+    }
+
+    fn compile_folding(source: &str, fold: bool) -> Code {
+        let tree = parse_expression(source).unwrap();
+        let mut literal = |node: Node| source[node.byte_range()].parse().ok().map(Value::Integer);
+        Compiler::new(source, &mut literal)
+            .folding(fold)
+            .compile(tree)
+    }
+
+    /// The ops of `code` other than statements and their constants
+    fn constants(code: &Code) -> Vec<Option<Value>> {
+        let ops = code.ops.iter().filter(|op| !matches!(op, Op::Statement(_)));
+        ops.map(|op| match op {
+            Op::Const(i) => Some(code.constants[*i].clone()),
+            _ => None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_folds_operations_on_constants() {
+        let code = compile("2*3+x");
+        assert_eq!(constants(&code), [Some(Value::Integer(6)), None, None]);
+        assert_eq!(code.constants, [Value::Integer(6)]);
+        let code = compile("-(1+2)<4");
+        assert_eq!(constants(&code), [Some(Value::Boolean(true))]);
+        // Operations that fail, and conditionals, are left to run
+        assert_eq!(compile("1+9223372036854775807").ops.len(), 4);
+        let code = compile("$[x;1;2]+3");
+        assert!(matches!(code.ops.last(), Some(Op::Arithmetic { .. })));
+        let code = compile("$[x;1;2+3]");
+        assert_eq!(code.ops.last(), Some(&Op::Const(1)));
+        assert_eq!(code.constants[1], Value::Integer(5));
+        assert_eq!(compile_folding("2*3", false).ops.len(), 4);
+    }
+
+    #[test]
+    fn test_compiles_statements_to_ops() {
+        let code = compile("y: x*2; $[y>10; y; f[y]]");
+        let ops: Vec<String> = code
+            .ops
+            .iter()
+            .map(|op| match op {
+                Op::Statement(_) => "statement".into(),
+                Op::Const(i) => format!("const {:?}", code.constants[*i]),
+                Op::Load { name, .. } => format!("load {}", name),
+                Op::Store { name, .. } => format!("store {}", name),
+                Op::Arithmetic { op, .. } | Op::Compare { op, .. } => op.clone(),
+                Op::Call { argc, .. } => format!("call {}", argc),
+                Op::JumpUnless { target, .. } => format!("jump unless {}", target),
+                Op::Jump(target) => format!("jump {}", target),
+                other => format!("{:?}", other),
+            })
+            .collect();
+        let expected = [
+            "statement",
+            "load x",
+            "const Integer(2)",
+            "*",
+            "store y",
+            "Pop",
+            "statement",
+            "const Integer(10)",
+            "load y",
+            ">",
+            "jump unless 13",
+            "load y",
+            "jump 16",
+            "load f",
+            "load y",
+            "call 1",
+        ];
+        assert_eq!(ops, expected);
+    }
+
+    #[test]
+    fn test_leaves_other_nodes_to_the_evaluator() {
+        // Projections, loops and literals with no value are walked
+        for source in ["f[;1]", "do[3;x+:1]", "1 2 3", "$[a;b]"] {
+            let code = compile(source);
+            assert!(
+                code.ops.iter().any(|op| matches!(op, Op::Eval(_))),
+                "{}",
+                source
+            );
+        }
+        let code = compile("x+1");
+        let Some(Op::Arithmetic { operator, .. }) = code.ops.last() else {
+            panic!("{:?}", code.ops);
+        };
+        assert_eq!(code.text(*operator), "+");
+    }
+
+    #[test]
+    fn test_code_cache_drops_the_least_recently_used() {
+        let mut interner = lasso::Rodeo::default();
+        let [a, b, c] = ["x+1", "x+2", "x+3"].map(|body| interner.get_or_intern(body));
+        let mut cache = CodeCache::new(2);
+        cache.insert(a, Arc::new(compile("x+1")));
+        cache.insert(b, Arc::new(compile("x+2")));
+        assert!(cache.get(a).is_some());
+        cache.insert(c, Arc::new(compile("x+3")));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(b).is_none());
+        assert!(cache.get(a).is_some() && cache.get(c).is_some());
+        cache.set_capacity(0);
+        assert!(cache.is_empty());
+        cache.insert(a, Arc::new(compile("x+1")));
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+//! Cooperative cancellation of evaluation
+//!
+//! The evaluator checks its [`CancelToken`], and the deadline given to
+//! [`Evaluator::eval_with_deadline`], as it evaluates each node and each op
+//! of compiled code. A runaway computation then stops at the next check
+//! with [`EvalErrorKind::Cancelled`] or [`EvalErrorKind::TimedOut`], which
+//! `@[f;x;handler]` does not trap, instead of hanging the REPL or kernel. A
+//! single primitive working through a long list finishes before the check.
+//!
+//! ```
+//! use wabznasm::Session;
+//! use std::time::Duration;
+//!
+//! let mut session = Session::new();
+//! session.set_timeout(Some(Duration::from_millis(50)));
+//! let error = session.eval("do[1000000; do[1000000; 0]]").unwrap_err();
+//! assert_eq!(error.to_string(), "Evaluation timed out");
+//! ```
+//!
+//! [`Evaluator::eval_with_deadline`]: crate::evaluator::Evaluator::eval_with_deadline
+//! [`EvalErrorKind::Cancelled`]: crate::errors::EvalErrorKind::Cancelled
+//! [`EvalErrorKind::TimedOut`]: crate::errors::EvalErrorKind::TimedOut
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A request to stop evaluating, shared between the evaluator and whatever
+/// stops it, such as a signal handler or another thread
+///
+/// Clones share the request. A request made while nothing is evaluating is
+/// forgotten when the next evaluation starts.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the evaluation in progress to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether a stop has been asked for and not yet acted on
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Whether a stop has been asked for, forgetting the request
+    pub fn take(&self) -> bool {
+        // Load first: it is the common case, and cheaper than a swap
+        self.is_cancelled() && self.0.swap(false, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,192 @@
+//! The `.ckpt` builtins: long computations that survive interruption
+//!
+//! `.ckpt.fold[`name;every;f;init;xs]` folds `f` over `xs` like
+//! `f[...f[f[init;xs 0];xs 1]...;xs n-1]`, persisting the accumulator under
+//! the database root every `every` items and whenever `f` fails. Running the
+//! same fold again resumes after the last persisted item instead of starting
+//! over; a fold that completes removes its checkpoint.
+//!
+//! ```text
+//! .ckpt.fold[`backfill; 10; {[acc;date] acc+load date}; 0; dates]
+//! ```
+//!
+//! Accumulators are persisted as JSON, so they must be convertible to it.
+//! A checkpoint's name must be a plain identifier, as it names a file, and a
+//! database attached read-only has no checkpoints written under it.
+
+use crate::builtins::Apply;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use serde_json::{Value as JsonValue, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tree_sitter::Node;
+
+/// Directory under the database root holding checkpoints
+pub const CHECKPOINT_DIR: &str = ".checkpoints";
+
+fn error(message: String, node: Node) -> EvalError {
+    EvalError::new(EvalErrorKind::Other(message), node)
+}
+
+fn type_error(message: String, node: Node) -> EvalError {
+    EvalError::new(EvalErrorKind::Type(message), node)
+}
+
+/// Where the checkpoint for the computation `name` is kept
+pub fn checkpoint_path(root: &Path, name: &str) -> PathBuf {
+    root.join(CHECKPOINT_DIR).join(format!("{}.json", name))
+}
+
+/// Whether `name` can name a checkpoint: letters, digits and underscores,
+/// starting with a letter, so it stays inside [`CHECKPOINT_DIR`]
+fn is_plain_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A checkpoint, if one was saved
+type LoadResult = Result<Option<Checkpoint>, EvalError>;
+
+/// Persisted progress of a fold: the accumulator after `index` items
+struct Checkpoint {
+    index: usize,
+    len: usize,
+    acc: Value,
+}
+
+impl Checkpoint {
+    fn load(path: &Path, node: Node) -> LoadResult {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(error(format!("Checkpoint error: {}", e), node)),
+        };
+        let corrupt = || error(format!("Corrupt checkpoint {}", path.display()), node);
+        let json: JsonValue = serde_json::from_str(&text).map_err(|_| corrupt())?;
+        let field = |name: &str| {
+            json.get(name)
+                .and_then(JsonValue::as_u64)
+                .map(|n| n as usize)
+        };
+        let (index, len) = field("index").zip(field("len")).ok_or_else(corrupt)?;
+        let acc = json
+            .get("acc")
+            .and_then(|acc| Value::try_from(acc).ok())
+            .ok_or_else(corrupt)?;
+        Ok(Some(Checkpoint { index, len, acc }))
+    }
+
+    /// Write via a temporary file so a crash never leaves a torn checkpoint
+    fn save(&self, path: &Path, node: Node) -> Result<(), EvalError> {
+        let acc = JsonValue::try_from(&self.acc)
+            .map_err(|e| type_error(format!("cannot checkpoint accumulator: {}", e), node))?;
+        let text = json!({"index": self.index, "len": self.len, "acc": acc}).to_string();
+        let io_error = |e: std::io::Error| error(format!("Checkpoint error: {}", e), node);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, text).map_err(io_error)?;
+        fs::rename(&temp, path).map_err(io_error)
+    }
+}
+
+/// `.ckpt.fold[`name;every;f;init;xs]`: fold `f` over `xs`, resumably
+pub fn fold(apply: &mut dyn Apply, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let name = args[0].as_symbol().ok_or_else(|| {
+        type_error(
+            format!(
+                "checkpoint name must be a symbol, got {}",
+                args[0].type_name()
+            ),
+            node,
+        )
+    })?;
+    if !is_plain_name(name) {
+        return Err(type_error(
+            format!("checkpoint name must be a plain identifier, got `{}", name),
+            node,
+        ));
+    }
+    let every = match args[1].as_integer() {
+        Some(n) if n > 0 => n as usize,
+        _ => {
+            return Err(type_error(
+                "checkpoint interval must be a positive integer".into(),
+                node,
+            ));
+        }
+    };
+    let function = &args[2];
+    let items = args[4].as_list().ok_or_else(|| {
+        type_error(
+            format!(
+                "fold expects a list to fold over, got {}",
+                args[4].type_name()
+            ),
+            node,
+        )
+    })?;
+
+    let root = apply
+        .context()
+        .database
+        .clone()
+        .ok_or_else(|| error("No database attached".into(), node))?;
+    if apply.context().read_only {
+        return Err(error(
+            format!("Database is read-only: cannot checkpoint `{}", name),
+            node,
+        ));
+    }
+    let path = checkpoint_path(&root, name);
+
+    let (start, mut acc) = match Checkpoint::load(&path, node)? {
+        Some(checkpoint) if checkpoint.len != items.len() => {
+            return Err(error(
+                format!(
+                    "Checkpoint `{} was taken over {} items, not {}",
+                    name,
+                    checkpoint.len,
+                    items.len()
+                ),
+                node,
+            ));
+        }
+        Some(checkpoint) => (checkpoint.index, checkpoint.acc),
+        None => (0, args[3].clone()),
+    };
+
+    for (index, item) in items.iter().enumerate().skip(start) {
+        match apply.apply(function, vec![acc.clone(), item.clone()], node) {
+            Ok(next) => acc = next,
+            Err(e) => {
+                // Resume at the item that failed
+                Checkpoint {
+                    index,
+                    len: items.len(),
+                    acc,
+                }
+                .save(&path, node)?;
+                return Err(e);
+            }
+        }
+        let done = index + 1;
+        if done % every == 0 && done < items.len() {
+            Checkpoint {
+                index: done,
+                len: items.len(),
+                acc: acc.clone(),
+            }
+            .save(&path, node)?;
+        }
+    }
+
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(error(format!("Checkpoint error: {}", e), node))
+        }
+        _ => Ok(acc),
+    }
+}
@@ -0,0 +1,147 @@
+//! Completion of the name being typed, for the REPL and the kernel
+//!
+//! The word before the cursor is completed from the names bound in the
+//! session and the builtins. A name bound to a function completes with the
+//! bracket its arguments go in, as `increment[`, unless one follows already.
+
+use crate::builtins;
+use crate::environment::{Environment, Value};
+use lasso::Rodeo;
+
+/// A name that can be completed to
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Name {
+    pub text: String,
+    /// Whether the name is bound to a function, and so completes with `[`
+    pub function: bool,
+}
+
+/// A completion of the word at the cursor
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    /// Byte offset in the line of the start of the word being completed
+    pub start: usize,
+    /// Names the word may be completed to, in order
+    pub matches: Vec<Name>,
+}
+
+impl Completion {
+    /// The text replacing the word for each match
+    pub fn replacements(&self, line: &str, pos: usize) -> Vec<String> {
+        let bracketed = line[pos..].starts_with('[');
+        self.matches
+            .iter()
+            .map(|name| {
+                if name.function && !bracketed {
+                    format!("{}[", name.text)
+                } else {
+                    name.text.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+/// The names visible in `env` and the builtins' names, to complete from
+pub fn names(env: &Environment, interner: &Rodeo) -> Vec<Name> {
+    let bound = env.visible_names_interned().into_iter().map(|name| Name {
+        text: interner.resolve(&name).to_string(),
+        function: env.lookup_interned(name).is_some_and(Value::is_function),
+    });
+    let builtins = builtins::names().map(|name| Name {
+        text: name.to_string(),
+        function: true,
+    });
+    bound.chain(builtins).collect()
+}
+
+/// Whether `c` can be part of a name, as in `.mylib.f_2`
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+/// Byte offset of the start of the name that ends at `pos` in `line`
+pub fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_name_char(c))
+        .last()
+        .map_or(pos, |(i, _)| i)
+}
+
+/// The name the cursor at `pos` in `line` is in or just after
+pub fn word_at(line: &str, pos: usize) -> &str {
+    let end = line[pos..]
+        .char_indices()
+        .find(|&(_, c)| !is_name_char(c))
+        .map_or(line.len(), |(i, _)| pos + i);
+    &line[word_start(line, pos)..end]
+}
+
+/// The names in `names` that the word ending at `pos` in `line` may be
+/// completed to, in order and without repeats
+pub fn complete(line: &str, pos: usize, names: &[Name]) -> Completion {
+    let start = word_start(line, pos);
+    let word = &line[start..pos];
+    // Completing nothing would offer every name; so would a number
+    if word.is_empty() || word.starts_with(|c: char| c.is_ascii_digit()) {
+        return Completion {
+            start,
+            matches: Vec::new(),
+        };
+    }
+    let mut matches: Vec<Name> = names
+        .iter()
+        .filter(|name| name.text.starts_with(word))
+        .cloned()
+        .collect();
+    matches.sort();
+    matches.dedup_by(|a, b| a.text == b.text);
+    Completion { start, matches }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(text: &str, function: bool) -> Name {
+        Name {
+            text: text.to_string(),
+            function,
+        }
+    }
+
+    #[test]
+    fn test_word_start() {
+        assert_eq!(word_start("1+incr", 6), 2);
+        assert_eq!(word_start("f[.mylib.g", 10), 2);
+        assert_eq!(word_start("x+ ", 3), 3);
+        assert_eq!(word_start("", 0), 0);
+    }
+
+    #[test]
+    fn test_word_at() {
+        assert_eq!(word_at("1+increment[2]", 4), "increment");
+        assert_eq!(word_at("1+increment[2]", 11), "increment");
+        assert_eq!(word_at("x+ ", 3), "");
+    }
+
+    #[test]
+    fn test_complete() {
+        let names = [
+            name("increment", true),
+            name("index", false),
+            name("in", true),
+            name("count", true),
+        ];
+        let completion = complete("1+inc", 5, &names);
+        assert_eq!(completion.start, 2);
+        assert_eq!(completion.matches, [name("increment", true)]);
+        assert_eq!(completion.replacements("1+inc", 5), ["increment["]);
+        assert_eq!(completion.replacements("1+inc[2]", 5), ["increment"]);
+        let texts: Vec<String> = complete("in", 2, &names).replacements("in", 2);
+        assert_eq!(texts, ["in[", "increment[", "index"]);
+        assert!(complete("1+", 2, &names).matches.is_empty());
+    }
+}
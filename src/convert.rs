@@ -0,0 +1,366 @@
+//! Conversions between wabznasm values and host representations
+//!
+//! Integers, booleans, symbols, lists and dicts map onto JSON numbers,
+//! booleans, strings, arrays and objects; atoms map onto storage scalars.
+//! The nulls `0N` and `0n` map onto JSON and storage nulls, which come back
+//! as `0N`. Dates, times and timestamps become ISO 8601 strings in JSON, and
+//! dates and timestamps become storage timestamps. Tables become JSON arrays
+//! of row objects, and convert to and from storage query results; a table,
+//! or a dict for a single row, also gives rows to ingest. Functions have no
+//! host representation and fail to convert.
+
+use crate::environment::{NULL_INTEGER, Value};
+use crate::table::Table;
+use crate::temporal;
+use chrono::{DateTime, SecondsFormat};
+use serde_json::{Map, Value as JsonValue};
+use std::sync::Arc;
+use storage::{ResultSet, ScalarValue, table::Row};
+use thiserror::Error;
+
+/// A value has no counterpart in the target representation
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("cannot convert {from} to {to}")]
+pub struct ConversionError {
+    /// Description of the value that failed to convert
+    pub from: String,
+    /// Name of the target representation
+    pub to: &'static str,
+}
+
+impl ConversionError {
+    fn new(from: impl Into<String>, to: &'static str) -> Self {
+        Self {
+            from: from.into(),
+            to,
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Integer(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+/// Strings become symbols
+impl From<&str> for Value {
+    fn from(name: &str) -> Self {
+        Value::Symbol(name.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(name: String) -> Self {
+        Value::Symbol(name.into())
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Value::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<crate::builtins::NativeFunction> for Value {
+    fn from(native: crate::builtins::NativeFunction) -> Self {
+        Value::Native(Arc::new(native))
+    }
+}
+
+impl TryFrom<&Value> for JsonValue {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            value if value.is_null() => Ok(JsonValue::Null),
+            Value::Integer(n) => Ok(JsonValue::from(*n)),
+            // Digits in a string, as JSON readers tend to lose precision past 53 bits
+            Value::BigInt(n) => Ok(JsonValue::String(n.to_string())),
+            // JSON has no representation for infinities
+            Value::Float(f) => serde_json::Number::from_f64(*f)
+                .map(JsonValue::Number)
+                .ok_or_else(|| ConversionError::new(format!("float {}", f), "JSON")),
+            Value::Boolean(b) => Ok(JsonValue::Bool(*b)),
+            Value::Symbol(name) => Ok(JsonValue::String(name.to_string())),
+            Value::Date(days) => Ok(JsonValue::String(
+                temporal::format_date(*days).replace('.', "-"),
+            )),
+            Value::Time(millis) => Ok(JsonValue::String(temporal::format_time(*millis))),
+            Value::Timestamp(nanos) => Ok(JsonValue::String(
+                DateTime::from_timestamp_nanos(*nanos).to_rfc3339_opts(SecondsFormat::Nanos, true),
+            )),
+            Value::List(items) => items
+                .iter()
+                .map(JsonValue::try_from)
+                .collect::<Result<Vec<_>, _>>()
+                .map(JsonValue::Array),
+            Value::Dict { keys, values } => {
+                let mut object = Map::new();
+                for (key, value) in keys.iter().zip(values) {
+                    let key = match key {
+                        Value::Symbol(name) => name.to_string(),
+                        Value::Integer(n) => n.to_string(),
+                        other => return Err(ConversionError::new(other.type_name(), "JSON key")),
+                    };
+                    object.insert(key, JsonValue::try_from(value)?);
+                }
+                Ok(JsonValue::Object(object))
+            }
+            Value::Table(table) => table
+                .rows()
+                .map(|row| JsonValue::try_from(&row))
+                .collect::<Result<Vec<_>, _>>()
+                .map(JsonValue::Array),
+            other => Err(ConversionError::new(other.type_name(), "JSON")),
+        }
+    }
+}
+
+impl TryFrom<&JsonValue> for Value {
+    type Error = ConversionError;
+
+    fn try_from(json: &JsonValue) -> Result<Self, Self::Error> {
+        match json {
+            JsonValue::Bool(b) => Ok(Value::Boolean(*b)),
+            JsonValue::Number(n) => n
+                .as_i64()
+                .map(Value::Integer)
+                .or_else(|| n.as_f64().map(Value::Float))
+                .ok_or_else(|| ConversionError::new(format!("JSON number {}", n), "number")),
+            JsonValue::String(s) => Ok(Value::Symbol(s.into())),
+            JsonValue::Array(items) => items
+                .iter()
+                .map(Value::try_from)
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::List),
+            JsonValue::Object(object) => {
+                let keys = object.keys().map(|k| Value::Symbol(k.into())).collect();
+                let values = object
+                    .values()
+                    .map(Value::try_from)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Dict { keys, values })
+            }
+            JsonValue::Null => Ok(Value::Integer(NULL_INTEGER)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for ScalarValue {
+    type Error = ConversionError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            value if value.is_null() => Ok(ScalarValue::Null),
+            Value::Integer(n) => Ok(ScalarValue::Int64(*n)),
+            Value::Float(f) => Ok(ScalarValue::Float64(*f)),
+            Value::Boolean(b) => Ok(ScalarValue::Boolean(*b)),
+            Value::Symbol(name) => Ok(ScalarValue::Utf8(name.to_string())),
+            Value::Date(days) => Ok(ScalarValue::Timestamp(temporal::date_to_timestamp(*days))),
+            Value::Timestamp(nanos) => Ok(ScalarValue::Timestamp(*nanos)),
+            other => Err(ConversionError::new(other.type_name(), "scalar")),
+        }
+    }
+}
+
+impl TryFrom<&ScalarValue> for Value {
+    type Error = ConversionError;
+
+    fn try_from(scalar: &ScalarValue) -> Result<Self, Self::Error> {
+        match scalar {
+            ScalarValue::Null => Ok(Value::Integer(NULL_INTEGER)),
+            ScalarValue::Boolean(b) => Ok(Value::Boolean(*b)),
+            ScalarValue::Utf8(s) => Ok(Value::Symbol(s.into())),
+            ScalarValue::Timestamp(nanos) => Ok(Value::Timestamp(*nanos)),
+            ScalarValue::Float32(f) => Ok(Value::Float(f64::from(*f))),
+            ScalarValue::Float64(f) => Ok(Value::Float(*f)),
+            other => other
+                .as_i64()
+                .map(Value::Integer)
+                .ok_or_else(|| ConversionError::new(format!("{:?}", other), "value")),
+        }
+    }
+}
+
+/// Query results become a table, one column per selected column
+impl TryFrom<&ResultSet> for Value {
+    type Error = ConversionError;
+
+    fn try_from(result: &ResultSet) -> Result<Self, Self::Error> {
+        let mut columns = vec![Vec::with_capacity(result.rows.len()); result.columns.len()];
+        for row in &result.rows {
+            for (column, scalar) in columns.iter_mut().zip(row) {
+                column.push(Value::try_from(scalar)?);
+            }
+        }
+        let names = result.columns.iter().map(|c| c.as_str().into()).collect();
+        Table::new(names, columns)
+            .map(Value::Table)
+            .map_err(|e| ConversionError::new(format!("query result ({})", e), "table"))
+    }
+}
+
+/// A table of atoms becomes query results with a row per table row
+impl TryFrom<&Table> for ResultSet {
+    type Error = ConversionError;
+
+    fn try_from(table: &Table) -> Result<Self, Self::Error> {
+        let rows = (0..table.len())
+            .map(|i| {
+                table
+                    .columns()
+                    .iter()
+                    .map(|column| ScalarValue::try_from(&column[i]))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ResultSet {
+            columns: table.names().iter().map(|n| n.to_string()).collect(),
+            rows,
+            scanned: 0,
+        })
+    }
+}
+
+/// Rows to ingest, or why a value does not convert to them
+pub type RowsResult = Result<Vec<Row>, ConversionError>;
+
+/// The storage rows of `value`: a row per row of a table, a dict of column
+/// names to atoms as a single row, or the empty list as none
+pub fn rows(value: &Value) -> RowsResult {
+    match value {
+        Value::Table(table) => {
+            let result = ResultSet::try_from(table)?;
+            let columns = &result.columns;
+            Ok((result.rows.iter())
+                .map(|row| columns.iter().cloned().zip(row.iter().cloned()).collect())
+                .collect())
+        }
+        Value::Dict { keys, values } => {
+            let row = keys
+                .iter()
+                .zip(values)
+                .map(|(key, value)| match key {
+                    Value::Symbol(name) => Ok((name.to_string(), ScalarValue::try_from(value)?)),
+                    other => Err(ConversionError::new(other.type_name(), "column name")),
+                })
+                .collect::<Result<Row, _>>()?;
+            Ok(vec![row])
+        }
+        Value::List(items) if items.is_empty() => Ok(Vec::new()),
+        other => Err(ConversionError::new(other.type_name(), "rows")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_round_trip() {
+        let value = Value::Dict {
+            keys: vec!["ok".into(), "px".into()],
+            values: vec![true.into(), Value::from(vec![1i64, 2])],
+        };
+        let json = JsonValue::try_from(&value).unwrap();
+        assert_eq!(json, json!({"px": [1, 2], "ok": true}));
+        assert_eq!(Value::try_from(&json).unwrap(), value);
+
+        assert_eq!(Value::try_from(&json!(1.5)).unwrap(), Value::Float(1.5));
+        assert!(JsonValue::try_from(&Value::Float(f64::INFINITY)).is_err());
+        assert_eq!(
+            JsonValue::try_from(&Value::Float(f64::NAN)).unwrap(),
+            JsonValue::Null
+        );
+        assert_eq!(
+            Value::try_from(&json!([1, null])).unwrap(),
+            Value::List(vec![Value::Integer(1), Value::Integer(NULL_INTEGER)])
+        );
+    }
+
+    #[test]
+    fn test_rows() {
+        let table = Table::new(
+            vec!["px".into()],
+            vec![vec![Value::Integer(1), Value::Integer(2)]],
+        )
+        .unwrap();
+        let table_rows = rows(&Value::Table(table)).unwrap();
+        assert_eq!(table_rows.len(), 2);
+        assert_eq!(table_rows[1]["px"], ScalarValue::Int64(2));
+        let row = Value::Dict {
+            keys: vec!["px".into()],
+            values: vec![Value::Float(1.5)],
+        };
+        assert_eq!(rows(&row).unwrap()[0]["px"], ScalarValue::Float64(1.5));
+        assert!(rows(&Value::List(Vec::new())).unwrap().is_empty());
+        assert!(rows(&Value::Integer(1)).is_err());
+    }
+
+    #[test]
+    fn test_scalar_conversions() {
+        assert_eq!(
+            ScalarValue::try_from(&Value::Integer(7)).unwrap(),
+            ScalarValue::Int64(7)
+        );
+        assert_eq!(
+            Value::try_from(&ScalarValue::Utf8("AAPL".into())).unwrap(),
+            Value::Symbol("AAPL".into())
+        );
+        assert_eq!(
+            Value::try_from(&ScalarValue::Int32(3)).unwrap(),
+            Value::Integer(3)
+        );
+        assert!(ScalarValue::try_from(&Value::List(vec![])).is_err());
+        assert_eq!(
+            Value::try_from(&ScalarValue::Float64(1.5)).unwrap(),
+            Value::Float(1.5)
+        );
+        assert!(Value::try_from(&ScalarValue::Binary(vec![1])).is_err());
+        assert_eq!(
+            Value::try_from(&ScalarValue::Timestamp(5)).unwrap(),
+            Value::Timestamp(5)
+        );
+        assert_eq!(
+            ScalarValue::try_from(&Value::Date(1)).unwrap(),
+            ScalarValue::Timestamp(temporal::NANOS_PER_DAY)
+        );
+        assert_eq!(
+            ScalarValue::try_from(&Value::Integer(NULL_INTEGER)).unwrap(),
+            ScalarValue::Null
+        );
+        assert_eq!(
+            Value::try_from(&ScalarValue::Null).unwrap(),
+            Value::Integer(NULL_INTEGER)
+        );
+    }
+
+    #[test]
+    fn test_result_set_round_trip() {
+        let result = ResultSet {
+            columns: vec!["sym".into(), "px".into()],
+            rows: vec![
+                vec![ScalarValue::Utf8("AAPL".into()), ScalarValue::Int64(100)],
+                vec![ScalarValue::Utf8("MSFT".into()), ScalarValue::Int64(250)],
+            ],
+            scanned: 2,
+        };
+        let Value::Table(table) = Value::try_from(&result).unwrap() else {
+            panic!("expected a table");
+        };
+        assert_eq!(table.column("px").unwrap(), [100i64.into(), 250i64.into()]);
+        assert_eq!(ResultSet::try_from(&table).unwrap().rows, result.rows);
+        assert_eq!(
+            JsonValue::try_from(&Value::Table(table)).unwrap(),
+            json!([{"sym": "AAPL", "px": 100}, {"sym": "MSFT", "px": 250}])
+        );
+    }
+}
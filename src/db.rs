@@ -0,0 +1,171 @@
+//! The `.db` builtins: table definitions from scripts
+//!
+//! Tables are created under the database root attached to the evaluator
+//! with [`Evaluator::set_database`](crate::evaluator::Evaluator::set_database).
+//! A schema is a dictionary from column names to q type names, where a type
+//! may be paired with an attribute:
+//!
+//! ```text
+//! .db.create[`trades; `time`sym`px!(`timestamp`sorted;`symbol`parted;`float)]
+//! ```
+//!
+//! A database attached read-only, as with `wabznasm --read-only`, can be
+//! listed and inspected but not created in.
+//!
+//! `.db.stats` reports how often each column of a table has been read and
+//! filtered on, to show which columns deserve an index or caching;
+//! `wabznasm advise` turns the same counts into suggestions.
+
+use crate::builtins::Context;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::table::Table as TableValue;
+use std::path::Path;
+use storage::{
+    ColumnAttribute, ColumnSchema, QStoreConfig, Table, TableSchema, schema::SimpleDataType,
+};
+use tree_sitter::Node;
+
+fn error(message: String, node: Node) -> EvalError {
+    EvalError::new(EvalErrorKind::Other(message), node)
+}
+
+fn type_error(message: String, node: Node) -> EvalError {
+    EvalError::new(EvalErrorKind::Type(message), node)
+}
+
+fn database<'a>(context: &'a Context, node: Node) -> Result<&'a Path, EvalError> {
+    context
+        .database
+        .as_deref()
+        .ok_or_else(|| error("No database attached".into(), node))
+}
+
+/// Storage configuration of table `name` in the attached database
+fn config(context: &Context, name: &str, node: Node) -> Result<QStoreConfig, EvalError> {
+    let root = database(context, node)?;
+    Ok(QStoreConfig::new(root, name.to_string()).with_read_only(context.read_only))
+}
+
+fn expect_symbol<'a>(value: &'a Value, what: &str, node: Node) -> Result<&'a str, EvalError> {
+    value.as_symbol().ok_or_else(|| {
+        type_error(
+            format!("{} must be a symbol, got {}", what, value.type_name()),
+            node,
+        )
+    })
+}
+
+/// Storage type for a q type name
+pub fn data_type(name: &str) -> Option<SimpleDataType> {
+    Some(match name {
+        "boolean" => SimpleDataType::Boolean,
+        "byte" => SimpleDataType::UInt8,
+        "short" => SimpleDataType::Int16,
+        "int" => SimpleDataType::Int32,
+        "long" => SimpleDataType::Int64,
+        "real" => SimpleDataType::Float32,
+        "float" => SimpleDataType::Float64,
+        "symbol" | "string" => SimpleDataType::Utf8,
+        "timestamp" => SimpleDataType::Timestamp,
+        _ => return None,
+    })
+}
+
+/// Column schema from a type symbol or a `type`attribute pair
+fn column(name: &str, spec: &Value, node: Node) -> Result<ColumnSchema, EvalError> {
+    let (type_name, attribute) = match spec {
+        Value::Symbol(type_name) => (type_name.as_str(), None),
+        Value::List(items) if items.len() == 2 => (
+            expect_symbol(&items[0], "column type", node)?,
+            Some(expect_symbol(&items[1], "column attribute", node)?),
+        ),
+        other => {
+            return Err(type_error(
+                format!(
+                    "column {} needs a type or `type`attribute, got {}",
+                    name,
+                    other.type_name()
+                ),
+                node,
+            ));
+        }
+    };
+
+    let data_type = data_type(type_name)
+        .ok_or_else(|| error(format!("Unknown column type: {}", type_name), node))?;
+    let mut column = ColumnSchema::new_simple(name.to_string(), data_type);
+    if let Some(attribute) = attribute {
+        let attribute = ColumnAttribute::parse(attribute)
+            .ok_or_else(|| error(format!("Unknown attribute: {}", attribute), node))?;
+        column = column.with_attribute(attribute);
+    }
+    Ok(column)
+}
+
+/// `.db.create[name; schema]`: create an empty table, returning its name
+pub fn create(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let name = expect_symbol(&args[0], "table name", node)?;
+    let config = config(context, name, node)?;
+    let Value::Dict { keys, values } = &args[1] else {
+        return Err(type_error(
+            format!("schema must be a dict, got {}", args[1].type_name()),
+            node,
+        ));
+    };
+
+    let mut schema = TableSchema::new(name.to_string());
+    for (key, spec) in keys.iter().zip(values) {
+        let column_name = expect_symbol(key, "column name", node)?;
+        schema = schema.add_column(column(column_name, spec, node)?);
+    }
+
+    Table::create(schema, config).map_err(|e| error(format!("Storage error: {}", e), node))?;
+    Ok(args[0].clone())
+}
+
+/// `.db.tables[]`: names of the tables in the database, sorted
+pub fn tables(context: &mut Context, _: &[Value], node: Node) -> Result<Value, EvalError> {
+    let root = database(context, node)?;
+    let names = storage::table::table_names(root)
+        .map_err(|e| error(format!("Storage error: {}", e), node))?;
+    Ok(Value::List(
+        names
+            .into_iter()
+            .map(|name| Value::Symbol(name.into()))
+            .collect(),
+    ))
+}
+
+/// `.db.stats[name]`: a table of the reads of each column of table `name`,
+/// most read first, with columns `column`, `reads`, `bytes`, `lookups` and
+/// `ranges`
+pub fn stats(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let name = expect_symbol(&args[0], "table name", node)?;
+    let config = config(context, name, node)?;
+    if !config.schema_path().is_file() {
+        return Err(error(format!("No such table: {}", name), node));
+    }
+    let storage_error = |e: storage::StorageError| error(format!("Storage error: {}", e), node);
+    let access = Table::load(config)
+        .and_then(|table| table.access_stats())
+        .map_err(storage_error)?;
+
+    let count = |n: u64| Value::Integer(i64::try_from(n).unwrap_or(i64::MAX));
+    let columns = vec![
+        access
+            .iter()
+            .map(|a| Value::Symbol(a.column.as_str().into()))
+            .collect(),
+        access.iter().map(|a| count(a.reads)).collect(),
+        access.iter().map(|a| count(a.bytes)).collect(),
+        access.iter().map(|a| count(a.lookups)).collect(),
+        access.iter().map(|a| count(a.ranges)).collect(),
+    ];
+    let names = ["column", "reads", "bytes", "lookups", "ranges"]
+        .map(Into::into)
+        .to_vec();
+    TableValue::new(names, columns)
+        .map(Value::Table)
+        .map_err(|message| error(message, node))
+}
@@ -0,0 +1,256 @@
+//! Structural differences between values
+//!
+//! [`Diff::between`] walks two values side by side: list items are compared
+//! by position, so a list of rows yields added, removed and changed rows,
+//! and dict entries are compared by key, so a row that is a dict yields
+//! changed cells; tables are compared row by row in the same way. The
+//! `diff[a;b]` builtin returns the same changes as a wabznasm value;
+//! [`Diff::render`] formats them for a terminal, optionally in colour.
+
+use crate::environment::Value;
+use lasso::Rodeo;
+use std::fmt;
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Where a change is: the list indices and dict keys leading to it
+pub type Path = Vec<Value>;
+
+/// One difference between two values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Present only in the second value
+    Added { path: Path, value: Value },
+    /// Present only in the first value
+    Removed { path: Path, value: Value },
+    /// Present in both, with different values
+    Changed { path: Path, old: Value, new: Value },
+}
+
+impl Change {
+    /// Name of the kind of change: `added`, `removed` or `changed`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Change::Added { .. } => "added",
+            Change::Removed { .. } => "removed",
+            Change::Changed { .. } => "changed",
+        }
+    }
+
+    /// Where the change is
+    pub fn path(&self) -> &[Value] {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. } => path,
+        }
+    }
+
+    /// The change as a dict with `kind` and `path` keys, plus `value` for
+    /// additions and removals or `old` and `new` for changes
+    pub fn to_value(&self) -> Value {
+        let mut keys: Vec<Value> = vec!["kind".into(), "path".into()];
+        let mut values = vec![self.kind().into(), Value::List(self.path().to_vec())];
+        match self {
+            Change::Added { value, .. } | Change::Removed { value, .. } => {
+                keys.push("value".into());
+                values.push(value.clone());
+            }
+            Change::Changed { old, new, .. } => {
+                keys.extend(["old".into(), "new".into()]);
+                values.extend([old.clone(), new.clone()]);
+            }
+        }
+        Value::Dict { keys, values }
+    }
+}
+
+/// All differences between two values, in the order they occur
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    /// The changes that turn `old` into `new`
+    pub fn between(old: &Value, new: &Value) -> Self {
+        let mut diff = Diff::default();
+        diff.walk(&mut Vec::new(), old, new);
+        diff
+    }
+
+    /// Whether the values were equal
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn walk(&mut self, path: &mut Path, old: &Value, new: &Value) {
+        if old == new {
+            return;
+        }
+        match (old, new) {
+            (Value::Table(a), Value::Table(b)) => {
+                let rows = |t: &crate::table::Table| Value::List(t.rows().collect());
+                self.walk(path, &rows(a), &rows(b));
+            }
+            (Value::List(a), Value::List(b)) => {
+                for i in 0..a.len().max(b.len()) {
+                    path.push(Value::Integer(i as i64));
+                    match (a.get(i), b.get(i)) {
+                        (Some(x), Some(y)) => self.walk(path, x, y),
+                        (Some(x), None) => self.removed(path, x),
+                        (None, Some(y)) => self.added(path, y),
+                        (None, None) => unreachable!("index is below the longer length"),
+                    }
+                    path.pop();
+                }
+            }
+            (
+                Value::Dict {
+                    keys: old_keys,
+                    values: old_values,
+                },
+                Value::Dict {
+                    keys: new_keys,
+                    values: new_values,
+                },
+            ) => {
+                for (key, x) in old_keys.iter().zip(old_values) {
+                    path.push(key.clone());
+                    match new_keys.iter().position(|k| k == key) {
+                        Some(j) => self.walk(path, x, &new_values[j]),
+                        None => self.removed(path, x),
+                    }
+                    path.pop();
+                }
+                for (key, y) in new_keys.iter().zip(new_values) {
+                    if !old_keys.contains(key) {
+                        path.push(key.clone());
+                        self.added(path, y);
+                        path.pop();
+                    }
+                }
+            }
+            _ => self.changes.push(Change::Changed {
+                path: path.clone(),
+                old: old.clone(),
+                new: new.clone(),
+            }),
+        }
+    }
+
+    fn added(&mut self, path: &Path, value: &Value) {
+        self.changes.push(Change::Added {
+            path: path.clone(),
+            value: value.clone(),
+        });
+    }
+
+    fn removed(&mut self, path: &Path, value: &Value) {
+        self.changes.push(Change::Removed {
+            path: path.clone(),
+            value: value.clone(),
+        });
+    }
+
+    /// The changes as a list of dicts; see [`Change::to_value`]
+    pub fn to_value(&self) -> Value {
+        Value::List(self.changes.iter().map(Change::to_value).collect())
+    }
+
+    /// One line per change: `+` additions, `-` removals and `~` changes,
+    /// coloured green, red and yellow when `color` is set
+    pub fn render(&self, interner: &Rodeo, color: bool) -> String {
+        let mut out = String::new();
+        for change in &self.changes {
+            let path = if change.path().is_empty() {
+                "(value)".to_string()
+            } else {
+                let parts: Vec<String> = change.path().iter().map(|p| p.format(interner)).collect();
+                format!("[{}]", parts.join(";"))
+            };
+            let (colour, line) = match change {
+                Change::Added { value, .. } => {
+                    (GREEN, format!("+ {} {}", path, value.format(interner)))
+                }
+                Change::Removed { value, .. } => {
+                    (RED, format!("- {} {}", path, value.format(interner)))
+                }
+                Change::Changed { old, new, .. } => (
+                    YELLOW,
+                    format!(
+                        "~ {} {} -> {}",
+                        path,
+                        old.format(interner),
+                        new.format(interner)
+                    ),
+                ),
+            };
+            if color {
+                out.push_str(&format!("{}{}{}\n", colour, line, RESET));
+            } else {
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// Uncoloured rendering, e.g. for assertion messages
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(&Rodeo::default(), false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(sym: &str, px: i64) -> Value {
+        Value::Dict {
+            keys: vec!["sym".into(), "px".into()],
+            values: vec![sym.into(), px.into()],
+        }
+    }
+
+    #[test]
+    fn test_rows_and_cells() {
+        let old = Value::List(vec![row("AAPL", 100), row("MSFT", 250)]);
+        let new = Value::List(vec![row("AAPL", 101), row("MSFT", 250), row("IBM", 140)]);
+        let diff = Diff::between(&old, &new);
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::Changed {
+                    path: vec![Value::Integer(0), "px".into()],
+                    old: Value::Integer(100),
+                    new: Value::Integer(101),
+                },
+                Change::Added {
+                    path: vec![Value::Integer(2)],
+                    value: row("IBM", 140),
+                },
+            ]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "~ [0;`px] 100 -> 101\n+ [2] `sym`px!(`IBM;140)\n"
+        );
+        assert!(diff.render(&Rodeo::default(), true).contains(GREEN));
+    }
+
+    #[test]
+    fn test_equal_values_have_no_changes() {
+        let value = Value::List(vec![row("AAPL", 100)]);
+        assert!(Diff::between(&value, &value).is_empty());
+        assert_eq!(
+            Diff::between(&value, &value).to_value(),
+            Value::List(vec![])
+        );
+    }
+}
@@ -5,20 +5,105 @@
 //! - Lexical scoping with parent environments
 //! - Efficient lookup with scope chain traversal
 //! - Support for closures and nested function definitions
-
+//!
+//! Bindings are kept in a persistent map, so cloning an environment, as
+//! closures and calls do, shares its bindings rather than copying them; a
+//! clone defining a name copies only the path to it.
+
+use crate::attributes::Attributed;
+use crate::bigint::BigInt;
+use crate::builtins::{self, Builtin, NativeFunction};
+use crate::bytecode::Code;
 use crate::errors::{EvalError, EvalErrorKind};
-use crate::interning::InternedString;
+use crate::interning::{InternedString, Symbol};
+use crate::suggest;
+use crate::table::Table;
+use crate::temporal;
+use crate::vectors::{self, Buffer};
 use bumpalo::Bump;
+use im::HashMap;
 use lasso::Rodeo;
-use std::collections::HashMap;
+use std::cmp::Ordering;
 use std::sync::Arc;
 use tree_sitter::Node;
 
+/// Items of a list value
+pub type ListItems = [Value];
+
+/// The integer null `0N`, used for missing values
+pub const NULL_INTEGER: i64 = i64::MIN;
+
+/// The integer infinity `0W`; `-0W` is its negation
+pub const INFINITY_INTEGER: i64 = i64::MAX;
+
+/// Order floats with NaN, the float null, before every other value
+fn compare_floats(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+    }
+}
+
 /// A value that can be stored in the environment
 #[derive(Debug, Clone)]
 pub enum Value {
     /// Integer value
     Integer(i64),
+    /// Integer beyond 64 bits, from arithmetic under the `bigint` overflow
+    /// mode; see [`crate::bigint`]
+    BigInt(BigInt),
+    /// Floating point value: `1.5`
+    Float(f64),
+    /// Boolean value: `1b` or `0b`
+    Boolean(bool),
+    /// Symbol: an interned name such as `` `trades ``
+    Symbol(Symbol),
+    /// Character: `"a"`; a string such as `"abc"` is a list of them
+    Char(char),
+    /// Date: days since 1970.01.01, written `2024.01.15`
+    Date(i32),
+    /// Time of day: milliseconds since midnight, written `12:30:00.250`
+    Time(i32),
+    /// Timestamp: nanoseconds since the Unix epoch, written
+    /// `2024.01.15D12:30:00.250000000`
+    Timestamp(i64),
+    /// General list of values: `1 2 3` or `(1;2 3;f)`
+    List(Vec<Value>),
+    /// List with an attribute: `` `s#1 2 3 ``; see [`crate::attributes`]
+    Attributed(Arc<Attributed>),
+    /// List of integers held in one buffer; see [`crate::vectors`]
+    IntVector(Buffer<i64>),
+    /// List of floats held in one buffer; see [`crate::vectors`]
+    FloatVector(Buffer<f64>),
+    /// Dictionary mapping each key to the value at the same position: `1 2!10 20`
+    Dict {
+        /// Keys, in insertion order
+        keys: Vec<Value>,
+        /// Values, one per key
+        values: Vec<Value>,
+    },
+    /// Table: a dictionary of equal-length columns, flipped
+    Table(Table),
+    /// Built-in function provided by the runtime
+    Builtin(&'static Builtin),
+    /// Function implemented by the embedding application
+    Native(Arc<NativeFunction>),
+    /// Function that remembers its results, made by `memo f`
+    Memo {
+        /// Function called for arguments not seen before
+        function: Box<Value>,
+        /// Index of the function's results in the evaluator's caches
+        cache: usize,
+    },
+    /// Function with some arguments already bound: `add[2;]` or `add[2]`
+    Projection {
+        /// Function being projected
+        function: Box<Value>,
+        /// Arguments in parameter order; `None` marks a slot still to be supplied
+        args: Vec<Option<Value>>,
+    },
     /// Function value with parameters, body, and captured environment
     Function {
         /// Function parameter names (empty for no params, single element for one param)
@@ -29,13 +114,78 @@ pub enum Value {
         body: InternedString,
         /// Captured lexical environment (closure)
         closure: Option<Arc<Environment>>,
+        /// Documentation: a string the body starts with, as in
+        /// `{[x] "Add one"; x+1}`
+        doc: Option<InternedString>,
+        /// The body compiled, once the evaluator has compiled it; see
+        /// [`crate::bytecode`]
+        code: Option<Arc<Code>>,
+        /// Where the body is in the source that defined the function, for
+        /// errors in the body to point into; boxed to keep values small
+        origin: Option<Arc<Origin>>,
     },
 }
 
+/// Where a function's body was written: the source its lambda was
+/// evaluated from, and the byte offset of the body within it
+///
+/// The source is shared by every lambda defined from the same input, and
+/// freed with the last of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub source: Arc<str>,
+    pub offset: u32,
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::BigInt(a), Value::BigInt(b)) => a == b,
+            // Nulls are equal to each other, as in q
+            (Value::Float(a), Value::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Symbol(a), Value::Symbol(b)) => a == b,
+            (Value::Char(a), Value::Char(b)) => a == b,
+            (Value::Date(a), Value::Date(b)) => a == b,
+            (Value::Time(a), Value::Time(b)) => a == b,
+            (Value::Timestamp(a), Value::Timestamp(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            // Attributes do not affect what a list holds
+            (Value::Attributed(a), Value::Attributed(b)) => a.items() == b.items(),
+            (Value::Attributed(a), Value::List(b)) | (Value::List(b), Value::Attributed(a)) => {
+                a.items() == b.as_slice()
+            }
+            // Nor does how the items are held
+            (Value::IntVector(a), Value::IntVector(b)) => a == b,
+            (Value::IntVector(_) | Value::FloatVector(_), _)
+            | (_, Value::IntVector(_) | Value::FloatVector(_)) => {
+                self.clone().plain() == other.clone().plain()
+            }
+            (
+                Value::Dict {
+                    keys: k1,
+                    values: v1,
+                },
+                Value::Dict {
+                    keys: k2,
+                    values: v2,
+                },
+            ) => k1 == k2 && v1 == v2,
+            (Value::Table(a), Value::Table(b)) => a == b,
+            (Value::Builtin(a), Value::Builtin(b)) => a.name == b.name,
+            (Value::Native(a), Value::Native(b)) => Arc::ptr_eq(a, b),
+            (Value::Memo { cache: a, .. }, Value::Memo { cache: b, .. }) => a == b,
+            (
+                Value::Projection {
+                    function: f1,
+                    args: a1,
+                },
+                Value::Projection {
+                    function: f2,
+                    args: a2,
+                },
+            ) => f1 == f2 && a1 == a2,
             (
                 Value::Function {
                     params: p1,
@@ -56,6 +206,19 @@ impl PartialEq for Value {
     }
 }
 
+impl From<BigInt> for Value {
+    /// An integer when `n` fits in 64 bits, otherwise a big integer; the
+    /// extremes of `i64` stay big as they stand for `0N` and `0W`
+    fn from(n: BigInt) -> Self {
+        match n.to_i64() {
+            Some(small) if small != NULL_INTEGER && small.abs() != INFINITY_INTEGER => {
+                Value::Integer(small)
+            }
+            _ => Value::BigInt(n),
+        }
+    }
+}
+
 impl Value {
     /// Convert value to integer if possible
     pub fn as_integer(&self) -> Option<i64> {
@@ -65,19 +228,304 @@ impl Value {
         }
     }
 
+    /// Borrow the name of a symbol value
+    pub fn as_symbol(&self) -> Option<&str> {
+        match self {
+            Value::Symbol(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Borrow the items of a list value
+    pub fn as_list(&self) -> Option<&ListItems> {
+        match self {
+            Value::List(items) => Some(items),
+            Value::Attributed(list) => Some(list.items()),
+            _ => None,
+        }
+    }
+
+    /// The value with any attribute removed and a vector's items as a
+    /// general list, for code that does not exploit either
+    pub fn plain(self) -> Value {
+        match self {
+            Value::Attributed(list) => Value::List(Arc::unwrap_or_clone(list).into_items()),
+            Value::IntVector(_) | Value::FloatVector(_) => {
+                Value::List(vectors::unpack(&self).unwrap_or_default())
+            }
+            other => other,
+        }
+    }
+
+    /// Look up `key` in a dictionary value
+    ///
+    /// Returns `None` if this is not a dictionary or the key is absent. Keys
+    /// are matched by equality, first occurrence wins.
+    pub fn dict_get(&self, key: &Value) -> Option<&Value> {
+        match self {
+            Value::Dict { keys, values } => keys
+                .iter()
+                .position(|k| k == key)
+                .and_then(|i| values.get(i)),
+            _ => None,
+        }
+    }
+
     /// Check if value is a function
     pub fn is_function(&self) -> bool {
-        matches!(self, Value::Function { .. })
+        matches!(
+            self,
+            Value::Function { .. }
+                | Value::Builtin(_)
+                | Value::Native(_)
+                | Value::Memo { .. }
+                | Value::Projection { .. }
+        )
     }
 
     /// Get function arity (parameter count)
     pub fn arity(&self) -> Option<usize> {
         match self {
             Value::Function { params, .. } => Some(params.len()),
+            Value::Builtin(builtin) => Some(builtin.arity),
+            Value::Native(native) => Some(native.arity),
+            Value::Memo { function, .. } => function.arity(),
+            Value::Projection { args, .. } => Some(args.iter().filter(|a| a.is_none()).count()),
             _ => None,
         }
     }
 
+    /// A string: the list of the characters of `text`
+    pub fn string(text: &str) -> Self {
+        Value::List(text.chars().map(Value::Char).collect())
+    }
+
+    /// The text of a string, a list of characters; the empty list is the
+    /// empty string
+    pub fn as_string(&self) -> Option<String> {
+        match self {
+            Value::List(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::Char(c) => Some(*c),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// Whether the value is a null: `0N` or `0n`
+    pub fn is_null(&self) -> bool {
+        match self {
+            Value::Integer(n) => *n == NULL_INTEGER,
+            Value::Float(f) => f.is_nan(),
+            _ => false,
+        }
+    }
+
+    /// The value as a float, with `0N` and `0W` becoming `0n` and `0w`
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(NULL_INTEGER) => Some(f64::NAN),
+            Value::Integer(INFINITY_INTEGER) => Some(f64::INFINITY),
+            Value::Integer(n) if *n == -INFINITY_INTEGER => Some(f64::NEG_INFINITY),
+            Value::Integer(n) => Some(*n as f64),
+            Value::BigInt(n) => Some(n.to_f64()),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Name of this value's type, as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::BigInt(_) => "bigint",
+            Value::Float(_) => "float",
+            Value::Boolean(_) => "boolean",
+            Value::Symbol(_) => "symbol",
+            Value::Char(_) => "char",
+            Value::Date(_) => "date",
+            Value::Time(_) => "time",
+            Value::Timestamp(_) => "timestamp",
+            Value::List(_) | Value::Attributed(_) | Value::IntVector(_) | Value::FloatVector(_) => {
+                "list"
+            }
+            Value::Dict { .. } => "dict",
+            Value::Table(_) => "table",
+            Value::Builtin(_)
+            | Value::Native(_)
+            | Value::Function { .. }
+            | Value::Memo { .. }
+            | Value::Projection { .. } => "function",
+        }
+    }
+
+    /// Order two values, or `None` if they are not comparable
+    ///
+    /// Numbers order numerically with nulls first, booleans false first,
+    /// temporal values chronologically, symbols and lists
+    /// lexicographically; functions and values of different types are
+    /// incomparable.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::BigInt(a), Value::BigInt(b)) => Some(a.cmp(b)),
+            // A big integer lies beyond every integer, nulls included
+            (Value::BigInt(a), Value::Integer(_)) => Some(if a.is_negative() {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }),
+            (Value::Integer(_), Value::BigInt(_)) => other.compare(self).map(Ordering::reverse),
+            (Value::Float(_), Value::Float(_))
+            | (Value::Integer(_), Value::Float(_))
+            | (Value::Float(_), Value::Integer(_))
+            | (Value::BigInt(_), Value::Float(_))
+            | (Value::Float(_), Value::BigInt(_)) => Some(compare_floats(
+                self.as_f64().unwrap_or(f64::NAN),
+                other.as_f64().unwrap_or(f64::NAN),
+            )),
+            (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+            (Value::Symbol(a), Value::Symbol(b)) => Some(a.cmp(b)),
+            (Value::Char(a), Value::Char(b)) => Some(a.cmp(b)),
+            (Value::Date(a), Value::Date(b)) | (Value::Time(a), Value::Time(b)) => Some(a.cmp(b)),
+            (Value::Timestamp(a), Value::Timestamp(b)) => Some(a.cmp(b)),
+            (Value::List(_) | Value::Attributed(_), Value::List(_) | Value::Attributed(_)) => {
+                let (a, b) = (self.as_list()?, other.as_list()?);
+                for (x, y) in a.iter().zip(b.iter()) {
+                    match x.compare(y)? {
+                        Ordering::Equal => continue,
+                        unequal => return Some(unequal),
+                    }
+                }
+                Some(a.len().cmp(&b.len()))
+            }
+            (Value::IntVector(_) | Value::FloatVector(_), _)
+            | (_, Value::IntVector(_) | Value::FloatVector(_)) => {
+                self.clone().plain().compare(&other.clone().plain())
+            }
+            _ => None,
+        }
+    }
+
+    /// Render the value in wabznasm syntax
+    pub fn format(&self, interner: &Rodeo) -> String {
+        match self {
+            Value::Integer(NULL_INTEGER) => "0N".to_string(),
+            Value::Integer(INFINITY_INTEGER) => "0W".to_string(),
+            Value::Integer(n) if *n == -INFINITY_INTEGER => "-0W".to_string(),
+            Value::Integer(n) => n.to_string(),
+            Value::BigInt(n) => n.to_string(),
+            Value::Float(f) if f.is_nan() => "0n".to_string(),
+            Value::Float(f) if f.is_infinite() => if *f > 0.0 { "0w" } else { "-0w" }.to_string(),
+            // Whole floats carry an f suffix so they read back as floats
+            Value::Float(f) if f.is_finite() && f.fract() == 0.0 => format!("{}f", f),
+            Value::Float(f) => f.to_string(),
+            Value::Boolean(b) => format!("{}b", u8::from(*b)),
+            Value::Symbol(name) => format!("`{}", name),
+            Value::Char(c) => format!("{:?}", c.to_string()),
+            Value::Date(days) => temporal::format_date(*days),
+            Value::Time(millis) => temporal::format_time(*millis),
+            Value::Timestamp(nanos) => temporal::format_timestamp(*nanos),
+            Value::List(items)
+                if !items.is_empty() && items.iter().all(|v| matches!(v, Value::Symbol(_))) =>
+            {
+                let text: String = items.iter().map(|v| v.format(interner)).collect();
+                let prefix = if items.len() == 1 { "," } else { "" };
+                format!("{}{}", prefix, text)
+            }
+            Value::List(items) if !items.is_empty() && self.as_string().is_some() => {
+                let text: String = self.as_string().unwrap_or_default();
+                let prefix = if items.len() == 1 { "," } else { "" };
+                format!("{}{:?}", prefix, text)
+            }
+            Value::List(items)
+                if !items.is_empty() && items.iter().all(|v| matches!(v, Value::Boolean(_))) =>
+            {
+                let bits: String = items
+                    .iter()
+                    .map(|v| if *v == Value::Boolean(true) { '1' } else { '0' })
+                    .collect();
+                let prefix = if items.len() == 1 { "," } else { "" };
+                format!("{}{}b", prefix, bits)
+            }
+            Value::List(items)
+                if items.len() > 1 && items.iter().all(|v| matches!(v, Value::Float(_))) =>
+            {
+                // A float vector needs the suffix only when every item is whole
+                let text: Vec<String> = items
+                    .iter()
+                    .map(|v| v.format(interner).trim_end_matches('f').to_string())
+                    .collect();
+                let suffix = if items.iter().all(|v| v.format(interner).ends_with('f')) {
+                    "f"
+                } else {
+                    ""
+                };
+                format!("{}{}", text.join(" "), suffix)
+            }
+            Value::List(items) => {
+                if !items.is_empty()
+                    && items
+                        .iter()
+                        .all(|v| matches!(v, Value::Integer(_) | Value::BigInt(_)))
+                {
+                    let text: Vec<String> = items.iter().map(|v| v.format(interner)).collect();
+                    if items.len() == 1 {
+                        format!(",{}", text[0])
+                    } else {
+                        text.join(" ")
+                    }
+                } else {
+                    let text: Vec<String> = items.iter().map(|v| v.format(interner)).collect();
+                    format!("({})", text.join(";"))
+                }
+            }
+            Value::Dict { keys, values } => {
+                let keys = Value::List(keys.clone()).format(interner);
+                let values = Value::List(values.clone()).format(interner);
+                format!("{}!{}", keys, values)
+            }
+            Value::Table(table) if table.is_keyed() => {
+                let (key, value) = table.split_key();
+                format!(
+                    "({})!({})",
+                    Value::Table(key).format(interner),
+                    Value::Table(value).format(interner)
+                )
+            }
+            Value::Table(table) => format!("flip {}", table.to_dict().format(interner)),
+            Value::Attributed(list) => format!(
+                "`{}#{}",
+                list.attribute().name(),
+                Value::List(list.items().to_vec()).format(interner)
+            ),
+            Value::IntVector(_) | Value::FloatVector(_) => self.clone().plain().format(interner),
+            Value::Builtin(builtin) => builtin.name.to_string(),
+            Value::Native(native) => native.name.clone(),
+            Value::Memo { function, .. } => format!("memo {}", function.format(interner)),
+            Value::Projection { function, args } => {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|a| a.as_ref().map_or(String::new(), |v| v.format(interner)))
+                    .collect();
+                format!("{}[{}]", function.format(interner), args.join(";"))
+            }
+            Value::Function { params, body, .. } => {
+                let body_str = interner.resolve(body);
+                if params.is_empty() {
+                    format!("{{{}}}", body_str)
+                } else {
+                    let param_names: Vec<&str> =
+                        params.iter().map(|p| interner.resolve(p)).collect();
+                    format!("{{[{}] {}}}", param_names.join(";"), body_str)
+                }
+            }
+        }
+    }
+
     /// Get function parameter names as resolved strings
     pub fn param_names(&self, interner: &Rodeo) -> Option<Vec<String>> {
         match self {
@@ -115,6 +563,26 @@ impl Value {
             params,
             body,
             closure,
+            doc: None,
+            code: None,
+            origin: None,
+        }
+    }
+
+    /// What `help` says about a function: the docstring of a function that
+    /// has one, the signature and description of a native function or
+    /// builtin, or else the function's text; `None` for other values
+    pub fn help(&self, interner: &Rodeo) -> Option<String> {
+        match self {
+            Value::Function { doc: Some(doc), .. } => Some(interner.resolve(doc).to_string()),
+            Value::Native(native) => Some(match &native.doc {
+                Some(doc) => format!("{}\n{}", native.signature(), doc),
+                None => native.signature(),
+            }),
+            Value::Builtin(builtin) => Some(format!("{}\n{}", builtin.signature(), builtin.doc)),
+            Value::Memo { function, .. } => function.help(interner),
+            value if value.is_function() => Some(value.format(interner)),
+            _ => None,
         }
     }
 }
@@ -126,6 +594,9 @@ pub struct Environment {
     bindings: HashMap<InternedString, Value>,
     /// Parent environment for lexical scoping
     parent: Option<Arc<Environment>>,
+    /// Name the function closing over this scope was assigned to; it is
+    /// rebound to the function on each call so the function can recurse
+    self_name: Option<InternedString>,
 }
 
 impl Environment {
@@ -134,6 +605,7 @@ impl Environment {
         Self {
             bindings: HashMap::new(),
             parent: None,
+            self_name: None,
         }
     }
 
@@ -142,9 +614,24 @@ impl Environment {
         Self {
             bindings: HashMap::new(),
             parent: Some(parent),
+            self_name: None,
+        }
+    }
+
+    /// Scope for a function assigned to `name`, in which `name` refers to
+    /// the function itself
+    pub fn recursive(parent: Arc<Environment>, name: InternedString) -> Self {
+        Self {
+            self_name: Some(name),
+            ..Self::with_parent(parent)
         }
     }
 
+    /// Name the function closing over this scope refers to itself by
+    pub fn self_name(&self) -> Option<InternedString> {
+        self.self_name
+    }
+
     /// Bind a value to a name in the current environment
     pub fn define(&mut self, name: String, value: Value, interner: &mut Rodeo) {
         let interned_name = interner.get_or_intern(&name);
@@ -189,14 +676,29 @@ impl Environment {
         None
     }
 
+    /// Look up a value by interned name in this environment and its
+    /// parents, short of the root: the scopes of the calls in progress
+    pub fn lookup_above_root(&self, name: InternedString) -> Option<&Value> {
+        let parent = self.parent.as_ref()?;
+        match self.bindings.get(&name) {
+            Some(value) => Some(value),
+            None => parent.lookup_above_root(name),
+        }
+    }
+
     /// Look up a value by name, returning an error if not found
     pub fn get(&self, name: &str, node: Node, interner: &mut Rodeo) -> Result<&Value, EvalError> {
-        self.lookup(name, interner).ok_or_else(|| {
-            EvalError::new(
-                EvalErrorKind::Other(format!("Undefined variable: {}", name)),
-                node,
-            )
-        })
+        if let Some(value) = self.lookup(name, interner) {
+            return Ok(value);
+        }
+        let names = self.visible_names_interned();
+        let mut candidates: Vec<&str> = names.iter().map(|name| interner.resolve(name)).collect();
+        for builtin in builtins::names() {
+            candidates.push(builtin);
+        }
+        let fix = suggest::closest(name, candidates);
+        let kind = EvalErrorKind::UndefinedVariable(name.to_string());
+        Err(EvalError::new(kind, node).with_suggestion(fix.map(String::from)))
     }
 
     /// Check if a name is defined in this environment (not searching parents)
@@ -228,6 +730,16 @@ impl Environment {
         self.bindings.keys().copied().collect()
     }
 
+    /// Get every name visible from this environment, in it or its parents,
+    /// as interned strings
+    pub fn visible_names_interned(&self) -> Vec<InternedString> {
+        let mut names = self.local_names_interned();
+        if let Some(parent) = &self.parent {
+            names.extend(parent.visible_names_interned());
+        }
+        names
+    }
+
     /// Create a new child environment for function calls
     pub fn extend(&self) -> Environment {
         Environment::with_parent(Arc::new(self.clone()))
@@ -242,8 +754,8 @@ impl Environment {
     ) -> Result<Environment, EvalError> {
         if params.len() != args.len() {
             return Err(EvalError::new(
-                EvalErrorKind::Other(format!(
-                    "Arity mismatch: expected {} arguments, got {}",
+                EvalErrorKind::ArityMismatch(format!(
+                    "expected {} arguments, got {}",
                     params.len(),
                     args.len()
                 )),
@@ -270,8 +782,8 @@ impl Environment {
     ) -> Result<Environment, EvalError> {
         if params.len() != args.len() {
             return Err(EvalError::new(
-                EvalErrorKind::Other(format!(
-                    "Arity mismatch: expected {} arguments, got {}",
+                EvalErrorKind::ArityMismatch(format!(
+                    "expected {} arguments, got {}",
                     params.len(),
                     args.len()
                 )),
@@ -288,7 +800,7 @@ impl Environment {
             temp_bindings.push((param_str, arg));
         }
 
-        // Create child environment (still uses regular HashMap for permanent storage)
+        // Create child environment, sharing the bindings of this one
         let mut child_env = self.extend();
 
         // Bind parameters from arena-allocated temporaries
@@ -378,6 +890,25 @@ mod tests {
         assert!(!child.has_local("x", &mut interner)); // Not in local scope
     }
 
+    #[test]
+    fn test_clones_share_bindings() {
+        let mut interner = Rodeo::default();
+        let mut env = Environment::new();
+        for i in 0..1000 {
+            env.define(format!("v{}", i), Value::Integer(i), &mut interner);
+        }
+        let mut clone = env.clone();
+        assert!(clone.bindings.ptr_eq(&env.bindings));
+
+        // Writes to either leave the other as it was
+        clone.define("v0".to_string(), Value::Integer(-1), &mut interner);
+        env.define("new".to_string(), Value::Integer(1), &mut interner);
+        assert_eq!(env.lookup("v0", &mut interner), Some(&Value::Integer(0)));
+        assert_eq!(clone.lookup("v0", &mut interner), Some(&Value::Integer(-1)));
+        assert!(!clone.has("new", &mut interner));
+        assert_eq!((env.size(), clone.size()), (1001, 1000));
+    }
+
     #[test]
     fn test_parameter_binding() {
         let mut interner = Rodeo::default();
@@ -419,6 +950,49 @@ mod tests {
         assert_eq!(func.body_source(&interner), Some("x+1".to_string()));
     }
 
+    #[test]
+    fn test_value_compare_and_format() {
+        let interner = Rodeo::default();
+        let a = Value::List(vec![Value::Integer(1), Value::Integer(2)]);
+        let b = Value::List(vec![Value::Integer(1), Value::Integer(3)]);
+
+        assert_eq!(a.compare(&b), Some(Ordering::Less));
+        assert_eq!(
+            Value::Integer(2).compare(&Value::Integer(2)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(Value::Integer(1).compare(&a), None);
+
+        assert_eq!(a.format(&interner), "1 2");
+        assert_eq!(Value::List(vec![Value::Integer(7)]).format(&interner), ",7");
+        assert_eq!(Value::List(vec![]).format(&interner), "()");
+        let bools = Value::List(vec![Value::Boolean(false), Value::Boolean(true)]);
+        assert_eq!(bools.format(&interner), "01b");
+        assert_eq!(Value::Boolean(true).format(&interner), "1b");
+        let syms = Value::List(vec![Value::Symbol("a".into()), Value::Symbol("b".into())]);
+        assert_eq!(syms.format(&interner), "`a`b");
+        assert_eq!(
+            Value::List(vec![Value::Integer(1), a.clone()]).format(&interner),
+            "(1;1 2)"
+        );
+    }
+
+    #[test]
+    fn test_string_values() {
+        let interner = Rodeo::default();
+        let hello = Value::string("hello");
+        assert_eq!(hello.as_string().as_deref(), Some("hello"));
+        assert_eq!(hello.format(&interner), "\"hello\"");
+        assert_eq!(Value::string("a\"b").format(&interner), "\"a\\\"b\"");
+        assert_eq!(Value::string("a").format(&interner), ",\"a\"");
+        assert_eq!(Value::Char('a').format(&interner), "\"a\"");
+        assert_eq!(Value::Char('a').as_string(), None);
+        assert_eq!(
+            Value::string("ab").compare(&Value::string("b")),
+            Some(Ordering::Less)
+        );
+    }
+
     #[test]
     fn test_environment_properties() {
         let mut interner = Rodeo::default();
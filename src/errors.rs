@@ -1,4 +1,5 @@
-use miette::{Diagnostic, NamedSource, SourceSpan};
+use miette::{Diagnostic, LabeledSpan, NamedSource, SourceSpan};
+use std::fmt;
 use thiserror::Error;
 use tree_sitter::Node;
 
@@ -20,12 +21,24 @@ impl From<Node<'_>> for Span {
     }
 }
 
+/// The line and column, each counted from 1, of the byte at `offset` in
+/// `source`
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
 impl From<Span> for SourceSpan {
     fn from(span: Span) -> Self {
         (span.start, span.end - span.start).into()
     }
 }
 
+/// Source code attached to an error for reporting
+pub type Source = Box<NamedSource<String>>;
+
 /// Error that occurs during parse tree evaluation
 #[derive(Error, Debug)]
 #[error("{kind}")]
@@ -35,9 +48,42 @@ pub struct EvalError {
 
     pub span: SourceSpan,
 
-    pub src: Option<NamedSource<String>>,
+    /// Boxed to keep the error small, as it is returned all through
+    /// evaluation
+    pub src: Option<Source>,
+
+    /// Function calls in progress when the error occurred, innermost first
+    pub stack: Vec<Frame>,
+
+    /// What was probably meant instead of the source at the span, such as
+    /// the name nearest an undefined one; see [`crate::suggest`]
+    pub suggestion: Option<String>,
+}
+
+/// A function call in progress when an error occurred
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// Name of the function called, or `None` for a lambda
+    pub function: Option<String>,
+    /// Span of the call in the source it was made from
+    pub span: SourceSpan,
+    /// Text of the call, once the source it was made from is known
+    pub call: Option<String>,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "in {}", self.function.as_deref().unwrap_or("lambda"))?;
+        match &self.call {
+            Some(call) => write!(f, ", called as {}", call),
+            None => Ok(()),
+        }
+    }
 }
 
+/// Frames of a stack trace shown before the rest are summarized
+const SHOWN_FRAMES: usize = 16;
+
 /// Specific kinds of errors that can occur during evaluation
 #[derive(Error, Debug)]
 pub enum EvalErrorKind {
@@ -68,6 +114,64 @@ pub enum EvalErrorKind {
     #[error("Missing operand")]
     MissingOperand,
 
+    #[error("Type error: {0}")]
+    Type(String),
+
+    #[error("Undefined variable: {0}")]
+    UndefinedVariable(String),
+
+    /// A function given a different number of arguments than it takes
+    #[error("Arity mismatch: {0}")]
+    ArityMismatch(String),
+
+    /// Applying a value that is neither a function nor indexable like one
+    #[error("Cannot call non-function value: {0}")]
+    NotCallable(String),
+
+    /// Lists that must be as long as each other are not
+    #[error("Length mismatch: {0}")]
+    LengthMismatch(String),
+
+    #[error("Index out of range: {0}")]
+    IndexOutOfRange(String),
+
+    /// A dict key or table column that is not there
+    #[error("Key not found: {0}")]
+    KeyNotFound(String),
+
+    /// An argument of the right type outside the values an operation is
+    /// defined for, such as a singular matrix to invert
+    #[error("Domain error: {0}")]
+    Domain(String),
+
+    /// Source that does not parse, such as a function body or the text
+    /// given to `eval`
+    #[error("{0}")]
+    Syntax(String),
+
+    #[error("Recursion limit exceeded: more than {0} nested calls")]
+    RecursionLimitExceeded(usize),
+
+    #[error("Iteration limit exceeded: more than {0} passes of a loop")]
+    IterationLimitExceeded(usize),
+
+    /// A value would take more memory than the evaluator allows; see
+    /// [`crate::memory`]
+    #[error("Memory limit exceeded: a value would take more than {0} bytes")]
+    MemoryLimitExceeded(usize),
+
+    /// Evaluation stopped through its [`crate::cancel::CancelToken`]
+    #[error("Evaluation cancelled")]
+    Cancelled,
+
+    /// Evaluation ran past its deadline; see [`crate::cancel`]
+    #[error("Evaluation timed out")]
+    TimedOut,
+
+    /// Error raised by user code with `'msg` or `error[msg]`
+    #[error("{0}")]
+    Signal(String),
+
     #[error("{0}")]
     Other(String),
 }
@@ -85,6 +189,21 @@ impl EvalErrorKind {
             EvalErrorKind::InvalidNumber(_) => "INVALID_NUMBER",
             EvalErrorKind::UnknownOperator(_) => "UNKNOWN_OPERATOR",
             EvalErrorKind::MissingOperand => "MISSING_OPERAND",
+            EvalErrorKind::Type(_) => "TYPE_ERROR",
+            EvalErrorKind::UndefinedVariable(_) => "UNDEFINED_VARIABLE",
+            EvalErrorKind::ArityMismatch(_) => "ARITY_MISMATCH",
+            EvalErrorKind::NotCallable(_) => "NOT_CALLABLE",
+            EvalErrorKind::LengthMismatch(_) => "LENGTH_MISMATCH",
+            EvalErrorKind::IndexOutOfRange(_) => "INDEX_OUT_OF_RANGE",
+            EvalErrorKind::KeyNotFound(_) => "KEY_NOT_FOUND",
+            EvalErrorKind::Domain(_) => "DOMAIN_ERROR",
+            EvalErrorKind::Syntax(_) => "SYNTAX_ERROR",
+            EvalErrorKind::RecursionLimitExceeded(_) => "RECURSION_LIMIT_EXCEEDED",
+            EvalErrorKind::IterationLimitExceeded(_) => "ITERATION_LIMIT_EXCEEDED",
+            EvalErrorKind::MemoryLimitExceeded(_) => "MEMORY_LIMIT_EXCEEDED",
+            EvalErrorKind::Cancelled => "CANCELLED",
+            EvalErrorKind::TimedOut => "TIMED_OUT",
+            EvalErrorKind::Signal(_) => "SIGNAL",
             EvalErrorKind::Other(_) => "OTHER_ERROR",
         }
     }
@@ -96,6 +215,27 @@ impl Diagnostic for EvalError {
     fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
         Some(Box::new(self.kind.code().to_string()))
     }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        Some(self.src.as_deref()? as &dyn miette::SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        // Only a span within the attached source can be shown
+        let src = self.src.as_deref()?;
+        let end = self.span.offset() + self.span.len();
+        let label = match &self.suggestion {
+            Some(fix) => {
+                LabeledSpan::new_with_span(Some(format!("did you mean `{}`?", fix)), self.span)
+            }
+            None => LabeledSpan::underline(self.span),
+        };
+        (end <= src.inner().len()).then(|| Box::new(std::iter::once(label)) as _)
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        (!self.stack.is_empty()).then(|| Box::new(self.trace().join("\n")) as Box<dyn fmt::Display>)
+    }
 }
 
 impl EvalError {
@@ -105,12 +245,96 @@ impl EvalError {
             kind,
             span: Span::from(node).into(),
             src: None,
+            stack: Vec::new(),
+            suggestion: None,
+        }
+    }
+
+    /// Suggests `fix` in place of the source at the error's span.
+    pub fn with_suggestion(mut self, fix: Option<String>) -> Self {
+        self.suggestion = fix;
+        self
+    }
+
+    /// Records that the error occurred in a call to `function` made at
+    /// `node`
+    pub fn called_from(mut self, function: Option<String>, node: Node) -> Self {
+        self.stack.push(Frame {
+            function,
+            span: Span::from(node).into(),
+            call: None,
+        });
+        self
+    }
+
+    /// Fills in the text of the calls made from `source`: those recorded
+    /// since the error left the code `source` is the text of
+    pub fn locate(mut self, source: &str) -> Self {
+        for frame in self.stack.iter_mut().rev() {
+            if frame.call.is_some() {
+                break;
+            }
+            let start = frame.span.offset();
+            let call = source.get(start..start + frame.span.len());
+            frame.call = Some(call.unwrap_or_default().to_string());
         }
+        self
+    }
+
+    /// The stack trace, a line per call, innermost first; long traces
+    /// elide their middle calls
+    pub fn trace(&self) -> Vec<String> {
+        let frames = &self.stack;
+        if frames.len() <= SHOWN_FRAMES {
+            return frames.iter().map(Frame::to_string).collect();
+        }
+        let half = SHOWN_FRAMES / 2;
+        let mut lines: Vec<String> = frames[..half].iter().map(Frame::to_string).collect();
+        lines.push(format!("... {} more calls", frames.len() - SHOWN_FRAMES));
+        lines.extend(frames[frames.len() - half..].iter().map(Frame::to_string));
+        lines
     }
 
-    /// Attaches the source code to the error for reporting.
-    pub fn with_source(mut self, source: &str) -> Self {
-        self.src = Some(NamedSource::new("calc", source.to_string()));
+    /// Attaches the source code to the error for reporting, unless the
+    /// source its span is in is already attached.
+    pub fn with_source(self, source: &str) -> Self {
+        self.with_named_source("calc", source)
+    }
+
+    /// Attaches the source code as [`EvalError::with_source`] does, under
+    /// `name`, such as the path of the file it was read from.
+    pub fn with_named_source(mut self, name: &str, source: &str) -> Self {
+        if self.src.is_none() {
+            self.src = Some(Box::new(NamedSource::new(name, source.to_string())));
+        }
+        self
+    }
+
+    /// Moves the span of an error in the function body `body` to where the
+    /// body starts at `offset` in `source`, and attaches that.
+    ///
+    /// The span is in the body if no source is attached yet, or if the body
+    /// is attached, as it is for errors in lambdas defined in the body.
+    pub fn offset_by(self, body: &str, offset: usize, source: &str) -> Self {
+        self.shifted(body, offset).with_source(source)
+    }
+
+    /// Moves the span of an error in `statement`, which starts at `offset`
+    /// in the script `script` called `name`, to the script, and attaches
+    /// that; see [`crate::script`].
+    pub fn in_script(self, statement: &str, offset: usize, name: &str, script: &str) -> Self {
+        self.shifted(statement, offset)
+            .with_named_source(name, script)
+    }
+
+    /// Moves the span of an error in `text` by `offset`, if it is in `text`,
+    /// and detaches `text`
+    fn shifted(mut self, text: &str, offset: usize) -> Self {
+        let attached = self.src.as_ref().map(|src| src.inner().as_str());
+        if attached.is_none_or(|attached| attached == text) {
+            self.span = (self.span.offset() + offset, self.span.len()).into();
+            self.src = None;
+        }
         self
     }
 }
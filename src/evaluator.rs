@@ -1,16 +1,42 @@
-use crate::environment::{Environment, Value};
+use crate::arithmetic::{self, OverflowMode};
+use crate::attributes;
+use crate::bucket;
+use crate::builtins;
+use crate::bytecode::{Code, CodeCache, Compiler, Op};
+use crate::cancel::CancelToken;
+use crate::environment::{Environment, INFINITY_INTEGER, ListItems, NULL_INTEGER, Origin, Value};
 use crate::errors::{EvalError, EvalErrorKind};
-use crate::interning::InternedString;
+use crate::fill;
+use crate::format::Printer;
+use crate::interning::{InternedString, Symbol};
+use crate::kernels;
+use crate::lookup;
+use crate::memory;
+use crate::operators;
+use crate::parallel;
 use crate::parser::{parse_expression, query_expression};
+use crate::random;
+use crate::script::Script;
+use crate::table::{self, Column, Table};
+use crate::temporal;
+use crate::vectors;
+use crate::warnings::{self, Warning};
 use bumpalo::Bump;
 use lasso::Rodeo;
 use miette::Report;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 use tree_sitter::Node;
 
 // Type aliases for cleaner code
 type EvalInternedStringListResult = Result<Vec<InternedString>, EvalError>;
-type EvalValueListResult = Result<Vec<Value>, EvalError>;
+type EvalArgSlotsResult = Result<Vec<Option<Value>>, EvalError>;
+type EvalRowsResult = Result<Vec<usize>, EvalError>;
+type EvalIndexedRowsResult = Result<Option<Vec<usize>>, EvalError>;
+type EvalSourceResult = Result<(Table, Option<Symbol>), EvalError>;
+type EvalColumnsResult = Result<Vec<(Symbol, Value)>, EvalError>;
 
 fn get_node_text<'a>(node: Node<'a>, source: &'a str) -> Result<&'a str, String> {
     node.utf8_text(source.as_bytes()).map_err(|e| e.to_string())
@@ -35,52 +61,340 @@ fn calculate_factorial(n: i64, node: Node) -> Result<i64, EvalError> {
     Ok(result)
 }
 
-fn evaluate_binary_operation(
-    left: i64,
-    right: i64,
-    op: &str,
-    node: Node,
-    op_node: Node,
-) -> Result<i64, EvalError> {
-    match op {
-        "+" => left
-            .checked_add(right)
-            .ok_or_else(|| EvalError::new(EvalErrorKind::IntegerOverflow("addition".into()), node)),
-        "-" => left.checked_sub(right).ok_or_else(|| {
-            EvalError::new(EvalErrorKind::IntegerOverflow("subtraction".into()), node)
-        }),
-        "*" => left.checked_mul(right).ok_or_else(|| {
-            EvalError::new(
-                EvalErrorKind::IntegerOverflow("multiplication".into()),
+/// Fill the empty slots of a projection with `new` arguments, in order
+///
+/// Empty new slots stay empty, and any arguments left over are appended.
+fn fill_slots(bound: Vec<Option<Value>>, new: Vec<Option<Value>>) -> Vec<Option<Value>> {
+    let mut new = new.into_iter();
+    let mut filled: Vec<Option<Value>> = bound
+        .into_iter()
+        .map(|slot| slot.or_else(|| new.next().flatten()))
+        .collect();
+    filled.extend(new);
+    filled
+}
+
+/// Rows of a table of `len` rows kept by a select condition's result: a
+/// boolean per row, or a single boolean for all of them
+fn filter_rows(mask: &Value, len: usize, node: Node) -> EvalRowsResult {
+    let wrong = |found: String| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "where condition must give {} booleans, got {}",
+                len, found
+            )),
+            node,
+        )
+    };
+    match mask {
+        Value::Boolean(true) => Ok((0..len).collect()),
+        Value::Boolean(false) => Ok(Vec::new()),
+        Value::List(items) if items.len() == len => items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| match item {
+                Value::Boolean(keep) => keep.then_some(Ok(i)),
+                other => Some(Err(wrong(other.type_name().to_string()))),
+            })
+            .collect(),
+        Value::List(items) => Err(wrong(format!("{} items", items.len()))),
+        other => Err(wrong(other.type_name().to_string())),
+    }
+}
+
+/// Name of a selected column that was not given one: the first column of
+/// `table` the expression mentions, as in q, or `x` if it mentions none
+fn column_name<'a>(node: Node, src: &'a str, table: &Table) -> &'a str {
+    mentioned_column(node, src, table).unwrap_or("x")
+}
+
+fn mentioned_column<'a>(node: Node, src: &'a str, table: &Table) -> Option<&'a str> {
+    if node.kind() == "identifier" {
+        let name = node.utf8_text(src.as_bytes()).ok()?;
+        return table.column(name).is_some().then_some(name);
+    }
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.named_children(&mut cursor).collect();
+    children
+        .into_iter()
+        .find_map(|child| mentioned_column(child, src, table))
+}
+
+/// `node` without the layers of the grammar around a single expression,
+/// such as a comparison without an operator
+fn innermost(node: Node) -> Node {
+    let layer = matches!(
+        node.kind(),
+        "statement"
+            | "expression"
+            | "primary"
+            | "dyadic"
+            | "comparison"
+            | "additive"
+            | "multiplicative"
+            | "unary"
+            | "power"
+            | "postfix"
+    );
+    match node.named_child(0) {
+        Some(child)
+            if layer
+                && node.named_child_count() == 1
+                && node.child_by_field_name("operator").is_none() =>
+        {
+            innermost(child)
+        }
+        _ => node,
+    }
+}
+
+/// Whether the value of `node` is used somewhere a list's attribute can be
+/// exploited: bound to a name, passed to or returned from a function, or
+/// shown as the result; elsewhere, as an operand of `+` say, the list is
+/// plain
+fn keeps_attributes(node: Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return true;
+    };
+    match parent.kind() {
+        "assignment" | "argument_list" | "application" | "source_file" | "block" => true,
+        // A query's columns keep them in the table it gives
+        "select_column" => true,
+        // Layers of the grammar the value passes through unchanged
+        "statement" | "expression" | "primary" | "conditional" => keeps_attributes(parent),
+        // Lookups search lists through their attributes
+        "dyadic"
+            if parent
+                .child_by_field_name("operator")
+                .is_some_and(|op| matches!(op.kind(), "?" | "in" | "within")) =>
+        {
+            true
+        }
+        "dyadic" | "comparison" | "additive" | "multiplicative" | "unary" | "power" | "postfix"
+            if parent.child_by_field_name("operator").is_none() =>
+        {
+            keeps_attributes(parent)
+        }
+        _ => false,
+    }
+}
+
+/// Whether the value of `node` may stay a typed vector: where it would keep
+/// an attribute, other than as the result shown or a lookup's operand, and
+/// as an operand of arithmetic, which the kernels read directly
+fn keeps_vectors(node: Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    let operator = parent.child_by_field_name("operator").is_some();
+    match parent.kind() {
+        "assignment" | "argument_list" | "application" | "block" => true,
+        "statement" | "expression" | "primary" | "conditional" => keeps_vectors(parent),
+        "additive" | "multiplicative" if operator => true,
+        "dyadic" | "comparison" | "additive" | "multiplicative" | "unary" | "power" | "postfix"
+            if !operator =>
+        {
+            keeps_vectors(parent)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `node` gives a value computed elsewhere unchanged, and already
+/// measured against the memory limit: a name, or a layer of the grammar
+fn passes_through(node: Node) -> bool {
+    match node.kind() {
+        "source_file" | "statement" | "expression" | "identifier" => true,
+        "dyadic" | "comparison" | "additive" | "multiplicative" | "unary" | "power" | "postfix" => {
+            node.child_by_field_name("operator").is_none()
+        }
+        _ => false,
+    }
+}
+
+/// Default maximum number of nested function calls
+pub const DEFAULT_MAX_DEPTH: usize = 512;
+
+/// Default number of compiled function bodies kept for reuse
+pub const DEFAULT_CODE_CACHE_CAPACITY: usize = 256;
+
+/// Default length from which lists are split across threads, when parallel
+/// execution is enabled
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 100_000;
+
+/// Settings of an [`Evaluator`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvaluatorConfig {
+    /// Whether operations on long lists are split across threads; see
+    /// [`crate::parallel`]
+    pub parallel: bool,
+    /// Length from which a list is long enough to split
+    pub parallel_threshold: usize,
+}
+
+impl Default for EvaluatorConfig {
+    fn default() -> Self {
+        Self {
+            parallel: false,
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+        }
+    }
+}
+
+/// Default maximum number of passes of a `do` or `while` loop
+pub const DEFAULT_MAX_ITERATIONS: usize = 1_000_000;
+
+/// Nodes evaluated between looks at the clock, when there is a deadline
+const CLOCK_INTERVAL: usize = 256;
+
+/// Stack left below which a function call moves to a freshly allocated
+/// segment, and the size of that segment. Each call recurses through the
+/// whole precedence chain, so nesting is bounded by `max_depth` rather than
+/// by the thread's stack.
+const STACK_RED_ZONE: usize = 256 * 1024;
+const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+/// The evaluator as seen by higher-order builtins, applying functions in the
+/// environment of the call that invoked the builtin
+struct Applier<'e, 'a> {
+    evaluator: &'e mut Evaluator,
+    env: &'a Environment,
+    arena: &'a Bump,
+}
+
+impl builtins::Apply for Applier<'_, '_> {
+    fn apply(
+        &mut self,
+        function: &Value,
+        args: Vec<Value>,
+        node: Node,
+    ) -> Result<Value, EvalError> {
+        let slots = args.into_iter().map(Some).collect();
+        self.evaluator.apply_slots_with_arena(
+            function.clone(),
+            slots,
+            node,
+            node,
+            self.env,
+            self.arena,
+        )
+    }
+
+    fn context(&mut self) -> &mut builtins::Context {
+        &mut self.evaluator.context
+    }
+
+    fn interner(&self) -> &Rodeo {
+        &self.evaluator.string_interner
+    }
+
+    fn evaluate(
+        &mut self,
+        source: &str,
+        bindings: builtins::Bindings,
+        node: Node,
+    ) -> Result<Value, EvalError> {
+        let tree = parse_expression(source)
+            .map_err(|e| EvalError::new(EvalErrorKind::Other(e.to_string()), node))?;
+        if tree.root_node().has_error() {
+            return Err(EvalError::new(
+                EvalErrorKind::Syntax(format!("Syntax error in {}", source)),
                 node,
-            )
-        }),
-        "/" => {
-            if right == 0 {
-                Err(EvalError::new(EvalErrorKind::DivisionByZero, node))
-            } else {
-                Ok(left / right)
-            }
+            ));
         }
-        "%" => {
-            if right == 0 {
-                Err(EvalError::new(EvalErrorKind::DivisionByZero, node))
-            } else {
-                Ok(left % right)
-            }
+        let mut env = self.env.clone();
+        for (name, value) in bindings {
+            let name = self.evaluator.intern(&name);
+            env.define_interned(name, value);
         }
-        _ => Err(EvalError::new(
-            EvalErrorKind::UnknownOperator(op.into()),
-            op_node,
-        )),
+        self.evaluator
+            .eval_with_env_and_arena(tree.root_node(), source, &mut env, self.arena)
+            .map_err(|e| e.locate(source).with_source(source))
+    }
+
+    fn run(&mut self, script: &Script, node: Node) -> Result<Value, EvalError> {
+        let mut env = self.env.clone();
+        let outer = self.evaluator.script_depth.replace(self.evaluator.depth);
+        let result = self
+            .evaluator
+            .run_script(script, node, &mut env, self.arena);
+        self.evaluator.script_depth = outer;
+        result
     }
 }
 
+/// The first node under `node` that failed to parse
+fn first_error(node: Node) -> Node {
+    let mut cursor = node.walk();
+    let child = (node.children(&mut cursor)).find(|child| child.has_error());
+    match child {
+        Some(child) if !child.is_error() && !child.is_missing() => first_error(child),
+        Some(child) => child,
+        None => node,
+    }
+}
+
+/// The namespace a dotted name is in: `.mylib` for `.mylib.f`, or `None`
+/// for a name at the root such as `f` or `.f`
+fn namespace_of(name: &str) -> Option<&str> {
+    let (namespace, _) = name.rsplit_once('.')?;
+    (name.starts_with('.') && !namespace.is_empty()).then_some(namespace)
+}
+
+/// Names bound to values, in the order they were bound
+type Bindings = Vec<(InternedString, Value)>;
+
 /// Visitor struct that encapsulates evaluation logic with environment support.
 /// Each evaluator instance maintains its own session-scoped string interner.
 pub struct Evaluator {
     /// Session-scoped string interner for this evaluator instance
     string_interner: Rodeo,
+    /// State available to builtins
+    context: builtins::Context,
+    /// Number of function calls currently being evaluated
+    depth: usize,
+    /// Nested calls allowed before evaluation fails instead of overflowing
+    /// the stack
+    max_depth: usize,
+    /// Passes of a single loop allowed before evaluation fails instead of
+    /// running on
+    max_iterations: usize,
+    /// Calls of [`Evaluator::eval_with_env`] in progress
+    entered: usize,
+    /// Stops evaluation when cancelled; see [`crate::cancel`]
+    cancel: CancelToken,
+    /// When evaluation in progress must stop by, if ever
+    deadline: Option<Instant>,
+    /// Checks for cancellation since the clock was last read
+    checks: usize,
+    /// Bytes any one value computed may take, if limited
+    memory_limit: Option<usize>,
+    /// Bytes the largest value computed took, while that is being tracked
+    peak_memory: Option<usize>,
+    /// Bytes all the values computed took together, while tracked
+    allocated_memory: Option<usize>,
+    /// Warnings about the inputs evaluated, not yet taken; see
+    /// [`crate::warnings`]
+    warnings: Vec<Warning>,
+    /// Globals set with `::` inside function calls, bound in the top-level
+    /// environment when the outermost evaluation returns and looked up
+    /// ahead of it until then
+    globals: Bindings,
+    /// Call depth of the script being loaded, whose assignments bind
+    /// globals; see [`crate::script`]
+    script_depth: Option<usize>,
+    /// Namespace undotted names are looked up and defined in first, such as
+    /// `.mylib`; `None` at the root
+    namespace: Option<String>,
+    /// Recently compiled function bodies
+    code_cache: CodeCache,
+    /// The input lambdas were last defined from, shared by their
+    /// [`Origin`]s
+    origin_source: Option<Arc<str>>,
+    /// Whether operations on constants in function bodies are folded when
+    /// they compile
+    fold_constants: bool,
+    config: EvaluatorConfig,
 }
 
 impl Default for Evaluator {
@@ -93,7 +407,268 @@ impl Evaluator {
     pub fn new() -> Self {
         Evaluator {
             string_interner: Rodeo::default(),
+            context: builtins::Context::default(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            entered: 0,
+            cancel: CancelToken::new(),
+            deadline: None,
+            checks: 0,
+            memory_limit: None,
+            peak_memory: None,
+            allocated_memory: None,
+            warnings: Vec::new(),
+            globals: Vec::new(),
+            script_depth: None,
+            namespace: None,
+            code_cache: CodeCache::new(DEFAULT_CODE_CACHE_CAPACITY),
+            origin_source: None,
+            fold_constants: true,
+            config: EvaluatorConfig::default(),
+        }
+    }
+
+    /// Create an evaluator with the settings in `config`
+    pub fn with_config(config: EvaluatorConfig) -> Self {
+        let mut evaluator = Self::new();
+        evaluator.set_config(config);
+        evaluator
+    }
+
+    /// Change the settings in [`EvaluatorConfig`]
+    pub fn set_config(&mut self, config: EvaluatorConfig) {
+        self.config = config;
+        self.context.parallel = config.parallel.then_some(config.parallel_threshold);
+    }
+
+    /// The settings in [`EvaluatorConfig`]
+    pub fn config(&self) -> EvaluatorConfig {
+        self.config
+    }
+
+    /// Create an evaluator allowing at most `max_depth` nested function calls
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            ..Self::new()
+        }
+    }
+
+    /// Maximum number of nested function calls
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Allow at most `max_iterations` passes of each `do` or `while` loop
+    pub fn set_max_iterations(&mut self, max_iterations: usize) {
+        self.max_iterations = max_iterations;
+    }
+
+    /// Maximum number of passes of a single loop
+    pub fn max_iterations(&self) -> usize {
+        self.max_iterations
+    }
+
+    /// A token that stops the evaluation in progress when cancelled, from
+    /// any thread
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Fail evaluations computing a value of more than `limit` bytes, or
+    /// allow any size with `None`, as by default; see [`crate::memory`]
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+    }
+
+    /// Bytes any one value computed may take, if limited
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
+
+    /// Measure each value computed from now on, to report the largest with
+    /// [`Evaluator::take_peak_memory`] and their total with
+    /// [`Evaluator::take_allocated_memory`]
+    ///
+    /// Measuring takes time in proportion to the size of each value, which
+    /// slows evaluation that builds large values.
+    pub fn track_memory(&mut self) {
+        self.peak_memory = Some(0);
+        self.allocated_memory = Some(0);
+    }
+
+    /// Bytes the largest value computed since [`Evaluator::track_memory`]
+    /// took, if it was called; stops tracking the largest
+    pub fn take_peak_memory(&mut self) -> Option<usize> {
+        self.peak_memory.take()
+    }
+
+    /// Bytes allocated since [`Evaluator::track_memory`], if it was called:
+    /// an estimate summing the size of every value computed, as if each were
+    /// built afresh; stops tracking the total
+    pub fn take_allocated_memory(&mut self) -> Option<usize> {
+        self.allocated_memory.take()
+    }
+
+    /// Warnings about the inputs evaluated since they were last taken
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Take the warnings about the inputs evaluated so far
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Fail if `bytes` are more than the memory limit allows
+    fn reserve(&self, bytes: usize, node: Node) -> Result<(), EvalError> {
+        match self.memory_limit {
+            Some(limit) if bytes > limit => Err(EvalError::new(
+                EvalErrorKind::MemoryLimitExceeded(limit),
+                node,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// `value`, if it is within the memory limit
+    fn measured(&mut self, value: Value, node: Node) -> Result<Value, EvalError> {
+        if self.memory_limit.is_some() || self.peak_memory.is_some() {
+            let bytes = memory::size_of(&value);
+            if let Some(peak) = &mut self.peak_memory {
+                *peak = (*peak).max(bytes);
+            }
+            if let Some(allocated) = &mut self.allocated_memory {
+                *allocated = allocated.saturating_add(bytes);
+            }
+            self.reserve(bytes, node)?;
+        }
+        Ok(value)
+    }
+
+    /// Keep the compiled code of at most `capacity` function bodies
+    pub fn set_code_cache_capacity(&mut self, capacity: usize) {
+        self.code_cache.set_capacity(capacity);
+    }
+
+    /// Fold operations on constants in function bodies as they compile, as
+    /// is done by default, or not; bodies already compiled keep their code
+    pub fn set_constant_folding(&mut self, fold: bool) {
+        self.fold_constants = fold;
+        self.code_cache.clear();
+    }
+
+    /// The cache of compiled function bodies
+    pub fn code_cache(&self) -> &CodeCache {
+        &self.code_cache
+    }
+
+    /// Choose what integer arithmetic does when a result overflows
+    pub fn set_overflow_mode(&mut self, mode: OverflowMode) {
+        self.context.overflow = mode;
+    }
+
+    /// What integer arithmetic does when a result overflows
+    pub fn overflow_mode(&self) -> OverflowMode {
+        self.context.overflow
+    }
+
+    /// Switch to the namespace `namespace`, such as `.mylib`, or back to the
+    /// root with `.`
+    ///
+    /// Top-level assignments to undotted names then define them in the
+    /// namespace, and undotted names are looked up there before the root.
+    pub fn set_namespace(&mut self, namespace: &str) -> Result<(), String> {
+        if namespace == "." {
+            self.namespace = None;
+            return Ok(());
+        }
+        let valid = namespace.strip_prefix('.').is_some_and(|path| {
+            path.split('.').all(|part| {
+                part.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+        });
+        if !valid {
+            return Err(format!(
+                "Invalid namespace: {} (expected a dotted name such as .mylib)",
+                namespace
+            ));
+        }
+        self.namespace = Some(namespace.to_string());
+        Ok(())
+    }
+
+    /// The current namespace, or `.` at the root
+    pub fn namespace(&self) -> &str {
+        self.namespace.as_deref().unwrap_or(".")
+    }
+
+    /// `name` in the current namespace, unless it is dotted already or
+    /// there is no current namespace
+    fn qualified(&self, name: &str) -> Option<String> {
+        match &self.namespace {
+            Some(namespace) if !name.starts_with('.') => Some(format!("{}.{}", namespace, name)),
+            _ => None,
+        }
+    }
+
+    /// The members of the namespace `name` as a dict from their names within
+    /// it, or `None` if it has none
+    fn namespace_members(&self, name: &str, env: &Environment) -> Option<Value> {
+        let prefix = format!("{}.", name);
+        let mut members: Vec<_> = env
+            .visible_names_interned()
+            .into_iter()
+            .filter_map(|key| {
+                let member = self.resolve(key).strip_prefix(&prefix)?;
+                let value = env.lookup_interned(key)?;
+                (!member.contains('.')).then_some((member, value))
+            })
+            .collect();
+        if members.is_empty() {
+            return None;
         }
+        // A name shadowed in a call scope is visible twice, with one value
+        members.sort_by_key(|(member, _)| *member);
+        members.dedup_by_key(|(member, _)| *member);
+        Some(Value::Dict {
+            keys: members
+                .iter()
+                .map(|(member, _)| Value::Symbol((*member).into()))
+                .collect(),
+            values: members
+                .into_iter()
+                .map(|(_, value)| value.clone())
+                .collect(),
+        })
+    }
+
+    /// Rows builtins have read from stored tables since the last call
+    pub fn take_rows_scanned(&mut self) -> usize {
+        std::mem::take(&mut self.context.rows_scanned)
+    }
+
+    /// Attach a database root for the `.db` builtins to create tables under
+    pub fn set_database<P: Into<PathBuf>>(&mut self, root: P) {
+        self.context.database = Some(root.into());
+    }
+
+    /// Open the attached database read-only: tables cannot be created or
+    /// written, and nothing under the root is modified
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.context.read_only = read_only;
+    }
+
+    /// Whether the database is open read-only
+    pub fn is_read_only(&self) -> bool {
+        self.context.read_only
+    }
+
+    /// Database root attached with [`Evaluator::set_database`], if any
+    pub fn database(&self) -> Option<&Path> {
+        self.context.database.as_deref()
     }
 
     /// Get a reference to the session-scoped string interner
@@ -111,6 +686,35 @@ impl Evaluator {
         self.string_interner.resolve(&key)
     }
 
+    /// Evaluate the statements of `script` one after another in `env`,
+    /// giving the value of the last, or an empty list if there are none;
+    /// `node` loaded the script
+    fn run_script(
+        &mut self,
+        script: &Script,
+        node: Node,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let mut last = Value::List(Vec::new());
+        for statement in script.statements() {
+            let text = statement.text;
+            let in_script =
+                |e: EvalError| e.in_script(text, statement.offset, &script.name, &script.text);
+            let tree = parse_expression(text)
+                .map_err(|e| EvalError::new(EvalErrorKind::Other(e.to_string()), node))?;
+            let root = tree.root_node();
+            if root.has_error() {
+                let kind = EvalErrorKind::Syntax("Syntax error in expression".to_string());
+                return Err(in_script(EvalError::new(kind, first_error(root))));
+            }
+            last = self
+                .eval_with_env_and_arena(root, text, env, arena)
+                .map_err(|e| in_script(e.locate(text)))?;
+        }
+        Ok(last)
+    }
+
     /// Evaluate a node with an environment, returning a Value
     /// This is the main public API that automatically uses bumpalo for temporaries
     pub fn eval_with_env(
@@ -121,16 +725,89 @@ impl Evaluator {
     ) -> Result<Value, EvalError> {
         // Create arena for this evaluation call - scoped to this invocation
         let arena = Bump::new();
-        self.eval_with_env_and_arena(node, src, env, &arena)
+        if self.entered == 0 {
+            self.cancel.take();
+            self.warnings.extend(warnings::check(node, src));
+        }
+        self.entered += 1;
+        let result = self
+            .eval_with_env_and_arena(node, src, env, &arena)
+            .map_err(|e| e.locate(src).with_source(src));
+        self.entered -= 1;
+        // Function calls only see snapshots of the environments they close
+        // over, so globals they set are bound once control is back here
+        if self.entered == 0 {
+            for (name, value) in self.globals.drain(..) {
+                env.define_interned(name, value);
+            }
+        }
+        result
     }
 
-    /// Internal evaluation method that uses provided bumpalo arena for temporaries
+    /// [`Evaluator::eval_with_env`], failing with
+    /// [`EvalErrorKind::TimedOut`] if evaluation runs past `deadline`, or
+    /// an earlier deadline already in force
+    pub fn eval_with_deadline(
+        &mut self,
+        node: Node<'_>,
+        src: &str,
+        env: &mut Environment,
+        deadline: Instant,
+    ) -> Result<Value, EvalError> {
+        let outer = self.deadline;
+        self.deadline = Some(outer.map_or(deadline, |outer| outer.min(deadline)));
+        let result = self.eval_with_env(node, src, env);
+        self.deadline = outer;
+        result
+    }
+
+    /// Fail if evaluation has been cancelled or has run past its deadline;
+    /// the clock is read only every [`CLOCK_INTERVAL`] checks
+    fn check_interrupt(&mut self, node: Node) -> Result<(), EvalError> {
+        if self.cancel.take() {
+            return Err(EvalError::new(EvalErrorKind::Cancelled, node));
+        }
+        if let Some(deadline) = self.deadline {
+            self.checks += 1;
+            if self.checks.is_multiple_of(CLOCK_INTERVAL) && Instant::now() >= deadline {
+                return Err(EvalError::new(EvalErrorKind::TimedOut, node));
+            }
+        }
+        Ok(())
+    }
+
+    /// Internal evaluation method that uses provided bumpalo arena for
+    /// temporaries; lists lose their attributes where nothing exploits them
     pub fn eval_with_env_and_arena(
         &mut self,
         node: Node<'_>,
         src: &str,
         env: &mut Environment,
         arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        self.check_interrupt(node)?;
+        let value = self.visit_with_env_and_arena(node, src, env, arena)?;
+        let value = if passes_through(node) {
+            value
+        } else {
+            self.measured(value, node)?
+        };
+        match value {
+            value @ Value::Attributed(_) if !keeps_attributes(node) => Ok(value.plain()),
+            value @ (Value::IntVector(_) | Value::FloatVector(_)) if !keeps_vectors(node) => {
+                Ok(value.plain())
+            }
+            value => Ok(value),
+        }
+    }
+
+    /// Evaluate `node` by its kind
+    fn visit_with_env_and_arena(
+        &mut self,
+        node: Node<'_>,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
     ) -> Result<Value, EvalError> {
         match node.kind() {
             // Handle source_file with multiple children
@@ -161,26 +838,65 @@ impl Evaluator {
                 })
             }
 
+            // Each statement gets its own span, nested in the request's
+            "statement" => {
+                let text = get_node_text(node, src).unwrap_or_default();
+                let _span = tracing::debug_span!("statement", text).entered();
+                let child = self.named_child(node)?;
+                let result = self.eval_with_env_and_arena(child, src, env, arena);
+                if let Err(e) = &result {
+                    tracing::debug!(code = e.kind.code(), error = %e.kind, "statement failed");
+                }
+                result
+            }
+
             // Delegate to child for wrapper nodes
-            "expression" | "statement" => {
+            "expression" => {
                 let child = self.named_child(node)?;
                 self.eval_with_env_and_arena(child, src, env, arena)
             }
 
-            // Arithmetic expressions (return integer values)
-            "number" => Ok(Value::Integer(self.visit_number_raw(node, src)?)),
-            "additive" | "multiplicative" => {
-                Ok(Value::Integer(self.visit_binary_raw(node, src, env)?))
+            // Precedence layers without an operator just pass their operand through
+            "dyadic" | "comparison" | "additive" | "multiplicative" | "unary" | "power"
+            | "postfix"
+                if node.child_by_field_name("operator").is_none() =>
+            {
+                let child = self.named_child(node)?;
+                self.eval_with_env_and_arena(child, src, env, arena)
             }
+
+            // Arithmetic expressions
+            "number" => self.visit_number_value(node, src),
+            "date" | "time" | "timestamp" => self.visit_temporal(node, src),
+            "boolean" => self.visit_boolean(node, src),
+            "symbol" => self.visit_symbol(node, src),
+            "string" => self.visit_string(node, src),
+            "additive" | "multiplicative" => self.visit_binary(node, src, env),
             "primary" => self.visit_primary_with_env(node, src, env),
-            "unary" => Ok(Value::Integer(self.visit_unary_raw(node, src, env)?)),
-            "power" => Ok(Value::Integer(self.visit_power_raw(node, src, env)?)),
+            "dyadic" | "comparison" => self.visit_dyadic_with_arena(node, src, env, arena),
+            "unary" => self.visit_unary(node, src, env),
+            "power" => self.visit_power(node, src, env),
+            "postfix" if node.child_by_field_name("values").is_some() => {
+                self.visit_dict_with_arena(node, src, env, arena)
+            }
             "postfix" => Ok(Value::Integer(self.visit_postfix_raw(node, src, env)?)),
 
             // Variable and function operations - use interned optimized versions
             "identifier" => self.visit_identifier_interned(node, src, env),
             "assignment" => self.visit_assignment_with_arena(node, src, env, arena),
             "function_call" => self.visit_function_call_with_arena(node, src, env, arena),
+            "application" => self.visit_application_with_arena(node, src, env, arena),
+            "function_body" => self.visit_function_body_with_arena(node, src, env, arena),
+            "conditional" => self.visit_conditional_with_arena(node, src, env, arena),
+            "loop" => self.visit_loop_with_arena(node, src, env, arena),
+            "trap" => self.visit_trap_with_arena(node, src, env, arena),
+            "signal" => self.visit_signal_with_arena(node, src, env, arena),
+            "select" => self.visit_select_with_arena(node, src, env, arena),
+            "update" => self.visit_update_with_arena(node, src, env, arena),
+            "delete" => self.visit_delete_with_arena(node, src, env, arena),
+
+            // List literals
+            "vector" | "list" => self.visit_list_with_arena(node, src, env, arena),
 
             other => Err(EvalError::new(
                 EvalErrorKind::Other(format!("Unexpected node type: {}", other)),
@@ -195,7 +911,7 @@ impl Evaluator {
         match self.eval_with_env(node, src, &mut env)? {
             Value::Integer(n) => Ok(n),
             _ => Err(EvalError::new(
-                EvalErrorKind::Other("Expected integer value".into()),
+                EvalErrorKind::Type("expected an integer value".into()),
                 node,
             )),
         }
@@ -227,8 +943,13 @@ impl Evaluator {
                 node,
             )
         })?;
-        txt.parse::<i64>()
-            .map_err(|e| EvalError::new(EvalErrorKind::InvalidNumber(e.to_string()), node))
+        // Hex and binary literals spell out all 64 bits, so 0xffffffffffffffff is -1
+        let parsed = match (txt.strip_prefix("0x"), txt.strip_prefix("0b")) {
+            (Some(hex), _) => u64::from_str_radix(hex, 16).map(|bits| bits as i64),
+            (_, Some(binary)) => u64::from_str_radix(binary, 2).map(|bits| bits as i64),
+            _ => txt.parse::<i64>(),
+        };
+        parsed.map_err(|e| EvalError::new(EvalErrorKind::InvalidNumber(e.to_string()), node))
     }
 
     // New environment-aware visitor methods
@@ -242,19 +963,40 @@ impl Evaluator {
     ) -> Result<Value, EvalError> {
         let name =
             get_node_text(node, src).map_err(|e| EvalError::new(EvalErrorKind::Other(e), node))?;
+        self.lookup(name, node, env)
+    }
+
+    /// The value of the identifier `name`, at `node`
+    fn lookup(&mut self, name: &str, node: Node, env: &Environment) -> Result<Value, EvalError> {
+        // An undotted name means the one in the current namespace, if any
+        if let Some(qualified) = self.qualified(name) {
+            let interned_name = self.intern(&qualified);
+            if let Some(value) = self.lookup_global(interned_name, env) {
+                return Ok(value.clone());
+            }
+        }
 
         // Intern the identifier name for efficient lookup using session-scoped interner
         let interned_name = self.intern(name);
 
-        // Try interned lookup first, fall back to string lookup for compatibility
-        if let Some(value) = env.lookup_interned(interned_name) {
+        // Try interned lookup first, then builtins, then string lookup for compatibility
+        if let Some(value) = self.lookup_global(interned_name, env) {
             Ok(value.clone())
+        } else if let Some(builtin) = builtins::lookup(name) {
+            Ok(Value::Builtin(builtin))
+        } else if let Some(members) = self.namespace_members(name, env) {
+            Ok(members)
         } else {
             env.get(name, node, &mut self.string_interner).cloned()
         }
     }
 
     /// Visit assignment with arena support: name: value or name: {body}
+    ///
+    /// `name:: value` sets the global `name`, unless the function being
+    /// called has a local of that name, which it sets instead, as in q. The
+    /// call sees the new value at once and the top-level environment once
+    /// the outermost evaluation returns.
     fn visit_assignment_with_arena(
         &mut self,
         node: Node,
@@ -272,15 +1014,89 @@ impl Evaluator {
         let name = get_node_text(name_node, src)
             .map_err(|e| EvalError::new(EvalErrorKind::Other(e), name_node))?;
 
-        let value = match value_node.kind() {
-            "function_body" => self.visit_function_body_with_arena(value_node, src, env, arena)?,
-            _ => self.eval_with_env_and_arena(value_node, src, env, arena)?,
-        };
+        let value = self.eval_with_env_and_arena(value_node, src, env, arena)?;
+        let global = self.op_text(self.child(node, "operator")?, src)? == "::";
+        Ok(self.assign(name, global, value, env))
+    }
+
+    /// The value of `name` in `env`, where a global set with `::` and not
+    /// yet bound at the top level stands in for the one there, which calls
+    /// see only a snapshot of
+    fn lookup_global<'a>(
+        &'a self,
+        name: InternedString,
+        env: &'a Environment,
+    ) -> Option<&'a Value> {
+        if self.globals.is_empty() {
+            return env.lookup_interned(name);
+        }
+        env.lookup_above_root(name)
+            .or_else(|| {
+                let set = self
+                    .globals
+                    .iter()
+                    .rev()
+                    .find(|(global, _)| *global == name);
+                set.map(|(_, value)| value)
+            })
+            .or_else(|| env.lookup_interned(name))
+    }
+
+    /// Bind `name` to `value`, as `name: value` or, if `global`,
+    /// `name:: value` does
+    fn assign(&mut self, name: &str, global: bool, value: Value, env: &mut Environment) -> Value {
+        // Assignments at the top level of a script bind where it was loaded
+        let global = global || self.script_depth == Some(self.depth);
+        // Call-local names stay as they are; others go in the current
+        // namespace
+        let local = self.depth > 0 && (!global || env.has_local(name, &mut self.string_interner));
+        let qualified = self.qualified(name).filter(|_| !local);
+        let name = qualified.as_deref().unwrap_or(name);
 
         // Intern the variable name for efficient storage and lookup
         let interned_name = self.intern(name);
+
+        let value = match value {
+            // A memoized function calls itself through the memo
+            Value::Memo { function, cache } => Value::Memo {
+                function: Box::new(Self::named(*function, interned_name)),
+                cache,
+            },
+            other => Self::named(other, interned_name),
+        };
+        if global && !local {
+            self.globals.push((interned_name, value.clone()));
+        } else if self.depth == 0 {
+            // Bound at the top level, so no longer pending
+            self.globals.retain(|(global, _)| *global != interned_name);
+        }
         env.define_interned(interned_name, value.clone());
-        Ok(value)
+        value
+    }
+
+    /// `value` knowing it is called `name`, if it is a function not yet named
+    ///
+    /// The closure was captured before the name was bound, so record the
+    /// name for the function to rebind when it calls itself.
+    fn named(value: Value, name: InternedString) -> Value {
+        match value {
+            Value::Function {
+                params,
+                body,
+                closure: Some(closure),
+                doc,
+                code,
+                origin,
+            } if closure.self_name().is_none() => Value::Function {
+                params,
+                body,
+                closure: Some(Arc::new(Environment::recursive(closure, name))),
+                doc,
+                code,
+                origin,
+            },
+            other => other,
+        }
     }
 
     /// Visit function body with arena support: {expr} or {[params] expr}
@@ -309,12 +1125,68 @@ impl Evaluator {
             .iter()
             .map(|&p| self.resolve(p).to_string())
             .collect();
-        Ok(Value::new_function(
+        let docstring = self
+            .docstring(body_node, src)
+            .map(|text| self.intern(&text));
+        let mut function = Value::new_function(
             &param_names,
             body_text,
             Some(Arc::new(env.clone())),
             &mut self.string_interner,
-        ))
+        );
+        let body_origin = u32::try_from(body_node.start_byte()).ok().map(|offset| {
+            Arc::new(Origin {
+                source: self.origin_source(src),
+                offset,
+            })
+        });
+        if let Value::Function {
+            doc,
+            code,
+            body,
+            origin,
+            ..
+        } = &mut function
+        {
+            *doc = docstring;
+            *code = self.code(*body);
+            *origin = body_origin;
+        }
+        Ok(function)
+    }
+
+    /// `src` shared with the lambdas already defined from it, so an input
+    /// defining many is kept once
+    fn origin_source(&mut self, src: &str) -> Arc<str> {
+        match &self.origin_source {
+            Some(source) if **source == *src => source.clone(),
+            _ => self.origin_source.insert(Arc::from(src)).clone(),
+        }
+    }
+
+    /// The docstring of a function body: the text of a string literal that
+    /// is the first of several statements, as in `{[x] "Add one"; x+1}`
+    ///
+    /// A body that is only a string returns it, and has no docstring. A
+    /// string with a bad escape is not a docstring; calls report the error.
+    fn docstring(&self, block: Node, src: &str) -> Option<String> {
+        let mut cursor = block.walk();
+        let statements: Vec<Node> = block.named_children(&mut cursor).collect();
+        let [first, _, ..] = statements.as_slice() else {
+            return None;
+        };
+        // Find the literal through any single-child wrappers around it
+        let mut node = *first;
+        while node.kind() != "string" && node.named_child_count() == 1 {
+            node = node.named_child(0).expect("the node has one named child");
+        }
+        if node.kind() != "string" {
+            return None;
+        }
+        match self.visit_string(node, src).ok()? {
+            Value::Char(c) => Some(c.to_string()),
+            text => text.as_string(),
+        }
     }
 
     /// Visit function call with arena support: func[args]
@@ -330,62 +1202,933 @@ impl Evaluator {
             .ok_or_else(|| EvalError::new(EvalErrorKind::MissingOperand, node))?;
         let args_node = node.child_by_field_name("args");
 
-        // Get function value using interned lookup
-        let func_value = self.visit_identifier_interned(func_node, src, env)?;
-        let (params, body, closure) = match func_value {
-            Value::Function {
-                params,
-                body,
-                closure,
-            } => (params, body, closure),
-            _ => {
-                return Err(EvalError::new(
-                    EvalErrorKind::Other("Cannot call non-function value".into()),
-                    func_node,
-                ));
-            }
+        // Get function value using interned lookup, or by evaluating what
+        // gives it, as for f[2][3]
+        let func_value = match func_node.kind() {
+            "identifier" => self.visit_identifier_interned(func_node, src, env)?,
+            _ => self.eval_with_env_and_arena(func_node, src, env, arena)?,
         };
 
         // Evaluate arguments using arena
-        let args = if let Some(args_node) = args_node {
+        let slots = if let Some(args_node) = args_node {
             self.extract_argument_list_with_arena(args_node, src, env, arena)?
         } else {
             vec![]
         };
 
-        // Create function execution environment using arena
-        let base_env = closure.as_ref().map(|c| c.as_ref()).unwrap_or(env);
-        let mut call_env = base_env.bind_parameters_with_arena(
-            &params,
-            &args,
-            node,
-            arena,
-            &mut self.string_interner,
-        )?;
-
-        // Parse and evaluate function body
-        let body_str = self.resolve(body).to_string();
-        let tree = parse_expression(&body_str).map_err(|e| {
-            EvalError::new(
-                EvalErrorKind::Other(format!("Function body parse error: {}", e)),
-                node,
-            )
-        })?;
-
-        self.eval_with_env_and_arena(tree.root_node(), &body_str, &mut call_env, arena)
+        self.apply_slots_with_arena(func_value, slots, node, func_node, env, arena)
     }
 
-    // Bumpalo-enhanced parameter/argument extraction methods
-
-    /// Extract parameter names using bumpalo for temporary allocations
-    /// Returns Vec<InternedString> for memory efficiency
-    fn extract_parameter_list_with_arena(
+    /// Visit prefix application with arena support: func arg
+    fn visit_application_with_arena(
         &mut self,
         node: Node,
         src: &str,
+        env: &mut Environment,
         arena: &Bump,
-    ) -> EvalInternedStringListResult {
-        // Use bumpalo Vec for temporary collection
+    ) -> Result<Value, EvalError> {
+        let func_node = self.child(node, "function")?;
+        let arg_node = self.child(node, "argument")?;
+
+        let func_value = self.visit_identifier_interned(func_node, src, env)?;
+        let arg = self.eval_with_env_and_arena(arg_node, src, env, arena)?;
+
+        self.apply_slots_with_arena(func_value, vec![Some(arg)], node, func_node, env, arena)
+    }
+
+    /// Apply a value to argument slots, projecting functions with empty slots
+    ///
+    /// Applying a projection fills its empty slots in order. A function given
+    /// fewer arguments than it takes, or an empty slot, yields a projection
+    /// awaiting the rest: `add[2]` and `add[2;]` are both `{[x;y] x+y}[2;]`.
+    fn apply_slots_with_arena(
+        &mut self,
+        func_value: Value,
+        slots: Vec<Option<Value>>,
+        node: Node,
+        func_node: Node,
+        env: &Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let (func_value, mut slots) = match func_value {
+            Value::Projection { function, args } => (*function, fill_slots(args, slots)),
+            other => (other, slots),
+        };
+
+        if func_value.is_function() {
+            let arity = func_value.arity().unwrap_or(0);
+            // f[] still calls f, so only pad calls that supplied something
+            if !slots.is_empty() && slots.len() < arity {
+                slots.resize(arity, None);
+            }
+            if slots.len() <= arity && slots.iter().any(Option::is_none) {
+                return Ok(Value::Projection {
+                    function: Box::new(func_value),
+                    args: slots,
+                });
+            }
+        }
+
+        let args = slots
+            .into_iter()
+            .map(|slot| {
+                slot.ok_or_else(|| {
+                    EvalError::new(
+                        EvalErrorKind::ArityMismatch("missing argument".into()),
+                        node,
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.apply_with_arena(func_value, &args, node, func_node, env, arena)
+    }
+
+    /// Apply a function value to already evaluated arguments
+    fn apply_with_arena(
+        &mut self,
+        func_value: Value,
+        args: &[Value],
+        node: Node,
+        func_node: Node,
+        env: &Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        match func_value {
+            Value::Memo { function, cache } => {
+                if let Some(result) = self.context.memos[cache].get(args) {
+                    return Ok(result.clone());
+                }
+                // The function calls itself through the memo, so its
+                // recursive calls are cached too
+                let itself = Value::Memo {
+                    function: function.clone(),
+                    cache,
+                };
+                let result = self.call_with_arena(*function, itself, args, node, env, arena)?;
+                self.context.memos[cache].insert(args, result.clone());
+                Ok(result)
+            }
+            function @ Value::Function { .. } => {
+                let itself = function.clone();
+                self.call_with_arena(function, itself, args, node, env, arena)
+            }
+            Value::Builtin(builtin) => {
+                let mut applier = Applier {
+                    evaluator: self,
+                    env,
+                    arena,
+                };
+                builtin.call(&mut applier, args, node)
+            }
+            Value::Native(native) => native.call(args, node),
+            // Lists, dicts and tables index like functions of their positions,
+            // keys or columns; each further argument indexes one level deeper: m[i;j]
+            list @ (Value::Attributed(_) | Value::IntVector(_) | Value::FloatVector(_)) => {
+                self.apply_with_arena(list.plain(), args, node, func_node, env, arena)
+            }
+            data @ (Value::List(_) | Value::Dict { .. } | Value::Table(_)) => args
+                .iter()
+                .try_fold(data, |value, arg| operators::index(&value, arg, node)),
+            other => Err(EvalError::new(
+                EvalErrorKind::NotCallable(other.type_name().to_string()),
+                func_node,
+            )),
+        }
+    }
+
+    /// Call `function` with `args`, binding `itself` as the function to call
+    /// through its own name and `.z.s`
+    fn call_with_arena(
+        &mut self,
+        function: Value,
+        itself: Value,
+        args: &[Value],
+        node: Node,
+        env: &Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let Value::Function {
+            params,
+            body,
+            closure,
+            code,
+            origin,
+            ..
+        } = function
+        else {
+            // Memoized builtins and projections
+            return self.apply_with_arena(function, args, node, node, env, arena);
+        };
+
+        // Create function execution environment using arena
+        let base_env = closure.as_ref().map(|c| c.as_ref()).unwrap_or(env);
+        let mut call_env = base_env.bind_parameters_with_arena(
+            &params,
+            args,
+            node,
+            arena,
+            &mut self.string_interner,
+        )?;
+
+        // Bind the function to .z.s, and to its own name if it was assigned
+        // one, so it can call itself
+        if let Some(name) = closure.as_ref().and_then(|c| c.self_name()) {
+            call_env.define_interned(name, itself.clone());
+        }
+        let self_ref = self.intern(".z.s");
+        call_env.define_interned(self_ref, itself);
+
+        // Functions made other than by evaluating a lambda are compiled
+        // when called
+        let code = match code.or_else(|| self.code(body)) {
+            Some(code) => code,
+            None => {
+                let e = parse_expression(self.resolve(body)).expect_err("the body failed to parse");
+                return Err(EvalError::new(
+                    EvalErrorKind::Syntax(format!("Function body parse error: {}", e)),
+                    node,
+                ));
+            }
+        };
+
+        if self.depth >= self.max_depth {
+            return Err(EvalError::new(
+                EvalErrorKind::RecursionLimitExceeded(self.max_depth),
+                node,
+            ));
+        }
+        // A function named in a namespace looks up and sets names there,
+        // wherever it is called from
+        let namespace = match closure.as_ref().and_then(|c| c.self_name()) {
+            Some(name) => namespace_of(self.resolve(name)).map(String::from),
+            None => self.namespace.clone(),
+        };
+        let caller_namespace = std::mem::replace(&mut self.namespace, namespace);
+        self.depth += 1;
+        let result = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || {
+            self.run(&code, &mut call_env, arena)
+        });
+        self.depth -= 1;
+        self.namespace = caller_namespace;
+        result.map_err(|e| {
+            let name = closure.as_ref().and_then(|c| c.self_name());
+            let name = name.map(|name| self.resolve(name).to_string());
+            let e = match origin {
+                Some(origin) => e.offset_by(&code.source, origin.offset as usize, &origin.source),
+                None => e.with_source(&code.source),
+            };
+            e.locate(&code.source).called_from(name, node)
+        })
+    }
+
+    /// The code of the function body `body`, from the cache or compiled
+    /// now; `None` if it does not parse
+    fn code(&mut self, body: InternedString) -> Option<Arc<Code>> {
+        if let Some(code) = self.code_cache.get(body) {
+            return Some(code);
+        }
+        let source = self.resolve(body).to_string();
+        let code = Arc::new(self.compile(&source)?);
+        self.code_cache.insert(body, code.clone());
+        Some(code)
+    }
+
+    /// The function body `source` compiled, or `None` if it does not parse
+    fn compile(&self, source: &str) -> Option<Code> {
+        let tree = parse_expression(source).ok()?;
+        let mut literal = |node: Node| {
+            match node.kind() {
+                "number" => self.visit_number_value(node, source),
+                "date" | "time" | "timestamp" => self.visit_temporal(node, source),
+                "boolean" => self.visit_boolean(node, source),
+                "symbol" => self.visit_symbol(node, source),
+                _ => self.visit_string(node, source),
+            }
+            .ok()
+        };
+        let compiler = Compiler::new(source, &mut literal).folding(self.fold_constants);
+        Some(compiler.compile(tree))
+    }
+
+    /// Run compiled `code` in `env`, giving the value of its last statement
+    fn run(
+        &mut self,
+        code: &Code,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let mut stack: Vec<Value> = Vec::new();
+        let mut pc = 0;
+        // Each statement gets its own span, as when the body is walked
+        let mut statement = None;
+        while let Some(op) = code.ops.get(pc) {
+            pc += 1;
+            self.check_interrupt(code.tree.root_node())?;
+            let result = match op {
+                Op::Statement(index) => {
+                    statement.take();
+                    let text = code.text(*index);
+                    statement = Some(tracing::debug_span!("statement", text).entered());
+                    continue;
+                }
+                Op::Const(index) => Ok(code.constants[*index].clone()),
+                Op::Load { name, node } => self.lookup(name, code.node(*node), env),
+                Op::Store { name, global } => {
+                    let value = stack.pop().expect("a value to store");
+                    Ok(self.assign(name, *global, value, env))
+                }
+                Op::Arithmetic { op, node, operator } => {
+                    let right = stack.pop().expect("a right operand");
+                    let left = stack.pop().expect("a left operand");
+                    let (node, operator) = (code.node(*node), code.node(*operator));
+                    self.arithmetic(op, left, right, node, operator)
+                }
+                Op::Compare { op, node } => {
+                    let left = stack.pop().expect("a left operand");
+                    let right = stack.pop().expect("a right operand");
+                    operators::compare(op, &left.plain(), &right.plain(), code.node(*node))
+                }
+                Op::Negate { node } => {
+                    let operand = stack.pop().expect("an operand");
+                    arithmetic::negate(&operand.plain(), self.context.overflow, code.node(*node))
+                }
+                Op::Call {
+                    argc,
+                    node,
+                    function,
+                } => {
+                    let args = stack.split_off(stack.len() - argc);
+                    let f = stack.pop().expect("a function to call");
+                    let slots = args.into_iter().map(Some).collect();
+                    let (node, function) = (code.node(*node), code.node(*function));
+                    self.apply_slots_with_arena(f, slots, node, function, env, arena)
+                }
+                Op::JumpUnless { target, node } => {
+                    let condition = stack.pop().expect("a condition");
+                    match operators::truthy(&condition.plain(), code.node(*node)) {
+                        Ok(true) => continue,
+                        Ok(false) => {
+                            pc = *target;
+                            continue;
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                Op::Jump(target) => {
+                    pc = *target;
+                    continue;
+                }
+                Op::Pop => {
+                    stack.pop();
+                    continue;
+                }
+                Op::Eval(index) => {
+                    self.eval_with_env_and_arena(code.node(*index), &code.source, env, arena)
+                }
+            };
+            match result.and_then(|value| self.measured(value, code.tree.root_node())) {
+                Ok(value) => stack.push(value),
+                Err(e) => {
+                    tracing::debug!(code = e.kind.code(), error = %e.kind, "statement failed");
+                    return Err(e);
+                }
+            }
+        }
+        Ok(stack.pop().expect("the value of the last statement"))
+    }
+
+    /// Visit a q-sql select with arena support:
+    /// `select [columns] [by groups] from table [where conditions]`
+    ///
+    /// Columns and conditions are evaluated with each column of the table
+    /// bound to its name as a list, so they apply to whole columns at once.
+    /// Each condition must give a boolean per row and keeps the rows where
+    /// it is true; the next condition sees only those rows. With a by
+    /// clause, the columns are evaluated over each group of rows with equal
+    /// group values, giving a table keyed by those values in ascending order.
+    fn visit_select_with_arena(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let (table, _) = self.query_source(node, src, env, arena)?;
+        let parent = Arc::new(env.clone());
+        let rows = self.query_rows(node, src, &table, &parent, arena)?;
+        // Kept whole, the columns keep their attributes
+        let table = match rows.len() == table.len() {
+            true => table,
+            false => table
+                .select_rows(&rows)
+                .expect("filtered rows are within the table"),
+        };
+        if node.child_by_field_name("by").is_some() {
+            return self.select_by(node, src, &table, &parent, arena);
+        }
+
+        let columns = self.query_columns(node, src, "column", &table, &parent, arena)?;
+        if columns.is_empty() {
+            return Ok(Value::Table(table));
+        }
+        let names: Vec<Value> = columns
+            .iter()
+            .map(|(name, _)| Value::Symbol(*name))
+            .collect();
+        let mut values: Vec<Value> = columns.into_iter().map(|(_, value)| value).collect();
+        // Columns that are all atoms, such as aggregates, make a single row
+        if !values
+            .iter()
+            .any(|v| matches!(v, Value::List(_) | Value::Attributed(_)))
+        {
+            values = values.into_iter().map(|v| Value::List(vec![v])).collect();
+        }
+        Table::from_dict(&names, &values)
+            .map(Value::Table)
+            .map_err(|e| EvalError::new(EvalErrorKind::Type(e), node))
+    }
+
+    /// The keyed table of a select with a by clause over the filtered
+    /// `table`: a row per group, with each column evaluated over the group's
+    /// rows, or without columns, the group's last row
+    fn select_by(
+        &mut self,
+        node: Node,
+        src: &str,
+        table: &Table,
+        parent: &Arc<Environment>,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let by = self.query_columns(node, src, "group", table, parent, arena)?;
+        let mut key_names = Vec::with_capacity(by.len());
+        let mut key_columns = Vec::with_capacity(by.len());
+        for (name, value) in by {
+            let column = match value.plain() {
+                Value::List(items) if items.len() == table.len() => items,
+                Value::List(items) => {
+                    return Err(EvalError::new(
+                        EvalErrorKind::Type(format!(
+                            "by `{} gives {} values for {} rows",
+                            name,
+                            items.len(),
+                            table.len()
+                        )),
+                        node,
+                    ));
+                }
+                atom => vec![atom; table.len()],
+            };
+            key_names.push(name);
+            key_columns.push(column);
+        }
+
+        let refs: Vec<&ListItems> = key_columns.iter().map(Vec::as_slice).collect();
+        let mut groups = table::group_rows(&refs);
+        let key_order = |a: &Vec<usize>, b: &Vec<usize>| {
+            key_columns
+                .iter()
+                .map(|c| c[a[0]].compare(&c[b[0]]).unwrap_or(Ordering::Equal))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        };
+        groups.sort_by(key_order);
+
+        let keys: Vec<Column> = key_columns
+            .iter()
+            .map(|c| groups.iter().map(|g| c[g[0]].clone()).collect())
+            .collect();
+        let key = Table::new(key_names.clone(), keys)
+            .map_err(|e| EvalError::new(EvalErrorKind::Type(e), node))?;
+
+        let mut names: Vec<Symbol> = Vec::new();
+        let mut columns: Vec<Column> = Vec::new();
+        if node.child_by_field_name("column").is_none() {
+            for (name, column) in table.names().iter().zip(table.columns()) {
+                if !key_names.contains(name) {
+                    names.push(*name);
+                    columns.push(
+                        groups
+                            .iter()
+                            .map(|g| column[g[g.len() - 1]].clone())
+                            .collect(),
+                    );
+                }
+            }
+        }
+        for group in &groups {
+            let rows = table
+                .select_rows(group)
+                .expect("grouped rows are within the table");
+            let evaluated = self.query_columns(node, src, "column", &rows, parent, arena)?;
+            if names.is_empty() && columns.is_empty() {
+                names = evaluated.iter().map(|(name, _)| *name).collect();
+                columns = vec![Vec::with_capacity(groups.len()); names.len()];
+            }
+            for (column, (_, value)) in columns.iter_mut().zip(evaluated) {
+                column.push(value);
+            }
+        }
+        let value =
+            Table::new(names, columns).map_err(|e| EvalError::new(EvalErrorKind::Type(e), node))?;
+        Table::keyed(&key, &value)
+            .map(Value::Table)
+            .map_err(|e| EvalError::new(EvalErrorKind::Type(e), node))
+    }
+
+    /// Visit a q-sql update with arena support:
+    /// `update columns from table [where conditions]`
+    ///
+    /// Columns are evaluated over the rows the conditions keep and written
+    /// back to those rows, an atom to each of them; the other rows keep
+    /// their values, or are null in a new column. Updating `` `t `` rather
+    /// than `t` assigns the result back to the variable `t`.
+    fn visit_update_with_arena(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let (table, target) = self.query_source(node, src, env, arena)?;
+        let parent = Arc::new(env.clone());
+        let rows = self.query_rows(node, src, &table, &parent, arena)?;
+        let selected = table
+            .select_rows(&rows)
+            .expect("filtered rows are within the table");
+
+        let mut updated = table;
+        for (name, value) in self.query_columns(node, src, "column", &selected, &parent, arena)? {
+            // A list with an attribute replacing a whole column keeps it
+            let whole = rows.len() == updated.len();
+            let attributed = match &value {
+                Value::Attributed(list) if whole => Some(Arc::clone(list)),
+                _ => None,
+            };
+            let values = match value.plain() {
+                Value::List(items) => items,
+                atom => vec![atom; rows.len()],
+            };
+            updated = updated
+                .update(name, &rows, values)
+                .map_err(|e| EvalError::new(EvalErrorKind::Type(e), node))?;
+            if let Some(list) = attributed {
+                updated = updated.with_attribute(name, list);
+            }
+        }
+        Ok(self.query_result(updated, target, env))
+    }
+
+    /// Visit a q-sql delete with arena support:
+    /// `delete from table [where conditions]` or `delete columns from table`
+    ///
+    /// Deleting rows without conditions deletes them all. As with update,
+    /// deleting from `` `t `` assigns the result back to `t`.
+    fn visit_delete_with_arena(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let (table, target) = self.query_source(node, src, env, arena)?;
+        let mut cursor = node.walk();
+        let columns = node
+            .children_by_field_name("column", &mut cursor)
+            .map(|column| {
+                get_node_text(column, src)
+                    .map(Symbol::new)
+                    .map_err(|e| EvalError::new(EvalErrorKind::Other(e), column))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let deleted = if columns.is_empty() {
+            let rows = self.query_rows(node, src, &table, &Arc::new(env.clone()), arena)?;
+            table.delete_rows(&rows)
+        } else if node.child_by_field_name("where").is_some() {
+            return Err(EvalError::new(
+                EvalErrorKind::Other("delete takes columns or a where clause, not both".into()),
+                node,
+            ));
+        } else {
+            table
+                .delete_columns(&columns)
+                .map_err(|e| EvalError::new(EvalErrorKind::Other(e), node))?
+        };
+        Ok(self.query_result(deleted, target, env))
+    }
+
+    /// The table a query reads, and the variable it came from when it was
+    /// named by a symbol: `` select from `t ``
+    fn query_source(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> EvalSourceResult {
+        let table_node = self.child(node, "table")?;
+        let (value, target) = match self.eval_with_env_and_arena(table_node, src, env, arena)? {
+            Value::Symbol(name) => {
+                let value = env.get(&name, table_node, &mut self.string_interner)?;
+                (value.clone(), Some(name))
+            }
+            value => (value, None),
+        };
+        match value {
+            Value::Table(table) => Ok((table, target)),
+            other => Err(EvalError::new(
+                EvalErrorKind::Type(format!(
+                    "{} expects a table, got {}",
+                    node.kind(),
+                    other.type_name()
+                )),
+                table_node,
+            )),
+        }
+    }
+
+    /// Rows of `table` kept by a query's where conditions, in table order
+    fn query_rows(
+        &mut self,
+        node: Node,
+        src: &str,
+        table: &Table,
+        parent: &Arc<Environment>,
+        arena: &Bump,
+    ) -> EvalRowsResult {
+        let mut rows: Vec<usize> = (0..table.len()).collect();
+        let mut cursor = node.walk();
+        let filters: Vec<Node> = node.children_by_field_name("filter", &mut cursor).collect();
+        for filter in filters {
+            // Columns keep their attributes until rows are filtered out
+            if rows.len() == table.len()
+                && let Some(found) = self.indexed_rows(filter, src, table, parent, arena)?
+            {
+                rows = found;
+                continue;
+            }
+            let kept = table
+                .select_rows(&rows)
+                .expect("filtered rows are within the table");
+            let mut scope = self.column_scope(&kept, parent);
+            let mask = self.eval_with_env_and_arena(filter, src, &mut scope, arena)?;
+            rows = filter_rows(&mask, rows.len(), filter)?
+                .into_iter()
+                .map(|i| rows[i])
+                .collect();
+        }
+        Ok(rows)
+    }
+
+    /// Rows of `table` kept by the condition `filter`, found through the
+    /// attribute of a column it tests, as `sym=x`, `sym in xs` or
+    /// `time within (lo;hi)`, without evaluating it row by row; `None` if it
+    /// tests no such column, or not that way
+    fn indexed_rows(
+        &mut self,
+        filter: Node,
+        src: &str,
+        table: &Table,
+        parent: &Arc<Environment>,
+        arena: &Bump,
+    ) -> EvalIndexedRowsResult {
+        let condition = innermost(filter);
+        let (Some(left), Some(op), Some(right)) = (
+            condition.child_by_field_name("left"),
+            condition.child_by_field_name("operator"),
+            condition.child_by_field_name("right"),
+        ) else {
+            return Ok(None);
+        };
+        let left = innermost(left);
+        let column = match left.kind() {
+            "identifier" => left.utf8_text(src.as_bytes()).ok(),
+            _ => None,
+        };
+        // What the column is tested against must be the same for every row
+        let Some(list) = column.and_then(|name| table.attributed(name)).cloned() else {
+            return Ok(None);
+        };
+        if mentioned_column(right, src, table).is_some() {
+            return Ok(None);
+        }
+        let mut scope = Environment::with_parent(Arc::clone(parent));
+        let value = self.eval_with_env_and_arena(right, src, &mut scope, arena)?;
+        Ok(list.matching(op.kind(), &value.plain()))
+    }
+
+    /// A query's named columns, or with `field` "group" its by columns,
+    /// evaluated over `table`
+    fn query_columns(
+        &mut self,
+        node: Node,
+        src: &str,
+        field: &str,
+        table: &Table,
+        parent: &Arc<Environment>,
+        arena: &Bump,
+    ) -> EvalColumnsResult {
+        let mut cursor = node.walk();
+        let columns: Vec<Node> = node.children_by_field_name(field, &mut cursor).collect();
+        let mut scope = self.column_scope(table, parent);
+        let mut evaluated = Vec::with_capacity(columns.len());
+        for column in columns {
+            let value_node = self.child(column, "value")?;
+            let name = match column.child_by_field_name("name") {
+                Some(name) => get_node_text(name, src)
+                    .map_err(|e| EvalError::new(EvalErrorKind::Other(e), name))?,
+                None => column_name(value_node, src, table),
+            };
+            let value = self.eval_with_env_and_arena(value_node, src, &mut scope, arena)?;
+            evaluated.push((Symbol::new(name), value));
+        }
+        Ok(evaluated)
+    }
+
+    /// What a query that changes a table gives: the table, or when it was
+    /// named by a symbol, the symbol after assigning the table back to it
+    fn query_result(
+        &mut self,
+        table: Table,
+        target: Option<Symbol>,
+        env: &mut Environment,
+    ) -> Value {
+        match target {
+            Some(name) => {
+                let variable = self.intern(&name);
+                env.define_interned(variable, Value::Table(table));
+                Value::Symbol(name)
+            }
+            None => Value::Table(table),
+        }
+    }
+
+    /// A scope under `parent` binding each column of `table` to its name,
+    /// with its attribute if it has one; the parent is shared so that a
+    /// scope per group copies nothing
+    fn column_scope(&mut self, table: &Table, parent: &Arc<Environment>) -> Environment {
+        let mut scope = Environment::with_parent(Arc::clone(parent));
+        for (name, column) in table.names().iter().zip(table.columns()) {
+            let value = match table.attributed(name) {
+                Some(list) => Value::Attributed(Arc::clone(list)),
+                None => Value::List(column.clone()),
+            };
+            let name = self.intern(name);
+            scope.define_interned(name, value);
+        }
+        scope
+    }
+
+    /// Visit a conditional with arena support: $[c;t;f] or $[c1;t1;c2;t2;f]
+    ///
+    /// Conditions are tested in turn and only the selected branch is
+    /// evaluated; the trailing branch is taken when every condition fails.
+    fn visit_conditional_with_arena(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let mut cursor = node.walk();
+        let args: Vec<Node> = node.children_by_field_name("arg", &mut cursor).collect();
+        let mut pairs = args.chunks_exact(2);
+        for pair in pairs.by_ref() {
+            let condition = self.eval_with_env_and_arena(pair[0], src, env, arena)?;
+            if operators::truthy(&condition, pair[0])? {
+                return self.eval_with_env_and_arena(pair[1], src, env, arena);
+            }
+        }
+        match pairs.remainder() {
+            [otherwise] => self.eval_with_env_and_arena(*otherwise, src, env, arena),
+            _ => Err(EvalError::new(
+                EvalErrorKind::Other("Conditional needs a final branch: $[c;t;f]".into()),
+                node,
+            )),
+        }
+    }
+
+    /// Visit a loop with arena support: do[n;body] or while[c;body]
+    ///
+    /// The count of `do` is evaluated once, the condition of `while` before
+    /// each pass. The loop gives the value of the last body statement
+    /// evaluated, or an empty list if the body never ran. A loop making more
+    /// than `max_iterations` passes fails rather than running on.
+    fn visit_loop_with_arena(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let keyword = self.op_text(self.child(node, "keyword")?, src)?;
+        let condition = self.child(node, "condition")?;
+        let mut cursor = node.walk();
+        let body: Vec<Node> = node.children_by_field_name("body", &mut cursor).collect();
+
+        let count = if keyword.starts_with("do") {
+            match self.eval_with_env_and_arena(condition, src, env, arena)? {
+                Value::Integer(n) => Some(n.max(0) as usize),
+                other => {
+                    return Err(EvalError::new(
+                        EvalErrorKind::Type(format!(
+                            "do expects an integer count, got {}",
+                            other.type_name()
+                        )),
+                        condition,
+                    ));
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut result = Value::List(vec![]);
+        let mut passes = 0;
+        loop {
+            let more = match count {
+                Some(count) => passes < count,
+                None => {
+                    let holds = self.eval_with_env_and_arena(condition, src, env, arena)?;
+                    operators::truthy(&holds, condition)?
+                }
+            };
+            if !more {
+                return Ok(result);
+            }
+            if passes == self.max_iterations {
+                return Err(EvalError::new(
+                    EvalErrorKind::IterationLimitExceeded(self.max_iterations),
+                    node,
+                ));
+            }
+            passes += 1;
+            for statement in &body {
+                result = self.eval_with_env_and_arena(*statement, src, env, arena)?;
+            }
+        }
+    }
+
+    /// Visit protected evaluation with arena support: @[f;x;handler]
+    ///
+    /// Only the application of `f` is trapped; errors evaluating the three
+    /// operands themselves propagate. A trapped error reaches the handler as
+    /// a symbol holding its message.
+    fn visit_trap_with_arena(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let func_node = self.child(node, "function")?;
+        let arg_node = self.child(node, "argument")?;
+        let handler_node = self.child(node, "handler")?;
+
+        let func_value = self.eval_with_env_and_arena(func_node, src, env, arena)?;
+        let arg = self.eval_with_env_and_arena(arg_node, src, env, arena)?;
+        let handler = self.eval_with_env_and_arena(handler_node, src, env, arena)?;
+
+        match self.apply_slots_with_arena(func_value, vec![Some(arg)], node, func_node, env, arena)
+        {
+            Ok(value) => Ok(value),
+            // Stopping evaluation is not the function's error to handle
+            Err(
+                error @ EvalError {
+                    kind: EvalErrorKind::Cancelled | EvalErrorKind::TimedOut,
+                    ..
+                },
+            ) => Err(error),
+            Err(error) if handler.is_function() => {
+                let message = Value::Symbol(error.kind.to_string().into());
+                self.apply_slots_with_arena(
+                    handler,
+                    vec![Some(message)],
+                    node,
+                    handler_node,
+                    env,
+                    arena,
+                )
+            }
+            Err(_) => Ok(handler),
+        }
+    }
+
+    /// Visit a signal with arena support: 'x raises x as an error
+    ///
+    /// A symbol's name is the message; any other value is formatted.
+    fn visit_signal_with_arena(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let message_node = self.child(node, "message")?;
+        let message = match self.eval_with_env_and_arena(message_node, src, env, arena)? {
+            Value::Symbol(name) => name.to_string(),
+            Value::Char(c) => c.to_string(),
+            other => match other.as_string() {
+                Some(text) => text,
+                None => Printer::default().inline(&other, &self.string_interner),
+            },
+        };
+        Err(EvalError::new(EvalErrorKind::Signal(message), node))
+    }
+
+    /// Visit a dictionary literal with arena support: keys!values
+    fn visit_dict_with_arena(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let keys_node = self.child(node, "keys")?;
+        let values_node = self.child(node, "values")?;
+
+        let values = self.eval_with_env_and_arena(values_node, src, env, arena)?;
+        let keys = self.eval_with_env_and_arena(keys_node, src, env, arena)?;
+        operators::dict(&keys, &values, node)
+    }
+
+    /// Visit a list literal with arena support: 1 2 3 or (a;b;c)
+    fn visit_list_with_arena(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let mut items = Vec::new();
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            if child.kind() == "comment" {
+                continue;
+            }
+            items.push(self.eval_with_env_and_arena(child, src, env, arena)?);
+        }
+        // A numeric vector with any float in it is a float vector: 1 0n 3
+        if node.kind() == "vector" && items.iter().any(|v| matches!(v, Value::Float(_))) {
+            items = items
+                .iter()
+                .map(|v| Value::Float(v.as_f64().unwrap_or(f64::NAN)))
+                .collect();
+        }
+        Ok(Value::List(items))
+    }
+
+    // Bumpalo-enhanced parameter/argument extraction methods
+
+    /// Extract parameter names using bumpalo for temporary allocations
+    /// Returns Vec<InternedString> for memory efficiency
+    fn extract_parameter_list_with_arena(
+        &mut self,
+        node: Node,
+        src: &str,
+        arena: &Bump,
+    ) -> EvalInternedStringListResult {
+        // Use bumpalo Vec for temporary collection
         let mut temp_params = bumpalo::collections::Vec::new_in(arena);
 
         // Walk through all named children that are identifiers
@@ -413,20 +2156,26 @@ impl Evaluator {
         src: &str,
         env: &mut Environment,
         arena: &Bump,
-    ) -> EvalValueListResult {
+    ) -> EvalArgSlotsResult {
         // Use bumpalo Vec for temporary collection
         let mut temp_args = bumpalo::collections::Vec::new_in(arena);
 
-        // Walk through all named children looking for argument expressions
+        // Walk all children so that separators delimit slots; a slot with no
+        // expression between separators is left empty
+        let mut slot = None;
         let mut cursor = node.walk();
-        for child in node.named_children(&mut cursor) {
-            if child.kind() == "expression" {
-                let arg_value = self.eval_with_env_and_arena(child, src, env, arena)?;
-                temp_args.push(arg_value);
+        for child in node.children(&mut cursor) {
+            match child.kind() {
+                ";" => temp_args.push(slot.take()),
+                "expression" => {
+                    slot = Some(self.eval_with_env_and_arena(child, src, env, arena)?);
+                }
+                _ => {}
             }
         }
+        temp_args.push(slot);
 
-        // Convert to owned Vec<Value> for return (long-lived data)
+        // Convert to owned Vec for return (long-lived data)
         Ok(temp_args.into_iter().collect())
     }
 
@@ -441,140 +2190,233 @@ impl Evaluator {
         self.eval_with_env(child, src, env)
     }
 
-    // Raw arithmetic methods (return i64) - updated to work with environments
-
-    fn visit_number_raw(&self, node: Node, src: &str) -> Result<i64, EvalError> {
-        self.visit_number(node, src)
-    }
-
-    fn visit_binary_raw(
+    /// Visit list verbs and comparisons with arena support: n#x, n_x, x>y
+    fn visit_dyadic_with_arena(
         &mut self,
         node: Node,
         src: &str,
         env: &mut Environment,
-    ) -> Result<i64, EvalError> {
-        // Binary operation if operator field exists, otherwise fallback to single child
-        if let Some(opn) = node.child_by_field_name("operator") {
-            let lhs = self.child(node, "left")?;
-            let rhs = self.child(node, "right")?;
-
-            let left_val = match self.eval_with_env(lhs, src, env)? {
-                Value::Integer(n) => n,
-                _ => {
-                    return Err(EvalError::new(
-                        EvalErrorKind::Other("Expected integer in arithmetic".into()),
-                        lhs,
-                    ));
+        arena: &Bump,
+    ) -> Result<Value, EvalError> {
+        let opn = self.child(node, "operator")?;
+        let lhs = self.child(node, "left")?;
+        let rhs = self.child(node, "right")?;
+
+        // Evaluate right to left, as q does for its verbs
+        let right = self.eval_with_env_and_arena(rhs, src, env, arena)?;
+        let left = self.eval_with_env_and_arena(lhs, src, env, arena)?;
+
+        let op = self.op_text(opn, src)?;
+        // A count of items is checked before they are built
+        if let ("#" | "?", Value::Integer(count)) = (op, &left) {
+            let len = usize::try_from(count.unsigned_abs()).unwrap_or(usize::MAX);
+            self.reserve(memory::list_size(len), node)?;
+        }
+        match op {
+            "#" => match left {
+                Value::Symbol(name) => attributes::apply(&name, right, node),
+                count => operators::take(&count, &right, node),
+            },
+            "_" => operators::drop(&left, &right, node),
+            "$" => operators::cast(&left, &right, node),
+            "?" => match left {
+                list @ (Value::List(_) | Value::Attributed(_)) => {
+                    lookup::find(&mut self.context, &[list, right], node)
                 }
-            };
+                count => random::roll(&mut self.context.rng, &count, &right.plain(), node),
+            },
+            "in" => lookup::member(&mut self.context, &[left, right], node),
+            "within" => lookup::within(&mut self.context, &[left, right], node),
+            "xbar" => bucket::xbar(&mut self.context, &[left, right], node),
+            op @ ("=" | "<>" | "<" | ">" | "<=" | ">=") => {
+                operators::compare(op, &left, &right, node)
+            }
+            op => Err(EvalError::new(
+                EvalErrorKind::UnknownOperator(op.into()),
+                opn,
+            )),
+        }
+    }
 
-            let right_val = match self.eval_with_env(rhs, src, env)? {
-                Value::Integer(n) => n,
-                _ => {
-                    return Err(EvalError::new(
-                        EvalErrorKind::Other("Expected integer in arithmetic".into()),
-                        rhs,
-                    ));
-                }
-            };
+    // Raw arithmetic methods (return i64) - updated to work with environments
 
-            let op = self.op_text(opn, src)?;
-            evaluate_binary_operation(left_val, right_val, op, node, opn)
+    /// Boolean literal: `1b` is an atom, `0101b` a boolean list
+    fn visit_boolean(&self, node: Node, src: &str) -> Result<Value, EvalError> {
+        let text =
+            get_node_text(node, src).map_err(|e| EvalError::new(EvalErrorKind::Other(e), node))?;
+        let mut bits: Vec<Value> = text
+            .trim_end_matches('b')
+            .chars()
+            .map(|c| Value::Boolean(c == '1'))
+            .collect();
+        Ok(if bits.len() == 1 {
+            bits.remove(0)
         } else {
-            // Single child fallback
-            let child = self.named_child(node)?;
-            match self.eval_with_env(child, src, env)? {
-                Value::Integer(n) => Ok(n),
-                _ => Err(EvalError::new(
-                    EvalErrorKind::Other("Expected integer".into()),
-                    child,
-                )),
-            }
+            Value::List(bits)
+        })
+    }
+
+    /// Symbol literal: `` `a `` is an atom, `` `a`b `` a symbol list
+    fn visit_symbol(&self, node: Node, src: &str) -> Result<Value, EvalError> {
+        let text =
+            get_node_text(node, src).map_err(|e| EvalError::new(EvalErrorKind::Other(e), node))?;
+        let mut names: Vec<Value> = text
+            .split('`')
+            .skip(1)
+            .map(|name| Value::Symbol(name.into()))
+            .collect();
+        Ok(if names.len() == 1 {
+            names.remove(0)
+        } else {
+            Value::List(names)
+        })
+    }
+
+    /// A string literal: a list of characters, or a char atom for one
+    fn visit_string(&self, node: Node, src: &str) -> Result<Value, EvalError> {
+        let text =
+            get_node_text(node, src).map_err(|e| EvalError::new(EvalErrorKind::Other(e), node))?;
+        let mut chars = Vec::new();
+        let mut escaped = text[1..text.len() - 1].chars();
+        while let Some(c) = escaped.next() {
+            chars.push(match c {
+                '\\' => match escaped.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some(c @ ('"' | '\\')) => c,
+                    other => {
+                        return Err(EvalError::new(
+                            EvalErrorKind::Other(format!(
+                                "Unknown escape in string: \\{}",
+                                other.map(String::from).unwrap_or_default()
+                            )),
+                            node,
+                        ));
+                    }
+                },
+                c => c,
+            });
+        }
+        Ok(match chars.as_slice() {
+            [c] => Value::Char(*c),
+            _ => Value::List(chars.into_iter().map(Value::Char).collect()),
+        })
+    }
+
+    /// A number literal: an integer, or a float when it has a decimal point
+    fn visit_number_value(&self, node: Node, src: &str) -> Result<Value, EvalError> {
+        let text =
+            get_node_text(node, src).map_err(|e| EvalError::new(EvalErrorKind::Other(e), node))?;
+        match text {
+            "0N" => return Ok(Value::Integer(NULL_INTEGER)),
+            "0W" => return Ok(Value::Integer(INFINITY_INTEGER)),
+            "0n" => return Ok(Value::Float(f64::NAN)),
+            "0w" => return Ok(Value::Float(f64::INFINITY)),
+            _ => {}
+        }
+        // The f of a hex literal is a digit, not a float suffix
+        if !text.starts_with("0x")
+            && let Some(whole) = text.strip_suffix('f')
+        {
+            whole
+                .parse::<f64>()
+                .map(Value::Float)
+                .map_err(|e| EvalError::new(EvalErrorKind::InvalidNumber(e.to_string()), node))
+        } else if text.contains('.') {
+            text.parse::<f64>()
+                .map(Value::Float)
+                .map_err(|e| EvalError::new(EvalErrorKind::InvalidNumber(e.to_string()), node))
+        } else {
+            self.visit_number(node, src).map(Value::Integer)
         }
     }
 
-    fn visit_unary_raw(
+    /// A date, time or timestamp literal
+    fn visit_temporal(&self, node: Node, src: &str) -> Result<Value, EvalError> {
+        let text =
+            get_node_text(node, src).map_err(|e| EvalError::new(EvalErrorKind::Other(e), node))?;
+        let value = match node.kind() {
+            "date" => temporal::parse_date(text).map(Value::Date),
+            "time" => temporal::parse_time(text).map(Value::Time),
+            _ => temporal::parse_timestamp(text).map(Value::Timestamp),
+        };
+        value.ok_or_else(|| {
+            EvalError::new(
+                EvalErrorKind::InvalidNumber(format!("invalid {}: {}", node.kind(), text)),
+                node,
+            )
+        })
+    }
+
+    /// Visit `+ - * / %` under the evaluator's overflow mode
+    fn visit_binary(
         &mut self,
         node: Node,
         src: &str,
         env: &mut Environment,
-    ) -> Result<i64, EvalError> {
-        if let Some(_op) = node.child_by_field_name("operator") {
-            let operand_node = self.child(node, "operand")?;
-            let operand = match self.eval_with_env(operand_node, src, env)? {
-                Value::Integer(n) => n,
-                _ => {
-                    return Err(EvalError::new(
-                        EvalErrorKind::Other("Expected integer in unary operation".into()),
-                        operand_node,
-                    ));
-                }
-            };
-            Ok(-operand)
+    ) -> Result<Value, EvalError> {
+        let opn = self.child(node, "operator")?;
+        let left = self.eval_with_env(self.child(node, "left")?, src, env)?;
+        let right = self.eval_with_env(self.child(node, "right")?, src, env)?;
+        let op = self.op_text(opn, src)?;
+        self.arithmetic(op, left, right, node, opn)
+    }
+
+    /// `left op right` under the evaluator's overflow mode, as a typed
+    /// vector if a kernel computes it, split across threads if the lists
+    /// are long enough and that is enabled
+    fn arithmetic(
+        &self,
+        op: &str,
+        left: Value,
+        right: Value,
+        node: Node,
+        op_node: Node,
+    ) -> Result<Value, EvalError> {
+        let len = vectors::len(&left).or(vectors::len(&right)).unwrap_or(0);
+        let kernel = if parallel::splits(self.context.parallel, len) {
+            kernels::binary_parallel
         } else {
-            let child = self.named_child(node)?;
-            match self.eval_with_env(child, src, env)? {
-                Value::Integer(n) => Ok(n),
-                _ => Err(EvalError::new(
-                    EvalErrorKind::Other("Expected integer".into()),
-                    child,
-                )),
-            }
+            kernels::binary
+        };
+        if let Some(result) = kernel(op, &left, &right) {
+            return Ok(result);
         }
+        let (left, right) = (left.plain(), right.plain());
+        arithmetic::general(op, &left, &right, self.context.overflow, node, op_node)
     }
 
-    fn visit_power_raw(
+    /// Visit prefix negation under the evaluator's overflow mode
+    fn visit_unary(
         &mut self,
         node: Node,
         src: &str,
         env: &mut Environment,
-    ) -> Result<i64, EvalError> {
-        if let Some(_op) = node.child_by_field_name("operator") {
-            let base_node = self.child(node, "base")?;
-            let exp_node = self.child(node, "exponent")?;
-
-            let base = match self.eval_with_env(base_node, src, env)? {
-                Value::Integer(n) => n,
-                _ => {
-                    return Err(EvalError::new(
-                        EvalErrorKind::Other("Expected integer in power operation".into()),
-                        base_node,
-                    ));
-                }
-            };
-
-            let exponent = match self.eval_with_env(exp_node, src, env)? {
-                Value::Integer(n) => n,
-                _ => {
-                    return Err(EvalError::new(
-                        EvalErrorKind::Other("Expected integer in power operation".into()),
-                        exp_node,
-                    ));
-                }
-            };
-
-            if exponent < 0 {
-                return Err(EvalError::new(EvalErrorKind::NegativeExponent, node));
-            }
-            if exponent > 63 {
-                return Err(EvalError::new(EvalErrorKind::ExponentTooLarge, node));
-            }
+    ) -> Result<Value, EvalError> {
+        let operand_node = self.child(node, "operand")?;
+        let operand = self.eval_with_env(operand_node, src, env)?;
+        arithmetic::negate(&operand, self.context.overflow, operand_node)
+    }
 
-            base.checked_pow(exponent as u32).ok_or_else(|| {
-                EvalError::new(
-                    EvalErrorKind::IntegerOverflow("exponentiation".into()),
-                    node,
-                )
-            })
-        } else {
-            let child = self.named_child(node)?;
-            match self.eval_with_env(child, src, env)? {
-                Value::Integer(n) => Ok(n),
-                _ => Err(EvalError::new(
-                    EvalErrorKind::Other("Expected integer".into()),
-                    child,
-                )),
+    /// Visit `^`: integer exponentiation under the evaluator's overflow
+    /// mode when both sides are integers, and otherwise q's fill, the right
+    /// side with its nulls replaced by the left
+    fn visit_power(
+        &mut self,
+        node: Node,
+        src: &str,
+        env: &mut Environment,
+    ) -> Result<Value, EvalError> {
+        let base_node = self.child(node, "base")?;
+        let exp_node = self.child(node, "exponent")?;
+        let base = self.eval_with_env(base_node, src, env)?;
+        let exponent = self.eval_with_env(exp_node, src, env)?;
+        match (base, exponent) {
+            (Value::Integer(base), Value::Integer(exponent)) => {
+                arithmetic::power(base, exponent, self.context.overflow, node)
             }
+            (default, value) => fill::coalesce(&default.plain(), &value.plain(), node),
         }
     }
 
@@ -590,7 +2432,7 @@ impl Evaluator {
                 Value::Integer(n) => n,
                 _ => {
                     return Err(EvalError::new(
-                        EvalErrorKind::Other("Expected integer in factorial".into()),
+                        EvalErrorKind::Type("expected an integer in factorial".into()),
                         operand_node,
                     ));
                 }
@@ -601,7 +2443,7 @@ impl Evaluator {
             match self.eval_with_env(child, src, env)? {
                 Value::Integer(n) => Ok(n),
                 _ => Err(EvalError::new(
-                    EvalErrorKind::Other("Expected integer".into()),
+                    EvalErrorKind::Type("expected an integer".into()),
                     child,
                 )),
             }
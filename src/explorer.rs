@@ -0,0 +1,183 @@
+//! Variable explorer: what each binding of a session holds
+//!
+//! [`variables`] describes the bindings of an environment by name, type,
+//! shape, approximate memory size and a short preview. A path of names
+//! drills into a binding: a table's columns, a dict's entries by key, or a
+//! list's items by position, so a notebook panel or the REPL's `\vars`
+//! command can expand nested values a level at a time:
+//!
+//! ```
+//! use wabznasm::Session;
+//!
+//! let mut session = Session::new();
+//! session.eval("t: flip `sym`px!(`AAPL`MSFT;100 250)").unwrap();
+//! let columns = session.explore(&["t"]).unwrap();
+//! assert_eq!(columns[1].name, "px");
+//! assert_eq!(columns[1].preview, "100 250");
+//! ```
+
+use crate::environment::{Environment, Value};
+use lasso::Rodeo;
+use serde::Serialize;
+
+/// Characters of a value's text kept in its preview
+pub const PREVIEW_CHARS: usize = 60;
+
+/// Items of a long list described when it is expanded
+pub const MAX_CHILDREN: usize = 100;
+
+/// Parts of an explored value, or why it cannot be explored
+pub type ExploreResult = Result<Vec<Variable>, String>;
+
+/// What one binding, or one part of a binding, holds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Variable {
+    /// Name of the binding, or the column, key or position in its parent
+    pub name: String,
+    /// Type name, as `type` gives it
+    #[serde(rename = "type")]
+    pub type_name: String,
+    /// Items of a list or dict, `rows x columns` of a table, or empty for
+    /// an atom
+    pub shape: String,
+    /// Approximate bytes the value occupies
+    pub bytes: usize,
+    /// The value's text, shortened to [`PREVIEW_CHARS`]
+    pub preview: String,
+    /// Whether the value has parts to explore
+    pub expandable: bool,
+}
+
+impl Variable {
+    /// Describe `value`, found under `name`
+    pub fn describe(name: impl Into<String>, value: &Value, interner: &Rodeo) -> Self {
+        let shape = match value {
+            Value::List(items) => items.len().to_string(),
+            Value::Dict { keys, .. } => keys.len().to_string(),
+            Value::Table(table) => format!("{} x {}", table.len(), table.names().len()),
+            _ => String::new(),
+        };
+        let text = value.format(interner);
+        let preview = match text.char_indices().nth(PREVIEW_CHARS) {
+            Some((end, _)) => format!("{}...", &text[..end]),
+            None => text,
+        };
+        Self {
+            name: name.into(),
+            type_name: value.type_name().to_string(),
+            shape,
+            bytes: size_of_value(value),
+            preview,
+            expandable: matches!(value, Value::List(_) | Value::Dict { .. } | Value::Table(_)),
+        }
+    }
+}
+
+/// The bindings of `env`, in name order
+pub fn variables(env: &Environment, interner: &Rodeo) -> Vec<Variable> {
+    let mut variables: Vec<Variable> = env
+        .local_names_interned()
+        .into_iter()
+        .filter_map(|name| {
+            let value = env.lookup_interned(name)?;
+            Some(Variable::describe(interner.resolve(&name), value, interner))
+        })
+        .collect();
+    variables.sort_by(|a, b| a.name.cmp(&b.name));
+    variables
+}
+
+/// The parts of the value at `path`, a binding name followed by the names
+/// of parts within it, or the bindings themselves for an empty path
+pub fn explore(env: &Environment, path: &[&str], interner: &Rodeo) -> ExploreResult {
+    let Some((first, rest)) = path.split_first() else {
+        return Ok(variables(env, interner));
+    };
+    let mut value = env
+        .lookup_interned(interner.get(first).ok_or_else(|| undefined(first))?)
+        .ok_or_else(|| undefined(first))?
+        .clone();
+    for part in rest {
+        value = child(&value, part, interner)
+            .ok_or_else(|| format!("No part {} in {}", part, path.join(" ")))?;
+    }
+    children(&value, interner).ok_or_else(|| {
+        format!(
+            "{} is {}, which has no parts",
+            path.join(" "),
+            value.type_name()
+        )
+    })
+}
+
+fn undefined(name: &str) -> String {
+    format!("Undefined variable: {}", name)
+}
+
+/// The parts of `value`, if it has any: the first [`MAX_CHILDREN`] items of
+/// a list, the entries of a dict, or the columns of a table
+pub fn children(value: &Value, interner: &Rodeo) -> Option<Vec<Variable>> {
+    match value {
+        Value::List(items) => Some(
+            items
+                .iter()
+                .take(MAX_CHILDREN)
+                .enumerate()
+                .map(|(i, item)| Variable::describe(i.to_string(), item, interner))
+                .collect(),
+        ),
+        Value::Dict { keys, values } => Some(
+            keys.iter()
+                .zip(values)
+                .map(|(key, value)| Variable::describe(key_name(key, interner), value, interner))
+                .collect(),
+        ),
+        Value::Table(table) => Some(
+            table
+                .names()
+                .iter()
+                .zip(table.columns())
+                .map(|(name, column)| {
+                    Variable::describe(name.to_string(), &Value::List(column.clone()), interner)
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// The part of `value` called `name` by [`children`]
+fn child(value: &Value, name: &str, interner: &Rodeo) -> Option<Value> {
+    match value {
+        Value::List(items) => items.get(name.parse::<usize>().ok()?).cloned(),
+        Value::Dict { keys, values } => keys
+            .iter()
+            .position(|key| key_name(key, interner) == name)
+            .map(|i| values[i].clone()),
+        Value::Table(table) => table
+            .column(name)
+            .map(|column| Value::List(column.to_vec())),
+        _ => None,
+    }
+}
+
+/// Name of a dict entry: a symbol key without its backtick, any other key
+/// as written
+fn key_name(key: &Value, interner: &Rodeo) -> String {
+    match key {
+        Value::Symbol(name) => name.to_string(),
+        other => other.format(interner),
+    }
+}
+
+/// Approximate bytes `value` occupies, counting the items it holds
+pub fn size_of_value(value: &Value) -> usize {
+    let nested = |items: &[Value]| items.iter().map(size_of_value).sum::<usize>();
+    size_of::<Value>()
+        + match value {
+            Value::List(items) => nested(items),
+            Value::Dict { keys, values } => nested(keys) + nested(values),
+            Value::Table(table) => table.columns().iter().map(|c| nested(c)).sum(),
+            _ => 0,
+        }
+}
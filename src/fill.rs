@@ -0,0 +1,156 @@
+//! Filling nulls: `fill` and `fills`
+//!
+//! `fill[x;y]` coalesces: it gives `y` with its nulls replaced by `x`, or
+//! by the items of `x` at the same positions or keys. `fills` carries the
+//! last value that is not null forward over the nulls after it, as a
+//! time series with missing points wants before it is analysed. Both apply
+//! to the columns of a table and the values of a dict. The empty symbol is
+//! the null of symbols here, as it is in joins.
+//!
+//! ```text
+//! fill[0;1 0N 3]              / 1 0 3
+//! fill[1 2 3;10 0N 30]        / 10 2 30
+//! fill[`a`b!1 2;`b`c!0N 3]    / `a`b`c!1 2 3
+//! fills 0N 1 0N 0N 4 0N       / 0N 1 1 1 4 4
+//! ```
+//!
+//! As in q, `x^y` is `fill[x;y]`, except that `^` between two integer atoms
+//! stays the power operator: `2^3` is 8, and a single null integer is
+//! filled with `fill[2;0N]`. Any other operands fill.
+//!
+//! ```text
+//! 0^1 0N 3                    / 1 0 3
+//! 0.5^0n 2.5                  / 0.5 2.5
+//! `z^`a``b                    / `a`z`b
+//! ```
+
+use crate::builtins::Context;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::table::Table;
+use tree_sitter::Node;
+
+/// Whether `value` is missing: a null number or the empty symbol
+fn missing(value: &Value) -> bool {
+    match value {
+        Value::Symbol(name) => name.is_empty(),
+        other => other.is_null(),
+    }
+}
+
+/// `f` applied to each column of `table`
+fn columns(
+    table: &Table,
+    f: impl Fn(&Value) -> Result<Value, EvalError>,
+    node: Node,
+) -> Result<Value, EvalError> {
+    let Value::Dict { keys, values } = table.to_dict() else {
+        unreachable!("a table's dict is a dict");
+    };
+    let filled = values.iter().map(f).collect::<Result<Vec<_>, _>>()?;
+    Table::from_dict(&keys, &filled)
+        .map(Value::Table)
+        .map_err(|message| EvalError::new(EvalErrorKind::Type(message), node))
+}
+
+/// `value` with its nulls replaced by `default`
+pub(crate) fn coalesce(default: &Value, value: &Value, node: Node) -> Result<Value, EvalError> {
+    match (default, value) {
+        (
+            Value::Dict {
+                keys: under,
+                values: below,
+            },
+            Value::Dict { keys, values },
+        ) => {
+            let mut keys_out = under.clone();
+            let mut values_out = below.clone();
+            for (key, item) in keys.iter().zip(values) {
+                match under.iter().position(|k| k == key) {
+                    Some(i) => values_out[i] = coalesce(&below[i], item, node)?,
+                    None => {
+                        keys_out.push(key.clone());
+                        values_out.push(item.clone());
+                    }
+                }
+            }
+            Ok(Value::Dict {
+                keys: keys_out,
+                values: values_out,
+            })
+        }
+        (Value::List(defaults), Value::List(items)) => {
+            if defaults.len() != items.len() {
+                return Err(EvalError::new(
+                    EvalErrorKind::LengthMismatch(format!(
+                        "{} vs {} in fill",
+                        defaults.len(),
+                        items.len()
+                    )),
+                    node,
+                ));
+            }
+            let filled = defaults
+                .iter()
+                .zip(items)
+                .map(|(default, item)| coalesce(default, item, node));
+            filled.collect::<Result<_, _>>().map(Value::List)
+        }
+        (default, Value::Table(table)) => {
+            columns(table, |column| coalesce(default, column, node), node)
+        }
+        (default, Value::Dict { keys, values }) => Ok(Value::Dict {
+            keys: keys.clone(),
+            values: values
+                .iter()
+                .map(|item| coalesce(default, item, node))
+                .collect::<Result<_, _>>()?,
+        }),
+        (default, Value::List(items)) => items
+            .iter()
+            .map(|item| coalesce(default, item, node))
+            .collect::<Result<_, _>>()
+            .map(Value::List),
+        (default, item) if missing(item) => Ok(default.clone()),
+        (_, item) => Ok(item.clone()),
+    }
+}
+
+/// `fill[x;y]`: `y` with its nulls replaced by `x`, item by item where `x`
+/// is a list, key by key where both are dicts
+pub fn fill(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    coalesce(&args[0], &args[1], node)
+}
+
+/// `items` with each null replaced by the last item before it that is not
+/// null; leading nulls stay
+fn forward(items: &[Value]) -> Vec<Value> {
+    let mut last: Option<&Value> = None;
+    items
+        .iter()
+        .map(|item| {
+            if !missing(item) {
+                last = Some(item);
+            }
+            last.unwrap_or(item).clone()
+        })
+        .collect()
+}
+
+/// `fills x`: `x` with each null replaced by the last value before it that
+/// is not null
+pub fn fills(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    match &args[0] {
+        Value::List(items) => Ok(Value::List(forward(items))),
+        Value::Table(table) => columns(
+            table,
+            |column| Ok(Value::List(forward(column.as_list().unwrap_or_default()))),
+            node,
+        ),
+        Value::Dict { keys, values } => Ok(Value::Dict {
+            keys: keys.clone(),
+            values: forward(values),
+        }),
+        atom => Ok(atom.clone()),
+    }
+}
@@ -0,0 +1,231 @@
+//! Pretty-printing values for the console and notebooks
+//!
+//! [`Value::format`] writes a value in wabznasm syntax, all on one line and
+//! in full. A [`Printer`] lays it out for reading instead, within a console
+//! of a given size, as q does:
+//!
+//! ```text
+//! `a`bc!1 2          a | 1
+//!                    bc| 2
+//!
+//! 1000#1 2 3         1 2 3 1 2 3 1 2 3 1 2 3 1 2 3 1 2 3 1 2 3 1 2 3 1 2 3 1 2 3 1..
+//!                    1000 items
+//! ```
+//!
+//! Dictionaries are aligned on their keys and tables on their columns. A
+//! value with more lines than the console has rows shows the first and last
+//! of them either side of `..`, followed by how many there are; a line wider
+//! than the console is cut short with `..`, and a list cut short is followed
+//! by its length. The REPL's `\page` shows a value in full instead.
+
+use crate::environment::Value;
+use crate::table::{self, Table};
+use lasso::Rodeo;
+use std::ops::Range;
+
+/// Console width the REPL and notebooks assume unless told otherwise
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Console height the REPL and notebooks assume unless told otherwise
+pub const DEFAULT_ROWS: usize = 25;
+
+/// Marks where a line or a list of lines was cut short
+const ELLIPSIS: &str = "..";
+
+/// Indices of the lines shown from the start and from the end of a value
+type Ends = (Range<usize>, Range<usize>);
+
+/// Renders values to fit a console of a given size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Printer {
+    /// Most characters in a line
+    pub width: usize,
+    /// Most lines in a rendering, not counting table headers
+    pub rows: usize,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self::new(DEFAULT_WIDTH, DEFAULT_ROWS)
+    }
+}
+
+impl Printer {
+    /// A printer for a console `width` characters wide and `rows` lines high;
+    /// each is at least enough to show `..`
+    pub fn new(width: usize, rows: usize) -> Self {
+        Self {
+            width: width.max(ELLIPSIS.len()),
+            rows: rows.max(1),
+        }
+    }
+
+    /// A printer that shows values in full, however large
+    pub fn unlimited() -> Self {
+        Self::new(usize::MAX, usize::MAX)
+    }
+
+    /// `value` laid out to fit the console: tables as aligned columns,
+    /// dictionaries as aligned keys and values, anything else on one line
+    pub fn render(&self, value: &Value, interner: &Rodeo) -> String {
+        match value {
+            Value::Table(table) => self.render_table(table, interner),
+            Value::Dict { keys, values } if !keys.is_empty() => {
+                self.dict(keys, values, interner).join("\n")
+            }
+            other => {
+                let line = self.inline(other, interner);
+                let len = match other {
+                    Value::IntVector(ints) => Some(ints.len()),
+                    Value::FloatVector(floats) => Some(floats.len()),
+                    other => other.as_list().map(<[Value]>::len),
+                };
+                match len {
+                    Some(len) if line.ends_with(ELLIPSIS) => format!("{}\n{} items", line, len),
+                    _ => line,
+                }
+            }
+        }
+    }
+
+    /// A header, a rule and the rows of `table`, as [`Table::render`] lays
+    /// them out; if there are more than fit, only the first and last either
+    /// side of `..`, and then how many there are
+    pub fn render_table(&self, table: &Table, interner: &Rodeo) -> String {
+        let (head, tail) = self.ends(table.len());
+        let rows: Vec<usize> = head.clone().chain(tail.clone()).collect();
+        let shown = table
+            .select_rows(&rows)
+            .expect("the first and last rows are within the table");
+        let mut lines: Vec<String> = shown
+            .render(interner)
+            .lines()
+            .map(|line| self.cut(line.to_string()))
+            .collect();
+        if rows.len() < table.len() {
+            // The header takes the lines that are not rows
+            let at = lines.len() - rows.len() + head.len();
+            lines.insert(at, ELLIPSIS.to_string());
+            lines.push(format!("{} rows", table.len()));
+        }
+        lines.join("\n")
+    }
+
+    /// The indices of the first and of the last of `len` lines that fit the
+    /// console, all of them in the first if they all fit
+    fn ends(&self, len: usize) -> Ends {
+        if len <= self.rows {
+            return (0..len, len..len);
+        }
+        let first = self.rows.div_ceil(2);
+        (0..first, len - (self.rows - first)..len)
+    }
+
+    /// `value` in wabznasm syntax, cut short at the console width; for
+    /// values inside messages
+    pub fn inline(&self, value: &Value, interner: &Rodeo) -> String {
+        // Every item takes at least one character and a separator, so more
+        // than a width's worth cannot show
+        let shown = match value {
+            Value::List(items) if items.len() > self.width => {
+                Some(Value::List(items[..self.width].to_vec()))
+            }
+            Value::IntVector(ints) if ints.len() > self.width => {
+                Some(Value::IntVector(ints.clone().sliced(0, self.width)))
+            }
+            Value::FloatVector(floats) if floats.len() > self.width => {
+                Some(Value::FloatVector(floats.clone().sliced(0, self.width)))
+            }
+            _ => None,
+        };
+        let text = match shown {
+            Some(shown) => format!("{}{}", shown.format(interner), ELLIPSIS),
+            None => value.format(interner),
+        };
+        self.cut(text)
+    }
+
+    /// `line` cut to the console width, ending in `..` if it was cut
+    fn cut(&self, line: String) -> String {
+        match line.char_indices().nth(self.width) {
+            Some(_) => {
+                let keep = self.width - ELLIPSIS.len();
+                let end = line.char_indices().nth(keep).map_or(line.len(), |(i, _)| i);
+                format!("{}{}", &line[..end], ELLIPSIS)
+            }
+            None => line,
+        }
+    }
+
+    /// Keys and values side by side, the keys padded to a common width and
+    /// separated from the values by a bar
+    ///
+    /// Long dictionaries show their first and last entries, as tables do.
+    fn dict(&self, keys: &[Value], values: &[Value], interner: &Rodeo) -> Vec<String> {
+        let (head, tail) = self.ends(keys.len());
+        let shown: Vec<usize> = head.clone().chain(tail).collect();
+        let cells: Vec<String> = (shown.iter())
+            .map(|&i| table::cell(&keys[i], interner))
+            .collect();
+        let width = cells
+            .iter()
+            .map(|key| key.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut lines: Vec<String> = cells
+            .iter()
+            .zip(&shown)
+            .map(|(key, &i)| {
+                let line = format!("{:<width$}| {}", key, self.inline(&values[i], interner));
+                self.cut(line)
+            })
+            .collect();
+        if shown.len() < keys.len() {
+            lines.insert(head.len(), ELLIPSIS.to_string());
+            lines.push(format!("{} keys", keys.len()));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(printer: Printer, value: Value) -> String {
+        printer.render(&value, &Rodeo::default())
+    }
+
+    #[test]
+    fn test_long_lines_are_cut() {
+        let printer = Printer::new(10, 5);
+        let list = Value::from((0..100i64).collect::<Vec<_>>());
+        assert_eq!(render(printer, list), "0 1 2 3 ..\n100 items");
+        assert_eq!(render(printer, Value::from(vec![1i64, 2])), "1 2");
+    }
+
+    #[test]
+    fn test_dicts_align_their_keys() {
+        let dict = Value::Dict {
+            keys: Value::from(vec!["a", "bc"]).as_list().unwrap().to_vec(),
+            values: vec![Value::Integer(1), Value::from(vec![2i64, 3])],
+        };
+        assert_eq!(render(Printer::default(), dict.clone()), "a | 1\nbc| 2 3");
+        assert_eq!(render(Printer::new(80, 1), dict), "a| 1\n..\n2 keys");
+    }
+
+    #[test]
+    fn test_tables_show_their_first_and_last_rows() {
+        let table = Table::from_dict(
+            &["x".into()],
+            &[Value::from((1..=1000i64).collect::<Vec<_>>())],
+        )
+        .unwrap();
+        assert_eq!(
+            render(Printer::new(80, 3), Value::Table(table.clone())),
+            "x\n-\n1\n2\n..\n1000\n1000 rows"
+        );
+        let full = render(Printer::unlimited(), Value::Table(table));
+        assert_eq!(full.lines().count(), 1002);
+    }
+}
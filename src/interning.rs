@@ -5,10 +5,11 @@
 //! evaluation process. Using string interning reduces memory usage and enables
 //! faster string comparisons via pointer equality.
 
-use lasso::Spur;
-// ThreadedRodeo and LazyLock are no longer needed as GLOBAL_INTERNER is removed.
-// Rodeo might be needed if other parts of this file still construct Rodeo instances directly,
-// but for now, assuming it's not if all global functionality is gone.
+use lasso::{Spur, ThreadedRodeo};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::LazyLock;
 
 /// Type alias for interned string keys
 ///
@@ -26,3 +27,111 @@ pub type InternedString = Spur;
 // If there are any other functions in this file that were private helpers
 // for the global interner, they should also be removed.
 // For now, this leaves the file very minimal, only defining InternedString.
+
+/// Process-wide table of symbol names
+///
+/// Unlike identifiers, symbols are values: they are returned from sessions,
+/// converted to JSON and storage scalars, and passed to plugins, none of which
+/// have a session's interner to hand. As in q, the symbol table is global and
+/// symbols are never freed.
+static SYMBOLS: LazyLock<ThreadedRodeo> = LazyLock::new(ThreadedRodeo::new);
+
+/// An interned symbol atom: `` `AAPL ``
+///
+/// Copying and comparing symbols for equality is as cheap as an integer;
+/// ordering compares their names.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(Spur);
+
+impl Symbol {
+    /// The symbol named `name`, interning it on first use
+    pub fn new(name: &str) -> Self {
+        Symbol(SYMBOLS.get_or_intern(name))
+    }
+
+    /// The symbol's name
+    pub fn as_str(self) -> &'static str {
+        SYMBOLS.resolve(&self.0)
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.0 == other.0 {
+            return Ordering::Equal;
+        }
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::new(&name)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Symbol::new(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbols_are_interned() {
+        let a = Symbol::new("AAPL");
+        assert_eq!(a, Symbol::from("AAPL".to_string()));
+        assert_eq!(a.as_str(), "AAPL");
+        assert_ne!(a, Symbol::new("MSFT"));
+        // Ordering follows the names, not the interning order
+        assert!(Symbol::new("zzz") > Symbol::new("aaa"));
+        assert_eq!(format!("{:?}", a), "`AAPL");
+    }
+}
@@ -0,0 +1,148 @@
+//! Append-only journal of executed input, for recovering a session
+//!
+//! Each successfully executed input is appended as one line holding a JSON
+//! string, so multi-line cells fit on a line, and synced to disk before the
+//! result is shown. After a crash, replaying the journal into a fresh session
+//! with `wabznasm replay-journal <path>` rebuilds its bindings.
+//!
+//! A crash part-way through an append leaves a torn last line; [`read`]
+//! drops it, since its input never reported a result, and [`Journal::open`]
+//! cuts it off so that new entries start on a line of their own.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// An open journal that entries are appended to
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+}
+
+impl Journal {
+    /// Open the journal at `path` for appending, creating it if needed and
+    /// dropping a torn last line
+    pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let path = path.into();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+        let complete = complete_len(&mut file)?;
+        if complete < file.metadata()?.len() {
+            file.set_len(complete)?;
+            file.sync_data()?;
+        }
+        Ok(Self { file, path })
+    }
+
+    /// Path the journal is written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `source` and wait until it is on disk
+    pub fn record(&mut self, source: &str) -> io::Result<()> {
+        let mut line = serde_json::to_string(source)?;
+        line.push('\n');
+        // One write per entry, so a crash tears at most the last line
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()
+    }
+}
+
+/// Length of `file` up to the end of its last complete line
+fn complete_len(file: &mut File) -> io::Result<u64> {
+    let mut buffer = [0; 4096];
+    let mut end = file.metadata()?.len();
+    while end > 0 {
+        let start = end.saturating_sub(buffer.len() as u64);
+        let chunk = &mut buffer[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(newline) = chunk.iter().rposition(|&byte| byte == b'\n') {
+            return Ok(start + newline as u64 + 1);
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+/// Entries of the journal at `path`, oldest first
+pub fn read(path: &Path) -> io::Result<Vec<String>> {
+    let lines = BufReader::new(File::open(path)?)
+        .lines()
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut entries = Vec::with_capacity(lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) if index + 1 == lines.len() => break,
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{}:{}: malformed journal entry: {}",
+                        path.display(),
+                        index + 1,
+                        e
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.log");
+        let mut journal = Journal::open(&path).unwrap();
+        journal.record("x: 1").unwrap();
+        journal.record("f: {[a]\n  a+x}").unwrap();
+        drop(journal);
+
+        // Reopening appends rather than truncating
+        Journal::open(&path).unwrap().record("f[2]").unwrap();
+        assert_eq!(read(&path).unwrap(), ["x: 1", "f: {[a]\n  a+x}", "f[2]"]);
+    }
+
+    #[test]
+    fn test_torn_last_entry_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.log");
+        std::fs::write(&path, "\"x: 1\"\n\"y: ").unwrap();
+        assert_eq!(read(&path).unwrap(), ["x: 1"]);
+
+        std::fs::write(&path, "\"x: 1\"\ngarbage\n\"y: 2\"\n").unwrap();
+        assert_eq!(read(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_appending_after_a_crash_drops_the_torn_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.log");
+        std::fs::write(&path, "\"x: 1\"\n\"y: ").unwrap();
+        assert_eq!(read(&path).unwrap(), ["x: 1"]);
+
+        // Replayed, then continued
+        Journal::open(&path).unwrap().record("z: 3").unwrap();
+        assert_eq!(read(&path).unwrap(), ["x: 1", "z: 3"]);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "\"x: 1\"\n\"z: 3\"\n"
+        );
+
+        // A torn line alone, with nothing complete before it
+        std::fs::write(&path, "\"y: ").unwrap();
+        Journal::open(&path).unwrap().record("z: 3").unwrap();
+        assert_eq!(read(&path).unwrap(), ["z: 3"]);
+    }
+}
@@ -0,0 +1,26 @@
+//! Comms: the variable explorer's channel to a notebook frontend
+//!
+//! A frontend opens a comm with target [`VARIABLES_TARGET`] and receives the
+//! session's bindings at once and again after every executed cell. Sending
+//! `{"path": ["t", "px"]}` on the comm asks for the parts of a binding
+//! instead, as the REPL's `\vars t px` lists them. Each reply is a
+//! `comm_msg` whose data holds the `path` and its `variables`, or an `error`.
+
+use crate::jupyter::session::JupyterSession;
+use serde_json::{Map, Value as JsonValue, json};
+
+/// Comm target name of the variable explorer
+pub const VARIABLES_TARGET: &str = "wabznasm.variables";
+
+/// Data of the explorer's reply to a message with `data`
+pub fn variables_message(session: &JupyterSession, data: &Map<String, JsonValue>) -> JsonValue {
+    let path: Vec<&str> = data
+        .get("path")
+        .and_then(JsonValue::as_array)
+        .map(|parts| parts.iter().filter_map(JsonValue::as_str).collect())
+        .unwrap_or_default();
+    match session.explore(&path) {
+        Ok(variables) => json!({ "path": path, "variables": variables }),
+        Err(error) => json!({ "path": path, "error": error }),
+    }
+}
@@ -1,6 +1,44 @@
 use crate::environment::Value;
+use crate::format::Printer;
+use crate::table::{self, Table};
 use serde_json::{Value as JsonValue, json};
 use std::collections::HashMap;
+use storage::{ResultSet, ScalarValue};
+
+/// MIME type of the Data Resource bundle rendered as a grid by JupyterLab
+/// and nteract
+pub const DATA_RESOURCE_MIME: &str = "application/vnd.dataresource+json";
+
+/// Table Schema field type for a column holding `value`
+fn field_type(value: &ScalarValue) -> &'static str {
+    match value {
+        ScalarValue::Boolean(_) => "boolean",
+        ScalarValue::Float32(_) | ScalarValue::Float64(_) => "number",
+        ScalarValue::Utf8(_) | ScalarValue::Binary(_) => "string",
+        ScalarValue::Timestamp(_) => "datetime",
+        ScalarValue::Null => "any",
+        _ => "integer",
+    }
+}
+
+/// JSON form of a cell: numbers and booleans as themselves, timestamps in
+/// RFC 3339 and everything else as text
+fn cell_json(value: &ScalarValue) -> JsonValue {
+    match value {
+        ScalarValue::Null => JsonValue::Null,
+        ScalarValue::Boolean(b) => json!(b),
+        ScalarValue::Float32(f) => json!(f),
+        ScalarValue::Float64(f) => json!(f),
+        ScalarValue::Timestamp(nanos) => json!(
+            chrono::DateTime::from_timestamp_nanos(*nanos)
+                .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+        ),
+        other => match other.as_i64() {
+            Some(n) => json!(n),
+            None => json!(other.to_string()),
+        },
+    }
+}
 
 /// Formats wabznasm values for display in Jupyter
 pub struct DisplayFormatter;
@@ -11,14 +49,42 @@ impl DisplayFormatter {
         let mut display_data = HashMap::new();
 
         match value {
-            Value::Integer(n) => {
-                // Display integers as plain text and HTML
-                display_data.insert("text/plain".to_string(), json!(n.to_string()));
+            Value::Integer(_) | Value::BigInt(_) => {
+                // Display integers as plain text and HTML; nulls read as 0N
+                let text = value.format(interner);
+                display_data.insert("text/plain".to_string(), json!(text));
+                display_data.insert(
+                    "text/html".to_string(),
+                    json!(format!("<span class=\"nb-integer\">{}</span>", text)),
+                );
+            }
+            Value::Float(_)
+            | Value::Boolean(_)
+            | Value::Symbol(_)
+            | Value::Char(_)
+            | Value::Date(_)
+            | Value::Time(_)
+            | Value::Timestamp(_)
+            | Value::List(_)
+            | Value::Attributed(_)
+            | Value::IntVector(_)
+            | Value::FloatVector(_)
+            | Value::Dict { .. }
+            | Value::Builtin(_)
+            | Value::Native(_)
+            | Value::Memo { .. }
+            | Value::Projection { .. } => {
+                let text = Printer::default().render(value, interner);
+                display_data.insert("text/plain".to_string(), json!(text));
                 display_data.insert(
                     "text/html".to_string(),
-                    json!(format!("<span class=\"nb-integer\">{}</span>", n)),
+                    json!(format!(
+                        "<code class=\"nb-list\">{}</code>",
+                        html_escape::encode_text(&text)
+                    )),
                 );
             }
+            Value::Table(table) => return Self::format_table(table, interner),
             Value::Function { params, body, .. } => {
                 // Display functions with their signature
                 let body_str = interner.resolve(body);
@@ -63,6 +129,101 @@ impl DisplayFormatter {
         }
     }
 
+    /// Data Resource bundle for query results: a Table Schema with one field
+    /// per column, typed by its first non-null cell, and one record per row
+    pub fn data_resource(result: &ResultSet) -> JsonValue {
+        let fields: Vec<JsonValue> = result
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let kind = result
+                    .rows
+                    .iter()
+                    .map(|row| &row[i])
+                    .find(|value| **value != ScalarValue::Null)
+                    .map_or("any", field_type);
+                json!({ "name": name, "type": kind })
+            })
+            .collect();
+        let data: Vec<JsonValue> = result
+            .rows
+            .iter()
+            .map(|row| {
+                let record: serde_json::Map<String, JsonValue> = result
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(name, value)| (name.clone(), cell_json(value)))
+                    .collect();
+                JsonValue::Object(record)
+            })
+            .collect();
+        json!({ "schema": { "fields": fields }, "data": data })
+    }
+
+    /// Convert query results to Jupyter display data: an aligned text table
+    /// in q's layout, an HTML table and a Data Resource bundle for grid viewers
+    pub fn format_result_set(result: &ResultSet) -> HashMap<String, JsonValue> {
+        let cells: Vec<Vec<String>> = result
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|value| value.to_string()).collect())
+            .collect();
+        let mut display_data = Self::tabular(&result.columns, &cells);
+        display_data.insert(DATA_RESOURCE_MIME.to_string(), Self::data_resource(result));
+        display_data
+    }
+
+    /// Plain text and HTML renderings of a table of cells
+    fn tabular(columns: &[String], cells: &table::Cells) -> HashMap<String, JsonValue> {
+        let mut html = String::from("<table class=\"nb-table\"><thead><tr>");
+        for column in columns {
+            html.push_str(&format!("<th>{}</th>", html_escape::encode_text(column)));
+        }
+        html.push_str("</tr></thead><tbody>");
+        for row in cells {
+            html.push_str("<tr>");
+            for cell in row {
+                html.push_str(&format!("<td>{}</td>", html_escape::encode_text(cell)));
+            }
+            html.push_str("</tr>");
+        }
+        html.push_str("</tbody></table>");
+
+        let mut display_data = HashMap::new();
+        display_data.insert(
+            "text/plain".to_string(),
+            json!(table::layout(columns, cells)),
+        );
+        display_data.insert("text/html".to_string(), json!(html));
+        display_data
+    }
+
+    /// Display data for a table value; tables whose cells are all atoms are
+    /// also offered as a Data Resource bundle
+    fn format_table(table: &Table, interner: &lasso::Rodeo) -> HashMap<String, JsonValue> {
+        let columns: Vec<String> = table.names().iter().map(|n| n.to_string()).collect();
+        let cells: Vec<Vec<String>> = (0..table.len())
+            .map(|i| {
+                table
+                    .columns()
+                    .iter()
+                    .map(|column| table::cell(&column[i], interner))
+                    .collect()
+            })
+            .collect();
+        let mut display_data = Self::tabular(&columns, &cells);
+        display_data.insert(
+            "text/plain".to_string(),
+            json!(Printer::default().render_table(table, interner)),
+        );
+        if let Ok(result) = ResultSet::try_from(table) {
+            display_data.insert(DATA_RESOURCE_MIME.to_string(), Self::data_resource(&result));
+        }
+        display_data
+    }
+
     /// Create CSS styles for wabznasm output
     pub fn get_css_styles() -> &'static str {
         r#"
@@ -87,6 +248,10 @@ impl DisplayFormatter {
             color: #24292e;
             font-family: 'SFMono-Regular', Consolas, 'Liberation Mono', Menlo, monospace;
         }
+        .nb-table th {
+            text-align: left;
+            border-bottom: 1px solid #e9ecef;
+        }
         </style>
         "#
     }
@@ -1,4 +1,4 @@
-use crate::errors::{EvalError, EvalErrorKind};
+use crate::errors::{EvalError, EvalErrorKind, line_column};
 use serde_json::{Value as JsonValue, json};
 use std::collections::HashMap;
 
@@ -25,10 +25,15 @@ impl JupyterErrorFormatter {
     }
 
     /// Create a traceback with syntax highlighting and location info
-    pub fn create_traceback(error: &EvalError, _source_code: &str) -> Vec<String> {
+    pub fn create_traceback(error: &EvalError, source_code: &str) -> Vec<String> {
         let mut traceback = Vec::new();
         traceback.push(format!("WabznasmError: {}", error));
-        // TODO: Add source location from error.span when implemented
+        if let Some(location) = Self::locate(error, source_code) {
+            traceback.push(location);
+        }
+        if let Some(fix) = &error.suggestion {
+            traceback.push(format!("  did you mean `{}`?", fix));
+        }
         match &error.kind {
             EvalErrorKind::Other(msg) => traceback.push(format!("Error: {}", msg)),
             EvalErrorKind::DivisionByZero => traceback.push("Error: Division by zero".to_string()),
@@ -37,9 +42,23 @@ impl JupyterErrorFormatter {
             }
             _ => traceback.push(format!("Error: {}", error.kind)),
         }
+        traceback.extend(error.trace().into_iter().map(|line| format!("  {}", line)));
         traceback
     }
 
+    /// Where the error occurred, as its line and column and the text of its
+    /// span, in the source attached to it or else in the cell
+    fn locate(error: &EvalError, source_code: &str) -> Option<String> {
+        let source = match &error.src {
+            Some(src) => src.inner().as_str(),
+            None => source_code,
+        };
+        let start = error.span.offset();
+        let text = source.get(start..start + error.span.len())?;
+        let (line, column) = line_column(source, start);
+        Some(format!("  at line {}, column {}: {}", line, column, text))
+    }
+
     /// Format error for HTML display with syntax highlighting
     pub fn format_error_html(error: &EvalError, source_code: &str) -> String {
         let traceback = Self::create_traceback(error, source_code);
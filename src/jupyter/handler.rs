@@ -1,16 +1,30 @@
+use crate::cancel::CancelToken;
+use crate::completion;
+use crate::journal::Journal;
 use crate::jupyter::errors::JupyterResult;
 use crate::jupyter::{
-    display::JupyterDisplay, errors::JupyterErrorFormatter, session::JupyterSession,
+    comm::{self, VARIABLES_TARGET},
+    display::{DisplayFormatter, JupyterDisplay},
+    errors::JupyterErrorFormatter,
+    inspect,
+    magic::{self, CellMagic},
+    session::JupyterSession,
     signature::SignatureSigner as JP_SignatureSigner,
 };
+use crate::script::{self, Completeness};
 use chrono::Utc;
 use jupyter_protocol::{
-    ExecuteReply, ExecuteRequest, Header, KernelInfoReply, LanguageInfo, ReplyStatus,
-    ShutdownRequest, messaging::CodeMirrorMode, messaging::ExecutionCount,
+    CommClose, CommId, CommInfo, CommInfoReply, CommInfoRequest, CommMsg, CommOpen, CompleteReply,
+    CompleteRequest, DebugReply, DebugRequest, ExecuteReply, ExecuteRequest, Header, InspectReply,
+    InspectRequest, IsCompleteReply, IsCompleteReplyStatus, IsCompleteRequest, KernelInfoReply,
+    LanguageInfo, Media, MediaType, ReplyStatus, ShutdownRequest, messaging::CodeMirrorMode,
+    messaging::ExecutionCount,
 };
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::Sender;
 use uuid::Uuid;
 use zeromq::ZmqMessage;
@@ -29,6 +43,12 @@ struct SimplifiedMessage {
     content: JsonValue,
 }
 
+/// A failed cell: the exception name, message and traceback lines
+type CellError = (String, String, Vec<String>);
+
+/// Display data of a successful cell, or how it failed
+type CellResult = Result<HashMap<String, JsonValue>, CellError>;
+
 /// The main Jupyter kernel implementation for wabznasm
 pub struct WabznasmJupyterKernel {
     /// Persistent session that maintains environment across cells
@@ -37,6 +57,8 @@ pub struct WabznasmJupyterKernel {
     iopub_sender: Sender<ZmqMessage>,
     /// Signature signer for IOPub messages
     signer: Arc<JP_SignatureSigner>,
+    /// Open comms of the variable explorer
+    explorer_comms: HashSet<CommId>,
 }
 
 impl WabznasmJupyterKernel {
@@ -47,9 +69,64 @@ impl WabznasmJupyterKernel {
             session: JupyterSession::new(),
             iopub_sender,
             signer,
+            explorer_comms: HashSet::new(),
         }
     }
 
+    /// A token that interrupts the cell in progress when cancelled
+    pub fn cancel_token(&self) -> CancelToken {
+        self.session.cancel_token()
+    }
+
+    /// Attach a database root for the `.db` builtins and `%%sql` cells
+    pub fn set_database<P: Into<PathBuf>>(&mut self, root: P) {
+        self.session.set_database(root);
+    }
+
+    /// Open the attached database read-only
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.session.set_read_only(read_only);
+    }
+
+    /// Journal successfully executed cells to `journal`
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.session.set_journal(journal);
+    }
+
+    /// Stop each cell that runs longer than `timeout`
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.session.set_timeout(timeout);
+    }
+
+    /// Fail cells computing a value of more than `limit` bytes
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.session.set_memory_limit(limit);
+    }
+
+    /// Metadata for the reply to the most recent execute_request: its wall
+    /// time and row counts
+    pub fn execution_metadata(&self) -> HashMap<String, JsonValue> {
+        self.session.stats().to_metadata()
+    }
+
+    /// Run a cell, returning its display data
+    ///
+    /// The cell runs on a blocking thread, so the kernel's other tasks, such
+    /// as the control socket's, go on meanwhile: an interrupt cancels the
+    /// session's token and the cell fails.
+    async fn run_cell(&mut self, code: &str) -> CellResult {
+        let mut session = std::mem::take(&mut self.session);
+        let code = code.to_string();
+        let ran = tokio::task::spawn_blocking(move || {
+            let result = run_cell(&mut session, &code);
+            (session, result)
+        })
+        .await;
+        let (session, result) = ran.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+        self.session = session;
+        result
+    }
+
     /// Handle kernel_info_request
     pub fn kernel_info(&self, _parent_header: &Header) -> KernelInfoReply {
         KernelInfoReply {
@@ -94,16 +171,24 @@ impl WabznasmJupyterKernel {
                 content: status_content,
             };
             // Send busy status via IOPub actor
-            if let Ok(zmq_msg) = construct_zmq_message_for_iopub(&msg, &self.signer) {
-                if let Err(e) = self.iopub_sender.send(zmq_msg).await {
-                    eprintln!("Failed to send busy status: {}", e);
-                }
+            if let Ok(zmq_msg) = construct_zmq_message_for_iopub(&msg, &self.signer)
+                && let Err(e) = self.iopub_sender.send(zmq_msg).await
+            {
+                eprintln!("Failed to send busy status: {}", e);
             }
         }
 
-        let exec_reply_content = match self.session.execute(code) {
-            Ok(result) => {
-                let display_data_map = result.to_display_data(self.session.interner());
+        let result = self.run_cell(code).await;
+        let warnings: Vec<String> = (self.session.warnings().iter())
+            .map(|warning| warning.describe(code) + "\n")
+            .collect();
+        if !warnings.is_empty() {
+            let content = serde_json::json!({ "name": "stderr", "text": warnings.concat() });
+            self.send_iopub(parent_header, "stream", content).await;
+        }
+
+        let exec_reply_content = match result {
+            Ok(display_data_map) => {
                 if !display_data_map.is_empty() {
                     let iopub_header =
                         self.create_iopub_header(parent_header, "execute_result".to_string());
@@ -119,10 +204,10 @@ impl WabznasmJupyterKernel {
                         content: exec_result_content,
                     };
                     // Send execute_result via IOPub actor
-                    if let Ok(zmq_msg) = construct_zmq_message_for_iopub(&msg, &self.signer) {
-                        if let Err(e) = self.iopub_sender.send(zmq_msg).await {
-                            eprintln!("Failed to send execute_result: {}", e);
-                        }
+                    if let Ok(zmq_msg) = construct_zmq_message_for_iopub(&msg, &self.signer)
+                        && let Err(e) = self.iopub_sender.send(zmq_msg).await
+                    {
+                        eprintln!("Failed to send execute_result: {}", e);
                     }
                 }
                 ExecuteReply {
@@ -133,11 +218,7 @@ impl WabznasmJupyterKernel {
                     error: None,
                 }
             }
-            Err(eval_error) => {
-                let ename = "WabznasmError".to_string();
-                let evalue = eval_error.to_string();
-                let traceback = JupyterErrorFormatter::create_traceback(&eval_error, code);
-
+            Err((ename, evalue, traceback)) => {
                 let iopub_header = self.create_iopub_header(parent_header, "error".to_string());
                 let error_content = serde_json::json!({
                     "ename": ename.clone(),
@@ -151,10 +232,10 @@ impl WabznasmJupyterKernel {
                     content: error_content,
                 };
                 // Send error via IOPub actor
-                if let Ok(zmq_msg) = construct_zmq_message_for_iopub(&msg, &self.signer) {
-                    if let Err(e) = self.iopub_sender.send(zmq_msg).await {
-                        eprintln!("Failed to send error IOPub: {}", e);
-                    }
+                if let Ok(zmq_msg) = construct_zmq_message_for_iopub(&msg, &self.signer)
+                    && let Err(e) = self.iopub_sender.send(zmq_msg).await
+                {
+                    eprintln!("Failed to send error IOPub: {}", e);
                 }
 
                 ExecuteReply {
@@ -167,6 +248,14 @@ impl WabznasmJupyterKernel {
             }
         };
 
+        // Open explorers show the bindings the cell left
+        let comm_ids: Vec<CommId> = self.explorer_comms.iter().cloned().collect();
+        for comm_id in comm_ids {
+            let data = comm::variables_message(&self.session, &Default::default());
+            self.send_comm_message(parent_header, "comm_msg", &comm_id, data)
+                .await;
+        }
+
         // Send idle status
         {
             let iopub_header = self.create_iopub_header(parent_header, "status".to_string());
@@ -180,10 +269,10 @@ impl WabznasmJupyterKernel {
                 content: status_content,
             };
             // Send idle status via IOPub actor
-            if let Ok(zmq_msg) = construct_zmq_message_for_iopub(&msg, &self.signer) {
-                if let Err(e) = self.iopub_sender.send(zmq_msg).await {
-                    eprintln!("Failed to send idle status: {}", e);
-                }
+            if let Ok(zmq_msg) = construct_zmq_message_for_iopub(&msg, &self.signer)
+                && let Err(e) = self.iopub_sender.send(zmq_msg).await
+            {
+                eprintln!("Failed to send idle status: {}", e);
             }
         }
         exec_reply_content
@@ -202,6 +291,167 @@ impl WabznasmJupyterKernel {
         }
     }
 
+    /// Handle comm_open: a variable explorer is sent the bindings, and a
+    /// comm with any other target is closed
+    pub async fn comm_open(&mut self, open: CommOpen, parent_header: &Header) {
+        if open.target_name == VARIABLES_TARGET {
+            let data = comm::variables_message(&self.session, &open.data);
+            self.explorer_comms.insert(open.comm_id.clone());
+            self.send_comm_message(parent_header, "comm_msg", &open.comm_id, data)
+                .await;
+        } else {
+            let data = serde_json::json!({});
+            self.send_comm_message(parent_header, "comm_close", &open.comm_id, data)
+                .await;
+        }
+    }
+
+    /// Handle comm_msg: an explorer is sent the parts of the path it asks for
+    pub async fn comm_msg(&mut self, message: CommMsg, parent_header: &Header) {
+        if self.explorer_comms.contains(&message.comm_id) {
+            let data = comm::variables_message(&self.session, &message.data);
+            self.send_comm_message(parent_header, "comm_msg", &message.comm_id, data)
+                .await;
+        }
+    }
+
+    /// Handle comm_close
+    pub fn comm_close(&mut self, close: CommClose) {
+        self.explorer_comms.remove(&close.comm_id);
+    }
+
+    /// Handle comm_info_request: the open comms, of the requested target if
+    /// one is named
+    pub fn comm_info(&self, request: &CommInfoRequest) -> CommInfoReply {
+        let listed = request.target_name.is_empty() || request.target_name == VARIABLES_TARGET;
+        let comms = self
+            .explorer_comms
+            .iter()
+            .filter(|_| listed)
+            .map(|comm_id| {
+                let info = CommInfo {
+                    target_name: VARIABLES_TARGET.to_string(),
+                };
+                (comm_id.clone(), info)
+            })
+            .collect();
+        CommInfoReply {
+            status: ReplyStatus::Ok,
+            comms,
+            error: None,
+        }
+    }
+
+    /// Handle complete_request: the names the word before the cursor may be
+    /// completed to, with the span of the cell they replace
+    ///
+    /// Cursor positions are counted in characters, as the protocol has it.
+    pub fn complete(&self, request: &CompleteRequest) -> CompleteReply {
+        let code = &request.code;
+        let pos = (code.char_indices().nth(request.cursor_pos)).map_or(code.len(), |(i, _)| i);
+        let completion = completion::complete(code, pos, &self.session.names());
+        let matches = completion.replacements(code, pos);
+        let types: Vec<JsonValue> = (completion.matches.iter())
+            .zip(&matches)
+            .map(|(name, text)| {
+                let kind = if name.function {
+                    "function"
+                } else {
+                    "variable"
+                };
+                serde_json::json!({ "text": text, "type": kind })
+            })
+            .collect();
+        let mut metadata = serde_json::Map::new();
+        metadata.insert("_jupyter_types_experimental".to_string(), types.into());
+        CompleteReply {
+            matches,
+            cursor_start: code[..completion.start].chars().count(),
+            cursor_end: code[..pos].chars().count(),
+            metadata,
+            status: ReplyStatus::Ok,
+            error: None,
+        }
+    }
+
+    /// Handle inspect_request: a description of the name at the cursor, or
+    /// nothing found if no name there is bound
+    pub fn inspect(&self, request: &InspectRequest) -> InspectReply {
+        let code = &request.code;
+        let pos = (code.char_indices().nth(request.cursor_pos)).map_or(code.len(), |(i, _)| i);
+        let inspection = inspect::inspect(&self.session, code, pos);
+        let data = match &inspection {
+            Some(inspection) => Media::new(vec![
+                MediaType::Plain(inspection.plain()),
+                MediaType::Html(inspection.html()),
+            ]),
+            None => Media::default(),
+        };
+        InspectReply {
+            found: inspection.is_some(),
+            data,
+            metadata: serde_json::Map::new(),
+            status: ReplyStatus::Ok,
+            error: None,
+        }
+    }
+
+    /// Handle is_complete_request: whether the code is ready to run, or
+    /// leaves a bracket open for another, indented, line
+    pub fn is_complete(&self, request: &IsCompleteRequest) -> IsCompleteReply {
+        let (status, indent) = match script::completeness(&request.code) {
+            Completeness::Complete => (IsCompleteReplyStatus::Complete, ""),
+            Completeness::Incomplete => (IsCompleteReplyStatus::Incomplete, "  "),
+            Completeness::Invalid => (IsCompleteReplyStatus::Invalid, ""),
+        };
+        IsCompleteReply {
+            status,
+            indent: indent.to_string(),
+        }
+    }
+
+    /// Handle debug_request: the kernel has no debugger, as its kernel_info
+    /// says, so each request is answered as failed
+    pub fn debug(request: &DebugRequest) -> DebugReply {
+        let content = &request.content;
+        DebugReply {
+            content: serde_json::json!({
+                "type": "response",
+                "request_seq": content.get("seq").cloned().unwrap_or(JsonValue::Null),
+                "command": content.get("command").cloned().unwrap_or(JsonValue::Null),
+                "success": false,
+                "message": "wabznasm has no debugger",
+            }),
+        }
+    }
+
+    /// Publish a comm message of type `msg_type` with `data` on IOPub
+    async fn send_comm_message(
+        &self,
+        parent_header: &Header,
+        msg_type: &str,
+        comm_id: &CommId,
+        data: JsonValue,
+    ) {
+        let content = serde_json::json!({ "comm_id": comm_id, "data": data });
+        self.send_iopub(parent_header, msg_type, content).await;
+    }
+
+    /// Send an IOPub message of `msg_type` in reply to `parent_header`
+    async fn send_iopub(&self, parent_header: &Header, msg_type: &str, content: JsonValue) {
+        let msg = SimplifiedMessage {
+            header: self.create_iopub_header(parent_header, msg_type.to_string()),
+            parent_header: Some(parent_header.clone()),
+            metadata: HashMap::new(),
+            content,
+        };
+        if let Ok(zmq_msg) = construct_zmq_message_for_iopub(&msg, &self.signer)
+            && let Err(e) = self.iopub_sender.send(zmq_msg).await
+        {
+            eprintln!("Failed to send {}: {}", msg_type, e);
+        }
+    }
+
     /// Create a header for IOPub messages
     fn create_iopub_header(&self, parent_header: &Header, msg_type: String) -> Header {
         Header {
@@ -215,6 +465,28 @@ impl WabznasmJupyterKernel {
     }
 }
 
+/// Run a cell in `session`, returning its display data
+fn run_cell(session: &mut JupyterSession, code: &str) -> CellResult {
+    match magic::parse(code) {
+        Some(CellMagic::Sql(sql)) => session
+            .execute_sql(sql)
+            .map(|result| DisplayFormatter::format_result_set(&result))
+            .map_err(|e| {
+                let message = e.to_string();
+                let traceback = vec![format!("SqlError: {}", message)];
+                ("SqlError".to_string(), message, traceback)
+            }),
+        None => match session.execute(code) {
+            Ok(result) => Ok(result.to_display_data(session.interner())),
+            Err(eval_error) => Err((
+                "WabznasmError".to_string(),
+                eval_error.to_string(),
+                JupyterErrorFormatter::create_traceback(&eval_error, code),
+            )),
+        },
+    }
+}
+
 /// Construct a ZMQ message from a SimplifiedMessage for IOPub publishing
 fn construct_zmq_message_for_iopub(
     message: &SimplifiedMessage,
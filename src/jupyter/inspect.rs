@@ -0,0 +1,174 @@
+//! Inspection: what a notebook shows for the name under the cursor
+//!
+//! Shift-Tab sends an `inspect_request`; the reply describes the binding
+//! of the name at the cursor, or of the function whose brackets the cursor
+//! is in: its type and a preview of its value, and for a function its
+//! signature, docstring and, for a lambda, its body. The description is
+//! given as text/plain and text/html.
+
+use crate::completion;
+use crate::environment::Value;
+use crate::explorer::Variable;
+use crate::jupyter::session::JupyterSession;
+use lasso::Rodeo;
+use std::fmt::Write;
+
+/// Labels and texts of the fields of an inspection
+type Fields<'a> = Vec<(&'static str, &'a str)>;
+
+/// What a binding holds, as inspection shows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inspection {
+    pub name: String,
+    /// Type name, as `type` gives it
+    pub type_name: String,
+    /// Items, keys or `rows x columns`; empty for atoms and functions
+    pub shape: String,
+    /// The value's text, shortened as the variable explorer shortens it
+    pub preview: String,
+    /// How a function is called, as `f[x;y]`
+    pub signature: Option<String>,
+    /// A function's docstring, or a builtin's description
+    pub doc: Option<String>,
+    /// A lambda's text
+    pub body: Option<String>,
+}
+
+impl Inspection {
+    /// Describe `value`, bound to `name`
+    pub fn describe(name: &str, value: &Value, interner: &Rodeo) -> Self {
+        let variable = Variable::describe(name, value, interner);
+        let mut inspection = Self {
+            name: name.to_string(),
+            type_name: variable.type_name,
+            shape: variable.shape,
+            preview: variable.preview,
+            signature: None,
+            doc: None,
+            body: None,
+        };
+        // A memoized function is described by the function it caches
+        let function = match value {
+            Value::Memo { function, .. } => function.as_ref(),
+            other => other,
+        };
+        match function {
+            Value::Function { params, doc, .. } => {
+                let params: Vec<&str> = params.iter().map(|p| interner.resolve(p)).collect();
+                inspection.signature = Some(format!("{}[{}]", name, params.join(";")));
+                inspection.doc = doc.map(|doc| interner.resolve(&doc).to_string());
+                inspection.body = Some(function.format(interner));
+            }
+            Value::Builtin(builtin) => {
+                inspection.signature = Some(builtin.signature());
+                inspection.doc = Some(builtin.doc.to_string());
+            }
+            Value::Native(native) => {
+                inspection.signature = Some(native.signature());
+                inspection.doc = native.doc.clone();
+            }
+            _ => {}
+        }
+        inspection
+    }
+
+    /// The description as text, a field a line
+    pub fn plain(&self) -> String {
+        let mut text = format!("{}: {}", self.name, self.type_name);
+        if !self.shape.is_empty() {
+            let _ = write!(text, " ({})", self.shape);
+        }
+        for (label, field) in self.fields() {
+            let _ = write!(text, "\n{}: {}", label, field);
+        }
+        text
+    }
+
+    /// The description as an HTML definition list
+    pub fn html(&self) -> String {
+        let mut html = format!(
+            "<div class=\"nb-inspect\"><code>{}</code>: {}",
+            html_escape::encode_text(&self.name),
+            html_escape::encode_text(&self.type_name)
+        );
+        if !self.shape.is_empty() {
+            let _ = write!(html, " ({})", html_escape::encode_text(&self.shape));
+        }
+        html.push_str("<dl>");
+        for (label, field) in self.fields() {
+            let _ = write!(
+                html,
+                "<dt>{}</dt><dd><pre>{}</pre></dd>",
+                label,
+                html_escape::encode_text(field)
+            );
+        }
+        html.push_str("</dl></div>");
+        html
+    }
+
+    /// The labelled fields there are to show, in order; a function's body
+    /// stands in for its preview
+    fn fields(&self) -> Fields<'_> {
+        let mut fields = Vec::new();
+        if let Some(signature) = &self.signature {
+            fields.push(("Signature", signature.as_str()));
+        }
+        if let Some(doc) = &self.doc {
+            fields.push(("Docstring", doc.as_str()));
+        }
+        match (&self.body, &self.signature) {
+            (Some(body), _) => fields.push(("Body", body.as_str())),
+            (None, None) => fields.push(("Value", self.preview.as_str())),
+            (None, Some(_)) => {}
+        }
+        fields
+    }
+}
+
+/// The name to inspect at byte offset `pos` of `code`: the one the cursor
+/// is in or just after, or else that of the function whose brackets it is
+/// in
+pub fn name_at(code: &str, pos: usize) -> Option<&str> {
+    let word = completion::word_at(code, pos);
+    // A number is not a name
+    if !word.is_empty() && !word.starts_with(|c: char| c.is_ascii_digit()) {
+        return Some(word);
+    }
+    // The innermost bracket left open before the cursor
+    let mut depth = 0;
+    for (i, c) in code[..pos].char_indices().rev() {
+        match c {
+            ']' => depth += 1,
+            '[' if depth == 0 => {
+                let name = completion::word_at(code, i);
+                return (!name.is_empty()).then_some(name);
+            }
+            '[' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Inspect the name at byte offset `pos` of `code` in `session`
+pub fn inspect(session: &JupyterSession, code: &str, pos: usize) -> Option<Inspection> {
+    let name = name_at(code, pos)?;
+    let value = session.lookup(name)?;
+    Some(Inspection::describe(name, &value, session.interner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_at() {
+        assert_eq!(name_at("1+increment[2]", 5), Some("increment"));
+        assert_eq!(name_at("1+increment[2]", 11), Some("increment"));
+        assert_eq!(name_at("f[g[1]; 2]", 8), Some("f"));
+        assert_eq!(name_at("f[g[1]; 2]", 5), Some("g"));
+        assert_eq!(name_at("1 + 2", 2), None);
+        assert_eq!(name_at("f[12]", 3), Some("f"));
+    }
+}
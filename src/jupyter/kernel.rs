@@ -1,3 +1,5 @@
+use crate::cancel::CancelToken;
+use crate::journal::Journal;
 use crate::jupyter::IdentityFrames;
 use crate::jupyter::connection::{ConnectionConfig, ConnectionConfigExt};
 use crate::jupyter::errors::JupyterResult;
@@ -7,17 +9,25 @@ use crate::jupyter::signature::{
     SignatureSigner as JP_SignatureSigner, SignatureVerifier as JP_SignatureVerifier,
 };
 use jupyter_protocol::{
-    Header, JupyterMessageContent, ReplyStatus, ShutdownReply as ProtocolShutdownReply,
-    ShutdownRequest, messaging::ExecutionState, messaging::Status as ProtocolStatus,
+    Header, InterruptReply, JupyterMessageContent, ReplyStatus,
+    ShutdownReply as ProtocolShutdownReply, messaging::ExecutionState,
+    messaging::Status as ProtocolStatus,
 };
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::Instrument;
 use zeromq::{PubSocket, RepSocket, RouterSocket, Socket, SocketRecv, SocketSend, ZmqMessage};
 
 // Type aliases to reduce complexity
 type ZmqSender = Sender<ZmqMessage>;
 type ZmqReceiver = Receiver<ZmqMessage>;
+type RequestReceiver = Receiver<ParsedMessage>;
+
+/// A request waiting for the worker, with the sender of its reply
+type Queued = (ParsedMessage, ZmqSender);
 
 pub struct JupyterKernelRunner {
     config: ConnectionConfig,
@@ -74,6 +84,36 @@ impl JupyterKernelRunner {
         })
     }
 
+    /// Attach a database root for the `.db` builtins and `%%sql` cells
+    pub fn with_database<P: Into<PathBuf>>(mut self, root: P) -> Self {
+        self.kernel_handler.set_database(root);
+        self
+    }
+
+    /// Open the attached database read-only
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.kernel_handler.set_read_only(read_only);
+        self
+    }
+
+    /// Journal successfully executed cells to `journal`
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.kernel_handler.set_journal(journal);
+        self
+    }
+
+    /// Stop each cell that runs longer than `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.kernel_handler.set_timeout(Some(timeout));
+        self
+    }
+
+    /// Fail cells computing a value of more than `limit` bytes
+    pub fn with_memory_limit(mut self, limit: usize) -> Self {
+        self.kernel_handler.set_memory_limit(Some(limit));
+        self
+    }
+
     async fn send_iopub_status(
         &self,
         parent_header: &Header,
@@ -145,6 +185,15 @@ impl JupyterKernelRunner {
         let mut shell_socket = RouterSocket::new();
         shell_socket.bind(&self.config.shell_url()).await?;
         println!("🐚 Shell socket bound to {}", self.config.shell_url());
+        let mut control_socket = RouterSocket::new();
+        control_socket.bind(&self.config.control_url()).await?;
+        println!("🎛️  Control socket bound to {}", self.config.control_url());
+        let (control_replies, mut control_requests) = spawn_control(
+            control_socket,
+            self.kernel_handler.cancel_token(),
+            Arc::clone(&self.verifier),
+            Arc::clone(&self.signer),
+        );
         // IOPub socket is bound and managed by the background actor spawned in new()
         let mut hb_socket = RepSocket::new();
         hb_socket.bind(&self.config.hb_url()).await?;
@@ -191,147 +240,248 @@ impl JupyterKernelRunner {
             eprintln!("❌ Failed to send initial IOPub status (idle): {}", e);
         }
 
-        loop {
-            let zmq_msg = shell_socket.recv().await?;
-            let parsed_msg = match ParsedMessage::parse(&zmq_msg, &self.verifier) {
-                Ok(msg) => msg,
-                Err(e) => {
-                    eprintln!("Error parsing message: {}", e);
-                    continue;
-                }
-            };
-            let parent_header_for_reply = parsed_msg.header.clone();
-            let reply_metadata = HashMap::new();
-            match parsed_msg.content {
-                JupyterMessageContent::KernelInfoRequest(_req_content) => {
-                    let reply_header = Header {
-                        msg_id: uuid::Uuid::new_v4().to_string(),
-                        session: parent_header_for_reply.session.clone(),
-                        username: parent_header_for_reply.username.clone(),
-                        date: chrono::Utc::now(),
-                        msg_type: "kernel_info_reply".to_string(),
-                        version: parent_header_for_reply.version.clone(),
-                    };
-
-                    let kernel_info_reply_content =
-                        self.kernel_handler.kernel_info(&parent_header_for_reply);
-
-                    // Debug: Log the kernel_info_reply content
-                    if let Ok(info_json) = serde_json::to_string_pretty(&kernel_info_reply_content)
-                    {
-                        println!("🔍 Kernel info reply content: {}", info_json);
+        // Requests that need the session wait in a queue for the worker,
+        // which takes them one at a time; the loop meanwhile goes on
+        // answering the rest, so kernel_info and interrupts are not stuck
+        // behind a long cell
+        let Self {
+            kernel_handler,
+            verifier,
+            signer,
+            ..
+        } = self;
+        let kernel_info = kernel_handler.kernel_info(&initial_dummy_header_for_status);
+        let cancel = kernel_handler.cancel_token();
+        let (queue, queued) = tokio::sync::mpsc::channel(1024);
+        let (shell_replies, mut shell_outbox): (ZmqSender, ZmqReceiver) =
+            tokio::sync::mpsc::channel(64);
+        let worker = work(kernel_handler, queued, Arc::clone(signer));
+        let sockets = async {
+            loop {
+                // Control requests, such as shutdown, go ahead of shell
+                // requests; each is answered on the socket it came in on
+                let (request, replies) = tokio::select! {
+                    biased;
+                    Some(request) = control_requests.recv() => (request, &control_replies),
+                    Some(reply) = shell_outbox.recv() => {
+                        shell_socket.send(reply).await?;
+                        continue;
                     }
-                    if let Ok(header_json) = serde_json::to_string_pretty(&reply_header) {
-                        println!("🔍 Kernel info reply header: {}", header_json);
+                    msg = shell_socket.recv() => match ParsedMessage::parse(&msg?, verifier) {
+                        Ok(request) => (request, &shell_replies),
+                        Err(e) => {
+                            eprintln!("Error parsing message: {}", e);
+                            continue;
+                        }
+                    },
+                };
+                let content = match &request.content {
+                    JupyterMessageContent::KernelInfoRequest(_) => {
+                        JupyterMessageContent::KernelInfoReply(Box::new(kernel_info.clone()))
+                    }
+                    JupyterMessageContent::ShutdownRequest(shutdown) => {
+                        // Stop the cell in progress, if any, and the kernel
+                        cancel.cancel();
+                        let content = JupyterMessageContent::ShutdownReply(ProtocolShutdownReply {
+                            restart: shutdown.restart,
+                            status: ReplyStatus::Ok,
+                            error: None,
+                        });
+                        replies
+                            .send(reply(&request, &HashMap::new(), &content, signer)?)
+                            .await?;
+                        // Shell replies still waiting go out before the kernel stops
+                        while let Ok(reply) = shell_outbox.try_recv() {
+                            shell_socket.send(reply).await?;
+                        }
+                        println!("Kernel shutdown requested.");
+                        return Ok(());
                     }
+                    JupyterMessageContent::InterruptRequest(_) => {
+                        // Sent on shell rather than control, as older
+                        // frontends do
+                        cancel.cancel();
+                        JupyterMessageContent::InterruptReply(InterruptReply::new())
+                    }
+                    JupyterMessageContent::DebugRequest(debug) => {
+                        JupyterMessageContent::DebugReply(WabznasmJupyterKernel::debug(debug))
+                    }
+                    _ => {
+                        queue.send((request, replies.clone())).await?;
+                        continue;
+                    }
+                };
+                replies
+                    .send(reply(&request, &HashMap::new(), &content, signer)?)
+                    .await?;
+            }
+        };
+        tokio::select! {
+            result = sockets => result,
+            () = worker => Ok(()),
+        }
+    }
+}
 
-                    let reply_msg = construct_zmq_message(
-                        &parsed_msg.identities,
-                        &reply_header,
-                        Some(&parent_header_for_reply),
-                        &reply_metadata,
-                        &JupyterMessageContent::KernelInfoReply(Box::new(
-                            kernel_info_reply_content,
-                        )),
-                        &self.signer,
-                    )
-                    .map_err(|e| {
-                        eprintln!("❌ Failed to construct kernel_info_reply: {}", e);
-                        e
-                    })?;
-                    match shell_socket.send(reply_msg).await {
-                        Ok(_) => println!("📤 Sent kernel_info_reply successfully"),
-                        Err(e) => eprintln!("❌ Failed to send kernel_info_reply: {}", e),
+/// Spawn the control socket's actor, giving the sender of the replies it
+/// sends and the receiver of the requests it passes on
+///
+/// The actor answers interrupt_request itself by cancelling the cell in
+/// progress, so an interrupt is acted on at once, whatever `run` is doing.
+fn spawn_control(
+    mut socket: RouterSocket,
+    cancel: CancelToken,
+    verifier: Arc<JP_SignatureVerifier>,
+    signer: Arc<JP_SignatureSigner>,
+) -> (ZmqSender, RequestReceiver) {
+    let (reply_sender, mut replies): (ZmqSender, ZmqReceiver) = tokio::sync::mpsc::channel(64);
+    let (request_sender, requests) = tokio::sync::mpsc::channel(64);
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                Some(reply) = replies.recv() => {
+                    if let Err(e) = socket.send(reply).await {
+                        eprintln!("Control send error: {}", e);
                     }
                 }
-                JupyterMessageContent::ExecuteRequest(req_content) => {
-                    let execute_reply_content = self
-                        .kernel_handler
-                        .execute_request(req_content, &parent_header_for_reply)
-                        .await;
-                    let reply_header = Header {
-                        msg_id: uuid::Uuid::new_v4().to_string(),
-                        session: parent_header_for_reply.session.clone(),
-                        username: parent_header_for_reply.username.clone(),
-                        date: chrono::Utc::now(),
-                        msg_type: "execute_reply".to_string(),
-                        version: parent_header_for_reply.version.clone(),
-                    };
-                    let reply_msg = construct_zmq_message(
-                        &parsed_msg.identities,
-                        &reply_header,
-                        Some(&parent_header_for_reply),
-                        &reply_metadata,
-                        &JupyterMessageContent::ExecuteReply(execute_reply_content),
-                        &self.signer,
-                    )?;
-                    shell_socket.send(reply_msg).await?;
-                }
-                JupyterMessageContent::ShutdownRequest(req_content) => {
-                    let shutdown_request_struct = ShutdownRequest {
-                        restart: req_content.restart,
-                    };
-                    let handler_shutdown_reply = self
-                        .kernel_handler
-                        .shutdown_request(shutdown_request_struct, &parent_header_for_reply);
-                    let protocol_shutdown_reply = ProtocolShutdownReply {
-                        restart: handler_shutdown_reply.restart,
-                        status: ReplyStatus::Ok,
-                        error: None,
-                    };
-                    let reply_header = Header {
-                        msg_id: uuid::Uuid::new_v4().to_string(),
-                        session: parent_header_for_reply.session.clone(),
-                        username: parent_header_for_reply.username.clone(),
-                        date: chrono::Utc::now(),
-                        msg_type: "shutdown_reply".to_string(),
-                        version: parent_header_for_reply.version.clone(),
-                    };
-                    let reply_msg = construct_zmq_message(
-                        &parsed_msg.identities,
-                        &reply_header,
-                        Some(&parent_header_for_reply),
-                        &reply_metadata,
-                        &JupyterMessageContent::ShutdownReply(protocol_shutdown_reply),
-                        &self.signer,
-                    )?;
-                    shell_socket.send(reply_msg).await?;
-                    println!("Kernel shutdown requested.");
-                    break;
-                }
-                JupyterMessageContent::InterruptRequest(_) => {
-                    let reply_header = Header {
-                        msg_id: uuid::Uuid::new_v4().to_string(),
-                        session: parent_header_for_reply.session.clone(),
-                        username: parent_header_for_reply.username.clone(),
-                        date: chrono::Utc::now(),
-                        msg_type: "interrupt_reply".to_string(),
-                        version: parent_header_for_reply.version.clone(),
-                    };
-                    let interrupt_reply = ProtocolShutdownReply {
-                        restart: false,
-                        status: ReplyStatus::Ok,
-                        error: None,
+                msg = socket.recv() => {
+                    let parsed = match msg {
+                        Ok(msg) => ParsedMessage::parse(&msg, &verifier),
+                        Err(e) => {
+                            eprintln!("Control recv error: {}", e);
+                            break;
+                        }
                     };
-                    let reply_msg = construct_zmq_message(
-                        &parsed_msg.identities,
-                        &reply_header,
-                        Some(&parent_header_for_reply),
-                        &reply_metadata,
-                        &JupyterMessageContent::ShutdownReply(interrupt_reply),
-                        &self.signer,
-                    )?;
-                    shell_socket.send(reply_msg).await?;
-                }
-                _ => {
-                    println!("⚠️  Unhandled message type: {}", parsed_msg.header.msg_type);
+                    match parsed {
+                        Ok(request) if matches!(request.content, JupyterMessageContent::InterruptRequest(_)) => {
+                            cancel.cancel();
+                            println!("Interrupt requested.");
+                            let content = JupyterMessageContent::InterruptReply(InterruptReply::new());
+                            match reply(&request, &HashMap::new(), &content, &signer) {
+                                Ok(reply) => {
+                                    if let Err(e) = socket.send(reply).await {
+                                        eprintln!("Control send error: {}", e);
+                                    }
+                                }
+                                Err(e) => eprintln!("Failed to construct interrupt_reply: {}", e),
+                            }
+                        }
+                        Ok(request) => {
+                            // Failing to pass it on means the kernel has stopped
+                            if request_sender.send(request).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => eprintln!("Error parsing control message: {}", e),
+                    }
                 }
             }
         }
-        Ok(())
+    });
+    (reply_sender, requests)
+}
+
+/// The worker: handle the queued requests one at a time, in order, until
+/// the queue is closed
+async fn work(
+    handler: &mut WabznasmJupyterKernel,
+    mut queued: Receiver<Queued>,
+    signer: Arc<JP_SignatureSigner>,
+) {
+    while let Some((request, replies)) = queued.recv().await {
+        if let Err(e) = serve(handler, &request, &signer, &replies).await {
+            eprintln!("Failed to handle {}: {}", request.header.msg_type, e);
+        }
     }
 }
 
+/// Handle a queued request, sending its reply, if it has one, to `replies`
+async fn serve(
+    handler: &mut WabznasmJupyterKernel,
+    request: &ParsedMessage,
+    signer: &JP_SignatureSigner,
+    replies: &ZmqSender,
+) -> JupyterResult<()> {
+    let header = &request.header;
+    // The request's msg_id ties together every span its handling opens
+    let request_span = crate::telemetry::request_span(&header.msg_id, &header.msg_type);
+    let mut metadata = HashMap::new();
+    let content = match &request.content {
+        JupyterMessageContent::ExecuteRequest(execute) => {
+            let reply = handler
+                .execute_request(execute.clone(), header)
+                .instrument(request_span)
+                .await;
+            // Report the cell's wall time and row counts
+            metadata = handler.execution_metadata();
+            JupyterMessageContent::ExecuteReply(reply)
+        }
+        JupyterMessageContent::CompleteRequest(complete) => {
+            JupyterMessageContent::CompleteReply(handler.complete(complete))
+        }
+        JupyterMessageContent::InspectRequest(inspect) => {
+            JupyterMessageContent::InspectReply(handler.inspect(inspect))
+        }
+        JupyterMessageContent::IsCompleteRequest(is_complete) => {
+            JupyterMessageContent::IsCompleteReply(handler.is_complete(is_complete))
+        }
+        JupyterMessageContent::CommOpen(open) => {
+            handler
+                .comm_open(open.clone(), header)
+                .instrument(request_span)
+                .await;
+            return Ok(());
+        }
+        JupyterMessageContent::CommMsg(message) => {
+            handler
+                .comm_msg(message.clone(), header)
+                .instrument(request_span)
+                .await;
+            return Ok(());
+        }
+        JupyterMessageContent::CommClose(close) => {
+            handler.comm_close(close.clone());
+            return Ok(());
+        }
+        JupyterMessageContent::CommInfoRequest(info) => {
+            JupyterMessageContent::CommInfoReply(handler.comm_info(info))
+        }
+        _ => {
+            println!("⚠️  Unhandled message type: {}", header.msg_type);
+            return Ok(());
+        }
+    };
+    replies
+        .send(reply(request, &metadata, &content, signer)?)
+        .await?;
+    Ok(())
+}
+
+/// The reply to `request` with `content`, whose type names the reply's type
+fn reply(
+    request: &ParsedMessage,
+    metadata: &HashMap<String, serde_json::Value>,
+    content: &JupyterMessageContent,
+    signer: &JP_SignatureSigner,
+) -> JupyterResult<ZmqMessage> {
+    let reply_header = Header {
+        msg_id: uuid::Uuid::new_v4().to_string(),
+        session: request.header.session.clone(),
+        username: request.header.username.clone(),
+        date: chrono::Utc::now(),
+        msg_type: content.message_type().to_string(),
+        version: request.header.version.clone(),
+    };
+    construct_zmq_message(
+        &request.identities,
+        &reply_header,
+        Some(&request.header),
+        metadata,
+        content,
+        signer,
+    )
+}
+
 fn construct_zmq_message(
     identities: &IdentityFrames,
     header: &Header,
@@ -0,0 +1,123 @@
+//! The kernelspec that tells Jupyter how to start the kernel
+//!
+//! `wabznasm jupyter install` writes a `kernel.json` into a `wabznasm`
+//! directory under one of Jupyter's kernel directories, as `jupyter
+//! kernelspec install` would: the system-wide one by default, the user's
+//! with `--user`, or the one under an environment's prefix with `--prefix`.
+
+use serde_json::{Value as JsonValue, json};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the kernel, and of its directory among Jupyter's kernels
+pub const KERNEL_NAME: &str = "wabznasm";
+
+/// Where a kernelspec is installed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Location {
+    /// Jupyter's system-wide kernels, for every user
+    System,
+    /// The current user's kernels
+    User,
+    /// The kernels of the environment installed at a prefix, such as a
+    /// virtualenv or conda environment
+    Prefix(PathBuf),
+}
+
+impl Location {
+    /// The kernels directory of this location, if it can be found
+    pub fn kernels_dir(&self) -> io::Result<PathBuf> {
+        let data = match self {
+            Location::System => system_data_dir(),
+            Location::User => user_data_dir()?,
+            Location::Prefix(prefix) => prefix.join("share").join("jupyter"),
+        };
+        Ok(data.join("kernels"))
+    }
+}
+
+/// The kernelspec starting the kernel with the binary at `exe`
+pub fn kernel_json(exe: &Path) -> JsonValue {
+    json!({
+        "argv": [exe.display().to_string(), "jupyter", "start", "{connection_file}"],
+        "display_name": KERNEL_NAME,
+        "language": KERNEL_NAME,
+    })
+}
+
+/// Write the kernelspec for the binary at `exe` into `kernels`, giving the
+/// directory it was written to
+pub fn install(kernels: &Path, exe: &Path) -> io::Result<PathBuf> {
+    let dir = kernels.join(KERNEL_NAME);
+    fs::create_dir_all(&dir)?;
+    let spec = serde_json::to_string_pretty(&kernel_json(exe))?;
+    fs::write(dir.join("kernel.json"), spec + "\n")?;
+    Ok(dir)
+}
+
+/// Jupyter's system-wide data directory
+fn system_data_dir() -> PathBuf {
+    if cfg!(windows) {
+        let data = env::var_os("PROGRAMDATA").unwrap_or_else(|| r"C:\ProgramData".into());
+        PathBuf::from(data).join("jupyter")
+    } else {
+        PathBuf::from("/usr/local/share/jupyter")
+    }
+}
+
+/// The user's Jupyter data directory: `$JUPYTER_DATA_DIR` if set, or else
+/// the platform's default
+fn user_data_dir() -> io::Result<PathBuf> {
+    if let Some(dir) = env::var_os("JUPYTER_DATA_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    if cfg!(windows) {
+        let appdata = env::var_os("APPDATA")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "APPDATA is not set"))?;
+        return Ok(PathBuf::from(appdata).join("jupyter"));
+    }
+    let home = env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+    if cfg!(target_os = "macos") {
+        return Ok(home.join("Library").join("Jupyter"));
+    }
+    let data = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home.join(".local").join("share"),
+    };
+    Ok(data.join("jupyter"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_install_writes_kernel_json() {
+        let prefix = TempDir::new().unwrap();
+        let kernels = Location::Prefix(prefix.path().to_path_buf())
+            .kernels_dir()
+            .unwrap();
+        assert_eq!(kernels, prefix.path().join("share/jupyter/kernels"));
+
+        let exe = Path::new("/opt/bin/wabznasm");
+        let dir = install(&kernels, exe).unwrap();
+        assert_eq!(dir, kernels.join("wabznasm"));
+        let written = fs::read_to_string(dir.join("kernel.json")).unwrap();
+        let spec: JsonValue = serde_json::from_str(&written).unwrap();
+        assert_eq!(
+            spec["argv"],
+            json!(["/opt/bin/wabznasm", "jupyter", "start", "{connection_file}"])
+        );
+        assert_eq!(spec["display_name"], "wabznasm");
+
+        // Installing again replaces the spec
+        install(&kernels, Path::new("/usr/bin/wabznasm")).unwrap();
+        let written = fs::read_to_string(dir.join("kernel.json")).unwrap();
+        assert!(written.contains("/usr/bin/wabznasm"));
+    }
+}
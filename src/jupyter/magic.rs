@@ -0,0 +1,37 @@
+//! Cell magics: a first line starting with `%%` selects how the rest of the
+//! cell is run
+
+/// A recognised cell magic and the cell body it applies to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CellMagic<'a> {
+    /// `%%sql`: run the body as a select against the attached database
+    Sql(&'a str),
+}
+
+/// The magic `code` starts with, if any
+///
+/// Unknown magics are not recognised, so they fail to parse as code.
+pub fn parse(code: &str) -> Option<CellMagic<'_>> {
+    let code = code.trim_start();
+    let (first_line, body) = code.split_once('\n').unwrap_or((code, ""));
+    match first_line.trim_end() {
+        "%%sql" => Some(CellMagic::Sql(body)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("%%sql\nselect from t"),
+            Some(CellMagic::Sql("select from t"))
+        );
+        assert_eq!(parse("  %%sql  \r\n"), Some(CellMagic::Sql("")));
+        assert_eq!(parse("%%sqlx\nselect from t"), None);
+        assert_eq!(parse("1+2"), None);
+    }
+}
@@ -1,8 +1,12 @@
+pub mod comm;
 pub mod connection;
 pub mod display;
 pub mod errors;
 pub mod handler; // This will contain the JupyterKernelProtocol implementation
+pub mod inspect;
 pub mod kernel; // Restored for low-level jupyter-protocol approach
+pub mod kernelspec;
+pub mod magic;
 pub mod message_parser;
 pub mod session;
 pub mod signature;
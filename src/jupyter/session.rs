@@ -1,4 +1,14 @@
-use crate::environment::Environment;
+use crate::builtins;
+use crate::cancel::CancelToken;
+use crate::completion::{self, Name};
+use crate::environment::{Environment, Value};
+use crate::explorer::{self, ExploreResult};
+use crate::journal::Journal;
+use crate::metrics::ExecutionStats;
+use crate::warnings::Warning;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use storage::{QStoreConfig, Query, ResultSet, StorageError, StorageResult, Table};
 
 /// Type alias for cleaner code
 type ExecuteResult = Result<Option<crate::environment::Value>, crate::errors::EvalError>;
@@ -11,6 +21,12 @@ pub struct JupyterSession {
     evaluator: crate::evaluator::Evaluator,
     /// Execution counter for cells
     execution_count: u32,
+    /// Cost of the most recent cell
+    stats: ExecutionStats,
+    /// Where successfully executed cells are journaled, if anywhere
+    journal: Option<Journal>,
+    /// How long each cell may run, if limited
+    timeout: Option<Duration>,
 }
 
 impl JupyterSession {
@@ -20,6 +36,9 @@ impl JupyterSession {
             environment: Environment::new(),
             evaluator: crate::evaluator::Evaluator::new(),
             execution_count: 0,
+            stats: ExecutionStats::default(),
+            journal: None,
+            timeout: None,
         }
     }
 
@@ -28,6 +47,25 @@ impl JupyterSession {
         self.evaluator.interner()
     }
 
+    /// The parts of the binding at `path`, or the bindings themselves for an
+    /// empty path; see [`explorer::explore`]
+    pub fn explore(&self, path: &[&str]) -> ExploreResult {
+        explorer::explore(&self.environment, path, self.evaluator.interner())
+    }
+
+    /// The names bound in the session and the builtins' names, to complete
+    /// cells from; see [`crate::completion`]
+    pub fn names(&self) -> Vec<Name> {
+        completion::names(&self.environment, self.evaluator.interner())
+    }
+
+    /// The value bound to `name`, or the builtin of that name
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        let bound = (self.evaluator.interner().get(name))
+            .and_then(|name| self.environment.lookup_interned(name).cloned());
+        bound.or_else(|| builtins::lookup(name).map(Value::Builtin))
+    }
+
     /// Get a clone of the current environment for read operations
     pub fn get_environment(&self) -> Environment {
         self.environment.clone()
@@ -36,7 +74,58 @@ impl JupyterSession {
     /// Execute code in the session environment and return the result
     pub fn execute(&mut self, code: &str) -> ExecuteResult {
         self.execution_count += 1;
+        let start = Instant::now();
+        self.evaluator.take_warnings();
+        let result = self.execute_statements(code);
+        self.stats = ExecutionStats {
+            elapsed: start.elapsed(),
+            rows_scanned: self.evaluator.take_rows_scanned(),
+            rows_returned: match &result {
+                Ok(Some(value)) => ExecutionStats::rows_in(value),
+                _ => 0,
+            },
+            peak_bytes: self.evaluator.take_peak_memory(),
+            allocated_bytes: self.evaluator.take_allocated_memory(),
+        };
+        if let (Ok(_), Some(journal)) = (&result, &mut self.journal) {
+            // The cell has run; failing to journal it must not fail the cell
+            if let Err(e) = journal.record(code) {
+                tracing::error!(path = %journal.path().display(), error = %e, "failed to journal cell");
+            }
+        }
+        result
+    }
+
+    /// Journal successfully executed cells to `journal` from now on
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+    }
+
+    /// Stop each cell that runs longer than `timeout`, or let them run on
+    /// with `None`, as by default
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Fail cells computing a value of more than `limit` bytes; see
+    /// [`crate::memory`]
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.evaluator.set_memory_limit(limit);
+    }
 
+    /// Warnings about the most recent cell
+    pub fn warnings(&self) -> &[Warning] {
+        self.evaluator.warnings()
+    }
+
+    /// A token that stops the cell in progress when cancelled, from any
+    /// thread; see [`crate::cancel`]
+    pub fn cancel_token(&self) -> CancelToken {
+        self.evaluator.cancel_token()
+    }
+
+    /// Parse `code` and evaluate each of its statements in turn
+    fn execute_statements(&mut self, code: &str) -> ExecuteResult {
         // Parse the code
         let mut parser = tree_sitter::Parser::new();
         parser
@@ -64,7 +153,7 @@ impl JupyterSession {
         // If there's a syntax error, return it
         if root.has_error() {
             return Err(crate::errors::EvalError::new(
-                crate::errors::EvalErrorKind::Other("Syntax error in input".to_string()),
+                crate::errors::EvalErrorKind::Syntax("Syntax error in input".to_string()),
                 root,
             ));
         }
@@ -79,6 +168,8 @@ impl JupyterSession {
         }
 
         let mut last_result = None;
+        // The whole cell shares one deadline
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
 
         // Process each top-level statement
         for i in 0..child_count {
@@ -89,15 +180,63 @@ impl JupyterSession {
                 continue;
             }
 
-            let result = self
-                .evaluator
-                .eval_with_env(child, code, &mut self.environment)?;
+            let env = &mut self.environment;
+            let result = match deadline {
+                Some(deadline) => self
+                    .evaluator
+                    .eval_with_deadline(child, code, env, deadline)?,
+                None => self.evaluator.eval_with_env(child, code, env)?,
+            };
             last_result = Some(result);
         }
 
         Ok(last_result)
     }
 
+    /// Attach a database root for the `.db` builtins and `%%sql` cells
+    pub fn set_database<P: Into<PathBuf>>(&mut self, root: P) {
+        self.evaluator.set_database(root);
+    }
+
+    /// Open the attached database read-only
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.evaluator.set_read_only(read_only);
+    }
+
+    /// Run a `%%sql` cell body against the attached database
+    pub fn execute_sql(&mut self, sql: &str) -> StorageResult<ResultSet> {
+        self.execution_count += 1;
+        let start = Instant::now();
+        let result = self.run_query(sql);
+        self.stats = ExecutionStats {
+            elapsed: start.elapsed(),
+            rows_scanned: result.as_ref().map_or(0, |r| r.scanned),
+            rows_returned: result.as_ref().map_or(0, |r| r.rows.len()),
+            peak_bytes: None,
+            allocated_bytes: None,
+        };
+        result
+    }
+
+    /// Cost of the most recent cell
+    pub fn stats(&self) -> ExecutionStats {
+        self.stats
+    }
+
+    fn run_query(&self, sql: &str) -> StorageResult<ResultSet> {
+        let root = self
+            .evaluator
+            .database()
+            .ok_or_else(|| StorageError::Configuration("No database attached".into()))?;
+        let query = Query::parse(sql)?;
+        let config = QStoreConfig::new(root, query.table.clone())
+            .with_read_only(self.evaluator.is_read_only());
+        if !config.schema_path().is_file() {
+            return Err(StorageError::TableNotFound(query.table));
+        }
+        query.execute(&Table::load(config)?)
+    }
+
     /// Get the current execution count
     pub fn execution_count(&self) -> u32 {
         self.execution_count
@@ -107,6 +246,7 @@ impl JupyterSession {
     pub fn reset(&mut self) {
         self.environment = Environment::new();
         self.execution_count = 0;
+        self.stats = ExecutionStats::default();
     }
 }
 
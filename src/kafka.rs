@@ -0,0 +1,148 @@
+//! Decoding Kafka messages with a function written in wz
+//!
+//! Enabled with the `kafka` cargo feature. A [`WzDecoder`] is the
+//! [`RowDecoder`] for a function defined in its session: each message is
+//! passed to it as a dict of `topic`, `partition`, `offset`, `timestamp`,
+//! `key` and `payload`, the key and payload as strings, and the function
+//! gives the message's rows as a table, a dict for a single row, or the
+//! empty list for none; see [`crate::convert::rows`].
+//!
+//! ```text
+//! decode: {[m] `sym`size!(`symbol$m`key; count m`payload)}
+//! ```
+
+use crate::convert;
+use crate::environment::{NULL_INTEGER, Value};
+use crate::session::Session;
+use storage::error::{StorageError, StorageResult};
+use storage::kafka::{KafkaRecord, MessageBytes, RowDecoder};
+use storage::table::Row;
+
+/// Name the message being decoded is bound to while the function runs
+const RECORD: &str = "kafkarecord";
+
+/// Decodes messages by applying a wz function to each
+pub struct WzDecoder {
+    session: Session,
+    function: String,
+}
+
+impl WzDecoder {
+    /// A decoder applying the function bound to `function` in `session`
+    pub fn new(session: Session, function: &str) -> StorageResult<Self> {
+        match session.get(function) {
+            Some(value) if value.type_name() == "function" => Ok(Self {
+                session,
+                function: function.to_string(),
+            }),
+            _ => Err(StorageError::Ingest(format!(
+                "No decoder function named {}",
+                function
+            ))),
+        }
+    }
+
+    /// The session the function runs in
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+}
+
+/// The text of a message's key or payload, lossily decoded as UTF-8
+fn text(bytes: MessageBytes<'_>) -> Value {
+    Value::string(&String::from_utf8_lossy(bytes.unwrap_or_default()))
+}
+
+/// The dict a message is passed to the function as
+fn message(record: &KafkaRecord<'_>) -> Value {
+    let timestamp = match record.timestamp {
+        Some(millis) => Value::Timestamp(millis.saturating_mul(1_000_000)),
+        None => Value::Integer(NULL_INTEGER),
+    };
+    Value::Dict {
+        keys: [
+            "topic",
+            "partition",
+            "offset",
+            "timestamp",
+            "key",
+            "payload",
+        ]
+        .map(Value::from)
+        .to_vec(),
+        values: vec![
+            Value::from(record.topic),
+            Value::Integer(i64::from(record.partition)),
+            Value::Integer(record.offset),
+            timestamp,
+            text(record.key),
+            text(record.payload),
+        ],
+    }
+}
+
+impl RowDecoder for WzDecoder {
+    fn decode(&mut self, record: &KafkaRecord<'_>) -> StorageResult<Vec<Row>> {
+        self.session.set(RECORD, message(record));
+        let source = format!("{}[{}]", self.function, RECORD);
+        let rows = self.session.eval(&source).map_err(|e| {
+            StorageError::Ingest(format!(
+                "{} failed on offset {}: {}",
+                self.function, record.offset, e
+            ))
+        })?;
+        convert::rows(&rows).map_err(|e| StorageError::Ingest(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::ScalarValue;
+
+    fn record(payload: &'static [u8]) -> KafkaRecord<'static> {
+        KafkaRecord {
+            topic: "trades",
+            partition: 0,
+            offset: 7,
+            timestamp: Some(1_000),
+            key: Some(b"AAPL"),
+            payload: Some(payload),
+        }
+    }
+
+    #[test]
+    fn test_wz_decoder() {
+        let mut session = Session::new();
+        session
+            .eval("decode: {[m] $[0=count m`payload; (); `sym`offset`size!(`symbol$m`key; m`offset; count m`payload)]}")
+            .unwrap();
+        let mut decoder = WzDecoder::new(session, "decode").unwrap();
+
+        let rows = decoder.decode(&record(b"100")).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["sym"], ScalarValue::Utf8("AAPL".to_string()));
+        assert_eq!(rows[0]["offset"], ScalarValue::Int64(7));
+        assert_eq!(rows[0]["size"], ScalarValue::Int64(3));
+        // A message may give no rows
+        assert!(decoder.decode(&record(b"")).unwrap().is_empty());
+
+        assert!(WzDecoder::new(Session::new(), "decode").is_err());
+    }
+
+    #[test]
+    fn test_wz_decoder_tables_and_errors() {
+        let mut session = Session::new();
+        session
+            .eval("decode: {[m] flip `topic`ts!(2#m`topic; 2#m`timestamp)}")
+            .unwrap();
+        session.eval("broken: {[m] m`payload + 1}").unwrap();
+        let mut decoder = WzDecoder::new(session, "decode").unwrap();
+        let rows = decoder.decode(&record(b"x")).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1]["ts"], ScalarValue::Timestamp(1_000_000_000));
+
+        let mut broken = WzDecoder::new(decoder.session, "broken").unwrap();
+        assert!(broken.decode(&record(b"x")).is_err());
+    }
+}
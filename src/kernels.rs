@@ -0,0 +1,281 @@
+//! Vector kernels for arithmetic on lists of one numeric type
+//!
+//! `1 2 3+10` applies item by item, and the general path in
+//! [`crate::arithmetic`] dispatches on the type of every pair of items.
+//! When both operands are lists of integers only or floats only, or such a
+//! list and a numeric atom, the items are unpacked into a typed buffer and
+//! the operation runs as a loop over fixed-width chunks, which the compiler
+//! turns into SIMD instructions. The result is a typed vector, whose buffer
+//! the next kernel reads as it is; see [`crate::vectors`].
+//!
+//! With parallel execution enabled, long lists are also split across
+//! threads; see [`crate::parallel`].
+//!
+//! The kernels give exactly what the general path does. Nulls propagate
+//! within the loop; a list that overflows or divides by zero is left to
+//! the general path, which resolves it under the overflow mode or reports
+//! the error.
+
+use crate::environment::{NULL_INTEGER, Value};
+use crate::parallel;
+use crate::vectors::{self, Buffer};
+
+/// Items processed by each pass of a kernel's loop
+const LANES: usize = 8;
+
+/// The items of an operand, unpacked
+enum Operand {
+    Ints(Buffer<i64>),
+    Floats(Buffer<f64>),
+}
+
+impl Operand {
+    /// The items of a vector, or of a list of integers only or floats only,
+    /// or a numeric atom repeated `len` times
+    fn unpack(value: &Value, len: usize) -> Option<Self> {
+        match value {
+            Value::Integer(n) => Some(Operand::Ints(vec![*n; len].into())),
+            Value::Float(f) => Some(Operand::Floats(vec![*f; len].into())),
+            Value::IntVector(ints) => Some(Operand::Ints(ints.clone())),
+            Value::FloatVector(floats) => Some(Operand::Floats(floats.clone())),
+            Value::List(items) => match vectors::pack(items)? {
+                Value::IntVector(ints) => Some(Operand::Ints(ints)),
+                Value::FloatVector(floats) => Some(Operand::Floats(floats)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The items as floats, as a float operation sees them
+    fn into_floats(self) -> Buffer<f64> {
+        match self {
+            Operand::Floats(floats) => floats,
+            Operand::Ints(ints) => ints
+                .iter()
+                .map(|&n| Value::Integer(n).as_f64().unwrap_or(f64::NAN))
+                .collect::<Vec<_>>()
+                .into(),
+        }
+    }
+}
+
+/// `f` of each pair of items of `a` and `b`, which have the same length,
+/// on several threads if `parallel`
+fn zip_with<T: Copy + Sync, R: Send>(
+    a: &[T],
+    b: &[T],
+    f: impl Fn(T, T) -> R + Sync,
+    parallel: bool,
+) -> Vec<R> {
+    if parallel {
+        parallel::zip_chunks(a, b, |a, b| chunked(a, b, &f))
+    } else {
+        chunked(a, b, &f)
+    }
+}
+
+/// `f` of each pair of items of `a` and `b`, a chunk of lanes at a time
+fn chunked<T: Copy, R>(a: &[T], b: &[T], f: &impl Fn(T, T) -> R) -> Vec<R> {
+    let mut out = Vec::with_capacity(a.len());
+    let (a_chunks, b_chunks) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+    let (a_rest, b_rest) = (a_chunks.remainder(), b_chunks.remainder());
+    for (x, y) in a_chunks.zip(b_chunks) {
+        out.extend((0..LANES).map(|i| f(x[i], y[i])));
+    }
+    out.extend(a_rest.iter().zip(b_rest).map(|(&x, &y)| f(x, y)));
+    out
+}
+
+/// `left op right` for `+ - * / %`, if both are vectors or numeric lists
+/// of one type, or one is and the other a numeric atom, and nothing
+/// overflows or is divided by zero; `None` for the general path to compute
+pub fn binary(op: &str, left: &Value, right: &Value) -> Option<Value> {
+    kernel(op, left, right, false)
+}
+
+/// [`binary`], with the items split across threads
+pub fn binary_parallel(op: &str, left: &Value, right: &Value) -> Option<Value> {
+    kernel(op, left, right, true)
+}
+
+fn kernel(op: &str, left: &Value, right: &Value, parallel: bool) -> Option<Value> {
+    let len = match (vectors::len(left), vectors::len(right)) {
+        (Some(l), Some(r)) if l == r => l,
+        (Some(_), Some(_)) | (None, None) => return None,
+        (Some(len), None) | (None, Some(len)) => len,
+    };
+    let l = Operand::unpack(left, len)?;
+    let r = Operand::unpack(right, len)?;
+    Some(match (l, r) {
+        (Operand::Ints(a), Operand::Ints(b)) => {
+            Value::IntVector(ints(op, &a, &b, parallel)?.into())
+        }
+        (l, r) => {
+            Value::FloatVector(floats(op, &l.into_floats(), &r.into_floats(), parallel)?.into())
+        }
+    })
+}
+
+/// An integer operation giving its wrapped result and whether it overflowed
+type Overflowing = fn(i64, i64) -> (i64, bool);
+
+/// Integer `a op b`, item by item
+fn ints(op: &str, a: &[i64], b: &[i64], parallel: bool) -> Option<Vec<i64>> {
+    let null = |x: i64, y: i64| x == NULL_INTEGER || y == NULL_INTEGER;
+    let checked = |f: Overflowing| {
+        let results = zip_with(
+            a,
+            b,
+            |x, y| {
+                let (n, overflowed) = f(x, y);
+                let null = null(x, y);
+                (if null { NULL_INTEGER } else { n }, overflowed && !null)
+            },
+            parallel,
+        );
+        if results.iter().any(|&(_, overflowed)| overflowed) {
+            return None;
+        }
+        Some(results.into_iter().map(|(n, _)| n).collect())
+    };
+    match op {
+        "+" => checked(i64::overflowing_add),
+        "-" => checked(i64::overflowing_sub),
+        "*" => checked(i64::overflowing_mul),
+        "/" | "%" => {
+            if a.iter().zip(b).any(|(&x, &y)| y == 0 && !null(x, y)) {
+                return None;
+            }
+            // The only overflow, i64::MIN / -1, divides the null
+            let divide = if op == "/" {
+                i64::wrapping_div
+            } else {
+                i64::wrapping_rem
+            };
+            let quotient = |x, y| {
+                if null(x, y) {
+                    NULL_INTEGER
+                } else {
+                    divide(x, y)
+                }
+            };
+            Some(zip_with(a, b, quotient, parallel))
+        }
+        _ => None,
+    }
+}
+
+type FloatOperation = fn(f64, f64) -> f64;
+
+/// Float `a op b`, item by item
+fn floats(op: &str, a: &[f64], b: &[f64], parallel: bool) -> Option<Vec<f64>> {
+    let f: FloatOperation = match op {
+        "+" => |x, y| x + y,
+        "-" => |x, y| x - y,
+        "*" => |x, y| x * y,
+        "/" => |x, y| x / y,
+        "%" => |x, y| x % y,
+        _ => return None,
+    };
+    let by_zero = |(x, y): (&f64, &f64)| *y == 0.0 && !x.is_nan() && !y.is_nan();
+    if matches!(op, "/" | "%") && a.iter().zip(b).any(by_zero) {
+        return None;
+    }
+    Some(zip_with(a, b, f, parallel))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arithmetic::{self, OverflowMode};
+    use crate::environment::INFINITY_INTEGER;
+    use crate::parser::parse_expression;
+
+    /// `left op right` item by item on the general path
+    fn general(op: &str, left: &Value, right: &Value) -> Value {
+        let tree = parse_expression("1").unwrap();
+        let node = tree.root_node();
+        let item = |value: &Value, i: usize| match value {
+            Value::List(items) => items[i].clone(),
+            atom => atom.clone(),
+        };
+        let len = left.as_list().or(right.as_list()).unwrap().len();
+        let items = (0..len).map(|i| {
+            arithmetic::binary(
+                op,
+                &item(left, i),
+                &item(right, i),
+                OverflowMode::Error,
+                node,
+                node,
+            )
+            .unwrap()
+        });
+        Value::List(items.collect())
+    }
+
+    #[test]
+    fn test_kernels_match_the_general_path() {
+        let ints: Vec<Value> = [
+            1,
+            -7,
+            NULL_INTEGER,
+            40,
+            INFINITY_INTEGER - 1,
+            3,
+            0,
+            12,
+            5,
+            -2,
+        ]
+        .into_iter()
+        .map(Value::Integer)
+        .collect();
+        let divisors: Vec<Value> = [3, 2, 0, -5, 1, 7, 9, 4, -1, NULL_INTEGER]
+            .into_iter()
+            .map(Value::Integer)
+            .collect();
+        let floats: Vec<Value> = [0.5, -1.25, f64::NAN, 8.0, 3.5, 1e10, -0.0, 2.0, 9.75, 1.0]
+            .into_iter()
+            .map(Value::Float)
+            .collect();
+        let cases = [
+            (Value::List(ints.clone()), Value::Integer(1)),
+            (Value::List(ints.clone()), Value::List(divisors.clone())),
+            (Value::Float(2.5), Value::List(ints)),
+            (Value::List(floats.clone()), Value::List(divisors)),
+            (Value::List(floats), Value::Float(3.0)),
+        ];
+        for (left, right) in &cases {
+            for op in ["+", "-", "*", "/", "%"] {
+                let Some(fast) = binary(op, left, right).map(Value::plain) else {
+                    continue;
+                };
+                let expected = general(op, left, right);
+                // NaN is not equal to itself, so compare formatted items
+                assert_eq!(format!("{:?}", fast), format!("{:?}", expected), "{}", op);
+            }
+        }
+    }
+
+    #[test]
+    fn test_kernels_leave_special_cases_to_the_general_path() {
+        let list = |items: &[i64]| Value::List(items.iter().map(|&n| Value::Integer(n)).collect());
+        // Overflow, which the mode resolves
+        assert!(binary("+", &list(&[1, INFINITY_INTEGER]), &Value::Integer(1)).is_none());
+        // Division by zero, which is an error
+        assert!(binary("/", &list(&[1, 2]), &list(&[1, 0])).is_none());
+        assert!(binary("/", &Value::Float(1.0), &list(&[0])).is_none());
+        // Mixed lists and other types
+        let mixed = Value::List(vec![Value::Integer(1), Value::Float(2.0)]);
+        assert!(binary("+", &mixed, &Value::Integer(1)).is_none());
+        assert!(binary("+", &list(&[1, 2]), &Value::Boolean(true)).is_none());
+        assert!(binary("+", &list(&[]), &Value::Integer(1)).is_none());
+        // Lists of different lengths, which is an error
+        assert!(binary("+", &list(&[1, 2]), &list(&[1])).is_none());
+        // Nulls divided by zero stay null
+        let divided = binary("/", &list(&[NULL_INTEGER]), &list(&[0]));
+        assert_eq!(divided, Some(list(&[NULL_INTEGER])));
+    }
+}
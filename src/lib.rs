@@ -1,11 +1,57 @@
 //! Library crate exposing the core calculator functionality and REPL.
+pub mod aggregate;
+pub mod arithmetic;
+pub mod attributes;
+pub mod bigint;
+pub mod bits;
+pub mod bucket;
+pub mod builtins;
+pub mod bytecode;
+pub mod cancel;
+pub mod ckpt;
+pub mod completion;
+pub mod convert;
+pub mod db;
+pub mod diff;
 pub mod environment;
 pub mod errors;
 pub mod evaluator;
+pub mod explorer;
+pub mod fill;
+pub mod format;
 pub mod interning;
+pub mod journal;
 pub mod jupyter;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod kernels;
+pub mod lookup;
+pub mod matrix;
+pub mod memo;
+pub mod memory;
+pub mod metrics;
+pub mod operators;
+pub mod parallel;
 pub mod parser;
+pub mod plugin;
+pub mod random;
+pub mod reflect;
 pub mod repl;
+pub mod script;
+pub mod session;
+pub mod strings;
+pub mod structural;
+pub mod suggest;
+pub mod table;
+pub mod telemetry;
+pub mod temporal;
+pub mod uniform;
+pub mod vectors;
+pub mod warnings;
+pub mod window;
+
+pub use environment::Value;
+pub use session::Session;
 #[cfg(test)]
 mod tests {
     use super::evaluator::evaluate_expression;
@@ -78,7 +124,7 @@ mod tests {
     fn test_invalid_number() {
         let err = evaluate_expression("abc").unwrap_err();
         // Identifiers now produce an "undefined variable" error instead of syntax error
-        assert_eq!(err.code().unwrap().to_string(), "OTHER_ERROR");
+        assert_eq!(err.code().unwrap().to_string(), "UNDEFINED_VARIABLE");
     }
 
     #[test]
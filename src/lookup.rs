@@ -0,0 +1,118 @@
+//! Lookup primitives: `in`, `within` and find
+//!
+//! ```text
+//! 2 in 1 2 3            / 1b
+//! 1 4 in 1 2 3          / 10b
+//! 3 7 within 1 5        / 10b
+//! `a`b`c?`b`z           / 1 3
+//! ```
+//!
+//! Each applies to the items of a list on its left, or to an atom; `in`
+//! and find look up a list whole in a list of lists. A list
+//! searched with `in` or `?` is looked up through its attribute when it has
+//! one, and a plain list searched for several items is hashed once for all
+//! of them; a sorted list tested with `within` is bounded by binary search.
+//! Items are found by value, as `=` compares them, and find gives the count
+//! of the list for an item it does not hold.
+
+use crate::attributes::{Attribute, Attributed};
+use crate::builtins::Context;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use std::cmp::Ordering;
+use tree_sitter::Node;
+
+/// `f` of each item of `x` as a list, or of `x` itself if it is an atom
+fn each_item(x: &Value, f: impl Fn(&Value) -> Value) -> Value {
+    match x.as_list() {
+        Some(items) => Value::List(items.iter().map(f).collect()),
+        None => f(x),
+    }
+}
+
+/// The first position of each of `needles` in the list `haystack`
+fn first_positions(haystack: &Value, needles: &[Value]) -> Vec<Option<usize>> {
+    if let Value::Attributed(list) = haystack {
+        return needles.iter().map(|needle| list.find(needle)).collect();
+    }
+    let items = haystack.as_list().unwrap_or(std::slice::from_ref(haystack));
+    if needles.len() > 1 {
+        let grouped =
+            Attributed::new(Attribute::Grouped, items.to_vec()).expect("any list can be grouped");
+        return needles.iter().map(|needle| grouped.find(needle)).collect();
+    }
+    needles
+        .iter()
+        .map(|needle| items.iter().position(|item| item == needle))
+        .collect()
+}
+
+/// Results of `f` for the items of `x` from the first positions of those
+/// items in `haystack`, shaped like `x`; `x` is one item if it is an atom
+/// or `haystack` holds lists
+fn lookup(x: &Value, haystack: &Value, f: impl Fn(Option<usize>) -> Value) -> Value {
+    let nested = haystack
+        .as_list()
+        .is_some_and(|items| items.iter().any(|item| item.as_list().is_some()));
+    match x.as_list() {
+        Some(needles) if !nested => Value::List(
+            first_positions(haystack, needles)
+                .into_iter()
+                .map(f)
+                .collect(),
+        ),
+        _ => f(first_positions(haystack, std::slice::from_ref(x))[0]),
+    }
+}
+
+/// `x in ys`: whether `x`, or each item of it, is an item of `ys`; an atom
+/// `ys` stands for the list of itself
+pub fn member(_: &mut Context, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    Ok(lookup(&args[0], &args[1], |position| {
+        Value::Boolean(position.is_some())
+    }))
+}
+
+/// `ys?x`, or `find[ys;x]`: the first position of `x`, or of each item of
+/// it, in the list `ys`, or the count of `ys` where it does not occur
+pub fn find(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let count = args[0].as_list().map(|items| items.len()).ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "find expects a list to search, got {}",
+                args[0].type_name()
+            )),
+            node,
+        )
+    })?;
+    Ok(lookup(&args[1], &args[0], |position| {
+        Value::Integer(position.unwrap_or(count) as i64)
+    }))
+}
+
+/// `x within (lo;hi)`: whether `x`, or each item of it, is at least `lo`
+/// and at most `hi`
+pub fn within(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let (x, bounds) = (&args[0], &args[1]);
+    let [lo, hi] = bounds.as_list().unwrap_or_default() else {
+        return Err(EvalError::new(
+            EvalErrorKind::Type("within expects a pair of bounds on the right: (lo;hi)".into()),
+            node,
+        ));
+    };
+    let below = |item: &Value| item.compare(lo).is_some_and(Ordering::is_lt);
+    let at_most = |item: &Value| item.compare(hi).is_some_and(Ordering::is_le);
+    if let Value::Attributed(list) = x
+        && list.attribute() == Attribute::Sorted
+    {
+        // The items within the bounds are a run of the sorted list
+        let items = list.items();
+        let start = items.partition_point(below);
+        let end = start + items[start..].partition_point(at_most);
+        let inside = (0..items.len()).map(|i| Value::Boolean((start..end).contains(&i)));
+        return Ok(Value::List(inside.collect()));
+    }
+    Ok(each_item(x, |item| {
+        Value::Boolean(!below(item) && at_most(item))
+    }))
+}
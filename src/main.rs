@@ -1,14 +1,93 @@
 use clap::{Parser, Subcommand};
 use color_eyre::eyre;
-use std::path::PathBuf;
-use wabznasm::repl;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use wabznasm::arithmetic::OverflowMode;
+use wabznasm::evaluator::EvaluatorConfig;
+use wabznasm::journal::Journal;
+use wabznasm::jupyter::kernelspec::{self, KERNEL_NAME, Location};
+use wabznasm::memory;
+use wabznasm::script::Script;
+use wabznasm::{Session, Value, repl, telemetry};
 
 #[derive(Parser)]
 #[command(name = "wabznasm")]
 #[command(about = "A Q/KDB+ inspired array processing language")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// Script to evaluate instead of starting the REPL, or - to read it
+    /// from standard input
+    #[arg(value_name = "SCRIPT")]
+    input: Option<PathBuf>,
+    /// Evaluate this source instead of starting the REPL, printing its value
+    #[arg(
+        short = 'e',
+        long = "eval",
+        value_name = "SOURCE",
+        conflicts_with = "input",
+        allow_hyphen_values = true
+    )]
+    eval: Option<String>,
+    /// Database root for the `.db` builtins and the kernel's `%%sql` cells
+    #[arg(long)]
+    db: Option<PathBuf>,
+    /// Open `--db` read-only: nothing under it is created or written, so it
+    /// is safe to point at production data
+    #[arg(long)]
+    read_only: bool,
+    /// What integer arithmetic does on overflow: error, wrap, saturate, promote or bigint
+    #[arg(long, default_value = "error", value_parser = parse_overflow)]
+    overflow: OverflowMode,
+    /// Split operations on lists of at least N items, 100000 by default,
+    /// across threads
+    #[arg(long, value_name = "N")]
+    parallel: Option<Option<usize>>,
+    /// Export tracing spans to this OTLP/HTTP endpoint, e.g.
+    /// http://localhost:4318/v1/traces (requires the `otel` feature)
+    #[arg(long)]
+    otlp_endpoint: Option<String>,
+    /// Journal executed input to this file for recovery with `replay-journal`
+    #[arg(long)]
+    journal: Option<PathBuf>,
+    /// Stop any evaluation, or Jupyter cell, that runs longer than this
+    #[arg(long, value_name = "SECONDS", value_parser = parse_timeout)]
+    timeout: Option<Duration>,
+    /// Fail any evaluation computing a value larger than this, in bytes
+    /// or with a K, M or G suffix: 4G
+    #[arg(long, value_name = "BYTES", value_parser = parse_memory_limit)]
+    memory_limit: Option<usize>,
+    /// Preload the N most read columns of each table in `--db` at startup,
+    /// so the first queries do not wait on page faults
+    #[arg(long, value_name = "N")]
+    warmup: Option<usize>,
+}
+
+fn parse_overflow(name: &str) -> Result<OverflowMode, String> {
+    OverflowMode::parse(name).ok_or_else(|| {
+        format!(
+            "expected error, wrap, saturate, promote or bigint, got {}",
+            name
+        )
+    })
+}
+
+fn parse_timeout(seconds: &str) -> Result<Duration, String> {
+    seconds
+        .parse()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("expected a number of seconds, got {}", seconds))
+}
+
+fn parse_memory_limit(size: &str) -> Result<usize, String> {
+    memory::parse_size(size).ok_or_else(|| {
+        format!(
+            "expected a number of bytes such as 512M or 4G, got {}",
+            size
+        )
+    })
 }
 
 #[derive(Subcommand)]
@@ -18,6 +97,40 @@ enum Commands {
         #[command(subcommand)]
         action: JupyterCommands,
     },
+    /// Replay historical partitions of a table as a simulated live feed
+    Replay {
+        /// Database root containing one directory per partition
+        db: PathBuf,
+        /// Table to replay
+        table: String,
+        /// Replay speed relative to recorded time, e.g. 1x, 10x or max
+        #[arg(long, default_value = "1x")]
+        speed: String,
+    },
+    /// Suggest attributes and partitioning from the tables' access statistics
+    Advise {
+        /// Database root containing one directory per table
+        db: PathBuf,
+        /// Only advise on this table
+        table: Option<String>,
+        /// Rows a table needs before partitioning it is suggested
+        #[arg(long, default_value_t = storage::advisor::DEFAULT_PARTITION_ROWS)]
+        partition_rows: usize,
+    },
+    /// Evaluate a script non-interactively and print the value of its last
+    /// statement; exits with status 1 if it fails
+    Run {
+        /// Script to evaluate; see `\l` in the REPL
+        script: PathBuf,
+        /// Arguments for the script, as the strings `.z.x`
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Recover a session from its journal, then continue it in the REPL
+    ReplayJournal {
+        /// Journal written with `--journal`; new input is appended to it
+        journal: PathBuf,
+    },
 }
 
 #[derive(Subcommand)]
@@ -27,6 +140,16 @@ enum JupyterCommands {
         /// Path to the Jupyter connection file
         connection_file: PathBuf,
     },
+    /// Write the kernelspec that starts this binary as a kernel into
+    /// Jupyter's system-wide kernels directory
+    Install {
+        /// Install for the current user instead
+        #[arg(long, conflicts_with = "prefix")]
+        user: bool,
+        /// Install into the kernels of the environment at this prefix instead
+        #[arg(long)]
+        prefix: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
@@ -35,6 +158,10 @@ async fn main() -> Result<(), eyre::Report> {
     color_eyre::install()?;
 
     let cli = Cli::parse();
+    let _telemetry = telemetry::init(cli.otlp_endpoint.as_deref())?;
+    if let (Some(root), Some(hottest)) = (&cli.db, cli.warmup) {
+        warmup(root, hottest, cli.read_only)?;
+    }
 
     match cli.command {
         Some(Commands::Jupyter { action }) => match action {
@@ -43,16 +170,210 @@ async fn main() -> Result<(), eyre::Report> {
                 use wabznasm::jupyter::kernel::JupyterKernelRunner;
                 let mut kernel = JupyterKernelRunner::from_file(&connection_file)
                     .map_err(|e| eyre::eyre!("Failed to create kernel: {}", e))?;
+                if let Some(root) = cli.db {
+                    kernel = kernel.with_database(root).with_read_only(cli.read_only);
+                }
+                if let Some(path) = cli.journal {
+                    kernel = kernel.with_journal(Journal::open(path)?);
+                }
+                if let Some(timeout) = cli.timeout {
+                    kernel = kernel.with_timeout(timeout);
+                }
+                if let Some(limit) = cli.memory_limit {
+                    kernel = kernel.with_memory_limit(limit);
+                }
                 kernel
                     .run()
                     .await
                     .map_err(|e| eyre::eyre!("Kernel execution failed: {}", e))?;
                 Ok(())
             }
+            JupyterCommands::Install { user, prefix } => {
+                let location = match prefix {
+                    Some(prefix) => Location::Prefix(prefix),
+                    None if user => Location::User,
+                    None => Location::System,
+                };
+                let kernels = location.kernels_dir()?;
+                let dir = kernelspec::install(&kernels, &std::env::current_exe()?)
+                    .map_err(|e| eyre::eyre!("cannot install in {}: {}", kernels.display(), e))?;
+                println!("Installed kernelspec {} in {}", KERNEL_NAME, dir.display());
+                Ok(())
+            }
         },
+        Some(Commands::Replay { db, table, speed }) => replay(db, table, &speed),
+        Some(Commands::Advise {
+            db,
+            table,
+            partition_rows,
+        }) => advise(db, table, partition_rows),
+        Some(Commands::Run { script, args }) => {
+            let mut session = session(cli.db, cli.read_only, cli.overflow, cli.parallel);
+            session.set_memory_limit(cli.memory_limit);
+            session.set_timeout(cli.timeout);
+            if let Some(path) = cli.journal {
+                session.set_journal(Journal::open(path)?);
+            }
+            run(session, &script, &args)
+        }
+        None if cli.eval.is_some() || cli.input.is_some() => {
+            let mut session = session(cli.db, cli.read_only, cli.overflow, cli.parallel);
+            session.set_memory_limit(cli.memory_limit);
+            session.set_timeout(cli.timeout);
+            if let Some(path) = cli.journal {
+                session.set_journal(Journal::open(path)?);
+            }
+            let script = match (cli.eval, cli.input) {
+                (Some(source), _) => Script {
+                    name: "-e".to_string(),
+                    text: source,
+                },
+                (None, Some(path)) if path == Path::new("-") => Script {
+                    name: "<stdin>".to_string(),
+                    text: std::io::read_to_string(std::io::stdin())?,
+                },
+                (None, Some(path)) => return run(session, &path, &[]),
+                (None, None) => unreachable!("guarded by the match arm"),
+            };
+            let result = session.eval_script(&script);
+            finish(&session, result)
+        }
+        Some(Commands::ReplayJournal { journal }) => {
+            let mut session = session(cli.db, cli.read_only, cli.overflow, cli.parallel);
+            session.set_memory_limit(cli.memory_limit);
+            // Entries ran to completion when journaled, so replay them unlimited
+            let replayed = repl::replay_journal(&mut session, &journal)?;
+            eprintln!("Replayed {} journal entries", replayed);
+            session.set_timeout(cli.timeout);
+            session.set_journal(Journal::open(journal)?);
+            repl::run(session)
+        }
         None => {
             // Default to REPL
-            repl::run()
+            let mut session = session(cli.db, cli.read_only, cli.overflow, cli.parallel);
+            session.set_memory_limit(cli.memory_limit);
+            session.set_timeout(cli.timeout);
+            if let Some(path) = cli.journal {
+                session.set_journal(Journal::open(path)?);
+            }
+            repl::run(session)
         }
     }
 }
+
+/// A REPL session over `database`, opened read-only if asked, with the given
+/// overflow mode, and splitting long lists across threads if `parallel` is
+/// given, from the threshold in it if any
+fn session(
+    database: Option<PathBuf>,
+    read_only: bool,
+    overflow: OverflowMode,
+    parallel: Option<Option<usize>>,
+) -> Session {
+    let mut session = Session::new();
+    if let Some(root) = database {
+        session.set_database(root);
+        session.set_read_only(read_only);
+    }
+    session.set_overflow_mode(overflow);
+    if let Some(threshold) = parallel {
+        let default = EvaluatorConfig::default();
+        session.set_config(EvaluatorConfig {
+            parallel: true,
+            parallel_threshold: threshold.unwrap_or(default.parallel_threshold),
+        });
+    }
+    session
+}
+
+/// Evaluate the script at `path` with `args` as `.z.x` and its path as
+/// `.z.f`, printing the value of its last statement in full, or the error
+/// and exiting with status 1
+fn run(mut session: Session, path: &Path, args: &[String]) -> Result<(), eyre::Report> {
+    session.set(".z.f", Value::string(&path.display().to_string()));
+    let args = args.iter().map(|arg| Value::string(arg)).collect();
+    session.set(".z.x", Value::List(args));
+    let result = session.load(path);
+    finish(&session, result)
+}
+
+/// Print the value of a non-interactive evaluation in full, or the error
+/// and exit with status 1
+fn finish(session: &Session, result: Result<Value, miette::Report>) -> Result<(), eyre::Report> {
+    match result {
+        Ok(value) => {
+            println!("{}", session.display_in_full(&value));
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Preload the `hottest` most read columns of each table under `root`
+fn warmup(root: &Path, hottest: usize, read_only: bool) -> Result<(), eyre::Report> {
+    let warmed = storage::WarmupConfig::new()
+        .with_hottest(hottest)
+        .with_read_only(read_only)
+        .warm_database(root)?;
+    let bytes: u64 = warmed.iter().map(|w| w.bytes).sum();
+    let columns: usize = warmed.iter().map(|w| w.columns.len()).sum();
+    eprintln!(
+        "Preloaded {} columns of {} tables ({} bytes)",
+        columns,
+        warmed.len(),
+        bytes
+    );
+    Ok(())
+}
+
+/// Print suggestions for the tables under `db`, or for one of them
+fn advise(db: PathBuf, table: Option<String>, partition_rows: usize) -> Result<(), eyre::Report> {
+    use storage::{Advisor, QStoreConfig, Table};
+
+    let advisor = Advisor::new().with_partition_rows(partition_rows);
+    let advice = match table {
+        Some(name) => advisor.advise(&Table::load(QStoreConfig::new(db, name))?)?,
+        None => advisor.advise_database(&db)?,
+    };
+    if advice.is_empty() {
+        eprintln!("No suggestions: no recorded queries would have benefited");
+    }
+    for item in advice {
+        println!("{}", item);
+    }
+    Ok(())
+}
+
+/// Replay a table, printing each row as its subscriber receives it
+fn replay(db: PathBuf, table: String, speed: &str) -> Result<(), eyre::Report> {
+    use storage::{
+        Publisher,
+        replay::{Replay, ReplaySpeed},
+    };
+
+    let speed = ReplaySpeed::parse(speed)?;
+    let mut publisher = Publisher::new();
+    let feed = publisher.subscribe(&table);
+    let printer = std::thread::spawn(move || {
+        for row in feed {
+            let mut columns: Vec<_> = row.into_iter().collect();
+            columns.sort_by(|a, b| a.0.cmp(&b.0));
+            let fields: Vec<String> = columns
+                .into_iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            println!("{}", fields.join(" "));
+        }
+    });
+
+    let replayed = Replay::new(db, table, speed).run(&mut publisher)?;
+    drop(publisher);
+    printer
+        .join()
+        .map_err(|_| eyre::eyre!("Replay printer thread panicked"))?;
+    eprintln!("Replayed {} rows", replayed);
+    Ok(())
+}
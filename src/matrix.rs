@@ -0,0 +1,197 @@
+//! Matrix builtins: basic linear algebra on nested numeric lists
+//!
+//! A matrix is a list of rows, each a list of numbers of the same length,
+//! and a vector is a simple list of numbers. Products and inverses are
+//! computed in floating point, so their results are floats whatever the
+//! items were.
+//!
+//! ```text
+//! m: (1 2;3 4)
+//! mmu[m;inv m]      / (1 0f;0 1f)
+//! mmu[1 2;3 4]      / 11f, the dot product
+//! transpose m       / (1 3;2 4)
+//! ```
+
+use crate::builtins::Context;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::structural;
+use tree_sitter::Node;
+
+/// Rows of numbers, all of one length
+type Rows = Vec<Vec<f64>>;
+
+/// An argument to a matrix builtin
+enum Operand {
+    Vector(Vec<f64>),
+    Matrix(Rows),
+}
+
+fn type_error(message: String, node: Node) -> EvalError {
+    EvalError::new(EvalErrorKind::Type(message), node)
+}
+
+/// The numbers of a simple numeric list, or `None` if it has other items
+fn numbers(items: &[Value]) -> Option<Vec<f64>> {
+    items
+        .iter()
+        .map(|item| match item {
+            Value::Integer(_) | Value::Float(_) => item.as_f64(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `value` as a vector or a rectangular matrix
+fn operand(value: &Value, name: &str, node: Node) -> Result<Operand, EvalError> {
+    let not_numeric = || {
+        type_error(
+            format!(
+                "{} expects a numeric vector or matrix, got {}",
+                name,
+                value.type_name()
+            ),
+            node,
+        )
+    };
+    let Value::List(items) = value else {
+        return Err(not_numeric());
+    };
+    if let Some(vector) = numbers(items) {
+        return Ok(Operand::Vector(vector));
+    }
+    let rows = items
+        .iter()
+        .map(|row| row.as_list().and_then(numbers))
+        .collect::<Option<Rows>>()
+        .ok_or_else(not_numeric)?;
+    if rows.iter().any(|row| row.len() != rows[0].len()) {
+        return Err(type_error(
+            format!("{} expects rows of equal length", name),
+            node,
+        ));
+    }
+    Ok(Operand::Matrix(rows))
+}
+
+fn vector(values: impl IntoIterator<Item = f64>) -> Value {
+    Value::List(values.into_iter().map(Value::Float).collect())
+}
+
+fn matrix(rows: Rows) -> Value {
+    Value::List(rows.into_iter().map(vector).collect())
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Width of the rows of a matrix
+fn width(rows: &Rows) -> usize {
+    rows.first().map_or(0, Vec::len)
+}
+
+/// Column `j` of a matrix
+fn column(rows: &Rows, j: usize) -> Vec<f64> {
+    rows.iter().map(|row| row[j]).collect()
+}
+
+/// `mmu[x;y]`: the matrix product of `x` and `y`; a vector on the left is a
+/// row and on the right a column, so two vectors give their dot product
+pub fn mmu(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let left = operand(&args[0], "mmu", node)?;
+    let right = operand(&args[1], "mmu", node)?;
+    // The lengths that must agree, and the product if they do
+    let (inner, other, product) = match (&left, &right) {
+        (Operand::Vector(a), Operand::Vector(b)) => (a.len(), b.len(), Value::Float(dot(a, b))),
+        (Operand::Matrix(a), Operand::Vector(b)) => {
+            (width(a), b.len(), vector(a.iter().map(|row| dot(row, b))))
+        }
+        (Operand::Vector(a), Operand::Matrix(b)) => (
+            a.len(),
+            b.len(),
+            vector((0..width(b)).map(|j| dot(a, &column(b, j)))),
+        ),
+        (Operand::Matrix(a), Operand::Matrix(b)) => {
+            let columns: Rows = (0..width(b)).map(|j| column(b, j)).collect();
+            let rows = a
+                .iter()
+                .map(|row| columns.iter().map(|col| dot(row, col)).collect())
+                .collect();
+            (width(a), b.len(), matrix(rows))
+        }
+    };
+    if inner != other {
+        return Err(EvalError::new(
+            EvalErrorKind::LengthMismatch(format!(
+                "mmu: cannot multiply {} columns by {} rows",
+                inner, other
+            )),
+            node,
+        ));
+    }
+    Ok(product)
+}
+
+/// `inv x`: the inverse of the square matrix `x`, by Gauss-Jordan
+/// elimination with partial pivoting
+pub fn inv(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let rows = match operand(&args[0], "inv", node)? {
+        Operand::Matrix(rows) if rows.len() == width(&rows) => rows,
+        _ => {
+            return Err(type_error("inv expects a square matrix".into(), node));
+        }
+    };
+    let n = rows.len();
+    // Each row followed by the matching row of the identity
+    let mut augmented: Rows = rows
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut row)| {
+            row.extend((0..n).map(|j| if i == j { 1.0 } else { 0.0 }));
+            row
+        })
+        .collect();
+    let scale = augmented
+        .iter()
+        .flat_map(|row| &row[..n])
+        .fold(0.0f64, |max, x| max.max(x.abs()));
+    let tolerance = scale * n as f64 * f64::EPSILON;
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&a, &b| augmented[a][col].abs().total_cmp(&augmented[b][col].abs()))
+            .expect("the column has rows at or below the diagonal");
+        if augmented[pivot][col].abs() <= tolerance {
+            return Err(EvalError::new(
+                EvalErrorKind::Domain("inv: matrix is singular".into()),
+                node,
+            ));
+        }
+        augmented.swap(col, pivot);
+        let divisor = augmented[col][col];
+        augmented[col].iter_mut().for_each(|x| *x /= divisor);
+        let pivot_row = augmented[col].clone();
+        for (i, row) in augmented.iter_mut().enumerate() {
+            let factor = row[col];
+            if i != col && factor != 0.0 {
+                row.iter_mut()
+                    .zip(&pivot_row)
+                    .for_each(|(x, p)| *x -= factor * p);
+            }
+        }
+    }
+    Ok(matrix(
+        augmented.into_iter().map(|row| row[n..].to_vec()).collect(),
+    ))
+}
+
+/// `transpose x`: the rows of the matrix `x` as columns, keeping its items
+/// as they are; the same as `flip` on a list of lists
+pub fn transpose(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    match (&args[0], operand(&args[0], "transpose", node)?) {
+        (Value::List(rows), Operand::Matrix(_)) => Ok(Value::List(
+            structural::transpose(rows).expect("a matrix has rows of equal length"),
+        )),
+        _ => Err(type_error("transpose expects a matrix".into(), node)),
+    }
+}
@@ -0,0 +1,71 @@
+//! Memoized functions: `memo f` remembers the result of each call of `f`
+//!
+//! The cache of each memoized function lives in the evaluator's
+//! [`Context`], keyed on the arguments by value, so a function that calls
+//! itself through its memoized name reuses every result computed so far:
+//!
+//! ```text
+//! fib: memo {[n] $[n<2;n;fib[n-1]+fib[n-2]]}
+//! fib[80]      / linear in n rather than exponential
+//! ```
+//!
+//! Only memoize pure functions: a function that reads globals it does not
+//! take as arguments, or has effects, returns a stale result from the cache.
+
+use crate::builtins::Context;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::table;
+use std::collections::HashMap;
+use tree_sitter::Node;
+
+/// Arguments of calls and their results, for arguments with one hash
+type Bucket = Vec<(Vec<Value>, Value)>;
+
+/// Results of one memoized function, by the arguments they were computed from
+#[derive(Debug, Default)]
+pub struct MemoTable {
+    /// Entries by the hash of their arguments
+    entries: HashMap<u64, Bucket>,
+}
+
+impl MemoTable {
+    /// The result remembered for `args`, if any
+    pub fn get(&self, args: &[Value]) -> Option<&Value> {
+        self.entries
+            .get(&table::hash_key(args))?
+            .iter()
+            .find(|(key, _)| key.as_slice() == args)
+            .map(|(_, result)| result)
+    }
+
+    /// Remember `result` as the result for `args`
+    pub fn insert(&mut self, args: &[Value], result: Value) {
+        self.entries
+            .entry(table::hash_key(args))
+            .or_default()
+            .push((args.to_vec(), result));
+    }
+}
+
+/// `memo f`: a function that calls `f` once for each distinct argument list
+/// and returns the remembered result for repeats
+pub fn memo(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    match &args[0] {
+        memoized @ Value::Memo { .. } => Ok(memoized.clone()),
+        function if function.is_function() => {
+            context.memos.push(MemoTable::default());
+            Ok(Value::Memo {
+                function: Box::new(function.clone()),
+                cache: context.memos.len() - 1,
+            })
+        }
+        other => Err(EvalError::new(
+            EvalErrorKind::Type(format!(
+                "memo expects a function, got {}",
+                other.type_name()
+            )),
+            node,
+        )),
+    }
+}
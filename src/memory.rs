@@ -0,0 +1,86 @@
+//! Memory budget for evaluation
+//!
+//! With a limit set, as by [`Evaluator::set_memory_limit`] or
+//! `wabznasm --memory-limit 4G`, the evaluator measures each value it
+//! computes, approximately: the inline size of a [`Value`] and the memory
+//! its items take. A value larger than the limit fails the evaluation with
+//! [`EvalErrorKind::MemoryLimitExceeded`] instead of exhausting the
+//! process's memory. `n#x` and `n?x` check the length they would build
+//! before allocating, so `1000000000#0` fails at once.
+//!
+//! The limit applies to each value, not to their total: bindings that are
+//! each within it may together exceed it.
+//!
+//! [`Evaluator::set_memory_limit`]: crate::evaluator::Evaluator::set_memory_limit
+//! [`EvalErrorKind::MemoryLimitExceeded`]: crate::errors::EvalErrorKind::MemoryLimitExceeded
+
+use crate::environment::Value;
+use std::mem;
+
+/// Approximate memory taken by `value`, in bytes
+pub fn size_of(value: &Value) -> usize {
+    let inline = mem::size_of::<Value>();
+    let items = |items: &[Value]| items.iter().map(size_of).sum::<usize>();
+    inline
+        + match value {
+            Value::List(list) => items(list),
+            Value::Attributed(list) => items(list.items()),
+            Value::IntVector(ints) => mem::size_of_val(ints.as_slice()),
+            Value::FloatVector(floats) => mem::size_of_val(floats.as_slice()),
+            Value::Dict { keys, values } => items(keys) + items(values),
+            Value::Table(table) => table.columns().iter().map(|c| items(c)).sum(),
+            Value::Memo { function, .. } => size_of(function),
+            Value::Projection { function, args } => {
+                size_of(function) + args.iter().flatten().map(size_of).sum::<usize>()
+            }
+            _ => 0,
+        }
+}
+
+/// Approximate memory taken by a list of `len` atoms, in bytes
+pub fn list_size(len: usize) -> usize {
+    len.saturating_add(1)
+        .saturating_mul(mem::size_of::<Value>())
+}
+
+/// A number of bytes written with an optional `K`, `M` or `G` suffix, for
+/// kibibytes, mebibytes or gibibytes: `512M`
+pub fn parse_size(text: &str) -> Option<usize> {
+    let text = text.trim();
+    let (digits, scale) = match text.char_indices().last()? {
+        (i, 'k' | 'K') => (&text[..i], 1 << 10),
+        (i, 'm' | 'M') => (&text[..i], 1 << 20),
+        (i, 'g' | 'G') => (&text[..i], 1 << 30),
+        _ => (text, 1),
+    };
+    digits.parse::<usize>().ok()?.checked_mul(scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sizes_grow_with_items() {
+        let atom = size_of(&Value::Integer(1));
+        assert_eq!(atom, mem::size_of::<Value>());
+        let list = Value::List(vec![Value::Integer(1); 10]);
+        assert_eq!(size_of(&list), atom * 11);
+        assert_eq!(list_size(10), size_of(&list));
+        let vector = Value::IntVector(vec![1; 10].into());
+        assert_eq!(size_of(&vector), atom + 80);
+        let nested = Value::List(vec![list.clone(), list]);
+        assert_eq!(size_of(&nested), atom + 2 * atom * 11);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("4K"), Some(4096));
+        assert_eq!(parse_size("512m"), Some(512 << 20));
+        assert_eq!(parse_size("2G"), Some(2 << 30));
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size("-1"), None);
+        assert_eq!(parse_size(""), None);
+    }
+}
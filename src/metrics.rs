@@ -0,0 +1,126 @@
+//! Cost of an evaluation, reported after each REPL line and in Jupyter
+//! `execute_reply` metadata
+
+use crate::environment::Value;
+use serde_json::{Value as JsonValue, json};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Wall time and row counts of one evaluation
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExecutionStats {
+    /// Wall time spent evaluating
+    pub elapsed: Duration,
+    /// Rows read from stored tables
+    pub rows_scanned: usize,
+    /// Rows in the result
+    pub rows_returned: usize,
+    /// Bytes the largest value computed took, if memory was tracked; see
+    /// [`Evaluator::track_memory`](crate::evaluator::Evaluator::track_memory)
+    pub peak_bytes: Option<usize>,
+    /// Bytes allocated for all the values computed, estimated, if memory
+    /// was tracked; see
+    /// [`Evaluator::take_allocated_memory`](crate::evaluator::Evaluator::take_allocated_memory)
+    pub allocated_bytes: Option<usize>,
+}
+
+impl ExecutionStats {
+    /// Rows a value counts as: the items of a list or dict, the rows of a
+    /// table, else one
+    pub fn rows_in(value: &Value) -> usize {
+        match value {
+            Value::List(items) => items.len(),
+            Value::Dict { keys, .. } => keys.len(),
+            Value::Table(table) => table.len(),
+            _ => 1,
+        }
+    }
+
+    /// Entries for a Jupyter reply's metadata
+    pub fn to_metadata(self) -> HashMap<String, JsonValue> {
+        let mut metadata = HashMap::from([
+            (
+                "elapsed_ms".to_string(),
+                json!(self.elapsed.as_secs_f64() * 1000.0),
+            ),
+            ("rows_scanned".to_string(), json!(self.rows_scanned)),
+            ("rows_returned".to_string(), json!(self.rows_returned)),
+        ]);
+        if let Some(bytes) = self.peak_bytes {
+            metadata.insert("peak_bytes".to_string(), json!(bytes));
+        }
+        if let Some(bytes) = self.allocated_bytes {
+            metadata.insert("allocated_bytes".to_string(), json!(bytes));
+        }
+        metadata
+    }
+}
+
+impl fmt::Display for ExecutionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.3}ms, {} rows scanned, {} rows returned",
+            self.elapsed.as_secs_f64() * 1000.0,
+            self.rows_scanned,
+            self.rows_returned
+        )?;
+        if let Some(bytes) = self.peak_bytes {
+            write!(f, ", {} bytes at peak", bytes)?;
+        }
+        if let Some(bytes) = self.allocated_bytes {
+            write!(f, ", {} bytes allocated", bytes)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_and_metadata() {
+        let stats = ExecutionStats {
+            elapsed: Duration::from_micros(1500),
+            rows_scanned: 10,
+            rows_returned: 3,
+            peak_bytes: None,
+            allocated_bytes: None,
+        };
+        assert_eq!(
+            stats.to_string(),
+            "1.500ms, 10 rows scanned, 3 rows returned"
+        );
+        let metadata = stats.to_metadata();
+        assert_eq!(metadata["elapsed_ms"], json!(1.5));
+        assert_eq!(metadata["rows_returned"], json!(3));
+        assert!(!metadata.contains_key("peak_bytes"));
+        let stats = ExecutionStats {
+            peak_bytes: Some(4096),
+            ..stats
+        };
+        assert_eq!(
+            stats.to_string(),
+            "1.500ms, 10 rows scanned, 3 rows returned, 4096 bytes at peak"
+        );
+        assert_eq!(stats.to_metadata()["peak_bytes"], json!(4096));
+        let stats = ExecutionStats {
+            allocated_bytes: Some(8192),
+            ..stats
+        };
+        assert_eq!(
+            stats.to_string(),
+            "1.500ms, 10 rows scanned, 3 rows returned, 4096 bytes at peak, 8192 bytes allocated"
+        );
+        assert_eq!(stats.to_metadata()["allocated_bytes"], json!(8192));
+    }
+
+    #[test]
+    fn test_rows_in() {
+        assert_eq!(ExecutionStats::rows_in(&Value::Integer(1)), 1);
+        let list = Value::List(vec![Value::Integer(1), Value::Integer(2)]);
+        assert_eq!(ExecutionStats::rows_in(&list), 2);
+    }
+}
@@ -0,0 +1,450 @@
+//! Implementations of the list verbs and indexing
+//!
+//! These operate on evaluated values; the evaluator only resolves operands
+//! and dispatches on the operator text.
+
+use crate::bigint::BigInt;
+use crate::environment::{INFINITY_INTEGER, NULL_INTEGER, Value};
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::format::Printer;
+use crate::table::Table;
+use crate::temporal;
+use tree_sitter::Node;
+
+fn expect_count(value: &Value, verb: &str, node: Node) -> Result<i64, EvalError> {
+    value.as_integer().ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "{} expects an integer count on the left, got {}",
+                verb,
+                value.type_name()
+            )),
+            node,
+        )
+    })
+}
+
+/// Whether a condition value selects its branch: a boolean or non-zero integer atom
+pub fn truthy(value: &Value, node: Node) -> Result<bool, EvalError> {
+    match value {
+        Value::Boolean(b) => Ok(*b),
+        Value::Integer(n) => Ok(*n != 0),
+        other => Err(EvalError::new(
+            EvalErrorKind::Type(format!(
+                "condition must be a boolean or integer atom, got {}",
+                other.type_name()
+            )),
+            node,
+        )),
+    }
+}
+
+/// `n#x`: the first `n` items of `x`, or the last `-n` when `n` is negative
+///
+/// Taking more items than the list holds cycles through it, and taking from
+/// an atom repeats the atom.
+pub fn take(count: &Value, target: &Value, node: Node) -> Result<Value, EvalError> {
+    let n = expect_count(count, "take", node)?;
+    let wanted = n.unsigned_abs() as usize;
+
+    let items = match target {
+        Value::List(items) => items.as_slice(),
+        atom => return Ok(Value::List(vec![atom.clone(); wanted])),
+    };
+    if items.is_empty() {
+        return if wanted == 0 {
+            Ok(Value::List(vec![]))
+        } else {
+            Err(EvalError::new(
+                EvalErrorKind::Domain("take: cannot take from an empty list".into()),
+                node,
+            ))
+        };
+    }
+
+    let len = items.len();
+    let taken = if n >= 0 {
+        (0..wanted).map(|i| items[i % len].clone()).collect()
+    } else {
+        // Count back from the end, cycling when more items are wanted than exist
+        let start = (len - wanted % len) % len;
+        (0..wanted)
+            .map(|i| items[(start + i) % len].clone())
+            .collect()
+    };
+    Ok(Value::List(taken))
+}
+
+/// `n_x`: `x` without its first `n` items, or without its last `-n`
+pub fn drop(count: &Value, target: &Value, node: Node) -> Result<Value, EvalError> {
+    let n = expect_count(count, "drop", node)?;
+    let items = target.as_list().ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "drop expects a list on the right, got {}",
+                target.type_name()
+            )),
+            node,
+        )
+    })?;
+
+    let len = items.len();
+    let dropped = n.unsigned_abs().min(len as u64) as usize;
+    let kept = if n >= 0 {
+        &items[dropped..]
+    } else {
+        &items[..len - dropped]
+    };
+    Ok(Value::List(kept.to_vec()))
+}
+
+/// `` `t$x ``: `x` converted to type `t`, item by item for lists
+///
+/// `t` is `integer` (or `long`), `bigint`, `float`, `boolean`, `symbol`,
+/// `date`, `time` or `timestamp`, as returned by `type`. Floats round to the
+/// nearest integer, with `0n` and `0w` becoming `0N` and `0W`, and big
+/// integers saturate likewise. Temporal values cast to and from integers
+/// counting their units, and timestamps cast to the date they fall on or
+/// their time of day. Strings cast to symbols, and their digits to big
+/// integers, for numbers too large to write as literals.
+pub fn cast(target: &Value, value: &Value, node: Node) -> Result<Value, EvalError> {
+    let name = target.as_symbol().ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "cast expects a type symbol on the left, got {}",
+                target.type_name()
+            )),
+            node,
+        )
+    })?;
+    if !matches!(
+        name,
+        "integer"
+            | "long"
+            | "bigint"
+            | "float"
+            | "boolean"
+            | "symbol"
+            | "date"
+            | "time"
+            | "timestamp"
+    ) {
+        return Err(EvalError::new(
+            EvalErrorKind::Type(format!("unknown type: `{}", name)),
+            node,
+        ));
+    }
+    cast_to(name, value, node)
+}
+
+fn cast_to(name: &str, value: &Value, node: Node) -> Result<Value, EvalError> {
+    // A string casts to a symbol whole, not character by character
+    if let Some(text) = value
+        .as_string()
+        .filter(|text| name == "symbol" && !text.is_empty())
+    {
+        return Ok(Value::Symbol(text.into()));
+    }
+    if let Some(text) = value.as_string().filter(|_| name == "bigint") {
+        return BigInt::parse(&text).map(Value::from).ok_or_else(|| {
+            EvalError::new(
+                EvalErrorKind::InvalidNumber(format!("not an integer: {:?}", text)),
+                node,
+            )
+        });
+    }
+    if let Value::List(items) = value {
+        return items
+            .iter()
+            .map(|item| cast_to(name, item, node))
+            .collect::<Result<_, _>>()
+            .map(Value::List);
+    }
+    let cast = match (name, value) {
+        ("integer" | "long", Value::Integer(n)) => Some(Value::Integer(*n)),
+        ("integer" | "long", Value::Float(f)) => Some(Value::Integer(float_to_integer(*f))),
+        ("integer" | "long", Value::Boolean(b)) => Some(Value::Integer(i64::from(*b))),
+        ("integer" | "long", Value::Date(d) | Value::Time(d)) => {
+            Some(Value::Integer(i64::from(*d)))
+        }
+        ("integer" | "long", Value::Timestamp(t)) => Some(Value::Integer(*t)),
+        ("integer" | "long", Value::BigInt(n)) => Some(Value::Integer(if n.is_negative() {
+            -INFINITY_INTEGER
+        } else {
+            INFINITY_INTEGER
+        })),
+        ("bigint", Value::Integer(_) | Value::BigInt(_)) => Some(value.clone()),
+        ("bigint", Value::Float(f)) => BigInt::from_f64(f.round()).map(Value::from),
+        ("float", Value::BigInt(n)) => Some(Value::Float(n.to_f64())),
+        ("float", Value::Float(f)) => Some(Value::Float(*f)),
+        ("float", Value::Integer(_)) => value.as_f64().map(Value::Float),
+        ("float", Value::Boolean(b)) => Some(Value::Float(f64::from(u8::from(*b)))),
+        ("boolean", Value::Boolean(b)) => Some(Value::Boolean(*b)),
+        ("boolean", Value::Integer(n)) => Some(Value::Boolean(*n != 0)),
+        ("boolean", Value::Float(f)) => Some(Value::Boolean(*f != 0.0)),
+        ("symbol", Value::Symbol(s)) => Some(Value::Symbol(*s)),
+        ("symbol", Value::Char(c)) => Some(Value::Symbol(c.to_string().into())),
+        ("date", Value::Date(d)) => Some(Value::Date(*d)),
+        ("date", Value::Integer(n)) => i32::try_from(*n).ok().map(Value::Date),
+        ("date", Value::Timestamp(t)) => Some(Value::Date(temporal::timestamp_to_date(*t))),
+        ("time", Value::Time(t)) => Some(Value::Time(*t)),
+        ("time", Value::Integer(n)) => i32::try_from(*n).ok().map(Value::Time),
+        ("time", Value::Timestamp(t)) => Some(Value::Time(temporal::timestamp_to_time(*t))),
+        ("timestamp", Value::Timestamp(t)) => Some(Value::Timestamp(*t)),
+        ("timestamp", Value::Integer(n)) => Some(Value::Timestamp(*n)),
+        ("timestamp", Value::Date(d)) => Some(Value::Timestamp(temporal::date_to_timestamp(*d))),
+        _ => None,
+    };
+    cast.ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!("cannot cast {} to {}", value.type_name(), name)),
+            node,
+        )
+    })
+}
+
+/// Round `f` to the nearest integer, saturating at the infinities
+fn float_to_integer(f: f64) -> i64 {
+    if f.is_nan() {
+        NULL_INTEGER
+    } else if f >= INFINITY_INTEGER as f64 {
+        INFINITY_INTEGER
+    } else if f <= -INFINITY_INTEGER as f64 {
+        -INFINITY_INTEGER
+    } else {
+        f.round() as i64
+    }
+}
+
+/// Compare `left` and `right` with `operator`, item by item
+///
+/// Lists must have equal lengths; an atom is compared against every item of
+/// a list. Each comparison yields a boolean.
+pub fn compare(
+    operator: &str,
+    left: &Value,
+    right: &Value,
+    node: Node,
+) -> Result<Value, EvalError> {
+    match (left, right) {
+        (Value::List(l), Value::List(r)) => {
+            if l.len() != r.len() {
+                return Err(EvalError::new(
+                    EvalErrorKind::LengthMismatch(format!(
+                        "{} vs {} in comparison",
+                        l.len(),
+                        r.len()
+                    )),
+                    node,
+                ));
+            }
+            l.iter()
+                .zip(r)
+                .map(|(a, b)| compare(operator, a, b, node))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::List)
+        }
+        (Value::List(l), atom) => l
+            .iter()
+            .map(|a| compare(operator, a, atom, node))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::List),
+        (atom, Value::List(r)) => r
+            .iter()
+            .map(|b| compare(operator, atom, b, node))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::List),
+        (a, b) => {
+            let result = match operator {
+                "=" => equal(a, b),
+                "<>" => !equal(a, b),
+                _ => {
+                    let ordering = a.compare(b).ok_or_else(|| {
+                        EvalError::new(
+                            EvalErrorKind::Type(format!(
+                                "cannot compare {} with {}",
+                                a.type_name(),
+                                b.type_name()
+                            )),
+                            node,
+                        )
+                    })?;
+                    match operator {
+                        "<" => ordering.is_lt(),
+                        ">" => ordering.is_gt(),
+                        "<=" => ordering.is_le(),
+                        ">=" => ordering.is_ge(),
+                        other => {
+                            return Err(EvalError::new(
+                                EvalErrorKind::UnknownOperator(other.to_string()),
+                                node,
+                            ));
+                        }
+                    }
+                }
+            };
+            Ok(Value::Boolean(result))
+        }
+    }
+}
+
+/// Whether two atoms are equal for `=`; an integer equals a float of the
+/// same value, as `<` and `>` compare them numerically
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Integer(_) | Value::BigInt(_), Value::Float(_))
+        | (Value::Float(_), Value::Integer(_) | Value::BigInt(_)) => {
+            a.compare(b).is_some_and(|ordering| ordering.is_eq())
+        }
+        _ => a == b,
+    }
+}
+
+/// `k!v`: a dictionary mapping each item of `k` to the matching item of `v`,
+/// or when both are tables, the table keyed by the columns of `k`
+pub fn dict(keys: &Value, values: &Value, node: Node) -> Result<Value, EvalError> {
+    if let (Value::Table(keys), Value::Table(values)) = (keys, values) {
+        return Table::keyed(keys, values)
+            .map(Value::Table)
+            .map_err(|message| EvalError::new(EvalErrorKind::Type(message), node));
+    }
+    let as_items = |value: &Value| match value {
+        Value::List(items) => items.clone(),
+        atom => vec![atom.clone()],
+    };
+    let (keys, values) = (as_items(keys), as_items(values));
+    if keys.len() != values.len() {
+        return Err(EvalError::new(
+            EvalErrorKind::LengthMismatch(format!(
+                "{} keys but {} values",
+                keys.len(),
+                values.len()
+            )),
+            node,
+        ));
+    }
+    Ok(Value::Dict { keys, values })
+}
+
+/// `x[i]`: the item of a list at position `i`, the value of a dict at key
+/// `i`, the column of a table named `i` or its row at position `i`, or the
+/// values of a keyed table's row with key `i`
+///
+/// A list of indices selects each of them in turn, so `xs[0 2]` yields a
+/// list and `t[0 2]` a table of those rows. A dict is first looked up with
+/// the whole index, so list keys work, as are keyed tables with several key
+/// columns.
+pub fn index(target: &Value, index: &Value, node: Node) -> Result<Value, EvalError> {
+    match target {
+        Value::List(items) => match index {
+            Value::Integer(i) => usize::try_from(*i)
+                .ok()
+                .and_then(|i| items.get(i))
+                .cloned()
+                .ok_or_else(|| {
+                    EvalError::new(
+                        EvalErrorKind::IndexOutOfRange(format!(
+                            "{} in list of length {}",
+                            i,
+                            items.len()
+                        )),
+                        node,
+                    )
+                }),
+            Value::List(indices) => indices
+                .iter()
+                .map(|i| self::index(target, i, node))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::List),
+            other => Err(EvalError::new(
+                EvalErrorKind::Type(format!(
+                    "list index must be an integer, got {}",
+                    other.type_name()
+                )),
+                node,
+            )),
+        },
+        Value::Dict { .. } => {
+            if let Some(value) = target.dict_get(index) {
+                return Ok(value.clone());
+            }
+            match index {
+                Value::List(keys) => keys
+                    .iter()
+                    .map(|k| self::index(target, k, node))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Value::List),
+                key => Err(EvalError::new(
+                    EvalErrorKind::KeyNotFound(Printer::default().inline(key, &Default::default())),
+                    node,
+                )),
+            }
+        }
+        Value::Table(table) if table.is_keyed() => match index {
+            Value::List(keys) if table.keys() == 1 => keys
+                .iter()
+                .map(|k| self::index(target, k, node))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::List),
+            key => table
+                .lookup(key)
+                .map_err(|message| EvalError::new(EvalErrorKind::LengthMismatch(message), node)),
+        },
+        Value::Table(table) => index_table(table, index, node),
+        other => Err(EvalError::new(
+            EvalErrorKind::Type(format!("cannot index {}", other.type_name())),
+            node,
+        )),
+    }
+}
+
+fn index_table(table: &Table, index: &Value, node: Node) -> Result<Value, EvalError> {
+    let out_of_range = || {
+        EvalError::new(
+            EvalErrorKind::IndexOutOfRange(format!(
+                "{} in table of {} rows",
+                Printer::default().inline(index, &Default::default()),
+                table.len()
+            )),
+            node,
+        )
+    };
+    match index {
+        // A column with an attribute is given with it
+        Value::Symbol(name) if let Some(list) = table.attributed(name) => {
+            Ok(Value::Attributed(list.clone()))
+        }
+        Value::Symbol(name) => table
+            .column(name)
+            .map(|column| Value::List(column.to_vec()))
+            .ok_or_else(|| {
+                EvalError::new(
+                    EvalErrorKind::KeyNotFound(format!("no column `{}", name)),
+                    node,
+                )
+            }),
+        Value::Integer(i) => usize::try_from(*i)
+            .ok()
+            .and_then(|i| table.row(i))
+            .ok_or_else(out_of_range),
+        Value::List(items) if items.iter().all(|i| matches!(i, Value::Integer(_))) => items
+            .iter()
+            .map(|i| i.as_integer().and_then(|i| usize::try_from(i).ok()))
+            .collect::<Option<Vec<_>>>()
+            .and_then(|rows| table.select_rows(&rows))
+            .map(Value::Table)
+            .ok_or_else(out_of_range),
+        Value::List(names) => names
+            .iter()
+            .map(|name| index_table(table, name, node))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::List),
+        other => Err(EvalError::new(
+            EvalErrorKind::Type(format!(
+                "table index must be a column name or row number, got {}",
+                other.type_name()
+            )),
+            node,
+        )),
+    }
+}
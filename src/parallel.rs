@@ -0,0 +1,54 @@
+//! Parallel execution of vector primitives
+//!
+//! Off by default. With [`EvaluatorConfig::parallel`] set, lists at least
+//! [`EvaluatorConfig::parallel_threshold`] long are split into chunks that
+//! rayon's thread pool processes at once: arithmetic kernels, the
+//! aggregates `sum`, `avg`, `min` and `max`, and the sorts `asc`, `desc`,
+//! `iasc` and `idesc`. Results come back in order, so they are what a
+//! single thread gives, except that float sums may round differently and a
+//! `sum` of integers only fails if a chunk's total or the whole total
+//! overflows.
+//!
+//! [`EvaluatorConfig::parallel`]: crate::evaluator::EvaluatorConfig::parallel
+//! [`EvaluatorConfig::parallel_threshold`]: crate::evaluator::EvaluatorConfig::parallel_threshold
+
+use rayon::prelude::*;
+use std::cmp::Ordering;
+
+/// Whether a list of `len` items is split across threads, given the
+/// threshold parallel execution is enabled with, if it is
+pub fn splits(threshold: Option<usize>, len: usize) -> bool {
+    threshold.is_some_and(|threshold| len >= threshold)
+}
+
+/// Items in each chunk of a list of `len` split across threads: a few
+/// chunks per thread, to balance the load
+pub fn chunk_size(len: usize) -> usize {
+    len.div_ceil(rayon::current_num_threads() * 4).max(1)
+}
+
+/// `f` of each chunk of `items`, computed across threads, in order
+pub fn chunks<'a, T: Sync, R: Send>(items: &'a [T], f: impl Fn(&'a [T]) -> R + Sync) -> Vec<R> {
+    items.par_chunks(chunk_size(items.len())).map(&f).collect()
+}
+
+/// `f` of each pair of chunks at the same positions of `a` and `b`, which
+/// have the same length, computed across threads and joined in order
+pub fn zip_chunks<T: Sync, R: Send>(
+    a: &[T],
+    b: &[T],
+    f: impl Fn(&[T], &[T]) -> Vec<R> + Sync,
+) -> Vec<R> {
+    let size = chunk_size(a.len());
+    let parts: Vec<Vec<R>> = a
+        .par_chunks(size)
+        .zip(b.par_chunks(size))
+        .map(|(a, b)| f(a, b))
+        .collect();
+    parts.into_iter().flatten().collect()
+}
+
+/// `items` sorted stably by `compare` across threads
+pub fn sort_by<T: Send>(items: &mut [T], compare: impl Fn(&T, &T) -> Ordering + Sync) {
+    items.par_sort_by(compare);
+}
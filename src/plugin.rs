@@ -0,0 +1,118 @@
+//! Builtin packs distributed separately from the evaluator
+//!
+//! A plugin registers native functions into a [`Session`]. Plugins are either
+//! compiled into the host and made available with
+//! [`Session::add_plugin`], or built as a `cdylib` that exports its
+//! registration function with [`declare_plugin!`](crate::declare_plugin) and
+//! loaded from a path at run time.
+//!
+//! Dynamic plugins pass Rust types across the library boundary, so they must
+//! be built with the same compiler and `wabznasm` version as the host. A
+//! loaded library is never unloaded: the functions it registers may be held
+//! by values that outlive the session.
+
+use crate::session::Session;
+use libloading::Library;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Version of the dynamic plugin interface; bumped on incompatible changes
+pub const PLUGIN_API_VERSION: u32 = 1;
+
+/// Symbol holding a dynamic plugin's [`PLUGIN_API_VERSION`]
+pub const VERSION_SYMBOL: &[u8] = b"wabznasm_plugin_api_version";
+
+/// Symbol of a dynamic plugin's registration function
+pub const REGISTER_SYMBOL: &[u8] = b"wabznasm_plugin_register";
+
+/// Signature of a dynamic plugin's registration function
+pub type RegisterFn = fn(&mut Session);
+
+/// A plugin shared between the sessions it is available to
+pub type SharedPlugin = Arc<dyn Plugin>;
+
+/// A pack of functions that can be registered into a session
+pub trait Plugin: Send + Sync {
+    /// Name the plugin is loaded by
+    fn name(&self) -> &str;
+
+    /// Bind the plugin's functions into `session`
+    fn register(&self, session: &mut Session);
+}
+
+/// Error loading a plugin
+#[derive(Error, Debug)]
+pub enum PluginError {
+    #[error("Unknown plugin: {0}")]
+    NotFound(String),
+
+    #[error("Failed to load plugin {path}: {source}")]
+    Load {
+        path: PathBuf,
+        #[source]
+        source: libloading::Error,
+    },
+
+    #[error("Plugin {path} was built for plugin API {found}, expected {PLUGIN_API_VERSION}")]
+    IncompatibleVersion { path: PathBuf, found: u32 },
+}
+
+/// Export `register` as the entry point of a dynamic plugin
+///
+/// Use once in a `cdylib` crate:
+///
+/// ```ignore
+/// fn register(session: &mut wabznasm::Session) {
+///     session.define("double", 1, |args| match args[0] {
+///         wabznasm::Value::Integer(n) => Ok(wabznasm::Value::Integer(n * 2)),
+///         _ => Err("double expects an integer".into()),
+///     });
+/// }
+///
+/// wabznasm::declare_plugin!(register);
+/// ```
+#[macro_export]
+macro_rules! declare_plugin {
+    ($register:path) => {
+        #[unsafe(no_mangle)]
+        pub static wabznasm_plugin_api_version: u32 = $crate::plugin::PLUGIN_API_VERSION;
+
+        #[unsafe(no_mangle)]
+        pub fn wabznasm_plugin_register(session: &mut $crate::Session) {
+            let register: $crate::plugin::RegisterFn = $register;
+            register(session)
+        }
+    };
+}
+
+/// Load the dynamic plugin at `path` and return its registration function
+pub(crate) fn open(path: &Path) -> Result<RegisterFn, PluginError> {
+    let load_error = |source| PluginError::Load {
+        path: path.to_path_buf(),
+        source,
+    };
+    // SAFETY: loading a library runs its initialisers; plugins are trusted
+    // code the user asked to load
+    let library = unsafe { Library::new(path) }.map_err(load_error)?;
+    // SAFETY: the symbols are declared by `declare_plugin!` with these types
+    let found = unsafe {
+        **library
+            .get::<*const u32>(VERSION_SYMBOL)
+            .map_err(load_error)?
+    };
+    if found != PLUGIN_API_VERSION {
+        return Err(PluginError::IncompatibleVersion {
+            path: path.to_path_buf(),
+            found,
+        });
+    }
+    let register = unsafe {
+        *library
+            .get::<RegisterFn>(REGISTER_SYMBOL)
+            .map_err(load_error)?
+    };
+    // Keep the code behind `register` and any closures it creates mapped
+    std::mem::forget(library);
+    Ok(register)
+}
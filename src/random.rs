@@ -0,0 +1,150 @@
+//! Random numbers: the `?` verb and the generator behind it
+//!
+//! `n?m` rolls `n` integers below `m`, or floats below `m` if it is a float,
+//! and `n?list` picks `n` items of the list, each independently. A negative
+//! `n` deals instead: `-n` distinct integers below `m`, or items from
+//! distinct positions of the list, as cards from a deck.
+//!
+//! Every evaluator starts from the same seed, as q does, so a script rolls
+//! the same numbers on each run; `seed n` restarts the sequence from `n`.
+//!
+//! ```text
+//! 3?10          / three integers in 0..9, repeats allowed
+//! -3?10         / three different integers in 0..9
+//! 2?`a`b`c      / two symbols picked from the list
+//! ```
+
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use std::collections::HashMap;
+use tree_sitter::Node;
+
+/// Seed of a fresh generator
+pub const DEFAULT_SEED: u64 = 314159;
+
+/// A small, fast generator (SplitMix64); not for cryptographic use
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
+
+impl Rng {
+    /// A generator whose sequence is fixed by `seed`
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// The next 64 random bits
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform integer in `0..n`; `n` must not be zero
+    pub fn below(&mut self, n: u64) -> u64 {
+        // Reject the top partial range so every result is equally likely
+        let limit = u64::MAX - u64::MAX % n;
+        loop {
+            let bits = self.next_u64();
+            if bits < limit {
+                return bits % n;
+            }
+        }
+    }
+
+    /// A uniform float in `[0, 1)`
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// `n` distinct uniform integers in `0..m`, by a Fisher-Yates shuffle
+    /// that only tracks the positions it has swapped
+    fn deal(&mut self, n: usize, m: usize) -> Vec<usize> {
+        let mut swapped: HashMap<usize, usize> = HashMap::new();
+        (0..n)
+            .map(|i| {
+                let j = i + self.below((m - i) as u64) as usize;
+                let at_j = swapped.get(&j).copied().unwrap_or(j);
+                let at_i = swapped.get(&i).copied().unwrap_or(i);
+                swapped.insert(j, at_i);
+                at_j
+            })
+            .collect()
+    }
+}
+
+/// `n?m`: `n` random integers below `m`, floats below `m`, or items of the
+/// list `m`; with a negative `n`, `-n` of them without repeats
+pub fn roll(rng: &mut Rng, count: &Value, target: &Value, node: Node) -> Result<Value, EvalError> {
+    let n = count.as_integer().ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "? expects an integer count on the left, got {}",
+                count.type_name()
+            )),
+            node,
+        )
+    })?;
+    let wanted = n.unsigned_abs() as usize;
+    let empty = |what: &str| {
+        EvalError::new(
+            EvalErrorKind::Domain(format!("?: cannot pick from {}", what)),
+            node,
+        )
+    };
+    // How many there are to pick from, and the list picked from if any
+    let (size, list) = match target {
+        Value::Integer(m) if *m > 0 => (*m as usize, None),
+        Value::Integer(_) => return Err(empty("a non-positive range")),
+        Value::Float(m) if n >= 0 => {
+            let items = (0..wanted).map(|_| Value::Float(rng.unit() * m)).collect();
+            return Ok(Value::List(items));
+        }
+        Value::Float(_) => {
+            return Err(EvalError::new(
+                EvalErrorKind::Type("? cannot deal floats, only roll them".into()),
+                node,
+            ));
+        }
+        Value::List(items) if items.is_empty() => return Err(empty("an empty list")),
+        Value::List(items) => (items.len(), Some(items.as_slice())),
+        other => {
+            return Err(EvalError::new(
+                EvalErrorKind::Type(format!(
+                    "? expects an integer, float or list on the right, got {}",
+                    other.type_name()
+                )),
+                node,
+            ));
+        }
+    };
+    let positions = if n >= 0 {
+        (0..wanted)
+            .map(|_| rng.below(size as u64) as usize)
+            .collect()
+    } else if wanted <= size {
+        rng.deal(wanted, size)
+    } else {
+        return Err(EvalError::new(
+            EvalErrorKind::LengthMismatch(format!(
+                "?: cannot deal {} distinct items from {}",
+                wanted, size
+            )),
+            node,
+        ));
+    };
+    let pick = |i: usize| match list {
+        Some(items) => items[i].clone(),
+        None => Value::Integer(i as i64),
+    };
+    Ok(Value::List(positions.into_iter().map(pick).collect()))
+}
@@ -0,0 +1,376 @@
+//! Reflection builtins: `parse` turns source text into a parse tree made of
+//! ordinary values, and `eval` evaluates such a tree
+//!
+//! A parse tree is written the way q writes one. An application is a list
+//! whose first item is the symbol naming the function or operator and
+//! whose other items are the trees of its arguments; a name is a symbol;
+//! and any other value stands for itself. Symbol literals are enlisted so
+//! they are not taken for names:
+//!
+//! ```text
+//! parse "1+x"             / (`+;1;`x)
+//! parse "f[x;`a]"         / (`f;`x;,`a)
+//! parse "(1;x)"           / (`enlist;1;`x)
+//! eval (`count;1 2 3)     / 3
+//! ```
+//!
+//! Other constructs are headed by the token that opens them: `` `$[ ``,
+//! `` `do[ ``, `` `while[ ``, `` `@[ `` and `` `' ``, while assignments are
+//! `` (`:;`name;value) `` and statements in turn `` (`;;s1;s2) ``. Queries
+//! are `` (`select;columns;by;table;where) ``, and likewise for `update`
+//! and `delete`, with a list of trees in each clause and named columns
+//! written as assignments. Since the trees are plain lists, queries can be
+//! built up with list operations and then evaluated.
+//!
+//! `eval` evaluates a tree in the environment it is called from, and its
+//! assignments set globals, as `::` does. A list headed by a function value,
+//! such as a lambda from `parse`, applies it to the rest.
+
+use crate::builtins::{Apply, Bindings};
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::parser::parse_expression;
+use tree_sitter::Node;
+
+/// Operators written between their two operands
+const INFIX: &[&str] = &[
+    "+", "-", "*", "/", "%", "^", "=", "<>", "<", ">", "<=", ">=", "#", "_", "$", "?", "!",
+];
+
+/// Openers of the bracketed forms, written before their arguments
+const BRACKETED: &[&str] = &["$[", "do[", "while[", "@["];
+
+/// The kinds of query, each with its clauses in the order of a tree
+const QUERIES: &[&str] = &["select", "update", "delete"];
+
+fn parse_error(message: String, node: Node) -> EvalError {
+    EvalError::new(EvalErrorKind::Other(format!("parse: {}", message)), node)
+}
+
+fn symbol(name: &str) -> Value {
+    Value::Symbol(name.into())
+}
+
+/// An application of the function or operator `head` to `args`
+fn application(head: &str, args: impl IntoIterator<Item = Value>) -> Value {
+    Value::List(std::iter::once(symbol(head)).chain(args).collect())
+}
+
+/// Parse trees of the items of a list
+type Trees = Vec<Value>;
+
+/// Source text of each of a clause's trees
+type Texts = Vec<String>;
+
+/// Turns a syntax tree into a parse tree
+struct Parser<'s, 'a, 'n> {
+    src: &'s str,
+    apply: &'a mut dyn Apply,
+    /// Node the builtin was called at, for errors
+    call: Node<'n>,
+}
+
+impl<'s> Parser<'s, '_, '_> {
+    fn text(&self, node: Node) -> &'s str {
+        &self.src[node.byte_range()]
+    }
+
+    /// The value a literal stands for
+    fn literal(&mut self, node: Node) -> Result<Value, EvalError> {
+        let text = self.text(node);
+        self.apply.evaluate(text, Vec::new(), self.call)
+    }
+
+    fn field(&self, node: Node<'s>, name: &str) -> Result<Node<'s>, EvalError> {
+        node.child_by_field_name(name)
+            .ok_or_else(|| parse_error(format!("{} has no {}", node.kind(), name), self.call))
+    }
+
+    fn fields(&mut self, node: Node<'s>, name: &str) -> Result<Trees, EvalError> {
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children_by_field_name(name, &mut cursor).collect();
+        children.into_iter().map(|child| self.tree(child)).collect()
+    }
+
+    fn tree(&mut self, node: Node<'s>) -> Result<Value, EvalError> {
+        let operator = node.child_by_field_name("operator").map(|op| self.text(op));
+        match (node.kind(), operator) {
+            ("source_file" | "block", _) => {
+                let mut statements = self.fields(node, "statement")?;
+                Ok(match statements.len() {
+                    1 => statements.remove(0),
+                    _ => application(";", statements),
+                })
+            }
+            ("assignment", Some(op)) => {
+                let name = self.text(self.field(node, "name")?);
+                let value = self.tree(self.field(node, "value")?)?;
+                Ok(application(op, [symbol(name), value]))
+            }
+            ("select_column", Some(op)) => {
+                let name = self.text(self.field(node, "name")?);
+                let value = self.tree(self.field(node, "value")?)?;
+                Ok(application(op, [symbol(name), value]))
+            }
+            ("select_column", None) => self.tree(self.field(node, "value")?),
+            ("dyadic" | "comparison" | "additive" | "multiplicative", Some(op)) => {
+                let left = self.tree(self.field(node, "left")?)?;
+                let right = self.tree(self.field(node, "right")?)?;
+                Ok(application(op, [left, right]))
+            }
+            ("power", Some(op)) => {
+                let base = self.tree(self.field(node, "base")?)?;
+                let exponent = self.tree(self.field(node, "exponent")?)?;
+                Ok(application(op, [base, exponent]))
+            }
+            ("unary", Some(op)) => {
+                let operand = self.tree(self.field(node, "operand")?)?;
+                Ok(application(op, [operand]))
+            }
+            ("postfix", Some(op)) => match node.child_by_field_name("keys") {
+                Some(keys) => {
+                    let keys = self.tree(keys)?;
+                    let values = self.tree(self.field(node, "values")?)?;
+                    Ok(application(op, [keys, values]))
+                }
+                None => {
+                    let operand = self.tree(self.field(node, "operand")?)?;
+                    Ok(application(op, [operand]))
+                }
+            },
+            ("primary", _) if node.child_by_field_name("expression").is_some() => {
+                self.tree(self.field(node, "expression")?)
+            }
+            ("identifier", _) => Ok(symbol(self.text(node))),
+            ("symbol", _) => Ok(Value::List(vec![self.literal(node)?])),
+            ("number" | "boolean" | "date" | "time" | "timestamp" | "string" | "vector", _)
+            | ("function_body", _) => self.literal(node),
+            ("list", _) => {
+                let items = self.fields(node, "item")?;
+                Ok(match items.is_empty() {
+                    true => Value::List(items),
+                    false => application("enlist", items),
+                })
+            }
+            ("function_call", _) => {
+                let function = self.field(node, "function")?;
+                let Some(args) = node.child_by_field_name("args") else {
+                    return Err(parse_error(
+                        format!(
+                            "cannot represent {}[] without arguments",
+                            self.text(function)
+                        ),
+                        self.call,
+                    ));
+                };
+                let mut cursor = args.walk();
+                let slots = args
+                    .children_by_field_name("separator", &mut cursor)
+                    .count()
+                    + 1;
+                let args = self.fields(args, "arg")?;
+                if args.len() < slots {
+                    return Err(parse_error(
+                        format!("cannot represent the projection {}", self.text(node)),
+                        self.call,
+                    ));
+                }
+                match function.kind() {
+                    "identifier" => Ok(application(self.text(function), args)),
+                    // What a call or lambda gives is applied in turn
+                    _ => Ok(Value::List(
+                        std::iter::once(self.tree(function)?).chain(args).collect(),
+                    )),
+                }
+            }
+            ("application", _) => {
+                let function = self.text(self.field(node, "function")?);
+                let argument = self.tree(self.field(node, "argument")?)?;
+                Ok(application(function, [argument]))
+            }
+            ("conditional", _) | ("trap", _) => {
+                let opener = self.text(self.field(node, "left_bracket")?);
+                let names: &[&str] = match node.kind() {
+                    "conditional" => &["arg"],
+                    _ => &["function", "argument", "handler"],
+                };
+                let mut args = Vec::new();
+                for name in names {
+                    args.extend(self.fields(node, name)?);
+                }
+                Ok(application(opener, args))
+            }
+            ("loop", _) => {
+                let opener = self.text(self.field(node, "keyword")?);
+                let condition = self.tree(self.field(node, "condition")?)?;
+                let body = self.fields(node, "body")?;
+                Ok(application(opener, std::iter::once(condition).chain(body)))
+            }
+            ("signal", Some(op)) => {
+                let message = self.tree(self.field(node, "message")?)?;
+                Ok(application(op, [message]))
+            }
+            ("select" | "update" | "delete", _) => {
+                let columns = self.fields(node, "column")?;
+                let by = self.fields(node, "group")?;
+                let table = self.tree(self.field(node, "table")?)?;
+                let filters = self.fields(node, "filter")?;
+                Ok(application(
+                    node.kind(),
+                    [
+                        Value::List(columns),
+                        Value::List(by),
+                        table,
+                        Value::List(filters),
+                    ],
+                ))
+            }
+            // Wrappers and precedence layers without an operator
+            (_, None) if node.named_child_count() == 1 => {
+                let child = node.named_child(0).expect("the node has one child");
+                self.tree(child)
+            }
+            (kind, _) => Err(parse_error(format!("cannot represent {}", kind), self.call)),
+        }
+    }
+}
+
+/// Writes a parse tree back as source text, binding the values that stand
+/// for themselves to names the text refers to them by, so that they need
+/// not be written out and read back
+#[derive(Default)]
+struct Writer {
+    bindings: Bindings,
+}
+
+impl Writer {
+    /// A name bound to `value`
+    fn bind(&mut self, value: &Value) -> String {
+        let name = format!(".z.e{}", self.bindings.len());
+        self.bindings.push((name.clone(), value.clone()));
+        name
+    }
+
+    fn write(&mut self, tree: &Value) -> Result<String, String> {
+        match tree {
+            Value::Symbol(name) => Ok(name.to_string()),
+            // An enlisted symbol stands for itself rather than a name
+            Value::List(items) if items.len() == 1 => Ok(self.bind(&items[0])),
+            Value::List(items) => match items.split_first() {
+                Some((Value::Symbol(head), args)) => self.application(head, args),
+                // A function value applies to the rest, as a name would
+                Some((function, args)) if function.is_function() => {
+                    let name = self.bind(function);
+                    self.application(&name, args)
+                }
+                // So does what a call gives, as in f[2][3]
+                Some((call @ Value::List(_), args)) => {
+                    let call = self.write(call)?;
+                    if !call.ends_with(']') {
+                        return Err(format!("cannot apply {}", call));
+                    }
+                    self.application(&call, args)
+                }
+                _ => Ok(self.bind(tree)),
+            },
+            other => Ok(self.bind(other)),
+        }
+    }
+
+    fn application(&mut self, head: &str, args: &[Value]) -> Result<String, String> {
+        if QUERIES.contains(&head) {
+            return self.query(head, args);
+        }
+        if let (":" | "::", [Value::Symbol(name), value]) = (head, args) {
+            return Ok(format!("{}::{}", name, self.write(value)?));
+        }
+        let written = args
+            .iter()
+            .map(|arg| self.write(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(match (head, written.as_slice()) {
+            (op, [left, right]) if INFIX.contains(&op) => format!("({}){}({})", left, op, right),
+            ("-" | "'", [operand]) => format!("{}({})", head, operand),
+            ("!", [operand]) => format!("({})!", operand),
+            (":" | "::", _) => return Err(format!("cannot assign with {} items", args.len())),
+            (";", statements) => statements.join(";"),
+            (opener, args) if BRACKETED.contains(&opener) => {
+                format!("{}{}]", opener, args.join(";"))
+            }
+            ("enlist", [item]) => format!("enlist[{}]", item),
+            ("enlist", items) => format!("({})", items.join(";")),
+            (function, args) => format!("{}[{}]", function, args.join(";")),
+        })
+    }
+
+    /// The trees of a clause of a query, columns named by assignments
+    fn clause(&mut self, kind: &str, trees: &Value) -> Result<Texts, String> {
+        let Value::List(trees) = trees else {
+            return Err(format!("{} clauses must be lists", kind));
+        };
+        trees
+            .iter()
+            .map(|tree| match tree.as_list() {
+                Some([Value::Symbol(op), Value::Symbol(name), value]) if &**op == ":" => {
+                    Ok(format!("{}:{}", name, self.write(value)?))
+                }
+                _ => self.write(tree),
+            })
+            .collect()
+    }
+
+    /// A query from its columns, groups, table and conditions
+    fn query(&mut self, kind: &str, args: &[Value]) -> Result<String, String> {
+        let [columns, by, table, filters] = args else {
+            return Err(format!("{} needs columns, by, table and where", kind));
+        };
+        let columns = self.clause(kind, columns)?;
+        let by = self.clause(kind, by)?;
+        let filters = self.clause(kind, filters)?;
+        let mut text = kind.to_string();
+        if !columns.is_empty() {
+            text.push_str(&format!(" {}", columns.join(", ")));
+        }
+        if !by.is_empty() {
+            text.push_str(&format!(" by {}", by.join(", ")));
+        }
+        text.push_str(&format!(" from {}", self.write(table)?));
+        if !filters.is_empty() {
+            text.push_str(&format!(" where {}", filters.join(", ")));
+        }
+        Ok(text)
+    }
+}
+
+/// `parse s`: the parse tree of the source text `s`
+pub fn parse(apply: &mut dyn Apply, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let Some(text) = args[0].as_string() else {
+        return Err(EvalError::new(
+            EvalErrorKind::Type(format!(
+                "parse expects a string, got {}",
+                args[0].type_name()
+            )),
+            node,
+        ));
+    };
+    let tree = parse_expression(&text).map_err(|e| parse_error(e.to_string(), node))?;
+    if tree.root_node().has_error() {
+        return Err(parse_error(format!("syntax error in {:?}", text), node));
+    }
+    Parser {
+        src: &text,
+        apply,
+        call: node,
+    }
+    .tree(tree.root_node())
+}
+
+/// `eval t`: the value of the parse tree `t`, evaluated where `eval` is
+/// called
+pub fn eval(apply: &mut dyn Apply, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let mut writer = Writer::default();
+    let source = writer
+        .write(&args[0])
+        .map_err(|e| EvalError::new(EvalErrorKind::Other(format!("eval: {}", e)), node))?;
+    apply.evaluate(&source, writer.bindings, node)
+}
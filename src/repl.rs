@@ -1,16 +1,101 @@
-use crate::environment::{Environment, Value};
-use crate::evaluator::Evaluator;
-use crate::parser::parse_expression;
+use crate::arithmetic::OverflowMode;
+use crate::builtins;
+use crate::cancel::CancelToken;
+use crate::completion::{self, Name};
+use crate::environment::Value;
+use crate::explorer::Variable;
+use crate::format::Printer;
+use crate::journal;
+use crate::script::Script;
+use crate::session::Session;
+use crate::table;
 use color_eyre::eyre;
-use rustyline::Editor;
+use miette::Report;
+use rustyline::completion::{Completer, Pair};
 use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
 use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-/// Run the interactive REPL with persistent environment.
-pub fn run() -> Result<(), eyre::Report> {
-    let mut rl: Editor<(), DefaultHistory> = Editor::new()?;
-    let mut env = Environment::new();
-    let mut evaluator = Evaluator::new();
+/// Line editor support: completing names with Tab
+///
+/// The names are those bound when the line was started, as the session is
+/// not borrowed while the line is edited.
+#[derive(Default)]
+struct ReplHelper {
+    names: Vec<Name>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let completion = completion::complete(line, pos, &self.names);
+        let replacements = completion.replacements(line, pos);
+        let pairs = (completion.matches.into_iter().zip(replacements))
+            .map(|(name, replacement)| Pair {
+                display: name.text,
+                replacement,
+            })
+            .collect();
+        Ok((completion.start, pairs))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// Stop the evaluation in progress on Ctrl-C, through `cancel`, or exit if
+/// nothing is evaluating
+///
+/// The line editor reads Ctrl-C at the prompt itself, so the signal only
+/// arrives there when input is not a terminal.
+fn handle_interrupts(cancel: CancelToken, evaluating: Arc<AtomicBool>) {
+    let handled = ctrlc::set_handler(move || {
+        if evaluating.load(Ordering::Relaxed) {
+            cancel.cancel();
+        } else {
+            std::process::exit(130);
+        }
+    });
+    if let Err(e) = handled {
+        eprintln!("Ctrl-C will not interrupt evaluation: {}", e);
+    }
+}
+
+/// Run the interactive REPL on `session`, keeping its bindings between lines
+///
+/// Inputs and `\` commands that change the session are journaled if the
+/// session has a journal. Ctrl-C stops the evaluation in progress; at the
+/// prompt, pressing it twice exits.
+pub fn run(mut session: Session) -> Result<(), eyre::Report> {
+    let mut rl: Editor<ReplHelper, DefaultHistory> = Editor::new()?;
+    rl.set_helper(Some(ReplHelper::default()));
+    let evaluating = Arc::new(AtomicBool::new(false));
+    handle_interrupts(session.cancel_token(), evaluating.clone());
+    // Whether the last prompt was left with Ctrl-C
+    let mut interrupted = false;
+    // The value of the last input, for `\page`
+    let mut last: Option<Value> = None;
 
     println!(
         "wabznasm REPL: enter expressions, assignments, or function definitions. Type 'exit' to quit"
@@ -18,8 +103,15 @@ pub fn run() -> Result<(), eyre::Report> {
     println!("Examples: 1+2, f: {{x+1}}, add: {{[x;y] x+y}}, f[5], add[2;3]");
 
     loop {
-        match rl.readline("wabz> ") {
+        if let Some(helper) = rl.helper_mut() {
+            helper.names = session.names();
+        }
+        evaluating.store(false, Ordering::Relaxed);
+        let line = rl.readline("wabz> ");
+        evaluating.store(true, Ordering::Relaxed);
+        match line {
             Ok(line) => {
+                interrupted = false;
                 let input = line.trim();
                 if input.is_empty() {
                     continue;
@@ -29,26 +121,54 @@ pub fn run() -> Result<(), eyre::Report> {
                     break;
                 }
 
-                // Parse and evaluate with persistent environment
-                match parse_expression(input) {
-                    Ok(tree) => match evaluator.eval_with_env(tree.root_node(), input, &mut env) {
-                        Ok(Value::Integer(val)) => println!("= {}", val),
-                        Ok(Value::Function { params, .. }) => {
-                            if params.is_empty() {
-                                println!("= {{expr}}");
-                            } else {
-                                let param_names: Vec<String> = params
-                                    .iter()
-                                    .map(|&p_val| evaluator.resolve(p_val).to_string())
-                                    .collect();
-                                println!("= {{[{}] expr}}", param_names.join(";"));
+                if let Some(command) = input.strip_prefix('\\') {
+                    let result = match command.split_once(char::is_whitespace) {
+                        _ if command == "page" => show_in_full(&session, last.as_ref()),
+                        Some(("page", source)) => match session.eval(source) {
+                            Ok(value) => show_in_full(&session, Some(&value)),
+                            Err(e) => Err(format!("Error: {:?}", e)),
+                        },
+                        _ => run_command(&mut session, command),
+                    };
+                    match result {
+                        Ok(true) => {
+                            if let Err(e) = session.record(input) {
+                                eprintln!("Error: {}", e);
                             }
                         }
-                        Err(e) => eprintln!("Error: {:?}", e),
-                    },
-                    Err(e) => eprintln!("Parse error: {:?}", e),
+                        Ok(false) => {}
+                        Err(e) => eprintln!("{}", e),
+                    }
+                    continue;
+                }
+
+                let request_id = uuid::Uuid::new_v4().to_string();
+                let _span = crate::telemetry::request_span(&request_id, "repl").entered();
+                let result = session.eval(input);
+                for warning in session.warnings() {
+                    let report = Report::new(warning.clone()).with_source_code(input.to_string());
+                    eprintln!("{:?}", report);
+                }
+                match result {
+                    Ok(value) => {
+                        // Tables and dictionaries span several lines, so
+                        // start them on their own
+                        let text = session.display(&value);
+                        if text.contains('\n') {
+                            println!("{}", text);
+                        } else {
+                            println!("= {}", text);
+                        }
+                        println!("({})", session.stats());
+                        last = Some(value);
+                    }
+                    Err(e) => eprintln!("Error: {:?}", e),
                 }
             }
+            Err(ReadlineError::Interrupted) if !interrupted => {
+                interrupted = true;
+                println!("(To exit, press Ctrl-C again or type exit)");
+            }
             Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
             Err(err) => {
                 eprintln!("Error: {}", err);
@@ -58,3 +178,232 @@ pub fn run() -> Result<(), eyre::Report> {
     }
     Ok(())
 }
+
+/// `\page`: show `value` in full through the pager, rather than cut to fit
+/// the console
+fn show_in_full(session: &Session, value: Option<&Value>) -> Result<bool, String> {
+    let value = value.ok_or("Nothing to page: no result yet")?;
+    page(&session.display_in_full(value));
+    Ok(false)
+}
+
+/// Show `text` through the pager named by `$PAGER`, or `less`, or print it
+/// if output is not to a terminal or there is no pager
+fn page(text: &str) {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut words = pager.split_whitespace();
+    let spawned = match words.next() {
+        Some(program) if std::io::stdout().is_terminal() => Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .spawn()
+            .ok(),
+        _ => None,
+    };
+    match spawned {
+        Some(mut pager) => {
+            // The pager closes its input if it is quit before the end
+            if let Some(mut input) = pager.stdin.take() {
+                let _ = writeln!(input, "{}", text);
+            }
+            let _ = pager.wait();
+        }
+        None => println!("{}", text),
+    }
+}
+
+/// Handle a `\` command line such as `\load-plugin stats`
+///
+/// Returns whether the command changed the session, and so belongs in its
+/// journal.
+fn run_command(session: &mut Session, command: &str) -> Result<bool, String> {
+    let (name, argument) = command
+        .split_once(char::is_whitespace)
+        .map_or((command, ""), |(name, rest)| (name, rest.trim()));
+    match name {
+        "load-plugin" if !argument.is_empty() => match session.load_plugin(argument) {
+            Ok(()) => {
+                println!("Loaded plugin {}", argument);
+                Ok(true)
+            }
+            Err(e) => Err(format!("Error: {}", e)),
+        },
+        "load-plugin" => Err("Usage: \\load-plugin <name or path>".to_string()),
+        "overflow" if argument.is_empty() => {
+            println!("{}", session.overflow_mode().name());
+            Ok(false)
+        }
+        "overflow" => match OverflowMode::parse(argument) {
+            Some(mode) => {
+                session.set_overflow_mode(mode);
+                Ok(true)
+            }
+            None => Err(format!(
+                "Unknown overflow mode: {} (error, wrap, saturate, promote or bigint)",
+                argument
+            )),
+        },
+        "c" if argument.is_empty() => {
+            let printer = session.printer();
+            println!("{} {}", printer.rows, printer.width);
+            Ok(false)
+        }
+        "c" => match argument
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Vec<_>>()[..]
+        {
+            [Ok(rows), Ok(width)] => {
+                session.set_printer(Printer::new(width, rows));
+                Ok(true)
+            }
+            _ => Err("Usage: \\c <rows> <columns>".to_string()),
+        },
+        "d" if argument.is_empty() => {
+            println!("{}", session.namespace());
+            Ok(false)
+        }
+        "d" => session.set_namespace(argument).map(|()| true),
+        "v" => {
+            let namespace = (!argument.is_empty()).then_some(argument);
+            println!("{}", session.variable_names(namespace).join(" "));
+            Ok(false)
+        }
+        "f" => {
+            let namespace = (!argument.is_empty()).then_some(argument);
+            let headers = ["name", "arity"].map(String::from);
+            let cells: Vec<Vec<String>> = (session.functions(namespace).into_iter())
+                .map(|(name, arity)| vec![name, arity.map_or("-".to_string(), |n| n.to_string())])
+                .collect();
+            println!("{}", table::layout(&headers, &cells));
+            Ok(false)
+        }
+        "t" | "ts" if argument.is_empty() => Err(format!("Usage: \\{} <expression>", name)),
+        "t" => {
+            let stats = session
+                .time(argument, false)
+                .map_err(|e| format!("Error: {:?}", e))?;
+            println!("{:.3}ms", stats.elapsed.as_secs_f64() * 1000.0);
+            Ok(false)
+        }
+        "ts" => {
+            let stats = session
+                .time(argument, true)
+                .map_err(|e| format!("Error: {:?}", e))?;
+            let bytes = stats.allocated_bytes.unwrap_or_default();
+            println!(
+                "{:.3}ms {} bytes",
+                stats.elapsed.as_secs_f64() * 1000.0,
+                bytes
+            );
+            Ok(false)
+        }
+        "l" if argument.is_empty() => Err("Usage: \\l <path>".to_string()),
+        "l" => match session.load(Path::new(argument)) {
+            Ok(_) => Ok(false),
+            Err(e) => Err(format!("Error: {:?}", e)),
+        },
+        "h" if argument.is_empty() => Err("Usage: \\h <name>".to_string()),
+        "h" => {
+            let manual = match builtins::lookup(argument) {
+                Some(builtin) => Some(builtin.manual()),
+                None => session.help(argument),
+            };
+            println!(
+                "{}",
+                manual.ok_or(format!("Nothing is named {}", argument))?
+            );
+            Ok(false)
+        }
+        "e" if argument.is_empty() => Err("Usage: \\e <function>".to_string()),
+        "e" => edit_function(session, argument),
+        "vars" => {
+            let long = argument == "-l" || argument.starts_with("-l ");
+            let path = argument.strip_prefix("-l").unwrap_or(argument);
+            let path: Vec<&str> = path.split_whitespace().collect();
+            let variables = session.explore(&path)?;
+            println!("{}", list_variables(&variables, long));
+            Ok(false)
+        }
+        _ => Err(format!("Unknown command: \\{}", name)),
+    }
+}
+
+/// `\e f`: open the source of the function `f` in the user's editor, then
+/// evaluate what was saved, statement by statement as a script is, so `f`
+/// is bound to the edited function
+///
+/// A statement that fails stops the rest; the file is then kept, for the
+/// edits to be fixed and loaded with `\l`.
+fn edit_function(session: &mut Session, name: &str) -> Result<bool, String> {
+    let definition = session.definition(name)?;
+    let path = std::env::temp_dir().join(format!("wabznasm-{}-{}.wz", std::process::id(), name));
+    std::fs::write(&path, format!("{}\n", definition))
+        .map_err(|e| format!("Error: cannot write {}: {}", path.display(), e))?;
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program).args(words).arg(&path).status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => return Err(format!("Editor {} failed: {}", program, status)),
+        Err(e) => return Err(format!("Cannot run editor {}: {}", program, e)),
+    }
+    let script = Script::read(&path).map_err(|e| format!("Error: {}", e))?;
+    for statement in script.statements() {
+        if let Err(e) = session.eval(statement.text) {
+            return Err(format!(
+                "Error: {:?}\nThe edits are kept in {}",
+                e,
+                path.display()
+            ));
+        }
+    }
+    let _ = std::fs::remove_file(&path);
+    Ok(false)
+}
+
+/// The table `\vars` prints: each variable's name, type and shape, and in
+/// the long form its size in bytes and a preview
+fn list_variables(variables: &[Variable], long: bool) -> String {
+    let mut headers = vec!["name", "type", "shape"];
+    if long {
+        headers.extend(["bytes", "preview"]);
+    }
+    let headers: Vec<String> = headers.into_iter().map(String::from).collect();
+    let cells: Vec<Vec<String>> = variables
+        .iter()
+        .map(|v| {
+            let mut row = vec![v.name.clone(), v.type_name.clone(), v.shape.clone()];
+            if long {
+                row.extend([v.bytes.to_string(), v.preview.clone()]);
+            }
+            row
+        })
+        .collect();
+    table::layout(&headers, &cells)
+}
+
+/// Re-run the entries of the journal at `path` into `session`
+///
+/// Stops at the first entry that fails, since later entries may depend on
+/// it. Returns the number of entries replayed. Attach the session's journal
+/// afterwards, or the replayed entries are journaled a second time.
+pub fn replay_journal(session: &mut Session, path: &Path) -> Result<usize, eyre::Report> {
+    let entries = journal::read(path)
+        .map_err(|e| eyre::eyre!("Failed to read journal {}: {}", path.display(), e))?;
+    for (index, entry) in entries.iter().enumerate() {
+        let replayed = match entry.strip_prefix('\\') {
+            Some(command) => run_command(session, command).map(|_| ()),
+            None => session
+                .eval(entry)
+                .map(|_| ())
+                .map_err(|e| format!("{:?}", e)),
+        };
+        replayed
+            .map_err(|e| eyre::eyre!("Journal entry {} ({}) failed: {}", index + 1, entry, e))?;
+    }
+    Ok(entries.len())
+}
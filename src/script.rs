@@ -0,0 +1,215 @@
+//! Scripts: files of statements, loaded with `\l path` in the REPL or the
+//! `load` builtin
+//!
+//! A script has a statement per line. A statement continues onto the lines
+//! after it that are indented, as in q, and onto those after a bracket it
+//! leaves open, so a function body can span several lines:
+//!
+//! ```text
+//! \ statistics helpers
+//! mean: {[x] (sum x) % count x}
+//! spread: {[x]
+//!   m: mean x;
+//!   (max x) - m}
+//! ```
+//!
+//! The statements are evaluated one after another, as if entered at the top
+//! level: their assignments bind in the environment `load` was called from.
+//! Evaluation stops at the first that fails, with the error pointing at its
+//! line in the script.
+//!
+//! By the same rule, [`completeness`] tells a console whether the lines
+//! typed so far make a statement or leave a bracket open for more.
+
+use crate::builtins::Apply;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use std::fs;
+use std::path::Path;
+use tree_sitter::Node;
+
+/// The text of a script and the name errors in it are reported under
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    pub name: String,
+    pub text: String,
+}
+
+/// A statement of a script
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statement<'a> {
+    pub text: &'a str,
+    /// Byte offset of the statement in the script
+    pub offset: usize,
+}
+
+impl Script {
+    /// The script in the file at `path`, named by its path
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            name: path.display().to_string(),
+            text: fs::read_to_string(path)?,
+        })
+    }
+
+    /// The script's statements, in order, without comment lines
+    pub fn statements(&self) -> Vec<Statement<'_>> {
+        let mut statements: Vec<Statement> = Vec::new();
+        // Brackets left open by the statement so far
+        let mut open = 0;
+        let mut offset = 0;
+        for line in self.text.split_inclusive('\n') {
+            let start = offset;
+            offset += line.len();
+            let content = line.trim_end();
+            let continues = open > 0 || line.starts_with(char::is_whitespace);
+            match statements.last_mut() {
+                _ if content.trim_start().is_empty() => continue,
+                Some(statement) if continues => {
+                    statement.text = &self.text[statement.offset..start + content.len()];
+                }
+                _ if content.starts_with('\\') => continue,
+                _ => {
+                    open = 0;
+                    statements.push(Statement {
+                        text: content,
+                        offset: start,
+                    });
+                }
+            }
+            open = (open + depth(content)).max(0);
+        }
+        statements
+    }
+}
+
+/// Whether source text is ready to evaluate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completeness {
+    /// It parses
+    Complete,
+    /// It leaves a bracket open, so more lines are to come
+    Incomplete,
+    /// It has a syntax error more lines cannot mend
+    Invalid,
+}
+
+/// Whether `source` is complete, for a console deciding whether Enter
+/// evaluates it or starts another line
+pub fn completeness(source: &str) -> Completeness {
+    if source.trim().is_empty() {
+        return Completeness::Complete;
+    }
+    let open: isize = source.lines().map(depth).sum();
+    if open > 0 {
+        return Completeness::Incomplete;
+    }
+    match crate::parser::parse_expression(source) {
+        Ok(tree) if !tree.root_node().has_error() => Completeness::Complete,
+        _ => Completeness::Invalid,
+    }
+}
+
+/// Brackets opened less those closed in `line`, leaving out those in strings
+/// and comments
+fn depth(line: &str) -> isize {
+    let mut depth = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '\\' => break,
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// `load "path"` or `` load `path ``: evaluate the script at `path`, giving
+/// the value of its last statement
+pub fn load(apply: &mut dyn Apply, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let path = match &args[0] {
+        Value::Symbol(name) => Some(name.to_string()),
+        other => other.as_string(),
+    }
+    .ok_or_else(|| {
+        EvalError::new(
+            EvalErrorKind::Type(format!(
+                "load expects a path as a string or symbol, got {}",
+                args[0].type_name()
+            )),
+            node,
+        )
+    })?;
+    let script = Script::read(Path::new(&path)).map_err(|e| {
+        EvalError::new(
+            EvalErrorKind::Other(format!("load: cannot read {}: {}", path, e)),
+            node,
+        )
+    })?;
+    apply.run(&script, node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Texts of statements, with the lines they start on
+    type Lines = Vec<(String, usize)>;
+
+    /// The statements of a script of `text`
+    fn statements(text: &str) -> Lines {
+        let script = Script {
+            name: "test.wz".to_string(),
+            text: text.to_string(),
+        };
+        (script.statements().iter())
+            .map(|s| {
+                let line = text[..s.offset].matches('\n').count() + 1;
+                (s.text.to_string(), line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_completeness() {
+        assert_eq!(completeness("1+2"), Completeness::Complete);
+        assert_eq!(completeness(""), Completeness::Complete);
+        assert_eq!(completeness("f: {[x]\n  y: x*2;"), Completeness::Incomplete);
+        assert_eq!(completeness("g: {[s] \"{\""), Completeness::Incomplete);
+        assert_eq!(completeness("(1;2 \\ a comment)"), Completeness::Incomplete);
+        assert_eq!(completeness("1+*2"), Completeness::Invalid);
+        assert_eq!(completeness("(1+2))"), Completeness::Invalid);
+    }
+
+    #[test]
+    fn test_a_statement_per_line() {
+        let found = statements("a: 1\n\n\\ a comment\nb: a+1\r\nb\n");
+        let expected = [("a: 1", 1), ("b: a+1", 4), ("b", 5)];
+        assert_eq!(found, expected.map(|(text, line)| (text.to_string(), line)));
+    }
+
+    #[test]
+    fn test_statements_continue_onto_indented_lines_and_open_brackets() {
+        let found = statements("f: {[x]\n  y: x*2;\n\n  y+1}\ng: {[s] \"{\"}\nh: (1;\n2)\nf[1]");
+        let expected = [
+            ("f: {[x]\n  y: x*2;\n\n  y+1}", 1),
+            ("g: {[s] \"{\"}", 5),
+            ("h: (1;\n2)", 6),
+            ("f[1]", 8),
+        ];
+        assert_eq!(found, expected.map(|(text, line)| (text.to_string(), line)));
+    }
+}
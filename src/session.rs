@@ -0,0 +1,454 @@
+//! Embedding API
+//!
+//! [`Session`] hosts the language inside a Rust application: it owns an
+//! evaluator and a persistent environment, evaluates source text to typed
+//! [`Value`]s, and lets the host bind values and native functions by name
+//! without touching the parser or environment internals.
+//!
+//! ```
+//! use wabznasm::{Session, Value};
+//!
+//! let mut session = Session::new();
+//! session.define("twice", 1, |args| match args[0] {
+//!     Value::Integer(n) => Ok(Value::Integer(n * 2)),
+//!     _ => Err("twice expects an integer".into()),
+//! });
+//! session.set("x", 20);
+//! assert_eq!(session.eval("twice[x] + 2").unwrap(), Value::Integer(42));
+//! ```
+
+use crate::arithmetic::OverflowMode;
+use crate::builtins::{self, NativeFunction};
+use crate::cancel::CancelToken;
+use crate::completion::{self, Name};
+use crate::environment::{Environment, Value};
+use crate::errors::EvalError;
+use crate::evaluator::{Evaluator, EvaluatorConfig};
+use crate::explorer::{self, ExploreResult, Variable};
+use crate::format::Printer;
+use crate::journal::Journal;
+use crate::metrics::ExecutionStats;
+use crate::parser::{parse_expression, query_expression};
+use crate::plugin::{self, Plugin, PluginError, SharedPlugin};
+use crate::script::Script;
+use crate::warnings::Warning;
+use miette::Report;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Values of several evaluations, or the error that stopped them
+type Results = Result<Vec<Value>, Report>;
+
+/// Names of functions with their arities
+type Functions = Vec<(String, Option<usize>)>;
+
+/// Bindings by their names within a namespace
+type Members<'a> = Vec<(String, &'a Value)>;
+
+/// An evaluation session with its own bindings
+#[derive(Default)]
+pub struct Session {
+    evaluator: Evaluator,
+    environment: Environment,
+    /// Compiled-in plugins available to [`Session::load_plugin`]
+    available: Vec<SharedPlugin>,
+    /// Names or paths of the plugins loaded so far
+    loaded: Vec<String>,
+    /// Cost of the most recent evaluation
+    stats: ExecutionStats,
+    /// Where successfully evaluated input is journaled, if anywhere
+    journal: Option<Journal>,
+    /// How [`Session::display`] lays values out
+    printer: Printer,
+    /// How long each evaluation may run, if limited
+    timeout: Option<Duration>,
+}
+
+impl Session {
+    /// Create a session with no bindings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach a database root for the `.db` builtins
+    pub fn set_database<P: Into<PathBuf>>(&mut self, root: P) {
+        self.evaluator.set_database(root);
+    }
+
+    /// Open the attached database read-only
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.evaluator.set_read_only(read_only);
+    }
+
+    /// Choose what integer arithmetic does when a result overflows
+    pub fn set_overflow_mode(&mut self, mode: OverflowMode) {
+        self.evaluator.set_overflow_mode(mode);
+    }
+
+    /// What integer arithmetic does when a result overflows
+    pub fn overflow_mode(&self) -> OverflowMode {
+        self.evaluator.overflow_mode()
+    }
+
+    /// Change the evaluator's settings, such as parallel execution
+    pub fn set_config(&mut self, config: EvaluatorConfig) {
+        self.evaluator.set_config(config);
+    }
+
+    /// The evaluator's settings
+    pub fn config(&self) -> EvaluatorConfig {
+        self.evaluator.config()
+    }
+
+    /// Stop each evaluation that runs longer than `timeout`, or let them
+    /// run on with `None`, as by default
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// How long each evaluation may run, if limited
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Fail evaluations computing a value of more than `limit` bytes, or
+    /// allow any size with `None`, as by default; see [`crate::memory`]
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.evaluator.set_memory_limit(limit);
+    }
+
+    /// Bytes any one value computed may take, if limited
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.evaluator.memory_limit()
+    }
+
+    /// Warnings about the most recent input evaluated
+    pub fn warnings(&self) -> &[Warning] {
+        self.evaluator.warnings()
+    }
+
+    /// A token that stops the evaluation in progress when cancelled, from
+    /// any thread; see [`crate::cancel`]
+    pub fn cancel_token(&self) -> CancelToken {
+        self.evaluator.cancel_token()
+    }
+
+    /// Switch to the namespace `namespace`, such as `.mylib`, or back to the
+    /// root with `.`
+    pub fn set_namespace(&mut self, namespace: &str) -> Result<(), String> {
+        self.evaluator.set_namespace(namespace)
+    }
+
+    /// The current namespace, or `.` at the root
+    pub fn namespace(&self) -> &str {
+        self.evaluator.namespace()
+    }
+
+    /// Evaluate `source`, keeping any assignments it makes
+    pub fn eval(&mut self, source: &str) -> Result<Value, Report> {
+        let start = Instant::now();
+        self.evaluator.take_warnings();
+        let tree = parse_expression(source)?;
+        // Report syntax errors with their location before evaluating
+        query_expression(&tree, source, |_, _| Ok(()))?;
+        let (root, env) = (tree.root_node(), &mut self.environment);
+        let result = match self.timeout {
+            Some(timeout) => self
+                .evaluator
+                .eval_with_deadline(root, source, env, start + timeout),
+            None => self.evaluator.eval_with_env(root, source, env),
+        };
+        self.stats = ExecutionStats {
+            elapsed: start.elapsed(),
+            rows_scanned: self.evaluator.take_rows_scanned(),
+            rows_returned: result.as_ref().map_or(0, ExecutionStats::rows_in),
+            peak_bytes: self.evaluator.take_peak_memory(),
+            allocated_bytes: self.evaluator.take_allocated_memory(),
+        };
+        let value = result.map_err(|e| Report::new(e.with_source(source)))?;
+        self.record(source)?;
+        Ok(value)
+    }
+
+    /// Evaluate the script at `path` statement by statement, as
+    /// `load "path"` does; see [`crate::script`]
+    pub fn load(&mut self, path: &Path) -> Result<Value, Report> {
+        let path = Value::string(&path.display().to_string());
+        self.eval(&format!("load {}", self.format(&path)))
+    }
+
+    /// Evaluate the statements of `script` one after another, as
+    /// [`Session::eval`] does each, stopping at the first that fails; see
+    /// [`crate::script`]
+    pub fn eval_script(&mut self, script: &Script) -> Result<Value, Report> {
+        let mut last = Value::List(Vec::new());
+        for statement in script.statements() {
+            let text = statement.text;
+            last = self
+                .eval(text)
+                .map_err(|report| match report.downcast::<EvalError>() {
+                    Ok(e) => {
+                        Report::new(e.in_script(text, statement.offset, &script.name, &script.text))
+                    }
+                    Err(report) => report,
+                })?;
+        }
+        Ok(last)
+    }
+
+    /// Evaluate `source` as [`Session::eval`] does and return what it cost,
+    /// including with `memory` the bytes the largest value computed took and
+    /// an estimate of the bytes allocated, as `\ts` reports
+    pub fn time(&mut self, source: &str, memory: bool) -> Result<ExecutionStats, Report> {
+        if memory {
+            self.evaluator.track_memory();
+        }
+        let result = self.eval(source);
+        // A syntax error stops evaluation before the stats are taken
+        self.evaluator.take_peak_memory();
+        self.evaluator.take_allocated_memory();
+        result.map(|_| self.stats)
+    }
+
+    /// Evaluate each of `sources` in turn, as [`Session::eval`] does,
+    /// stopping at the first that fails
+    pub fn eval_all<'a>(&mut self, sources: impl IntoIterator<Item = &'a str>) -> Results {
+        sources
+            .into_iter()
+            .map(|source| self.eval(source))
+            .collect()
+    }
+
+    /// Journal input that changed the session to `journal` from now on
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+    }
+
+    /// Append `entry` to the journal, if there is one
+    ///
+    /// [`Session::eval`] records its input itself; front ends record their
+    /// own state-changing commands with this.
+    pub fn record(&mut self, entry: &str) -> Result<(), Report> {
+        if let Some(journal) = &mut self.journal {
+            journal.record(entry).map_err(|e| {
+                miette::miette!("Failed to journal to {}: {}", journal.path().display(), e)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Cost of the most recent call to [`Session::eval`]
+    pub fn stats(&self) -> ExecutionStats {
+        self.stats
+    }
+
+    /// Value bound to `name`, if any
+    pub fn get(&self, name: &str) -> Option<Value> {
+        // A name that was never interned cannot be bound
+        let name = self.evaluator.interner().get(name)?;
+        self.environment.lookup_interned(name).cloned()
+    }
+
+    /// Bind `value` to `name`
+    pub fn set(&mut self, name: &str, value: impl Into<Value>) {
+        let name = self.evaluator.intern(name);
+        self.environment.define_interned(name, value.into());
+    }
+
+    /// Bind a native function of `arity` arguments to `name`
+    ///
+    /// The closure's error message is raised as an evaluation error.
+    pub fn define<F>(&mut self, name: &str, arity: usize, func: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.set(name, NativeFunction::new(name, arity, func));
+    }
+
+    /// Bind a native function under its own name, keeping its declared
+    /// parameters and documentation for [`Session::help`]
+    ///
+    /// ```
+    /// use wabznasm::{Session, Value, builtins::NativeFunction};
+    ///
+    /// let mut session = Session::new();
+    /// session.register(
+    ///     NativeFunction::new("sum", 1, |args| {
+    ///         let total = args[0].as_list().unwrap().iter().filter_map(Value::as_integer).sum();
+    ///         Ok(Value::Integer(total))
+    ///     })
+    ///     .param("xs", "list")
+    ///     .doc("Sum of a list of integers"),
+    /// );
+    /// assert_eq!(session.eval("sum 1 2 3").unwrap(), Value::Integer(6));
+    /// assert_eq!(session.help("sum").unwrap(), "sum[xs:list]\nSum of a list of integers");
+    /// ```
+    pub fn register(&mut self, function: NativeFunction) {
+        let name = function.name.clone();
+        self.set(&name, function);
+    }
+
+    /// Signature and description of the function bound to `name`
+    ///
+    /// Looks in the session's bindings first, then the builtins.
+    ///
+    /// A function with a docstring is described like a native function, by
+    /// its name and parameters followed by the docstring.
+    pub fn help(&self, name: &str) -> Option<String> {
+        let value = self
+            .get(name)
+            .or_else(|| builtins::lookup(name).map(Value::Builtin))?;
+        // A memoized function is described by the function it caches
+        let value = match value {
+            Value::Memo { function, .. } => *function,
+            other => other,
+        };
+        let interner = self.evaluator.interner();
+        match &value {
+            Value::Function {
+                params,
+                doc: Some(doc),
+                ..
+            } => {
+                let params: Vec<&str> = params.iter().map(|p| interner.resolve(p)).collect();
+                Some(format!(
+                    "{}[{}]\n{}",
+                    name,
+                    params.join(";"),
+                    interner.resolve(doc)
+                ))
+            }
+            other => other.help(interner),
+        }
+    }
+
+    /// Make a compiled-in plugin loadable by name
+    pub fn add_plugin(&mut self, plugin: impl Plugin + 'static) {
+        self.available.push(Arc::new(plugin));
+    }
+
+    /// Load the plugin `spec` into this session
+    ///
+    /// `spec` names a plugin added with [`Session::add_plugin`] or is the
+    /// path of a dynamic plugin library.
+    pub fn load_plugin(&mut self, spec: &str) -> Result<(), PluginError> {
+        if let Some(found) = self.available.iter().find(|p| p.name() == spec).cloned() {
+            found.register(self);
+        } else if Path::new(spec).exists() {
+            plugin::open(Path::new(spec))?(self);
+        } else {
+            return Err(PluginError::NotFound(spec.to_string()));
+        }
+        if !self.loaded.iter().any(|name| name == spec) {
+            self.loaded.push(spec.to_string());
+        }
+        Ok(())
+    }
+
+    /// Names or paths of the plugins loaded into this session
+    pub fn loaded_plugins(&self) -> &[String] {
+        &self.loaded
+    }
+
+    /// Source text defining the function bound to `name`, as
+    /// `name: {[x] ...}`, to be edited and evaluated again
+    pub fn definition(&self, name: &str) -> Result<String, String> {
+        match self.get(name) {
+            Some(function @ Value::Function { .. }) => {
+                Ok(format!("{}: {}", name, self.format(&function)))
+            }
+            Some(other) if other.is_function() => Err(format!("{} has no source to edit", name)),
+            Some(other) => Err(format!("{} is {}, not a function", name, other.type_name())),
+            None if builtins::lookup(name).is_some() => Err(format!(
+                "{} is a builtin, which has no source to edit",
+                name
+            )),
+            None => Err(format!("Undefined function: {}", name)),
+        }
+    }
+
+    /// The session's bindings, in name order, for a variable explorer
+    pub fn variables(&self) -> Vec<Variable> {
+        explorer::variables(&self.environment, self.evaluator.interner())
+    }
+
+    /// The names bound in the session and the builtins' names, to complete
+    /// input from; see [`crate::completion`]
+    pub fn names(&self) -> Vec<Name> {
+        completion::names(&self.environment, self.evaluator.interner())
+    }
+
+    /// Names of the variables in `namespace`, or the current namespace, in
+    /// name order: the values bound there that are not functions
+    pub fn variable_names(&self, namespace: Option<&str>) -> Vec<String> {
+        self.members(namespace)
+            .into_iter()
+            .filter(|(_, value)| !value.is_function())
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Names and arities of the functions in `namespace`, or the current
+    /// namespace, in name order
+    pub fn functions(&self, namespace: Option<&str>) -> Functions {
+        self.members(namespace)
+            .into_iter()
+            .filter(|(_, value)| value.is_function())
+            .map(|(name, value)| (name, value.arity()))
+            .collect()
+    }
+
+    /// The bindings directly in `namespace`, by their names within it: at
+    /// the root `.` the undotted names, and in `.mylib` those of the form
+    /// `.mylib.name`
+    fn members(&self, namespace: Option<&str>) -> Members<'_> {
+        let namespace = namespace.unwrap_or_else(|| self.namespace());
+        let prefix = match namespace {
+            "." => String::new(),
+            _ => format!("{}.", namespace),
+        };
+        let interner = self.evaluator.interner();
+        let mut members: Vec<_> = (self.environment.local_names_interned().into_iter())
+            .filter_map(|key| {
+                let member = interner.resolve(&key).strip_prefix(&prefix)?;
+                let value = self.environment.lookup_interned(key)?;
+                (!member.is_empty() && !member.contains('.')).then(|| (member.to_string(), value))
+            })
+            .collect();
+        members.sort_by(|a, b| a.0.cmp(&b.0));
+        members
+    }
+
+    /// The parts of the binding at `path`, or the bindings themselves for an
+    /// empty path; see [`explorer::explore`]
+    pub fn explore(&self, path: &[&str]) -> ExploreResult {
+        explorer::explore(&self.environment, path, self.evaluator.interner())
+    }
+
+    /// Render `value` in wabznasm syntax
+    pub fn format(&self, value: &Value) -> String {
+        value.format(self.evaluator.interner())
+    }
+
+    /// Render `value` for display, laid out to fit the console by the
+    /// session's [`Printer`]
+    pub fn display(&self, value: &Value) -> String {
+        self.printer.render(value, self.evaluator.interner())
+    }
+
+    /// Render `value` for display in full, however large; for a pager
+    pub fn display_in_full(&self, value: &Value) -> String {
+        Printer::unlimited().render(value, self.evaluator.interner())
+    }
+
+    /// Lay values out for a console of this size in [`Session::display`]
+    pub fn set_printer(&mut self, printer: Printer) {
+        self.printer = printer;
+    }
+
+    /// How [`Session::display`] lays values out
+    pub fn printer(&self) -> Printer {
+        self.printer
+    }
+}
@@ -0,0 +1,289 @@
+//! String builtins: search, replace, case, trimming, splitting and joining
+//!
+//! A string is a list of characters, so `count`, `reverse`, `#` and indexing
+//! already apply to it; these are the operations that need to see the text
+//! as a whole. A char atom such as `","` serves as a one-character string.
+//! Given a list of strings, such as a column read from a CSV file, each
+//! builtin applies to every string in it:
+//!
+//! ```text
+//! update name: lower trim name from people
+//! vs[",";"a,b,c"]          / ("a";"b";"c")
+//! sv["-";("2024";"01")]    / "2024-01"
+//! format["{} traded {}";(`AAPL;100)]   / "AAPL traded 100"
+//! ```
+
+use crate::builtins::{Apply, Context};
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use lasso::Rodeo;
+use tree_sitter::Node;
+
+/// The text of a string or char atom
+fn text(value: &Value, name: &str, node: Node) -> Result<String, EvalError> {
+    match value {
+        Value::Char(c) => Ok(c.to_string()),
+        other => other.as_string().ok_or_else(|| not_text(name, other, node)),
+    }
+}
+
+fn not_text(name: &str, value: &Value, node: Node) -> EvalError {
+    EvalError::new(
+        EvalErrorKind::Type(format!(
+            "{} expects a string, got {}",
+            name,
+            value.type_name()
+        )),
+        node,
+    )
+}
+
+/// What a builtin makes of the text of one string
+type StringFn<'a> = &'a dyn Fn(&str) -> Value;
+
+/// A change of case
+type Convert = fn(&str) -> String;
+
+/// `f` applied to the text of `value`, or to each string of a list of them
+fn each_string(value: &Value, name: &str, node: Node, f: StringFn) -> Result<Value, EvalError> {
+    match value {
+        Value::Char(_) => Ok(f(&text(value, name, node)?)),
+        Value::List(items) => match value.as_string() {
+            Some(text) => Ok(f(&text)),
+            None => items
+                .iter()
+                .map(|item| each_string(item, name, node, f))
+                .collect::<Result<_, _>>()
+                .map(Value::List),
+        },
+        other => Err(not_text(name, other, node)),
+    }
+}
+
+/// `value` with `convert` applied to every character and symbol in it
+fn each_char(value: &Value, convert: Convert) -> Value {
+    match value {
+        Value::Char(c) => Value::Char(convert(&c.to_string()).chars().next().unwrap_or(*c)),
+        Value::Symbol(s) => Value::Symbol(convert(s.as_str()).into()),
+        Value::List(items) => Value::List(items.iter().map(|v| each_char(v, convert)).collect()),
+        other => other.clone(),
+    }
+}
+
+/// `lower x`: `x` with its characters and symbols in lower case
+pub fn lower(_: &mut Context, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    Ok(each_char(&args[0], str::to_lowercase))
+}
+
+/// `upper x`: `x` with its characters and symbols in upper case
+pub fn upper(_: &mut Context, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    Ok(each_char(&args[0], str::to_uppercase))
+}
+
+/// `trim x`: the string `x` without leading or trailing whitespace
+pub fn trim(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    each_string(&args[0], "trim", node, &|text| Value::string(text.trim()))
+}
+
+/// `ltrim x`: the string `x` without leading whitespace
+pub fn ltrim(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    each_string(&args[0], "ltrim", node, &|text| {
+        Value::string(text.trim_start())
+    })
+}
+
+/// `rtrim x`: the string `x` without trailing whitespace
+pub fn rtrim(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    each_string(&args[0], "rtrim", node, &|text| {
+        Value::string(text.trim_end())
+    })
+}
+
+/// Character positions where `pattern` starts in `chars`, not overlapping
+fn find_all(chars: &[char], pattern: &[char]) -> Vec<usize> {
+    let mut found = Vec::new();
+    if pattern.is_empty() {
+        return found;
+    }
+    let mut i = 0;
+    while i + pattern.len() <= chars.len() {
+        if chars[i..].starts_with(pattern) {
+            found.push(i);
+            i += pattern.len();
+        } else {
+            i += 1;
+        }
+    }
+    found
+}
+
+/// `ss[s;p]`: the positions where `p` occurs in the string `s`
+pub fn ss(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let pattern: Vec<char> = text(&args[1], "ss", node)?.chars().collect();
+    each_string(&args[0], "ss", node, &|text| {
+        let chars: Vec<char> = text.chars().collect();
+        let found = find_all(&chars, &pattern);
+        Value::List(
+            found
+                .into_iter()
+                .map(|i| Value::Integer(i as i64))
+                .collect(),
+        )
+    })
+}
+
+/// `ssr[s;p;r]`: the string `s` with each occurrence of `p` replaced by `r`
+pub fn ssr(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let pattern: Vec<char> = text(&args[1], "ssr", node)?.chars().collect();
+    let replacement = text(&args[2], "ssr", node)?;
+    each_string(&args[0], "ssr", node, &|text| {
+        let chars: Vec<char> = text.chars().collect();
+        let mut replaced = String::new();
+        let mut from = 0;
+        for at in find_all(&chars, &pattern) {
+            replaced.extend(&chars[from..at]);
+            replaced.push_str(&replacement);
+            from = at + pattern.len();
+        }
+        replaced.extend(&chars[from..]);
+        Value::string(&replaced)
+    })
+}
+
+/// `vs[sep;s]`: the string `s` split into the strings between each `sep`
+pub fn vs(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let separator = text(&args[0], "vs", node)?;
+    if separator.is_empty() {
+        return Err(EvalError::new(
+            EvalErrorKind::Domain("vs: cannot split on an empty string".into()),
+            node,
+        ));
+    }
+    each_string(&args[1], "vs", node, &|text| {
+        Value::List(text.split(separator.as_str()).map(Value::string).collect())
+    })
+}
+
+/// `sv[sep;x]`: the strings of the list `x` joined into one, with `sep`
+/// between each
+pub fn sv(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let separator = text(&args[0], "sv", node)?;
+    let Value::List(items) = &args[1] else {
+        return Err(EvalError::new(
+            EvalErrorKind::Type(format!(
+                "sv expects a list of strings, got {}",
+                args[1].type_name()
+            )),
+            node,
+        ));
+    };
+    let parts = items
+        .iter()
+        .map(|item| text(item, "sv", node))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::string(&parts.join(&separator)))
+}
+
+/// Text of `value` for a message: a string or character as it is, a symbol
+/// without its backtick, and anything else as it is formatted
+fn plain_text(value: &Value, interner: &Rodeo) -> String {
+    match value {
+        Value::Symbol(name) => name.to_string(),
+        Value::Char(c) => c.to_string(),
+        other => other
+            .as_string()
+            .filter(|text| !text.is_empty())
+            .unwrap_or_else(|| other.format(interner)),
+    }
+}
+
+/// `string x`: the text of an atom as a string; lists and the values of
+/// dicts give a string for each item
+pub fn string(apply: &mut dyn Apply, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    Ok(to_string(&args[0], apply.interner()))
+}
+
+fn to_string(value: &Value, interner: &Rodeo) -> Value {
+    match value {
+        Value::List(items) => Value::List(items.iter().map(|v| to_string(v, interner)).collect()),
+        Value::Dict { keys, values } => Value::Dict {
+            keys: keys.clone(),
+            values: values.iter().map(|v| to_string(v, interner)).collect(),
+        },
+        Value::Char(c) => Value::string(&c.to_string()),
+        other => Value::string(&plain_text(other, interner)),
+    }
+}
+
+/// A piece of a `format` template
+enum Piece {
+    Literal(char),
+    Placeholder,
+}
+
+/// The pieces of a template, or why it is malformed
+type Template = Result<Vec<Piece>, String>;
+
+/// The pieces of `template`: `{}` marks a placeholder, and `{{` and `}}`
+/// stand for literal braces
+fn pieces(template: &str) -> Template {
+    let mut pieces = Vec::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('}')) => {
+                chars.next();
+                pieces.push(Piece::Placeholder);
+            }
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                pieces.push(Piece::Literal(c));
+            }
+            ('{' | '}', _) => return Err(format!("format: unmatched {} in template", c)),
+            _ => pieces.push(Piece::Literal(c)),
+        }
+    }
+    Ok(pieces)
+}
+
+/// `format[template;x]`: `template` with each `{}` replaced by the text of
+/// a value: `x` itself for a single placeholder, otherwise the items of the
+/// list `x` in turn
+pub fn format(apply: &mut dyn Apply, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let template = text(&args[0], "format", node)?;
+    let pieces = pieces(&template)
+        .map_err(|message| EvalError::new(EvalErrorKind::Domain(message), node))?;
+    let wanted = pieces
+        .iter()
+        .filter(|piece| matches!(piece, Piece::Placeholder))
+        .count();
+    let values = match &args[1] {
+        single if wanted == 1 => std::slice::from_ref(single),
+        Value::List(items) if items.len() == wanted => items.as_slice(),
+        other => {
+            let given = match other {
+                Value::List(items) => items.len(),
+                _ => 1,
+            };
+            return Err(EvalError::new(
+                EvalErrorKind::LengthMismatch(format!(
+                    "format: template has {} placeholders but {} values were given",
+                    wanted, given
+                )),
+                node,
+            ));
+        }
+    };
+    let mut values = values.iter();
+    let mut formatted = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Literal(c) => formatted.push(c),
+            Piece::Placeholder => {
+                let value = values.next().expect("one value per placeholder");
+                formatted.push_str(&plain_text(value, apply.interner()));
+            }
+        }
+    }
+    Ok(Value::string(&formatted))
+}
@@ -0,0 +1,202 @@
+//! Structural primitives: builtins that rearrange lists, dicts and tables
+//!
+//! Most of these do not look at the items they move, so they apply to any
+//! list. `distinct` and `group` compare items by value, as the `by` clause
+//! of `select` groups rows. An atom stands for a list of itself where that makes sense, as in q:
+//! `raze 5` is `,5` and `first 5` is `5`.
+//!
+//! ```text
+//! raze (1 2;3;4 5)      / 1 2 3 4 5
+//! flip (1 2 3;4 5 6)    / (1 4;2 5;3 6)
+//! first trades          / the first row, as a dict
+//! group `a`b`a          / `a`b!(0 2;,1)
+//! ```
+
+use crate::builtins::Context;
+use crate::environment::{ListItems, NULL_INTEGER, Value};
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::table::{self, Table};
+use tree_sitter::Node;
+
+/// `enlist x`: the list of `x` alone; a dict becomes a one-row table
+pub fn enlist(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    match &args[0] {
+        Value::Dict { keys, values } => {
+            let columns: Vec<Value> = values
+                .iter()
+                .map(|v| Value::List(vec![v.clone()]))
+                .collect();
+            Table::from_dict(keys, &columns)
+                .map(Value::Table)
+                .map_err(|message| EvalError::new(EvalErrorKind::Type(message), node))
+        }
+        other => Ok(Value::List(vec![other.clone()])),
+    }
+}
+
+/// `flip x`: the table whose columns are the dict `x`, the dict of a
+/// table's columns, or the transpose of a list of equal-length lists
+pub fn flip(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    match &args[0] {
+        Value::Dict { keys, values } => Table::from_dict(keys, values)
+            .map(Value::Table)
+            .map_err(|message| EvalError::new(EvalErrorKind::Type(message), node)),
+        Value::Table(table) => Ok(table.to_dict()),
+        Value::List(rows) => transpose(rows).map(Value::List).ok_or_else(|| {
+            EvalError::new(
+                EvalErrorKind::Type("flip expects lists of equal length".into()),
+                node,
+            )
+        }),
+        other => Err(EvalError::new(
+            EvalErrorKind::Type(format!(
+                "flip expects a dict, table or list of lists, got {}",
+                other.type_name()
+            )),
+            node,
+        )),
+    }
+}
+
+/// Columns of `rows`, or `None` unless every row is a list of one length
+pub(crate) fn transpose(rows: &[Value]) -> Option<Vec<Value>> {
+    let rows: Vec<&ListItems> = rows.iter().map(Value::as_list).collect::<Option<_>>()?;
+    let width = rows.first().map_or(0, |row| row.len());
+    if rows.iter().any(|row| row.len() != width) {
+        return None;
+    }
+    Some(
+        (0..width)
+            .map(|i| Value::List(rows.iter().map(|row| row[i].clone()).collect()))
+            .collect(),
+    )
+}
+
+/// `raze x`: the items of `x` joined one level down, so a list of lists
+/// becomes one list; the values of a dict are joined likewise
+pub fn raze(_: &mut Context, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    let items = match &args[0] {
+        Value::List(items) => items,
+        Value::Dict { values, .. } => values,
+        atom => return Ok(Value::List(vec![atom.clone()])),
+    };
+    let mut razed = Vec::new();
+    for item in items {
+        match item {
+            Value::List(inner) => razed.extend(inner.iter().cloned()),
+            atom => razed.push(atom.clone()),
+        }
+    }
+    Ok(Value::List(razed))
+}
+
+/// `reverse x`: the items of a list, the entries of a dict or the rows of a
+/// table in reverse order; an atom is itself
+pub fn reverse(_: &mut Context, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    let reversed = |items: &[Value]| items.iter().rev().cloned().collect();
+    Ok(match &args[0] {
+        Value::List(items) => Value::List(reversed(items)),
+        Value::Dict { keys, values } => Value::Dict {
+            keys: reversed(keys),
+            values: reversed(values),
+        },
+        Value::Table(table) => {
+            let rows: Vec<usize> = (0..table.len()).rev().collect();
+            Value::Table(
+                table
+                    .select_rows(&rows)
+                    .expect("reversed rows are within the table"),
+            )
+        }
+        atom => atom.clone(),
+    })
+}
+
+/// `first x`: the first item of a list, value of a dict or row of a table;
+/// an atom is itself
+pub fn first(_: &mut Context, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    Ok(end(&args[0], false))
+}
+
+/// `last x`: the last item of a list, value of a dict or row of a table;
+/// an atom is itself
+pub fn last(_: &mut Context, args: &[Value], _: Node) -> Result<Value, EvalError> {
+    Ok(end(&args[0], true))
+}
+
+/// The first or `last` item of `value`, or a null when it has no items:
+/// `0N`, or a row of nulls for a table
+fn end(value: &Value, last: bool) -> Value {
+    let at = |len: usize| if last { len.saturating_sub(1) } else { 0 };
+    let items = match value {
+        Value::List(items) => items,
+        Value::Dict { values, .. } => values,
+        Value::Table(table) => {
+            return table
+                .row(at(table.len()))
+                .unwrap_or_else(|| table.null_row());
+        }
+        atom => return atom.clone(),
+    };
+    items
+        .get(at(items.len()))
+        .cloned()
+        .unwrap_or(Value::Integer(NULL_INTEGER))
+}
+
+/// `distinct x`: the items of a list, or rows of a table, without repeats,
+/// in order of first appearance
+pub fn distinct(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    match &args[0] {
+        Value::List(items) => Ok(Value::List(
+            table::group_rows(&[items])
+                .into_iter()
+                .map(|group| items[group[0]].clone())
+                .collect(),
+        )),
+        Value::Table(t) => {
+            let columns: Vec<&ListItems> = t.columns().iter().map(Vec::as_slice).collect();
+            let rows: Vec<usize> = table::group_rows(&columns)
+                .into_iter()
+                .map(|group| group[0])
+                .collect();
+            Ok(Value::Table(
+                t.select_rows(&rows)
+                    .expect("grouped rows are within the table"),
+            ))
+        }
+        other => Err(EvalError::new(
+            EvalErrorKind::Type(format!(
+                "distinct expects a list or table, got {}",
+                other.type_name()
+            )),
+            node,
+        )),
+    }
+}
+
+/// `group x`: a dict from each distinct item of a list to the indices where
+/// it occurs, in order of first appearance
+pub fn group(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let Value::List(items) = &args[0] else {
+        return Err(EvalError::new(
+            EvalErrorKind::Type(format!("group expects a list, got {}", args[0].type_name())),
+            node,
+        ));
+    };
+    let groups = table::group_rows(&[items]);
+    Ok(Value::Dict {
+        keys: groups.iter().map(|group| items[group[0]].clone()).collect(),
+        values: groups
+            .into_iter()
+            .map(|group| {
+                Value::List(
+                    group
+                        .into_iter()
+                        .map(|i| Value::Integer(i as i64))
+                        .collect(),
+                )
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,71 @@
+//! Suggestions for misspelt names: `incremnt` is probably `increment`
+//!
+//! A name nothing is bound to is compared with the names in scope and the
+//! builtins by edit distance, and the closest near enough is offered as
+//! [`EvalError::suggestion`](crate::errors::EvalError::suggestion).
+
+/// The candidate nearest `name`, if any is near enough to be a likely
+/// misspelling: within a third of its length in edits, and at least one
+///
+/// Ties go to the first candidate. Names in namespaces, such as `.z.s`, are
+/// only suggested for names that are themselves dotted.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    let dotted = name.contains('.');
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name && (dotted || !candidate.contains('.')))
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= limit)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edits (insertions, deletions, substitutions or swaps of adjacent
+/// characters) that turn `a` into `b`
+pub fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Three rows of the table: two back, the last, and the one being filled
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut last: Vec<usize> = (0..=b.len()).collect();
+    let mut row = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        row[0] = i;
+        for j in 1..=b.len() {
+            let substitution = last[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            row[j] = substitution.min(last[j] + 1).min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before, &mut last);
+        std::mem::swap(&mut last, &mut row);
+    }
+    last[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("increment", "increment"), 0);
+        assert_eq!(distance("incremnt", "increment"), 1);
+        assert_eq!(distance("cuont", "count"), 1);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_closest() {
+        let names = ["increment", "count", "sum", ".z.s"];
+        assert_eq!(closest("incremnt", names), Some("increment"));
+        assert_eq!(closest("cuont", names), Some("count"));
+        assert_eq!(closest("sun", names), Some("sum"));
+        assert_eq!(closest("total", names), None);
+        assert_eq!(closest("z.s", names), Some(".z.s"));
+        assert_eq!(closest("zs", names), None);
+    }
+}
@@ -0,0 +1,989 @@
+//! Tables: dictionaries of equal-length columns, flipped
+//!
+//! A table is built by flipping a dictionary whose keys are column names
+//! and whose values are the columns, as in q:
+//!
+//! ```text
+//! t: flip `sym`px!(`AAPL`MSFT;100 250)
+//! t`px      / the px column: 100 250
+//! t 0       / the first row: `sym`px!(`AAPL;100)
+//! flip t    / back to the dictionary of columns
+//! ```
+//!
+//! Flipping checks that every column has the same number of rows; an atom
+//! among list columns is repeated down its column.
+//!
+//! A keyed table, such as the result of `select ... by`, is a table whose
+//! leading columns are its key. It is written as a dictionary from the
+//! table of key columns to the table of the others:
+//!
+//! ```text
+//! (flip ,`sym!,`AAPL`MSFT)!(flip ,`px!,100 250)
+//! ```
+//!
+//! `xkey` keys a table by some of its columns. Indexing a keyed table looks
+//! up the row with that key, and `upsert` replaces the rows whose keys it
+//! is given and appends the others:
+//!
+//! ```text
+//! r: xkey[`sym; t]
+//! r`MSFT                       / ,`px!,250
+//! r: upsert[r; `sym`px!(`MSFT;260)]
+//! ```
+//!
+//! A column flipped or updated in whole from a list with an attribute keeps
+//! it, so that `select` conditions on the column and `aj` on the table
+//! look its rows up through it; see [`crate::attributes`]. Anything that
+//! changes the column's rows leaves it plain.
+//!
+//! ```text
+//! t: update `g#sym from t
+//! select from t where sym=`MSFT   / the rows of `MSFT, without a scan
+//! ```
+
+use crate::attributes::{Attribute, Attributed, Groups};
+use crate::environment::{ListItems, NULL_INTEGER, Value};
+use crate::interning::Symbol;
+use lasso::Rodeo;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+/// Values of one column, a row each
+pub type Column = Vec<Value>;
+
+/// Rows of rendered cells, as laid out by [`layout`]
+pub type Cells = [Vec<String>];
+
+/// A column's attribute, with the column's items
+pub type ColumnAttribute = Arc<Attributed>;
+
+/// Columns with an attribute, by name
+type Attributes = Vec<(Symbol, ColumnAttribute)>;
+
+/// A table: named columns of equal length
+#[derive(Debug, Clone)]
+pub struct Table {
+    names: Box<[Symbol]>,
+    columns: Vec<Column>,
+    /// Number of leading columns that form the key; 0 for an unkeyed table
+    keys: usize,
+    /// Columns with an attribute, if any, kept out of line so that a table
+    /// is no bigger than a list in a [`Value`]
+    attributes: Option<Arc<Attributes>>,
+}
+
+/// Tables are equal when their columns are, as lists are whatever their
+/// attributes
+impl PartialEq for Table {
+    fn eq(&self, other: &Self) -> bool {
+        self.names == other.names && self.columns == other.columns && self.keys == other.keys
+    }
+}
+
+impl Table {
+    /// A table with the given columns, or an error if their lengths differ
+    /// or a name repeats
+    pub fn new(names: Vec<Symbol>, columns: Vec<Column>) -> Result<Self, String> {
+        if names.len() != columns.len() {
+            return Err(format!(
+                "{} column names for {} columns",
+                names.len(),
+                columns.len()
+            ));
+        }
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                return Err(format!("duplicate column `{}", name));
+            }
+        }
+        if let Some(first) = columns.first() {
+            for (name, column) in names.iter().zip(&columns) {
+                if column.len() != first.len() {
+                    return Err(format!(
+                        "column `{} has {} rows, but `{} has {}",
+                        name,
+                        column.len(),
+                        names[0],
+                        first.len()
+                    ));
+                }
+            }
+        }
+        Ok(Self {
+            names: names.into(),
+            columns,
+            keys: 0,
+            attributes: None,
+        })
+    }
+
+    /// The keyed table from a table of key columns to a table of values,
+    /// which must have as many rows
+    pub fn keyed(key: &Table, value: &Table) -> Result<Self, String> {
+        if key.len() != value.len() {
+            return Err(format!(
+                "key has {} rows, but value has {}",
+                key.len(),
+                value.len()
+            ));
+        }
+        let names = key.names.iter().chain(&value.names).copied().collect();
+        let columns = key.columns.iter().chain(&value.columns).cloned().collect();
+        let mut table = Self::new(names, columns)?;
+        table.keys = key.names.len();
+        table.set_attributes(
+            key.attributes()
+                .chain(value.attributes())
+                .cloned()
+                .collect(),
+        );
+        Ok(table)
+    }
+
+    /// Flip a dictionary of columns into a table
+    pub fn from_dict(keys: &[Value], values: &[Value]) -> Result<Self, String> {
+        let names = keys
+            .iter()
+            .map(|key| match key {
+                Value::Symbol(name) => Ok(*name),
+                other => Err(format!(
+                    "column names must be symbols, got {}",
+                    other.type_name()
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let rows = values
+            .iter()
+            .filter_map(|value| value.as_list().map(<[Value]>::len))
+            .next()
+            .ok_or("a table needs at least one list column")?;
+        let columns = values
+            .iter()
+            .map(|value| match value {
+                Value::List(items) => items.clone(),
+                Value::Attributed(list) => list.items().to_vec(),
+                atom => vec![atom.clone(); rows],
+            })
+            .collect();
+        let mut table = Self::new(names, columns)?;
+        for (name, value) in table.names.clone().into_iter().zip(values) {
+            if let Value::Attributed(list) = value {
+                table = table.with_attribute(name, list.clone());
+            }
+        }
+        Ok(table)
+    }
+
+    /// The table with the column `name`, which holds the items of `list`,
+    /// looked up through its attribute
+    pub(crate) fn with_attribute(mut self, name: Symbol, list: ColumnAttribute) -> Self {
+        let mut attributes: Attributes = self
+            .attributes()
+            .filter(|(n, _)| *n != name)
+            .cloned()
+            .collect();
+        attributes.push((name, list));
+        self.set_attributes(attributes);
+        self
+    }
+
+    /// The columns with an attribute, and their attributes
+    fn attributes(&self) -> impl Iterator<Item = &(Symbol, ColumnAttribute)> {
+        self.attributes
+            .iter()
+            .flat_map(|attributes| attributes.iter())
+    }
+
+    /// Give the columns `attributes`, holding none out of line when empty
+    fn set_attributes(&mut self, attributes: Attributes) {
+        self.attributes = (!attributes.is_empty()).then(|| Arc::new(attributes));
+    }
+
+    /// Keep the attributes of only the columns whose names pass `keep`
+    fn retain_attributes(&mut self, keep: impl Fn(&Symbol) -> bool) {
+        let kept = self
+            .attributes()
+            .filter(|(name, _)| keep(name))
+            .cloned()
+            .collect();
+        self.set_attributes(kept);
+    }
+
+    /// Append the column `name`, giving its position
+    fn add_column(&mut self, name: Symbol, column: Column) -> usize {
+        let mut names = std::mem::take(&mut self.names).into_vec();
+        names.push(name);
+        self.names = names.into();
+        self.columns.push(column);
+        self.names.len() - 1
+    }
+
+    /// The column `name` with its attribute, if it has one
+    pub fn attributed(&self, name: &str) -> Option<&ColumnAttribute> {
+        self.attributes()
+            .find(|(n, _)| *n == name)
+            .map(|(_, list)| list)
+    }
+
+    /// Column names, in order
+    pub fn names(&self) -> &[Symbol] {
+        &self.names
+    }
+
+    /// Number of key columns, 0 if the table is not keyed
+    pub fn keys(&self) -> usize {
+        self.keys
+    }
+
+    /// Whether the table has key columns
+    pub fn is_keyed(&self) -> bool {
+        self.keys > 0
+    }
+
+    /// The same columns, with none of them a key
+    pub fn unkeyed(&self) -> Self {
+        Self {
+            keys: 0,
+            ..self.clone()
+        }
+    }
+
+    /// The table of key columns and the table of the others
+    pub fn split_key(&self) -> (Self, Self) {
+        let part = |range: std::ops::Range<usize>| {
+            let names = &self.names[range.clone()];
+            let mut part = Self {
+                names: names.into(),
+                columns: self.columns[range].to_vec(),
+                keys: 0,
+                attributes: self.attributes.clone(),
+            };
+            part.retain_attributes(|name| names.contains(name));
+            part
+        };
+        (part(0..self.keys), part(self.keys..self.names.len()))
+    }
+
+    /// The same columns keyed by `names`, which move to the front in that
+    /// order; no names give an unkeyed table
+    pub fn xkey(&self, names: &[Symbol]) -> Result<Self, String> {
+        let mut order = Vec::with_capacity(self.names.len());
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                return Err(format!("duplicate key column `{}", name));
+            }
+            let position = self
+                .names
+                .iter()
+                .position(|n| n == name)
+                .ok_or_else(|| format!("No such column: `{}", name))?;
+            order.push(position);
+        }
+        let rest: Vec<usize> = (0..self.names.len())
+            .filter(|i| !order.contains(i))
+            .collect();
+        order.extend(rest);
+        Ok(Self {
+            names: order.iter().map(|&i| self.names[i]).collect(),
+            columns: order.iter().map(|&i| self.columns[i].clone()).collect(),
+            keys: names.len(),
+            attributes: self.attributes.clone(),
+        })
+    }
+
+    /// The first row whose key columns hold `key`, a value per key column
+    pub fn find_key(&self, key: &[Value]) -> Option<usize> {
+        if key.len() != self.keys {
+            return None;
+        }
+        (0..self.len()).find(|&row| {
+            self.columns[..self.keys]
+                .iter()
+                .zip(key)
+                .all(|(column, value)| column[row] == *value)
+        })
+    }
+
+    /// The value columns of the row keyed by `key`, as a dictionary, or
+    /// nulls if no row has that key
+    ///
+    /// A table with one key column is looked up by an atom; with several,
+    /// by a list with an item per key column.
+    pub fn lookup(&self, key: &Value) -> Result<Value, String> {
+        let key = match key {
+            Value::List(items) if self.keys > 1 => items.as_slice(),
+            atom => std::slice::from_ref(atom),
+        };
+        if key.len() != self.keys {
+            return Err(format!(
+                "key of {} values for a table keyed by {} columns",
+                key.len(),
+                self.keys
+            ));
+        }
+        let (_, value) = self.split_key();
+        Ok(match self.find_key(key) {
+            Some(row) => value.row(row).expect("found rows are within the table"),
+            None => value.null_row(),
+        })
+    }
+
+    /// The table with `rows` written in: rows whose key is already present
+    /// replace the columns they hold in that row, and the others, or every
+    /// row of an unkeyed table, are appended with nulls in columns they lack
+    pub fn upsert(&self, rows: &Table) -> Result<Self, String> {
+        let positions = rows
+            .names
+            .iter()
+            .map(|name| {
+                self.names
+                    .iter()
+                    .position(|n| n == name)
+                    .ok_or_else(|| format!("No such column: `{}", name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(key) = self.names[..self.keys]
+            .iter()
+            .find(|key| !rows.names.contains(key))
+        {
+            return Err(format!("upserted rows need key column `{}", key));
+        }
+
+        let mut table = self.clone();
+        table.attributes = None;
+        let mut index = table.key_index();
+        for row in 0..rows.len() {
+            let mut values = vec![None; table.names.len()];
+            for (&position, column) in positions.iter().zip(&rows.columns) {
+                values[position] = Some(column[row].clone());
+            }
+            let key: Vec<&Value> = values[..table.keys]
+                .iter()
+                .map(|value| value.as_ref().expect("key columns are present"))
+                .collect();
+            let existing = (table.keys > 0)
+                .then(|| table.find_key_in(&index, &key))
+                .flatten();
+            match existing {
+                Some(target) => {
+                    for (column, value) in table.columns.iter_mut().zip(values) {
+                        if let Some(value) = value {
+                            column[target] = value;
+                        }
+                    }
+                }
+                None => {
+                    let hash = hash_key(key);
+                    index.entry(hash).or_default().push(table.len());
+                    for (column, value) in table.columns.iter_mut().zip(values) {
+                        let value = value.unwrap_or_else(|| null_for(column));
+                        column.push(value);
+                    }
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    /// Rows by the hash of their key
+    fn key_index(&self) -> Buckets {
+        let mut index = Buckets::new();
+        for row in 0..self.len() {
+            let key = self.columns[..self.keys].iter().map(|column| &column[row]);
+            index.entry(hash_key(key)).or_default().push(row);
+        }
+        index
+    }
+
+    /// The row with key `key` among the rows of `index`, made by
+    /// [`Table::key_index`]
+    fn find_key_in(&self, index: &Buckets, key: &[&Value]) -> Option<usize> {
+        let candidates = index.get(&hash_key(key.iter().copied()))?;
+        candidates.iter().copied().find(|&row| {
+            self.columns[..self.keys]
+                .iter()
+                .zip(key)
+                .all(|(column, value)| column[row] == **value)
+        })
+    }
+
+    /// q's `lj`: every row, with the value columns of the row of `keyed`
+    /// whose key its columns hold
+    ///
+    /// Value columns new to this table are null in rows without a match;
+    /// columns it already has keep their values there.
+    pub fn left_join(&self, keyed: &Table) -> Result<Self, String> {
+        self.join(keyed, false)
+    }
+
+    /// q's `ij`: like [`Table::left_join`], but only the rows with a match
+    pub fn inner_join(&self, keyed: &Table) -> Result<Self, String> {
+        self.join(keyed, true)
+    }
+
+    fn join(&self, keyed: &Table, inner: bool) -> Result<Self, String> {
+        if !keyed.is_keyed() {
+            return Err("the right of a join must be a keyed table".into());
+        }
+        let key_columns = keyed.names[..keyed.keys]
+            .iter()
+            .map(|name| {
+                self.column(name)
+                    .ok_or_else(|| format!("No such column: `{}", name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let index = keyed.key_index();
+        let matches: Vec<Option<usize>> = (0..self.len())
+            .map(|row| {
+                let key: Vec<&Value> = key_columns.iter().map(|column| &column[row]).collect();
+                keyed.find_key_in(&index, &key)
+            })
+            .collect();
+        let rows: Vec<usize> = (0..self.len())
+            .filter(|&row| !inner || matches[row].is_some())
+            .collect();
+        let table = self
+            .select_rows(&rows)
+            .expect("joined rows are within the table");
+        let values = &keyed.names[keyed.keys..];
+        Ok(table.merge(keyed, values, &rows, &matches))
+    }
+
+    /// q's `aj`: every row, with the columns of the last row of `other`
+    /// that matches it in all of `columns` but the last and is at or before
+    /// it in the last, usually a time
+    ///
+    /// The rows of `other` are grouped by the leading columns and each group
+    /// is sorted by time, so each row is matched by a binary search within
+    /// its group. Columns are taken and nulled as in [`Table::left_join`].
+    ///
+    /// A single leading column of `other` that is grouped or unique gives
+    /// its groups outright, and a sorted time column leaves them in time
+    /// order already, so neither is worked out again.
+    pub fn as_of_join(&self, columns: &[Symbol], other: &Table) -> Result<Self, String> {
+        let Some((time, equal)) = columns.split_last() else {
+            return Err("aj needs at least a time column".into());
+        };
+        let column_pair = |name: &Symbol| match (self.column(name), other.column(name)) {
+            (Some(left), Some(right)) => Ok((left, right)),
+            _ => Err(format!("No such column: `{}", name)),
+        };
+        let (times, other_times) = column_pair(time)?;
+        let pairs = equal
+            .iter()
+            .map(column_pair)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let right: Vec<&ListItems> = pairs.iter().map(|&(_, right)| right).collect();
+        let indexed = match equal {
+            [name] => other
+                .attributed(name)
+                .filter(|list| list.attribute() != Attribute::Sorted),
+            _ => None,
+        };
+        let mut groups: Cow<Groups> = match indexed {
+            Some(list) => Cow::Borrowed(list.groups()),
+            // Matching on time alone, all of `other` is one group
+            None if right.is_empty() && !other.is_empty() => {
+                Cow::Owned(vec![(0..other.len()).collect()])
+            }
+            None => Cow::Owned(group_rows(&right)),
+        };
+        let time_sorted = other
+            .attributed(time)
+            .is_some_and(|list| list.attribute() == Attribute::Sorted);
+        if !time_sorted {
+            for group in groups.to_mut() {
+                group.sort_by(|a, b| {
+                    other_times[*a]
+                        .compare(&other_times[*b])
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+            }
+        }
+        let mut index = Buckets::new();
+        if indexed.is_none() {
+            for (i, group) in groups.iter().enumerate() {
+                let key = right.iter().map(|column| &column[group[0]]);
+                index.entry(hash_key(key)).or_default().push(i);
+            }
+        }
+
+        let matches: Vec<Option<usize>> = (0..self.len())
+            .map(|row| {
+                let group = match indexed {
+                    Some(list) => list.group_of(&pairs[0].0[row])?,
+                    None => {
+                        let key = pairs.iter().map(|&(left, _)| &left[row]);
+                        *index.get(&hash_key(key))?.iter().find(|&&g| {
+                            let first = groups[g][0];
+                            pairs.iter().all(|(left, right)| left[row] == right[first])
+                        })?
+                    }
+                };
+                let group = &groups[group];
+                let after = group.partition_point(|&candidate| {
+                    other_times[candidate]
+                        .compare(&times[row])
+                        .is_some_and(|ordering| ordering.is_le())
+                });
+                after.checked_sub(1).map(|i| group[i])
+            })
+            .collect();
+        let values: Vec<Symbol> = other
+            .names
+            .iter()
+            .filter(|name| !columns.contains(name))
+            .copied()
+            .collect();
+        let rows: Vec<usize> = (0..self.len()).collect();
+        Ok(self.clone().merge(other, &values, &rows, &matches))
+    }
+
+    /// This table, already cut down to `rows` of the original, with the
+    /// columns `names` of `other` written where the original row matched a
+    /// row of `other` in `matches`
+    fn merge(mut self, other: &Table, names: &[Symbol], rows: &[usize], matches: &Matches) -> Self {
+        self.retain_attributes(|name| !names.contains(name));
+        for name in names {
+            let column = other.column(name).expect("merged columns are in the table");
+            let position = self.names.iter().position(|n| n == name);
+            let position = position
+                .unwrap_or_else(|| self.add_column(*name, vec![null_for(column); rows.len()]));
+            for (i, &row) in rows.iter().enumerate() {
+                if let Some(matched) = matches[row] {
+                    self.columns[position][i] = column[matched].clone();
+                }
+            }
+        }
+        self
+    }
+
+    /// q's `uj`: the columns of both tables, with the rows of `other`
+    /// appended, or for keyed tables upserted, and nulls where a table
+    /// lacks a column
+    pub fn union_join(&self, other: &Table) -> Result<Self, String> {
+        if self.names[..self.keys] != other.names[..other.keys] {
+            return Err("uj needs two unkeyed tables or two with the same key".into());
+        }
+        let mut table = self.clone();
+        for (name, column) in other.names.iter().zip(&other.columns) {
+            if !table.names.contains(name) {
+                table.add_column(*name, vec![null_for(column); self.len()]);
+            }
+        }
+        table.upsert(&other.unkeyed())
+    }
+
+    /// Columns, in the order of [`Table::names`]
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// The column called `name`
+    pub fn column(&self, name: &str) -> Option<&ListItems> {
+        self.names
+            .iter()
+            .position(|n| *n == name)
+            .map(|i| self.columns[i].as_slice())
+    }
+
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        self.columns.first().map_or(0, Vec::len)
+    }
+
+    /// Whether the table has no rows
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Row `index` as a dictionary from column name to value
+    pub fn row(&self, index: usize) -> Option<Value> {
+        (index < self.len()).then(|| Value::Dict {
+            keys: self.names.iter().map(|&n| Value::Symbol(n)).collect(),
+            values: self.columns.iter().map(|c| c[index].clone()).collect(),
+        })
+    }
+
+    /// A row of nulls, typed as each column is
+    pub fn null_row(&self) -> Value {
+        Value::Dict {
+            keys: self.names.iter().map(|&n| Value::Symbol(n)).collect(),
+            values: self.columns.iter().map(|c| null_for(c)).collect(),
+        }
+    }
+
+    /// Every row, as [`Table::row`] gives them
+    pub fn rows(&self) -> impl Iterator<Item = Value> + '_ {
+        (0..self.len()).filter_map(|i| self.row(i))
+    }
+
+    /// The table of rows at `indices`, in that order
+    pub fn select_rows(&self, indices: &[usize]) -> Option<Self> {
+        if indices.iter().any(|&i| i >= self.len()) {
+            return None;
+        }
+        Some(Self {
+            names: self.names.clone(),
+            columns: self
+                .columns
+                .iter()
+                .map(|c| indices.iter().map(|&i| c[i].clone()).collect())
+                .collect(),
+            keys: self.keys,
+            attributes: None,
+        })
+    }
+
+    /// The table with `values` written to column `name` at `rows`
+    ///
+    /// A new column is added at the end, null in the rows not written.
+    pub fn update(&self, name: Symbol, rows: &[usize], values: Column) -> Result<Self, String> {
+        if values.len() != rows.len() {
+            return Err(format!(
+                "column `{} needs {} values, got {}",
+                name,
+                rows.len(),
+                values.len()
+            ));
+        }
+        if let Some(&row) = rows.iter().find(|&&row| row >= self.len()) {
+            return Err(format!("row {} out of range", row));
+        }
+        let mut table = self.clone();
+        table.retain_attributes(|&n| n != name);
+        let position = match table.names.iter().position(|&n| n == name) {
+            Some(position) => position,
+            None => {
+                let null = null_for(&values);
+                table.add_column(name, vec![null; self.len()])
+            }
+        };
+        for (&row, value) in rows.iter().zip(values) {
+            table.columns[position][row] = value;
+        }
+        Ok(table)
+    }
+
+    /// The table without the rows at `rows`
+    pub fn delete_rows(&self, rows: &[usize]) -> Self {
+        let mut deleted = vec![false; self.len()];
+        for &row in rows {
+            if let Some(flag) = deleted.get_mut(row) {
+                *flag = true;
+            }
+        }
+        let kept: Vec<usize> = (0..self.len()).filter(|&i| !deleted[i]).collect();
+        self.select_rows(&kept)
+            .expect("kept rows are within the table")
+    }
+
+    /// The table without the columns `names`
+    pub fn delete_columns(&self, names: &[Symbol]) -> Result<Self, String> {
+        if let Some(missing) = names.iter().find(|n| !self.names.contains(n)) {
+            return Err(format!("No such column: `{}", missing));
+        }
+        let keys = self.names[..self.keys]
+            .iter()
+            .filter(|name| !names.contains(name))
+            .count();
+        let attributes = self
+            .attributes()
+            .filter(|(name, _)| !names.contains(name))
+            .cloned()
+            .collect();
+        let (names, columns): (Vec<_>, _) = self
+            .names
+            .iter()
+            .zip(&self.columns)
+            .filter(|(name, _)| !names.contains(name))
+            .map(|(&name, column)| (name, column.clone()))
+            .unzip();
+        let mut table = Self {
+            names: names.into(),
+            columns,
+            keys,
+            attributes: None,
+        };
+        table.set_attributes(attributes);
+        Ok(table)
+    }
+
+    /// The dictionary of columns this table flips
+    pub fn to_dict(&self) -> Value {
+        Value::Dict {
+            keys: self.names.iter().map(|&n| Value::Symbol(n)).collect(),
+            values: self.columns.iter().cloned().map(Value::List).collect(),
+        }
+    }
+
+    /// Aligned text in q's layout: a header, a rule, then one line per row,
+    /// with a bar after the key columns of a keyed table
+    pub fn render(&self, interner: &Rodeo) -> String {
+        if !self.is_keyed() || self.keys == self.names.len() {
+            return self.unkeyed_render(interner);
+        }
+        let (key, value) = self.split_key();
+        let key = key.unkeyed_render(interner);
+        let width = key.lines().map(str::len).max().unwrap_or(0);
+        key.lines()
+            .zip(value.unkeyed_render(interner).lines())
+            .enumerate()
+            .map(|(i, (left, right))| {
+                // The rule runs through the bar
+                let fill = if i == 1 { '-' } else { ' ' };
+                let padding: String = std::iter::repeat_n(fill, width - left.len()).collect();
+                format!("{}{}| {}", left, padding, right)
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn unkeyed_render(&self, interner: &Rodeo) -> String {
+        let headers: Vec<String> = self.names.iter().map(|n| n.to_string()).collect();
+        let cells: Vec<Vec<String>> = (0..self.len())
+            .map(|i| self.columns.iter().map(|c| cell(&c[i], interner)).collect())
+            .collect();
+        layout(&headers, &cells)
+    }
+}
+
+/// Groups by the hash of their key
+type Buckets = HashMap<u64, Vec<usize>>;
+
+/// Rows grouped by their values in `columns`: the row indices of each
+/// group, in order of each group's first row
+pub fn group_rows(columns: &[&ListItems]) -> Vec<Vec<usize>> {
+    let rows = columns.first().map_or(0, |c| c.len());
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    // Keys are compared to resolve clashes between hashes
+    let mut by_hash = Buckets::new();
+    for row in 0..rows {
+        let key = columns.iter().map(|column| &column[row]);
+        let candidates = by_hash.entry(hash_key(key)).or_default();
+        let same_key = |group: &usize| {
+            let first = groups[*group][0];
+            columns.iter().all(|c| c[first] == c[row])
+        };
+        match candidates.iter().find(|g| same_key(g)) {
+            Some(&group) => groups[group].push(row),
+            None => {
+                candidates.push(groups.len());
+                groups.push(vec![row]);
+            }
+        }
+    }
+    groups
+}
+
+/// For each row, the row of another table it matched, if any
+type Matches = [Option<usize>];
+
+/// Hash of a key made of `values`
+pub(crate) fn hash_key<'a>(values: impl IntoIterator<Item = &'a Value>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for value in values {
+        hash_value(value, &mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Feed `value` to `hasher` consistently with `Value`'s equality
+fn hash_value(value: &Value, hasher: &mut impl Hasher) {
+    // An attributed list equals the plain list of its items
+    if let Value::Attributed(list) = value {
+        std::mem::discriminant(&Value::List(Vec::new())).hash(hasher);
+        list.items()
+            .iter()
+            .for_each(|item| hash_value(item, hasher));
+        return;
+    }
+    std::mem::discriminant(value).hash(hasher);
+    match value {
+        Value::Integer(n) => n.hash(hasher),
+        Value::BigInt(n) => n.hash(hasher),
+        // Every NaN is the same null
+        Value::Float(f) if f.is_nan() => f64::NAN.to_bits().hash(hasher),
+        // 0.0 and -0.0 are equal
+        Value::Float(f) => (f + 0.0).to_bits().hash(hasher),
+        Value::Boolean(b) => b.hash(hasher),
+        Value::Symbol(s) => s.hash(hasher),
+        Value::Char(c) => c.hash(hasher),
+        Value::Date(d) | Value::Time(d) => d.hash(hasher),
+        Value::Timestamp(t) => t.hash(hasher),
+        Value::List(items) => items.iter().for_each(|item| hash_value(item, hasher)),
+        // Equal values of other types still hash alike; their groups are
+        // told apart by comparison
+        _ => {}
+    }
+}
+
+/// The null to fill `column` with
+fn null_for(column: &[Value]) -> Value {
+    column.first().map_or(Value::Integer(NULL_INTEGER), null_of)
+}
+
+/// The null to fill a column of values like `value` with: `0n` for floats,
+/// the empty symbol for symbols, `0b` for booleans and `0N` otherwise
+fn null_of(value: &Value) -> Value {
+    match value {
+        Value::Float(_) => Value::Float(f64::NAN),
+        Value::Symbol(_) => Value::Symbol("".into()),
+        Value::Boolean(_) => Value::Boolean(false),
+        _ => Value::Integer(NULL_INTEGER),
+    }
+}
+
+/// Text of one cell: symbols without their backtick, anything else as written
+pub fn cell(value: &Value, interner: &Rodeo) -> String {
+    match value {
+        Value::Symbol(name) => name.to_string(),
+        other => other.format(interner),
+    }
+}
+
+/// Align `cells` under `headers`, separated from them by a rule
+pub fn layout(headers: &[String], cells: &Cells) -> String {
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            cells
+                .iter()
+                .map(|row| row[i].len())
+                .fold(header.len(), usize::max)
+        })
+        .collect();
+    let line = |items: &[String]| {
+        let padded: Vec<String> = items
+            .iter()
+            .zip(&widths)
+            .map(|(item, &width)| format!("{:<width$}", item))
+            .collect();
+        padded.join(" ").trim_end().to_string()
+    };
+
+    let mut lines = vec![line(headers)];
+    lines.push("-".repeat(lines[0].len()));
+    lines.extend(cells.iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trades() -> Table {
+        Table::from_dict(
+            &["sym".into(), "px".into()],
+            &[
+                Value::from(vec!["AAPL", "MSFT"]),
+                Value::from(vec![100i64, 250]),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_flip_checks_lengths() {
+        let t = trades();
+        assert_eq!(t.len(), 2);
+        assert_eq!(t.column("px").unwrap(), [100i64.into(), 250i64.into()]);
+
+        let ragged = Table::from_dict(
+            &["a".into(), "b".into()],
+            &[Value::from(vec![1i64, 2]), Value::from(vec![1i64])],
+        );
+        assert_eq!(ragged.unwrap_err(), "column `b has 1 rows, but `a has 2");
+
+        // Atoms are repeated down their column
+        let t = Table::from_dict(
+            &["a".into(), "b".into()],
+            &[Value::from(vec![1i64, 2]), Value::Integer(0)],
+        )
+        .unwrap();
+        assert_eq!(t.column("b").unwrap(), [0i64.into(), 0i64.into()]);
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        let t = trades();
+        let t = t.update("px".into(), &[1], vec![260i64.into()]).unwrap();
+        assert_eq!(t.column("px").unwrap(), [100i64.into(), 260i64.into()]);
+        // New columns are null where not written
+        let t = t
+            .update("qty".into(), &[0], vec![Value::Float(1.5)])
+            .unwrap();
+        let qty = t.column("qty").unwrap();
+        assert!(qty[1].is_null());
+        assert!(t.update("px".into(), &[0, 1], vec![]).is_err());
+
+        assert_eq!(t.delete_rows(&[0]).len(), 1);
+        let t = t.delete_columns(&["px".into(), "qty".into()]).unwrap();
+        assert_eq!(t.names(), [Symbol::new("sym")]);
+        assert_eq!(
+            t.delete_columns(&["px".into()]).unwrap_err(),
+            "No such column: `px"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let text = trades().render(&Rodeo::default());
+        assert_eq!(text, "sym  px\n-------\nAAPL 100\nMSFT 250");
+    }
+
+    #[test]
+    fn test_group_rows() {
+        let sym = vec![Value::Integer(1), 2i64.into(), 1i64.into(), 2i64.into()];
+        let side = vec![Value::Boolean(true), true.into(), false.into(), true.into()];
+        assert_eq!(group_rows(&[&sym]), [vec![0, 2], vec![1, 3]]);
+        assert_eq!(group_rows(&[&sym, &side]), [vec![0], vec![1, 3], vec![2]]);
+        assert!(group_rows(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_keyed() {
+        let t = trades();
+        let (key, value) = t.split_key();
+        assert_eq!(key.names().len(), 0);
+        assert_eq!(value, t);
+
+        let sym = Table::new(vec!["sym".into()], vec![t.columns()[0].clone()]).unwrap();
+        let px = Table::new(vec!["px".into()], vec![t.columns()[1].clone()]).unwrap();
+        let keyed = Table::keyed(&sym, &px).unwrap();
+        assert_eq!(keyed.keys(), 1);
+        assert_eq!(keyed.split_key(), (sym, px));
+        assert_eq!(
+            keyed.render(&Rodeo::default()),
+            "sym | px\n----| --\nAAPL| 100\nMSFT| 250"
+        );
+        assert_eq!(keyed.unkeyed(), t);
+    }
+
+    #[test]
+    fn test_upsert() {
+        let keyed = trades().xkey(&["sym".into()]).unwrap();
+        let rows = Table::from_dict(
+            &["sym".into(), "px".into()],
+            &[
+                Value::from(vec!["MSFT", "IBM"]),
+                Value::from(vec![260i64, 140]),
+            ],
+        )
+        .unwrap();
+        let upserted = keyed.upsert(&rows).unwrap();
+        assert_eq!(upserted.keys(), 1);
+        assert_eq!(
+            upserted.column("px").unwrap(),
+            [100i64.into(), 260i64.into(), 140i64.into()]
+        );
+        assert_eq!(upserted.find_key(&[Value::from("IBM")]), Some(2));
+        assert_eq!(trades().upsert(&rows).unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,118 @@
+//! Structured logging and tracing spans
+//!
+//! The REPL and Jupyter kernel open a `request` span per input, carrying a
+//! `request_id`. The evaluator opens a `statement` span per statement and
+//! storage opens `scan`, `query` and `insert_batch` spans; all of them nest
+//! under the request that caused them, so an event can be traced back to the
+//! input that produced it.
+//!
+//! Events are logged to stderr, filtered by the [`LOG_ENV`] environment
+//! variable with `tracing-subscriber`'s directive syntax, e.g.
+//! `WABZNASM_LOG=wabznasm=debug,storage=debug`. Only warnings are logged by
+//! default. Built with the `otel` feature, spans can also be exported to an
+//! OpenTelemetry collector over OTLP/HTTP.
+
+use thiserror::Error;
+use tracing::Span;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
+
+/// Environment variable holding the log filter
+pub const LOG_ENV: &str = "WABZNASM_LOG";
+
+/// Filter used when [`LOG_ENV`] is unset or invalid
+pub const DEFAULT_LOG_FILTER: &str = "warn";
+
+/// Error setting up telemetry
+#[derive(Error, Debug)]
+pub enum TelemetryError {
+    #[error("Exporting traces requires wabznasm to be built with the `otel` feature")]
+    ExportUnavailable,
+
+    #[cfg(feature = "otel")]
+    #[error("Failed to create OTLP exporter: {0}")]
+    Exporter(#[from] opentelemetry_otlp::ExporterBuildError),
+
+    #[error("Failed to install the tracing subscriber: {0}")]
+    Install(#[from] tracing_subscriber::util::TryInitError),
+}
+
+/// Installed telemetry; flushes exported spans when dropped
+#[derive(Default)]
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take() {
+            // Nothing useful can be done about a failed flush while exiting
+            let _ = provider.shutdown();
+        }
+    }
+}
+
+/// Install the global subscriber, exporting spans to `otlp_endpoint` if given
+///
+/// Keep the returned [`Telemetry`] alive until the process exits so pending
+/// spans are exported.
+pub fn init(otlp_endpoint: Option<&str>) -> Result<Telemetry, TelemetryError> {
+    let filter =
+        EnvFilter::try_from_env(LOG_ENV).unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let logs = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_filter(filter);
+    let registry = tracing_subscriber::registry().with(logs);
+
+    let Some(endpoint) = otlp_endpoint else {
+        registry.try_init()?;
+        return Ok(Telemetry::default());
+    };
+    export(registry, endpoint)
+}
+
+#[cfg(feature = "otel")]
+fn export<S>(registry: S, endpoint: &str) -> Result<Telemetry, TelemetryError>
+where
+    S: tracing::Subscriber
+        + for<'a> tracing_subscriber::registry::LookupSpan<'a>
+        + Send
+        + Sync
+        + 'static,
+{
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::WithExportConfig;
+    use tracing_subscriber::filter::LevelFilter;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    let resource = opentelemetry_sdk::Resource::builder()
+        .with_service_name("wabznasm")
+        .build();
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build();
+    // Export every span, whatever the log filter says
+    let traces = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer("wabznasm"))
+        .with_filter(LevelFilter::DEBUG);
+    registry.with(traces).try_init()?;
+    Ok(Telemetry {
+        provider: Some(provider),
+    })
+}
+
+#[cfg(not(feature = "otel"))]
+fn export<S>(_registry: S, _endpoint: &str) -> Result<Telemetry, TelemetryError> {
+    Err(TelemetryError::ExportUnavailable)
+}
+
+/// Span covering one request: a REPL line or a Jupyter message
+pub fn request_span(request_id: &str, kind: &str) -> Span {
+    tracing::info_span!("request", request_id, kind)
+}
@@ -0,0 +1,224 @@
+//! Dates, times and timestamps
+//!
+//! Temporal atoms count from the Unix epoch, like the storage crate's
+//! `ScalarValue::Timestamp`:
+//!
+//! | type        | literal                         | stored as                       |
+//! |-------------|---------------------------------|---------------------------------|
+//! | `date`      | `2024.01.15`                    | days since 1970.01.01           |
+//! | `time`      | `12:30:00.250`                  | milliseconds since midnight     |
+//! | `timestamp` | `2024.01.15D12:30:00.250000000` | nanoseconds since the epoch     |
+//!
+//! Adding an integer moves a temporal value by that many of its units, and
+//! subtracting two values of the same type counts the units between them;
+//! a date plus a time is a timestamp.
+
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use chrono::{DateTime, NaiveDate, NaiveTime, Timelike};
+use tree_sitter::Node;
+
+/// Milliseconds in a day
+pub const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// Nanoseconds in a day
+pub const NANOS_PER_DAY: i64 = MILLIS_PER_DAY * 1_000_000;
+
+/// Nanoseconds in a millisecond
+pub const NANOS_PER_MILLI: i64 = 1_000_000;
+
+fn epoch() -> NaiveDate {
+    DateTime::UNIX_EPOCH.date_naive()
+}
+
+/// Days since the epoch of a `YYYY.MM.DD` date
+pub fn parse_date(text: &str) -> Option<i32> {
+    let date = NaiveDate::parse_from_str(text, "%Y.%m.%d").ok()?;
+    i32::try_from((date - epoch()).num_days()).ok()
+}
+
+/// Milliseconds since midnight of a `hh:mm`, `hh:mm:ss` or `hh:mm:ss.mmm` time
+pub fn parse_time(text: &str) -> Option<i32> {
+    let (clock, fraction) = match text.split_once('.') {
+        Some((clock, fraction)) => (clock, fraction),
+        None => (text, ""),
+    };
+    let mut parts = clock.split(':').map(|part| part.parse::<i32>().ok());
+    let hours = parts.next()??;
+    let minutes = parts.next()??;
+    let seconds = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() || minutes >= 60 || seconds >= 60 || fraction.len() > 3 {
+        return None;
+    }
+    // `.5` is half a second: pad the fraction out to milliseconds
+    let millis = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<3}", fraction).parse::<i32>().ok()?
+    };
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+/// Nanoseconds since the epoch of a `YYYY.MM.DDDhh:mm:ss.nnnnnnnnn`
+/// timestamp; the time of day may be shortened as for [`parse_time`], or
+/// left out for midnight
+pub fn parse_timestamp(text: &str) -> Option<i64> {
+    let (date, time) = text.split_once('D')?;
+    let days = i64::from(parse_date(date)?);
+    let nanos = if time.is_empty() {
+        0
+    } else {
+        let time = NaiveTime::parse_from_str(time, "%H:%M:%S%.f")
+            .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+            .ok()?;
+        i64::from(time.num_seconds_from_midnight()) * 1_000_000_000 + i64::from(time.nanosecond())
+    };
+    days.checked_mul(NANOS_PER_DAY)?.checked_add(nanos)
+}
+
+/// `2024.01.15`
+pub fn format_date(days: i32) -> String {
+    match epoch().checked_add_signed(chrono::Duration::days(i64::from(days))) {
+        Some(date) => date.format("%Y.%m.%d").to_string(),
+        None => format!("{}d", days),
+    }
+}
+
+/// `12:30:00.250`; times past midnight keep counting hours
+pub fn format_time(millis: i32) -> String {
+    let sign = if millis < 0 { "-" } else { "" };
+    let millis = i64::from(millis).abs();
+    format!(
+        "{}{:02}:{:02}:{:02}.{:03}",
+        sign,
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// `2024.01.15D12:30:00.250000000`
+pub fn format_timestamp(nanos: i64) -> String {
+    let days = nanos.div_euclid(NANOS_PER_DAY);
+    let of_day = nanos.rem_euclid(NANOS_PER_DAY);
+    format!(
+        "{}D{:02}:{:02}:{:02}.{:09}",
+        format_date(days as i32),
+        of_day / 3_600_000_000_000,
+        of_day / 60_000_000_000 % 60,
+        of_day / 1_000_000_000 % 60,
+        of_day % 1_000_000_000
+    )
+}
+
+/// The timestamp at midnight starting `days`
+pub fn date_to_timestamp(days: i32) -> i64 {
+    i64::from(days) * NANOS_PER_DAY
+}
+
+/// The date a timestamp falls on
+pub fn timestamp_to_date(nanos: i64) -> i32 {
+    nanos.div_euclid(NANOS_PER_DAY) as i32
+}
+
+/// The time of day of a timestamp, in milliseconds
+pub fn timestamp_to_time(nanos: i64) -> i32 {
+    (nanos.rem_euclid(NANOS_PER_DAY) / NANOS_PER_MILLI) as i32
+}
+
+/// Whether `value` is a date, time or timestamp
+pub fn is_temporal(value: &Value) -> bool {
+    matches!(value, Value::Date(_) | Value::Time(_) | Value::Timestamp(_))
+}
+
+/// The result of temporal arithmetic, if it applied
+type TemporalResult = Option<Result<Value, EvalError>>;
+
+/// `left op right` when either operand is temporal
+///
+/// Returns `None` when neither is, so plain arithmetic applies.
+pub fn binary(op: &str, left: &Value, right: &Value, node: Node) -> TemporalResult {
+    if !is_temporal(left) && !is_temporal(right) {
+        return None;
+    }
+    let overflow = || {
+        EvalError::new(
+            EvalErrorKind::IntegerOverflow(format!("{} {}", left.type_name(), op)),
+            node,
+        )
+    };
+    let result = match (op, left, right) {
+        ("+", Value::Date(d), Value::Integer(n)) | ("+", Value::Integer(n), Value::Date(d)) => {
+            shift(i64::from(*d), *n, 1)
+                .and_then(|d| i32::try_from(d).ok())
+                .map(Value::Date)
+        }
+        ("-", Value::Date(d), Value::Integer(n)) => shift(i64::from(*d), *n, -1)
+            .and_then(|d| i32::try_from(d).ok())
+            .map(Value::Date),
+        ("-", Value::Date(a), Value::Date(b)) => {
+            Some(Value::Integer(i64::from(*a) - i64::from(*b)))
+        }
+        ("+", Value::Time(t), Value::Integer(n)) | ("+", Value::Integer(n), Value::Time(t)) => {
+            shift(i64::from(*t), *n, 1)
+                .and_then(|t| i32::try_from(t).ok())
+                .map(Value::Time)
+        }
+        ("-", Value::Time(t), Value::Integer(n)) => shift(i64::from(*t), *n, -1)
+            .and_then(|t| i32::try_from(t).ok())
+            .map(Value::Time),
+        ("-", Value::Time(a), Value::Time(b)) => {
+            Some(Value::Integer(i64::from(*a) - i64::from(*b)))
+        }
+        ("+", Value::Timestamp(t), Value::Integer(n))
+        | ("+", Value::Integer(n), Value::Timestamp(t)) => shift(*t, *n, 1).map(Value::Timestamp),
+        ("-", Value::Timestamp(t), Value::Integer(n)) => shift(*t, *n, -1).map(Value::Timestamp),
+        ("-", Value::Timestamp(a), Value::Timestamp(b)) => a.checked_sub(*b).map(Value::Integer),
+        ("+", Value::Date(d), Value::Time(t)) | ("+", Value::Time(t), Value::Date(d)) => {
+            date_to_timestamp(*d)
+                .checked_add(i64::from(*t) * NANOS_PER_MILLI)
+                .map(Value::Timestamp)
+        }
+        _ => {
+            return Some(Err(EvalError::new(
+                EvalErrorKind::Type(format!(
+                    "cannot apply {} to {} and {}",
+                    op,
+                    left.type_name(),
+                    right.type_name()
+                )),
+                node,
+            )));
+        }
+    };
+    Some(result.ok_or_else(overflow))
+}
+
+fn shift(value: i64, by: i64, sign: i64) -> Option<i64> {
+    value.checked_add(by.checked_mul(sign)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(parse_date("1970.01.01"), Some(0));
+        assert_eq!(parse_date("2000.01.01"), Some(10957));
+        assert_eq!(format_date(10957), "2000.01.01");
+        assert_eq!(parse_date("2024.02.30"), None);
+
+        assert_eq!(parse_time("00:00:01.5"), Some(1500));
+        assert_eq!(parse_time("12:30"), Some(45_000_000));
+        assert_eq!(format_time(45_000_250), "12:30:00.250");
+        assert_eq!(parse_time("12:60"), None);
+
+        let nanos = parse_timestamp("2000.01.01D00:00:01.000000002").unwrap();
+        assert_eq!(nanos, 10957 * NANOS_PER_DAY + 1_000_000_002);
+        assert_eq!(format_timestamp(nanos), "2000.01.01D00:00:01.000000002");
+        assert_eq!(format_timestamp(-1), "1969.12.31D23:59:59.999999999");
+        assert_eq!(parse_timestamp("2000.01.01D"), Some(10957 * NANOS_PER_DAY));
+    }
+}
@@ -0,0 +1,144 @@
+//! Uniform builtins: running and pairwise operations over a list
+//!
+//! Each gives a list as long as its argument, or an atom for an atom.
+//! Arithmetic follows the evaluator's rules, overflow mode included, so
+//! `deltas` applies to timestamps and `sums` promotes to float as `+` does.
+//! The running operations skip nulls, as the aggregates do, carrying the
+//! result so far past them; `deltas` and `ratios` propagate them.
+//!
+//! ```text
+//! sums 1 2 3 4       / 1 3 6 10
+//! deltas 1 4 9 16    / 1 3 5 7
+//! maxs 3 1 4 1 5     / 3 3 4 4 5
+//! ```
+
+use crate::arithmetic;
+use crate::builtins::Context;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use std::cmp::Ordering;
+use tree_sitter::Node;
+
+/// `f` applied to the items of `value`, giving a list for a list and the
+/// single result for an atom
+pub(crate) fn uniform(
+    value: &Value,
+    mut f: impl FnMut(&[Value]) -> Result<Vec<Value>, EvalError>,
+) -> Result<Value, EvalError> {
+    match value {
+        Value::List(items) => f(items).map(Value::List),
+        atom => Ok(f(std::slice::from_ref(atom))?.remove(0)),
+    }
+}
+
+/// Each item combined by `op` with the running result of those before it,
+/// skipping nulls
+fn running(context: &Context, value: &Value, op: &str, node: Node) -> Result<Value, EvalError> {
+    uniform(value, |items| {
+        let mut so_far: Option<Value> = None;
+        let mut results = Vec::with_capacity(items.len());
+        for item in items {
+            if !item.is_null() {
+                so_far = Some(match so_far {
+                    None => item.clone(),
+                    Some(total) => {
+                        arithmetic::binary(op, &total, item, context.overflow, node, node)?
+                    }
+                });
+            }
+            results.push(so_far.clone().unwrap_or_else(|| item.clone()));
+        }
+        Ok(results)
+    })
+}
+
+/// `sums x`: the running totals of `x`
+pub fn sums(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    running(context, &args[0], "+", node)
+}
+
+/// `prds x`: the running products of `x`
+pub fn prds(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    running(context, &args[0], "*", node)
+}
+
+/// The least or greatest item so far at each item, skipping nulls
+fn extremes(name: &str, value: &Value, keep: Ordering, node: Node) -> Result<Value, EvalError> {
+    uniform(value, |items| {
+        let mut best: Option<&Value> = None;
+        let mut results = Vec::with_capacity(items.len());
+        for item in items {
+            if !item.is_null() {
+                best = match best {
+                    None => Some(item),
+                    Some(current) => match item.compare(current) {
+                        Some(ordering) if ordering == keep => Some(item),
+                        Some(_) => Some(current),
+                        None => {
+                            return Err(EvalError::new(
+                                EvalErrorKind::Type(format!(
+                                    "{} cannot compare {} with {}",
+                                    name,
+                                    item.type_name(),
+                                    current.type_name()
+                                )),
+                                node,
+                            ));
+                        }
+                    },
+                };
+            }
+            results.push(best.unwrap_or(item).clone());
+        }
+        Ok(results)
+    })
+}
+
+/// `maxs x`: the greatest item of `x` so far at each item
+pub fn maxs(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    extremes("maxs", &args[0], Ordering::Greater, node)
+}
+
+/// `mins x`: the least item of `x` so far at each item
+pub fn mins(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    extremes("mins", &args[0], Ordering::Less, node)
+}
+
+/// The first item, then each item combined by `op` with the one before it
+fn pairwise(context: &Context, value: &Value, op: &str, node: Node) -> Result<Value, EvalError> {
+    uniform(value, |items| {
+        let mut results = Vec::with_capacity(items.len());
+        results.extend(items.first().cloned());
+        for pair in items.windows(2) {
+            results.push(arithmetic::binary(
+                op,
+                &pair[1],
+                &pair[0],
+                context.overflow,
+                node,
+                node,
+            )?);
+        }
+        Ok(results)
+    })
+}
+
+/// `deltas x`: the first item of `x`, then the difference of each item
+/// from the one before it
+pub fn deltas(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    pairwise(context, &args[0], "-", node)
+}
+
+/// `ratios x`: the first item of `x`, then the ratio of each item to the
+/// one before it, all as floats so integer items do not truncate
+pub fn ratios(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let to_float = |item: &Value| match item {
+        Value::Integer(_) => Value::Float(item.as_f64().unwrap_or(f64::NAN)),
+        other => other.clone(),
+    };
+    let floats = match &args[0] {
+        Value::List(items) => Value::List(items.iter().map(to_float).collect()),
+        atom => to_float(atom),
+    };
+    pairwise(context, &floats, "/", node)
+}
@@ -0,0 +1,192 @@
+//! Typed vectors: lists of integers or floats held in one buffer
+//!
+//! A general list holds each item as a tagged [`Value`]. The lists the
+//! vector kernels in [`crate::kernels`] give, integers only or floats only,
+//! are instead [`Value::IntVector`] and [`Value::FloatVector`]: the numbers
+//! side by side in an arrow2 [`Buffer`], the layout of an Arrow primitive
+//! array. [`to_arrow`] and [`from_arrow`] convert between the two without
+//! copying the numbers.
+//!
+//! A vector is a representation, not a type: it equals the list of its
+//! items, is a `list` to `type`, and formats the same. Like an attribute,
+//! it stays while the list is bound to a name, passed to or returned from a
+//! function, or an operand of arithmetic, and the builtins that read
+//! vectors directly ([`consumed_by`]) get it; anything else sees a general
+//! list, as [`Value::plain`] gives it.
+//!
+//! ```text
+//! x: 1 2 3*10      / a vector
+//! sum x+1          / the kernels and sum read it as it is
+//! x[0]             / indexing sees the list 10 20 30
+//! ```
+//!
+//! Nulls are stored as they are in q, `0N` as the least integer and `0n`
+//! as NaN, and become Arrow nulls on the way out.
+
+use crate::environment::{NULL_INTEGER, Value};
+use arrow2::array::{Array, PrimitiveArray};
+use arrow2::bitmap::Bitmap;
+use arrow2::datatypes::DataType;
+
+pub use arrow2::buffer::Buffer;
+
+/// An Arrow array of any type
+pub type ArrowArray = Box<dyn Array>;
+
+/// Whether the builtin `name` reads vectors as they are
+pub fn consumed_by(name: &str) -> bool {
+    matches!(name, "sum" | "avg" | "min" | "max" | "count")
+}
+
+/// The number of items of a list or vector
+pub fn len(value: &Value) -> Option<usize> {
+    match value {
+        Value::IntVector(ints) => Some(ints.len()),
+        Value::FloatVector(floats) => Some(floats.len()),
+        other => other.as_list().map(<[Value]>::len),
+    }
+}
+
+/// The vector of a list of integers only or floats only
+pub fn pack(items: &[Value]) -> Option<Value> {
+    match items.first()? {
+        Value::Integer(_) => items
+            .iter()
+            .map(Value::as_integer)
+            .collect::<Option<Vec<_>>>()
+            .map(|ints| Value::IntVector(ints.into())),
+        Value::Float(_) => items
+            .iter()
+            .map(|item| match item {
+                Value::Float(f) => Some(*f),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|floats| Value::FloatVector(floats.into())),
+        _ => None,
+    }
+}
+
+/// The items of a vector as a general list
+pub fn unpack(value: &Value) -> Option<Vec<Value>> {
+    match value {
+        Value::IntVector(ints) => Some(ints.iter().copied().map(Value::Integer).collect()),
+        Value::FloatVector(floats) => Some(floats.iter().copied().map(Value::Float).collect()),
+        _ => None,
+    }
+}
+
+/// Validity of `items`, if any is null
+fn validity<T: Copy>(items: &[T], is_null: impl Fn(T) -> bool) -> Option<Bitmap> {
+    items
+        .iter()
+        .any(|&item| is_null(item))
+        .then(|| items.iter().map(|&item| !is_null(item)).collect())
+}
+
+/// An Arrow array of a vector, or of a list a vector can hold, sharing the
+/// vector's buffer
+pub fn to_arrow(value: &Value) -> Option<ArrowArray> {
+    let packed;
+    let value = match value {
+        Value::List(items) => {
+            packed = pack(items)?;
+            &packed
+        }
+        vector => vector,
+    };
+    match value {
+        Value::IntVector(ints) => Some(Box::new(PrimitiveArray::new(
+            DataType::Int64,
+            ints.clone(),
+            validity(ints, |n| n == NULL_INTEGER),
+        ))),
+        Value::FloatVector(floats) => Some(Box::new(PrimitiveArray::new(
+            DataType::Float64,
+            floats.clone(),
+            validity(floats, f64::is_nan),
+        ))),
+        _ => None,
+    }
+}
+
+/// The vector of an Arrow array of 64-bit integers or floats, sharing its
+/// buffer unless nulls must be written into it
+pub fn from_arrow(array: &dyn Array) -> Option<Value> {
+    match array.data_type() {
+        DataType::Int64 => {
+            let array = array.as_any().downcast_ref::<PrimitiveArray<i64>>()?;
+            Some(Value::IntVector(filled(array, NULL_INTEGER)))
+        }
+        DataType::Float64 => {
+            let array = array.as_any().downcast_ref::<PrimitiveArray<f64>>()?;
+            Some(Value::FloatVector(filled(array, f64::NAN)))
+        }
+        _ => None,
+    }
+}
+
+/// The values of `array` with its nulls as `null`
+fn filled<T: arrow2::types::NativeType>(array: &PrimitiveArray<T>, null: T) -> Buffer<T> {
+    if array.null_count() == 0 {
+        return array.values().clone();
+    }
+    array
+        .iter()
+        .map(|item| item.copied().unwrap_or(null))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrow_arrays_share_the_buffer() {
+        let vector = Value::IntVector(vec![1, 2, 3].into());
+        let array = to_arrow(&vector).unwrap();
+        let Value::IntVector(ints) = &vector else {
+            unreachable!()
+        };
+        let shared = array
+            .as_any()
+            .downcast_ref::<PrimitiveArray<i64>>()
+            .unwrap();
+        assert_eq!(shared.values().as_ptr(), ints.as_ptr());
+        let Some(Value::IntVector(back)) = from_arrow(array.as_ref()) else {
+            panic!("expected an integer vector")
+        };
+        assert_eq!(back.as_ptr(), ints.as_ptr());
+    }
+
+    #[test]
+    fn test_nulls_become_arrow_nulls() {
+        let list = Value::List(vec![
+            Value::Float(1.5),
+            Value::Float(f64::NAN),
+            Value::Float(2.0),
+        ]);
+        let array = to_arrow(&list).unwrap();
+        assert_eq!(array.null_count(), 1);
+        assert!(array.is_null(1));
+        assert_eq!(from_arrow(array.as_ref()), Some(list));
+
+        let ints = PrimitiveArray::<i64>::from(vec![Some(4), None]);
+        let expected = Value::List(vec![Value::Integer(4), Value::Integer(NULL_INTEGER)]);
+        assert_eq!(from_arrow(&ints), Some(expected));
+    }
+
+    #[test]
+    fn test_only_lists_of_one_number_type_pack() {
+        assert_eq!(
+            pack(&[Value::Integer(1), Value::Integer(2)]),
+            Some(Value::IntVector(vec![1, 2].into()))
+        );
+        assert_eq!(pack(&[Value::Integer(1), Value::Float(2.0)]), None);
+        assert_eq!(pack(&[Value::Boolean(true)]), None);
+        assert_eq!(pack(&[]), None);
+        let strings = PrimitiveArray::<i32>::from(vec![Some(1)]);
+        assert_eq!(from_arrow(&strings), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! Warnings: diagnostics about code that runs but likely does not do what
+//! was meant
+//!
+//! The evaluator checks each input it is given before evaluating it, and
+//! keeps the warnings found until they are taken with
+//! [`Evaluator::take_warnings`]. Warnings never stop evaluation. The checks
+//! look at the parse tree, so they cover function bodies when the functions
+//! are defined, including branches that never run:
+//!
+//! - assigning to the name of a builtin, or naming a parameter after one,
+//!   which hides the builtin from then on
+//! - a parameter the function body never uses
+//! - an integer literal so large that doubling it overflows
+//!
+//! [`Evaluator::take_warnings`]: crate::evaluator::Evaluator::take_warnings
+
+use crate::builtins;
+use crate::errors::{Span, line_column};
+use miette::{Diagnostic, LabeledSpan, Severity, SourceSpan};
+use std::fmt;
+use thiserror::Error;
+use tree_sitter::Node;
+
+/// Magnitude from which an integer literal doubled overflows
+const LARGE_INTEGER: u64 = 1 << 62;
+
+/// A warning about the source at `span`
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{kind}")]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub span: SourceSpan,
+}
+
+/// Specific kinds of warnings
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum WarningKind {
+    #[error("{0} is a builtin; binding it hides the builtin")]
+    ShadowsBuiltin(String),
+
+    #[error("Parameter {0} is never used")]
+    UnusedParameter(String),
+
+    #[error("Integer literal {0} is close to the integer limit; arithmetic on it may overflow")]
+    OverflowProneLiteral(String),
+}
+
+impl WarningKind {
+    /// Returns a machine-readable code for this warning kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            WarningKind::ShadowsBuiltin(_) => "SHADOWS_BUILTIN",
+            WarningKind::UnusedParameter(_) => "UNUSED_PARAMETER",
+            WarningKind::OverflowProneLiteral(_) => "OVERFLOW_PRONE_LITERAL",
+        }
+    }
+}
+
+impl Diagnostic for Warning {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.kind.code()))
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Warning)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(Box::new(std::iter::once(LabeledSpan::underline(self.span))))
+    }
+}
+
+impl Warning {
+    fn new(kind: WarningKind, node: Node) -> Self {
+        Self {
+            kind,
+            span: Span::from(node).into(),
+        }
+    }
+
+    /// The warning and where it is in `source`, on one line
+    pub fn describe(&self, source: &str) -> String {
+        let (line, column) = line_column(source, self.span.offset());
+        format!("Warning: {} (line {}, column {})", self, line, column)
+    }
+}
+
+/// Warnings about the code at `node` in `src`
+pub fn check(node: Node, src: &str) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    visit(node, src, &mut warnings);
+    warnings
+}
+
+fn visit(node: Node, src: &str, warnings: &mut Vec<Warning>) {
+    let text = |node: Node| &src[node.byte_range()];
+    match node.kind() {
+        "assignment" => {
+            if let Some(name) = node.child_by_field_name("name")
+                && builtins::lookup(text(name)).is_some()
+            {
+                let kind = WarningKind::ShadowsBuiltin(text(name).to_string());
+                warnings.push(Warning::new(kind, name));
+            }
+        }
+        "function_body" => {
+            if let (Some(params), Some(body)) = (
+                node.child_by_field_name("params"),
+                node.child_by_field_name("body"),
+            ) {
+                let mut cursor = params.walk();
+                for param in params.named_children(&mut cursor) {
+                    if param.kind() != "identifier" {
+                        continue;
+                    }
+                    let name = text(param);
+                    if builtins::lookup(name).is_some() {
+                        let kind = WarningKind::ShadowsBuiltin(name.to_string());
+                        warnings.push(Warning::new(kind, param));
+                    }
+                    if !uses(body, name, src) {
+                        let kind = WarningKind::UnusedParameter(name.to_string());
+                        warnings.push(Warning::new(kind, param));
+                    }
+                }
+            }
+        }
+        "number" => {
+            if let Ok(n) = text(node).parse::<i64>()
+                && n.unsigned_abs() >= LARGE_INTEGER
+            {
+                let kind = WarningKind::OverflowProneLiteral(text(node).to_string());
+                warnings.push(Warning::new(kind, node));
+            }
+        }
+        _ => {}
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        visit(child, src, warnings);
+    }
+}
+
+/// Whether the code at `node` refers to `name`
+fn uses(node: Node, name: &str, src: &str) -> bool {
+    if node.kind() == "identifier" && &src[node.byte_range()] == name {
+        return true;
+    }
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor)
+        .any(|child| uses(child, name, src))
+}
@@ -0,0 +1,317 @@
+//! Moving-window builtins: statistics over the last `n` items at each item
+//!
+//! `msum[n;x]` gives, at each item of `x`, the sum of that item and the
+//! `n-1` before it; the first windows are shorter. Each is a single pass,
+//! however wide the window: sums and moments are updated as items enter
+//! and leave it, and `mmax` and `mmin` keep the candidates for the extreme
+//! in a queue. Nulls are skipped, so a window of nulls alone averages to
+//! `0n`.
+//!
+//! ```text
+//! msum[2;1 2 3 4]       / 1 3 5 7
+//! mavg[3;1 2 3 4 5]     / 1 1.5 2 3 4
+//! mmax[2;3 1 4 1 5]     / 3 3 4 4 5
+//! mdev[2;1 3 5]         / 0 1 1
+//! ```
+
+use crate::arithmetic;
+use crate::builtins::Context;
+use crate::environment::Value;
+use crate::errors::{EvalError, EvalErrorKind};
+use crate::uniform::uniform;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use tree_sitter::Node;
+
+/// The window size `n` given to the builtin `name`, which must be positive
+fn width(name: &str, n: &Value, node: Node) -> Result<usize, EvalError> {
+    match n {
+        Value::Integer(n) if *n > 0 => Ok(*n as usize),
+        _ => Err(EvalError::new(
+            EvalErrorKind::Type(format!(
+                "{} expects a positive window size, got {}",
+                name,
+                n.type_name()
+            )),
+            node,
+        )),
+    }
+}
+
+/// Items of a list as floats, nulls as NaN
+type Numbers = Vec<f64>;
+
+/// The items of `x` as floats, for the builtin `name`
+fn floats(name: &str, items: &[Value], node: Node) -> Result<Numbers, EvalError> {
+    items
+        .iter()
+        .map(|item| match item {
+            Value::Integer(_) | Value::BigInt(_) | Value::Float(_) => item.as_f64(),
+            _ => None,
+        })
+        .map(|number| {
+            number.ok_or_else(|| {
+                EvalError::new(
+                    EvalErrorKind::Type(format!("{} expects numbers", name)),
+                    node,
+                )
+            })
+        })
+        .collect()
+}
+
+/// A running sum of floats that items can be taken out of again
+///
+/// Finite items are added with Neumaier's compensation, so a large item
+/// leaving the window takes none of the small ones with it; infinities are
+/// counted rather than added, since one taken out of an infinite sum would
+/// leave `0n`.
+#[derive(Clone, Copy, Default)]
+struct Sum {
+    total: f64,
+    /// Low-order bits rounded off `total`
+    compensation: f64,
+    /// Infinities in the sum: positive, then negative
+    infinities: [usize; 2],
+}
+
+impl Sum {
+    /// Add `x` to the sum or, with `sign` -1, take it out
+    fn add(&mut self, x: f64, sign: f64) {
+        if x.is_infinite() {
+            let count = &mut self.infinities[usize::from(x < 0.0)];
+            *count = if sign > 0.0 { *count + 1 } else { *count - 1 };
+            return;
+        }
+        let x = sign * x;
+        let total = self.total + x;
+        self.compensation += if self.total.abs() >= x.abs() {
+            (self.total - total) + x
+        } else {
+            (x - total) + self.total
+        };
+        self.total = total;
+    }
+
+    /// Number of infinities in the sum
+    fn infinite(&self) -> usize {
+        self.infinities[0] + self.infinities[1]
+    }
+
+    /// The sum of the finite items
+    fn finite(&self) -> f64 {
+        self.total + self.compensation
+    }
+
+    fn value(&self) -> f64 {
+        match self.infinities {
+            [0, 0] => self.finite(),
+            [_, 0] => f64::INFINITY,
+            [0, _] => f64::NEG_INFINITY,
+            _ => f64::NAN,
+        }
+    }
+}
+
+/// Count, sum and spread of the numbers in a window, nulls left out
+#[derive(Clone, Copy, Default)]
+struct Moments {
+    count: usize,
+    sum: Sum,
+    /// Sum of the squared deviations of the finite numbers from their mean,
+    /// updated as in Welford's algorithm
+    deviations: f64,
+}
+
+/// How many times what is left of the spread of a window the part of it
+/// taken out with one number can be before what is left is worked out
+/// afresh
+const CANCELLATION: f64 = 1e6;
+
+impl Moments {
+    /// The moments of `numbers`
+    fn of(numbers: &[f64]) -> Self {
+        let mut moments = Self::default();
+        for &x in numbers {
+            moments.add(x, 1.0);
+        }
+        moments
+    }
+
+    /// Mean of the finite numbers, or 0 if there are none
+    fn finite_mean(&self) -> f64 {
+        match self.count - self.sum.infinite() {
+            0 => 0.0,
+            finite => self.sum.finite() / finite as f64,
+        }
+    }
+
+    /// Put `x` in the window or, with `sign` -1, take it out, giving how
+    /// much that changed the squared deviations by
+    fn add(&mut self, x: f64, sign: f64) -> f64 {
+        if x.is_nan() {
+            return 0.0;
+        }
+        let before = self.finite_mean();
+        self.count = if sign > 0.0 {
+            self.count + 1
+        } else {
+            self.count - 1
+        };
+        self.sum.add(x, sign);
+        if x.is_infinite() {
+            return 0.0;
+        }
+        let change = (x - before) * (x - self.finite_mean());
+        self.deviations = if self.count == self.sum.infinite() {
+            0.0
+        } else {
+            self.deviations + sign * change
+        };
+        change
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum.value() / self.count as f64
+    }
+
+    /// The standard deviation; `0n` if the window holds no numbers or an
+    /// infinity
+    fn deviation(&self) -> f64 {
+        if self.count == 0 || self.sum.infinite() > 0 {
+            return f64::NAN;
+        }
+        // Rounding can take the spread of equal items below zero
+        (self.deviations.max(0.0) / self.count as f64).sqrt()
+    }
+}
+
+/// The moments of each window of `n` numbers
+fn moments(n: usize, numbers: &[f64]) -> impl Iterator<Item = Moments> + '_ {
+    let mut window = Moments::default();
+    numbers.iter().enumerate().map(move |(i, &x)| {
+        window.add(x, 1.0);
+        if let Some(j) = i.checked_sub(n) {
+            let removed = window.add(numbers[j], -1.0);
+            // Taking out a number far from the rest cancels nearly all of
+            // the spread, and rounding all that is left
+            if removed > window.deviations * CANCELLATION {
+                window = Moments::of(&numbers[j + 1..=i]);
+            }
+        }
+        window
+    })
+}
+
+/// `msum[n;x]`: the sum of each item of `x` and the `n-1` before it
+pub fn msum(context: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let n = width("msum", &args[0], node)?;
+    uniform(&args[1], |items| {
+        if items.iter().any(|item| matches!(item, Value::Float(_))) {
+            let numbers = floats("msum", items, node)?;
+            let mut sum = Sum::default();
+            let sums = numbers.iter().enumerate().map(|(i, &x)| {
+                if !x.is_nan() {
+                    sum.add(x, 1.0);
+                }
+                if let Some(old) = i.checked_sub(n).map(|j| numbers[j])
+                    && !old.is_nan()
+                {
+                    sum.add(old, -1.0);
+                }
+                Value::Float(sum.value())
+            });
+            return Ok(sums.collect());
+        }
+        let mut sum = Value::Integer(0);
+        let mut results = Vec::with_capacity(items.len());
+        for (i, item) in items.iter().enumerate() {
+            if !item.is_null() {
+                sum = arithmetic::binary("+", &sum, item, context.overflow, node, node)?;
+            }
+            if let Some(old) = i.checked_sub(n).map(|j| &items[j])
+                && !old.is_null()
+            {
+                sum = arithmetic::binary("-", &sum, old, context.overflow, node, node)?;
+            }
+            results.push(sum.clone());
+        }
+        Ok(results)
+    })
+}
+
+/// `mavg[n;x]`: the average of each item of `x` and the `n-1` before it
+pub fn mavg(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let n = width("mavg", &args[0], node)?;
+    uniform(&args[1], |items| {
+        let numbers = floats("mavg", items, node)?;
+        let averages = moments(n, &numbers).map(|window| Value::Float(window.mean()));
+        Ok(averages.collect())
+    })
+}
+
+/// `mdev[n;x]`: the standard deviation of each item of `x` and the `n-1`
+/// before it
+pub fn mdev(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let n = width("mdev", &args[0], node)?;
+    uniform(&args[1], |items| {
+        let numbers = floats("mdev", items, node)?;
+        let deviations = moments(n, &numbers).map(|window| Value::Float(window.deviation()));
+        Ok(deviations.collect())
+    })
+}
+
+/// The greatest or least item of each window of `n`, skipping nulls
+fn extremes(
+    name: &str,
+    n: usize,
+    value: &Value,
+    keep: Ordering,
+    node: Node,
+) -> Result<Value, EvalError> {
+    uniform(value, |items| {
+        // Positions of the items that could still be the extreme of a
+        // window, their items in order from the extreme down
+        let mut candidates: VecDeque<usize> = VecDeque::new();
+        let mut results = Vec::with_capacity(items.len());
+        for (i, item) in items.iter().enumerate() {
+            if candidates.front().is_some_and(|&j| j + n <= i) {
+                candidates.pop_front();
+            }
+            if !item.is_null() {
+                while let Some(&last) = candidates.back() {
+                    let ordering = item.compare(&items[last]).ok_or_else(|| {
+                        EvalError::new(
+                            EvalErrorKind::Type(format!(
+                                "{} cannot compare {} with {}",
+                                name,
+                                item.type_name(),
+                                items[last].type_name()
+                            )),
+                            node,
+                        )
+                    })?;
+                    if ordering == keep.reverse() {
+                        break;
+                    }
+                    candidates.pop_back();
+                }
+                candidates.push_back(i);
+            }
+            results.push(candidates.front().map_or(item, |&j| &items[j]).clone());
+        }
+        Ok(results)
+    })
+}
+
+/// `mmax[n;x]`: the greatest of each item of `x` and the `n-1` before it
+pub fn mmax(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let n = width("mmax", &args[0], node)?;
+    extremes("mmax", n, &args[1], Ordering::Greater, node)
+}
+
+/// `mmin[n;x]`: the least of each item of `x` and the `n-1` before it
+pub fn mmin(_: &mut Context, args: &[Value], node: Node) -> Result<Value, EvalError> {
+    let n = width("mmin", &args[0], node)?;
+    extremes("mmin", n, &args[1], Ordering::Less, node)
+}
@@ -0,0 +1,158 @@
+//! Column-level access statistics
+//!
+//! Every value read from a column file is counted against its column, along
+//! with the bytes read for it, and every query condition on a column is
+//! counted as an equality lookup or a range filter. Counts accumulate in memory while a table is
+//! open and are added to the totals in the table's [`ACCESS_FILE`] when it is
+//! dropped, so they survive across the short-lived handles opened per query.
+//! Columns that are read often are candidates for an index, dictionary
+//! encoding or caching; columns that are never read are candidates for
+//! colder storage. The [`advisor`](crate::advisor) turns these counts into
+//! suggestions.
+//!
+//! Handles that close at the same moment may each add their counts to the
+//! same stale totals, so the statistics are a guide rather than an audit.
+
+use crate::{config::QStoreConfig, error::StorageResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Name of the access statistics file inside a table directory
+pub const ACCESS_FILE: &str = ".access";
+
+/// Reads of one column
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnAccess {
+    /// Column name
+    pub column: String,
+    /// Values read
+    pub reads: u64,
+    /// Bytes read from the column file, including framing
+    pub bytes: u64,
+    /// Queries with an equality condition on the column
+    pub lookups: u64,
+    /// Queries with a range condition on the column
+    pub ranges: u64,
+}
+
+impl ColumnAccess {
+    fn add(&mut self, other: &ColumnAccess) {
+        self.reads += other.reads;
+        self.bytes += other.bytes;
+        self.lookups += other.lookups;
+        self.ranges += other.ranges;
+    }
+}
+
+/// Counts, by column
+type Counts = HashMap<String, ColumnAccess>;
+
+/// Reads since a table was opened
+#[derive(Debug, Default)]
+pub(crate) struct AccessCounter {
+    counts: Mutex<Counts>,
+}
+
+impl AccessCounter {
+    /// Count one read of `bytes` from `column`
+    pub(crate) fn record(&self, column: &str, bytes: u64) {
+        self.update(column, |entry| {
+            entry.reads += 1;
+            entry.bytes += bytes;
+        });
+    }
+
+    /// Count a query condition on `column`: an equality lookup, or a range
+    pub(crate) fn record_filter(&self, column: &str, equality: bool) {
+        self.update(column, |entry| {
+            if equality {
+                entry.lookups += 1;
+            } else {
+                entry.ranges += 1;
+            }
+        });
+    }
+
+    /// Forget the counts so far, for reads made on the table's own behalf
+    pub(crate) fn discard(&self) {
+        self.counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    fn update(&self, column: &str, f: impl FnOnce(&mut ColumnAccess)) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = counts
+            .entry(column.to_string())
+            .or_insert_with(|| ColumnAccess {
+                column: column.to_string(),
+                ..ColumnAccess::default()
+            });
+        f(entry);
+    }
+
+    fn snapshot(&self) -> Counts {
+        self.counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Add the counts so far to the persisted totals and start again from zero
+    pub(crate) fn flush(&self, config: &QStoreConfig) -> StorageResult<()> {
+        let counts = std::mem::take(&mut *self.counts.lock().unwrap_or_else(|e| e.into_inner()));
+        if counts.is_empty() {
+            return Ok(());
+        }
+        let mut totals = load(config)?;
+        merge(&mut totals, &counts);
+        let path = config.table_path().join(ACCESS_FILE);
+        std::fs::write(path, bincode::serialize(&totals)?)?;
+        Ok(())
+    }
+
+    /// Persisted totals plus the counts so far, for each of `columns`,
+    /// most read first
+    pub(crate) fn report(
+        &self,
+        config: &QStoreConfig,
+        columns: &[&str],
+    ) -> StorageResult<Vec<ColumnAccess>> {
+        let mut totals = load(config)?;
+        merge(&mut totals, &self.snapshot());
+        let mut report: Vec<ColumnAccess> = columns
+            .iter()
+            .map(|&column| {
+                totals.remove(column).unwrap_or_else(|| ColumnAccess {
+                    column: column.to_string(),
+                    ..ColumnAccess::default()
+                })
+            })
+            .collect();
+        // Stable, so equally read columns stay in schema order
+        report.sort_by_key(|a| std::cmp::Reverse(a.reads));
+        Ok(report)
+    }
+}
+
+fn load(config: &QStoreConfig) -> StorageResult<Counts> {
+    match std::fs::read(config.table_path().join(ACCESS_FILE)) {
+        Ok(bytes) => Ok(bincode::deserialize(&bytes)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn merge(totals: &mut Counts, counts: &Counts) {
+    for (column, count) in counts {
+        totals
+            .entry(column.clone())
+            .or_insert_with(|| ColumnAccess {
+                column: column.clone(),
+                ..ColumnAccess::default()
+            })
+            .add(count);
+    }
+}
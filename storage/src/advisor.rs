@@ -0,0 +1,393 @@
+//! Attribute and partitioning advice from access statistics
+//!
+//! An [`Advisor`] weighs how a table has been queried, as recorded in its
+//! [access statistics](crate::access), against what its data looks like, and
+//! suggests the layout changes that would have saved the most reading:
+//!
+//! - a column looked up by equality gets an attribute that finds matching
+//!   rows without a scan: `sorted` if its values are already in order,
+//!   `unique` if they are distinct, `parted` if equal values are stored
+//!   together, and `grouped` otherwise
+//! - a column filtered by range gets `sorted` if its values are in order
+//! - a timestamp column filtered by range in a large table is suggested as
+//!   the column to partition the table on by date
+//!
+//! Benefits are estimates of the values the recorded queries would not have
+//! read had the change been in place, assuming a lookup reads only the rows
+//! it matches, a range query keeps half the rows, and a query on a
+//! partitioned table reads a single day.
+
+use crate::{
+    access::ColumnAccess,
+    config::QStoreConfig,
+    error::StorageResult,
+    schema::{ColumnAttribute, ColumnSchema, SimpleDataType},
+    table::{Table, table_names},
+    value::ScalarValue,
+    view::compare,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+/// Rows below which partitioning is not worth its directories
+pub const DEFAULT_PARTITION_ROWS: usize = 1_000_000;
+
+const NANOS_PER_DAY: i64 = 86_400_000_000_000;
+
+/// A suggested change to a table's layout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Declare an attribute on the column
+    SetAttribute(ColumnAttribute),
+    /// Partition the table by the date of the column
+    PartitionByDate,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::SetAttribute(attribute) => write!(f, "set the `{} attribute", attribute.name()),
+            Action::PartitionByDate => write!(f, "partition by date"),
+        }
+    }
+}
+
+/// One suggestion for one column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advice {
+    /// Table the column belongs to
+    pub table: String,
+    /// Column the change applies to
+    pub column: String,
+    /// What to change
+    pub action: Action,
+    /// Why, in terms of the recorded queries and the data
+    pub reason: String,
+    /// Estimated values the recorded queries would not have read
+    pub saved_reads: u64,
+    /// `saved_reads` as a share of the column's recorded reads, from 0 to 1
+    pub share: f64,
+}
+
+impl fmt::Display for Advice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}: {} ({}); saves ~{} reads ({:.0}%)",
+            self.table,
+            self.column,
+            self.action,
+            self.reason,
+            self.saved_reads,
+            self.share * 100.0
+        )
+    }
+}
+
+/// What the advisor needs to know about a column's values
+struct Profile {
+    rows: usize,
+    sorted: bool,
+    distinct: usize,
+    /// Whether equal values are stored together
+    parted: bool,
+    /// Days between the first and last timestamp, inclusive
+    days: Option<i64>,
+}
+
+impl Profile {
+    fn of(values: &[ScalarValue], column: &ColumnSchema) -> StorageResult<Self> {
+        let sorted = values
+            .windows(2)
+            .all(|pair| compare(&pair[0], &pair[1]).is_some_and(|o| o.is_le()));
+        let mut seen = HashSet::new();
+        let mut parted = true;
+        let mut previous: Option<Vec<u8>> = None;
+        for value in values {
+            let key = bincode::serialize(value)?;
+            if previous.as_ref() != Some(&key) {
+                // A value seen before, but not just now, is stored apart
+                parted &= seen.insert(key.clone());
+                previous = Some(key);
+            }
+        }
+        let days = (column.data_type == SimpleDataType::Timestamp).then(|| {
+            let nanos = values.iter().filter_map(|v| match v {
+                ScalarValue::Timestamp(t) => Some(*t),
+                _ => None,
+            });
+            let (min, max) = nanos.fold((i64::MAX, i64::MIN), |(lo, hi), t| (lo.min(t), hi.max(t)));
+            if min > max {
+                0
+            } else {
+                max.div_euclid(NANOS_PER_DAY) - min.div_euclid(NANOS_PER_DAY) + 1
+            }
+        });
+        Ok(Self {
+            rows: values.len(),
+            sorted,
+            distinct: seen.len(),
+            parted,
+            days,
+        })
+    }
+
+    /// The attribute that serves lookups best for these values
+    fn lookup_attribute(&self) -> ColumnAttribute {
+        if self.sorted {
+            ColumnAttribute::Sorted
+        } else if self.distinct == self.rows {
+            ColumnAttribute::Unique
+        } else if self.parted {
+            ColumnAttribute::Parted
+        } else {
+            ColumnAttribute::Grouped
+        }
+    }
+}
+
+/// Suggests attributes and partitioning from a table's access statistics
+#[derive(Debug, Clone)]
+pub struct Advisor {
+    partition_rows: usize,
+}
+
+impl Default for Advisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Advisor {
+    /// An advisor with the default thresholds
+    pub fn new() -> Self {
+        Self {
+            partition_rows: DEFAULT_PARTITION_ROWS,
+        }
+    }
+
+    /// Only suggest partitioning tables of at least `rows` rows
+    pub fn with_partition_rows(mut self, rows: usize) -> Self {
+        self.partition_rows = rows;
+        self
+    }
+
+    /// Suggestions for `table`, greatest benefit first
+    ///
+    /// Reads made to profile the data are not counted in the table's access
+    /// statistics.
+    pub fn advise(&self, table: &Table) -> StorageResult<Vec<Advice>> {
+        let schema = table.schema();
+        let _span = tracing::debug_span!("advise", table = %schema.name).entered();
+        let access = table.access_stats()?;
+        // Persist the workload so far, so that only the profiling is forgotten
+        table.flush_access_stats()?;
+
+        let mut advice = Vec::new();
+        for usage in &access {
+            let Some(column) = schema.get_column(&usage.column) else {
+                continue;
+            };
+            if usage.lookups == 0 && usage.ranges == 0 {
+                continue;
+            }
+            let profile = Profile::of(&table.get_column(&column.name)?, column)?;
+            advice.extend(self.advise_column(&schema.name, column, usage, &profile));
+        }
+        table.discard_access_stats();
+
+        advice.sort_by_key(|a| std::cmp::Reverse(a.saved_reads));
+        Ok(advice)
+    }
+
+    /// Suggestions for every table under the database `root`, greatest
+    /// benefit first
+    pub fn advise_database(&self, root: &Path) -> StorageResult<Vec<Advice>> {
+        let mut advice = Vec::new();
+        for name in table_names(root)? {
+            let table = Table::load(QStoreConfig::new(root, name))?;
+            advice.extend(self.advise(&table)?);
+        }
+        advice.sort_by_key(|a| std::cmp::Reverse(a.saved_reads));
+        Ok(advice)
+    }
+
+    fn advise_column(
+        &self,
+        table: &str,
+        column: &ColumnSchema,
+        usage: &ColumnAccess,
+        profile: &Profile,
+    ) -> Vec<Advice> {
+        let rows = profile.rows as u64;
+        let advice = |action, reason: String, saved_reads: u64| Advice {
+            table: table.to_string(),
+            column: column.name.clone(),
+            action,
+            reason,
+            saved_reads,
+            share: (saved_reads as f64 / usage.reads.max(1) as f64).min(1.0),
+        };
+        let mut suggestions = Vec::new();
+
+        if usage.lookups > 0 && column.attribute.is_none() && profile.distinct > 1 {
+            let matched = rows / profile.distinct as u64;
+            let attribute = profile.lookup_attribute();
+            let reason = format!(
+                "{} lookups over {} rows with {} distinct values",
+                usage.lookups, rows, profile.distinct
+            );
+            let saved = usage.lookups * (rows - matched);
+            suggestions.push(advice(Action::SetAttribute(attribute), reason, saved));
+        }
+
+        let has_sorted = column.attribute == Some(ColumnAttribute::Sorted);
+        let lookups_sorted = suggestions
+            .iter()
+            .any(|a| a.action == Action::SetAttribute(ColumnAttribute::Sorted));
+        if usage.ranges > 0 && profile.sorted && !has_sorted && !lookups_sorted {
+            let reason = format!(
+                "{} range filters over {} rows already in order",
+                usage.ranges, rows
+            );
+            suggestions.push(advice(
+                Action::SetAttribute(ColumnAttribute::Sorted),
+                reason,
+                usage.ranges * (rows / 2),
+            ));
+        }
+
+        if let Some(days) = profile.days
+            && usage.ranges > 0
+            && days > 1
+            && profile.rows >= self.partition_rows
+        {
+            let reason = format!(
+                "{} range filters over {} rows spanning {} days",
+                usage.ranges, rows, days
+            );
+            let saved = usage.ranges * (rows - rows / days as u64);
+            suggestions.push(advice(Action::PartitionByDate, reason, saved));
+        }
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        query::Query,
+        schema::{SchemaBuilder, TableSchema},
+        table::Row,
+    };
+    use tempfile::TempDir;
+
+    fn trades(dir: &TempDir) -> Table {
+        let schema = TableSchema::new("trades".to_string())
+            .add_column(ColumnSchema::new_simple("sym".into(), SimpleDataType::Utf8))
+            .add_column(ColumnSchema::new_simple("px".into(), SimpleDataType::Int64))
+            .add_column(ColumnSchema::new_simple("id".into(), SimpleDataType::Int64));
+        let config = QStoreConfig::new(dir.path(), "trades".into());
+        let mut table = Table::create(schema, config).unwrap();
+        for i in 0..40 {
+            let mut row = Row::new();
+            let sym = ["AAPL", "MSFT", "IBM", "GOOG"][i % 4];
+            row.insert("sym".into(), ScalarValue::Utf8(sym.into()));
+            row.insert("px".into(), ScalarValue::Int64(100 + (i as i64 * 7) % 50));
+            row.insert("id".into(), ScalarValue::Int64(i as i64));
+            table.insert(row).unwrap();
+        }
+        table
+    }
+
+    fn run(table: &Table, query: &str) {
+        Query::parse(query).unwrap().execute(table).unwrap();
+    }
+
+    #[test]
+    fn test_lookups_suggest_attributes() {
+        let dir = TempDir::new().unwrap();
+        let table = trades(&dir);
+        for _ in 0..3 {
+            run(&table, "select px from trades where sym=`AAPL");
+        }
+        run(&table, "select px from trades where id>=30");
+        run(&table, "select px from trades where px<>120");
+
+        let advice = Advisor::new().advise(&table).unwrap();
+        let actions: Vec<_> = advice
+            .iter()
+            .map(|a| (a.column.as_str(), a.action))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                ("sym", Action::SetAttribute(ColumnAttribute::Grouped)),
+                ("id", Action::SetAttribute(ColumnAttribute::Sorted)),
+            ]
+        );
+        // Three lookups each skip the 30 rows of the other symbols
+        assert_eq!(advice[0].saved_reads, 90);
+        assert!(
+            advice[0]
+                .to_string()
+                .starts_with("trades.sym: set the `grouped attribute")
+        );
+
+        // Profiling the data is not counted as a read
+        let reads = |table: &Table| table.access_stats().unwrap()[0].reads;
+        let before = reads(&table);
+        Advisor::new().advise(&table).unwrap();
+        assert_eq!(reads(&table), before);
+    }
+
+    #[test]
+    fn test_declared_attributes_are_not_suggested() {
+        let dir = TempDir::new().unwrap();
+        let schema = TableSchema::new("t".to_string()).add_column(
+            ColumnSchema::new_simple("sym".into(), SimpleDataType::Utf8)
+                .with_attribute(ColumnAttribute::Grouped),
+        );
+        let mut table = Table::create(schema, QStoreConfig::new(dir.path(), "t".into())).unwrap();
+        for sym in ["a", "b", "a"] {
+            let mut row = Row::new();
+            row.insert("sym".into(), ScalarValue::Utf8(sym.into()));
+            table.insert(row).unwrap();
+        }
+        run(&table, "select from t where sym=`a");
+        assert!(Advisor::new().advise(&table).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_partitioning_large_time_ranges() {
+        let dir = TempDir::new().unwrap();
+        let config = QStoreConfig::new(dir.path(), "ticks".into());
+        let mut table = Table::create(SchemaBuilder::time_series(), config).unwrap();
+        for day in 0..4 {
+            let mut row = Row::new();
+            row.insert("time".into(), ScalarValue::Timestamp(day * NANOS_PER_DAY));
+            row.insert("value".into(), ScalarValue::Float64(1.0));
+            table.insert(row).unwrap();
+        }
+        run(&table, "select value from ticks where time>0");
+        drop(table);
+
+        let advisor = Advisor::new().with_partition_rows(4);
+        let advice = advisor.advise_database(dir.path()).unwrap();
+        let actions: Vec<_> = advice.iter().map(|a| a.action).collect();
+        assert_eq!(
+            actions,
+            [
+                Action::PartitionByDate,
+                Action::SetAttribute(ColumnAttribute::Sorted)
+            ]
+        );
+        assert_eq!(advice[0].saved_reads, 3);
+
+        // Below the threshold only the attribute is suggested
+        let advice = Advisor::new().advise_database(dir.path()).unwrap();
+        assert_eq!(advice.len(), 1);
+    }
+}
@@ -0,0 +1,111 @@
+//! Offset checkpoints for ingestion sources
+//!
+//! Checkpoints are kept in an ordinary splayed metadata table with the
+//! columns `source`, `partition` and `offset`. The table is append-only;
+//! the most recent row for a `(source, partition)` pair wins on reload.
+
+use crate::{
+    config::QStoreConfig,
+    error::{StorageError, StorageResult},
+    storage::SplayedTable,
+    table::Row,
+    value::ScalarValue,
+};
+use std::collections::HashMap;
+
+/// Checkpoint key: source name and partition
+type SourcePartition = (String, i32);
+
+/// Durable record of how far each ingestion source has been consumed
+pub struct CheckpointStore {
+    storage: SplayedTable,
+    offsets: HashMap<SourcePartition, i64>,
+}
+
+impl CheckpointStore {
+    /// Open the checkpoint table described by `config`, creating it if needed
+    pub fn open(config: QStoreConfig) -> StorageResult<Self> {
+        let storage = if config.table_path().exists() {
+            SplayedTable::open(config)?
+        } else {
+            SplayedTable::new(config)?
+        };
+
+        let mut offsets = HashMap::new();
+        for index in 0..storage.count()? {
+            let row = storage.get(index)?;
+            let source = row
+                .get("source")
+                .and_then(ScalarValue::as_str)
+                .ok_or_else(|| StorageError::FileFormat("checkpoint missing source".into()))?;
+            let partition = row
+                .get("partition")
+                .and_then(ScalarValue::as_i64)
+                .ok_or_else(|| StorageError::FileFormat("checkpoint missing partition".into()))?;
+            let offset = row
+                .get("offset")
+                .and_then(ScalarValue::as_i64)
+                .ok_or_else(|| StorageError::FileFormat("checkpoint missing offset".into()))?;
+            offsets.insert((source.to_string(), partition as i32), offset);
+        }
+
+        Ok(Self { storage, offsets })
+    }
+
+    /// Last committed offset for a source partition, if any
+    pub fn get(&self, source: &str, partition: i32) -> Option<i64> {
+        self.offsets.get(&(source.to_string(), partition)).copied()
+    }
+
+    /// All committed offsets for a source, keyed by partition
+    pub fn partitions(&self, source: &str) -> HashMap<i32, i64> {
+        self.offsets
+            .iter()
+            .filter(|((s, _), _)| s == source)
+            .map(|((_, partition), offset)| (*partition, *offset))
+            .collect()
+    }
+
+    /// Durably record the last processed offset for a source partition
+    pub fn commit(&mut self, source: &str, partition: i32, offset: i64) -> StorageResult<()> {
+        if self.get(source, partition) == Some(offset) {
+            return Ok(());
+        }
+
+        let mut row = Row::new();
+        row.insert("source".to_string(), ScalarValue::Utf8(source.to_string()));
+        row.insert("partition".to_string(), ScalarValue::Int32(partition));
+        row.insert("offset".to_string(), ScalarValue::Int64(offset));
+        self.storage.put(row)?;
+
+        self.offsets.insert((source.to_string(), partition), offset);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_checkpoint_commit_and_reload() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = QStoreConfig::new(temp_dir.path(), "_checkpoints".to_string());
+
+        {
+            let mut store = CheckpointStore::open(config.clone()).unwrap();
+            assert_eq!(store.get("trades", 0), None);
+            store.commit("trades", 0, 10).unwrap();
+            store.commit("trades", 1, 4).unwrap();
+            store.commit("trades", 0, 42).unwrap();
+            store.commit("quotes", 0, 7).unwrap();
+        }
+
+        let store = CheckpointStore::open(config).unwrap();
+        assert_eq!(store.get("trades", 0), Some(42));
+        assert_eq!(store.get("trades", 1), Some(4));
+        assert_eq!(store.get("quotes", 0), Some(7));
+        assert_eq!(store.partitions("trades").len(), 2);
+    }
+}
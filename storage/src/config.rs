@@ -3,6 +3,10 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+/// Name of the schema file inside a table directory; the leading dot keeps it
+/// from being read as a column
+pub const SCHEMA_FILE: &str = ".schema";
+
 /// Configuration for a Q-style storage system
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QStoreConfig {
@@ -16,6 +20,9 @@ pub struct QStoreConfig {
     pub enable_compression: bool,
     /// Buffer size for memory-mapped files
     pub mmap_buffer_size: usize,
+    /// Open without creating or writing anything under `data_dir`
+    #[serde(default)]
+    pub read_only: bool,
 }
 
 impl QStoreConfig {
@@ -27,6 +34,7 @@ impl QStoreConfig {
             max_file_size: 1024 * 1024 * 1024, // 1GB default
             enable_compression: false,         // Start simple, add compression later
             mmap_buffer_size: 8192,            // 8KB buffer
+            read_only: false,
         }
     }
 
@@ -35,6 +43,11 @@ impl QStoreConfig {
         self.data_dir.join(&self.table_name).join(column_name)
     }
 
+    /// Get the path of the file holding the table's schema
+    pub fn schema_path(&self) -> PathBuf {
+        self.table_path().join(SCHEMA_FILE)
+    }
+
     /// Get the table directory path
     pub fn table_path(&self) -> PathBuf {
         self.data_dir.join(&self.table_name)
@@ -52,6 +65,15 @@ impl QStoreConfig {
         self
     }
 
+    /// Set read-only mode: tables open without creating directories,
+    /// open their files for reading only, reject inserts with
+    /// [`StorageError::ReadOnly`](crate::StorageError::ReadOnly), and keep
+    /// their access statistics in memory instead of saving them
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
     /// Set memory map buffer size
     pub fn with_mmap_buffer_size(mut self, size: usize) -> Self {
         self.mmap_buffer_size = size;
@@ -90,9 +112,11 @@ mod tests {
         let config = QStoreConfig::default()
             .with_compression(true)
             .with_max_file_size(2048)
-            .with_mmap_buffer_size(4096);
+            .with_mmap_buffer_size(4096)
+            .with_read_only(true);
 
         assert!(config.enable_compression);
+        assert!(config.read_only);
         assert_eq!(config.max_file_size, 2048);
         assert_eq!(config.mmap_buffer_size, 4096);
     }
@@ -0,0 +1,360 @@
+//! Differential testing of query execution
+//!
+//! [`MemoryTable`] answers queries from rows held in memory, with none of the
+//! column files, encodings or caches a stored [`Table`] goes through. Running
+//! the same randomly generated queries against both and requiring identical
+//! results keeps the execution paths from drifting apart as each is
+//! optimized:
+//!
+//! ```no_run
+//! # fn main() -> storage::StorageResult<()> {
+//! let dir = tempfile::tempdir()?;
+//! if let Err(divergence) = storage::differential::run(dir.path(), 42, 100, 500)? {
+//!     panic!("{}", divergence);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Runs are deterministic in their seed, so a reported divergence can be
+//! replayed.
+
+use crate::{
+    config::QStoreConfig,
+    error::{StorageError, StorageResult},
+    query::{Query, ResultSet},
+    schema::{ColumnSchema, SimpleDataType, TableSchema},
+    table::{Row, Table},
+    value::ScalarValue,
+};
+use std::fmt;
+use std::path::Path;
+
+/// Name of the table generated rows are stored in
+pub const TABLE_NAME: &str = "trades";
+
+const SYMBOLS: [&str; 4] = ["AAPL", "MSFT", "IBM", "GOOG"];
+
+/// Something that answers queries over one table
+pub trait QueryEngine {
+    /// Run `query`, as [`Query::execute`] does
+    fn execute(&self, query: &Query) -> StorageResult<ResultSet>;
+}
+
+impl QueryEngine for Table {
+    fn execute(&self, query: &Query) -> StorageResult<ResultSet> {
+        query.execute(self)
+    }
+}
+
+/// Reference engine: a table whose rows are kept in memory
+#[derive(Debug, Clone)]
+pub struct MemoryTable {
+    schema: TableSchema,
+    rows: Vec<Row>,
+}
+
+impl MemoryTable {
+    /// An empty table with `schema`
+    pub fn new(schema: TableSchema) -> Self {
+        Self {
+            schema,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Append a row
+    pub fn insert(&mut self, row: Row) {
+        self.rows.push(row);
+    }
+
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Whether the table has no rows
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+impl QueryEngine for MemoryTable {
+    fn execute(&self, query: &Query) -> StorageResult<ResultSet> {
+        let columns: Vec<String> = match &query.columns {
+            Some(columns) => columns.clone(),
+            None => self.schema.columns.iter().map(|c| c.name.clone()).collect(),
+        };
+        let referenced = columns
+            .iter()
+            .chain(query.filters.iter().map(|f| &f.column));
+        for column in referenced {
+            if self.schema.get_column(column).is_none() {
+                return Err(StorageError::ColumnNotFound(column.clone()));
+            }
+        }
+
+        let rows = self
+            .rows
+            .iter()
+            .filter(|row| query.filters.iter().all(|f| f.matches(row)))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|c| row.get(c).cloned().unwrap_or(ScalarValue::Null))
+                    .collect()
+            })
+            .collect();
+        Ok(ResultSet {
+            columns,
+            rows,
+            scanned: self.rows.len(),
+        })
+    }
+}
+
+/// What an engine made of a query: its result, or its error message
+pub type Outcome = Result<ResultSet, String>;
+
+/// Result of a differential run: the first divergence, if any
+pub type RunResult = StorageResult<Result<(), Divergence>>;
+
+/// A query two engines disagreed on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// Seed of the run that produced the query
+    pub seed: u64,
+    /// Query text
+    pub query: String,
+    /// Result of the in-memory engine
+    pub expected: Outcome,
+    /// Result of the engine under test
+    pub actual: Outcome,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "engines disagree on `{}` (seed {}):\n  expected {:?}\n  actual   {:?}",
+            self.query, self.seed, self.expected, self.actual
+        )
+    }
+}
+
+/// Run `query` against both engines, returning how they differ if they do
+pub fn compare(
+    seed: u64,
+    query: &str,
+    expected: &dyn QueryEngine,
+    actual: &dyn QueryEngine,
+) -> Option<Divergence> {
+    let outcome = |engine: &dyn QueryEngine| {
+        Query::parse(query)
+            .and_then(|q| engine.execute(&q))
+            .map_err(|e| e.to_string())
+    };
+    let (expected, actual) = (outcome(expected), outcome(actual));
+    (expected != actual).then(|| Divergence {
+        seed,
+        query: query.to_string(),
+        expected,
+        actual,
+    })
+}
+
+/// Small deterministic generator (xorshift64*), so runs replay from a seed
+#[derive(Debug, Clone)]
+pub struct Generator {
+    state: u64,
+}
+
+impl Generator {
+    /// A generator starting from `seed`
+    pub fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift
+        Self {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// True one time in `n`
+    fn one_in(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+
+    /// Schema of generated tables: `sym`, `px`, `qty` and `time`
+    pub fn schema() -> TableSchema {
+        TableSchema::new(TABLE_NAME.to_string())
+            .add_column(ColumnSchema::new_simple("sym".into(), SimpleDataType::Utf8))
+            .add_column(ColumnSchema::new_simple("px".into(), SimpleDataType::Int64))
+            .add_column(ColumnSchema::new_simple(
+                "qty".into(),
+                SimpleDataType::Float64,
+            ))
+            .add_column(ColumnSchema::new_simple(
+                "time".into(),
+                SimpleDataType::Timestamp,
+            ))
+    }
+
+    /// A row of [`Generator::schema`], with the occasional null price
+    pub fn row(&mut self) -> Row {
+        let mut row = Row::new();
+        let sym = self.pick(&SYMBOLS);
+        row.insert("sym".into(), ScalarValue::Utf8(sym.into()));
+        let px = if self.one_in(10) {
+            ScalarValue::Null
+        } else {
+            ScalarValue::Int64(self.below(300) as i64 - 50)
+        };
+        row.insert("px".into(), px);
+        row.insert(
+            "qty".into(),
+            ScalarValue::Float64(self.below(200) as f64 / 2.0),
+        );
+        row.insert(
+            "time".into(),
+            ScalarValue::Timestamp(self.below(1_000) as i64 * 1_000_000_000),
+        );
+        row
+    }
+
+    fn literal(&mut self, column: &str, sql: bool) -> String {
+        match column {
+            "sym" if sql => format!("'{}'", self.pick(&SYMBOLS)),
+            "sym" => format!("`{}", self.pick(&SYMBOLS)),
+            "qty" => format!("{}.5", self.below(100)),
+            "time" => (self.below(1_000) as i64 * 1_000_000_000).to_string(),
+            _ => (self.below(300) as i64 - 50).to_string(),
+        }
+    }
+
+    /// A select over [`TABLE_NAME`] in SQL or q-sql form; it now and then
+    /// names a column the table lacks, to compare error handling too
+    pub fn query(&mut self) -> String {
+        let names = ["sym", "px", "qty", "time"];
+        let column = |g: &mut Self| {
+            if g.one_in(25) { "size" } else { g.pick(&names) }
+        };
+        let sql = self.one_in(2);
+        let separator = if sql { ", " } else { "," };
+
+        let columns = if self.one_in(4) {
+            if sql { "*".to_string() } else { String::new() }
+        } else {
+            let count = 1 + self.below(names.len());
+            (0..count)
+                .map(|_| column(self))
+                .collect::<Vec<_>>()
+                .join(separator)
+        };
+        let mut text = if sql {
+            format!("SELECT {} FROM {}", columns, TABLE_NAME)
+        } else {
+            format!("select {} from {}", columns, TABLE_NAME)
+        };
+
+        let filters: Vec<String> = (0..self.below(4))
+            .map(|_| {
+                let name = column(self);
+                let op = self.pick(&["=", "<>", "<", "<=", ">", ">="]);
+                let literal = self.literal(name, sql);
+                if sql {
+                    format!("{} {} {}", name, op, literal)
+                } else {
+                    format!("{}{}{}", name, op, literal)
+                }
+            })
+            .collect();
+        if !filters.is_empty() {
+            let joiner = if sql { " AND " } else { ", " };
+            text.push_str(&format!(" where {}", filters.join(joiner)));
+        }
+        if self.one_in(3) {
+            text.push_str(&format!(" limit {}", self.below(20)));
+        }
+        text
+    }
+}
+
+/// Store `rows` generated rows under `dir` and in memory, then compare
+/// `queries` generated queries across the two
+///
+/// Returns the first divergence found, if any.
+pub fn run(dir: &Path, seed: u64, rows: usize, queries: usize) -> RunResult {
+    let mut generator = Generator::new(seed);
+    let schema = Generator::schema();
+    let config = QStoreConfig::new(dir, TABLE_NAME.to_string());
+    let mut stored = Table::create(schema.clone(), config)?;
+    let mut memory = MemoryTable::new(schema);
+    for _ in 0..rows {
+        let row = generator.row();
+        stored.insert(row.clone())?;
+        memory.insert(row);
+    }
+
+    for _ in 0..queries {
+        let query = generator.query();
+        if let Some(divergence) = compare(seed, &query, &memory, &stored) {
+            return Ok(Err(divergence));
+        }
+    }
+    Ok(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_engines_agree() {
+        for seed in 0..3 {
+            let dir = TempDir::new().unwrap();
+            if let Err(divergence) = run(dir.path(), seed, 60, 200).unwrap() {
+                panic!("{}", divergence);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_queries_parse_and_replay() {
+        let mut a = Generator::new(7);
+        let mut b = Generator::new(7);
+        for _ in 0..200 {
+            let query = a.query();
+            assert_eq!(query, b.query());
+            Query::parse(&query).unwrap_or_else(|e| panic!("{}: {}", query, e));
+        }
+    }
+
+    #[test]
+    fn test_divergence_is_reported() {
+        let schema = Generator::schema();
+        let mut full = MemoryTable::new(schema.clone());
+        full.insert(Generator::new(1).row());
+        let empty = MemoryTable::new(schema);
+        let divergence = compare(1, "select from trades", &full, &empty).unwrap();
+        assert_eq!(divergence.query, "select from trades");
+        assert!(divergence.to_string().contains("seed 1"));
+        assert!(compare(1, "select from trades", &full, &full).is_none());
+    }
+}
@@ -17,6 +17,9 @@ pub enum StorageError {
     #[error("Arrow error: {0}")]
     Arrow(#[from] arrow2::error::Error),
 
+    #[error("Table not found: {0}")]
+    TableNotFound(String),
+
     #[error("Column not found: {0}")]
     ColumnNotFound(String),
 
@@ -29,9 +32,18 @@ pub enum StorageError {
     #[error("Empty table")]
     EmptyTable,
 
+    #[error("Table is open read-only: {0}")]
+    ReadOnly(String),
+
     #[error("Configuration error: {0}")]
     Configuration(String),
 
     #[error("File format error: {0}")]
     FileFormat(String),
+
+    #[error("Ingest error: {0}")]
+    Ingest(String),
+
+    #[error("Query error: {0}")]
+    Query(String),
 }
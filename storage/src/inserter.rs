@@ -0,0 +1,152 @@
+//! Streaming inserter that batches rows into a table
+
+use crate::{
+    error::StorageResult,
+    table::{Row, Table},
+};
+
+/// Default number of rows buffered before an automatic flush
+pub const DEFAULT_BATCH_SIZE: usize = 1024;
+
+/// Buffers incoming rows and appends them to a table in batches
+///
+/// Rows are only durable once `flush` returns; ingestion sources should
+/// checkpoint their position after a successful flush, never before.
+pub struct StreamingInserter {
+    table: Table,
+    buffer: Vec<Row>,
+    batch_size: usize,
+    rows_written: usize,
+}
+
+impl StreamingInserter {
+    /// Create a new inserter with the default batch size
+    pub fn new(table: Table) -> Self {
+        Self::with_batch_size(table, DEFAULT_BATCH_SIZE)
+    }
+
+    /// Create a new inserter that flushes every `batch_size` rows
+    pub fn with_batch_size(table: Table, batch_size: usize) -> Self {
+        let batch_size = batch_size.max(1);
+        Self {
+            table,
+            buffer: Vec::with_capacity(batch_size),
+            batch_size,
+            rows_written: 0,
+        }
+    }
+
+    /// Queue a row, flushing automatically when the batch is full
+    pub fn push(&mut self, row: Row) -> StorageResult<()> {
+        self.buffer.push(row);
+        if self.buffer.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Queue several rows
+    pub fn extend<I>(&mut self, rows: I) -> StorageResult<()>
+    where
+        I: IntoIterator<Item = Row>,
+    {
+        for row in rows {
+            self.push(row)?;
+        }
+        Ok(())
+    }
+
+    /// Append all buffered rows to the table, returning how many were written
+    pub fn flush(&mut self) -> StorageResult<usize> {
+        let _span = tracing::debug_span!(
+            "insert_batch",
+            table = %self.table.schema().name,
+            rows = self.buffer.len(),
+        )
+        .entered();
+        let mut written = 0;
+        for row in self.buffer.drain(..) {
+            self.table.insert(row)?;
+            written += 1;
+        }
+        self.rows_written += written;
+        Ok(written)
+    }
+
+    /// Number of rows waiting to be flushed
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Total number of rows flushed since creation
+    pub fn rows_written(&self) -> usize {
+        self.rows_written
+    }
+
+    /// Get the underlying table
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+
+    /// Flush outstanding rows and return the underlying table
+    pub fn into_table(mut self) -> StorageResult<Table> {
+        self.flush()?;
+        Ok(self.table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::QStoreConfig,
+        schema::{ColumnSchema, SimpleDataType, TableSchema},
+        value::ScalarValue,
+    };
+    use tempfile::TempDir;
+
+    fn create_test_table() -> (Table, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let config = QStoreConfig::new(temp_dir.path(), "ticks".to_string());
+        let schema = TableSchema::new("ticks".to_string()).add_column(ColumnSchema::new_simple(
+            "id".to_string(),
+            SimpleDataType::Int64,
+        ));
+        (Table::new(schema, config).unwrap(), temp_dir)
+    }
+
+    fn row(id: i64) -> Row {
+        let mut row = Row::new();
+        row.insert("id".to_string(), ScalarValue::Int64(id));
+        row
+    }
+
+    #[test]
+    fn test_inserter_batches_rows() {
+        let (table, _temp_dir) = create_test_table();
+        let mut inserter = StreamingInserter::with_batch_size(table, 3);
+
+        inserter.push(row(1)).unwrap();
+        inserter.push(row(2)).unwrap();
+        assert_eq!(inserter.buffered(), 2);
+        assert_eq!(inserter.table().row_count().unwrap(), 0);
+
+        inserter.push(row(3)).unwrap();
+        assert_eq!(inserter.buffered(), 0);
+        assert_eq!(inserter.table().row_count().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_inserter_flush_and_into_table() {
+        let (table, _temp_dir) = create_test_table();
+        let mut inserter = StreamingInserter::with_batch_size(table, 10);
+
+        inserter.extend((0..4).map(row)).unwrap();
+        assert_eq!(inserter.flush().unwrap(), 4);
+        inserter.push(row(4)).unwrap();
+
+        assert_eq!(inserter.rows_written(), 4);
+        let table = inserter.into_table().unwrap();
+        assert_eq!(table.row_count().unwrap(), 5);
+    }
+}
@@ -6,16 +6,42 @@
 //! - Memory-mapped files for zero-copy data access
 //! - Splayed table format (one file per column)
 
+pub mod access;
+pub mod advisor;
+pub mod checkpoint;
 pub mod config;
+pub mod differential;
 pub mod error;
+pub mod ingest;
+pub mod inserter;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod pubsub;
+pub mod query;
+pub mod replay;
 pub mod schema;
+pub mod snapshot;
+pub mod stats;
 pub mod storage;
 pub mod table;
 pub mod value;
+pub mod view;
+pub mod warmup;
 
+pub use access::ColumnAccess;
+pub use advisor::{Advice, Advisor};
+pub use checkpoint::CheckpointStore;
 pub use config::QStoreConfig;
 pub use error::{StorageError, StorageResult};
-pub use schema::{ColumnSchema, TableSchema};
+pub use ingest::IngestSource;
+pub use inserter::StreamingInserter;
+pub use pubsub::Publisher;
+pub use query::{Query, ResultSet};
+pub use schema::{ColumnAttribute, ColumnSchema, TableSchema};
+pub use snapshot::{Catalog, Snapshot};
+pub use stats::StatsEngine;
 pub use storage::SplayedTable;
 pub use table::Table;
 pub use value::ScalarValue;
+pub use view::{Aggregate, MaterializedView, ViewDefinition};
+pub use warmup::WarmupConfig;
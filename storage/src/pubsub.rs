@@ -0,0 +1,73 @@
+//! In-process publish/subscribe for row updates
+//!
+//! Subscribers register interest in a table and receive every row published
+//! to it over a channel. Subscribers that hang up are dropped on the next
+//! publish.
+
+use crate::table::Row;
+use std::{
+    collections::HashMap,
+    sync::mpsc::{Receiver, Sender, channel},
+};
+
+/// Channels of the subscribers to one table
+type Subscribers = Vec<Sender<Row>>;
+
+/// Fans published rows out to per-table subscribers
+#[derive(Default)]
+pub struct Publisher {
+    subscribers: HashMap<String, Subscribers>,
+}
+
+impl Publisher {
+    /// Create a publisher with no subscribers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to rows published to `table`
+    pub fn subscribe(&mut self, table: &str) -> Receiver<Row> {
+        let (sender, receiver) = channel();
+        self.subscribers
+            .entry(table.to_string())
+            .or_default()
+            .push(sender);
+        receiver
+    }
+
+    /// Publish a row to every subscriber of `table`, returning how many received it
+    pub fn publish(&mut self, table: &str, row: &Row) -> usize {
+        let Some(senders) = self.subscribers.get_mut(table) else {
+            return 0;
+        };
+        senders.retain(|sender| sender.send(row.clone()).is_ok());
+        senders.len()
+    }
+
+    /// Number of live subscribers for `table`
+    pub fn subscriber_count(&self, table: &str) -> usize {
+        self.subscribers.get(table).map_or(0, Vec::len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::ScalarValue;
+
+    #[test]
+    fn test_publish_to_subscribers() {
+        let mut publisher = Publisher::new();
+        let trades = publisher.subscribe("trades");
+        let dropped = publisher.subscribe("trades");
+        drop(dropped);
+
+        let mut row = Row::new();
+        row.insert("px".to_string(), ScalarValue::Int64(101));
+
+        assert_eq!(publisher.publish("trades", &row), 1);
+        assert_eq!(publisher.publish("quotes", &row), 0);
+        assert_eq!(publisher.subscriber_count("trades"), 1);
+        assert_eq!(trades.try_recv().unwrap(), row);
+    }
+}
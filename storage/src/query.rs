@@ -0,0 +1,466 @@
+//! Simple selects over a stored table
+//!
+//! Accepts the common subset of SQL and q-sql so that either reads naturally:
+//!
+//! ```text
+//! SELECT sym, px FROM trades WHERE px > 100 AND sym = 'AAPL' LIMIT 10
+//! select sym,px from trades where px>100, sym=`AAPL
+//! ```
+//!
+//! Keywords are case-insensitive. Conditions compare a column with a number,
+//! a quoted string or a symbol, and are joined by `AND` or by commas. An
+//! omitted or `*` column list selects every column in schema order.
+
+use crate::{
+    error::{StorageError, StorageResult},
+    table::{Row, Table},
+    value::ScalarValue,
+    view::compare,
+};
+use std::cmp::Ordering;
+
+/// Selected column names; `None` selects every column
+type ColumnList = Option<Vec<String>>;
+
+/// Comparison applied by a filter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn parse(op: &str) -> Option<Self> {
+        Some(match op {
+            "=" => CompareOp::Eq,
+            "<>" | "!=" => CompareOp::Ne,
+            "<" => CompareOp::Lt,
+            "<=" => CompareOp::Le,
+            ">" => CompareOp::Gt,
+            ">=" => CompareOp::Ge,
+            _ => return None,
+        })
+    }
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering.is_eq(),
+            CompareOp::Ne => ordering.is_ne(),
+            CompareOp::Lt => ordering.is_lt(),
+            CompareOp::Le => ordering.is_le(),
+            CompareOp::Gt => ordering.is_gt(),
+            CompareOp::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+/// A `column op literal` condition
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub column: String,
+    pub op: CompareOp,
+    pub value: ScalarValue,
+}
+
+impl Filter {
+    /// Whether `row` satisfies the condition; nulls and mismatched types never do
+    pub fn matches(&self, row: &Row) -> bool {
+        row.get(&self.column)
+            .and_then(|value| compare(value, &self.value))
+            .is_some_and(|ordering| self.op.holds(ordering))
+    }
+}
+
+/// A parsed select
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    /// Table to read
+    pub table: String,
+    /// Columns to return; `None` for all of them
+    pub columns: ColumnList,
+    /// Conditions every returned row satisfies
+    pub filters: Vec<Filter>,
+    /// Maximum number of rows to return
+    pub limit: Option<usize>,
+}
+
+/// Rows returned by a query, in table order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<ScalarValue>>,
+    /// Rows read from the table to produce the result
+    pub scanned: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(String),
+    Text(String),
+    Op(String),
+    Comma,
+    Star,
+}
+
+fn error(message: impl Into<String>) -> StorageError {
+    StorageError::Query(message.into())
+}
+
+fn tokenize(source: &str) -> StorageResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            ',' => {
+                chars.next();
+                tokens.push(Token::Comma);
+            }
+            '*' => {
+                chars.next();
+                tokens.push(Token::Star);
+            }
+            ';' => {
+                // A trailing semicolon ends the statement
+                chars.next();
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(ch) => text.push(ch),
+                        None => return Err(error("unterminated string")),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            '`' => {
+                chars.next();
+                let mut text = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if !(ch.is_alphanumeric() || matches!(ch, '_' | '.' | ':')) {
+                        break;
+                    }
+                    text.push(ch);
+                    chars.next();
+                }
+                tokens.push(Token::Text(text));
+            }
+            '=' | '<' | '>' | '!' => {
+                chars.next();
+                let mut op = c.to_string();
+                if let Some(&next) = chars.peek()
+                    && matches!((c, next), ('<', '>') | ('<', '=') | ('>', '=') | ('!', '='))
+                {
+                    op.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Op(op));
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut number = String::new();
+                number.push(c);
+                chars.next();
+                while let Some(&ch) = chars.peek() {
+                    if !(ch.is_ascii_digit() || ch == '.') {
+                        break;
+                    }
+                    number.push(ch);
+                    chars.next();
+                }
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if !(ch.is_alphanumeric() || ch == '_') {
+                        break;
+                    }
+                    word.push(ch);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+            other => return Err(error(format!("unexpected character '{}'", other))),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Cursor over the tokens of one query
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.at_keyword(keyword);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn name(&mut self, what: &str) -> StorageResult<String> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word),
+            other => Err(error(format!("expected {}, found {:?}", what, other))),
+        }
+    }
+
+    fn columns(&mut self) -> StorageResult<ColumnList> {
+        if self.peek() == Some(&Token::Star) {
+            self.position += 1;
+            return Ok(None);
+        }
+        let mut columns = Vec::new();
+        while !self.at_keyword("from") {
+            if !columns.is_empty() && self.next() != Some(Token::Comma) {
+                return Err(error("expected ',' between columns"));
+            }
+            columns.push(self.name("column name")?);
+        }
+        Ok(if columns.is_empty() {
+            None
+        } else {
+            Some(columns)
+        })
+    }
+
+    fn literal(&mut self) -> StorageResult<ScalarValue> {
+        match self.next() {
+            Some(Token::Text(text)) => Ok(ScalarValue::Utf8(text)),
+            Some(Token::Number(number)) => number
+                .parse()
+                .map(ScalarValue::Int64)
+                .or_else(|_| number.parse().map(ScalarValue::Float64))
+                .map_err(|_| error(format!("invalid number {}", number))),
+            other => Err(error(format!("expected a literal, found {:?}", other))),
+        }
+    }
+
+    fn filter(&mut self) -> StorageResult<Filter> {
+        let column = self.name("column name")?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => {
+                CompareOp::parse(&op).ok_or_else(|| error(format!("unknown operator {}", op)))?
+            }
+            other => return Err(error(format!("expected an operator, found {:?}", other))),
+        };
+        let value = self.literal()?;
+        Ok(Filter { column, op, value })
+    }
+}
+
+impl Query {
+    /// Parse a select in SQL or q-sql form
+    pub fn parse(source: &str) -> StorageResult<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+        if !parser.keyword("select") {
+            return Err(error("only select queries are supported"));
+        }
+        let columns = parser.columns()?;
+        if !parser.keyword("from") {
+            return Err(error("expected from"));
+        }
+        let table = parser.name("table name")?;
+
+        let mut filters = Vec::new();
+        if parser.keyword("where") {
+            filters.push(parser.filter()?);
+            while parser.keyword("and") || parser.peek() == Some(&Token::Comma) {
+                if parser.peek() == Some(&Token::Comma) {
+                    parser.position += 1;
+                }
+                filters.push(parser.filter()?);
+            }
+        }
+
+        let limit = if parser.keyword("limit") {
+            match parser.next() {
+                Some(Token::Number(n)) => Some(
+                    n.parse()
+                        .map_err(|_| error(format!("invalid limit {}", n)))?,
+                ),
+                other => return Err(error(format!("expected a limit, found {:?}", other))),
+            }
+        } else {
+            None
+        };
+
+        if let Some(token) = parser.peek() {
+            return Err(error(format!("unexpected {:?}", token)));
+        }
+        Ok(Self {
+            table,
+            columns,
+            filters,
+            limit,
+        })
+    }
+
+    /// Run the query against `table`
+    pub fn execute(&self, table: &Table) -> StorageResult<ResultSet> {
+        let schema = table.schema();
+        let span = tracing::debug_span!(
+            "query",
+            table = %schema.name,
+            filters = self.filters.len(),
+            returned = tracing::field::Empty,
+        )
+        .entered();
+        let columns: Vec<String> = match &self.columns {
+            Some(columns) => columns.clone(),
+            None => schema
+                .column_names()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        };
+        let referenced = columns.iter().chain(self.filters.iter().map(|f| &f.column));
+        for column in referenced {
+            if schema.get_column(column).is_none() {
+                return Err(StorageError::ColumnNotFound(column.clone()));
+            }
+        }
+
+        // `<>` matches most rows, so no index would help it
+        for filter in self.filters.iter().filter(|f| f.op != CompareOp::Ne) {
+            table.record_filter(&filter.column, filter.op == CompareOp::Eq);
+        }
+
+        let scanned = table.row_count()?;
+        // Read only the columns the query touches
+        let mut touched: Vec<&str> = Vec::new();
+        for column in columns.iter().chain(self.filters.iter().map(|f| &f.column)) {
+            if !touched.contains(&column.as_str()) {
+                touched.push(column);
+            }
+        }
+        let mut matched =
+            table.filter_columns(&touched, |row| self.filters.iter().all(|f| f.matches(row)))?;
+        if let Some(limit) = self.limit {
+            matched.truncate(limit);
+        }
+        let rows = matched
+            .into_iter()
+            .map(|row| {
+                // A column may be selected more than once, so copy rather than take
+                columns
+                    .iter()
+                    .map(|c| row.get(c).cloned().unwrap_or(ScalarValue::Null))
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        span.record("returned", rows.len());
+        Ok(ResultSet {
+            columns,
+            rows,
+            scanned,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{ColumnSchema, SimpleDataType};
+    use crate::{config::QStoreConfig, schema::TableSchema};
+    use tempfile::TempDir;
+
+    fn trades(dir: &TempDir) -> Table {
+        let schema = TableSchema::new("trades".to_string())
+            .add_column(ColumnSchema::new_simple("sym".into(), SimpleDataType::Utf8))
+            .add_column(ColumnSchema::new_simple("px".into(), SimpleDataType::Int64));
+        let mut table = Table::new(schema, QStoreConfig::new(dir.path(), "trades".into())).unwrap();
+        for (sym, px) in [("AAPL", 101), ("MSFT", 250), ("AAPL", 99), ("AAPL", 120)] {
+            let mut row = Row::new();
+            row.insert("sym".into(), ScalarValue::Utf8(sym.into()));
+            row.insert("px".into(), ScalarValue::Int64(px));
+            table.insert(row).unwrap();
+        }
+        table
+    }
+
+    #[test]
+    fn test_sql_and_qsql_forms_agree() {
+        let sql = Query::parse("SELECT px FROM trades WHERE sym = 'AAPL' AND px > 100").unwrap();
+        let q = Query::parse("select px from trades where sym=`AAPL, px>100").unwrap();
+        assert_eq!(sql, q);
+        assert_eq!(sql.table, "trades");
+        assert_eq!(sql.filters.len(), 2);
+    }
+
+    #[test]
+    fn test_execute() {
+        let dir = TempDir::new().unwrap();
+        let table = trades(&dir);
+
+        let result = Query::parse("select px from trades where sym=`AAPL, px>100")
+            .unwrap()
+            .execute(&table)
+            .unwrap();
+        assert_eq!(result.columns, ["px"]);
+        assert_eq!(
+            result.rows,
+            [[ScalarValue::Int64(101)], [ScalarValue::Int64(120)]]
+        );
+
+        let result = Query::parse("select * from trades limit 1")
+            .unwrap()
+            .execute(&table)
+            .unwrap();
+        assert_eq!(result.columns, ["sym", "px"]);
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.scanned, 4);
+
+        let twice = Query::parse("select px,px from trades limit 1")
+            .unwrap()
+            .execute(&table)
+            .unwrap();
+        assert_eq!(
+            twice.rows,
+            [[ScalarValue::Int64(101), ScalarValue::Int64(101)]]
+        );
+
+        let missing = Query::parse("select size from trades")
+            .unwrap()
+            .execute(&table);
+        assert!(matches!(missing, Err(StorageError::ColumnNotFound(c)) if c == "size"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Query::parse("delete from trades").is_err());
+        assert!(Query::parse("select px trades").is_err());
+        assert!(Query::parse("select px from trades where px ~ 1").is_err());
+        assert!(Query::parse("select px from trades where sym = 'AAPL").is_err());
+    }
+}
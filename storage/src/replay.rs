@@ -0,0 +1,208 @@
+//! Replay historical partitions as a simulated live feed
+//!
+//! A database root holds one directory per partition (for example
+//! `db/2024.01.02/trades`), and partition names sort chronologically. Replay
+//! walks the partitions in order, sorts each partition's rows by their `time`
+//! column and publishes them, optionally pacing publication to the original
+//! inter-arrival times scaled by a speed factor.
+
+use crate::{
+    config::QStoreConfig,
+    error::{StorageError, StorageResult},
+    pubsub::Publisher,
+    storage::SplayedTable,
+    table::Row,
+    value::ScalarValue,
+};
+use std::{
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// Name of the column rows are ordered and paced by
+pub const TIME_COLUMN: &str = "time";
+
+/// How fast to replay relative to the recorded timestamps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Publish as fast as possible, ignoring timestamps
+    Max,
+    /// Multiple of real time: 1.0 is real time, 10.0 is ten times faster
+    Factor(f64),
+}
+
+impl ReplaySpeed {
+    /// Parse `10x`, `0.5x`, `10` or `max`
+    pub fn parse(text: &str) -> StorageResult<Self> {
+        let text = text.trim();
+        if text.eq_ignore_ascii_case("max") {
+            return Ok(ReplaySpeed::Max);
+        }
+        let number = text.strip_suffix(['x', 'X']).unwrap_or(text);
+        match number.parse::<f64>() {
+            Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(ReplaySpeed::Factor(factor)),
+            _ => Err(StorageError::Configuration(format!(
+                "Invalid replay speed: {}",
+                text
+            ))),
+        }
+    }
+
+    /// Wall-clock delay for a gap of `nanos` recorded nanoseconds
+    fn delay(&self, nanos: i64) -> Option<Duration> {
+        match self {
+            ReplaySpeed::Max => None,
+            ReplaySpeed::Factor(factor) if nanos > 0 => {
+                Some(Duration::from_secs_f64(nanos as f64 / 1e9 / factor))
+            }
+            ReplaySpeed::Factor(_) => None,
+        }
+    }
+}
+
+/// Replays one table from a partitioned database
+pub struct Replay {
+    db_root: PathBuf,
+    table: String,
+    speed: ReplaySpeed,
+}
+
+impl Replay {
+    /// Create a replay of `table` under `db_root`
+    pub fn new<P: Into<PathBuf>>(db_root: P, table: impl Into<String>, speed: ReplaySpeed) -> Self {
+        Self {
+            db_root: db_root.into(),
+            table: table.into(),
+            speed,
+        }
+    }
+
+    /// Partition directories holding the table, in chronological order
+    pub fn partitions(&self) -> StorageResult<Vec<PathBuf>> {
+        partitions(&self.db_root, &self.table)
+    }
+
+    /// Publish every row to `publisher`, returning the number of rows replayed
+    pub fn run(&self, publisher: &mut Publisher) -> StorageResult<usize> {
+        let mut replayed = 0;
+        let mut last_time = None;
+
+        for partition in self.partitions()? {
+            for row in load_sorted(&partition, &self.table)? {
+                let time = row_time(&row);
+                if let (Some(previous), Some(current)) = (last_time, time)
+                    && let Some(delay) = self.speed.delay(current - previous)
+                {
+                    thread::sleep(delay);
+                }
+                if time.is_some() {
+                    last_time = time;
+                }
+
+                publisher.publish(&self.table, &row);
+                replayed += 1;
+            }
+        }
+
+        Ok(replayed)
+    }
+}
+
+/// Partition directories under `db_root` that contain `table`, sorted by name
+pub fn partitions(db_root: &Path, table: &str) -> StorageResult<Vec<PathBuf>> {
+    if !db_root.is_dir() {
+        return Err(StorageError::Configuration(format!(
+            "Database directory does not exist: {:?}",
+            db_root
+        )));
+    }
+
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(db_root)? {
+        let path = entry?.path();
+        if path.join(table).is_dir() {
+            found.push(path);
+        }
+    }
+    found.sort();
+
+    if found.is_empty() {
+        return Err(StorageError::Configuration(format!(
+            "No partitions of {} found under {:?}",
+            table, db_root
+        )));
+    }
+    Ok(found)
+}
+
+/// Load all rows of `table` in `partition`, stably sorted by time
+fn load_sorted(partition: &Path, table: &str) -> StorageResult<Vec<Row>> {
+    let storage = SplayedTable::open(QStoreConfig::new(partition, table.to_string()))?;
+    let mut rows = (0..storage.count()?)
+        .map(|i| storage.get(i))
+        .collect::<StorageResult<Vec<Row>>>()?;
+    rows.sort_by_key(|row| row_time(row).unwrap_or(i64::MIN));
+    Ok(rows)
+}
+
+fn row_time(row: &Row) -> Option<i64> {
+    match row.get(TIME_COLUMN)? {
+        ScalarValue::Timestamp(nanos) => Some(*nanos),
+        other => other.as_i64(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_partition(root: &Path, partition: &str, times: &[i64]) {
+        let mut table = SplayedTable::new(QStoreConfig::new(
+            root.join(partition),
+            "trades".to_string(),
+        ))
+        .unwrap();
+        for &time in times {
+            let mut row = Row::new();
+            row.insert(TIME_COLUMN.to_string(), ScalarValue::Timestamp(time));
+            row.insert("px".to_string(), ScalarValue::Int64(time * 10));
+            table.put(row).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_replay_speed_parse() {
+        assert_eq!(
+            ReplaySpeed::parse("10x").unwrap(),
+            ReplaySpeed::Factor(10.0)
+        );
+        assert_eq!(ReplaySpeed::parse("0.5").unwrap(), ReplaySpeed::Factor(0.5));
+        assert_eq!(ReplaySpeed::parse("MAX").unwrap(), ReplaySpeed::Max);
+        assert!(ReplaySpeed::parse("0x").is_err());
+        assert!(ReplaySpeed::parse("fast").is_err());
+    }
+
+    #[test]
+    fn test_replay_in_time_order() {
+        let temp_dir = TempDir::new().unwrap();
+        write_partition(temp_dir.path(), "2024.01.02", &[5, 4]);
+        write_partition(temp_dir.path(), "2024.01.01", &[3, 1, 2]);
+
+        let mut publisher = Publisher::new();
+        let feed = publisher.subscribe("trades");
+        let replay = Replay::new(temp_dir.path(), "trades", ReplaySpeed::Max);
+        assert_eq!(replay.run(&mut publisher).unwrap(), 5);
+
+        let times: Vec<i64> = feed.try_iter().filter_map(|row| row_time(&row)).collect();
+        assert_eq!(times, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_replay_missing_table() {
+        let temp_dir = TempDir::new().unwrap();
+        let replay = Replay::new(temp_dir.path(), "trades", ReplaySpeed::Max);
+        assert!(replay.partitions().is_err());
+    }
+}
@@ -71,6 +71,45 @@ impl From<&DataType> for SimpleDataType {
     }
 }
 
+/// Layout guarantee declared for a column, as in q's `s#`, `u#`, `p#`, `g#`
+///
+/// Attributes are recorded with the schema as hints for lookups and are not
+/// enforced on insert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColumnAttribute {
+    /// Values are in ascending order
+    Sorted,
+    /// Every value is distinct
+    Unique,
+    /// Equal values are stored contiguously
+    Parted,
+    /// Values are indexed by a hash of their positions
+    Grouped,
+}
+
+impl ColumnAttribute {
+    /// Parse an attribute name such as `sorted` or its q letter `s`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sorted" | "s" => Some(ColumnAttribute::Sorted),
+            "unique" | "u" => Some(ColumnAttribute::Unique),
+            "parted" | "p" => Some(ColumnAttribute::Parted),
+            "grouped" | "g" => Some(ColumnAttribute::Grouped),
+            _ => None,
+        }
+    }
+
+    /// Lowercase name of the attribute
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColumnAttribute::Sorted => "sorted",
+            ColumnAttribute::Unique => "unique",
+            ColumnAttribute::Parted => "parted",
+            ColumnAttribute::Grouped => "grouped",
+        }
+    }
+}
+
 /// Schema for a single column
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ColumnSchema {
@@ -80,6 +119,8 @@ pub struct ColumnSchema {
     pub data_type: SimpleDataType,
     /// Whether the column allows null values
     pub nullable: bool,
+    /// Declared layout attribute, if any
+    pub attribute: Option<ColumnAttribute>,
     /// Optional metadata
     pub metadata: HashMap<String, String>,
 }
@@ -91,6 +132,7 @@ impl ColumnSchema {
             name,
             data_type: SimpleDataType::from(&data_type),
             nullable: true,
+            attribute: None,
             metadata: HashMap::new(),
         }
     }
@@ -101,6 +143,7 @@ impl ColumnSchema {
             name,
             data_type,
             nullable: true,
+            attribute: None,
             metadata: HashMap::new(),
         }
     }
@@ -116,6 +159,12 @@ impl ColumnSchema {
         self
     }
 
+    /// Set the layout attribute
+    pub fn with_attribute(mut self, attribute: ColumnAttribute) -> Self {
+        self.attribute = Some(attribute);
+        self
+    }
+
     /// Add metadata
     pub fn with_metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
         self.metadata.insert(key.into(), value.into());
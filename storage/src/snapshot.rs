@@ -0,0 +1,278 @@
+//! Snapshot-consistent reads across several tables
+//!
+//! Tables are append-only, so the state of a table at any moment is fully
+//! described by its row count. A [`Catalog`] serializes inserts behind one
+//! lock and bumps a logical version with each of them; [`Catalog::snapshot`]
+//! records the row count of every requested table under that same lock. Reads
+//! through the resulting [`Snapshot`] never see rows beyond those counts, so a
+//! query joining trades and quotes sees both tables as of the same version
+//! while ingestion carries on.
+//!
+//! Materialized views registered with the catalog are updated by the same
+//! insert that appends to their source table.
+
+use crate::{
+    error::{StorageError, StorageResult},
+    table::{Row, Table},
+    view::MaterializedView,
+};
+use std::{
+    collections::HashMap,
+    sync::{PoisonError, RwLock},
+};
+
+/// Tables and the version counter guarded by the catalog lock
+#[derive(Default)]
+struct CatalogState {
+    tables: HashMap<String, Table>,
+    views: HashMap<String, MaterializedView>,
+    version: u64,
+}
+
+/// A set of named tables that can be read at a consistent version
+#[derive(Default)]
+pub struct Catalog {
+    state: RwLock<CatalogState>,
+}
+
+impl Catalog {
+    /// Create an empty catalog
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `table` under `name`, replacing any table of that name
+    pub fn register(&self, name: impl Into<String>, table: Table) {
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        state.tables.insert(name.into(), table);
+        state.version += 1;
+    }
+
+    /// Register a materialized view under `name`, fed by inserts to its source
+    pub fn register_view(&self, name: impl Into<String>, view: MaterializedView) {
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        state.views.insert(name.into(), view);
+        state.version += 1;
+    }
+
+    /// Current rows of the materialized view `name`
+    pub fn view_rows(&self, name: &str) -> StorageResult<Vec<Row>> {
+        let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        state
+            .views
+            .get(name)
+            .map(MaterializedView::rows)
+            .ok_or_else(|| StorageError::TableNotFound(name.to_string()))
+    }
+
+    /// Names of the registered tables, sorted
+    pub fn table_names(&self) -> Vec<String> {
+        let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        let mut names: Vec<String> = state.tables.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Current logical version; it increases with every insert
+    pub fn version(&self) -> u64 {
+        self.state
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .version
+    }
+
+    /// Insert a row into `table`, returning the version that includes it
+    pub fn insert(&self, table: &str, row: Row) -> StorageResult<u64> {
+        self.insert_all(table, vec![row])
+    }
+
+    /// Insert rows into `table` atomically with respect to snapshots
+    ///
+    /// Views over `table` are updated before the lock is released.
+    pub fn insert_all(&self, table: &str, rows: Vec<Row>) -> StorageResult<u64> {
+        let _span = tracing::debug_span!("insert_batch", table, rows = rows.len()).entered();
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        let target = state
+            .tables
+            .get_mut(table)
+            .ok_or_else(|| StorageError::TableNotFound(table.to_string()))?;
+        for row in &rows {
+            target.insert(row.clone())?;
+        }
+        for view in state.views.values_mut() {
+            if view.source() == table {
+                view.apply_all(&rows)?;
+            }
+        }
+        state.version += 1;
+        Ok(state.version)
+    }
+
+    /// Pin `tables` at the current version
+    pub fn snapshot(&self, tables: &[&str]) -> StorageResult<Snapshot<'_>> {
+        let state = self.state.read().unwrap_or_else(PoisonError::into_inner);
+        let mut row_counts = HashMap::new();
+        for &name in tables {
+            let table = state
+                .tables
+                .get(name)
+                .ok_or_else(|| StorageError::TableNotFound(name.to_string()))?;
+            row_counts.insert(name.to_string(), table.row_count()?);
+        }
+        Ok(Snapshot {
+            catalog: self,
+            version: state.version,
+            row_counts,
+        })
+    }
+}
+
+/// A read-only view of several tables pinned at one catalog version
+pub struct Snapshot<'a> {
+    catalog: &'a Catalog,
+    version: u64,
+    row_counts: HashMap<String, usize>,
+}
+
+impl Snapshot<'_> {
+    /// Catalog version the snapshot was taken at
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Number of rows visible in `table`
+    pub fn row_count(&self, table: &str) -> StorageResult<usize> {
+        self.row_counts
+            .get(table)
+            .copied()
+            .ok_or_else(|| StorageError::TableNotFound(table.to_string()))
+    }
+
+    /// Row `index` of `table` as of the snapshot
+    pub fn get(&self, table: &str, index: usize) -> StorageResult<Row> {
+        let max = self.row_count(table)?;
+        if index >= max {
+            return Err(StorageError::InvalidRowIndex { index, max });
+        }
+        self.with_table(table, |t| t.get(index))
+    }
+
+    /// All rows of `table` visible in the snapshot
+    pub fn rows(&self, table: &str) -> StorageResult<Vec<Row>> {
+        let count = self.row_count(table)?;
+        self.with_table(table, |t| (0..count).map(|i| t.get(i)).collect())
+    }
+
+    /// Rows of `table` visible in the snapshot that satisfy `predicate`
+    pub fn filter<F>(&self, table: &str, predicate: F) -> StorageResult<Vec<Row>>
+    where
+        F: Fn(&Row) -> bool,
+    {
+        Ok(self
+            .rows(table)?
+            .into_iter()
+            .filter(|row| predicate(row))
+            .collect())
+    }
+
+    fn with_table<T>(
+        &self,
+        table: &str,
+        read: impl FnOnce(&Table) -> StorageResult<T>,
+    ) -> StorageResult<T> {
+        // Rows below the pinned count are immutable, so a short read lock is
+        // enough; writers may append in between reads
+        let state = self
+            .catalog
+            .state
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let table = state
+            .tables
+            .get(table)
+            .ok_or_else(|| StorageError::TableNotFound(table.to_string()))?;
+        read(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::QStoreConfig,
+        schema::{ColumnSchema, SimpleDataType, TableSchema},
+        value::ScalarValue,
+    };
+    use tempfile::TempDir;
+
+    fn price_table(root: &std::path::Path, name: &str) -> Table {
+        let schema = TableSchema::new(name.to_string()).add_column(
+            ColumnSchema::new_simple("px".to_string(), SimpleDataType::Int64).with_nullable(false),
+        );
+        Table::new(schema, QStoreConfig::new(root, name.to_string())).unwrap()
+    }
+
+    fn px(value: i64) -> Row {
+        let mut row = Row::new();
+        row.insert("px".to_string(), ScalarValue::Int64(value));
+        row
+    }
+
+    #[test]
+    fn test_snapshot_pins_all_tables() {
+        let temp_dir = TempDir::new().unwrap();
+        let catalog = Catalog::new();
+        catalog.register("trades", price_table(temp_dir.path(), "trades"));
+        catalog.register("quotes", price_table(temp_dir.path(), "quotes"));
+        catalog.insert("trades", px(1)).unwrap();
+        catalog.insert("quotes", px(2)).unwrap();
+
+        let snapshot = catalog.snapshot(&["trades", "quotes"]).unwrap();
+        catalog.insert_all("trades", vec![px(3), px(4)]).unwrap();
+        catalog.insert("quotes", px(5)).unwrap();
+
+        assert!(catalog.version() > snapshot.version());
+        assert_eq!(snapshot.rows("trades").unwrap(), vec![px(1)]);
+        assert_eq!(snapshot.rows("quotes").unwrap(), vec![px(2)]);
+        assert!(snapshot.get("trades", 1).is_err());
+
+        let latest = catalog.snapshot(&["trades"]).unwrap();
+        assert_eq!(latest.row_count("trades").unwrap(), 3);
+        assert!(latest.row_count("quotes").is_err());
+    }
+
+    #[test]
+    fn test_insert_updates_views() {
+        use crate::view::{Aggregate, ViewDefinition};
+
+        let temp_dir = TempDir::new().unwrap();
+        let catalog = Catalog::new();
+        catalog.register("trades", price_table(temp_dir.path(), "trades"));
+        let definition =
+            ViewDefinition::new("trades").aggregate("total", Aggregate::Sum("px".into()));
+        let view = MaterializedView::open(
+            definition,
+            QStoreConfig::new(temp_dir.path(), "totals".to_string()),
+        )
+        .unwrap();
+        catalog.register_view("totals", view);
+
+        catalog.insert_all("trades", vec![px(1), px(2)]).unwrap();
+        catalog.insert("trades", px(3)).unwrap();
+        assert_eq!(
+            catalog.view_rows("totals").unwrap()[0]["total"],
+            ScalarValue::Int64(6)
+        );
+        assert!(catalog.view_rows("missing").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_unknown_table() {
+        let catalog = Catalog::new();
+        assert!(matches!(
+            catalog.snapshot(&["trades"]),
+            Err(StorageError::TableNotFound(_))
+        ));
+        assert!(catalog.insert("trades", Row::new()).is_err());
+    }
+}
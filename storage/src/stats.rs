@@ -0,0 +1,207 @@
+//! Incremental group-by state for real-time analytics
+//!
+//! A [`StatsEngine`] folds rows into running aggregates keyed by the group
+//! columns of a [`ViewDefinition`] (for example the last price and traded
+//! volume per symbol) and serves the current state as an in-memory keyed
+//! table. It consumes the row stream of a [`Publisher`](crate::Publisher)
+//! subscription, which makes it the core of a stats process that sits next to
+//! the real-time database.
+
+use crate::{error::StorageResult, table::Row, value::ScalarValue, view::ViewDefinition};
+use std::{collections::HashMap, sync::mpsc::Receiver};
+
+/// Encoded group key mapped to the group's position
+type GroupIndex = HashMap<Vec<u8>, usize>;
+
+/// One group: its key and running aggregates
+struct Group {
+    key: Vec<ScalarValue>,
+    values: Vec<ScalarValue>,
+}
+
+/// Running aggregates per group, updated one row at a time
+pub struct StatsEngine {
+    definition: ViewDefinition,
+    groups: Vec<Group>,
+    index: GroupIndex,
+}
+
+impl StatsEngine {
+    /// Create an empty engine computing `definition`
+    pub fn new(definition: ViewDefinition) -> StorageResult<Self> {
+        definition.validate()?;
+        Ok(Self {
+            definition,
+            groups: Vec::new(),
+            index: HashMap::new(),
+        })
+    }
+
+    /// The aggregation being computed
+    pub fn definition(&self) -> &ViewDefinition {
+        &self.definition
+    }
+
+    /// Number of groups seen so far
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Whether no rows have been seen yet
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Fold one row into its group
+    pub fn apply(&mut self, row: &Row) -> StorageResult<()> {
+        self.apply_tracked(std::slice::from_ref(row)).map(|_| ())
+    }
+
+    /// Fold rows into their groups
+    pub fn apply_all(&mut self, rows: &[Row]) -> StorageResult<()> {
+        self.apply_tracked(rows).map(|_| ())
+    }
+
+    /// Fold every row already waiting on `feed` without blocking
+    ///
+    /// Returns the number of rows consumed.
+    pub fn consume(&mut self, feed: &Receiver<Row>) -> StorageResult<usize> {
+        let rows: Vec<Row> = feed.try_iter().collect();
+        self.apply_all(&rows)?;
+        Ok(rows.len())
+    }
+
+    /// Fold rows from `feed` until every publisher has hung up
+    pub fn run(&mut self, feed: &Receiver<Row>) -> StorageResult<usize> {
+        let mut consumed = 0;
+        for row in feed {
+            self.apply(&row)?;
+            consumed += 1;
+        }
+        Ok(consumed)
+    }
+
+    /// Current row of the group with the given key values, in key column order
+    pub fn get(&self, key: &[ScalarValue]) -> StorageResult<Option<Row>> {
+        let encoded = bincode::serialize(key)?;
+        Ok(self.index.get(&encoded).map(|&p| self.row_at(p)))
+    }
+
+    /// Current state, one row per group in order of first appearance
+    pub fn rows(&self) -> Vec<Row> {
+        (0..self.groups.len()).map(|p| self.row_at(p)).collect()
+    }
+
+    /// Fold rows in, returning the positions of the groups they touched
+    pub(crate) fn apply_tracked(&mut self, rows: &[Row]) -> StorageResult<Vec<usize>> {
+        let mut touched = Vec::new();
+        for row in rows {
+            let key = self.definition.key(row)?;
+            let position = self.group_position(key)?;
+            let group = &mut self.groups[position];
+            for ((_, aggregate), state) in self.definition.aggregates.iter().zip(&mut group.values)
+            {
+                aggregate.update(state, aggregate.column().and_then(|c| row.get(c)));
+            }
+            if !touched.contains(&position) {
+                touched.push(position);
+            }
+        }
+        Ok(touched)
+    }
+
+    /// Reinstate a group from one of its own output rows
+    pub(crate) fn restore(&mut self, row: &Row) -> StorageResult<()> {
+        let column = |name: &str| row.get(name).cloned().unwrap_or(ScalarValue::Null);
+        let key = self.definition.key_columns().map(column).collect();
+        let values = self
+            .definition
+            .aggregates
+            .iter()
+            .map(|(name, _)| column(name))
+            .collect();
+        let position = self.group_position(key)?;
+        self.groups[position].values = values;
+        Ok(())
+    }
+
+    /// Output row of the group at `position`
+    pub(crate) fn row_at(&self, position: usize) -> Row {
+        let group = &self.groups[position];
+        let keys = self.definition.key_columns().zip(group.key.iter());
+        let values = self
+            .definition
+            .aggregates
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .zip(group.values.iter());
+        keys.chain(values)
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    /// Position of the group for `key`, creating an empty group if needed
+    fn group_position(&mut self, key: Vec<ScalarValue>) -> StorageResult<usize> {
+        let encoded = bincode::serialize(&key)?;
+        if let Some(&position) = self.index.get(&encoded) {
+            return Ok(position);
+        }
+        let position = self.groups.len();
+        self.groups.push(Group {
+            key,
+            values: vec![ScalarValue::Null; self.definition.aggregates.len()],
+        });
+        self.index.insert(encoded, position);
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pubsub::Publisher, view::Aggregate};
+
+    fn trade(sym: &str, px: i64, size: i64) -> Row {
+        let mut row = Row::new();
+        row.insert("sym".to_string(), ScalarValue::Utf8(sym.to_string()));
+        row.insert("px".to_string(), ScalarValue::Int64(px));
+        row.insert("size".to_string(), ScalarValue::Int64(size));
+        row
+    }
+
+    fn sym(name: &str) -> Vec<ScalarValue> {
+        vec![ScalarValue::Utf8(name.to_string())]
+    }
+
+    #[test]
+    fn test_stats_from_subscription() {
+        let definition = ViewDefinition::new("trades")
+            .group_by("sym")
+            .aggregate("last", Aggregate::Last("px".into()))
+            .aggregate("high", Aggregate::Max("px".into()))
+            .aggregate("volume", Aggregate::Sum("size".into()))
+            .aggregate("count", Aggregate::Count);
+        let mut stats = StatsEngine::new(definition).unwrap();
+
+        let mut publisher = Publisher::new();
+        let feed = publisher.subscribe("trades");
+        publisher.publish("trades", &trade("AAPL", 100, 10));
+        publisher.publish("trades", &trade("MSFT", 300, 5));
+        publisher.publish("trades", &trade("AAPL", 99, 20));
+        assert_eq!(stats.consume(&feed).unwrap(), 3);
+        assert_eq!(stats.consume(&feed).unwrap(), 0);
+
+        let aapl = stats.get(&sym("AAPL")).unwrap().unwrap();
+        assert_eq!(aapl["last"], ScalarValue::Int64(99));
+        assert_eq!(aapl["high"], ScalarValue::Int64(100));
+        assert_eq!(aapl["volume"], ScalarValue::Int64(30));
+        assert_eq!(aapl["count"], ScalarValue::Int64(2));
+        assert!(stats.get(&sym("GOOG")).unwrap().is_none());
+
+        publisher.publish("trades", &trade("GOOG", 150, 1));
+        drop(publisher);
+        assert_eq!(stats.run(&feed).unwrap(), 1);
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats.rows()[1]["sym"], ScalarValue::Utf8("MSFT".into()));
+    }
+}
@@ -1,6 +1,7 @@
 //! Core storage implementation with memory-mapped splayed tables
 
 use crate::{
+    access::{AccessCounter, ColumnAccess},
     config::QStoreConfig,
     error::{StorageError, StorageResult},
     table::Row,
@@ -14,6 +15,10 @@ use std::{
     path::PathBuf,
 };
 
+/// Bytes between the addresses [`SplayedTable::preload`] touches; pages are
+/// at least this large on every supported platform
+const PAGE_SIZE: usize = 4096;
+
 /// Column data stored in memory-mapped files
 struct ColumnData {
     /// Memory-mapped file for reading
@@ -31,11 +36,15 @@ pub struct SplayedTable {
     config: QStoreConfig,
     columns: HashMap<String, ColumnData>,
     row_count: usize,
+    access: AccessCounter,
 }
 
 impl SplayedTable {
     /// Create a new splayed table
     pub fn new(config: QStoreConfig) -> StorageResult<Self> {
+        if config.read_only {
+            return Err(StorageError::ReadOnly(config.table_name));
+        }
         // Create table directory
         let table_path = config.table_path();
         create_dir_all(&table_path)?;
@@ -44,6 +53,7 @@ impl SplayedTable {
             config,
             columns: HashMap::new(),
             row_count: 0,
+            access: AccessCounter::default(),
         })
     }
 
@@ -65,30 +75,35 @@ impl SplayedTable {
             let entry = entry?;
             let path = entry.path();
 
-            if path.is_file() {
-                if let Some(column_name) = path.file_name().and_then(|n| n.to_str()) {
-                    let file = OpenOptions::new().read(true).append(true).open(&path)?;
-
-                    // Count entries in this column file to determine row count
-                    let count = Self::count_entries_in_file(&path)?;
-                    row_count = row_count.max(count);
-
-                    let mmap = if file.metadata()?.len() > 0 {
-                        Some(unsafe { MmapOptions::new().map(&file)? })
-                    } else {
-                        None
-                    };
-
-                    columns.insert(
-                        column_name.to_string(),
-                        ColumnData {
-                            mmap,
-                            file,
-                            path: path.clone(),
-                            count,
-                        },
-                    );
-                }
+            // Dotfiles hold table metadata such as the schema, not columns
+            if path.is_file()
+                && let Some(column_name) = path.file_name().and_then(|n| n.to_str())
+                && !column_name.starts_with('.')
+            {
+                let file = OpenOptions::new()
+                    .read(true)
+                    .append(!config.read_only)
+                    .open(&path)?;
+
+                // Count entries in this column file to determine row count
+                let count = Self::count_entries_in_file(&path)?;
+                row_count = row_count.max(count);
+
+                let mmap = if file.metadata()?.len() > 0 {
+                    Some(unsafe { MmapOptions::new().map(&file)? })
+                } else {
+                    None
+                };
+
+                columns.insert(
+                    column_name.to_string(),
+                    ColumnData {
+                        mmap,
+                        file,
+                        path: path.clone(),
+                        count,
+                    },
+                );
             }
         }
 
@@ -96,9 +111,15 @@ impl SplayedTable {
             config,
             columns,
             row_count,
+            access: AccessCounter::default(),
         })
     }
 
+    /// Whether the table was opened read-only
+    pub fn is_read_only(&self) -> bool {
+        self.config.read_only
+    }
+
     /// Get the number of rows in the table
     pub fn count(&self) -> StorageResult<usize> {
         Ok(self.row_count)
@@ -106,6 +127,9 @@ impl SplayedTable {
 
     /// Insert a row into the table
     pub fn put(&mut self, row: Row) -> StorageResult<()> {
+        if self.config.read_only {
+            return Err(StorageError::ReadOnly(self.config.table_name.clone()));
+        }
         // Ensure all columns exist
         for column_name in row.keys() {
             if !self.columns.contains_key(column_name) {
@@ -140,13 +164,85 @@ impl SplayedTable {
         let mut row = Row::new();
 
         for (column_name, column_data) in &self.columns {
-            let value = self.read_value_from_column(column_data, index)?;
+            let value = self.read_value_from_column(column_name, column_data, index)?;
             row.insert(column_name.clone(), value);
         }
 
         Ok(row)
     }
 
+    /// Get the values of `columns` in a row; columns never written read as null
+    pub fn get_columns(&self, index: usize, columns: &[&str]) -> StorageResult<Row> {
+        if index >= self.row_count {
+            return Err(StorageError::InvalidRowIndex {
+                index,
+                max: self.row_count,
+            });
+        }
+
+        let mut row = Row::new();
+        for &column_name in columns {
+            let value = match self.columns.get(column_name) {
+                Some(column_data) => {
+                    self.read_value_from_column(column_name, column_data, index)?
+                }
+                None => ScalarValue::Null,
+            };
+            row.insert(column_name.to_string(), value);
+        }
+        Ok(row)
+    }
+
+    /// Reads of each of `columns`, persisted and since opening, most read first
+    pub fn access_stats(&self, columns: &[&str]) -> StorageResult<Vec<ColumnAccess>> {
+        self.access.report(&self.config, columns)
+    }
+
+    /// Count a query condition on `column`: an equality lookup, or a range
+    pub fn record_filter(&self, column: &str, equality: bool) {
+        self.access.record_filter(column, equality);
+    }
+
+    /// Forget the reads since opening instead of persisting them
+    pub(crate) fn discard_access_stats(&self) {
+        self.access.discard();
+    }
+
+    /// Add the reads since opening to the persisted access statistics;
+    /// a read-only table keeps them in memory
+    pub fn flush_access_stats(&self) -> StorageResult<()> {
+        if self.config.read_only {
+            return Ok(());
+        }
+        self.access.flush(&self.config)
+    }
+
+    /// Map `columns` and touch every page of them, so that the first reads
+    /// after opening find their data in the page cache instead of faulting
+    ///
+    /// Columns never written have nothing to load and are skipped. Returns
+    /// the number of bytes touched; touching does not count as reading in
+    /// the access statistics.
+    pub fn preload(&mut self, columns: &[&str]) -> StorageResult<u64> {
+        let mut touched = 0;
+        for &name in columns {
+            let Some(column) = self.columns.get_mut(name) else {
+                continue;
+            };
+            if column.mmap.is_none() && column.file.metadata()?.len() > 0 {
+                column.mmap = Some(unsafe { MmapOptions::new().map(&column.file)? });
+            }
+            if let Some(mmap) = &column.mmap {
+                let checksum = mmap
+                    .chunks(PAGE_SIZE)
+                    .fold(0u8, |sum, page| sum.wrapping_add(page[0]));
+                std::hint::black_box(checksum);
+                touched += mmap.len() as u64;
+            }
+        }
+        Ok(touched)
+    }
+
     /// Ensure a column file exists
     fn ensure_column_exists(&mut self, column_name: &str) -> StorageResult<()> {
         if self.columns.contains_key(column_name) {
@@ -197,6 +293,7 @@ impl SplayedTable {
     /// Read a value from a column file
     fn read_value_from_column(
         &self,
+        column_name: &str,
         column_data: &ColumnData,
         index: usize,
     ) -> StorageResult<ScalarValue> {
@@ -228,6 +325,7 @@ impl SplayedTable {
         file.read_exact(&mut data)?;
 
         let value: ScalarValue = bincode::deserialize(&data)?;
+        self.access.record(column_name, len as u64 + 4);
         Ok(value)
     }
 
@@ -255,7 +353,17 @@ impl SplayedTable {
     }
 }
 
+impl Drop for SplayedTable {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_access_stats() {
+            tracing::warn!(table = %self.config.table_name, error = %e, "failed to save access statistics");
+        }
+    }
+}
+
 #[cfg(test)]
+// 3.14 is sample data, not an approximation of pi
+#[allow(clippy::approx_constant)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
@@ -1,13 +1,15 @@
 //! High-level table interface
 
 use crate::{
-    config::QStoreConfig,
+    access::ColumnAccess,
+    config::{QStoreConfig, SCHEMA_FILE},
     error::{StorageError, StorageResult},
     schema::TableSchema,
     storage::SplayedTable,
     value::ScalarValue,
 };
 use std::collections::HashMap;
+use std::path::Path;
 
 /// A row of data (column name -> value mapping)
 pub type Row = HashMap<String, ScalarValue>;
@@ -31,11 +33,38 @@ impl Table {
         Ok(Self { schema, storage })
     }
 
+    /// Create a new table and persist its schema alongside the columns
+    pub fn create(schema: TableSchema, config: QStoreConfig) -> StorageResult<Self> {
+        if config.read_only {
+            return Err(StorageError::ReadOnly(config.table_name));
+        }
+        if config.schema_path().exists() {
+            return Err(StorageError::Configuration(format!(
+                "Table already exists: {}",
+                config.table_name
+            )));
+        }
+        let storage = SplayedTable::new(config.clone())?;
+        std::fs::write(config.schema_path(), bincode::serialize(&schema)?)?;
+        Ok(Self { schema, storage })
+    }
+
+    /// Open a table created with [`Table::create`], reading its stored schema
+    pub fn load(config: QStoreConfig) -> StorageResult<Self> {
+        let schema = read_schema(&config)?;
+        Self::open(schema, config)
+    }
+
     /// Get the table schema
     pub fn schema(&self) -> &TableSchema {
         &self.schema
     }
 
+    /// Whether the table was opened read-only, rejecting inserts
+    pub fn is_read_only(&self) -> bool {
+        self.storage.is_read_only()
+    }
+
     /// Get the number of rows in the table
     pub fn row_count(&self) -> StorageResult<usize> {
         self.storage.count()
@@ -83,8 +112,15 @@ impl Table {
             return Err(StorageError::ColumnNotFound(column_name.to_string()));
         }
 
-        let row = self.get(row_index)?;
-        Ok(row.get(column_name).cloned().unwrap_or(ScalarValue::Null))
+        let row_count = self.row_count()?;
+        if row_index >= row_count {
+            return Err(StorageError::InvalidRowIndex {
+                index: row_index,
+                max: row_count,
+            });
+        }
+        let mut row = self.storage.get_columns(row_index, &[column_name])?;
+        Ok(row.remove(column_name).unwrap_or(ScalarValue::Null))
     }
 
     /// Get all values for a specific column
@@ -95,6 +131,13 @@ impl Table {
         }
 
         let row_count = self.row_count()?;
+        let _span = tracing::debug_span!(
+            "scan_column",
+            table = %self.schema.name,
+            column = column_name,
+            rows = row_count,
+        )
+        .entered();
         let mut values = Vec::with_capacity(row_count);
 
         for i in 0..row_count {
@@ -106,7 +149,7 @@ impl Table {
     }
 
     /// Iterate over all rows
-    pub fn iter(&self) -> StorageResult<TableIterator> {
+    pub fn iter(&self) -> StorageResult<TableIterator<'_>> {
         let row_count = self.row_count()?;
         Ok(TableIterator {
             table: self,
@@ -122,6 +165,13 @@ impl Table {
     {
         let mut results = Vec::new();
         let row_count = self.row_count()?;
+        let span = tracing::debug_span!(
+            "scan",
+            table = %self.schema.name,
+            rows = row_count,
+            matched = tracing::field::Empty,
+        )
+        .entered();
 
         for i in 0..row_count {
             let row = self.get(i)?;
@@ -130,9 +180,74 @@ impl Table {
             }
         }
 
+        span.record("matched", results.len());
+        Ok(results)
+    }
+
+    /// Filter rows based on a predicate, reading only `columns`
+    ///
+    /// Rows passed to the predicate and returned hold just those columns.
+    pub fn filter_columns<F>(&self, columns: &[&str], predicate: F) -> StorageResult<Vec<Row>>
+    where
+        F: Fn(&Row) -> bool,
+    {
+        let mut results = Vec::new();
+        let row_count = self.row_count()?;
+        let span = tracing::debug_span!(
+            "scan",
+            table = %self.schema.name,
+            rows = row_count,
+            columns = columns.len(),
+            matched = tracing::field::Empty,
+        )
+        .entered();
+
+        for i in 0..row_count {
+            let row = self.storage.get_columns(i, columns)?;
+            if predicate(&row) {
+                results.push(row);
+            }
+        }
+
+        span.record("matched", results.len());
         Ok(results)
     }
 
+    /// Bring `columns` into memory ahead of the first query, returning the
+    /// number of bytes loaded; see [`SplayedTable::preload`]
+    pub fn preload(&mut self, columns: &[&str]) -> StorageResult<u64> {
+        if let Some(missing) = columns.iter().find(|c| self.schema.get_column(c).is_none()) {
+            return Err(StorageError::ColumnNotFound(missing.to_string()));
+        }
+        let _span =
+            tracing::debug_span!("preload", table = %self.schema.name, columns = columns.len())
+                .entered();
+        self.storage.preload(columns)
+    }
+
+    /// Reads of each column, most read first, including those of earlier
+    /// handles on the same table
+    pub fn access_stats(&self) -> StorageResult<Vec<ColumnAccess>> {
+        self.storage.access_stats(&self.schema.column_names())
+    }
+
+    /// Count a query condition on `column` in the access statistics: an
+    /// equality lookup, or a range
+    pub fn record_filter(&self, column: &str, equality: bool) {
+        self.storage.record_filter(column, equality);
+    }
+
+    /// Add the reads made through this handle to the persisted statistics
+    pub(crate) fn flush_access_stats(&self) -> StorageResult<()> {
+        self.storage.flush_access_stats()
+    }
+
+    /// Forget the reads made through this handle, so that scans on the
+    /// table's own behalf do not count as workload
+    pub(crate) fn discard_access_stats(&self) {
+        self.storage.discard_access_stats();
+    }
+
     /// Get basic statistics about the table
     pub fn stats(&self) -> StorageResult<TableStats> {
         let row_count = self.row_count()?;
@@ -147,10 +262,31 @@ impl Table {
                 .into_iter()
                 .map(|s| s.to_string())
                 .collect(),
+            access: self.access_stats()?,
         })
     }
 }
 
+/// Read the schema persisted by [`Table::create`]
+pub fn read_schema(config: &QStoreConfig) -> StorageResult<TableSchema> {
+    let bytes = std::fs::read(config.schema_path()).map_err(|_| {
+        StorageError::Configuration(format!("No schema stored for table {}", config.table_name))
+    })?;
+    Ok(bincode::deserialize(&bytes)?)
+}
+
+/// Names of the tables created with [`Table::create`] under the database
+/// `root`, sorted
+pub fn table_names(root: &Path) -> StorageResult<Vec<String>> {
+    let mut names: Vec<String> = std::fs::read_dir(root)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.join(SCHEMA_FILE).is_file())
+        .filter_map(|path| path.file_name()?.to_str().map(str::to_string))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
 /// Iterator over table rows
 pub struct TableIterator<'a> {
     table: &'a Table,
@@ -178,9 +314,13 @@ pub struct TableStats {
     pub row_count: usize,
     pub column_count: usize,
     pub column_names: Vec<String>,
+    /// Reads of each column, most read first
+    pub access: Vec<ColumnAccess>,
 }
 
 #[cfg(test)]
+// 3.14 is sample data, not an approximation of pi
+#[allow(clippy::approx_constant)]
 mod tests {
     use super::*;
     use crate::schema::SchemaBuilder;
@@ -234,6 +374,31 @@ mod tests {
         assert_eq!(column_values[0], ScalarValue::Float64(3.14));
     }
 
+    #[test]
+    fn test_table_create_persists_schema() {
+        use crate::schema::{ColumnAttribute, ColumnSchema, SimpleDataType};
+
+        let temp_dir = TempDir::new().unwrap();
+        let config = QStoreConfig::new(temp_dir.path(), "trades".to_string());
+        let schema = TableSchema::new("trades".to_string()).add_column(
+            ColumnSchema::new_simple("time".to_string(), SimpleDataType::Timestamp)
+                .with_attribute(ColumnAttribute::Sorted),
+        );
+        let mut table = Table::create(schema.clone(), config.clone()).unwrap();
+        let mut row = Row::new();
+        row.insert("time".to_string(), ScalarValue::Timestamp(1));
+        table.insert(row).unwrap();
+        assert!(Table::create(schema.clone(), config.clone()).is_err());
+
+        let loaded = Table::load(config).unwrap();
+        assert_eq!(loaded.schema(), &schema);
+        assert_eq!(loaded.row_count().unwrap(), 1);
+        assert_eq!(
+            loaded.schema().get_column("time").unwrap().attribute,
+            Some(ColumnAttribute::Sorted)
+        );
+    }
+
     #[test]
     fn test_table_schema_validation() {
         let (mut table, _temp_dir) = create_test_table();
@@ -268,4 +433,74 @@ mod tests {
         assert_eq!(stats.column_count, 2);
         assert_eq!(stats.column_names, vec!["time", "value"]);
     }
+
+    #[test]
+    fn test_column_access_stats_persist() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = QStoreConfig::new(temp_dir.path(), "ticks".to_string());
+        let mut table = Table::create(SchemaBuilder::time_series(), config.clone()).unwrap();
+        for i in 0..3 {
+            let mut row = Row::new();
+            row.insert("time".to_string(), ScalarValue::Timestamp(i));
+            row.insert("value".to_string(), ScalarValue::Float64(i as f64));
+            table.insert(row).unwrap();
+        }
+        table.get_column("value").unwrap();
+        let access = table.access_stats().unwrap();
+        assert_eq!(access[0].column, "value");
+        assert_eq!(access[0].reads, 3);
+        assert!(access[0].bytes > 12);
+        assert_eq!((access[1].column.as_str(), access[1].reads), ("time", 0));
+        drop(table);
+
+        // Counts from earlier handles are kept and added to
+        let table = Table::load(config).unwrap();
+        table.get_value(0, "time").unwrap();
+        let stats = table.stats().unwrap();
+        let reads: Vec<_> = stats
+            .access
+            .iter()
+            .map(|a| (a.column.as_str(), a.reads))
+            .collect();
+        assert_eq!(reads, [("value", 3), ("time", 1)]);
+    }
+
+    #[test]
+    fn test_read_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = QStoreConfig::new(temp_dir.path(), "ticks".to_string());
+        let mut table = Table::create(SchemaBuilder::time_series(), config.clone()).unwrap();
+        let mut row = Row::new();
+        row.insert("time".to_string(), ScalarValue::Timestamp(1));
+        row.insert("value".to_string(), ScalarValue::Float64(1.5));
+        table.insert(row.clone()).unwrap();
+        drop(table);
+
+        let read_only = config.with_read_only(true);
+        let mut table = Table::load(read_only.clone()).unwrap();
+        assert!(table.is_read_only());
+        assert_eq!(
+            table.get_value(0, "value").unwrap(),
+            ScalarValue::Float64(1.5)
+        );
+        assert!(matches!(
+            table.insert(row),
+            Err(StorageError::ReadOnly(name)) if name == "ticks"
+        ));
+        assert_eq!(table.row_count().unwrap(), 1);
+        // Reads are counted, but only in memory
+        assert_eq!(table.access_stats().unwrap()[0].reads, 1);
+        drop(table);
+        let table = Table::load(read_only.clone()).unwrap();
+        assert_eq!(table.access_stats().unwrap()[0].reads, 0);
+
+        // Nothing is created
+        let missing = QStoreConfig::new(temp_dir.path(), "quotes".to_string()).with_read_only(true);
+        assert!(matches!(
+            Table::create(SchemaBuilder::time_series(), missing.clone()),
+            Err(StorageError::ReadOnly(_))
+        ));
+        assert!(Table::new(SchemaBuilder::time_series(), missing).is_err());
+        assert!(!temp_dir.path().join("quotes").exists());
+    }
 }
@@ -3,6 +3,7 @@
 use crate::schema::SimpleDataType;
 use arrow2::datatypes::DataType;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// Scalar values that can be stored in columns
 /// Aligned with Arrow2's type system for zero-copy compatibility
@@ -207,7 +208,37 @@ impl From<Vec<u8>> for ScalarValue {
     }
 }
 
+impl fmt::Display for ScalarValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScalarValue::Null => Ok(()),
+            ScalarValue::Boolean(v) => write!(f, "{}", v),
+            ScalarValue::Int8(v) => write!(f, "{}", v),
+            ScalarValue::Int16(v) => write!(f, "{}", v),
+            ScalarValue::Int32(v) => write!(f, "{}", v),
+            ScalarValue::Int64(v) => write!(f, "{}", v),
+            ScalarValue::UInt8(v) => write!(f, "{}", v),
+            ScalarValue::UInt16(v) => write!(f, "{}", v),
+            ScalarValue::UInt32(v) => write!(f, "{}", v),
+            ScalarValue::UInt64(v) => write!(f, "{}", v),
+            ScalarValue::Float32(v) => write!(f, "{}", v),
+            ScalarValue::Float64(v) => write!(f, "{}", v),
+            ScalarValue::Utf8(v) => write!(f, "{}", v),
+            ScalarValue::Binary(bytes) => {
+                write!(f, "0x")?;
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+            ScalarValue::Timestamp(v) => write!(f, "{}", v),
+        }
+    }
+}
+
 #[cfg(test)]
+// 3.14 is sample data, not an approximation of pi
+#[allow(clippy::approx_constant)]
 mod tests {
     use super::*;
 
@@ -244,4 +275,12 @@ mod tests {
         );
         assert_eq!(ScalarValue::from(true), ScalarValue::Boolean(true));
     }
+
+    #[test]
+    fn test_scalar_value_display() {
+        assert_eq!(ScalarValue::Int64(-3).to_string(), "-3");
+        assert_eq!(ScalarValue::Utf8("AAPL".to_string()).to_string(), "AAPL");
+        assert_eq!(ScalarValue::Binary(vec![0, 255]).to_string(), "0x00ff");
+        assert_eq!(ScalarValue::Null.to_string(), "");
+    }
 }
@@ -0,0 +1,368 @@
+//! Materialized views maintained incrementally on append
+//!
+//! A view groups the rows of a source table by some key columns, optionally
+//! flooring a time column into fixed-width buckets, and keeps running
+//! aggregates per group (for example one-minute OHLC bars per symbol). Each
+//! appended source row updates its group in place, so dashboards read the
+//! aggregates without rescanning raw ticks.
+//!
+//! Like checkpoints, the view is persisted as an append-only splayed table:
+//! every update appends the group's new row, and the most recent row for a
+//! group wins on reload. All supported aggregates can be resumed from their
+//! own output, so no extra state needs to be stored.
+
+use crate::{
+    config::QStoreConfig,
+    error::{StorageError, StorageResult},
+    stats::StatsEngine,
+    storage::SplayedTable,
+    table::Row,
+    value::ScalarValue,
+};
+use std::cmp::Ordering;
+
+/// Output column name and the aggregate computing it
+pub type NamedAggregate = (String, Aggregate);
+
+/// Running aggregate over one column of the source rows
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aggregate {
+    /// Number of rows in the group
+    Count,
+    /// Sum of a numeric column
+    Sum(String),
+    /// Smallest value of a column
+    Min(String),
+    /// Largest value of a column
+    Max(String),
+    /// First value of a column
+    First(String),
+    /// Most recent value of a column
+    Last(String),
+}
+
+impl Aggregate {
+    /// Source column the aggregate reads, if any
+    pub(crate) fn column(&self) -> Option<&str> {
+        match self {
+            Aggregate::Count => None,
+            Aggregate::Sum(c)
+            | Aggregate::Min(c)
+            | Aggregate::Max(c)
+            | Aggregate::First(c)
+            | Aggregate::Last(c) => Some(c),
+        }
+    }
+
+    /// Fold `value` into the running `state`
+    pub(crate) fn update(&self, state: &mut ScalarValue, value: Option<&ScalarValue>) {
+        if let Aggregate::Count = self {
+            *state = ScalarValue::Int64(state.as_i64().unwrap_or(0) + 1);
+            return;
+        }
+        let Some(value) = value.filter(|v| !v.is_null()) else {
+            return;
+        };
+        if state.is_null() {
+            *state = value.clone();
+            return;
+        }
+        let replace = match self {
+            Aggregate::Count | Aggregate::First(_) => false,
+            Aggregate::Last(_) => true,
+            Aggregate::Min(_) => compare(value, state) == Some(Ordering::Less),
+            Aggregate::Max(_) => compare(value, state) == Some(Ordering::Greater),
+            Aggregate::Sum(_) => {
+                *state = add(state, value);
+                false
+            }
+        };
+        if replace {
+            *state = value.clone();
+        }
+    }
+}
+
+pub(crate) fn compare(a: &ScalarValue, b: &ScalarValue) -> Option<Ordering> {
+    match (a, b) {
+        (ScalarValue::Utf8(a), ScalarValue::Utf8(b)) => Some(a.cmp(b)),
+        (ScalarValue::Timestamp(a), ScalarValue::Timestamp(b)) => Some(a.cmp(b)),
+        _ => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        },
+    }
+}
+
+fn add(a: &ScalarValue, b: &ScalarValue) -> ScalarValue {
+    match (a.as_i64(), b.as_i64()) {
+        (Some(a), Some(b)) => ScalarValue::Int64(a.wrapping_add(b)),
+        _ => match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => ScalarValue::Float64(a + b),
+            _ => ScalarValue::Null,
+        },
+    }
+}
+
+/// Time column floored into fixed-width buckets before grouping
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeBucket {
+    /// Column holding the timestamp
+    pub column: String,
+    /// Bucket width, in the column's units (nanoseconds for timestamps)
+    pub width: i64,
+}
+
+/// What a materialized view computes from its source table
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewDefinition {
+    /// Table whose appends feed the view
+    pub source: String,
+    /// Columns rows are grouped by
+    pub group_by: Vec<String>,
+    /// Optional time bucketing, applied as an extra leading group column
+    pub bucket: Option<TimeBucket>,
+    /// Output column names and the aggregates computing them
+    pub aggregates: Vec<NamedAggregate>,
+}
+
+impl ViewDefinition {
+    /// Start a view over `source` with no grouping or aggregates
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            group_by: Vec::new(),
+            bucket: None,
+            aggregates: Vec::new(),
+        }
+    }
+
+    /// Group by another column
+    pub fn group_by(mut self, column: impl Into<String>) -> Self {
+        self.group_by.push(column.into());
+        self
+    }
+
+    /// Floor `column` into buckets of `width` and group by the bucket
+    pub fn bucket(mut self, column: impl Into<String>, width: i64) -> Self {
+        self.bucket = Some(TimeBucket {
+            column: column.into(),
+            width,
+        });
+        self
+    }
+
+    /// Add an output column computed by `aggregate`
+    pub fn aggregate(mut self, name: impl Into<String>, aggregate: Aggregate) -> Self {
+        self.aggregates.push((name.into(), aggregate));
+        self
+    }
+
+    /// Names of the key columns, bucket first
+    pub(crate) fn key_columns(&self) -> impl Iterator<Item = &str> {
+        self.bucket
+            .iter()
+            .map(|b| b.column.as_str())
+            .chain(self.group_by.iter().map(String::as_str))
+    }
+
+    /// Grouping key of a source row
+    pub(crate) fn key(&self, row: &Row) -> StorageResult<Vec<ScalarValue>> {
+        let mut key = Vec::with_capacity(self.group_by.len() + 1);
+        if let Some(bucket) = &self.bucket {
+            let value = row.get(&bucket.column).unwrap_or(&ScalarValue::Null);
+            key.push(floor(value, bucket.width)?);
+        }
+        for column in &self.group_by {
+            key.push(row.get(column).cloned().unwrap_or(ScalarValue::Null));
+        }
+        Ok(key)
+    }
+
+    pub(crate) fn validate(&self) -> StorageResult<()> {
+        if self.aggregates.is_empty() {
+            return Err(StorageError::Configuration(
+                "A view needs at least one aggregate".into(),
+            ));
+        }
+        if let Some(bucket) = &self.bucket
+            && bucket.width <= 0
+        {
+            return Err(StorageError::Configuration(format!(
+                "Invalid bucket width: {}",
+                bucket.width
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Floor a time value to the start of its bucket, keeping its type
+fn floor(value: &ScalarValue, width: i64) -> StorageResult<ScalarValue> {
+    let floored = |n: i64| n.div_euclid(width) * width;
+    match value {
+        ScalarValue::Timestamp(n) => Ok(ScalarValue::Timestamp(floored(*n))),
+        ScalarValue::Null => Ok(ScalarValue::Null),
+        other => other
+            .as_i64()
+            .map(|n| ScalarValue::Int64(floored(n)))
+            .ok_or_else(|| StorageError::SchemaMismatch {
+                expected: "integer or timestamp bucket column".into(),
+                actual: format!("{:?}", other.simple_data_type()),
+            }),
+    }
+}
+
+/// A view kept up to date as rows are appended to its source
+pub struct MaterializedView {
+    state: StatsEngine,
+    storage: SplayedTable,
+}
+
+impl MaterializedView {
+    /// Open the view persisted at `config`, creating it if needed
+    pub fn open(definition: ViewDefinition, config: QStoreConfig) -> StorageResult<Self> {
+        let mut state = StatsEngine::new(definition)?;
+        let storage = if config.table_path().exists() {
+            SplayedTable::open(config)?
+        } else {
+            SplayedTable::new(config)?
+        };
+
+        for i in 0..storage.count()? {
+            state.restore(&storage.get(i)?)?;
+        }
+        Ok(Self { state, storage })
+    }
+
+    /// The view's definition
+    pub fn definition(&self) -> &ViewDefinition {
+        self.state.definition()
+    }
+
+    /// Name of the table feeding the view
+    pub fn source(&self) -> &str {
+        &self.definition().source
+    }
+
+    /// Number of groups in the view
+    pub fn len(&self) -> usize {
+        self.state.len()
+    }
+
+    /// Whether the view has no groups yet
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
+
+    /// Fold one appended source row into the view
+    pub fn apply(&mut self, row: &Row) -> StorageResult<()> {
+        self.apply_all(std::slice::from_ref(row))
+    }
+
+    /// Fold appended source rows into the view, persisting each touched group once
+    pub fn apply_all(&mut self, rows: &[Row]) -> StorageResult<()> {
+        for position in self.state.apply_tracked(rows)? {
+            self.storage.put(self.state.row_at(position))?;
+        }
+        Ok(())
+    }
+
+    /// Current contents of the view, one row per group in order of first appearance
+    pub fn rows(&self) -> Vec<Row> {
+        self.state.rows()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MINUTE: i64 = 60_000_000_000;
+
+    fn bars() -> ViewDefinition {
+        ViewDefinition::new("trades")
+            .bucket("time", MINUTE)
+            .group_by("sym")
+            .aggregate("open", Aggregate::First("px".into()))
+            .aggregate("high", Aggregate::Max("px".into()))
+            .aggregate("low", Aggregate::Min("px".into()))
+            .aggregate("close", Aggregate::Last("px".into()))
+            .aggregate("volume", Aggregate::Sum("size".into()))
+            .aggregate("trades", Aggregate::Count)
+    }
+
+    fn trade(time: i64, sym: &str, px: f64, size: i64) -> Row {
+        let mut row = Row::new();
+        row.insert("time".to_string(), ScalarValue::Timestamp(time));
+        row.insert("sym".to_string(), ScalarValue::Utf8(sym.to_string()));
+        row.insert("px".to_string(), ScalarValue::Float64(px));
+        row.insert("size".to_string(), ScalarValue::Int64(size));
+        row
+    }
+
+    fn bar(view: &MaterializedView, bucket: i64, sym: &str) -> Row {
+        view.rows()
+            .into_iter()
+            .find(|row| {
+                row["time"] == ScalarValue::Timestamp(bucket)
+                    && row["sym"] == ScalarValue::Utf8(sym.to_string())
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_minute_bars_update_on_append() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = QStoreConfig::new(temp_dir.path(), "bars".to_string());
+        let mut view = MaterializedView::open(bars(), config).unwrap();
+
+        view.apply_all(&[
+            trade(1, "AAPL", 10.0, 100),
+            trade(2, "AAPL", 12.0, 50),
+            trade(3, "MSFT", 30.0, 10),
+        ])
+        .unwrap();
+        view.apply(&trade(4, "AAPL", 9.0, 25)).unwrap();
+        view.apply(&trade(MINUTE + 1, "AAPL", 11.0, 5)).unwrap();
+        assert_eq!(view.len(), 3);
+
+        let first = bar(&view, 0, "AAPL");
+        assert_eq!(first["open"], ScalarValue::Float64(10.0));
+        assert_eq!(first["high"], ScalarValue::Float64(12.0));
+        assert_eq!(first["low"], ScalarValue::Float64(9.0));
+        assert_eq!(first["close"], ScalarValue::Float64(9.0));
+        assert_eq!(first["volume"], ScalarValue::Int64(175));
+        assert_eq!(first["trades"], ScalarValue::Int64(3));
+        assert_eq!(bar(&view, MINUTE, "AAPL")["trades"], ScalarValue::Int64(1));
+    }
+
+    #[test]
+    fn test_view_resumes_from_persisted_table() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = QStoreConfig::new(temp_dir.path(), "bars".to_string());
+        {
+            let mut view = MaterializedView::open(bars(), config.clone()).unwrap();
+            view.apply(&trade(1, "AAPL", 10.0, 100)).unwrap();
+            view.apply(&trade(2, "AAPL", 12.0, 50)).unwrap();
+        }
+
+        let mut view = MaterializedView::open(bars(), config).unwrap();
+        assert_eq!(view.len(), 1);
+        view.apply(&trade(3, "AAPL", 8.0, 1)).unwrap();
+        let resumed = bar(&view, 0, "AAPL");
+        assert_eq!(resumed["open"], ScalarValue::Float64(10.0));
+        assert_eq!(resumed["low"], ScalarValue::Float64(8.0));
+        assert_eq!(resumed["volume"], ScalarValue::Int64(151));
+        assert_eq!(resumed["trades"], ScalarValue::Int64(3));
+    }
+
+    #[test]
+    fn test_view_requires_aggregates() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = QStoreConfig::new(temp_dir.path(), "bars".to_string());
+        let definition = ViewDefinition::new("trades").group_by("sym");
+        assert!(MaterializedView::open(definition, config).is_err());
+    }
+}
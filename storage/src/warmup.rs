@@ -0,0 +1,195 @@
+//! Preloading columns when a process starts
+//!
+//! Column files are memory-mapped, so the first query after a restart pays
+//! a page fault for every page it reads. A [`WarmupConfig`] names the
+//! columns worth loading ahead of that query, either explicitly per table or
+//! as the most read columns in each table's
+//! [access statistics](crate::access), and preloads them across a database:
+//!
+//! ```no_run
+//! # fn main() -> storage::StorageResult<()> {
+//! use storage::warmup::WarmupConfig;
+//!
+//! let warmup = WarmupConfig::new()
+//!     .with_hottest(2)
+//!     .with_columns("trades", ["time", "sym"]);
+//! for warmed in warmup.warm_database("db".as_ref())? {
+//!     println!("{}: {} bytes", warmed.table, warmed.bytes);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    config::QStoreConfig,
+    error::StorageResult,
+    table::{Table, table_names},
+};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Columns preloaded per table when none are named for it
+pub const DEFAULT_HOTTEST: usize = 3;
+
+/// Columns to preload, by table
+type ColumnsByTable = HashMap<String, Vec<String>>;
+
+/// Which columns to preload in each table of a database
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarmupConfig {
+    /// Most read columns to preload in tables without named columns
+    hottest: usize,
+    /// Columns named explicitly
+    columns: ColumnsByTable,
+    /// Whether tables are opened read-only
+    read_only: bool,
+}
+
+/// What was preloaded in one table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warmed {
+    /// Table name
+    pub table: String,
+    /// Columns preloaded
+    pub columns: Vec<String>,
+    /// Bytes loaded
+    pub bytes: u64,
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WarmupConfig {
+    /// Preload the [`DEFAULT_HOTTEST`] most read columns of every table
+    pub fn new() -> Self {
+        Self {
+            hottest: DEFAULT_HOTTEST,
+            columns: HashMap::new(),
+            read_only: false,
+        }
+    }
+
+    /// Preload the `count` most read columns of tables without named columns
+    pub fn with_hottest(mut self, count: usize) -> Self {
+        self.hottest = count;
+        self
+    }
+
+    /// Preload exactly `columns` of `table`, whatever their reads
+    pub fn with_columns<I, S>(mut self, table: impl Into<String>, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.columns
+            .insert(table.into(), columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Open the tables of [`WarmupConfig::warm_database`] read-only
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// The columns of `table` to preload: those named for it, or else its
+    /// most read columns that have been read at all
+    pub fn columns(&self, table: &Table) -> StorageResult<Vec<String>> {
+        if let Some(columns) = self.columns.get(&table.schema().name) {
+            return Ok(columns.clone());
+        }
+        Ok(table
+            .access_stats()?
+            .into_iter()
+            .filter(|access| access.reads > 0)
+            .take(self.hottest)
+            .map(|access| access.column)
+            .collect())
+    }
+
+    /// Preload the configured columns of `table`
+    pub fn warm(&self, table: &mut Table) -> StorageResult<Warmed> {
+        let columns = self.columns(table)?;
+        let names: Vec<&str> = columns.iter().map(String::as_str).collect();
+        let bytes = table.preload(&names)?;
+        Ok(Warmed {
+            table: table.schema().name.clone(),
+            columns,
+            bytes,
+        })
+    }
+
+    /// Preload the configured columns of every table under `root`, in name
+    /// order
+    pub fn warm_database(&self, root: &Path) -> StorageResult<Vec<Warmed>> {
+        let _span = tracing::info_span!("warmup", root = %root.display()).entered();
+        table_names(root)?
+            .into_iter()
+            .map(|name| {
+                let config = QStoreConfig::new(root, name).with_read_only(self.read_only);
+                self.warm(&mut Table::load(config)?)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::StorageError, schema::SchemaBuilder, table::Row, value::ScalarValue};
+    use tempfile::TempDir;
+
+    fn ticks(dir: &TempDir, name: &str) -> Table {
+        let config = QStoreConfig::new(dir.path(), name.to_string());
+        let mut schema = SchemaBuilder::time_series();
+        schema.name = name.to_string();
+        let mut table = Table::create(schema, config).unwrap();
+        for i in 0..10 {
+            let mut row = Row::new();
+            row.insert("time".into(), ScalarValue::Timestamp(i));
+            row.insert("value".into(), ScalarValue::Float64(i as f64));
+            table.insert(row).unwrap();
+        }
+        table
+    }
+
+    #[test]
+    fn test_preloads_hottest_columns() {
+        let dir = TempDir::new().unwrap();
+        let table = ticks(&dir, "ticks");
+        table.get_column("value").unwrap();
+        drop(table);
+        ticks(&dir, "cold");
+
+        let warmed = WarmupConfig::new().warm_database(dir.path()).unwrap();
+        assert_eq!(warmed.len(), 2);
+        // Columns never read are not worth loading
+        assert_eq!(warmed[0].table, "cold");
+        assert!(warmed[0].columns.is_empty());
+        assert_eq!(warmed[0].bytes, 0);
+        assert_eq!(warmed[1].columns, ["value"]);
+        assert!(warmed[1].bytes > 0);
+
+        // Preloading is not a read
+        let table = Table::load(QStoreConfig::new(dir.path(), "ticks".into())).unwrap();
+        assert_eq!(table.access_stats().unwrap()[0].reads, 10);
+    }
+
+    #[test]
+    fn test_named_columns() {
+        let dir = TempDir::new().unwrap();
+        let mut table = ticks(&dir, "ticks");
+        let warmup = WarmupConfig::new().with_columns("ticks", ["time", "value"]);
+        let warmed = warmup.warm(&mut table).unwrap();
+        assert_eq!(warmed.columns, ["time", "value"]);
+
+        let missing = WarmupConfig::new().with_columns("ticks", ["size"]);
+        assert!(matches!(
+            missing.warm(&mut table),
+            Err(StorageError::ColumnNotFound(column)) if column == "size"
+        ));
+    }
+}
@@ -7,6 +7,9 @@
 //! - Persistence across restarts
 //! - Performance with larger datasets
 
+// 3.14 is sample data, not an approximation of pi
+#![allow(clippy::approx_constant, clippy::useless_vec, clippy::unnecessary_cast)]
+
 use storage::{
     QStoreConfig, ScalarValue, Table,
     schema::{ColumnSchema, SchemaBuilder, SimpleDataType},
@@ -0,0 +1,61 @@
+//! Helpers shared by the integration tests
+//!
+//! Each test binary declares `mod common;` and uses what it needs, so the
+//! rest look unused from any one of them.
+#![allow(dead_code)]
+
+use wabznasm::Session;
+use wabznasm::environment::{Environment, Value};
+use wabznasm::errors::EvalError;
+use wabznasm::evaluator::Evaluator;
+use wabznasm::parser::parse_expression;
+
+/// The value of `source` in `session`, formatted as the REPL prints it
+pub fn show(session: &mut Session, source: &str) -> String {
+    let value = session.eval(source).unwrap();
+    session.format(&value)
+}
+
+/// The value of `source` in a session of its own
+pub fn value(source: &str) -> Value {
+    Session::new().eval(source).unwrap()
+}
+
+/// The value of `source` in a session of its own, formatted as the REPL
+/// prints it
+pub fn shown(source: &str) -> String {
+    show(&mut Session::new(), source)
+}
+
+/// Evaluate each line with `evaluator` in one environment, returning the
+/// last result
+pub fn run(evaluator: &mut Evaluator, lines: &[&str]) -> Result<Value, EvalError> {
+    let mut env = Environment::new();
+    let mut result = Value::Integer(0);
+    for src in lines {
+        let tree = parse_expression(src).unwrap();
+        assert!(!tree.root_node().has_error(), "syntax error in {:?}", src);
+        result = evaluator.eval_with_env(tree.root_node(), src, &mut env)?;
+    }
+    Ok(result)
+}
+
+/// Evaluate each line in one environment, returning the last result
+pub fn eval(lines: &[&str]) -> Result<Value, EvalError> {
+    run(&mut Evaluator::new(), lines)
+}
+
+/// Evaluate each line in one environment, returning the last value
+pub fn eval_lines(lines: &[&str]) -> Value {
+    eval(lines).unwrap()
+}
+
+/// A list of integers
+pub fn ints(values: &[i64]) -> Value {
+    Value::List(values.iter().copied().map(Value::Integer).collect())
+}
+
+/// A list of booleans
+pub fn bools(values: &[bool]) -> Value {
+    Value::List(values.iter().copied().map(Value::Boolean).collect())
+}
@@ -1,7 +1,14 @@
 // Test for connection config parsing and kernel construction
+use chrono::Utc;
+use jupyter_protocol::{Header, JupyterMessageContent, ReplyStatus};
+use std::net::TcpListener;
+use std::time::Duration;
 use tempfile::NamedTempFile;
 use wabznasm::jupyter::connection::{ConnectionConfig, ConnectionConfigExt};
 use wabznasm::jupyter::kernel::JupyterKernelRunner;
+use wabznasm::jupyter::message_parser::ParsedMessage;
+use wabznasm::jupyter::signature::{SignatureSigner, SignatureVerifier};
+use zeromq::{DealerSocket, Socket, SocketRecv, SocketSend, ZmqMessage};
 
 #[tokio::test]
 async fn test_connection_config_parsing() {
@@ -40,3 +47,220 @@ async fn test_connection_config_parsing() {
 
     println!("✅ Connection config parsing and kernel construction test passed");
 }
+
+// Requests sent to a running kernel on its shell and control sockets
+const KEY: &str = "control-test-key";
+
+/// A port nothing is listening on
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn config() -> ConnectionConfig {
+    serde_json::from_value(serde_json::json!({
+        "transport": "tcp",
+        "ip": "127.0.0.1",
+        "control_port": free_port(),
+        "hb_port": free_port(),
+        "iopub_port": free_port(),
+        "stdin_port": free_port(),
+        "shell_port": free_port(),
+        "signature_scheme": "hmac-sha256",
+        "key": KEY
+    }))
+    .unwrap()
+}
+
+/// A signed request of `msg_type` with `content`, as a frontend sends it
+fn request(msg_type: &str, content: serde_json::Value) -> ZmqMessage {
+    let header = Header {
+        msg_id: uuid::Uuid::new_v4().to_string(),
+        session: "control-test".to_string(),
+        username: "test".to_string(),
+        date: Utc::now(),
+        msg_type: msg_type.to_string(),
+        version: "5.3".to_string(),
+    };
+    let parts = [
+        serde_json::to_vec(&header).unwrap(),
+        b"{}".to_vec(),
+        b"{}".to_vec(),
+        serde_json::to_vec(&content).unwrap(),
+    ];
+    let signer = SignatureSigner::new("hmac-sha256".to_string(), KEY.as_bytes()).unwrap();
+    let signature = signer
+        .sign(&[&parts[0], &parts[1], &parts[2], &parts[3]])
+        .unwrap();
+    let mut message = ZmqMessage::from(b"<IDS|MSG>".to_vec());
+    message.push_back(signature.into_bytes().into());
+    for part in parts {
+        message.push_back(part.into());
+    }
+    message
+}
+
+/// The next message on `socket`, parsed
+async fn reply(socket: &mut DealerSocket) -> ParsedMessage {
+    let reply = tokio::time::timeout(Duration::from_secs(10), socket.recv())
+        .await
+        .expect("no reply")
+        .unwrap();
+    let verifier = SignatureVerifier::new("hmac-sha256".to_string(), KEY.as_bytes()).unwrap();
+    ParsedMessage::parse(&reply, &verifier).unwrap()
+}
+
+#[tokio::test]
+async fn test_interrupt_on_control_stops_the_running_cell() {
+    let config = config();
+    let (shell_url, control_url) = (config.shell_url(), config.control_url());
+    let mut kernel = JupyterKernelRunner::new(config).unwrap();
+    let running = tokio::spawn(async move { kernel.run().await.map_err(|e| e.to_string()) });
+
+    let mut shell = DealerSocket::new();
+    shell.connect(&shell_url).await.unwrap();
+    let mut control = DealerSocket::new();
+    control.connect(&control_url).await.unwrap();
+    let cell = serde_json::json!({
+        "code": "do[1000000; do[1000000; 0]]",
+        "silent": false,
+        "store_history": true,
+        "user_expressions": {},
+        "allow_stdin": false,
+        "stop_on_error": true
+    });
+    shell.send(request("execute_request", cell)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    control
+        .send(request("interrupt_request", serde_json::json!({})))
+        .await
+        .unwrap();
+
+    assert_eq!(reply(&mut control).await.header.msg_type, "interrupt_reply");
+    let executed = reply(&mut shell).await;
+    assert_eq!(executed.header.msg_type, "execute_reply");
+    assert!(matches!(
+        executed.content,
+        JupyterMessageContent::ExecuteReply(ref reply) if reply.status == ReplyStatus::Error
+    ));
+
+    // The kernel goes on to run the next cell
+    let cell = serde_json::json!({
+        "code": "1+2",
+        "silent": false,
+        "store_history": true,
+        "user_expressions": {},
+        "allow_stdin": false,
+        "stop_on_error": true
+    });
+    shell.send(request("execute_request", cell)).await.unwrap();
+    let executed = reply(&mut shell).await;
+    assert!(matches!(
+        executed.content,
+        JupyterMessageContent::ExecuteReply(ref reply) if reply.status == ReplyStatus::Ok
+    ));
+    running.abort();
+}
+
+#[tokio::test]
+async fn test_requests_queued_behind_a_running_cell() {
+    let config = config();
+    let (shell_url, control_url) = (config.shell_url(), config.control_url());
+    let mut kernel = JupyterKernelRunner::new(config).unwrap();
+    let running = tokio::spawn(async move { kernel.run().await.map_err(|e| e.to_string()) });
+
+    let mut shell = DealerSocket::new();
+    shell.connect(&shell_url).await.unwrap();
+    let mut control = DealerSocket::new();
+    control.connect(&control_url).await.unwrap();
+    let execute = |code: &str| {
+        let content = serde_json::json!({
+            "code": code,
+            "silent": false,
+            "store_history": true,
+            "user_expressions": {},
+            "allow_stdin": false,
+            "stop_on_error": true
+        });
+        request("execute_request", content)
+    };
+    shell
+        .send(execute("do[1000000; do[1000000; 0]]"))
+        .await
+        .unwrap();
+    shell.send(execute("x: 42")).await.unwrap();
+    let complete = serde_json::json!({"code": "x", "cursor_pos": 1});
+    shell
+        .send(request("complete_request", complete))
+        .await
+        .unwrap();
+
+    // kernel_info is answered while the cell runs, ahead of the queue
+    shell
+        .send(request("kernel_info_request", serde_json::json!({})))
+        .await
+        .unwrap();
+    assert_eq!(reply(&mut shell).await.header.msg_type, "kernel_info_reply");
+
+    // The rest are answered in order once the cell is interrupted
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    control
+        .send(request("interrupt_request", serde_json::json!({})))
+        .await
+        .unwrap();
+    assert_eq!(reply(&mut control).await.header.msg_type, "interrupt_reply");
+    let types = [
+        reply(&mut shell).await,
+        reply(&mut shell).await,
+        reply(&mut shell).await,
+    ]
+    .map(|reply| reply.header.msg_type);
+    assert_eq!(types, ["execute_reply", "execute_reply", "complete_reply"]);
+
+    // Shutdown is also answered on shell, as older frontends send it there
+    shell
+        .send(request(
+            "shutdown_request",
+            serde_json::json!({"restart": false}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(reply(&mut shell).await.header.msg_type, "shutdown_reply");
+    let stopped = tokio::time::timeout(Duration::from_secs(10), running)
+        .await
+        .expect("kernel still running after shutdown");
+    assert_eq!(stopped.unwrap(), Ok(()));
+}
+
+#[tokio::test]
+async fn test_shutdown_on_control_stops_the_kernel() {
+    let config = config();
+    let control_url = config.control_url();
+    let mut kernel = JupyterKernelRunner::new(config).unwrap();
+    let running = tokio::spawn(async move { kernel.run().await.map_err(|e| e.to_string()) });
+
+    let mut control = DealerSocket::new();
+    control.connect(&control_url).await.unwrap();
+    control
+        .send(request(
+            "shutdown_request",
+            serde_json::json!({"restart": false}),
+        ))
+        .await
+        .unwrap();
+
+    let reply = reply(&mut control).await;
+    assert_eq!(reply.header.msg_type, "shutdown_reply");
+    assert!(matches!(
+        reply.content,
+        JupyterMessageContent::ShutdownReply(ref shutdown) if !shutdown.restart
+    ));
+
+    let stopped = tokio::time::timeout(Duration::from_secs(10), running)
+        .await
+        .expect("kernel still running after shutdown");
+    assert_eq!(stopped.unwrap(), Ok(()));
+}
@@ -0,0 +1,613 @@
+//! Tests for raising, trapping and reporting errors
+mod common;
+
+use common::eval;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wabznasm::environment::Environment;
+use wabznasm::errors::{EvalError, EvalErrorKind};
+use wabznasm::evaluator::Evaluator;
+use wabznasm::jupyter::errors::JupyterErrorFormatter;
+use wabznasm::jupyter::session::JupyterSession;
+use wabznasm::parser::parse_expression;
+use wabznasm::{Session, Value, memory};
+
+#[test]
+fn test_trap_returns_result_when_no_error() {
+    let mut session = Session::new();
+    assert_eq!(
+        session.eval("@[{[x] x+1}; 41; 0]").unwrap(),
+        Value::Integer(42)
+    );
+}
+
+#[test]
+fn test_trap_returns_handler_value_on_error() {
+    let mut session = Session::new();
+    session.eval("f: {[x] 10%x}").unwrap();
+    assert_eq!(session.eval("@[f; 0; -1]").unwrap(), Value::Integer(-1));
+    assert_eq!(session.eval("@[f; 5; -1]").unwrap(), Value::Integer(0));
+}
+
+#[test]
+fn test_trap_passes_message_to_handler_function() {
+    let mut session = Session::new();
+    session.eval("f: {[x] 10%x}").unwrap();
+    assert_eq!(
+        session.eval("@[f; 0; {[e] e}]").unwrap(),
+        Value::Symbol("Division by zero".into())
+    );
+    // Builtins and projections work as handlers too
+    session.eval("pair: {[a;b] (a;b)}").unwrap();
+    assert_eq!(
+        session.eval("@[f; 0; pair[1]]").unwrap(),
+        Value::List(vec![
+            Value::Integer(1),
+            Value::Symbol("Division by zero".into())
+        ])
+    );
+}
+
+#[test]
+fn test_trap_catches_errors_in_nested_calls() {
+    let mut session = Session::new();
+    session.eval("g: {[x] x*undefined}").unwrap();
+    session.eval("f: {[x] 1+g[x]}").unwrap();
+    assert_eq!(session.eval("@[f; 1; 0]").unwrap(), Value::Integer(0));
+}
+
+#[test]
+fn test_operand_errors_are_not_trapped() {
+    let mut session = Session::new();
+    assert!(session.eval("@[{[x] x}; undefined; 0]").is_err());
+}
+
+// Signals, raised with ' and error
+#[test]
+fn test_signal_raises_symbol_message() {
+    let err = eval(&["'`oops"]).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::Signal(ref msg) if msg == "oops"));
+    assert_eq!(err.kind.code(), "SIGNAL");
+    assert_eq!(err.to_string(), "oops");
+}
+
+#[test]
+fn test_signal_span_points_at_signal() {
+    let src = "$[1<0;0;'`negative]";
+    let err = eval(&[src]).unwrap_err();
+    let start = src.find('\'').unwrap();
+    assert_eq!(err.span.offset(), start);
+    assert_eq!(err.span.len(), "'`negative".len());
+}
+
+#[test]
+fn test_signal_formats_non_symbol_values() {
+    let err = eval(&["'42"]).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::Signal(ref msg) if msg == "42"));
+}
+
+#[test]
+fn test_error_builtin() {
+    let err = eval(&["error[`bad]"]).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::Signal(ref msg) if msg == "bad"));
+    let err = eval(&["error[\"bad input\"]"]).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::Signal(ref msg) if msg == "bad input"));
+    let err = eval(&["'\"bad input\""]).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::Signal(ref msg) if msg == "bad input"));
+    let err = eval(&["error[1]"]).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::Type(_)));
+}
+
+#[test]
+fn test_signals_are_trapped() {
+    let mut session = Session::new();
+    session.eval("f: {[x] $[x<0;'`negative;x]}").unwrap();
+    assert_eq!(session.eval("@[f; 3; 0]").unwrap(), Value::Integer(3));
+    assert_eq!(
+        session.eval("@[f; -1; {[e] e}]").unwrap(),
+        Value::Symbol("negative".into())
+    );
+    assert_eq!(
+        session.eval("@[{[x] error[x]}; `bad; {[e] e}]").unwrap(),
+        Value::Symbol("bad".into())
+    );
+}
+
+// Cancellation and timeouts
+/// A loop that runs far longer than any test waits
+const FOREVER: &str = "do[1000000; do[1000000; 0]]";
+
+/// Evaluate `src` in `env`, stopping once `timeout` has passed if given
+fn run_until(
+    evaluator: &mut Evaluator,
+    env: &mut Environment,
+    src: &str,
+    timeout: Option<Duration>,
+) -> Result<Value, EvalError> {
+    let tree = parse_expression(src).unwrap();
+    match timeout {
+        Some(timeout) => {
+            evaluator.eval_with_deadline(tree.root_node(), src, env, Instant::now() + timeout)
+        }
+        None => evaluator.eval_with_env(tree.root_node(), src, env),
+    }
+}
+
+#[test]
+fn test_evaluation_stops_at_its_deadline() {
+    let mut evaluator = Evaluator::new();
+    let mut env = Environment::new();
+    let start = Instant::now();
+    let timeout = Some(Duration::from_millis(50));
+    let err = run_until(&mut evaluator, &mut env, FOREVER, timeout).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::TimedOut));
+    assert_eq!(err.kind.code(), "TIMED_OUT");
+    assert!(start.elapsed() < Duration::from_secs(5));
+    // The deadline ends with the evaluation
+    let result = run_until(&mut evaluator, &mut env, "1+1", None).unwrap();
+    assert_eq!(result, Value::Integer(2));
+}
+
+#[test]
+fn test_compiled_function_bodies_stop_at_the_deadline() {
+    let mut evaluator = Evaluator::new();
+    let mut env = Environment::new();
+    let fib = "fib: {[n] $[n<2;n;fib[n-1]+fib[n-2]]}";
+    run_until(&mut evaluator, &mut env, fib, None).unwrap();
+    let timeout = Some(Duration::from_millis(50));
+    let err = run_until(&mut evaluator, &mut env, "fib[40]", timeout).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::TimedOut));
+}
+
+#[test]
+fn test_cancelling_from_another_thread() {
+    let mut evaluator = Evaluator::new();
+    let mut env = Environment::new();
+    let token = evaluator.cancel_token();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        token.cancel();
+    });
+    let err = run_until(&mut evaluator, &mut env, FOREVER, None).unwrap_err();
+    canceller.join().unwrap();
+    assert!(matches!(err.kind, EvalErrorKind::Cancelled));
+    assert_eq!(err.kind.code(), "CANCELLED");
+    assert!(!evaluator.cancel_token().is_cancelled());
+}
+
+#[test]
+fn test_cancelling_while_idle_is_forgotten() {
+    let mut evaluator = Evaluator::new();
+    let mut env = Environment::new();
+    evaluator.cancel_token().cancel();
+    let result = run_until(&mut evaluator, &mut env, "1+1", None).unwrap();
+    assert_eq!(result, Value::Integer(2));
+}
+
+#[test]
+fn test_trap_does_not_catch_a_timeout() {
+    let mut evaluator = Evaluator::new();
+    let mut env = Environment::new();
+    let src = format!("@[{{[x] {}}}; 0; {{[e] 42}}]", FOREVER);
+    let timeout = Some(Duration::from_millis(50));
+    let err = run_until(&mut evaluator, &mut env, &src, timeout).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::TimedOut));
+}
+
+#[test]
+fn test_session_timeout() {
+    let mut session = Session::new();
+    assert_eq!(session.timeout(), None);
+    session.set_timeout(Some(Duration::from_millis(50)));
+    let err = session.eval(FOREVER).unwrap_err();
+    assert_eq!(err.to_string(), "Evaluation timed out");
+    // Bindings and later evaluations are unaffected
+    session.eval("a: 41").unwrap();
+    assert_eq!(session.eval("a+1").unwrap(), Value::Integer(42));
+}
+
+// The memory limit
+/// A session in which no value may take more than `limit` bytes
+fn limited(limit: usize) -> Session {
+    let mut session = Session::new();
+    session.set_memory_limit(Some(limit));
+    session
+}
+
+fn error_code(session: &mut Session, source: &str) -> String {
+    let report = session.eval(source).unwrap_err();
+    report.code().unwrap().to_string()
+}
+
+#[test]
+fn test_memory_is_unlimited_by_default() {
+    let mut session = Session::new();
+    assert_eq!(session.memory_limit(), None);
+    let count = session.eval("count 100000#0").unwrap();
+    assert_eq!(count, Value::Integer(100000));
+}
+
+#[test]
+fn test_long_lists_fail_before_they_are_built() {
+    let mut session = limited(1 << 20);
+    for source in ["1000000000#0", "-1000000000#0", "1000000000?10"] {
+        assert_eq!(error_code(&mut session, source), "MEMORY_LIMIT_EXCEEDED");
+    }
+    let message = session.eval("1000000000#0").unwrap_err().to_string();
+    assert_eq!(
+        message,
+        "Memory limit exceeded: a value would take more than 1048576 bytes"
+    );
+    // Lists within the limit are built as usual
+    let count = session.eval("count 1000#0").unwrap();
+    assert_eq!(count, Value::Integer(1000));
+}
+
+#[test]
+fn test_computed_values_are_measured() {
+    let mut session = limited(memory::list_size(1000) * 3 / 2);
+    session.eval("x: 1000#1").unwrap();
+    session.eval("g: {[v] 2#enlist v}").unwrap();
+    let count = session.eval("count x+1").unwrap();
+    assert_eq!(count, Value::Integer(1000));
+    // Each copy of x is within the limit, but not two together
+    assert_eq!(
+        error_code(&mut session, "2#enlist x"),
+        "MEMORY_LIMIT_EXCEEDED"
+    );
+    assert_eq!(error_code(&mut session, "g[x]"), "MEMORY_LIMIT_EXCEEDED");
+}
+
+#[test]
+fn test_trap_catches_the_limit() {
+    let mut session = limited(1 << 20);
+    let caught = session.eval("@[{[n] n#0}; 1000000000; {[e] `caught}]");
+    assert_eq!(caught.unwrap(), Value::Symbol("caught".into()));
+}
+
+// Stack traces
+fn eval_error(session: &mut JupyterSession, code: &str) -> EvalError {
+    session.execute(code).unwrap_err()
+}
+
+#[test]
+fn test_errors_record_the_calls_they_occurred_in() {
+    let mut session = JupyterSession::new();
+    session.execute("f: {[x] x+`a}").unwrap();
+    session.execute("g: {[y] 1+f[y]}").unwrap();
+    let error = eval_error(&mut session, "g[2]");
+    let calls: Vec<_> = error
+        .stack
+        .iter()
+        .map(|frame| frame.function.as_deref())
+        .collect();
+    assert_eq!(calls, [Some("f"), Some("g")]);
+    assert_eq!(
+        error.trace(),
+        ["in f, called as f[y]", "in g, called as g[2]"]
+    );
+    // The outermost call is spanned in the cell's source
+    let outer = &error.stack[1];
+    assert_eq!((outer.span.offset(), outer.span.len()), (0, 4));
+}
+
+#[test]
+fn test_errors_outside_functions_have_no_trace() {
+    let mut session = JupyterSession::new();
+    let error = eval_error(&mut session, "1+`a");
+    assert!(error.stack.is_empty());
+    assert!(error.trace().is_empty());
+}
+
+#[test]
+fn test_projections_are_named_by_their_function() {
+    let mut session = JupyterSession::new();
+    session.execute("add: {[x;y] x+y}").unwrap();
+    session.execute("p: add[`a]").unwrap();
+    let error = eval_error(&mut session, "p[1]");
+    assert_eq!(error.trace(), ["in add, called as p[1]"]);
+}
+
+#[test]
+fn test_deep_recursion_elides_the_middle_of_the_trace() {
+    let mut session = JupyterSession::new();
+    session.execute("r: {[n] r[n+1]}").unwrap();
+    let error = eval_error(&mut session, "r[0]");
+    let trace = error.trace();
+    assert_eq!(trace.len(), 17);
+    assert_eq!(trace[0], "in r, called as r[n+1]");
+    assert!(trace[8].ends_with("more calls"), "{}", trace[8]);
+    assert_eq!(trace[16], "in r, called as r[0]");
+}
+
+#[test]
+fn test_traces_are_shown_in_reports_and_tracebacks() {
+    let mut session = Session::new();
+    session.eval("f: {[x] x+`a}").unwrap();
+    let report = session.eval("f[1]").unwrap_err();
+    assert_eq!(report.help().unwrap().to_string(), "in f, called as f[1]");
+
+    let mut session = JupyterSession::new();
+    session.execute("f: {[x] x+`a}").unwrap();
+    let error = eval_error(&mut session, "f[1]");
+    let traceback = JupyterErrorFormatter::create_traceback(&error, "f[1]");
+    assert_eq!(traceback.last().unwrap(), "  in f, called as f[1]");
+}
+
+// Error spans in function bodies mapped to the defining source
+/// The source attached to `error` and the text its span underlines there
+fn underlined(error: &EvalError) -> (&str, &str) {
+    let source = error.src.as_ref().unwrap().inner().as_str();
+    let start = error.span.offset();
+    (source, &source[start..start + error.span.len()])
+}
+
+#[test]
+fn test_errors_in_bodies_point_into_the_defining_source() {
+    let mut session = Session::new();
+    let report = session.eval("f: {[x] x+`a}; f[1]").unwrap_err();
+    let error = report.downcast_ref::<EvalError>().unwrap();
+    assert_eq!(underlined(error), ("f: {[x] x+`a}; f[1]", "x+`a"));
+    assert_eq!(error.span.offset(), 8);
+}
+
+#[test]
+fn test_functions_defined_earlier_point_to_their_definition() {
+    let mut session = JupyterSession::new();
+    session.execute("f: {[x]\n  x+`a}").unwrap();
+    session.execute("g: {[y] 2*f[y]}").unwrap();
+    let error = session.execute("g[1]").unwrap_err();
+    assert_eq!(underlined(&error), ("f: {[x]\n  x+`a}", "x+`a"));
+    let traceback = JupyterErrorFormatter::create_traceback(&error, "g[1]");
+    assert_eq!(traceback[1], "  at line 2, column 3: x+`a");
+}
+
+#[test]
+fn test_lambdas_defined_in_bodies_point_into_the_cell() {
+    let mut session = JupyterSession::new();
+    let source = "g: {[y] h: {[x] x+`a}; 1+h[y]}";
+    session.execute(source).unwrap();
+    let error = session.execute("g[2]").unwrap_err();
+    assert_eq!(underlined(&error), (source, "x+`a"));
+}
+
+#[test]
+fn test_errors_outside_functions_keep_their_spans() {
+    let mut session = Session::new();
+    session.eval("f: {[x] x}").unwrap();
+    let report = session.eval("f[1]+`b").unwrap_err();
+    let error = report.downcast_ref::<EvalError>().unwrap();
+    assert_eq!(underlined(error), ("f[1]+`b", "f[1]+`b"));
+}
+
+#[test]
+fn test_reports_label_the_span() {
+    let mut session = Session::new();
+    session.eval("f: {[x] x+`a}").unwrap();
+    let report = session.eval("f[1]").unwrap_err();
+    let labels: Vec<_> = report.labels().unwrap().collect();
+    assert_eq!(labels.len(), 1);
+    assert_eq!((labels[0].offset(), labels[0].len()), (8, 4));
+    let source = report.source_code().unwrap();
+    let contents = source.read_span(labels[0].inner(), 0, 0).unwrap();
+    assert_eq!(contents.data(), b"x+`a");
+}
+
+#[test]
+fn test_lambdas_share_the_source_they_were_defined_in() {
+    let mut session = Session::new();
+    session.eval("f: {[x] x}; g: {[x] x+1}").unwrap();
+    session.eval("h: {[x] x+2}").unwrap();
+    let source = |name| match session.get(name) {
+        Some(Value::Function {
+            origin: Some(origin),
+            ..
+        }) => origin.source.clone(),
+        other => panic!("{} is {:?}", name, other),
+    };
+    let (f, g, h) = (source("f"), source("g"), source("h"));
+    assert!(Arc::ptr_eq(&f, &g));
+    assert_eq!(&*h, "h: {[x] x+2}");
+}
+
+// Warnings
+/// The codes of the warnings about `source`, after evaluating it
+fn codes(session: &mut Session, source: &str) -> Vec<&'static str> {
+    let _ = session.eval(source);
+    session.warnings().iter().map(|w| w.kind.code()).collect()
+}
+
+#[test]
+fn test_clean_code_has_no_warnings() {
+    let mut session = Session::new();
+    assert!(codes(&mut session, "f: {[x;y] x+y}; f[1;2]").is_empty());
+    assert!(codes(&mut session, "n: 9007199254740993").is_empty());
+}
+
+#[test]
+fn test_shadowing_a_builtin() {
+    let mut session = Session::new();
+    assert_eq!(codes(&mut session, "count: 3"), ["SHADOWS_BUILTIN"]);
+    // The binding is made all the same
+    assert_eq!(session.get("count"), Some(Value::Integer(3)));
+    assert_eq!(codes(&mut session, "g: {[sum] sum*2}"), ["SHADOWS_BUILTIN"]);
+    let warning = &session.warnings()[0];
+    assert_eq!(
+        warning.to_string(),
+        "sum is a builtin; binding it hides the builtin"
+    );
+    assert_eq!((warning.span.offset(), warning.span.len()), (5, 3));
+}
+
+#[test]
+fn test_unused_parameters() {
+    let mut session = Session::new();
+    assert_eq!(codes(&mut session, "f: {[x;y] x+1}"), ["UNUSED_PARAMETER"]);
+    assert_eq!(
+        session.warnings()[0].to_string(),
+        "Parameter y is never used"
+    );
+    // Parameters used only by nested lambdas are used
+    assert!(codes(&mut session, "g: {[x] {[y] x+y}}").is_empty());
+    // Nested lambdas are checked when the outer one is defined
+    assert_eq!(
+        codes(&mut session, "h: {[x] {[y] x}}"),
+        ["UNUSED_PARAMETER"]
+    );
+}
+
+#[test]
+fn test_overflow_prone_literals() {
+    let mut session = Session::new();
+    let source = "a: 4611686018427387904";
+    assert_eq!(codes(&mut session, source), ["OVERFLOW_PRONE_LITERAL"]);
+    assert_eq!(codes(&mut session, "a+1"), Vec::<&str>::new());
+}
+
+#[test]
+fn test_warnings_do_not_stop_evaluation_and_are_kept_per_input() {
+    let mut session = Session::new();
+    let value = session.eval("f: {[x;y] 42}; f[1;2]").unwrap();
+    assert_eq!(value, Value::Integer(42));
+    assert_eq!(session.warnings().len(), 2);
+    session.eval("1+1").unwrap();
+    assert!(session.warnings().is_empty());
+    // Inputs that fail are still checked
+    assert_eq!(codes(&mut session, "max: 1+`a"), ["SHADOWS_BUILTIN"]);
+}
+
+#[test]
+fn test_jupyter_cells_collect_warnings() {
+    let mut session = JupyterSession::new();
+    let code = "f: {[x;y]\n  x}";
+    session.execute(code).unwrap();
+    let warnings = session.warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].describe(code),
+        "Warning: Parameter y is never used (line 1, column 8)"
+    );
+    session.execute("f[1;2]").unwrap();
+    assert!(session.warnings().is_empty());
+}
+
+// Error codes
+/// Inputs evaluated in turn, and the code of the error the last one fails with
+type Case<'a> = (&'a [&'a str], &'a str);
+
+/// The code of the error that evaluating `sources` in turn ends in
+fn code(sources: &[&str]) -> String {
+    let mut session = Session::new();
+    let (last, setup) = sources.split_last().unwrap();
+    for source in setup {
+        session.eval(source).unwrap();
+    }
+    let report = session.eval(last).unwrap_err();
+    report.code().unwrap().to_string()
+}
+
+#[test]
+fn test_each_failure_has_its_own_code() {
+    let cases: &[Case] = &[
+        (&["nothing+1"], "UNDEFINED_VARIABLE"),
+        (&["f: {[x;y] x+y}", "f[1;2;3]"], "ARITY_MISMATCH"),
+        (&["count[1;2]"], "ARITY_MISMATCH"),
+        (&["n: 5", "n[1]"], "NOT_CALLABLE"),
+        (&["1+`a"], "TYPE_ERROR"),
+        (&["1 2+1 2 3"], "LENGTH_MISMATCH"),
+        (&["1 2 3=1 2"], "LENGTH_MISMATCH"),
+        (&["xs: 1 2 3", "xs[5]"], "INDEX_OUT_OF_RANGE"),
+        (&["d: `a`b!1 2", "d[`c]"], "KEY_NOT_FOUND"),
+        (&["t: flip `a`b!(1 2;3 4)", "t[`c]"], "KEY_NOT_FOUND"),
+        (&["3#()"], "DOMAIN_ERROR"),
+        (&["3?0"], "DOMAIN_ERROR"),
+        (&["3?()"], "DOMAIN_ERROR"),
+        (&["-5?3"], "LENGTH_MISMATCH"),
+        (&["inv[(1 2;2 4)]"], "DOMAIN_ERROR"),
+        (&["mmu[(1 2;3 4);(1 2;3 4;5 6)]"], "LENGTH_MISMATCH"),
+        (&["vs[\"\";\"abc\"]"], "DOMAIN_ERROR"),
+        (&["format[\"{\";1]"], "DOMAIN_ERROR"),
+        (&["format[\"{} and {}\";1 2 3]"], "LENGTH_MISMATCH"),
+        (
+            &["k: xkey[`a`b; flip `a`b`c!(1 2;3 4;5 6)]", "k[(1;3;5)]"],
+            "LENGTH_MISMATCH",
+        ),
+    ];
+    for (sources, expected) in cases {
+        assert_eq!(code(sources), *expected, "{:?}", sources);
+    }
+}
+
+#[test]
+fn test_messages_name_what_went_wrong() {
+    let mut session = Session::new();
+    let message = |session: &mut Session, source| session.eval(source).unwrap_err().to_string();
+    assert_eq!(
+        message(&mut session, "nothing"),
+        "Undefined variable: nothing"
+    );
+    session.eval("n: 5").unwrap();
+    assert_eq!(
+        message(&mut session, "n[1]"),
+        "Cannot call non-function value: integer"
+    );
+    session.eval("d: `a`b!1 2").unwrap();
+    assert_eq!(message(&mut session, "d[`c]"), "Key not found: `c");
+    session.eval("f: {[x] x}").unwrap();
+    assert_eq!(
+        message(&mut session, "f[1;2]"),
+        "Arity mismatch: expected 1 arguments, got 2"
+    );
+}
+
+// Suggestions for misspelt names
+fn suggestion(session: &mut Session, source: &str) -> Option<String> {
+    let report = session.eval(source).unwrap_err();
+    report
+        .downcast_ref::<EvalError>()
+        .unwrap()
+        .suggestion
+        .clone()
+}
+
+#[test]
+fn test_misspelt_names_suggest_the_nearest() {
+    let mut session = Session::new();
+    session.eval("increment: {[x] x+1}").unwrap();
+    assert_eq!(
+        suggestion(&mut session, "incremnt[2]").as_deref(),
+        Some("increment")
+    );
+    // Builtins are suggested too
+    assert_eq!(
+        suggestion(&mut session, "cuont 1 2 3").as_deref(),
+        Some("count")
+    );
+    // As are the parameters and locals of the function being called
+    session.eval("f: {[price] prise*2}").unwrap();
+    assert_eq!(suggestion(&mut session, "f[1]").as_deref(), Some("price"));
+}
+
+#[test]
+fn test_distant_names_suggest_nothing() {
+    let mut session = Session::new();
+    session.eval("increment: {[x] x+1}").unwrap();
+    assert_eq!(suggestion(&mut session, "quux"), None);
+}
+
+#[test]
+fn test_suggestions_label_reports_and_tracebacks() {
+    let mut session = Session::new();
+    session.eval("total: 10").unwrap();
+    let report = session.eval("1+totl").unwrap_err();
+    let labels: Vec<_> = report.labels().unwrap().collect();
+    assert_eq!(labels[0].label(), Some("did you mean `total`?"));
+    assert_eq!((labels[0].offset(), labels[0].len()), (2, 4));
+
+    let mut session = JupyterSession::new();
+    session.execute("total: 10").unwrap();
+    let error = session.execute("totl").unwrap_err();
+    let traceback = JupyterErrorFormatter::create_traceback(&error, "totl");
+    assert!(traceback.contains(&"  did you mean `total`?".to_string()));
+}
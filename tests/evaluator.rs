@@ -1,7 +1,13 @@
 //! Tests for the wabznasm expression evaluator.
-// EvalError is not directly asserted by type in these tests, relying on string messages.
-// If specific error kinds need to be asserted, import wabznasm::errors::EvalErrorKind;
-use wabznasm::evaluator::evaluate_expression; // Use the standalone function
+mod common;
+
+use common::{eval_lines, run, show, shown, value};
+use wabznasm::arithmetic::OverflowMode;
+use wabznasm::environment::{INFINITY_INTEGER, NULL_INTEGER};
+use wabznasm::errors::EvalErrorKind;
+use wabznasm::evaluator::{DEFAULT_MAX_ITERATIONS, Evaluator, evaluate_expression};
+use wabznasm::temporal::NANOS_PER_DAY;
+use wabznasm::{Session, Value};
 
 /// Test simple addition
 #[test]
@@ -322,3 +328,695 @@ fn test_evaluator_table_driven_mod_by_zero() {
     let msg = format!("{}", err);
     assert!(msg.contains("Division by zero"));
 }
+
+// Bitwise operations and hex and binary literals
+#[test]
+fn test_hex_and_binary_literals() {
+    let mut s = Session::new();
+    assert_eq!(s.eval("0x1f").unwrap(), Value::Integer(31));
+    assert_eq!(s.eval("0xFF").unwrap(), Value::Integer(255));
+    assert_eq!(s.eval("0b1010").unwrap(), Value::Integer(10));
+    // All 64 bits may be given
+    assert_eq!(s.eval("0xffffffffffffffff").unwrap(), Value::Integer(-1));
+    assert!(s.eval("0x10000000000000000").is_err());
+    assert_eq!(show(&mut s, "0x1f 0x20 3"), "31 32 3");
+    // Boolean literals are unchanged
+    assert_eq!(s.eval("0b").unwrap(), Value::Boolean(false));
+    assert_eq!(show(&mut s, "0101b"), "0101b");
+}
+
+#[test]
+fn test_bitwise_operations() {
+    let mut s = Session::new();
+    assert_eq!(s.eval("and[0b1100;0b1010]").unwrap(), Value::Integer(8));
+    assert_eq!(s.eval("or[0b1100;0b1010]").unwrap(), Value::Integer(14));
+    assert_eq!(s.eval("xor[0b1100;0b1010]").unwrap(), Value::Integer(6));
+    assert_eq!(show(&mut s, "and[1 2 3 6;2]"), "0 2 2 2");
+    assert_eq!(show(&mut s, "and[1010b;0110b]"), "0010b");
+    assert_eq!(s.eval("or[8;1b]").unwrap(), Value::Integer(9));
+    assert!(s.eval("and[1.5;2]").is_err());
+    assert!(s.eval("and[1 2;1 2 3]").is_err());
+}
+
+#[test]
+fn test_shifts() {
+    let mut s = Session::new();
+    assert_eq!(s.eval("shl[1;10]").unwrap(), Value::Integer(1024));
+    assert_eq!(show(&mut s, "shr[1024;1 2 3]"), "512 256 128");
+    // Right shifts keep the sign
+    assert_eq!(s.eval("shr[-16;2]").unwrap(), Value::Integer(-4));
+    // Bits shifted off the top are dropped
+    assert_eq!(
+        s.eval("shl[0x4000000000000001;2]").unwrap(),
+        Value::Integer(4)
+    );
+    assert!(s.eval("shl[1;64]").is_err());
+    assert!(s.eval("shr[1;-1]").is_err());
+}
+
+#[test]
+fn test_flags_in_tables() {
+    let mut s = Session::new();
+    s.eval("t:flip `id`flags!(1 2 3 4;0b01 0b10 0b11 0b00)")
+        .unwrap();
+    assert_eq!(
+        show(&mut s, "r:select id from t where 0<and[flags;0b10]; r `id"),
+        "2 3"
+    );
+}
+
+// Integer overflow, big integers and floats
+const MAX: &str = "9223372036854775807";
+
+fn eval_with(mode: OverflowMode, source: &str) -> Value {
+    let mut session = Session::new();
+    session.set_overflow_mode(mode);
+    session.eval(source).unwrap()
+}
+
+#[test]
+fn test_overflow_is_an_error_by_default() {
+    let mut session = Session::new();
+    assert_eq!(session.overflow_mode(), OverflowMode::Error);
+    let err = session.eval(&format!("{}+1", MAX)).unwrap_err();
+    assert!(err.to_string().contains("Integer overflow"));
+}
+
+#[test]
+fn test_wrap() {
+    assert_eq!(
+        eval_with(OverflowMode::Wrap, &format!("{}+1", MAX)),
+        Value::Integer(i64::MIN)
+    );
+    assert_eq!(
+        eval_with(OverflowMode::Wrap, &format!("-(-{}-1)", MAX)),
+        Value::Integer(i64::MIN)
+    );
+    assert_eq!(
+        eval_with(OverflowMode::Wrap, "2^63"),
+        Value::Integer(i64::MIN)
+    );
+}
+
+#[test]
+fn test_saturate() {
+    assert_eq!(
+        eval_with(OverflowMode::Saturate, &format!("{}*2", MAX)),
+        Value::Integer(i64::MAX)
+    );
+    assert_eq!(
+        eval_with(OverflowMode::Saturate, &format!("(-{})-10", MAX)),
+        Value::Integer(i64::MIN)
+    );
+}
+
+#[test]
+fn test_promote() {
+    let mut session = Session::new();
+    session.set_overflow_mode(OverflowMode::Promote);
+    let big = session.eval(&format!("x: {}*4", MAX)).unwrap();
+    assert_eq!(big, Value::Float(i64::MAX as f64 * 4.0));
+    // Promoted values keep taking part in arithmetic
+    assert_eq!(session.eval("x/4").unwrap(), Value::Float(i64::MAX as f64));
+    assert_eq!(session.eval("2+3").unwrap(), Value::Integer(5));
+}
+
+#[test]
+fn test_bigint() {
+    let mut session = Session::new();
+    assert!(session.eval("9999999999999999*999999").is_err());
+    session.set_overflow_mode(OverflowMode::BigInt);
+    assert_eq!(
+        show(&mut session, "x: 9999999999999999*999999"),
+        "9999989999999999000001"
+    );
+    assert_eq!(show(&mut session, "type x"), "`bigint");
+    // Big integers stay exact, and turn back into integers when they fit
+    assert_eq!(show(&mut session, "x+1"), "9999989999999999000002");
+    assert_eq!(
+        session.eval("x/999999").unwrap(),
+        Value::Integer(9999999999999999)
+    );
+    assert_eq!(session.eval("x-x").unwrap(), Value::Integer(0));
+    assert_eq!(show(&mut session, "-x"), "-9999989999999999000001");
+    assert_eq!(show(&mut session, "2^63"), "9223372036854775808");
+    assert_eq!(
+        show(&mut session, &format!("{}+1 2", MAX)),
+        "9223372036854775808 9223372036854775809"
+    );
+    assert_eq!(session.eval("x>0W").unwrap(), Value::Boolean(true));
+    assert_eq!(session.eval("x+0N").unwrap(), Value::Integer(i64::MIN));
+    assert!(session.eval("x/0").is_err());
+    // Against a float the result is a float
+    assert_eq!(
+        session.eval("x*1.0").unwrap(),
+        Value::Float(9999989999999999000001.0)
+    );
+}
+
+#[test]
+fn test_bigint_cast() {
+    // A big operand makes arithmetic exact whatever the mode
+    let mut session = Session::new();
+    assert_eq!(
+        show(
+            &mut session,
+            "y: `bigint$\"123456789012345678901234567890\"; y*10"
+        ),
+        "1234567890123456789012345678900"
+    );
+    assert_eq!(session.eval("`bigint$42").unwrap(), Value::Integer(42));
+    assert_eq!(
+        session.eval("`integer$y").unwrap(),
+        Value::Integer(i64::MAX)
+    );
+    assert!(session.eval("`bigint$\"12x\"").is_err());
+}
+
+#[test]
+fn test_float_arithmetic_and_display() {
+    let mut session = Session::new();
+    assert_eq!(session.eval("1.5+1").unwrap(), Value::Float(2.5));
+    assert_eq!(session.eval("7/2.0").unwrap(), Value::Float(3.5));
+    assert_eq!(session.eval("-0.5").unwrap(), Value::Float(-0.5));
+    assert_eq!(session.eval("1.5>1").unwrap(), Value::Boolean(true));
+    assert!(session.eval("1.5/0").is_err());
+
+    let whole = session.eval("0.5*4").unwrap();
+    assert_eq!(session.format(&whole), "2f");
+    let list = session.eval("1.5 2.0").unwrap();
+    assert_eq!(session.format(&list), "1.5 2");
+    let list = session.eval("1.0 2.0").unwrap();
+    assert_eq!(session.format(&list), "1 2f");
+}
+
+#[test]
+fn test_whole_floats_read_back_as_floats() {
+    let mut session = Session::new();
+    for source in [
+        "2.0*2",
+        "1.0 2.0",
+        "-3.0",
+        "1000000.0*1000000000",
+        "0.5 2.0",
+    ] {
+        let value = session.eval(source).unwrap();
+        let text = session.format(&value);
+        assert_eq!(
+            session.eval(&text).unwrap(),
+            value,
+            "{} printed {}",
+            source,
+            text
+        );
+    }
+    assert_eq!(session.eval("4f").unwrap(), Value::Float(4.0));
+    assert_eq!(session.eval("0x1f").unwrap(), Value::Integer(31));
+}
+
+// Nulls and infinities
+#[test]
+fn test_null_and_infinity_literals() {
+    assert_eq!(value("0N"), Value::Integer(NULL_INTEGER));
+    assert_eq!(value("0W"), Value::Integer(INFINITY_INTEGER));
+    assert_eq!(value("-0W"), Value::Integer(-INFINITY_INTEGER));
+    assert!(value("0n").is_null());
+    assert_eq!(value("0w"), Value::Float(f64::INFINITY));
+    assert_eq!(value("-0w"), Value::Float(f64::NEG_INFINITY));
+}
+
+#[test]
+fn test_nulls_format_as_literals() {
+    for source in ["0N", "0W", "-0W", "0n", "0w", "-0w", "1 0N 3", "1.5 0n 0w"] {
+        assert_eq!(shown(source), source);
+    }
+}
+
+#[test]
+fn test_float_vectors_promote_integers() {
+    assert_eq!(
+        value("1 0n"),
+        Value::List(vec![Value::Float(1.0), Value::Float(f64::NAN)])
+    );
+    assert_eq!(shown("1 0n"), "1 0n");
+    assert_eq!(
+        value("0W 1.5"),
+        Value::List(vec![Value::Float(f64::INFINITY), Value::Float(1.5)])
+    );
+}
+
+#[test]
+fn test_arithmetic_propagates_nulls() {
+    assert_eq!(value("0N+1"), Value::Integer(NULL_INTEGER));
+    assert_eq!(value("2*0N"), Value::Integer(NULL_INTEGER));
+    assert_eq!(value("-0N"), Value::Integer(NULL_INTEGER));
+    assert_eq!(value("0N^2"), Value::Integer(NULL_INTEGER));
+    // Null operands take precedence over division by zero
+    assert_eq!(value("0N%0"), Value::Integer(NULL_INTEGER));
+    assert!(value("0N+1.5").is_null());
+    assert!(value("0n*2").is_null());
+    assert_eq!(value("0w+1"), Value::Float(f64::INFINITY));
+}
+
+#[test]
+fn test_nulls_compare_equal_and_sort_first() {
+    assert_eq!(value("0N=0N"), Value::Boolean(true));
+    assert_eq!(value("0n=0n"), Value::Boolean(true));
+    assert_eq!(value("0N<-0W"), Value::Boolean(true));
+    assert_eq!(value("0n<-0w"), Value::Boolean(true));
+    assert_eq!(value("0W>1"), Value::Boolean(true));
+    assert_eq!(
+        value("asc[3 0N 1]"),
+        Value::List(vec![
+            Value::Integer(NULL_INTEGER),
+            Value::Integer(1),
+            Value::Integer(3)
+        ])
+    );
+}
+
+#[test]
+fn test_fill_replaces_nulls() {
+    assert_eq!(shown("fill[0;1 0N 3]"), "1 0 3");
+    assert_eq!(shown("fill[0.5;0n 2.5]"), "0.5 2.5");
+    assert_eq!(shown("fill[1 2 3;10 0N 30]"), "10 2 30");
+    assert_eq!(shown("fill[`z;`a``b]"), "`a`z`b");
+    assert_eq!(shown("fill[7;0N]"), "7");
+    assert_eq!(shown("fill[`a`b!1 2;`b`c!0N 3]"), shown("`a`b`c!1 2 3"));
+    assert!(Session::new().eval("fill[1 2;0N 0N 0N]").is_err());
+}
+
+#[test]
+fn test_caret_fills_unless_both_sides_are_integers() {
+    assert_eq!(shown("0^1 0N 3"), "1 0 3");
+    assert_eq!(shown("0.5^0n 2.5"), "0.5 2.5");
+    assert_eq!(shown("1 2 3^10 0N 30"), "10 2 30");
+    assert_eq!(shown("`z^`a``b"), "`a`z`b");
+    assert_eq!(
+        shown("0^flip `px`qty!(1 0N;0N 5)"),
+        shown("flip `px`qty!(1 0;0 5)")
+    );
+    // Between two integers it is still the power operator
+    assert_eq!(shown("2^3"), "8");
+    assert_eq!(value("0N^2"), Value::Integer(NULL_INTEGER));
+}
+
+#[test]
+fn test_fills_carries_values_forward() {
+    assert_eq!(shown("fills 0N 1 0N 0N 4 0N"), "0N 1 1 1 4 4");
+    assert_eq!(shown("fills 1.5 0n 2"), "1.5 1.5 2");
+    assert_eq!(shown("fills `a``b`"), "`a`a`b`b");
+    assert_eq!(
+        shown("fills flip `px`sym!(1 0N 3;`x``y)"),
+        shown("flip `px`sym!(1 1 3;`x`x`y)")
+    );
+    assert_eq!(
+        shown("fill[0;flip `px`qty!(1 0N;0N 5)]"),
+        shown("flip `px`qty!(1 0;0 5)")
+    );
+}
+
+// Types and casts
+fn sym(name: &str) -> Value {
+    Value::Symbol(name.into())
+}
+
+#[test]
+fn test_type_names_every_value() {
+    assert_eq!(value("type 1"), sym("integer"));
+    assert_eq!(value("type 1.5"), sym("float"));
+    assert_eq!(value("type 1b"), sym("boolean"));
+    assert_eq!(value("type `a"), sym("symbol"));
+    assert_eq!(value("type 1 2 3"), sym("list"));
+    assert_eq!(value("type `a`b!1 2"), sym("dict"));
+    assert_eq!(value("type {[x] x}"), sym("function"));
+    assert_eq!(value("type type"), sym("function"));
+}
+
+#[test]
+fn test_cast_numbers() {
+    assert_eq!(value("`long$2.5"), Value::Integer(3));
+    assert_eq!(value("`integer$-1.4"), Value::Integer(-1));
+    assert_eq!(value("`float$3"), Value::Float(3.0));
+    assert_eq!(value("`boolean$0 2"), Value::from(vec![false, true]));
+    assert_eq!(value("`long$1b"), Value::Integer(1));
+    assert_eq!(
+        value("`float$1 2"),
+        Value::List(vec![Value::Float(1.0), Value::Float(2.0)])
+    );
+}
+
+#[test]
+fn test_cast_keeps_nulls_and_infinities() {
+    assert_eq!(value("`long$0n"), Value::Integer(NULL_INTEGER));
+    assert_eq!(value("`long$0w"), Value::Integer(INFINITY_INTEGER));
+    assert!(value("`float$0N").is_null());
+    assert_eq!(value("`float$-0W"), Value::Float(f64::NEG_INFINITY));
+}
+
+#[test]
+fn test_cast_to_type_of_another_value() {
+    let mut session = Session::new();
+    session.eval("like: {[x;y] (type x)$y}").unwrap();
+    assert_eq!(session.eval("like[1.5; 2]").unwrap(), Value::Float(2.0));
+}
+
+#[test]
+fn test_invalid_casts() {
+    let mut session = Session::new();
+    let err = session.eval("`string$1").unwrap_err();
+    assert!(err.to_string().contains("unknown type"));
+    let err = session.eval("`long$`a").unwrap_err();
+    assert!(err.to_string().contains("cannot cast symbol to long"));
+    assert!(session.eval("1$2").is_err());
+}
+
+// Dates, times and timestamps
+#[test]
+fn test_literals_round_trip() {
+    assert_eq!(value("1970.01.02"), Value::Date(1));
+    assert_eq!(shown("2024.01.15"), "2024.01.15");
+    assert_eq!(value("00:00:01.500"), Value::Time(1500));
+    assert_eq!(shown("12:30"), "12:30:00.000");
+    assert_eq!(value("1970.01.01D00:00:00.000000001"), Value::Timestamp(1));
+    assert_eq!(
+        shown("2024.01.15D12:30:00.25"),
+        "2024.01.15D12:30:00.250000000"
+    );
+    assert_eq!(value("1970.01.02D"), Value::Timestamp(NANOS_PER_DAY));
+}
+
+#[test]
+fn test_invalid_literal_is_an_error() {
+    assert!(Session::new().eval("2024.02.30").is_err());
+    assert!(Session::new().eval("25:61").is_err());
+}
+
+#[test]
+fn test_arithmetic() {
+    assert_eq!(shown("2024.01.31+1"), "2024.02.01");
+    assert_eq!(shown("2024.03.01-1"), "2024.02.29");
+    assert_eq!(value("2024.01.15-2024.01.01"), Value::Integer(14));
+    assert_eq!(shown("12:00+1000"), "12:00:01.000");
+    assert_eq!(value("12:00-11:00"), Value::Integer(3_600_000));
+    assert_eq!(
+        value("2024.01.15D00:00:01-2024.01.15D"),
+        Value::Integer(1_000_000_000)
+    );
+    assert_eq!(shown("2024.01.15+12:30"), "2024.01.15D12:30:00.000000000");
+    assert!(Session::new().eval("2024.01.15*2").is_err());
+    assert!(Session::new().eval("2024.01.15-12:00").is_err());
+}
+
+#[test]
+fn test_comparison_and_sorting() {
+    assert_eq!(value("2024.01.15<2024.01.16"), Value::Boolean(true));
+    assert_eq!(value("12:00=12:00:00"), Value::Boolean(true));
+    assert_eq!(
+        shown("asc (2024.01.16;2024.01.15)"),
+        "(2024.01.15;2024.01.16)"
+    );
+}
+
+#[test]
+fn test_types_and_casts() {
+    assert_eq!(value("type 2024.01.15"), Value::Symbol("date".into()));
+    assert_eq!(value("type 12:00"), Value::Symbol("time".into()));
+    assert_eq!(value("type 2024.01.15D"), Value::Symbol("timestamp".into()));
+    assert_eq!(value("`long$1970.01.11"), Value::Integer(10));
+    assert_eq!(value("`date$10"), Value::Date(10));
+    assert_eq!(shown("`date$2024.01.15D23:59"), "2024.01.15");
+    assert_eq!(shown("`time$2024.01.15D23:59"), "23:59:00.000");
+    assert_eq!(
+        shown("`timestamp$2024.01.15"),
+        "2024.01.15D00:00:00.000000000"
+    );
+}
+
+// Random rolls, deals and samples
+/// The value of `source` in `session`
+fn eval_in(session: &mut Session, source: &str) -> Value {
+    session.eval(source).unwrap()
+}
+
+/// The items of a list
+fn items(value: Value) -> Vec<Value> {
+    match value {
+        Value::List(items) => items,
+        other => panic!("expected a list, got {:?}", other),
+    }
+}
+
+/// The items of a list of integers
+fn integers(value: Value) -> Vec<i64> {
+    items(value)
+        .into_iter()
+        .map(|item| item.as_integer().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_roll_integers() {
+    let mut s = Session::new();
+    let rolled = integers(eval_in(&mut s, "1000?10"));
+    assert_eq!(rolled.len(), 1000);
+    assert!(rolled.iter().all(|n| (0..10).contains(n)));
+    // Every value turns up in a thousand rolls
+    assert!((0..10).all(|n| rolled.contains(&n)));
+    assert_eq!(integers(eval_in(&mut s, "0?5")), Vec::<i64>::new());
+}
+
+#[test]
+fn test_roll_floats() {
+    let mut s = Session::new();
+    for item in items(eval_in(&mut s, "100?2.5")) {
+        let Value::Float(f) = item else {
+            panic!("expected a float, got {:?}", item);
+        };
+        assert!((0.0..2.5).contains(&f));
+    }
+}
+
+#[test]
+fn test_deal_has_no_repeats() {
+    let mut s = Session::new();
+    let mut dealt = integers(eval_in(&mut s, "-10?10"));
+    dealt.sort();
+    assert_eq!(dealt, (0..10).collect::<Vec<_>>());
+    // Dealing few from a huge range does not enumerate it
+    let mut dealt = integers(eval_in(&mut s, "-5?1000000000000"));
+    dealt.dedup();
+    assert_eq!(dealt.len(), 5);
+    assert!(s.eval("-4?3").is_err());
+}
+
+#[test]
+fn test_sample_list() {
+    let mut s = Session::new();
+    let picked = items(eval_in(&mut s, "20?`a`b`c"));
+    assert_eq!(picked.len(), 20);
+    let symbols = items(eval_in(&mut s, "`a`b`c"));
+    assert!(picked.iter().all(|item| symbols.contains(item)));
+    let mut dealt = items(eval_in(&mut s, "-3?10 20 30"));
+    dealt.sort_by(|a, b| a.compare(b).unwrap());
+    assert_eq!(dealt, items(eval_in(&mut s, "10 20 30")));
+}
+
+#[test]
+fn test_seed_makes_rolls_reproducible() {
+    let mut s = Session::new();
+    eval_in(&mut s, "seed 42");
+    let first = eval_in(&mut s, "10?100");
+    let next = eval_in(&mut s, "10?100");
+    assert_ne!(first, next);
+    eval_in(&mut s, "seed 42");
+    assert_eq!(eval_in(&mut s, "10?100"), first);
+    // Fresh sessions start from the same seed
+    assert_eq!(
+        eval_in(&mut Session::new(), "5?100"),
+        eval_in(&mut Session::new(), "5?100")
+    );
+}
+
+#[test]
+fn test_roll_errors() {
+    let mut s = Session::new();
+    assert!(s.eval("3?0").is_err());
+    assert!(s.eval("3?0#1").is_err());
+    assert!(s.eval("-3?1.5").is_err());
+    assert!(s.eval("`a?10").is_err());
+    assert!(s.eval("seed `a").is_err());
+}
+
+// Strings
+#[test]
+fn test_string_literals() {
+    let mut s = Session::new();
+    assert_eq!(s.eval("\"hello\"").unwrap(), Value::string("hello"));
+    assert_eq!(s.eval("\"a\"").unwrap(), Value::Char('a'));
+    assert_eq!(
+        s.eval(r#""tab\there \"quoted\"""#).unwrap(),
+        Value::string("tab\there \"quoted\"")
+    );
+    assert!(s.eval(r#""\q""#).is_err());
+    // Strings are lists of characters
+    assert_eq!(show(&mut s, "count \"hello\""), "5");
+    assert_eq!(show(&mut s, "reverse \"abc\""), "\"cba\"");
+    assert_eq!(show(&mut s, "2#\"abc\""), "\"ab\"");
+    assert_eq!(show(&mut s, "`symbol$(\"ab\";\"cd\")"), "`ab`cd");
+}
+
+#[test]
+fn test_case_and_trim() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "lower \"HeLLo\""), "\"hello\"");
+    assert_eq!(show(&mut s, "upper \"a\""), "\"A\"");
+    assert_eq!(show(&mut s, "upper `abc`Def"), "`ABC`DEF");
+    assert_eq!(show(&mut s, "trim \"  hi \""), "\"hi\"");
+    assert_eq!(show(&mut s, "ltrim \"  hi \""), "\"hi \"");
+    assert_eq!(show(&mut s, "rtrim \"  hi \""), "\"  hi\"");
+    assert!(s.eval("trim 5").is_err());
+}
+
+#[test]
+fn test_search_and_replace() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "ss[\"abcabc\";\"bc\"]"), "1 4");
+    // Matches do not overlap
+    assert_eq!(show(&mut s, "ss[\"aaaa\";\"aa\"]"), "0 2");
+    assert_eq!(
+        show(&mut s, "ssr[\"hello world\";\"o\";\"0\"]"),
+        "\"hell0 w0rld\""
+    );
+    assert_eq!(show(&mut s, "ssr[\"a.b.c\";\".\";\"::\"]"), "\"a::b::c\"");
+}
+
+#[test]
+fn test_split_and_join() {
+    let mut s = Session::new();
+    assert_eq!(
+        show(&mut s, "vs[\", \";\"ab, cd, ef\"]"),
+        "(\"ab\";\"cd\";\"ef\")"
+    );
+    assert_eq!(
+        show(&mut s, "sv[\"-\";(\"2024\";\"01\";\"15\")]"),
+        "\"2024-01-15\""
+    );
+    assert_eq!(show(&mut s, "sv[\",\";vs[\",\";\"a,b,c\"]]"), "\"a,b,c\"");
+    assert!(s.eval("vs[\"\";\"abc\"]").is_err());
+    assert!(s.eval("sv[\",\";1 2]").is_err());
+}
+
+#[test]
+fn test_cleaning_a_column() {
+    let mut s = Session::new();
+    s.eval("t: flip `name`n!((\"  Alice\";\"BOB \");1 2)")
+        .unwrap();
+    s.eval("u: update name: lower trim name from t").unwrap();
+    assert_eq!(show(&mut s, "u `name"), "(\"alice\";\"bob\")");
+}
+
+#[test]
+fn test_string_conversion() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "string 42"), "\"42\"");
+    assert_eq!(show(&mut s, "string `abc"), "\"abc\"");
+    assert_eq!(show(&mut s, "string 1.5"), "\"1.5\"");
+    assert_eq!(show(&mut s, "string 2024.01.15"), "\"2024.01.15\"");
+    assert_eq!(show(&mut s, "string 1b"), "\"1b\"");
+    assert_eq!(show(&mut s, "string {x+1}"), "\"{x+1}\"");
+    // Lists and dict values give a string per item
+    assert_eq!(show(&mut s, "string 10 20"), "(\"10\";\"20\")");
+    assert_eq!(show(&mut s, "string `a`b!10 20"), "`a`b!(\"10\";\"20\")");
+    assert_eq!(show(&mut s, "string \"ab\""), "(,\"a\";,\"b\")");
+}
+
+#[test]
+fn test_format() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "format[\"x is {}\";42]"), "\"x is 42\"");
+    assert_eq!(
+        show(&mut s, "format[\"{} traded {} at {}\";(`AAPL;100;1.5)]"),
+        "\"AAPL traded 100 at 1.5\""
+    );
+    // A single placeholder takes the whole value, list or not
+    assert_eq!(show(&mut s, "format[\"px: {}\";1 2 3]"), "\"px: 1 2 3\"");
+    assert_eq!(show(&mut s, "format[\"hi {}\";\"bob\"]"), "\"hi bob\"");
+    assert_eq!(show(&mut s, "format[\"{{{}}}\";1]"), "\"{1}\"");
+    assert!(s.eval("format[\"{} {}\";1]").is_err());
+    assert!(s.eval("format[\"{\";1]").is_err());
+    assert!(s.eval("format[1;1]").is_err());
+}
+
+// Loops: do and while
+#[test]
+fn test_do() {
+    assert_eq!(
+        eval_lines(&["x: 1", "do[5; x: x*2]", "x"]),
+        Value::Integer(32)
+    );
+    // The loop gives the last body value, or () if the body never ran
+    assert_eq!(
+        eval_lines(&["x: 1", "do[3; x: x+1; x*10]"]),
+        Value::Integer(40)
+    );
+    assert_eq!(eval_lines(&["do[0; 1]"]), Value::List(vec![]));
+    assert_eq!(eval_lines(&["do[-2; 1]"]), Value::List(vec![]));
+}
+
+#[test]
+fn test_while() {
+    assert_eq!(
+        eval_lines(&["i: 0; s: 0", "while[i<5; i: i+1; s: s+i]", "s"]),
+        Value::Integer(15)
+    );
+    // The condition is checked before the first pass
+    assert_eq!(
+        eval_lines(&["n: 0", "while[n>0; n: n-1]", "n"]),
+        Value::Integer(0)
+    );
+}
+
+#[test]
+fn test_loops_in_functions() {
+    let result = eval_lines(&["f: {[n] acc: 1; do[n; acc: acc*2]; acc}", "f[10]"]);
+    assert_eq!(result, Value::Integer(1024));
+
+    let result = eval_lines(&[
+        "gcd: {[a;b] while[a<>b; d: a-b; a: $[d>0;d;a]; b: $[d<0;0-d;b]]; a}",
+        "gcd[48;18]",
+    ]);
+    assert_eq!(result, Value::Integer(6));
+}
+
+#[test]
+fn test_loop_operand_errors() {
+    let mut evaluator = Evaluator::new();
+    let err = run(&mut evaluator, &["do[`a; 1]"]).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::Type(_)));
+    assert!(run(&mut evaluator, &["while[`a; 1]"]).is_err());
+}
+
+#[test]
+fn test_iteration_limit() {
+    let mut evaluator = Evaluator::new();
+    assert_eq!(evaluator.max_iterations(), DEFAULT_MAX_ITERATIONS);
+    evaluator.set_max_iterations(100);
+    assert!(run(&mut evaluator, &["do[100; 1]"]).is_ok());
+
+    let err = run(&mut evaluator, &["while[1b; 1]"]).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        EvalErrorKind::IterationLimitExceeded(100)
+    ));
+    assert_eq!(err.kind.code(), "ITERATION_LIMIT_EXCEEDED");
+    assert!(run(&mut evaluator, &["do[101; 1]"]).is_err());
+}
+
+#[test]
+fn test_loop_keywords_are_not_reserved_names() {
+    assert_eq!(
+        eval_lines(&["do: 5", "while: 6", "do+while"]),
+        Value::Integer(11)
+    );
+}
@@ -1,6 +1,15 @@
-use wabznasm::environment::{Environment, Value};
-use wabznasm::evaluator::Evaluator;
+mod common;
+
+use common::{eval, eval_lines, run, show};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wabznasm::builtins::NativeFunction;
+use wabznasm::environment::Environment;
+use wabznasm::errors::EvalErrorKind;
+use wabznasm::evaluator::{DEFAULT_MAX_DEPTH, Evaluator};
 use wabznasm::parser::parse_expression;
+use wabznasm::plugin::{Plugin, PluginError};
+use wabznasm::{Session, Value, builtins};
 
 #[test]
 fn test_simple_assignment() {
@@ -127,3 +136,690 @@ fn test_function_call_with_args() {
 
     assert_eq!(result, Value::Integer(5)); // 2+3 = 5
 }
+
+#[test]
+fn test_function_returning_closure() {
+    let result = eval_lines(&[
+        "makeAdder: {[n] {[x] x + n}}",
+        "add5: makeAdder[5]",
+        "add5[10]",
+    ]);
+    assert_eq!(result, Value::Integer(15));
+
+    let adder = eval_lines(&["makeAdder: {[n] {[x] x + n}}", "makeAdder[1]"]);
+    assert!(adder.is_function());
+    assert_eq!(adder.arity(), Some(1));
+}
+
+#[test]
+fn test_nested_closures_capture_each_level() {
+    let result = eval_lines(&[
+        "curry: {[a] {[b] {[c] a*100 + b*10 + c}}}",
+        "f: curry[1]",
+        "g: f[2]",
+        "g[3]",
+    ]);
+    assert_eq!(result, Value::Integer(123));
+}
+
+#[test]
+fn test_returned_closures_called_directly() {
+    let result = eval_lines(&["makeAdder: {[n] {[x] x + n}}", "makeAdder[5][10]"]);
+    assert_eq!(result, Value::Integer(15));
+    let result = eval_lines(&[
+        "curry: {[a] {[b] {[c] a*100 + b*10 + c}}}",
+        "curry[1][2][3]",
+    ]);
+    assert_eq!(result, Value::Integer(123));
+}
+
+#[test]
+fn test_lambda_as_argument() {
+    let result = eval_lines(&["twice: {[f;x] f[f[x]]}", "twice[{[y] y*3}; 2]"]);
+    assert_eq!(result, Value::Integer(18));
+}
+
+#[test]
+fn test_conditional() {
+    assert_eq!(eval_lines(&["$[1b;10;20]"]), Value::Integer(10));
+    assert_eq!(eval_lines(&["$[0;10;20]"]), Value::Integer(20));
+    assert_eq!(
+        eval_lines(&["x: 7", "$[x<5;1;x<10;2;3]"]),
+        Value::Integer(2)
+    );
+    // Only the selected branch is evaluated
+    assert_eq!(eval_lines(&["$[1b;1;undefined]"]), Value::Integer(1));
+}
+
+#[test]
+fn test_named_recursion() {
+    let result = eval_lines(&["fact: {[n] $[n<2;1;n*fact[n-1]]}", "fact[5]"]);
+    assert_eq!(result, Value::Integer(120));
+
+    let result = eval_lines(&["fib: {[n] $[n<2;n;fib[n-1]+fib[n-2]]}", "g: fib", "g[10]"]);
+    assert_eq!(result, Value::Integer(55));
+}
+
+#[test]
+fn test_self_reference() {
+    let result = eval_lines(&["sum: {[n] $[n=0;0;n+.z.s[n-1]]}", "sum[4]"]);
+    assert_eq!(result, Value::Integer(10));
+}
+
+#[test]
+fn test_block_locals() {
+    let result = eval_lines(&["f: {[x] t: x*2; t+1}", "f[3]"]);
+    assert_eq!(result, Value::Integer(7));
+
+    // Locals are rebound in turn, and the last statement is the result
+    let result = eval_lines(&["f: {[n] a: n; a: a*2; a: a+1}", "f[5]"]);
+    assert_eq!(result, Value::Integer(11));
+
+    // A local shadows a global of the same name without changing it
+    let result = eval_lines(&["t: 100", "f: {[x] t: x*2; t+1}", "f[3]", "t"]);
+    assert_eq!(result, Value::Integer(100));
+}
+
+#[test]
+fn test_block_locals_do_not_leak() {
+    let mut env = Environment::new();
+    let mut evaluator = Evaluator::new();
+    for line in ["f: {[x] t: x*2; t+1}", "f[3]"] {
+        let tree = parse_expression(line).unwrap();
+        evaluator
+            .eval_with_env(tree.root_node(), line, &mut env)
+            .unwrap();
+    }
+    let tree = parse_expression("t").unwrap();
+    assert!(
+        evaluator
+            .eval_with_env(tree.root_node(), "t", &mut env)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_statement_sequence() {
+    assert_eq!(eval_lines(&["a: 1; b: 2; a+b"]), Value::Integer(3));
+}
+
+#[test]
+fn test_global_assignment() {
+    let result = eval_lines(&[
+        "counter: 0",
+        "bump: {[n] counter:: n; 0}",
+        "bump[5]",
+        "counter",
+    ]);
+    assert_eq!(result, Value::Integer(5));
+
+    // The call sees the new value at once
+    let result = eval_lines(&["f: {[n] g:: n; g*10}", "f[2]"]);
+    assert_eq!(result, Value::Integer(20));
+
+    // Nested calls reach the global scope too
+    let result = eval_lines(&[
+        "outer: {[v] inner: {[w] deep:: w}; inner[v]}",
+        "outer[3]",
+        "deep",
+    ]);
+    assert_eq!(result, Value::Integer(3));
+
+    // At top level :: is an ordinary assignment
+    assert_eq!(eval_lines(&["top:: 4", "top"]), Value::Integer(4));
+}
+
+#[test]
+fn test_global_assignment_is_seen_within_the_same_evaluation() {
+    // Each update builds on the last
+    let result = eval_lines(&["n: 0; inc: {n:: n+1}; inc[]; inc[]; n"]);
+    assert_eq!(result, Value::Integer(2));
+    let result = eval_lines(&["n: 0; inc: {n:: n+1}; do[3; inc[]]; n"]);
+    assert_eq!(result, Value::Integer(3));
+
+    // Another call reads the new value before the evaluation returns
+    let result = eval_lines(&["x: 1; f: {x:: 5}; g: {f[]; x}; g[]"]);
+    assert_eq!(result, Value::Integer(5));
+
+    // An assignment at the top level after the call wins
+    let result = eval_lines(&["x: 1; f: {x:: 5}; f[]; x: 7; x"]);
+    assert_eq!(result, Value::Integer(7));
+    let result = eval_lines(&["x: 1; f: {x:: 5}; f[]; x: 7", "x"]);
+    assert_eq!(result, Value::Integer(7));
+}
+
+#[test]
+fn test_global_assignment_to_a_local_sets_the_local() {
+    let result = eval_lines(&["f: {[x] x:: 99; x}", "f[1]"]);
+    assert_eq!(result, Value::Integer(99));
+
+    let mut env = Environment::new();
+    let mut evaluator = Evaluator::new();
+    for line in ["f: {[a] t: 1; t:: 2; t}", "f[0]"] {
+        let tree = parse_expression(line).unwrap();
+        evaluator
+            .eval_with_env(tree.root_node(), line, &mut env)
+            .unwrap();
+    }
+    let tree = parse_expression("t").unwrap();
+    assert!(
+        evaluator
+            .eval_with_env(tree.root_node(), "t", &mut env)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_docstrings() {
+    use wabznasm::Session;
+
+    let mut session = Session::new();
+    session.eval(r#"inc: {[x] "Add one to x"; x+1}"#).unwrap();
+    // The docstring is documentation only; the result is the last statement
+    assert_eq!(session.eval("inc[1]").unwrap(), Value::Integer(2));
+    assert_eq!(
+        session.eval("help inc").unwrap(),
+        Value::string("Add one to x")
+    );
+    assert_eq!(session.help("inc").unwrap(), "inc[x]\nAdd one to x");
+
+    // A body that is only a string returns it and documents nothing
+    session.eval(r#"greeting: {"hello"}"#).unwrap();
+    assert_eq!(session.eval("greeting[]").unwrap(), Value::string("hello"));
+    assert_eq!(
+        session.eval("help greeting").unwrap(),
+        Value::string("{\"hello\"}")
+    );
+
+    // Builtins are described by how they are called and what they do
+    let count = "count[x]\nThe number of items in a list or dict, or rows in a table; an atom counts as one";
+    assert_eq!(session.eval("help count").unwrap(), Value::string(count));
+    assert!(session.eval("help 5").is_err());
+}
+
+// Projections
+const ADD: &str = "add:{[x;y] x+y}";
+const SUB3: &str = "f:{[x;y;z] x-y-z}";
+
+#[test]
+fn test_projection_with_empty_slot() {
+    let result = eval(&[ADD, "inc:add[1;]", "inc[41]"]).unwrap();
+    assert_eq!(result, Value::Integer(42));
+
+    let result = eval(&[SUB3, "g:f[;2;]", "g[10;3]"]).unwrap();
+    assert_eq!(result, Value::Integer(5));
+}
+
+#[test]
+fn test_projection_by_omitting_trailing_arguments() {
+    assert_eq!(eval(&[ADD, "p:add[2]", "p[3]"]).unwrap(), Value::Integer(5));
+    assert_eq!(
+        eval(&[ADD, "inc:add 1", "inc 2"]).unwrap(),
+        Value::Integer(3)
+    );
+}
+
+#[test]
+fn test_projection_of_projection() {
+    let result = eval(&[SUB3, "g:f[10]", "h:g[;3]", "h[2]"]).unwrap();
+    assert_eq!(result, Value::Integer(5));
+}
+
+#[test]
+fn test_calling_a_projection_directly() {
+    assert_eq!(eval(&[ADD, "add[2][3]"]).unwrap(), Value::Integer(5));
+    assert_eq!(eval(&[SUB3, "f[10][3][2]"]).unwrap(), Value::Integer(5));
+    assert_eq!(eval(&[SUB3, "f[;3;][10][2]"]).unwrap(), Value::Integer(5));
+    assert_eq!(eval(&["{[x;y] x*y}[6][7]"]).unwrap(), Value::Integer(42));
+    // Inside a function body too
+    assert_eq!(
+        eval(&[ADD, "g:{[a;b] add[a][b]}", "g[2;3]"]).unwrap(),
+        Value::Integer(5)
+    );
+    assert!(eval(&[ADD, "add[1][2][3]"]).is_err());
+}
+
+#[test]
+fn test_projection_value() {
+    let projection = eval(&[ADD, "add[;5]"]).unwrap();
+    assert!(projection.is_function());
+    assert_eq!(projection.arity(), Some(1));
+    assert_eq!(projection.type_name(), "function");
+}
+
+#[test]
+fn test_over_application_is_an_error() {
+    assert!(eval(&[ADD, "add[1;2;3]"]).is_err());
+    assert!(eval(&[ADD, "p:add[1]", "p[2;3]"]).is_err());
+}
+
+// Functions loaded from plugins
+struct Stats;
+
+impl Plugin for Stats {
+    fn name(&self) -> &str {
+        "stats"
+    }
+
+    fn register(&self, session: &mut Session) {
+        session.define("mean", 1, |args| {
+            let xs = args[0].as_list().ok_or("mean expects a list")?;
+            let total: i64 = xs.iter().filter_map(Value::as_integer).sum();
+            Ok(Value::Integer(total / xs.len() as i64))
+        });
+    }
+}
+
+#[test]
+fn test_compiled_in_plugin_is_loaded_by_name() {
+    let mut session = Session::new();
+    session.add_plugin(Stats);
+    assert!(session.eval("mean 2 4 6").is_err());
+
+    session.load_plugin("stats").unwrap();
+    assert_eq!(session.eval("mean 2 4 6").unwrap(), Value::Integer(4));
+    assert_eq!(session.loaded_plugins(), ["stats"]);
+
+    // Loading again re-registers without duplicating the record
+    session.load_plugin("stats").unwrap();
+    assert_eq!(session.loaded_plugins(), ["stats"]);
+}
+
+#[test]
+fn test_unknown_plugin() {
+    let mut session = Session::new();
+    assert!(matches!(
+        session.load_plugin("ta"),
+        Err(PluginError::NotFound(name)) if name == "ta"
+    ));
+}
+
+#[test]
+fn test_invalid_plugin_library() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("libbroken.so");
+    std::fs::write(&path, b"not a shared library").unwrap();
+
+    let mut session = Session::new();
+    let err = session.load_plugin(path.to_str().unwrap()).unwrap_err();
+    assert!(matches!(err, PluginError::Load { .. }));
+    assert!(session.loaded_plugins().is_empty());
+}
+
+// The recursion limit
+const COUNT_DOWN: &str = "f: {[n] $[n=0;0;1+f[n-1]]}";
+
+#[test]
+fn test_recursion_within_limit() {
+    let mut evaluator = Evaluator::new();
+    let depth = DEFAULT_MAX_DEPTH as i64 - 1;
+    let call = format!("f[{}]", depth);
+    let result = run(&mut evaluator, &[COUNT_DOWN, &call]).unwrap();
+    assert_eq!(result, Value::Integer(depth));
+}
+
+#[test]
+fn test_unbounded_recursion_is_an_error() {
+    let mut evaluator = Evaluator::new();
+    let err = run(&mut evaluator, &["f: {[n] f[n+1]}", "f[0]"]).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        EvalErrorKind::RecursionLimitExceeded(DEFAULT_MAX_DEPTH)
+    ));
+}
+
+#[test]
+fn test_configured_limit() {
+    let mut evaluator = Evaluator::with_max_depth(10);
+    assert_eq!(evaluator.max_depth(), 10);
+    assert!(run(&mut evaluator, &[COUNT_DOWN, "f[9]"]).is_ok());
+
+    let err = run(&mut evaluator, &[COUNT_DOWN, "f[10]"]).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        EvalErrorKind::RecursionLimitExceeded(10)
+    ));
+    assert_eq!(err.kind.code(), "RECURSION_LIMIT_EXCEEDED");
+
+    // The depth unwinds after an error, so later calls start from zero
+    assert!(run(&mut evaluator, &[COUNT_DOWN, "f[9]"]).is_ok());
+}
+
+// Namespaces
+#[test]
+fn test_dotted_names() {
+    let mut s = Session::new();
+    s.eval(".mylib.inc: {[x] x+1}").unwrap();
+    assert_eq!(s.eval(".mylib.inc[1]").unwrap(), Value::Integer(2));
+    assert!(s.eval("inc[1]").is_err());
+}
+
+#[test]
+fn test_switching_namespace() {
+    let mut s = Session::new();
+    assert_eq!(s.namespace(), ".");
+    s.set_namespace(".mylib").unwrap();
+    assert_eq!(s.namespace(), ".mylib");
+    s.eval("k: 10").unwrap();
+    s.eval("sq: {[x] x*x}").unwrap();
+    // Undotted names are defined and found in the namespace
+    assert_eq!(s.eval("k+sq[3]").unwrap(), Value::Integer(19));
+    assert_eq!(s.eval(".mylib.k").unwrap(), Value::Integer(10));
+    // Names at the root are still found when the namespace lacks them
+    s.set_namespace(".").unwrap();
+    s.eval("base: 100").unwrap();
+    assert!(s.eval("k").is_err());
+    s.set_namespace(".mylib").unwrap();
+    assert_eq!(s.eval("base+k").unwrap(), Value::Integer(110));
+    assert!(s.set_namespace("mylib").is_err());
+    assert!(s.set_namespace(".1x").is_err());
+}
+
+#[test]
+fn test_functions_resolve_names_in_their_namespace() {
+    let mut s = Session::new();
+    s.set_namespace(".stats").unwrap();
+    s.eval("scale: 2").unwrap();
+    s.eval("double: {[x] x*scale}").unwrap();
+    s.eval("quad: {[x] double[double[x]]}").unwrap();
+    s.set_namespace(".").unwrap();
+    // Called from the root, the function still sees its namespace
+    assert_eq!(s.eval(".stats.quad[3]").unwrap(), Value::Integer(12));
+    // Globals it sets go in its namespace too
+    s.eval(".stats.remember: {[x] last:: x}").unwrap();
+    s.eval(".stats.remember[7]").unwrap();
+    assert_eq!(s.eval(".stats.last").unwrap(), Value::Integer(7));
+    assert!(s.eval("last").unwrap().is_function());
+}
+
+#[test]
+fn test_namespace_as_dict() {
+    let mut s = Session::new();
+    s.eval(".cfg.port: 5000").unwrap();
+    s.eval(".cfg.host: `localhost").unwrap();
+    s.eval(".cfg.limits.rows: 10").unwrap();
+    assert_eq!(show(&mut s, ".cfg"), "`host`port!(`localhost;5000)");
+    assert_eq!(show(&mut s, ".cfg.limits"), ",`rows!,10");
+    assert!(s.eval(".nothing").is_err());
+}
+
+#[test]
+fn test_listing_variables_and_functions() {
+    let mut s = Session::new();
+    s.eval("b: 2").unwrap();
+    s.eval("a: 1").unwrap();
+    s.eval("add: {[x;y] x+y}").unwrap();
+    s.eval(".mylib.k: 10").unwrap();
+    s.eval(".mylib.sq: {[x] x*x}").unwrap();
+    s.eval(".mylib.inner.z: 0").unwrap();
+    assert_eq!(s.variable_names(None), ["a", "b"]);
+    assert_eq!(s.functions(None), [("add".to_string(), Some(2))]);
+    // Only the names directly in a namespace are its members
+    assert_eq!(s.variable_names(Some(".mylib")), ["k"]);
+    assert_eq!(s.functions(Some(".mylib")), [("sq".to_string(), Some(1))]);
+    s.set_namespace(".mylib").unwrap();
+    assert_eq!(s.variable_names(None), ["k"]);
+    assert_eq!(s.variable_names(Some(".")), ["a", "b"]);
+    assert!(s.variable_names(Some(".none")).is_empty());
+}
+
+// Memoized functions
+#[test]
+fn test_memo_caches_recursive_calls() {
+    let mut s = Session::new();
+    s.eval("fib: memo {[n] $[n<2;n;fib[n-1]+fib[n-2]]}")
+        .unwrap();
+    // Exponential without the cache
+    assert_eq!(show(&mut s, "fib[80]"), "23416728348467685");
+    assert_eq!(show(&mut s, "fib 10"), "55");
+    assert_eq!(show(&mut s, "type fib"), "`function");
+}
+
+/// Calls made so far
+type Counter = Arc<AtomicUsize>;
+
+/// A session with `tick`, which counts its calls and returns its argument
+fn counting() -> (Session, Counter) {
+    let mut s = Session::new();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    s.register(NativeFunction::new("tick", 1, move |args| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Ok(args[0].clone())
+    }));
+    (s, calls)
+}
+
+#[test]
+fn test_memo_calls_once_per_argument() {
+    let (mut s, calls) = counting();
+    s.eval("double: memo {[x] 2*tick x}").unwrap();
+    assert_eq!(show(&mut s, "double[3]"), "6");
+    assert_eq!(show(&mut s, "double[3]"), "6");
+    assert_eq!(show(&mut s, "double[4]"), "8");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    // Arguments are keyed by value, lists included
+    s.eval("len: memo {[x] count tick x}").unwrap();
+    assert_eq!(show(&mut s, "len 1 2 3"), "3");
+    assert_eq!(show(&mut s, "len 1 2 3"), "3");
+    assert_eq!(show(&mut s, "len 1 2"), "2");
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn test_memo_of_projections_and_builtins() {
+    let mut s = Session::new();
+    s.eval("add: memo {[x;y] x+y}").unwrap();
+    assert_eq!(show(&mut s, "add[1;2]"), "3");
+    s.eval("inc: add[1]").unwrap();
+    assert_eq!(show(&mut s, "inc 5"), "6");
+    s.eval("size: memo count").unwrap();
+    assert_eq!(show(&mut s, "size 1 2 3"), "3");
+    // Memoizing twice shares the cache
+    s.eval("again: memo add").unwrap();
+    assert_eq!(s.eval("again").unwrap(), s.eval("add").unwrap());
+}
+
+#[test]
+fn test_memo_needs_a_function() {
+    let mut s = Session::new();
+    let err = s.eval("memo 42").unwrap_err();
+    assert!(err.to_string().contains("memo expects a function"));
+}
+
+// Code as data: parse and eval
+#[test]
+fn test_parse_gives_trees_of_values() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "parse \"1+x\""), "(`+;1;`x)");
+    assert_eq!(show(&mut s, "parse \"f[x;`a]\""), "(`f;`x;,`a)");
+    assert_eq!(show(&mut s, "parse \"(1;x)\""), "(`enlist;1;`x)");
+    assert_eq!(show(&mut s, "parse \"-x\""), "`-`x");
+    assert_eq!(show(&mut s, "parse \"a: 2*3\""), "(`:;`a;(`*;2;3))");
+    assert_eq!(show(&mut s, "parse \"1 2 3\""), "1 2 3");
+    assert_eq!(
+        show(&mut s, "parse \"$[x>5;`big;`small]\""),
+        "(`$[;(`>;`x;5);,`big;,`small)"
+    );
+    assert!(s.eval("parse \"f[1;]\"").is_err());
+    assert!(s.eval("parse \"1+\"").is_err());
+    assert!(s.eval("parse 42").is_err());
+}
+
+#[test]
+fn test_eval_applies_trees() {
+    let mut s = Session::new();
+    s.eval("x: 10").unwrap();
+    assert_eq!(show(&mut s, "eval parse \"x*2+1\""), "21");
+    assert_eq!(show(&mut s, "eval (`count;1 2 3)"), "3");
+    assert_eq!(show(&mut s, "eval (`enlist;1;`x)"), "1 10");
+    assert_eq!(show(&mut s, "eval enlist `x"), "`x");
+    assert_eq!(show(&mut s, "eval 42"), "42");
+    assert_eq!(show(&mut s, "eval (parse \"{[y] y*2}\";4)"), "8");
+    // A call's result applied in turn
+    s.eval("add: {[x;y] x+y}").unwrap();
+    assert_eq!(show(&mut s, "parse \"add[2][3]\""), "((`add;2);3)");
+    assert_eq!(show(&mut s, "eval parse \"add[2][3]\""), "5");
+    // Assignments set globals
+    assert_eq!(show(&mut s, "eval parse \"a: 5; a*x\""), "50");
+    assert_eq!(show(&mut s, "a"), "5");
+    // Names are looked up where eval is called
+    s.eval("f: {[x] eval `x}").unwrap();
+    assert_eq!(show(&mut s, "f 3"), "3");
+}
+
+#[test]
+fn test_queries_built_as_trees() {
+    let mut s = Session::new();
+    s.eval("t: flip `sym`px!(`a`b`c;1 2 3)").unwrap();
+    s.eval("q: parse \"select total:sum px by sym from t where px>1\"")
+        .unwrap();
+    assert_eq!(
+        show(&mut s, "q"),
+        "(`select;((`:;`total;`sum`px));,`sym;`t;((`>;`px;1)))"
+    );
+    assert_eq!(
+        show(&mut s, "eval q"),
+        show(&mut s, "select total:sum px by sym from t where px>1")
+    );
+    // A where clause made up at run time
+    s.eval("w: (`symbol$\"=\";`sym;enlist `b)").unwrap();
+    s.eval("q: (`select;();();`t;enlist w)").unwrap();
+    assert_eq!(show(&mut s, "eval q"), "flip `sym`px!(,`b;,2)");
+}
+
+// Compiled function bodies
+#[test]
+fn test_lambdas_are_compiled_when_evaluated() {
+    let mut s = Session::new();
+    let Value::Function { code, .. } = s.eval("{[x] x+1}").unwrap() else {
+        panic!("expected a function");
+    };
+    assert!(code.is_some());
+}
+
+#[test]
+fn test_compiled_bodies_evaluate_as_walked() {
+    let mut s = Session::new();
+    s.eval("fib:{[n] $[n<2; n; fib[n-1]+fib[n-2]]}").unwrap();
+    assert_eq!(show(&mut s, "fib 15"), "610");
+    s.eval("f:{[x] y: x*2; $[y>10; y; f[y]]}").unwrap();
+    assert_eq!(show(&mut s, "f 3"), "12");
+    s.eval("neg:{[x] -x}").unwrap();
+    assert_eq!(show(&mut s, "neg 3"), "-3");
+    s.eval("name:{[x;y] $[x=1; `one; x=2; `two; y]}").unwrap();
+    assert_eq!(show(&mut s, "name[2;`other]"), "`two");
+    assert_eq!(show(&mut s, "name[3;`other]"), "`other");
+}
+
+#[test]
+fn test_compiled_bodies_fall_back_to_the_walker() {
+    let mut s = Session::new();
+    s.eval("add:{[x;y] x+y}").unwrap();
+    // Projections, loops, lists and queries are left to the evaluator
+    s.eval("t: flip enlist[`a]!enlist 1 2 3").unwrap();
+    s.eval("f:{[x] g: add[;x]; g 1}").unwrap();
+    assert_eq!(show(&mut s, "f 10"), "11");
+    s.eval("f:{[n] i: 0; do[n; i: i+2]; i}").unwrap();
+    assert_eq!(show(&mut s, "f 4"), "8");
+    s.eval("f:{[x] x * 1 2 3}").unwrap();
+    assert_eq!(show(&mut s, "f 2"), "2 4 6");
+    s.eval("f:{[n] count select from t where a > n}").unwrap();
+    assert_eq!(show(&mut s, "f 1"), "2");
+}
+
+#[test]
+fn test_compiled_bodies_set_globals() {
+    let mut s = Session::new();
+    s.eval("set:{[x] counter:: x * 2}").unwrap();
+    s.eval("set 4").unwrap();
+    assert_eq!(show(&mut s, "counter"), "8");
+    // A local of the same name is set instead
+    s.eval("local:{[counter] counter:: 7; counter}").unwrap();
+    assert_eq!(show(&mut s, "local 1"), "7");
+    assert_eq!(show(&mut s, "counter"), "8");
+}
+
+#[test]
+fn test_compiled_bodies_report_errors() {
+    let mut s = Session::new();
+    s.eval("f:{[x] x + `a}").unwrap();
+    assert!(s.eval("f 1").is_err());
+    s.eval("f:{[x] $[x; 1; 2]}").unwrap();
+    assert!(s.eval("f `a").is_err());
+    s.eval("f:{[x] undefined + x}").unwrap();
+    assert!(s.eval("f 1").is_err());
+    // The session carries on after a failed call
+    s.eval("f:{[x] x * x}").unwrap();
+    assert_eq!(show(&mut s, "f 4"), "16");
+}
+
+/// The value of `source` evaluated by `evaluator` in `env`
+fn eval_in(evaluator: &mut Evaluator, env: &mut Environment, source: &str) -> Value {
+    let tree = parse_expression(source).unwrap();
+    evaluator
+        .eval_with_env(tree.root_node(), source, env)
+        .unwrap()
+}
+
+#[test]
+fn test_bodies_are_compiled_once() {
+    let mut evaluator = Evaluator::new();
+    let mut env = Environment::new();
+    eval_in(&mut evaluator, &mut env, "f:{[x] g: {[y] y*2}; g x}");
+    for _ in 0..3 {
+        eval_in(&mut evaluator, &mut env, "f 1");
+    }
+    // f and the lambda evaluated on each call of it
+    assert_eq!(evaluator.code_cache().len(), 2);
+    evaluator.set_code_cache_capacity(1);
+    assert_eq!(evaluator.code_cache().len(), 1);
+    assert_eq!(eval_in(&mut evaluator, &mut env, "f 4"), Value::Integer(8));
+}
+
+#[test]
+fn test_folding_keeps_results() {
+    let mut s = Session::new();
+    s.eval("f:{[x] (2*3+x) + -(4-1)}").unwrap();
+    assert_eq!(show(&mut s, "f 1"), "4");
+    // Overflow is left to the mode in force when the function runs
+    s.eval("big:{[x] x + 9223372036854775807 + 1}").unwrap();
+    assert!(s.eval("big 0").is_err());
+
+    let mut evaluator = Evaluator::new();
+    let mut env = Environment::new();
+    evaluator.set_constant_folding(false);
+    eval_in(&mut evaluator, &mut env, "f:{[x] 2*3+x}");
+    assert_eq!(eval_in(&mut evaluator, &mut env, "f 1"), Value::Integer(7));
+}
+
+// Documentation of builtins
+#[test]
+fn test_every_builtin_is_documented() {
+    for builtin in builtins::all() {
+        assert_eq!(builtin.params.len(), builtin.arity, "{}", builtin.name);
+        assert!(!builtin.doc.is_empty(), "{}", builtin.name);
+    }
+}
+
+#[test]
+fn test_examples_give_the_documented_values() {
+    for builtin in builtins::all() {
+        for (source, expected) in builtin.examples {
+            let mut session = Session::new();
+            let value = session.eval(source).unwrap();
+            assert_eq!(&session.format(&value), expected, "{}", source);
+        }
+    }
+}
+
+#[test]
+fn test_manual() {
+    let manual = builtins::lookup("wsum").unwrap().manual();
+    assert_eq!(
+        manual,
+        "wsum[w;x]\nThe sum of the items of `x` weighted by those of `w`\n\nExamples:\n  wsum[1 2;3 4]\n  = 11"
+    );
+    let manual = builtins::lookup(".db.tables").unwrap().manual();
+    assert!(manual.starts_with(".db.tables[]\n"));
+}
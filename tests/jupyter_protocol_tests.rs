@@ -1,5 +1,8 @@
 use chrono::Utc;
-use jupyter_protocol::{ExecuteRequest, Header, JupyterMessageContent};
+use jupyter_protocol::{
+    CompleteRequest, ExecuteRequest, Header, InspectReply, InspectRequest, IsCompleteReplyStatus,
+    IsCompleteRequest, JupyterMessageContent, MediaType,
+};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use wabznasm::jupyter::handler::WabznasmJupyterKernel;
@@ -91,6 +94,114 @@ async fn test_execute_request_error() {
     println!("✅ Execute request properly handles errors");
 }
 
+#[tokio::test]
+async fn test_complete_request() {
+    let mut kernel = create_test_kernel().await;
+    let header = create_test_header();
+    for code in ["increment: {[x] x+1}", "incomes: 10 20 30"] {
+        let execute_request = ExecuteRequest {
+            code: code.to_string(),
+            silent: false,
+            store_history: true,
+            user_expressions: None,
+            allow_stdin: false,
+            stop_on_error: true,
+        };
+        kernel.execute_request(execute_request, &header).await;
+    }
+
+    // The cursor is counted in characters, not bytes
+    let request = CompleteRequest {
+        code: "\"é\"; 1+inc + 2".to_string(),
+        cursor_pos: 10,
+    };
+    let reply = kernel.complete(&request);
+    assert_eq!(reply.status, jupyter_protocol::ReplyStatus::Ok);
+    assert_eq!(reply.matches, ["incomes", "increment["]);
+    assert_eq!((reply.cursor_start, reply.cursor_end), (7, 10));
+
+    let request = CompleteRequest {
+        code: "cou".to_string(),
+        cursor_pos: 3,
+    };
+    assert_eq!(kernel.complete(&request).matches, ["count["]);
+}
+
+#[tokio::test]
+async fn test_inspect_request() {
+    let mut kernel = create_test_kernel().await;
+    let header = create_test_header();
+    for code in [
+        "scale: {[x;k] \"Multiply x by k\"; x*k}",
+        "prices: 10 20 30",
+    ] {
+        let execute_request = ExecuteRequest {
+            code: code.to_string(),
+            silent: false,
+            store_history: true,
+            user_expressions: None,
+            allow_stdin: false,
+            stop_on_error: true,
+        };
+        kernel.execute_request(execute_request, &header).await;
+    }
+    let inspect = |code: &str, cursor_pos: usize| {
+        let request = InspectRequest {
+            code: code.to_string(),
+            cursor_pos,
+            detail_level: None,
+        };
+        kernel.inspect(&request)
+    };
+    let plain = |reply: &InspectReply| {
+        (reply.data.content.iter())
+            .find_map(|media| match media {
+                MediaType::Plain(text) => Some(text.clone()),
+                _ => None,
+            })
+            .unwrap()
+    };
+
+    // Inside the brackets of a call, the function is inspected
+    let reply = inspect("scale[prices; 2]", 15);
+    assert!(reply.found);
+    assert_eq!(
+        plain(&reply),
+        "scale: function\nSignature: scale[x;k]\nDocstring: Multiply x by k\n\
+         Body: {[x;k] \"Multiply x by k\"; x*k}"
+    );
+    let html =
+        reply.data.content.iter().any(
+            |media| matches!(media, MediaType::Html(html) if html.contains("<dt>Signature</dt>")),
+        );
+    assert!(html);
+
+    let reply = inspect("scale[prices; 2]", 8);
+    assert_eq!(plain(&reply), "prices: list (3)\nValue: 10 20 30");
+    let reply = inspect("count prices", 2);
+    assert!(plain(&reply).starts_with("count: function\nSignature: count[x]\n"));
+
+    assert!(!inspect("undefined_name", 3).found);
+}
+
+#[tokio::test]
+async fn test_is_complete_request() {
+    let kernel = create_test_kernel().await;
+    let is_complete = |code: &str| {
+        kernel.is_complete(&IsCompleteRequest {
+            code: code.to_string(),
+        })
+    };
+    assert_eq!(
+        is_complete("x: 1 2 3").status,
+        IsCompleteReplyStatus::Complete
+    );
+    let reply = is_complete("f: {[x]\n  y: x*2;");
+    assert_eq!(reply.status, IsCompleteReplyStatus::Incomplete);
+    assert_eq!(reply.indent, "  ");
+    assert_eq!(is_complete("1+*2").status, IsCompleteReplyStatus::Invalid);
+}
+
 #[test]
 fn test_kernel_info_json_serialization() {
     use serde_json;
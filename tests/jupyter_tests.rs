@@ -26,6 +26,9 @@ fn test_display_formatter_function() {
         params: vec![local_interner.get_or_intern("x")],
         body: local_interner.get_or_intern("x+1"),
         closure: None,
+        doc: None,
+        code: None,
+        origin: None,
     };
     let display_data = DisplayFormatter::format_value(&value, &local_interner);
 
@@ -43,6 +46,9 @@ fn test_display_formatter_function_no_params() {
         params: vec![],
         body: local_interner.get_or_intern("42"),
         closure: None,
+        doc: None,
+        code: None,
+        origin: None,
     };
     let display_data = DisplayFormatter::format_value(&value, &local_interner);
 
@@ -173,6 +179,7 @@ fn test_jupyter_session_persistent_environment() {
 }
 
 #[test]
+#[allow(clippy::unnecessary_unwrap)]
 fn test_jupyter_session_empty_code() {
     let mut session = JupyterSession::new();
 
@@ -210,6 +217,32 @@ fn test_jupyter_session_reset() {
     assert!(result.is_err()); // Should be undefined variable error
 }
 
+#[test]
+fn test_variable_explorer_comm() {
+    use serde_json::json;
+    use wabznasm::jupyter::comm;
+
+    let mut session = JupyterSession::new();
+    session
+        .execute("t: flip `sym`px!(`AAPL`MSFT;100 250)")
+        .unwrap();
+
+    let all = comm::variables_message(&session, &Default::default());
+    assert_eq!(all["path"], json!([]));
+    assert_eq!(all["variables"][0]["name"], "t");
+    assert_eq!(all["variables"][0]["type"], "table");
+    assert_eq!(all["variables"][0]["expandable"], true);
+
+    let request = json!({ "path": ["t"] });
+    let columns = comm::variables_message(&session, request.as_object().unwrap());
+    assert_eq!(columns["variables"][1]["name"], "px");
+    assert_eq!(columns["variables"][1]["preview"], "100 250");
+
+    let request = json!({ "path": ["u"] });
+    let missing = comm::variables_message(&session, request.as_object().unwrap());
+    assert_eq!(missing["error"], "Undefined variable: u");
+}
+
 /*
 // Tests using WabznasmJupyterKernel - commented out due to low-level approach
 
@@ -383,3 +416,88 @@ fn test_complete_function_workflow_in_session() {
 
     println!("🎉 All session tests passed!");
 }
+
+#[test]
+fn test_jupyter_session_sql_cell() {
+    use storage::{
+        ColumnSchema, QStoreConfig, ScalarValue, Table, TableSchema, schema::SimpleDataType,
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let schema = TableSchema::new("trades".to_string())
+        .add_column(ColumnSchema::new_simple("sym".into(), SimpleDataType::Utf8))
+        .add_column(ColumnSchema::new_simple("px".into(), SimpleDataType::Int64));
+    let mut table = Table::create(schema, QStoreConfig::new(dir.path(), "trades".into())).unwrap();
+    for (sym, px) in [("AAPL", 101), ("MSFT", 250), ("AAPL", 99)] {
+        let row = [
+            ("sym".to_string(), ScalarValue::Utf8(sym.into())),
+            ("px".to_string(), ScalarValue::Int64(px)),
+        ];
+        table.insert(row.into_iter().collect()).unwrap();
+    }
+
+    let mut session = JupyterSession::new();
+    assert!(session.execute_sql("select from trades").is_err());
+
+    session.set_database(dir.path());
+    let result = session
+        .execute_sql("SELECT sym, px FROM trades WHERE px > 100")
+        .unwrap();
+    assert_eq!(session.execution_count(), 2);
+    assert_eq!(session.stats().rows_scanned, 3);
+    assert_eq!(session.stats().rows_returned, 2);
+
+    let display_data = DisplayFormatter::format_result_set(&result);
+    let plain_text = display_data.get("text/plain").unwrap().as_str().unwrap();
+    assert_eq!(plain_text, "sym  px\n-------\nAAPL 101\nMSFT 250");
+    let html = display_data.get("text/html").unwrap().as_str().unwrap();
+    assert!(html.starts_with("<table class=\"nb-table\"><thead><tr><th>sym</th><th>px</th>"));
+    assert!(html.contains("<tr><td>MSFT</td><td>250</td></tr>"));
+
+    assert!(session.execute_sql("select from quotes").is_err());
+}
+
+#[test]
+fn test_result_set_data_resource_bundle() {
+    use storage::{ResultSet, ScalarValue};
+    use wabznasm::jupyter::display::DATA_RESOURCE_MIME;
+
+    let result = ResultSet {
+        columns: vec!["time".into(), "sym".into(), "px".into(), "size".into()],
+        rows: vec![
+            vec![
+                ScalarValue::Timestamp(1_000_000_000),
+                ScalarValue::Utf8("AAPL".into()),
+                ScalarValue::Float64(101.5),
+                ScalarValue::Null,
+            ],
+            vec![
+                ScalarValue::Timestamp(2_000_000_000),
+                ScalarValue::Utf8("MSFT".into()),
+                ScalarValue::Float64(250.0),
+                ScalarValue::Int32(300),
+            ],
+        ],
+        scanned: 2,
+    };
+
+    let display_data = DisplayFormatter::format_result_set(&result);
+    let bundle = display_data.get(DATA_RESOURCE_MIME).unwrap();
+    assert_eq!(
+        bundle,
+        &serde_json::json!({
+            "schema": {
+                "fields": [
+                    { "name": "time", "type": "datetime" },
+                    { "name": "sym", "type": "string" },
+                    { "name": "px", "type": "number" },
+                    { "name": "size", "type": "integer" },
+                ]
+            },
+            "data": [
+                { "time": "1970-01-01T00:00:01Z", "sym": "AAPL", "px": 101.5, "size": null },
+                { "time": "1970-01-01T00:00:02Z", "sym": "MSFT", "px": 250.0, "size": 300 },
+            ]
+        })
+    );
+}
@@ -204,10 +204,7 @@ fn test_complex_function_examples() {
 fn test_function_closures() {
     let mut session = JupyterSession::new();
     assert!(session.execute("offset: 100").unwrap().is_some());
-    // The following higher-order function syntax causes a parse error.
-    // This might be an unsupported feature or require different syntax.
-    // TODO: Investigate and enable if higher-order functions like this are supported.
-    /*
+    // Higher-order function returning a closure over its parameter
     assert!(
         session
             .execute("makeAdder: {[n] {[x] x + n + offset}}")
@@ -215,8 +212,15 @@ fn test_function_closures() {
             .is_some()
     );
     assert!(session.execute("add5: makeAdder[5]").unwrap().is_some());
-    assert_eq!(session.execute("add5[10]").unwrap().unwrap().as_integer().unwrap(), 115);
-    */
+    assert_eq!(
+        session
+            .execute("add5[10]")
+            .unwrap()
+            .unwrap()
+            .as_integer()
+            .unwrap(),
+        115
+    );
 
     // Simpler closure test (variable capture)
     session.execute("val_closure: 10").unwrap();
@@ -265,10 +269,10 @@ fn test_error_handling() {
     );
     // Arity error
     assert!(session.execute("add: {[x;y] x + y}").unwrap().is_some()); // Definition should succeed
-    let arity_error_result = session.execute("add[5]");
+    let arity_error_result = session.execute("add[1;2;3]");
     assert!(arity_error_result.is_err());
     let arity_error_message = arity_error_result.unwrap_err().to_string();
-    println!("Arity error for 'add[5]': {}", arity_error_message); // Print the actual error
+    println!("Arity error for 'add[1;2;3]': {}", arity_error_message); // Print the actual error
     assert!(arity_error_message.contains("Arity mismatch")); // Updated to actual error message substring
 }
 
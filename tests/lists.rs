@@ -0,0 +1,962 @@
+//! Tests for list literals and the builtins that work on lists
+mod common;
+
+use arrow2::array::PrimitiveArray;
+use common::{bools, eval, ints, show};
+use wabznasm::arithmetic::OverflowMode;
+use wabznasm::environment::Environment;
+use wabznasm::errors::EvalErrorKind;
+use wabznasm::evaluator::{Evaluator, EvaluatorConfig};
+use wabznasm::parser::parse_expression;
+use wabznasm::{Session, Value, vectors};
+
+#[test]
+fn test_list_literals() {
+    assert_eq!(eval(&["3 1 2"]).unwrap(), ints(&[3, 1, 2]));
+    assert_eq!(eval(&["(1;2+3;4)"]).unwrap(), ints(&[1, 5, 4]));
+    assert_eq!(eval(&["()"]).unwrap(), ints(&[]));
+}
+
+#[test]
+fn test_asc_desc() {
+    assert_eq!(eval(&["asc 3 1 2"]).unwrap(), ints(&[1, 2, 3]));
+    assert_eq!(eval(&["desc[3 1 2]"]).unwrap(), ints(&[3, 2, 1]));
+    assert_eq!(eval(&["asc ()"]).unwrap(), ints(&[]));
+}
+
+#[test]
+fn test_grades_are_stable() {
+    assert_eq!(eval(&["iasc 2 1 2 1"]).unwrap(), ints(&[1, 3, 0, 2]));
+    assert_eq!(eval(&["idesc 2 1 2 1"]).unwrap(), ints(&[0, 2, 1, 3]));
+}
+
+#[test]
+fn test_sort_nested_lists() {
+    assert_eq!(
+        eval(&["asc (2 1;1 5;1 2)"]).unwrap(),
+        Value::List(vec![ints(&[1, 2]), ints(&[1, 5]), ints(&[2, 1])])
+    );
+}
+
+#[test]
+fn test_sort_errors() {
+    let err = eval(&["asc (1;2 3)"]).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::Type(_)));
+
+    let err = eval(&["asc 5"]).unwrap_err();
+    assert!(matches!(err.kind, EvalErrorKind::Type(_)));
+}
+
+#[test]
+fn test_builtins_can_be_shadowed() {
+    let mut env = Environment::new();
+    let mut evaluator = Evaluator::new();
+    for src in ["asc: {[x] x+1}", "asc[1]"] {
+        let tree = parse_expression(src).unwrap();
+        let result = evaluator.eval_with_env(tree.root_node(), src, &mut env);
+        if src == "asc[1]" {
+            assert_eq!(result.unwrap(), Value::Integer(2));
+        }
+    }
+}
+
+// Take and drop
+#[test]
+fn test_take() {
+    assert_eq!(eval(&["3#1 2 3 4 5"]).unwrap(), ints(&[1, 2, 3]));
+    assert_eq!(eval(&["-2#1 2 3 4 5"]).unwrap(), ints(&[4, 5]));
+    assert_eq!(eval(&["0#1 2 3"]).unwrap(), ints(&[]));
+}
+
+#[test]
+fn test_take_cycles_and_repeats() {
+    assert_eq!(eval(&["5#1 2"]).unwrap(), ints(&[1, 2, 1, 2, 1]));
+    assert_eq!(eval(&["-5#1 2 3"]).unwrap(), ints(&[2, 3, 1, 2, 3]));
+    assert_eq!(eval(&["3#7"]).unwrap(), ints(&[7, 7, 7]));
+}
+
+#[test]
+fn test_drop() {
+    assert_eq!(eval(&["2_1 2 3 4"]).unwrap(), ints(&[3, 4]));
+    assert_eq!(eval(&["-3_1 2 3 4"]).unwrap(), ints(&[1]));
+    assert_eq!(eval(&["10_1 2 3"]).unwrap(), ints(&[]));
+}
+
+#[test]
+fn test_take_drop_precedence() {
+    // Verbs bind looser than arithmetic and associate to the right
+    assert_eq!(eval(&["1+1#10 20 30"]).unwrap(), ints(&[10, 20]));
+    assert_eq!(eval(&["2#1_10 20 30 40"]).unwrap(), ints(&[20, 30]));
+    assert_eq!(eval(&["asc 2#3 1 2"]).unwrap(), ints(&[1, 3]));
+}
+
+#[test]
+fn test_take_drop_with_variables() {
+    let mut env = Environment::new();
+    let mut evaluator = Evaluator::new();
+    let mut result = None;
+    for src in ["xs: 5 6 7 8", "n: 2", "n_xs"] {
+        let tree = parse_expression(src).unwrap();
+        result = Some(evaluator.eval_with_env(tree.root_node(), src, &mut env));
+    }
+    // n_xs is a single identifier, exactly as in q
+    assert!(result.unwrap().is_err());
+
+    let src = "n _ xs";
+    let tree = parse_expression(src).unwrap();
+    let result = evaluator.eval_with_env(tree.root_node(), src, &mut env);
+    assert_eq!(result.unwrap(), ints(&[7, 8]));
+}
+
+#[test]
+fn test_take_drop_errors() {
+    assert!(matches!(
+        eval(&["1 2#3 4"]).unwrap_err().kind,
+        EvalErrorKind::Type(_)
+    ));
+    assert!(matches!(
+        eval(&["2_5"]).unwrap_err().kind,
+        EvalErrorKind::Type(_)
+    ));
+    assert!(eval(&["2#()"]).is_err());
+}
+
+// Indexing
+#[test]
+fn test_index_list() {
+    assert_eq!(
+        eval(&["xs:10 20 30 40 50", "xs[2]"]).unwrap(),
+        Value::Integer(30)
+    );
+    assert_eq!(
+        eval(&["xs:10 20 30 40 50", "xs 0"]).unwrap(),
+        Value::Integer(10)
+    );
+    assert_eq!(
+        eval(&["xs:10 20 30 40 50", "xs[0 2 4]"]).unwrap(),
+        ints(&[10, 30, 50])
+    );
+}
+
+#[test]
+fn test_index_nested_list() {
+    let m = ["m:(1 2 3;4 5 6)"];
+    assert_eq!(eval(&[m[0], "m[1]"]).unwrap(), ints(&[4, 5, 6]));
+    assert_eq!(eval(&[m[0], "m[1;2]"]).unwrap(), Value::Integer(6));
+}
+
+#[test]
+fn test_index_dict() {
+    let d = "d:1 2 3!10 20 30";
+    assert_eq!(eval(&[d, "d[2]"]).unwrap(), Value::Integer(20));
+    assert_eq!(eval(&[d, "d[3 1]"]).unwrap(), ints(&[30, 10]));
+    assert_eq!(
+        eval(&[d]).unwrap(),
+        Value::Dict {
+            keys: vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)],
+            values: vec![Value::Integer(10), Value::Integer(20), Value::Integer(30)],
+        }
+    );
+}
+
+#[test]
+fn test_index_still_calls_functions() {
+    assert_eq!(eval(&["f:{[x] x*2}", "f[21]"]).unwrap(), Value::Integer(42));
+    assert_eq!(eval(&["5!"]).unwrap(), Value::Integer(120));
+}
+
+#[test]
+fn test_index_errors() {
+    assert!(matches!(
+        eval(&["xs:1 2 3", "xs[3]"]).unwrap_err().kind,
+        EvalErrorKind::IndexOutOfRange(_)
+    ));
+    assert!(matches!(
+        eval(&["d:1 2!3 4", "d[5]"]).unwrap_err().kind,
+        EvalErrorKind::KeyNotFound(_)
+    ));
+    assert!(matches!(
+        eval(&["1 2!3"]).unwrap_err().kind,
+        EvalErrorKind::LengthMismatch(_)
+    ));
+    assert!(matches!(
+        eval(&["f:{[x] x}", "xs:1 2 3", "xs[f]"]).unwrap_err().kind,
+        EvalErrorKind::Type(_)
+    ));
+}
+
+// Comparisons, booleans and where
+#[test]
+fn test_boolean_literals() {
+    assert_eq!(eval(&["1b"]).unwrap(), Value::Boolean(true));
+    assert_eq!(
+        eval(&["0110b"]).unwrap(),
+        bools(&[false, true, true, false])
+    );
+}
+
+#[test]
+fn test_comparisons_are_itemwise() {
+    assert_eq!(eval(&["3>2"]).unwrap(), Value::Boolean(true));
+    assert_eq!(eval(&["1 5 9>5"]).unwrap(), bools(&[false, false, true]));
+    assert_eq!(
+        eval(&["1 2 3=3 2 1"]).unwrap(),
+        bools(&[false, true, false])
+    );
+    assert_eq!(eval(&["2<>1 2"]).unwrap(), bools(&[true, false]));
+    assert_eq!(eval(&["1+1<=2"]).unwrap(), Value::Boolean(true));
+    // Integers and floats compare by value, for equality as for order
+    assert_eq!(eval(&["1=1.0"]).unwrap(), Value::Boolean(true));
+    assert_eq!(eval(&["1<>1.0"]).unwrap(), Value::Boolean(false));
+    assert_eq!(
+        eval(&["1 2 3=1 2.0 4"]).unwrap(),
+        bools(&[true, true, false])
+    );
+    assert_eq!(eval(&["2.5<>2"]).unwrap(), Value::Boolean(true));
+    assert!(matches!(
+        eval(&["1 2=1 2 3"]).unwrap_err().kind,
+        EvalErrorKind::LengthMismatch(_)
+    ));
+}
+
+#[test]
+fn test_where() {
+    assert_eq!(eval(&["where 0101b"]).unwrap(), ints(&[1, 3]));
+    assert_eq!(eval(&["where 2 0 1"]).unwrap(), ints(&[0, 0, 2]));
+    assert_eq!(eval(&["where 0000b"]).unwrap(), ints(&[]));
+    assert!(matches!(
+        eval(&["where 1"]).unwrap_err().kind,
+        EvalErrorKind::Type(_)
+    ));
+}
+
+#[test]
+fn test_filter_with_where() {
+    let xs = "xs:3 8 1 9 6";
+    assert_eq!(eval(&[xs, "xs[where xs>5]"]).unwrap(), ints(&[8, 9, 6]));
+    assert_eq!(eval(&[xs, "where xs>5"]).unwrap(), ints(&[1, 3, 4]));
+}
+
+// Structural builtins: enlist, flip, raze, reverse and the like
+fn session() -> Session {
+    let mut session = Session::new();
+    session
+        .eval("t: flip `sym`px!(`AAPL`MSFT`IBM;100 250 140)")
+        .unwrap();
+    session
+}
+
+#[test]
+fn test_enlist() {
+    let mut s = session();
+    assert_eq!(show(&mut s, "enlist 5"), ",5");
+    assert_eq!(show(&mut s, "count enlist 1 2 3"), "1");
+    // A dict becomes a table of one row
+    assert_eq!(
+        show(&mut s, "enlist `sym`px!(`GOOG;170)"),
+        "flip `sym`px!(,`GOOG;,170)"
+    );
+}
+
+#[test]
+fn test_flip_lists() {
+    let mut s = session();
+    assert_eq!(show(&mut s, "flip (1 2 3;4 5 6)"), "(1 4;2 5;3 6)");
+    assert_eq!(show(&mut s, "flip flip (1 2 3;4 5 6)"), "(1 2 3;4 5 6)");
+    assert!(s.eval("flip (1 2;3 4 5)").is_err());
+    assert!(s.eval("flip 5").is_err());
+}
+
+#[test]
+fn test_raze() {
+    let mut s = session();
+    assert_eq!(show(&mut s, "raze (1 2;3;4 5)"), "1 2 3 4 5");
+    assert_eq!(show(&mut s, "raze 5"), ",5");
+    assert_eq!(show(&mut s, "raze `a`b!(1 2;3 4)"), "1 2 3 4");
+    // Only one level is joined
+    assert_eq!(show(&mut s, "count raze ((1 2;3);4)"), "3");
+}
+
+#[test]
+fn test_reverse() {
+    let mut s = session();
+    assert_eq!(show(&mut s, "reverse 1 2 3"), "3 2 1");
+    assert_eq!(show(&mut s, "reverse `a`b!1 2"), "`b`a!2 1");
+    assert_eq!(
+        show(&mut s, "reverse t"),
+        "flip `sym`px!(`IBM`MSFT`AAPL;140 250 100)"
+    );
+    assert_eq!(show(&mut s, "reverse 5"), "5");
+}
+
+#[test]
+fn test_first_and_last() {
+    let mut s = session();
+    assert_eq!(show(&mut s, "first 1 2 3"), "1");
+    assert_eq!(show(&mut s, "last 1 2 3"), "3");
+    assert_eq!(show(&mut s, "last `a`b!1 2"), "2");
+    assert_eq!(show(&mut s, "first t"), "`sym`px!(`AAPL;100)");
+    assert_eq!(show(&mut s, "last t"), "`sym`px!(`IBM;140)");
+    assert_eq!(show(&mut s, "first 5"), "5");
+    // Nothing to take gives a null
+    assert_eq!(show(&mut s, "last 0#1 2"), "0N");
+}
+
+#[test]
+fn test_distinct() {
+    let mut s = session();
+    assert_eq!(show(&mut s, "distinct 3 1 3 2 1"), "3 1 2");
+    assert_eq!(show(&mut s, "distinct `b`a`b"), "`b`a");
+    // Nulls are equal to each other
+    assert_eq!(show(&mut s, "distinct 1.5 0n 0n"), "1.5 0n");
+    assert_eq!(
+        show(&mut s, "distinct flip `a`b!(1 1 2;3 3 4)"),
+        "flip `a`b!(1 2;3 4)"
+    );
+    assert!(s.eval("distinct 5").is_err());
+}
+
+#[test]
+fn test_group() {
+    let mut s = session();
+    assert_eq!(show(&mut s, "group `a`b`a`c"), "`a`b`c!(0 2;,1;,3)");
+    assert_eq!(show(&mut s, "group 0#1"), "()!()");
+    s.eval("g: group 5 7 5 5").unwrap();
+    assert_eq!(show(&mut s, "g[5]"), "0 2 3");
+    assert!(s.eval("group t").is_err());
+}
+
+// Running and moving aggregates
+#[test]
+fn test_running_sums_and_products() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "sums 1 2 3 4"), "1 3 6 10");
+    assert_eq!(show(&mut s, "prds 1 2 3 4"), "1 2 6 24");
+    assert_eq!(show(&mut s, "sums 1 2.5"), "1 3.5");
+    // Nulls are skipped, carrying the total past them
+    assert_eq!(show(&mut s, "sums 1 0N 2"), "1 1 3");
+    assert_eq!(show(&mut s, "sums 0N 1 2"), "0N 1 3");
+    // An atom is its own running total
+    assert_eq!(show(&mut s, "sums 5"), "5");
+    assert_eq!(show(&mut s, "sums 0#1"), "()");
+    assert!(s.eval("sums `a`b").is_err());
+}
+
+#[test]
+fn test_running_sums_follow_overflow_mode() {
+    let mut s = Session::new();
+    assert!(s.eval("sums 9223372036854775807 1").is_err());
+    s.set_overflow_mode(OverflowMode::Wrap);
+    assert_eq!(show(&mut s, "sums 9223372036854775807 1"), "0W 0N");
+}
+
+#[test]
+fn test_running_extremes() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "maxs 3 1 4 1 5"), "3 3 4 4 5");
+    assert_eq!(show(&mut s, "mins 3 1 4 0N 5"), "3 1 1 1 1");
+    assert_eq!(show(&mut s, "mins 0N 2 1"), "0N 2 1");
+    assert_eq!(show(&mut s, "maxs `b`a`c"), "`b`b`c");
+    assert!(s.eval("maxs (1;`a)").is_err());
+}
+
+#[test]
+fn test_pairwise_deltas_and_ratios() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "deltas 1 4 9 16"), "1 3 5 7");
+    assert_eq!(show(&mut s, "ratios 1 2 8"), "1 2 4f");
+    assert_eq!(show(&mut s, "ratios 4 2"), "4 0.5");
+    assert_eq!(show(&mut s, "deltas 1 0N 3"), "1 0N 0N");
+    assert_eq!(show(&mut s, "deltas 7"), "7");
+    // The running total undone
+    assert_eq!(show(&mut s, "deltas sums 5 1 3"), "5 1 3");
+}
+
+#[test]
+fn test_moving_windows() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "msum[2;1 2 3 4]"), "1 3 5 7");
+    assert_eq!(show(&mut s, "msum[2;1.5 2.5 3]"), "1.5 4 5.5");
+    assert_eq!(show(&mut s, "mavg[3;1 2 3 4 5]"), "1 1.5 2 3 4");
+    assert_eq!(show(&mut s, "mmax[2;3 1 4 1 5]"), "3 3 4 4 5");
+    assert_eq!(show(&mut s, "mmin[3;3 1 4 1 5 9 2 6]"), "3 1 1 1 1 1 2 2");
+    assert_eq!(show(&mut s, "mdev[2;1 3 5]"), "0 1 1f");
+    assert_eq!(show(&mut s, "msum[9;5]"), "5");
+    assert!(s.eval("msum[0;1 2]").is_err());
+    assert!(s.eval("mavg[2;`a`b]").is_err());
+}
+
+#[test]
+fn test_moving_windows_skip_nulls() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "msum[3;1 0N 3 0N 0N 0N]"), "1 1 4 3 3 0");
+    assert_eq!(show(&mut s, "mavg[2;(1;0N;0N;4)]"), "1 1 0n 4");
+    assert_eq!(show(&mut s, "mdev[2;(1;0N;0N;4)]"), "0 0 0n 0");
+    assert_eq!(show(&mut s, "mmax[2;(0N;0N;2)]"), "0N 0N 2");
+}
+
+#[test]
+fn test_moving_windows_keep_precision() {
+    let mut s = Session::new();
+    // Far from zero, a variance from sums of squares cancels to nothing
+    assert_eq!(
+        show(
+            &mut s,
+            "mdev[2;1000000001.0 1000000002.0 1000000003.0 1000000004.0]"
+        ),
+        "0 0.5 0.5 0.5"
+    );
+    // A large item leaving the window takes no small ones with it
+    assert_eq!(
+        show(&mut s, "mavg[2;100000000000000000.0 1.0 1.0 1.0]"),
+        "100000000000000000 50000000000000000 1 1f"
+    );
+    assert_eq!(
+        show(&mut s, "mdev[2;100000000000000000.0 1.0 1.0 1.0]"),
+        "0 50000000000000000 0 0f"
+    );
+    assert_eq!(
+        show(&mut s, "msum[2;100000000000000000.0 1.0 1.0 1.0]"),
+        "100000000000000000 100000000000000000 2 2f"
+    );
+}
+
+#[test]
+fn test_moving_windows_recover_from_infinities() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "msum[2;1 0w 1 1 1]"), "1 0w 0w 2 2");
+    assert_eq!(show(&mut s, "mavg[2;1 0w 1 1 1]"), "1 0w 0w 1 1");
+    assert_eq!(show(&mut s, "mdev[2;1 0w 1 3 1]"), "0 0n 0n 1 1");
+    assert_eq!(show(&mut s, "msum[2;(1;0w;-0w;2;3.0)]"), "1 0w 0n -0w 5");
+}
+
+#[test]
+fn test_moving_extremes_match_each_window() {
+    let mut s = Session::new();
+    // A fixed pseudo-random list, so rises and falls of every length occur
+    let items: Vec<i64> = (0..200i64).map(|i| (i * 7919 + 13) % 97).collect();
+    let list = items
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    for n in [1, 2, 5, 17] {
+        let window = |i: usize| &items[i.saturating_sub(n - 1)..=i];
+        let maxima: Vec<String> = (0..items.len())
+            .map(|i| window(i).iter().max().unwrap().to_string())
+            .collect();
+        let minima: Vec<String> = (0..items.len())
+            .map(|i| window(i).iter().min().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            show(&mut s, &format!("mmax[{};{}]", n, list)),
+            maxima.join(" ")
+        );
+        assert_eq!(
+            show(&mut s, &format!("mmin[{};{}]", n, list)),
+            minima.join(" ")
+        );
+    }
+}
+
+// Matrices
+/// Rows of a matrix result as floats
+fn rows(value: Value) -> Vec<Vec<f64>> {
+    let Value::List(rows) = value else {
+        panic!("expected a matrix, got {:?}", value);
+    };
+    rows.iter()
+        .map(|row| {
+            row.as_list()
+                .unwrap()
+                .iter()
+                .map(|x| x.as_f64().unwrap())
+                .collect()
+        })
+        .collect()
+}
+
+/// Expected rows, each a slice of floats
+type Expected<'a> = &'a [&'a [f64]];
+
+fn assert_close(actual: Vec<Vec<f64>>, expected: Expected) {
+    assert_eq!(actual.len(), expected.len());
+    for (row, want) in actual.iter().zip(expected) {
+        assert_eq!(row.len(), want.len());
+        for (x, y) in row.iter().zip(*want) {
+            assert!(
+                (x - y).abs() < 1e-9,
+                "{:?} is not close to {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn test_matrix_multiplication() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "mmu[(1 2;3 4);(5 6;7 8)]"), "(19 22f;43 50f)");
+    assert_eq!(
+        show(&mut s, "mmu[(1 2 3;4 5 6);(1 2;3 4;5 6)]"),
+        "(22 28f;49 64f)"
+    );
+    // Vectors stand for a row on the left and a column on the right
+    assert_eq!(show(&mut s, "mmu[(1 2;3 4);1 1]"), "3 7f");
+    assert_eq!(show(&mut s, "mmu[1 1;(1 2;3 4)]"), "4 6f");
+    assert_eq!(show(&mut s, "mmu[1 2 3;4 5 6]"), "32f");
+    assert!(s.eval("mmu[(1 2 3;4 5 6);(1 2;3 4)]").is_err());
+    assert!(s.eval("mmu[(1 2;3);(1 2;3 4)]").is_err());
+    assert!(s.eval("mmu[`a`b;1 2]").is_err());
+}
+
+#[test]
+fn test_matrix_inverse() {
+    let mut s = Session::new();
+    let inverse = s.eval("inv (1 2;3 4)").unwrap();
+    assert_close(rows(inverse), &[&[-2.0, 1.0], &[1.5, -0.5]]);
+    let identity = s.eval("m:(2 1 1;1 3 2;1 0 0); mmu[m;inv m]").unwrap();
+    assert_close(
+        rows(identity),
+        &[&[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0], &[0.0, 0.0, 1.0]],
+    );
+    assert!(s.eval("inv (1 2;2 4)").is_err());
+    assert!(s.eval("inv (1 2 3;4 5 6)").is_err());
+    assert!(s.eval("inv 1 2").is_err());
+}
+
+#[test]
+fn test_transpose() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "transpose (1 2 3;4 5 6)"), "(1 4;2 5;3 6)");
+    assert_eq!(show(&mut s, "transpose transpose (1 2;3 4)"), "(1 2;3 4)");
+    assert!(s.eval("transpose 1 2 3").is_err());
+    assert!(s.eval("transpose (1 2;3)").is_err());
+}
+
+// Attributes
+#[test]
+fn test_attributes_are_checked_and_shown() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "x: `s#1 3 5"), "`s#1 3 5");
+    assert_eq!(show(&mut s, "attr x"), "`s");
+    assert_eq!(show(&mut s, "attr 1 3 5"), "`");
+    assert_eq!(show(&mut s, "`#x"), "1 3 5");
+    assert_eq!(show(&mut s, "`u#`a`b"), "`u#`a`b");
+    assert!(s.eval("`s#3 1").is_err());
+    assert!(s.eval("`u#1 1").is_err());
+    assert!(s.eval("`q#1 2").is_err());
+    assert!(s.eval("`s#5").is_err());
+}
+
+#[test]
+fn test_attributes_are_dropped_where_unused() {
+    let mut s = Session::new();
+    s.eval("x: `s#1 3 5").unwrap();
+    assert_eq!(show(&mut s, "x+1"), "2 4 6");
+    assert_eq!(show(&mut s, "x[1]"), "3");
+    assert_eq!(show(&mut s, "x = 1 3 5"), "111b");
+    // Passed to and returned from a function, the list keeps it
+    s.eval("f: {[l] l}").unwrap();
+    assert_eq!(show(&mut s, "attr f[x]"), "`s");
+    assert_eq!(show(&mut s, "attr f x"), "`s");
+}
+
+#[test]
+fn test_builtins_agree_with_plain_lists() {
+    let mut s = Session::new();
+    s.eval("x: `s#0N 2 4 4 7").unwrap();
+    for builtin in ["max", "min", "count", "distinct", "group"] {
+        assert_eq!(
+            show(&mut s, &format!("{} x", builtin)),
+            show(&mut s, &format!("{} `#x", builtin)),
+            "{}",
+            builtin
+        );
+    }
+    s.eval("y: `g#`a`b`a`c").unwrap();
+    s.eval("z: `u#`c`a`b").unwrap();
+    for source in ["group y", "distinct y", "group z", "distinct z"] {
+        let plain = source.replace(' ', " `#");
+        assert_eq!(show(&mut s, source), show(&mut s, &plain), "{}", source);
+    }
+}
+
+#[test]
+fn test_table_columns_keep_attributes() {
+    let mut s = Session::new();
+    s.eval("t: flip `sym`time`px!(`a`b`a`c`b;1 2 3 4 5;10 20 30 40 50)")
+        .unwrap();
+    s.eval("a: update `g#sym, `s#time from t").unwrap();
+    assert_eq!(show(&mut s, "attr a`sym"), "`g");
+    assert_eq!(show(&mut s, "attr a`time"), "`s");
+    // Changing the rows leaves the columns plain
+    for (changed, attribute) in [
+        ("select from a where px>10", "`"),
+        ("update sym: `z from a where px>30", "`"),
+        ("update px: px+1 from a", "`g"),
+        ("select sym, px from a", "`g"),
+    ] {
+        s.eval(&format!("c: {}", changed)).unwrap();
+        assert_eq!(show(&mut s, "attr c`sym"), attribute, "{}", changed);
+    }
+}
+
+#[test]
+fn test_where_conditions_on_attributed_columns() {
+    let mut s = Session::new();
+    s.eval("t: flip `sym`time`px!(`a`b`a`c`b;1 2 3 4 5;10 20 30 40 50)")
+        .unwrap();
+    s.eval("a: update `g#sym, `s#time from t").unwrap();
+    s.eval("k: `b").unwrap();
+    for condition in [
+        "sym=`b",
+        "sym=k",
+        "sym in `a`c",
+        "sym in `z",
+        "time within 2 4",
+        "time=3",
+        "sym=`a, px>10",
+        "px>10, sym=`a",
+        "sym=sym",
+    ] {
+        let plain = format!("select from t where {}", condition);
+        let attributed = format!("select from a where {}", condition);
+        assert_eq!(
+            show(&mut s, &attributed),
+            show(&mut s, &plain),
+            "{}",
+            condition
+        );
+    }
+    s.eval("b: select from a where sym=`b").unwrap();
+    assert_eq!(show(&mut s, "b`px"), "20 50");
+}
+
+#[test]
+fn test_as_of_join_on_attributed_columns() {
+    let mut s = Session::new();
+    s.eval("q: flip `sym`time`bid!(`a`b`a`b`a;1 2 3 4 5;10 20 11 21 12)")
+        .unwrap();
+    s.eval("t: flip `sym`time!(`a`b`a`c`b;2 4 6 1 0)").unwrap();
+    let plain = show(&mut s, "aj[`sym`time;t;q]");
+    for attributed in [
+        "update `g#sym from q",
+        "update `s#time from q",
+        "update `g#sym, `s#time from q",
+    ] {
+        s.eval(&format!("r: {}", attributed)).unwrap();
+        assert_eq!(show(&mut s, "aj[`sym`time;t;r]"), plain, "{}", attributed);
+    }
+    // Unsorted times within a group are still sorted for the search
+    s.eval("q: flip `sym`time`bid!(`a`a`b;5 1 2;1 2 3)")
+        .unwrap();
+    s.eval("t: flip `sym`time!(`a`a;2 6)").unwrap();
+    let plain = show(&mut s, "aj[`sym`time;t;q]");
+    s.eval("r: update `g#sym from q").unwrap();
+    assert_eq!(show(&mut s, "aj[`sym`time;t;r]"), plain);
+    s.eval("j: aj[`sym`time;t;r]").unwrap();
+    assert_eq!(show(&mut s, "j`bid"), "2 1");
+}
+
+// Membership and lookups: in, within and find
+#[test]
+fn test_in_tests_membership() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "2 in 1 2 3"), "1b");
+    assert_eq!(show(&mut s, "1 4 in 1 2 3"), "10b");
+    assert_eq!(show(&mut s, "`b in `a`b"), "1b");
+    assert_eq!(show(&mut s, "1 in 1"), "1b");
+    assert_eq!(show(&mut s, "3 4 in (1 2;3 4)"), "1b");
+    assert_eq!(show(&mut s, "in[5;1 2 3]"), "0b");
+}
+
+#[test]
+fn test_within_tests_ranges() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "3 7 within 1 5"), "10b");
+    assert_eq!(show(&mut s, "5 within (1;5)"), "1b");
+    assert_eq!(
+        show(&mut s, "2024.01.15 within (2024.01.01;2024.01.31)"),
+        "1b"
+    );
+    assert!(s.eval("1 within 1 2 3").is_err());
+}
+
+#[test]
+fn test_find_gives_positions() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "`a`b`c?`b`z"), "1 3");
+    assert_eq!(show(&mut s, "10 20 30?30"), "2");
+    assert_eq!(show(&mut s, "find[1 2 1;1]"), "0");
+    assert_eq!(show(&mut s, "(1 2;3 4)?3 4"), "1");
+    // An integer on the left still rolls
+    assert_eq!(show(&mut s, "count 3?10"), "3");
+    assert!(s.eval("find[5;5]").is_err());
+}
+
+#[test]
+fn test_lookups_agree_with_attributes() {
+    let mut s = Session::new();
+    s.eval("x: `s#1 3 3 5 7").unwrap();
+    s.eval("y: `g#`b`a`b`c").unwrap();
+    s.eval("z: `u#40 10 30").unwrap();
+    for source in [
+        "0 3 4 7 8 in x",
+        "x?3 4 7",
+        "x within 2 5",
+        "x within 8 9",
+        "`a`b`d in y",
+        "y?`b`c`d",
+        "z?10 20 30",
+        "10 in z",
+        "find[z;30]",
+    ] {
+        let plain = source
+            .replace('x', "(`#x)")
+            .replace('y', "(`#y)")
+            .replace('z', "(`#z)");
+        assert_eq!(show(&mut s, source), show(&mut s, &plain), "{}", source);
+    }
+    assert_eq!(show(&mut s, "x within 2 5"), "01110b");
+}
+
+// Bucketing with xbar
+#[test]
+fn test_xbar_rounds_down_to_buckets() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "5 xbar 3 7 12 15"), "0 5 10 15");
+    assert_eq!(show(&mut s, "3 xbar (-1;-4;0N)"), "-3 -6 0N");
+    assert_eq!(show(&mut s, "0.25 xbar 1.3 1.6"), "1.25 1.5");
+    assert_eq!(show(&mut s, "xbar[10;25]"), "20");
+    assert!(s.eval("0 xbar 3").is_err());
+    assert!(s.eval("5 xbar `a").is_err());
+}
+
+#[test]
+fn test_xbar_buckets_temporal_values() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "00:05 xbar 10:07:30"), "10:05:00.000");
+    assert_eq!(show(&mut s, "1000 xbar 10:07:30.250"), "10:07:30.000");
+    assert_eq!(
+        show(&mut s, "00:05 xbar 2024.01.15D10:07:30"),
+        "2024.01.15D10:05:00.000000000"
+    );
+    assert_eq!(show(&mut s, "7 xbar 2024.01.15"), "2024.01.11");
+}
+
+#[test]
+fn test_xbar_makes_bars_with_select_by() {
+    let mut s = Session::new();
+    s.eval("trades: flip `time`px!((10:01;10:03;10:06;10:09;10:11);1 2 3 4 5)")
+        .unwrap();
+    assert_eq!(
+        show(&mut s, "select px: sum px by 00:05 xbar time from trades"),
+        "(flip ,`time!((10:00:00.000;10:05:00.000;10:10:00.000)))!(flip ,`px!(3 7 5))"
+    );
+}
+
+// Statistics
+#[test]
+fn test_weighted_sums_and_averages() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "wsum[1 2 3;4 5 6]"), "32");
+    assert_eq!(show(&mut s, "wsum[1 2 3;0.5 1 1]"), "5.5");
+    assert_eq!(show(&mut s, "wavg[1 1 2;4 6 8]"), "6.5");
+    // A null on either side leaves its position out, weight included
+    assert_eq!(show(&mut s, "wavg[(1;0N;3);4 5 6]"), "5.5");
+    assert_eq!(show(&mut s, "wsum[1 2;(3;0N)]"), "3");
+    assert!(s.eval("wsum[1 2;1 2 3]").is_err());
+    assert!(s.eval("wavg[1 2;`a`b]").is_err());
+}
+
+#[test]
+fn test_weighted_integers_stay_exact() {
+    let mut s = Session::new();
+    // Past 2^53, where floats no longer hold every integer
+    assert_eq!(
+        show(&mut s, "wsum[1 2;9007199254740993 1]"),
+        "9007199254740995"
+    );
+    assert_eq!(
+        show(&mut s, "wavg[1 1;9007199254740993 9007199254740995]"),
+        "9007199254740994f"
+    );
+    // Overflow is resolved as in arithmetic
+    assert!(s.eval("wsum[3 1;4611686018427387904 0]").is_err());
+    s.set_overflow_mode(OverflowMode::Promote);
+    assert_eq!(
+        show(&mut s, "wsum[3 1;4611686018427387904 0]"),
+        show(&mut s, "3*4611686018427387904.0")
+    );
+}
+
+#[test]
+fn test_variance_and_deviation() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "var 2 4 4 4 5 5 7 9"), "4f");
+    assert_eq!(show(&mut s, "dev 2 4 4 4 5 5 7 9"), "2f");
+    assert_eq!(show(&mut s, "dev (2;0N;4)"), "1f");
+    assert_eq!(show(&mut s, "var 0#1"), "0n");
+    assert!(s.eval("var `a").is_err());
+}
+
+#[test]
+fn test_covariance_and_correlation() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "cov[1 2 3;1 2 4]"), "1f");
+    assert_eq!(show(&mut s, "cor[1 2 3;2 4 6]"), "1f");
+    assert_eq!(show(&mut s, "cor[1 2 3;3 2 1]"), "-1f");
+    assert_eq!(show(&mut s, "cov[(1;2;0N);(1;0N;5)]"), "0f");
+}
+
+#[test]
+fn test_statistics_aggregate_groups() {
+    let mut s = Session::new();
+    s.eval("t: flip `sym`px`qty!(`a`b`a;10 20 30;1 1 3)")
+        .unwrap();
+    assert_eq!(
+        show(&mut s, "select vwap: wavg[qty;px] by sym from t"),
+        "(flip ,`sym!(`a`b))!(flip ,`vwap!(25 20f))"
+    );
+}
+
+// Operations on long lists split across threads
+/// A serial session and one splitting lists of 4 or more items, with the
+/// same bindings
+fn sessions(setup: &[&str]) -> (Session, Session) {
+    let mut serial = Session::new();
+    let mut parallel = Session::new();
+    parallel.set_config(EvaluatorConfig {
+        parallel: true,
+        parallel_threshold: 4,
+    });
+    for source in setup {
+        serial.eval(source).unwrap();
+        parallel.eval(source).unwrap();
+    }
+    (serial, parallel)
+}
+
+#[test]
+fn test_parallel_execution_is_off_by_default() {
+    let config = Session::new().config();
+    assert!(!config.parallel);
+    assert_eq!(config, EvaluatorConfig::default());
+}
+
+#[test]
+fn test_parallel_results_match_serial() {
+    let (mut serial, mut parallel) = sessions(&[
+        "seed 42",
+        "x: 1000?1000",
+        "y: 1000?1000",
+        "f: 0.5*(1000?100)",
+        "n: (3;0N;7;1;0N;2;9)",
+    ]);
+    let sources = [
+        "x+y", "x*y", "x-3", "x%7", "x/1+y", "f+x", "2.5*f", "n+1", "sum x", "sum n", "sum f",
+        "avg x", "avg n", "min x", "max f", "max n", "asc x", "desc f", "iasc x", "idesc n",
+    ];
+    for source in sources {
+        assert_eq!(
+            show(&mut parallel, source),
+            show(&mut serial, source),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn test_parallel_errors_match_serial() {
+    let (mut serial, mut parallel) = sessions(&["x: 1 2 3 4 5 6 7 8", "z: 0 1 2 3 4 5 6 7"]);
+    for source in ["x/z", "min (1;`a;2;3;4)", "sum (1;2;`a;4)", "x+1 2"] {
+        assert!(serial.eval(source).is_err(), "{}", source);
+        assert!(parallel.eval(source).is_err(), "{}", source);
+    }
+    // Overflow is resolved by the mode, as without threads
+    parallel.eval("big: 9223372036854775806 1 1 1 1 1").unwrap();
+    assert!(parallel.eval("big+2").is_err());
+    assert!(parallel.eval("sum big").is_err());
+}
+
+// Lists stored as vectors
+#[test]
+fn test_arithmetic_binds_vectors_and_shows_lists() {
+    let mut session = Session::new();
+    session.eval("x: 1 2 3*10").unwrap();
+    session.eval("y: x+0.5").unwrap();
+    assert!(matches!(session.get("x"), Some(Value::IntVector(_))));
+    assert!(matches!(session.get("y"), Some(Value::FloatVector(_))));
+    // Results are shown as general lists
+    assert!(matches!(session.eval("x+1").unwrap(), Value::List(_)));
+    assert_eq!(session.eval("x").unwrap(), Value::from(vec![10i64, 20, 30]));
+}
+
+#[test]
+fn test_vectors_behave_as_lists() {
+    // x and y are vectors, a and b the same lists written out
+    let mut session = Session::new();
+    for source in [
+        "x: 1 2 0N 4*10",
+        "a: 10 20 0N 40",
+        "y: x+0.5",
+        "b: 10.5 20.5 0n 40.5",
+        "f: {[v] v*2}",
+    ] {
+        session.eval(source).unwrap();
+    }
+    let pairs = [
+        ("x", "a"),
+        ("x+1", "a+1"),
+        ("x*y", "a*b"),
+        ("sum x", "sum a"),
+        ("sum y", "sum b"),
+        ("avg x", "avg a"),
+        ("avg y", "avg b"),
+        ("min x", "min a"),
+        ("max y", "max b"),
+        ("count x", "count a"),
+        ("x[1]", "a[1]"),
+        ("type x", "type a"),
+        ("x=20", "a=20"),
+        ("asc y", "asc b"),
+        ("f x", "f a"),
+        ("100-x", "100-a"),
+    ];
+    for (vector, list) in pairs {
+        assert_eq!(
+            show(&mut session, vector),
+            show(&mut session, list),
+            "{}",
+            vector
+        );
+    }
+    assert_eq!(session.eval("x").unwrap(), session.eval("a").unwrap());
+}
+
+#[test]
+fn test_aggregates_of_empty_and_null_vectors() {
+    let mut session = Session::new();
+    session.set("e", Value::IntVector(Vec::new().into()));
+    session.set("n", Value::FloatVector(vec![f64::NAN, f64::NAN].into()));
+    assert_eq!(show(&mut session, "sum e"), "0");
+    assert_eq!(show(&mut session, "max e"), "-0W");
+    assert_eq!(show(&mut session, "sum n"), show(&mut session, "sum 0n 0n"));
+    assert_eq!(show(&mut session, "avg n"), "0n");
+    assert_eq!(show(&mut session, "min n"), "0W");
+}
+
+#[test]
+fn test_arrow_arrays_are_read_as_vectors() {
+    let array = PrimitiveArray::<i64>::from_vec(vec![5, 6, 7]);
+    let mut session = Session::new();
+    session.set("a", vectors::from_arrow(&array).unwrap());
+    assert_eq!(show(&mut session, "sum a*2"), "36");
+    session.eval("b: a-5").unwrap();
+    let back = vectors::to_arrow(&session.get("b").unwrap()).unwrap();
+    let back = back.as_any().downcast_ref::<PrimitiveArray<i64>>().unwrap();
+    assert_eq!(back.values().as_slice(), &[0, 1, 2]);
+}
@@ -0,0 +1,586 @@
+use serde_json::{Value as JsonValue, json};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use storage::schema::SimpleDataType;
+use storage::{ColumnSchema, QStoreConfig, ScalarValue, StreamingInserter, Table, TableSchema};
+use tracing::Subscriber;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+use wabznasm::arithmetic::OverflowMode;
+use wabznasm::errors::EvalError;
+use wabznasm::format::Printer;
+use wabznasm::journal::{self, Journal};
+use wabznasm::jupyter::session::JupyterSession;
+use wabznasm::repl::replay_journal;
+use wabznasm::script::Script;
+use wabznasm::telemetry::request_span;
+use wabznasm::{Session, Value, completion};
+
+#[test]
+fn test_eval_keeps_bindings() {
+    let mut session = Session::new();
+    session.eval("add: {[x;y] x+y}").unwrap();
+    session.eval("a: 40").unwrap();
+    assert_eq!(session.eval("add[a;2]").unwrap(), Value::Integer(42));
+    assert_eq!(session.get("a"), Some(Value::Integer(40)));
+    assert_eq!(session.get("missing"), None);
+}
+
+#[test]
+fn test_eval_all_stops_at_the_first_error() {
+    let mut session = Session::new();
+    let values = session.eval_all(["a: 2", "b: a*3", "a+b"]).unwrap();
+    assert_eq!(values.last(), Some(&Value::Integer(8)));
+    assert!(session.eval_all(["c: 1", "missing", "d: 2"]).is_err());
+    assert_eq!(session.get("c"), Some(Value::Integer(1)));
+    assert_eq!(session.get("d"), None);
+}
+
+#[test]
+fn test_host_values_and_natives() {
+    let mut session = Session::new();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&seen);
+    session.define("record", 1, move |args| {
+        sink.lock().unwrap().push(args[0].clone());
+        Ok(args[0].clone())
+    });
+    session.set("prices", vec![3i64, 1, 2]);
+
+    assert_eq!(
+        session.eval("record asc prices").unwrap(),
+        Value::from(vec![1i64, 2, 3])
+    );
+    assert_eq!(seen.lock().unwrap().len(), 1);
+    assert_eq!(session.format(&session.get("record").unwrap()), "record");
+}
+
+#[test]
+fn test_native_errors_and_arity() {
+    let mut session = Session::new();
+    session.define("fail", 1, |_| Err("refused".into()));
+    let message = session.eval("fail[1]").unwrap_err().to_string();
+    assert!(message.contains("refused"));
+    assert!(session.eval("fail[1;2]").is_err());
+}
+
+#[test]
+fn test_syntax_error_is_reported() {
+    let mut session = Session::new();
+    let report = session.eval("1+").unwrap_err();
+    assert_eq!(report.code().unwrap().to_string(), "SYNTAX_ERROR");
+}
+
+#[test]
+fn test_result_as_json() {
+    let mut session = Session::new();
+    let value = session.eval("`a`b!1 2").unwrap();
+    assert_eq!(
+        JsonValue::try_from(&value).unwrap(),
+        json!({"a": 1, "b": 2})
+    );
+}
+
+#[test]
+fn test_registered_function_metadata() {
+    use wabznasm::builtins::NativeFunction;
+
+    let mut session = Session::new();
+    session.register(
+        NativeFunction::new("vwap", 2, |args| {
+            let px = args[0].as_list().unwrap();
+            let size = args[1].as_list().unwrap();
+            let notional: i64 = px
+                .iter()
+                .zip(size)
+                .filter_map(|(p, s)| Some(p.as_integer()? * s.as_integer()?))
+                .sum();
+            let volume: i64 = size.iter().filter_map(Value::as_integer).sum();
+            Ok(Value::Integer(notional / volume))
+        })
+        .param("px", "list")
+        .param("size", "list")
+        .doc("Volume-weighted average price"),
+    );
+
+    assert_eq!(
+        session.eval("vwap[10 20; 1 3]").unwrap(),
+        Value::Integer(17)
+    );
+    assert_eq!(
+        session.help("vwap").unwrap(),
+        "vwap[px:list;size:list]\nVolume-weighted average price"
+    );
+    let message = session.eval("vwap[10; 1 3]").unwrap_err().to_string();
+    assert!(message.contains("px expects list"));
+
+    assert_eq!(
+        session.help("asc").unwrap(),
+        "asc[x]\nThe items of the list `x` sorted ascending"
+    );
+    session.eval("f: {[x] x}").unwrap();
+    assert_eq!(session.help("f").unwrap(), "{[x] x}");
+    assert_eq!(session.help("nothing"), None);
+}
+
+#[test]
+fn test_function_definitions_to_edit() {
+    let mut session = Session::new();
+    session.eval("f: {[x;y] x+y}").unwrap();
+    session.eval("n: 5").unwrap();
+    assert_eq!(session.definition("f").unwrap(), "f: {[x;y] x+y}");
+    assert_eq!(
+        session.definition("n").unwrap_err(),
+        "n is integer, not a function"
+    );
+    assert!(session.definition("count").is_err());
+    assert!(session.definition("missing").is_err());
+    // Evaluating the definition again rebinds the function as it was
+    let definition = session.definition("f").unwrap();
+    session.eval("f: 0").unwrap();
+    session.eval(&definition).unwrap();
+    assert_eq!(session.eval("f[1;2]").unwrap(), Value::Integer(3));
+}
+
+#[test]
+fn test_execution_stats() {
+    let mut session = Session::new();
+    session.eval("1 2 3 4 5").unwrap();
+    let stats = session.stats();
+    assert_eq!(stats.rows_returned, 5);
+    assert_eq!(stats.rows_scanned, 0);
+
+    session.eval("1+2").unwrap();
+    assert_eq!(session.stats().rows_returned, 1);
+
+    assert!(session.eval("undefined").is_err());
+    assert_eq!(session.stats().rows_returned, 0);
+}
+
+#[test]
+fn test_timing_with_peak_memory() {
+    let mut session = Session::new();
+    let stats = session.time("count 1000#1", false).unwrap();
+    assert_eq!(stats.peak_bytes, None);
+    let stats = session.time("count 1000#1", true).unwrap();
+    assert_eq!(stats.peak_bytes, Some(wabznasm::memory::list_size(1000)));
+    // Every value computed counts towards the bytes allocated
+    let allocated = stats.allocated_bytes.unwrap();
+    assert!(allocated > wabznasm::memory::list_size(1000));
+    let stats = session.time("count 1000#1; count 1000#1", true).unwrap();
+    assert!(stats.allocated_bytes.unwrap() >= 2 * wabznasm::memory::list_size(1000));
+    assert_eq!(stats.peak_bytes, Some(wabznasm::memory::list_size(1000)));
+    // Only the timed evaluation is measured
+    session.eval("x: 10#1").unwrap();
+    assert_eq!(session.stats().peak_bytes, None);
+    assert_eq!(session.stats().allocated_bytes, None);
+    assert!(session.time("1+", true).is_err());
+    assert_eq!(session.eval("1+1").unwrap(), Value::Integer(2));
+    assert_eq!(session.stats().peak_bytes, None);
+}
+
+#[test]
+fn test_variable_explorer() {
+    let mut session = Session::new();
+    session
+        .eval("t: flip `sym`px!(`AAPL`MSFT;100 250)")
+        .unwrap();
+    session.eval("d: `a`b!(1 2 3;`x)").unwrap();
+    session.eval("x: 42").unwrap();
+
+    let variables = session.variables();
+    let names: Vec<&str> = variables.iter().map(|v| v.name.as_str()).collect();
+    assert_eq!(names, ["d", "t", "x"]);
+    assert_eq!(variables[1].type_name, "table");
+    assert_eq!(variables[1].shape, "2 x 2");
+    assert!(variables[1].expandable);
+    assert_eq!(variables[2].preview, "42");
+    assert!(!variables[2].expandable);
+    assert!(variables[0].bytes > variables[2].bytes);
+
+    // Paths drill into columns, dict entries and list items
+    let items = session.explore(&["d", "a"]).unwrap();
+    assert_eq!(items.len(), 3);
+    assert_eq!(
+        (items[2].name.as_str(), items[2].preview.as_str()),
+        ("2", "3")
+    );
+    assert_eq!(
+        session.explore(&["t", "px", "1"]).unwrap_err(),
+        "t px 1 is integer, which has no parts"
+    );
+    assert!(session.explore(&["missing"]).is_err());
+    assert!(session.explore(&["t", "qty"]).is_err());
+
+    // Long values are cut short
+    let items: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+    session.eval(&format!("big: {}", items.join(" "))).unwrap();
+    let big = &session.explore(&[]).unwrap()[0];
+    assert!(big.preview.ends_with("..."));
+}
+
+#[test]
+fn test_display_fits_the_console() {
+    let mut session = Session::new();
+    let dict = session.eval("`a`bc!(1;2 3)").unwrap();
+    assert_eq!(session.display(&dict), "a | 1\nbc| 2 3");
+
+    session.set_printer(Printer::new(12, 2));
+    let list = session.eval("100#1 2 3").unwrap();
+    assert_eq!(session.display(&list), "1 2 3 1 2 ..\n100 items");
+    let table = session.eval("flip `x`y!(1 2 3;4 5 6)").unwrap();
+    assert_eq!(session.display(&table), "x y\n---\n1 4\n..\n3 6\n3 rows");
+    // In full, nothing is left out
+    let full = session.display_in_full(&list);
+    assert_eq!(full.split(' ').count(), 100);
+    // Formatting is unaffected
+    assert_eq!(session.format(&dict), "`a`bc!(1;2 3)");
+}
+
+// Tracing spans tagged with the request they serve
+/// A span's name and the id of the request it belongs to
+type TracedSpan = (String, Option<String>);
+
+type TracedSpans = Arc<Mutex<Vec<TracedSpan>>>;
+
+/// Request id recorded on a `request` span
+struct RequestId(String);
+
+#[derive(Default)]
+struct FindRequestId(Option<String>);
+
+impl Visit for FindRequestId {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "request_id" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+}
+
+/// Records each span's name with the id of the request it belongs to
+#[derive(Clone, Default)]
+struct Recorder(TracedSpans);
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        let mut found = FindRequestId::default();
+        attrs.record(&mut found);
+        if let Some(request_id) = found.0 {
+            span.extensions_mut().insert(RequestId(request_id));
+        }
+        let request_id = span
+            .scope()
+            .find_map(|s| s.extensions().get::<RequestId>().map(|r| r.0.clone()));
+        self.0
+            .lock()
+            .unwrap()
+            .push((span.name().to_string(), request_id));
+    }
+}
+
+fn record(f: impl FnOnce()) -> Vec<TracedSpan> {
+    let recorder = Recorder::default();
+    let subscriber = Registry::default().with(recorder.clone());
+    tracing::subscriber::with_default(subscriber, f);
+    recorder.0.lock().unwrap().clone()
+}
+
+fn in_request(spans: &[TracedSpan], name: &str, request_id: &str) -> bool {
+    spans
+        .iter()
+        .any(|(n, id)| n == name && id.as_deref() == Some(request_id))
+}
+
+#[test]
+fn test_statements_are_traced_within_requests() {
+    let spans = record(|| {
+        let mut session = Session::new();
+        let _request = request_span("req-1", "test").entered();
+        session.eval("x: 1").unwrap();
+        session.eval("x+1").unwrap();
+    });
+    let statements = spans
+        .iter()
+        .filter(|(name, id)| name == "statement" && id.as_deref() == Some("req-1"))
+        .count();
+    assert_eq!(statements, 2);
+}
+
+#[test]
+fn test_storage_spans_share_the_request_id() {
+    let dir = tempfile::tempdir().unwrap();
+    let schema = TableSchema::new("trades".to_string())
+        .add_column(ColumnSchema::new_simple("px".into(), SimpleDataType::Int64));
+    let table = Table::create(schema, QStoreConfig::new(dir.path(), "trades".into())).unwrap();
+
+    let spans = record(|| {
+        let _request = request_span("req-2", "test").entered();
+        let mut inserter = StreamingInserter::new(table);
+        for px in [1, 2, 3] {
+            let row = [("px".to_string(), ScalarValue::Int64(px))];
+            inserter.push(row.into_iter().collect()).unwrap();
+        }
+        let table = inserter.into_table().unwrap();
+        table.filter(|_| true).unwrap();
+    });
+    assert!(in_request(&spans, "insert_batch", "req-2"));
+    assert!(in_request(&spans, "scan", "req-2"));
+}
+
+// Journals of executed input, and replaying them
+#[test]
+fn test_session_journals_successful_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.log");
+
+    let mut session = Session::new();
+    session.set_journal(Journal::open(&path).unwrap());
+    session.eval("x: 20").unwrap();
+    assert!(session.eval("undefined+1").is_err());
+    session.eval("f: {[a] a+x}").unwrap();
+    session.record("\\overflow wrap").unwrap();
+
+    assert_eq!(
+        journal::read(&path).unwrap(),
+        ["x: 20", "f: {[a] a+x}", "\\overflow wrap"]
+    );
+}
+
+#[test]
+fn test_replay_recovers_session_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.log");
+    let mut journal = Journal::open(&path).unwrap();
+    for entry in ["x: 20", "f: {[a] a+x}", "\\overflow wrap"] {
+        journal.record(entry).unwrap();
+    }
+
+    let mut session = Session::new();
+    assert_eq!(replay_journal(&mut session, &path).unwrap(), 3);
+    assert_eq!(session.eval("f[22]").unwrap(), Value::Integer(42));
+    assert_eq!(session.overflow_mode(), OverflowMode::Wrap);
+}
+
+#[test]
+fn test_replay_stops_at_failing_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.log");
+    let mut journal = Journal::open(&path).unwrap();
+    for entry in ["x: 1", "x+undefined", "y: 2"] {
+        journal.record(entry).unwrap();
+    }
+
+    let mut session = Session::new();
+    let err = replay_journal(&mut session, &path).unwrap_err();
+    assert!(err.to_string().contains("entry 2"));
+    assert_eq!(session.get("x"), Some(Value::Integer(1)));
+    assert_eq!(session.get("y"), None);
+}
+
+#[test]
+fn test_jupyter_session_journals_cells() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kernel.log");
+
+    let mut kernel = JupyterSession::new();
+    kernel.set_journal(Journal::open(&path).unwrap());
+    kernel.execute("x: 5").unwrap();
+    assert!(kernel.execute("x%0").is_err());
+    kernel.execute("y: x*2").unwrap();
+
+    let mut session = Session::new();
+    replay_journal(&mut session, &path).unwrap();
+    assert_eq!(session.get("y"), Some(Value::Integer(10)));
+}
+
+// Completion of names
+fn complete(session: &Session, line: &str) -> Vec<String> {
+    let names = session.names();
+    completion::complete(line, line.len(), &names).replacements(line, line.len())
+}
+
+#[test]
+fn test_bound_names_and_builtins_complete() {
+    let mut session = Session::new();
+    session.eval("increment: {[x] x+1}").unwrap();
+    session.eval("incomes: 10 20 30").unwrap();
+    assert_eq!(complete(&session, "1+inc"), ["incomes", "increment["]);
+    assert_eq!(complete(&session, "cou"), ["count["]);
+    assert!(complete(&session, "zzz").is_empty());
+}
+
+#[test]
+fn test_namespaced_names_complete() {
+    let mut session = Session::new();
+    session.eval(".mylib.double: {[x] 2*x}").unwrap();
+    assert_eq!(complete(&session, "f[.myl"), [".mylib.double["]);
+}
+
+#[test]
+fn test_names_bound_later_complete() {
+    let mut session = Session::new();
+    assert!(complete(&session, "tot").is_empty());
+    session.eval("total: 3").unwrap();
+    assert_eq!(complete(&session, "tot"), ["total"]);
+}
+
+// Scripts
+fn write(dir: &Path, name: &str, text: &str) -> String {
+    let path = dir.join(name);
+    fs::write(&path, text).unwrap();
+    path.display().to_string()
+}
+
+#[test]
+fn test_load_binds_each_statement() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write(
+        dir.path(),
+        "lib.wz",
+        "\\ helpers\ndouble: {[x] x*2}\nspread: {[v]\n  m: min v;\n  (max v) - m}\nk: double 21\n",
+    );
+    let mut session = Session::new();
+    assert_eq!(session.load(Path::new(&path)).unwrap(), Value::Integer(42));
+    assert_eq!(session.eval("spread 3 9 4").unwrap(), Value::Integer(6));
+    // The function's locals stay in it
+    assert!(session.get("m").is_none());
+}
+
+#[test]
+fn test_load_builtin_binds_where_it_is_called() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write(dir.path(), "k.wz", "k: 10\nk2: k*k\n");
+    let mut session = Session::new();
+    session.set("path", Value::string(&path));
+    assert_eq!(session.eval("load path").unwrap(), Value::Integer(100));
+    assert_eq!(session.get("k"), Some(Value::Integer(10)));
+    // Loaded from inside a function, the script still binds globals
+    session.eval("k: 0").unwrap();
+    session.eval("reload: {[p] load p}").unwrap();
+    assert_eq!(session.eval("reload[path]").unwrap(), Value::Integer(100));
+    assert_eq!(session.get("k"), Some(Value::Integer(10)));
+    session.set_namespace(".lib").unwrap();
+    session.eval("load path").unwrap();
+    assert_eq!(session.eval(".lib.k").unwrap(), Value::Integer(10));
+}
+
+#[test]
+fn test_later_statements_see_globals_set_in_calls() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write(dir.path(), "t.wz", "x: 1\nf: {x:: 5}\nf[]\nx\n");
+    let mut session = Session::new();
+    assert_eq!(session.load(Path::new(&path)).unwrap(), Value::Integer(5));
+    assert_eq!(session.get("x"), Some(Value::Integer(5)));
+}
+
+#[test]
+fn test_errors_point_at_the_script() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write(dir.path(), "bad.wz", "a: 1\n\nb: a + nothing\nc: 3\n");
+    let mut session = Session::new();
+    let report = session.load(Path::new(&path)).unwrap_err();
+    assert_eq!(report.code().unwrap().to_string(), "UNDEFINED_VARIABLE");
+    let error = report.downcast_ref::<EvalError>().unwrap();
+    let source = error.src.as_ref().unwrap();
+    assert_eq!(source.name(), path);
+    let span = error.span.offset()..error.span.offset() + error.span.len();
+    assert_eq!(&source.inner()[span], "nothing");
+    // Statements before the failing one have run, and those after have not
+    assert_eq!(session.get("a"), Some(Value::Integer(1)));
+    assert!(session.get("c").is_none());
+
+    let path = write(dir.path(), "syntax.wz", "x: 1\ny: (1+\n");
+    let report = session.load(Path::new(&path)).unwrap_err();
+    assert_eq!(report.code().unwrap().to_string(), "SYNTAX_ERROR");
+    let error = report.downcast_ref::<EvalError>().unwrap();
+    assert!(error.span.offset() >= "x: 1\n".len());
+
+    let missing = dir.path().join("missing.wz");
+    assert!(session.load(&missing).is_err());
+}
+
+#[test]
+fn test_eval_script_points_errors_at_its_lines() {
+    let script = Script {
+        name: "<stdin>".to_string(),
+        text: "a: 10\nb: a*zz\n".to_string(),
+    };
+    let mut session = Session::new();
+    let report = session.eval_script(&script).unwrap_err();
+    let error = report.downcast_ref::<EvalError>().unwrap();
+    let source = error.src.as_ref().unwrap();
+    assert_eq!(source.name(), "<stdin>");
+    assert_eq!(error.span.offset(), "a: 10\nb: a*".len());
+    assert_eq!(session.get("a"), Some(Value::Integer(10)));
+}
+
+// The command line: scripts, -e and standard input
+fn wabznasm() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_wabznasm"))
+}
+
+#[test]
+fn test_run_prints_the_last_value_with_the_arguments() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("args.wz");
+    fs::write(&path, "n: count .z.x\n(n;last .z.x)\n").unwrap();
+    let output = wabznasm()
+        .args(["run", path.to_str().unwrap(), "--verbose", "a b"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "(2;\"a b\")\n");
+}
+
+#[test]
+fn test_run_fails_with_the_script_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bad.wz");
+    fs::write(&path, "a: 1\nb: a + nothing\n").unwrap();
+    let output = wabznasm()
+        .args(["run", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("UNDEFINED_VARIABLE"));
+    assert!(stderr.contains("bad.wz:2:8"));
+}
+
+#[test]
+fn test_eval_flag_prints_the_value() {
+    let output = wabznasm().args(["-e", "x: 3; x*x"]).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "9\n");
+    let output = wabznasm().args(["-e", "1+"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    // Source starting with a minus is source, not another option
+    let output = wabznasm().args(["-e", "-2#1 2 3"]).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2 3\n");
+}
+
+#[test]
+fn test_script_from_standard_input() {
+    let mut child = wabznasm()
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"a: 10\nb: a*2\n\nsum (a;b)\n").unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "30\n");
+}
@@ -0,0 +1,694 @@
+mod common;
+
+use common::{run, show};
+use std::collections::HashMap;
+use storage::schema::SimpleDataType;
+use storage::{ColumnAttribute, QStoreConfig, Table};
+use tempfile::TempDir;
+use wabznasm::ckpt::checkpoint_path;
+use wabznasm::diff::Diff;
+use wabznasm::environment::Environment;
+use wabznasm::errors::EvalError;
+use wabznasm::evaluator::Evaluator;
+use wabznasm::jupyter::display::{DATA_RESOURCE_MIME, DisplayFormatter};
+use wabznasm::parser::parse_expression;
+use wabznasm::{Session, Value};
+
+const TRADES: &str = "t: flip `sym`px!(`AAPL`MSFT`IBM;100 250 140)";
+
+fn session() -> Session {
+    let mut session = Session::new();
+    session.eval(TRADES).unwrap();
+    session
+}
+
+fn eval(session: &mut Session, source: &str) -> Value {
+    session.eval(source).unwrap()
+}
+
+#[test]
+fn test_flip_builds_a_table() {
+    let mut s = session();
+    assert_eq!(eval(&mut s, "type t"), Value::Symbol("table".into()));
+    assert_eq!(
+        show(&mut s, "t"),
+        "flip `sym`px!(`AAPL`MSFT`IBM;100 250 140)"
+    );
+    assert_eq!(
+        show(&mut s, "flip t"),
+        "`sym`px!(`AAPL`MSFT`IBM;100 250 140)"
+    );
+    // The formatted table reads back as the same table
+    let t = eval(&mut s, "t");
+    let text = s.format(&t);
+    assert_eq!(eval(&mut s, &text), t);
+}
+
+#[test]
+fn test_columns_must_have_equal_lengths() {
+    let err = Session::new()
+        .eval("flip `a`b!(1 2 3;4 5)")
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("column `b has 2 rows, but `a has 3"),
+        "{}",
+        err
+    );
+    assert!(Session::new().eval("flip 1 2 3").is_err());
+    assert!(Session::new().eval("flip 1 2!(3 4;5 6)").is_err());
+}
+
+#[test]
+fn test_column_and_row_access() {
+    let mut s = session();
+    assert_eq!(eval(&mut s, "t`px"), Value::from(vec![100i64, 250, 140]));
+    assert_eq!(show(&mut s, "t[1]"), "`sym`px!(`MSFT;250)");
+    assert_eq!(eval(&mut s, "t[2;`sym]"), Value::Symbol("IBM".into()));
+    assert_eq!(show(&mut s, "t[0 2]"), "flip `sym`px!(`AAPL`IBM;100 140)");
+    assert!(s.eval("t`size").is_err());
+    assert!(s.eval("t[3]").is_err());
+}
+
+#[test]
+fn test_tabular_display() {
+    let mut s = session();
+    let t = eval(&mut s, "t");
+    assert_eq!(
+        s.display(&t),
+        "sym  px\n-------\nAAPL 100\nMSFT 250\nIBM  140"
+    );
+
+    let data: HashMap<_, _> = DisplayFormatter::format_value(&t, &Default::default());
+    let html = data["text/html"].as_str().unwrap();
+    assert!(html.contains("<th>sym</th><th>px</th>"), "{}", html);
+    assert!(html.contains("<td>AAPL</td><td>100</td>"), "{}", html);
+    assert_eq!(data[DATA_RESOURCE_MIME]["data"][1]["px"], 250);
+}
+
+#[test]
+fn test_diff_compares_rows() {
+    let mut s = session();
+    let changes = eval(&mut s, "diff[t;flip `sym`px!(`AAPL`MSFT`IBM;100 251 140)]");
+    assert_eq!(
+        s.format(&changes),
+        "(`kind`path`old`new!(`changed;(1;`px);250;251))"
+    );
+}
+
+const REFDATA: &str = "r: xkey[`sym; flip `sym`px`qty!(`AAPL`MSFT`IBM;100 250 140;10 20 30)]";
+
+fn keyed() -> Session {
+    let mut session = Session::new();
+    session.eval(REFDATA).unwrap();
+    session
+}
+
+#[test]
+fn test_xkey() {
+    let mut s = keyed();
+    assert_eq!(
+        show(&mut s, "r"),
+        "(flip ,`sym!(`AAPL`MSFT`IBM))!(flip `px`qty!(100 250 140;10 20 30))"
+    );
+    // Key columns move to the front
+    assert_eq!(
+        show(&mut s, "xkey[`qty`sym;r]"),
+        "(flip `qty`sym!(10 20 30;`AAPL`MSFT`IBM))!(flip ,`px!(100 250 140))"
+    );
+    assert_eq!(
+        show(&mut s, "xkey[();r]"),
+        "flip `sym`px`qty!(`AAPL`MSFT`IBM;100 250 140;10 20 30)"
+    );
+    assert!(s.eval("xkey[`size;r]").is_err());
+    assert!(s.eval("xkey[`sym;1 2]").is_err());
+}
+
+#[test]
+fn test_lookup_by_key() {
+    let mut s = keyed();
+    assert_eq!(show(&mut s, "r`MSFT"), "`px`qty!250 20");
+    // Missing keys give nulls
+    assert_eq!(show(&mut s, "r`GOOG"), "`px`qty!0N 0N");
+    assert_eq!(
+        show(&mut s, "r[`AAPL`IBM]"),
+        "(`px`qty!100 10;`px`qty!140 30)"
+    );
+
+    s.eval("m: xkey[`sym`qty; r]").unwrap();
+    assert_eq!(show(&mut s, "m[(`IBM;30)]"), ",`px!,140");
+    assert!(s.eval("m`IBM").is_err());
+}
+
+#[test]
+fn test_upsert() {
+    let mut s = keyed();
+    // A present key replaces that row's values; a new one is appended
+    s.eval("r: upsert[r; `sym`px!(`MSFT;260)]").unwrap();
+    s.eval("r: upsert[r; flip `sym`px`qty!(`GOOG`AAPL;90 105;5 15)]")
+        .unwrap();
+    assert_eq!(
+        show(&mut s, "r"),
+        "(flip ,`sym!(`AAPL`MSFT`IBM`GOOG))!(flip `px`qty!(105 260 140 90;15 20 30 5))"
+    );
+    assert!(s.eval("upsert[r; `px`qty!(1;2)]").is_err());
+    assert!(s.eval("upsert[r; `sym`size!(`X;1)]").is_err());
+
+    // Unkeyed tables append every row
+    assert_eq!(
+        show(&mut s, "count upsert[xkey[();r]; `sym`px!(`AAPL;1)]"),
+        "5"
+    );
+}
+
+const NAMES: &str = "n: xkey[`sym; flip `sym`name`px!(`AAPL`MSFT;`apple`microsoft;1 2)]";
+
+#[test]
+fn test_left_and_inner_join() {
+    let mut s = session();
+    s.eval(NAMES).unwrap();
+    // Matched rows take the keyed table's values; unmatched ones keep
+    // theirs, or are null in new columns
+    assert_eq!(
+        show(&mut s, "lj[t;n]"),
+        "flip `sym`px`name!(`AAPL`MSFT`IBM;1 2 140;`apple`microsoft`)"
+    );
+    assert_eq!(
+        show(&mut s, "ij[t;n]"),
+        "flip `sym`px`name!(`AAPL`MSFT;1 2;`apple`microsoft)"
+    );
+    assert!(s.eval("lj[t;t]").is_err());
+    assert!(s.eval("lj[flip ,`px!,1;n]").is_err());
+}
+
+#[test]
+fn test_union_join() {
+    let mut s = session();
+    assert_eq!(
+        show(&mut s, "uj[t; flip `sym`qty!(`GOOG`IBM;5 6)]"),
+        "flip `sym`px`qty!(`AAPL`MSFT`IBM`GOOG`IBM;100 250 140 0N 0N;0N 0N 0N 5 6)"
+    );
+    // Keyed tables upsert on their key
+    s.eval(NAMES).unwrap();
+    assert_eq!(
+        show(&mut s, "uj[n; xkey[`sym; flip `sym`px!(`MSFT`IBM;3 4)]]"),
+        "(flip ,`sym!(`AAPL`MSFT`IBM))!(flip `name`px!(`apple`microsoft`;1 3 4))"
+    );
+    assert!(s.eval("uj[n;t]").is_err());
+}
+
+#[test]
+fn test_as_of_join() {
+    let mut s = session();
+    s.eval("trades: flip `sym`time`qty!(`A`B`A`C;10 10 25 5;1 2 3 4)")
+        .unwrap();
+    // Quotes out of time order, to be sorted within each sym
+    s.eval("quotes: flip `sym`time`bid!(`A`B`A`B`A;1 5 20 12 30;1.5 2.5 1.6 2.6 1.7)")
+        .unwrap();
+    assert_eq!(
+        show(&mut s, "aj[`sym`time;trades;quotes]"),
+        "flip `sym`time`qty`bid!(`A`B`A`C;10 10 25 5;1 2 3 4;1.5 2.5 1.6 0n)"
+    );
+    // A trade before any quote for its sym gets nulls
+    assert_eq!(
+        show(&mut s, "aj[`sym`time;flip `sym`time!(`A`B;0 11);quotes]"),
+        "flip `sym`time`bid!(`A`B;0 11;0n 2.5)"
+    );
+    // On time alone, all quotes are one group
+    s.eval("by_time: aj[`time;trades;quotes]").unwrap();
+    assert_eq!(show(&mut s, "by_time `bid"), "2.5 2.5 1.6 2.5");
+    assert!(s.eval("aj[`sym`size;trades;quotes]").is_err());
+}
+
+// Tables in a database, created and listed with the .db builtins
+/// Evaluate each line against a database rooted at `root`
+fn eval_in(root: &TempDir, lines: &[&str]) -> Result<Value, EvalError> {
+    let mut evaluator = Evaluator::new();
+    evaluator.set_database(root.path());
+    run(&mut evaluator, lines)
+}
+
+fn symbols(names: &[&str]) -> Value {
+    Value::List(names.iter().map(|n| Value::Symbol((*n).into())).collect())
+}
+
+#[test]
+fn test_symbol_literals() {
+    let root = TempDir::new().unwrap();
+    assert_eq!(
+        eval_in(&root, &["`trades"]).unwrap(),
+        Value::Symbol("trades".into())
+    );
+    assert_eq!(
+        eval_in(&root, &["`a`b`c"]).unwrap(),
+        symbols(&["a", "b", "c"])
+    );
+}
+
+#[test]
+fn test_create_table_from_script() {
+    let root = TempDir::new().unwrap();
+    let result = eval_in(
+        &root,
+        &[".db.create[`trades; `time`sym`px!`timestamp`symbol`float]"],
+    )
+    .unwrap();
+    assert_eq!(result, Value::Symbol("trades".into()));
+
+    let table = Table::load(QStoreConfig::new(root.path(), "trades".to_string())).unwrap();
+    let schema = table.schema();
+    assert_eq!(schema.column_names(), vec!["time", "sym", "px"]);
+    assert_eq!(
+        schema.get_column("time").unwrap().data_type,
+        SimpleDataType::Timestamp
+    );
+    assert_eq!(
+        schema.get_column("px").unwrap().data_type,
+        SimpleDataType::Float64
+    );
+}
+
+#[test]
+fn test_create_table_with_attributes() {
+    let root = TempDir::new().unwrap();
+    eval_in(
+        &root,
+        &[
+            "schema: `time`sym`px!(`timestamp`sorted;`symbol`parted;`float)",
+            ".db.create[`quotes; schema]",
+        ],
+    )
+    .unwrap();
+
+    let table = Table::load(QStoreConfig::new(root.path(), "quotes".to_string())).unwrap();
+    let attribute = |name| table.schema().get_column(name).unwrap().attribute;
+    assert_eq!(attribute("time"), Some(ColumnAttribute::Sorted));
+    assert_eq!(attribute("sym"), Some(ColumnAttribute::Parted));
+    assert_eq!(attribute("px"), None);
+}
+
+#[test]
+fn test_list_tables() {
+    let root = TempDir::new().unwrap();
+    let result = eval_in(
+        &root,
+        &[
+            ".db.create[`trades; `px!`float]",
+            ".db.create[`quotes; `bid!`float]",
+            ".db.tables[]",
+        ],
+    )
+    .unwrap();
+    assert_eq!(result, symbols(&["quotes", "trades"]));
+}
+
+#[test]
+fn test_create_errors() {
+    let root = TempDir::new().unwrap();
+    assert!(eval_in(&root, &[".db.create[`t; `px!`decimal]"]).is_err());
+    assert!(eval_in(&root, &[".db.create[`t; `px!(`float`fast)]"]).is_err());
+    assert!(eval_in(&root, &[".db.create[`t; 1 2]"]).is_err());
+    assert!(
+        eval_in(
+            &root,
+            &[".db.create[`t; `px!`float]", ".db.create[`t; `px!`float]"]
+        )
+        .is_err()
+    );
+
+    let mut evaluator = Evaluator::new();
+    let src = ".db.tables[]";
+    let tree = parse_expression(src).unwrap();
+    assert!(
+        evaluator
+            .eval_with_env(tree.root_node(), src, &mut Environment::new())
+            .is_err()
+    );
+}
+
+#[test]
+fn test_column_access_stats() {
+    let root = TempDir::new().unwrap();
+    eval_in(&root, &[".db.create[`trades; `sym`px!`symbol`float]"]).unwrap();
+    let mut table = Table::load(QStoreConfig::new(root.path(), "trades".into())).unwrap();
+    let mut row = storage::table::Row::new();
+    row.insert("sym".into(), storage::ScalarValue::Utf8("AAPL".into()));
+    row.insert("px".into(), storage::ScalarValue::Float64(100.0));
+    table.insert(row).unwrap();
+    table.get_column("px").unwrap();
+    drop(table);
+
+    let stats = eval_in(&root, &[".db.stats[`trades]"]).unwrap();
+    let Value::Table(stats) = stats else {
+        panic!("expected a table, got {:?}", stats);
+    };
+    assert_eq!(
+        stats.column("column").unwrap(),
+        symbols(&["px", "sym"]).as_list().unwrap()
+    );
+    assert_eq!(
+        stats.column("reads").unwrap(),
+        [Value::Integer(1), Value::Integer(0)]
+    );
+    assert!(eval_in(&root, &[".db.stats[`quotes]"]).is_err());
+}
+
+#[test]
+fn test_read_only_database() {
+    let root = TempDir::new().unwrap();
+    eval_in(&root, &[".db.create[`trades; `time`px!`timestamp`float]"]).unwrap();
+
+    let mut env = Environment::new();
+    let mut evaluator = Evaluator::new();
+    evaluator.set_database(root.path());
+    evaluator.set_read_only(true);
+    let mut run = |src: &str| {
+        let tree = parse_expression(src).unwrap();
+        evaluator.eval_with_env(tree.root_node(), src, &mut env)
+    };
+
+    assert_eq!(run(".db.tables[]").unwrap(), symbols(&["trades"]));
+    assert!(run(".db.stats[`trades]").is_ok());
+    let err = run(".db.create[`quotes; `bid`ask!`float`float]").unwrap_err();
+    assert!(err.to_string().contains("read-only"), "{}", err);
+    assert!(!root.path().join("quotes").exists());
+}
+
+// Diffs of tables and rows
+#[test]
+fn test_diff_builtin_reports_changes() {
+    let mut session = Session::new();
+    let changes = session.eval("diff[1 2 3; 1 5]").unwrap();
+    assert_eq!(
+        session.format(&changes),
+        "(`kind`path`old`new!(`changed;,1;2;5);`kind`path`value!(`removed;,2;3))"
+    );
+    assert_eq!(session.eval("diff[`a; `a]").unwrap(), Value::List(vec![]));
+}
+
+#[test]
+fn test_diff_of_dict_rows() {
+    let mut session = Session::new();
+    session
+        .eval("old: (`sym`px!(`AAPL;100); `sym`px!(`MSFT;250))")
+        .unwrap();
+    session
+        .eval("new: (`sym`px!(`AAPL;101); `sym`px`qty!(`MSFT;250;10))")
+        .unwrap();
+    let old = session.get("old").unwrap();
+    let new = session.get("new").unwrap();
+    assert_eq!(
+        Diff::between(&old, &new).to_string(),
+        "~ [0;`px] 100 -> 101\n+ [1;`qty] 10\n"
+    );
+}
+
+// Folds checkpointed to the database
+/// A session on the database rooted at `root`
+fn session_in(root: &std::path::Path) -> Session {
+    let mut session = Session::new();
+    session.set_database(root);
+    session
+}
+
+#[test]
+fn test_fold_completes_and_removes_checkpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut session = session_in(dir.path());
+    assert_eq!(
+        session
+            .eval(".ckpt.fold[`sum; 2; {[acc;x] acc+x}; 0; 1 2 3 4 5]")
+            .unwrap(),
+        Value::Integer(15)
+    );
+    assert!(!checkpoint_path(dir.path(), "sum").exists());
+}
+
+#[test]
+fn test_interrupted_fold_resumes_from_checkpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut session = session_in(dir.path());
+    session.eval("xs: 1 2 3 4 5").unwrap();
+
+    // Fails on the fourth item, after 1+2+3 has been accumulated
+    session.eval("f: {[acc;x] $[x=4; '`boom; acc+x]}").unwrap();
+    let err = session.eval(".ckpt.fold[`sum; 2; f; 0; xs]").unwrap_err();
+    assert!(err.to_string().contains("boom"));
+    assert!(checkpoint_path(dir.path(), "sum").exists());
+
+    // A fresh session resumes at the failed item: g would fail on any earlier one
+    let mut session = session_in(dir.path());
+    session.eval("xs: 1 2 3 4 5").unwrap();
+    session
+        .eval("g: {[acc;x] $[x<4; '`redone; acc+x]}")
+        .unwrap();
+    assert_eq!(
+        session.eval(".ckpt.fold[`sum; 2; g; 0; xs]").unwrap(),
+        Value::Integer(15)
+    );
+    assert!(!checkpoint_path(dir.path(), "sum").exists());
+}
+
+#[test]
+fn test_checkpoint_must_match_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut session = session_in(dir.path());
+    session.eval("f: {[acc;x] $[x=3; '`boom; acc+x]}").unwrap();
+    assert!(session.eval(".ckpt.fold[`sum; 1; f; 0; 1 2 3]").is_err());
+    let err = session
+        .eval(".ckpt.fold[`sum; 1; {[acc;x] acc+x}; 0; 1 2 3 4]")
+        .unwrap_err();
+    assert!(err.to_string().contains("taken over 3 items"));
+}
+
+#[test]
+fn test_checkpoint_name_must_be_plain() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("db");
+    let mut session = session_in(&root);
+    for name in ["../x", "a/b", ""] {
+        session.set("name", Value::Symbol(name.into()));
+        let err = session
+            .eval(".ckpt.fold[name; 1; {[acc;x] acc+x}; 0; 1 2]")
+            .unwrap_err();
+        assert!(err.to_string().contains("plain identifier"), "{}", err);
+    }
+    assert!(!dir.path().join("x.json").exists());
+    assert!(!root.exists());
+}
+
+#[test]
+fn test_fold_does_not_checkpoint_read_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut session = session_in(dir.path());
+    session.set_read_only(true);
+    session.eval("f: {[acc;x] $[x=3; '`boom; acc+x]}").unwrap();
+    let err = session
+        .eval(".ckpt.fold[`sum; 1; f; 0; 1 2 3]")
+        .unwrap_err();
+    assert!(err.to_string().contains("read-only"), "{}", err);
+    assert!(!checkpoint_path(dir.path(), "sum").exists());
+    assert!(!dir.path().join(".checkpoints").exists());
+}
+
+#[test]
+fn test_fold_needs_a_database() {
+    let mut session = Session::new();
+    let err = session
+        .eval(".ckpt.fold[`sum; 1; {[acc;x] acc+x}; 0; 1 2]")
+        .unwrap_err();
+    assert!(err.to_string().contains("No database"));
+}
+
+// qSQL queries
+/// Trades with their sizes
+const FILLS: &str = "t: flip `sym`px`qty!(`AAPL`MSFT`AAPL`IBM;100 250 110 140;10 20 30 40)";
+
+/// A session with `t` bound to [`FILLS`]
+fn fills() -> Session {
+    let mut session = Session::new();
+    session.eval(FILLS).unwrap();
+    session
+}
+
+#[test]
+fn test_select_columns() {
+    let mut s = fills();
+    assert_eq!(
+        show(&mut s, "select px from t"),
+        "flip ,`px!(100 250 110 140)"
+    );
+    assert_eq!(
+        show(&mut s, "select sym, qty from t"),
+        "flip `sym`qty!(`AAPL`MSFT`AAPL`IBM;10 20 30 40)"
+    );
+    assert_eq!(s.eval("select from t").unwrap(), s.eval("t").unwrap());
+}
+
+#[test]
+fn test_select_expressions_are_named() {
+    let mut s = fills();
+    // Unnamed expressions take the first column they mention
+    assert_eq!(
+        show(&mut s, "select 2*px, notional:px*qty from t where sym=`IBM"),
+        "flip `px`notional!(,280;,5600)"
+    );
+    assert_eq!(show(&mut s, "select n:1+2 from t"), "flip ,`n!(,3)");
+    // Atoms are repeated down the rows of list columns
+    assert_eq!(
+        show(&mut s, "select px, one:1 from t where px>200"),
+        "flip `px`one!(,250;,1)"
+    );
+}
+
+#[test]
+fn test_where_filters_in_turn() {
+    let mut s = fills();
+    assert_eq!(
+        show(&mut s, "select px from t where sym=`AAPL"),
+        "flip ,`px!(100 110)"
+    );
+    assert_eq!(
+        show(&mut s, "select px from t where sym=`AAPL, qty>10"),
+        "flip ,`px!(,110)"
+    );
+    let Value::Table(none) = s.eval("select from t where px>1000").unwrap() else {
+        panic!("expected a table");
+    };
+    assert!(none.is_empty());
+}
+
+#[test]
+fn test_select_sees_variables_and_functions() {
+    let mut s = fills();
+    s.eval("limit: 120").unwrap();
+    s.eval("less: {[x] x-limit}").unwrap();
+    assert_eq!(
+        show(&mut s, "select h:less px from t where px>limit"),
+        "flip ,`h!(130 20)"
+    );
+    // The result is a value like any other
+    s.eval("big: select sym from t where px>limit").unwrap();
+    assert_eq!(show(&mut s, "big`sym"), "`MSFT`IBM");
+}
+
+#[test]
+fn test_select_errors() {
+    let mut s = fills();
+    assert!(s.eval("select px from 1 2 3").is_err());
+    assert!(s.eval("select size from t").is_err());
+    assert!(s.eval("select from t where px").is_err());
+    assert!(s.eval("select from t where 1b 0b").is_err());
+}
+
+#[test]
+fn test_arithmetic_applies_item_by_item() {
+    let mut s = Session::new();
+    assert_eq!(show(&mut s, "1 2 3+10"), "11 12 13");
+    assert_eq!(show(&mut s, "2*1 2 3"), "2 4 6");
+    assert_eq!(show(&mut s, "1 2-0.5 1.5"), "0.5 0.5");
+    assert!(s.eval("1 2+1 2 3").is_err());
+}
+
+#[test]
+fn test_update_returns_a_modified_table() {
+    let mut s = fills();
+    assert_eq!(
+        show(&mut s, "update px:px*2 from t where sym=`AAPL"),
+        "flip `sym`px`qty!(`AAPL`MSFT`AAPL`IBM;200 250 220 140;10 20 30 40)"
+    );
+    // New columns are null outside the updated rows, and atoms fill every row
+    assert_eq!(
+        show(
+            &mut s,
+            "select big from (update big:1b from t where px>200)"
+        ),
+        "flip ,`big!(0100b)"
+    );
+    assert!(show(&mut s, "update qty:0 from t").ends_with(";0 0 0 0)"));
+    // The variable itself is unchanged
+    assert_eq!(show(&mut s, "t`px"), "100 250 110 140");
+}
+
+#[test]
+fn test_update_in_place() {
+    let mut s = fills();
+    assert_eq!(
+        s.eval("update px:px+1 from `t where sym=`IBM").unwrap(),
+        Value::Symbol("t".into())
+    );
+    assert_eq!(show(&mut s, "t`px"), "100 250 110 141");
+    assert!(s.eval("update px:1 from `missing").is_err());
+    assert!(s.eval("update px:1 2 from t").is_err());
+}
+
+#[test]
+fn test_delete_rows_and_columns() {
+    let mut s = fills();
+    assert_eq!(
+        show(&mut s, "delete from t where px<120"),
+        "flip `sym`px`qty!(`MSFT`IBM;250 140;20 40)"
+    );
+    assert_eq!(
+        show(&mut s, "delete px, qty from t"),
+        "flip ,`sym!(`AAPL`MSFT`AAPL`IBM)"
+    );
+    assert!(s.eval("delete size from t").is_err());
+    assert!(s.eval("delete px from t where px>1").is_err());
+
+    s.eval("delete from `t where sym=`AAPL").unwrap();
+    assert_eq!(show(&mut s, "t`sym"), "`MSFT`IBM");
+    s.eval("delete from `t").unwrap();
+    assert_eq!(show(&mut s, "t`sym"), "()");
+}
+
+#[test]
+fn test_select_by_groups_into_a_keyed_table() {
+    let mut s = fills();
+    assert_eq!(
+        show(&mut s, "select sum qty by sym from t"),
+        "(flip ,`sym!(`AAPL`IBM`MSFT))!(flip ,`qty!(40 40 20))"
+    );
+    assert_eq!(
+        show(
+            &mut s,
+            "select n:count px, hi:max px by sym from t where qty>10"
+        ),
+        "(flip ,`sym!(`AAPL`IBM`MSFT))!(flip `n`hi!(1 1 1;110 140 250))"
+    );
+    // Without columns, each group keeps its last row
+    assert_eq!(
+        show(&mut s, "select by sym from t"),
+        "(flip ,`sym!(`AAPL`IBM`MSFT))!(flip `px`qty!(110 140 250;30 40 20))"
+    );
+    // Groups are keyed by every by column, in ascending order
+    assert_eq!(
+        show(&mut s, "select sum qty by big:px>120, sym from t"),
+        "(flip `big`sym!(011b;`AAPL`IBM`MSFT))!(flip ,`qty!(40 40 20))"
+    );
+    // A keyed table is written, and built, as a dictionary of tables
+    let written = "(flip `a`b!(1 2;3 4))!(flip `c`d!(5 6;7 8))";
+    assert_eq!(show(&mut s, written), written);
+}
+
+#[test]
+fn test_keyed_table_display() {
+    let mut s = fills();
+    let keyed = s.eval("select sum qty by sym from t").unwrap();
+    assert_eq!(
+        s.display(&keyed),
+        "sym | qty\n----| ---\nAAPL| 40\nIBM | 40\nMSFT| 20"
+    );
+}
+
+#[test]
+fn test_aggregates_without_by_give_one_row() {
+    let mut s = fills();
+    assert_eq!(
+        show(&mut s, "select sum qty, avg px, lo:min px from t"),
+        "flip `qty`px`lo!(,100;(150f);,100)"
+    );
+    assert_eq!(show(&mut s, "count t"), "4");
+    assert_eq!(show(&mut s, "sum 1 2 0N 4"), "7");
+    assert_eq!(show(&mut s, "max ()"), "-0W");
+}